@@ -38,7 +38,13 @@ impl Addr {
     }
 
     pub fn from_str_and_port(host: &str, port: u16) -> Result<Self, Error> {
-        IpAddr::from_str(host)
+        // IPv6 hosts are bracketed when they appear in an authority.
+        let ip = if host.starts_with('[') && host.ends_with(']') {
+            &host[1..host.len() - 1]
+        } else {
+            host
+        };
+        IpAddr::from_str(ip)
             .map(|ip| Addr::Socket((ip, port).into()))
             .or_else(|_| NameAddr::from_str_and_port(host, port).map(Addr::Name))
     }
@@ -222,4 +228,11 @@ mod tests {
             assert_eq!(a.is_loopback(), *expected_result, "{:?}", host)
         }
     }
+
+    #[test]
+    fn test_from_str_and_port_brackets() {
+        let a = Addr::from_str_and_port("[::1]", 8080).unwrap();
+        assert_eq!(a, Addr::Socket("[::1]:8080".parse().unwrap()));
+        assert!(Addr::from_str_and_port("[localhost]", 8080).is_err());
+    }
 }
@@ -0,0 +1,124 @@
+use super::{rsp, ClientAddr};
+pub use crate::freeze::Registry as Freeze;
+use futures::future::{self, Future};
+use http::{Method, StatusCode};
+use hyper::{service::Service, Body, Request, Response};
+use linkerd2_addr::NameAddr;
+use std::io;
+use tracing::{error, warn};
+
+impl Service for Freeze {
+    type ReqBody = Body;
+    type ResBody = Body;
+    type Error = io::Error;
+    type Future = Box<dyn Future<Item = Response<Body>, Error = Self::Error> + Send + 'static>;
+
+    fn call(&mut self, req: Request<Body>) -> Self::Future {
+        if *req.method() == Method::GET {
+            let body = self
+                .frozen()
+                .into_iter()
+                .map(|dst| format!("{}\n", dst))
+                .collect::<String>();
+            return Box::new(future::ok(rsp(StatusCode::OK, body)));
+        }
+
+        // Freezing may only be changed from loopback IPs.
+        if let Some(addr) = req.extensions().get::<ClientAddr>() {
+            let addr = addr.addr();
+            if !addr.ip().is_loopback() {
+                warn!(message = "denying request from non-loopback IP", %addr);
+                return Box::new(future::ok(rsp(
+                    StatusCode::FORBIDDEN,
+                    "changes to /freeze only allowed from loopback interface",
+                )));
+            }
+        } else {
+            error!(message = "ClientAddr extension should always be set");
+            return Box::new(future::ok(rsp(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Body::empty(),
+            )));
+        }
+
+        let dst = match dst_param(req.uri().query()) {
+            Ok(dst) => dst,
+            Err(error) => return Box::new(future::ok(rsp(StatusCode::BAD_REQUEST, error))),
+        };
+
+        match req.method() {
+            &Method::POST => {
+                self.freeze(dst);
+                Box::new(future::ok(rsp(StatusCode::NO_CONTENT, Body::empty())))
+            }
+            &Method::DELETE => {
+                self.unfreeze(&dst);
+                Box::new(future::ok(rsp(StatusCode::NO_CONTENT, Body::empty())))
+            }
+            _ => Box::new(future::ok(
+                Response::builder()
+                    .status(StatusCode::METHOD_NOT_ALLOWED)
+                    .header("allow", "GET")
+                    .header("allow", "POST")
+                    .header("allow", "DELETE")
+                    .body(Body::empty())
+                    .expect("builder with known status code must not fail"),
+            )),
+        }
+    }
+}
+
+/// Parses the `dst` query parameter as a named destination.
+fn dst_param(query: Option<&str>) -> Result<NameAddr, String> {
+    let value = query
+        .into_iter()
+        .flat_map(|q| q.split('&'))
+        .filter_map(|param| {
+            let mut kv = param.splitn(2, '=');
+            match (kv.next(), kv.next()) {
+                (Some("dst"), Some(value)) => Some(value),
+                _ => None,
+            }
+        })
+        .next()
+        .ok_or_else(|| "missing dst parameter".to_string())?;
+
+    let value = percent_decode(value).ok_or_else(|| "invalid dst encoding".to_string())?;
+    NameAddr::from_str(&value).map_err(|e| format!("invalid dst {:?}: {}", value, e))
+}
+
+fn percent_decode(s: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(s.len());
+    let mut iter = s.bytes();
+    while let Some(b) = iter.next() {
+        if b != b'%' {
+            bytes.push(b);
+            continue;
+        }
+        let hex = [iter.next()?, iter.next()?];
+        let hex = std::str::from_utf8(&hex).ok()?;
+        bytes.push(u8::from_str_radix(hex, 16).ok()?);
+    }
+    String::from_utf8(bytes).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_dst_param() {
+        let dst = NameAddr::from_str("web.ns.svc.cluster.local:8080").unwrap();
+        assert_eq!(
+            dst_param(Some("dst=web.ns.svc.cluster.local:8080")),
+            Ok(dst.clone())
+        );
+        assert_eq!(
+            dst_param(Some("a=b&dst=web.ns.svc.cluster.local%3A8080")),
+            Ok(dst)
+        );
+        assert!(dst_param(None).is_err());
+        assert!(dst_param(Some("dst=web.ns.svc.cluster.local")).is_err());
+        assert!(dst_param(Some("dst=web%3")).is_err());
+    }
+}
@@ -2,6 +2,8 @@
 //!
 //! * `/metrics` -- reports prometheus-formatted metrics.
 //! * `/ready` -- returns 200 when the proxy is ready to participate in meshed traffic.
+//! * `/freeze` -- lists, freezes (`POST /freeze?dst=...`), and unfreezes
+//!   (`DELETE /freeze?dst=...`) destinations whose discovery updates are held.
 
 use crate::{svc, transport::tls::accept::Connection};
 use futures::{future, Future, Poll};
@@ -11,9 +13,11 @@ use hyper::{Body, Request, Response};
 use linkerd2_metrics::{self as metrics, FmtMetrics};
 use std::io;
 
+mod freeze;
 mod readiness;
 mod trace_level;
 
+pub use self::freeze::Freeze;
 pub use self::readiness::{Latch, Readiness};
 use self::trace_level::TraceLevel;
 
@@ -22,6 +26,7 @@ pub struct Admin<M: FmtMetrics> {
     metrics: metrics::Serve<M>,
     trace_level: TraceLevel,
     ready: Readiness,
+    freeze: Freeze,
 }
 
 #[derive(Debug, Clone)]
@@ -34,11 +39,12 @@ pub type ResponseFuture =
     Box<dyn Future<Item = Response<Body>, Error = io::Error> + Send + 'static>;
 
 impl<M: FmtMetrics> Admin<M> {
-    pub fn new(m: M, ready: Readiness, trace_level: TraceLevel) -> Self {
+    pub fn new(m: M, ready: Readiness, trace_level: TraceLevel, freeze: Freeze) -> Self {
         Self {
             metrics: metrics::Serve::new(m),
             trace_level,
             ready,
+            freeze,
         }
     }
 
@@ -72,6 +78,7 @@ impl<M: FmtMetrics> Service for Admin<M> {
             "/metrics" => Box::new(self.metrics.call(req)),
             "/proxy-log-level" => self.trace_level.call(req),
             "/ready" => Box::new(future::ok(self.ready_rsp())),
+            "/freeze" => self.freeze.call(req),
             _ => Box::new(future::ok(rsp(StatusCode::NOT_FOUND, Body::empty()))),
         }
     }
@@ -87,8 +94,8 @@ impl<M: FmtMetrics + Clone + Send + 'static> svc::Service<Connection> for Accept
     }
 
     fn call(&mut self, (meta, io): Connection) -> Self::Future {
-        // Since the `/proxy-log-level` controls access based on the
-        // client's IP address, we wrap the service with a new service
+        // Since the `/proxy-log-level` and `/freeze` endpoints control access
+        // based on the client's IP address, we wrap the service with a new service
         // that adds the remote IP as a request extension.
         let peer = meta.addrs.peer();
         let mut svc = self.0.clone();
@@ -129,7 +136,7 @@ mod tests {
         let l1 = l0.clone();
 
         let mut rt = Runtime::new().unwrap();
-        let mut srv = Admin::new((), r, TraceLevel::dangling());
+        let mut srv = Admin::new((), r, TraceLevel::dangling(), Freeze::default());
         macro_rules! call {
             () => {{
                 let r = Request::builder()
@@ -1,7 +1,7 @@
 use http;
 use linkerd2_error::Error;
 pub use linkerd2_proxy_http::metrics::classify::{self, layer, CanClassify};
-use linkerd2_proxy_http::{profiles, timeout, HasH2Reason};
+use linkerd2_proxy_http::{client::ClientError, profiles, timeout, HasH2Reason};
 use std::borrow::Cow;
 use tower_grpc::{self as grpc};
 use tracing::trace;
@@ -197,6 +197,13 @@ fn grpc_class(headers: &http::HeaderMap) -> Option<Class> {
 }
 
 fn h2_error(err: &Error) -> String {
+    // Connection failures are distinguished from the failures of individual
+    // streams, since they affect all of the connection's requests and may be
+    // retried.
+    if ClientError::is_connection_error(&**err) {
+        return String::from("connection");
+    }
+
     if let Some(reason) = err.h2_reason() {
         // This should output the error code in the same format as the spec,
         // for example: PROTOCOL_ERROR
@@ -314,4 +321,24 @@ mod tests {
             .eos(Some(&trailers));
         assert_eq!(class, Class::Grpc(SuccessOrFailure::Failure, 4));
     }
+
+    #[test]
+    fn connection_errors_are_distinguished() {
+        use linkerd2_error::Error;
+        use linkerd2_proxy_http::client::ClientError;
+
+        let conn: Error = Box::new(ClientError::Connection("connection closed".into()));
+        let class = super::Response::Default.error(&conn);
+        assert_eq!(
+            class,
+            Class::Stream(SuccessOrFailure::Failure, "connection".into())
+        );
+
+        let stream: Error = Box::new(ClientError::Stream("stream reset".into()));
+        let class = super::Response::Default.error(&stream);
+        assert_eq!(
+            class,
+            Class::Stream(SuccessOrFailure::Failure, "unclassified".into())
+        );
+    }
 }
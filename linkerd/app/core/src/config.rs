@@ -1,6 +1,6 @@
 pub use super::control::ControlAddr;
 pub use crate::exp_backoff::ExponentialBackoff;
-pub use crate::proxy::http::h2;
+pub use crate::proxy::http::{h1, h2, metrics::ContinueTtfb};
 pub use crate::transport::{Bind, Listen, NoOrigDstAddr, OrigDstAddr, SysOrigDstAddr};
 use indexmap::IndexSet;
 use std::sync::Arc;
@@ -18,6 +18,7 @@ pub struct ConnectConfig {
     pub backoff: ExponentialBackoff,
     pub timeout: Duration,
     pub keepalive: Option<Duration>,
+    pub h1_settings: h1::Settings,
     pub h2_settings: h2::Settings,
 }
 
@@ -28,6 +29,9 @@ pub struct ProxyConfig<A: OrigDstAddr = SysOrigDstAddr> {
     pub router_capacity: usize,
     pub router_max_idle_age: Duration,
     pub disable_protocol_detection_for_ports: Arc<IndexSet<u16>>,
+    /// When the time to first byte of a response to an `Expect:
+    /// 100-continue` request is measured.
+    pub continue_ttfb: ContinueTtfb,
 }
 
 #[derive(Clone, Debug)]
@@ -43,6 +47,63 @@ pub struct BufferConfig {
     pub max_in_flight: usize,
 }
 
+/// Configures failure accrual, which ejects a service from its balancer
+/// while it is consistently failing.
+#[derive(Copy, Clone, Debug)]
+pub struct FailureAccrualConfig {
+    /// The number of consecutive failures after which a service is ejected.
+    pub max_consecutive_failures: usize,
+    /// The proportion of failed responses, between 0 and 1, above which a
+    /// service is ejected, if set.
+    pub max_failure_rate: Option<f64>,
+    /// The number of most recent responses over which the failure rate is
+    /// measured. If `sample_window` is set, this is instead the minimum
+    /// number of responses in the sample window before the failure rate is
+    /// measured.
+    pub window: usize,
+    /// If set, the failure rate is measured over all of the responses
+    /// received within this duration.
+    pub sample_window: Option<Duration>,
+    /// How long a service is ejected before a probe request is allowed.
+    pub backoff: Duration,
+}
+
+/// Configures a circuit breaker that stops dispatching requests to a service
+/// while its error rate is too high.
+///
+/// A circuit breaker is failure accrual that only considers the error rate.
+#[derive(Copy, Clone, Debug)]
+pub struct CircuitBreakerConfig {
+    /// The proportion of failed requests, between 0 and 1, above which the
+    /// circuit is opened.
+    pub error_rate_threshold: f64,
+    /// The window over which the error rate is measured.
+    pub sample_window: Duration,
+    /// How long the circuit remains open before a probe request is allowed.
+    pub open_duration: Duration,
+}
+
+// === impl CircuitBreakerConfig ===
+
+impl CircuitBreakerConfig {
+    /// The error rate is not measured until at least this many requests have
+    /// been observed in the sample window, so that a single failure does not
+    /// open the circuit.
+    const MIN_REQUESTS: usize = 5;
+}
+
+impl From<CircuitBreakerConfig> for FailureAccrualConfig {
+    fn from(config: CircuitBreakerConfig) -> Self {
+        FailureAccrualConfig {
+            max_consecutive_failures: usize::max_value(),
+            max_failure_rate: Some(config.error_rate_threshold),
+            window: CircuitBreakerConfig::MIN_REQUESTS,
+            sample_window: Some(config.sample_window),
+            backoff: config.open_duration,
+        }
+    }
+}
+
 // === impl ServerConfig ===
 
 impl<A: OrigDstAddr> ServerConfig<A> {
@@ -65,6 +126,7 @@ impl<A: OrigDstAddr> ProxyConfig<A> {
             router_capacity: self.router_capacity,
             router_max_idle_age: self.router_max_idle_age,
             disable_protocol_detection_for_ports: self.disable_protocol_detection_for_ports,
+            continue_ttfb: self.continue_ttfb,
         }
     }
 }
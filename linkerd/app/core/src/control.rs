@@ -280,8 +280,7 @@ pub mod resolve {
     impl<I: fmt::Display> fmt::Display for Error<I> {
         fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
             match self {
-                Error::Dns(dns::Error::NoAddressesFound) => write!(f, "no addresses found"),
-                Error::Dns(dns::Error::ResolutionFailed(e)) => fmt::Display::fmt(&e, f),
+                Error::Dns(ref e) => fmt::Display::fmt(&e, f),
                 Error::Inner(ref e) => fmt::Display::fmt(&e, f),
             }
         }
@@ -1,6 +1,9 @@
 use crate::Error;
+use futures::future;
 pub use linkerd2_dns::*;
+use std::net::SocketAddr;
 use std::path::PathBuf;
+use std::sync::Arc;
 use std::time::Duration;
 
 #[derive(Clone, Debug)]
@@ -8,6 +11,17 @@ pub struct Config {
     pub min_ttl: Option<Duration>,
     pub max_ttl: Option<Duration>,
     pub resolv_conf_path: PathBuf,
+
+    /// Nameservers that replace the system DNS configuration.
+    pub nameservers: Option<Vec<SocketAddr>>,
+    /// Search domains that replace those in the system DNS configuration.
+    pub search: Option<Vec<Name>>,
+    pub ndots: Option<usize>,
+    pub timeout: Option<Duration>,
+    pub rotate: bool,
+
+    /// Replaces the system resolver entirely, e.g. so tests may mock DNS.
+    pub resolver: Option<Arc<dyn DnsResolver>>,
 }
 
 pub struct Dns {
@@ -19,6 +33,14 @@ pub struct Dns {
 
 impl Config {
     pub fn build(self) -> Result<Dns, Error> {
+        if let Some(dns) = self.resolver.clone() {
+            let ndots = self.ndots.unwrap_or_else(|| ResolverOpts::default().ndots);
+            let search = SearchPath::new(self.search.clone().unwrap_or_default(), ndots);
+            let resolver = Resolver::new(dns, search);
+            let task: Task = Box::new(future::ok(()));
+            return Ok(Dns { resolver, task });
+        }
+
         let (resolver, task) =
             Resolver::from_system_config_with(&self).expect("system DNS config must be valid");
         Ok(Dns { resolver, task })
@@ -27,11 +49,29 @@ impl Config {
 
 impl ConfigureResolver for Config {
     /// Modify a `trust-dns-resolver::config::ResolverOpts` to reflect
-    /// the configured minimum and maximum DNS TTL values.
+    /// the configured minimum and maximum DNS TTL values and any overridden
+    /// resolver options.
     fn configure_resolver(&self, opts: &mut ResolverOpts) {
         opts.positive_min_ttl = self.min_ttl;
         opts.positive_max_ttl = self.max_ttl;
         opts.negative_min_ttl = self.min_ttl;
         opts.negative_max_ttl = self.max_ttl;
+        if let Some(ndots) = self.ndots {
+            opts.ndots = ndots;
+        }
+        if let Some(timeout) = self.timeout {
+            opts.timeout = timeout;
+        }
+        if self.rotate {
+            opts.rotate = true;
+        }
+    }
+
+    fn nameservers(&self) -> Option<Vec<SocketAddr>> {
+        self.nameservers.clone()
+    }
+
+    fn search(&self) -> Option<Vec<Name>> {
+        self.search.clone()
     }
 }
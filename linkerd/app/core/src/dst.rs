@@ -1,12 +1,17 @@
 use super::classify;
+use crate::transport::tls;
+use crate::Conditional;
 use http;
 use indexmap::IndexMap;
 use linkerd2_addr::{Addr, NameAddr};
+use linkerd2_error::Error;
 use linkerd2_proxy_http::{
+    client::ClientError,
     metrics::classify::{CanClassify, Classify, ClassifyEos, ClassifyResponse},
     profiles, retry, settings, timeout,
 };
 use std::fmt;
+use std::hash::{Hash, Hasher};
 use std::sync::Arc;
 use std::time::Duration;
 
@@ -20,6 +25,10 @@ pub enum Direction {
 pub struct Route {
     pub dst_addr: DstAddr,
     pub route: profiles::Route,
+    /// The route's retry policy, if it is retryable.
+    pub retry: Option<Retry>,
+    /// The route's timeout, if one is configured.
+    pub timeout: Option<Duration>,
 }
 
 #[derive(Clone, Debug)]
@@ -34,8 +43,23 @@ pub struct DstAddr {
     dst_concrete: Addr,
     direction: Direction,
     pub http_settings: settings::Settings,
+    /// The identity of the client that sent the request, if it is known.
+    ///
+    /// Requests from different clients are routed separately, so that
+    /// policies may distinguish them.
+    src_identity: tls::PeerIdentity,
 }
 
+/// Formats a `DstAddr`'s logical destination with its settings, e.g.
+/// `web.ns.svc.cluster.local:8080/h2`.
+#[derive(Copy, Clone, Debug)]
+pub struct Logical<'a>(&'a DstAddr);
+
+/// Formats a `DstAddr`'s concrete destination with its settings, e.g.
+/// `web-v2.ns.svc.cluster.local:8080/h1`.
+#[derive(Copy, Clone, Debug)]
+pub struct Concrete<'a>(&'a DstAddr);
+
 // === impl Route ===
 
 impl CanClassify for Route {
@@ -50,16 +74,13 @@ impl retry::CanRetry for Route {
     type Retry = Retry;
 
     fn can_retry(&self) -> Option<Self::Retry> {
-        self.route.retries().map(|retries| Retry {
-            budget: retries.budget().clone(),
-            response_classes: self.route.response_classes().clone(),
-        })
+        self.retry.clone()
     }
 }
 
 impl timeout::HasTimeout for Route {
     fn timeout(&self) -> Option<Duration> {
-        self.route.timeout()
+        self.timeout
     }
 }
 
@@ -87,6 +108,18 @@ impl retry::Retry for Retry {
         Err(retry::NoRetry::Success)
     }
 
+    /// Requests that failed because their connection was lost may be retried
+    /// on another connection.
+    fn retry_error<B>(&self, _: &http::Request<B>, err: &Error) -> Result<(), retry::NoRetry> {
+        if !ClientError::is_connection_error(&**err) {
+            return Err(retry::NoRetry::Unretryable);
+        }
+
+        self.budget
+            .withdraw()
+            .map_err(|_overdrawn| retry::NoRetry::Budget)
+    }
+
     fn clone_request<B: retry::TryClone>(
         &self,
         req: &http::Request<B>,
@@ -100,6 +133,22 @@ impl retry::Retry for Retry {
     }
 }
 
+/// Retry policies are equal if they share a budget and response classes.
+impl PartialEq for Retry {
+    fn eq(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.budget, &other.budget) && self.response_classes == other.response_classes
+    }
+}
+
+impl Eq for Retry {}
+
+impl Hash for Retry {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        (&*self.budget as *const retry::Budget).hash(state);
+        self.response_classes.hash(state);
+    }
+}
+
 // === impl DstAddr ===
 
 impl AsRef<Addr> for DstAddr {
@@ -115,6 +164,7 @@ impl DstAddr {
             dst_concrete: addr,
             direction: Direction::Out,
             http_settings,
+            src_identity: Self::no_src_identity(),
         }
     }
 
@@ -124,9 +174,35 @@ impl DstAddr {
             dst_concrete: addr,
             direction: Direction::In,
             http_settings,
+            src_identity: Self::no_src_identity(),
+        }
+    }
+
+    fn no_src_identity() -> tls::PeerIdentity {
+        Conditional::None(tls::ReasonForNoPeerName::NotProvidedByRemote.into())
+    }
+
+    /// Sets the identity of the client that sent the request.
+    pub fn with_src_identity(self, src_identity: tls::PeerIdentity) -> Self {
+        Self {
+            src_identity,
+            ..self
+        }
+    }
+
+    /// Sets the concrete destination, which may be an `IP:PORT`, e.g. to
+    /// resolve the same service in another cluster.
+    pub fn with_concrete(self, dst_concrete: Addr) -> Self {
+        Self {
+            dst_concrete,
+            ..self
         }
     }
 
+    pub fn src_identity(&self) -> &tls::PeerIdentity {
+        &self.src_identity
+    }
+
     pub fn direction(&self) -> Direction {
         self.direction
     }
@@ -138,6 +214,16 @@ impl DstAddr {
     pub fn dst_concrete(&self) -> &Addr {
         &self.dst_concrete
     }
+
+    /// Describes the logical destination, e.g. for tracing.
+    pub fn logical(&self) -> Logical<'_> {
+        Logical(self)
+    }
+
+    /// Describes the concrete destination, e.g. for tracing.
+    pub fn concrete(&self) -> Concrete<'_> {
+        Concrete(self)
+    }
 }
 
 impl<'t> From<&'t DstAddr> for http::header::HeaderValue {
@@ -162,8 +248,14 @@ impl profiles::WithRoute for DstAddr {
     type Output = Route;
 
     fn with_route(self, route: profiles::Route) -> Self::Output {
+        let retry = route.retries().map(|retries| Retry {
+            budget: retries.budget().clone(),
+            response_classes: route.response_classes().clone(),
+        });
         Route {
             dst_addr: self,
+            retry,
+            timeout: route.timeout(),
             route,
         }
     }
@@ -176,12 +268,39 @@ impl profiles::WithAddr for DstAddr {
     }
 }
 
+// === impl Logical ===
+
+impl<'a> fmt::Display for Logical<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.0.dst_logical, self.0.http_settings)
+    }
+}
+
+// === impl Concrete ===
+
+impl<'a> fmt::Display for Concrete<'a> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.0.dst_concrete, self.0.http_settings)
+    }
+}
+
 // === impl Route ===
 
 impl Route {
-    pub fn labels(&self) -> &Arc<IndexMap<String, String>> {
+    pub fn route_labels(&self) -> &Arc<IndexMap<String, String>> {
         self.route.labels()
     }
+
+    /// Adds a label to the route, e.g. to describe an individual request.
+    ///
+    /// Cloning a `Route` does not copy its labels; they are copied only when
+    /// a label is added to a route whose labels are shared.
+    pub fn with_extra_label(self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        Self {
+            route: self.route.with_extra_label(key, value),
+            ..self
+        }
+    }
 }
 
 impl fmt::Display for Route {
@@ -189,3 +308,167 @@ impl fmt::Display for Route {
         self.dst_addr.fmt(f)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::{profiles, retry, settings, DstAddr, Retry};
+    use linkerd2_addr::Addr;
+    use linkerd2_proxy_http::retry::{CanRetry, Retry as _};
+    use linkerd2_proxy_http::timeout::HasTimeout;
+    use profiles::WithRoute;
+    use std::sync::Arc;
+    use std::time::Duration;
+
+    #[test]
+    fn extra_labels_are_copied_on_write() {
+        let dst = DstAddr::outbound(
+            Addr::from_str("web.ns.svc.cluster.local:8080").unwrap(),
+            settings::Settings::Http2,
+        );
+        let route = dst.with_route(profiles::Route::new(
+            vec![("route".to_owned(), "get".to_owned())].into_iter(),
+            Vec::new(),
+        ));
+
+        // The labels are shared with the clone, so they're copied.
+        let shared = route.clone();
+        let labeled = route.with_extra_label("request_id", "abc");
+        assert!(!Arc::ptr_eq(shared.route_labels(), labeled.route_labels()));
+        assert_eq!(shared.route_labels().len(), 1);
+        assert_eq!(labeled.route_labels().get("request_id").unwrap(), "abc");
+
+        // The labels are not shared, so they're updated in place.
+        let ptr = Arc::as_ref(labeled.route_labels()) as *const _;
+        let relabeled = labeled.with_extra_label("request_id", "def");
+        assert_eq!(Arc::as_ref(relabeled.route_labels()) as *const _, ptr);
+        assert_eq!(relabeled.route_labels().get("request_id").unwrap(), "def");
+        assert_eq!(relabeled.route_labels().get("route").unwrap(), "get");
+    }
+
+    #[test]
+    fn retries_are_limited_by_budget() {
+        // Permits one retry for each successful request, with no reserve.
+        let budget = Arc::new(retry::Budget::new(Duration::from_secs(10), 0, 1.0));
+        let policy = Retry {
+            budget,
+            response_classes: Default::default(),
+        };
+
+        let req = http::Request::new(());
+        let success = http::Response::builder().status(200).body(()).unwrap();
+        let failure = http::Response::builder().status(500).body(()).unwrap();
+
+        match policy.retry(&req, &failure) {
+            Err(retry::NoRetry::Budget) => {}
+            _ => panic!("retries must not exceed an empty budget"),
+        }
+
+        match policy.retry(&req, &success) {
+            Err(retry::NoRetry::Success) => {}
+            _ => panic!("successful responses must not be retried"),
+        }
+
+        assert!(
+            policy.retry(&req, &failure).is_ok(),
+            "a success must permit a retry"
+        );
+        match policy.retry(&req, &failure) {
+            Err(retry::NoRetry::Budget) => {}
+            _ => panic!("retries must not exceed the budget"),
+        }
+    }
+
+    #[test]
+    fn route_policies_are_extracted() {
+        let addr = DstAddr::outbound(
+            Addr::from_str("web.ns.svc.cluster.local:80").unwrap(),
+            settings::Settings::Http2,
+        );
+
+        let route = addr
+            .clone()
+            .with_route(profiles::Route::new(std::iter::empty(), Vec::new()));
+        assert!(route.retry.is_none());
+        assert!(route.timeout.is_none());
+        assert!(route.can_retry().is_none());
+
+        let budget = Arc::new(retry::Budget::new(Duration::from_secs(10), 0, 0.2));
+        let mut profile = profiles::Route::new(std::iter::empty(), Vec::new());
+        profile.set_retries(budget.clone());
+        profile.set_timeout(Duration::from_secs(3));
+
+        let route = addr.with_route(profile);
+        let policy = route.retry.clone().expect("route must be retryable");
+        assert!(Arc::ptr_eq(&policy.budget, &budget));
+        assert_eq!(route.can_retry(), Some(policy));
+        assert_eq!(route.timeout, Some(Duration::from_secs(3)));
+        assert_eq!(HasTimeout::timeout(&route), Some(Duration::from_secs(3)));
+    }
+
+    #[test]
+    fn src_identities_distinguish_destinations() {
+        use crate::transport::tls;
+        use crate::Conditional;
+
+        let addr = DstAddr::inbound(
+            Addr::from_str("10.1.1.1:80").unwrap(),
+            settings::Settings::Http2,
+        );
+        let id = |name: &str| {
+            let name = crate::proxy::identity::Name::from_hostname(name.as_bytes()).unwrap();
+            Conditional::Some(name) as tls::PeerIdentity
+        };
+
+        let foo = addr
+            .clone()
+            .with_src_identity(id("foo.ns.serviceaccount.identity.linkerd.cluster.local"));
+        let bar = addr
+            .clone()
+            .with_src_identity(id("bar.ns.serviceaccount.identity.linkerd.cluster.local"));
+        assert_ne!(foo, bar);
+        assert_ne!(foo, addr);
+        assert_eq!(
+            foo,
+            addr.with_src_identity(id("foo.ns.serviceaccount.identity.linkerd.cluster.local"))
+        );
+    }
+
+    #[test]
+    fn logical_and_concrete_display_settings() {
+        use profiles::WithAddr;
+
+        let h1 = settings::Settings::Http1 {
+            keep_alive: true,
+            wants_h1_upgrade: false,
+            was_absolute_form: false,
+            is_http_1_0: false,
+            is_connect: false,
+        };
+        let addr = DstAddr::outbound(Addr::from_str("web.ns.svc.cluster.local:8080").unwrap(), h1)
+            .with_addr(
+                linkerd2_addr::NameAddr::from_str("web-v2.ns.svc.cluster.local:8080").unwrap(),
+            );
+        assert_eq!(
+            addr.logical().to_string(),
+            "web.ns.svc.cluster.local:8080/h1"
+        );
+        assert_eq!(
+            addr.concrete().to_string(),
+            "web-v2.ns.svc.cluster.local:8080/h1"
+        );
+        // The plain `Display` impl is used in headers, so it is unchanged.
+        assert_eq!(addr.to_string(), "web-v2.ns.svc.cluster.local:8080");
+
+        let addr = DstAddr::outbound(
+            Addr::from_str("10.1.1.1:80").unwrap(),
+            settings::Settings::Http2,
+        );
+        assert_eq!(addr.logical().to_string(), "10.1.1.1:80/h2");
+
+        let addr = DstAddr::inbound(
+            Addr::from_str("10.1.1.1:80").unwrap(),
+            settings::Settings::NotHttp,
+        );
+        assert_eq!(addr.concrete().to_string(), "10.1.1.1:80/tcp");
+    }
+}
@@ -1,10 +1,13 @@
 //! Layer to map HTTP service errors into appropriate `http::Response`s.
 
+use crate::headers::L5D_PROXY_ERROR;
 use crate::svc;
 use futures::{Future, Poll};
 use http::{header, Request, Response, StatusCode, Version};
 use linkerd2_error::Error;
 use linkerd2_proxy_http::HasH2Reason;
+use linkerd2_timeout::error::Timedout;
+use std::net::SocketAddr;
 use tracing::{debug, error, warn};
 
 /// Layer to map HTTP service errors into appropriate `http::Response`s.
@@ -35,6 +38,13 @@ pub struct StatusError {
     pub message: String,
 }
 
+/// Indicates that traffic could not be forwarded through an upstream proxy.
+#[derive(Debug)]
+pub struct UpstreamProxyError {
+    pub proxy: SocketAddr,
+    pub source: Error,
+}
+
 impl<M> svc::Layer<M> for Layer {
     type Service = Stack<M>;
 
@@ -102,7 +112,11 @@ where
                     }
                 }
 
-                let response = Response::builder()
+                let mut response = Response::builder();
+                if let Some(message) = describe(&err) {
+                    response.header(&*L5D_PROXY_ERROR, message);
+                }
+                let response = response
                     .status(map_err_to_5xx(err))
                     .header(header::CONTENT_LENGTH, "0")
                     .body(B::default())
@@ -115,23 +129,38 @@ where
 }
 
 fn map_err_to_5xx(e: Error) -> StatusCode {
-    use crate::proxy::buffer;
+    use crate::proxy::{buffer, http::validate_response, pending::PendingTimeout};
     use linkerd2_router::error as router;
     use tower::load_shed::error as shed;
 
-    if let Some(ref c) = e.downcast_ref::<router::NoCapacity>() {
+    // Errors may be wrapped with context (e.g. describing the endpoint they
+    // pertain to), so the whole chain is searched for known causes. The
+    // logged message is the outermost error, including its context.
+    if let Some(ref c) = find::<router::NoCapacity>(&e) {
         warn!("router at capacity ({})", c.0);
         http::StatusCode::SERVICE_UNAVAILABLE
-    } else if let Some(_) = e.downcast_ref::<shed::Overloaded>() {
+    } else if let Some(_) = find::<shed::Overloaded>(&e) {
         warn!("server overloaded, max-in-flight reached");
         http::StatusCode::SERVICE_UNAVAILABLE
-    } else if let Some(_) = e.downcast_ref::<buffer::Aborted>() {
+    } else if let Some(_) = find::<buffer::Aborted>(&e) {
         warn!("request aborted because it reached the configured dispatch deadline");
         http::StatusCode::SERVICE_UNAVAILABLE
-    } else if let Some(_) = e.downcast_ref::<router::NotRecognized>() {
+    } else if let Some(_) = find::<PendingTimeout>(&e) {
+        warn!("{}", e);
+        http::StatusCode::SERVICE_UNAVAILABLE
+    } else if let Some(t) = find::<Timedout>(&e) {
+        warn!("{}", t);
+        http::StatusCode::GATEWAY_TIMEOUT
+    } else if let Some(_) = find::<router::NotRecognized>(&e) {
         error!("could not recognize request");
         http::StatusCode::BAD_GATEWAY
-    } else if let Some(err) = e.downcast_ref::<StatusError>() {
+    } else if let Some(_) = find::<validate_response::InvalidResponse>(&e) {
+        warn!("{}", e);
+        http::StatusCode::BAD_GATEWAY
+    } else if let Some(err) = find::<UpstreamProxyError>(&e) {
+        warn!(proxy.addr = %err.proxy, "upstream proxy failed: {}", e);
+        http::StatusCode::BAD_GATEWAY
+    } else if let Some(err) = find::<StatusError>(&e) {
         error!(%err.status, %err.message);
         err.status
     } else {
@@ -141,6 +170,47 @@ fn map_err_to_5xx(e: Error) -> StatusCode {
     }
 }
 
+/// Describes errors that the client may act on, e.g. by raising a timeout.
+///
+/// Other errors are not described, since they may leak details of the
+/// proxy's configuration.
+fn describe(e: &Error) -> Option<String> {
+    find::<Timedout>(e).map(ToString::to_string)
+}
+
+/// Finds the first error of type `E` in `e`'s chain of sources.
+fn find<E: std::error::Error + 'static>(e: &Error) -> Option<&E> {
+    let mut cause = Some(&**e as &(dyn std::error::Error + 'static));
+    while let Some(err) = cause {
+        if let Some(err) = err.downcast_ref::<E>() {
+            return Some(err);
+        }
+        cause = err.source();
+    }
+    None
+}
+
+impl UpstreamProxyError {
+    pub fn new(proxy: SocketAddr, source: impl Into<Error>) -> Self {
+        Self {
+            proxy,
+            source: source.into(),
+        }
+    }
+}
+
+impl std::fmt::Display for UpstreamProxyError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "upstream proxy {} failed: {}", self.proxy, self.source)
+    }
+}
+
+impl std::error::Error for UpstreamProxyError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&*self.source)
+    }
+}
+
 impl std::fmt::Display for StatusError {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         self.message.fmt(f)
@@ -148,3 +218,56 @@ impl std::fmt::Display for StatusError {
 }
 
 impl std::error::Error for StatusError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::proxy::error_context::ContextError;
+    use crate::svc::{Layer as _, Service as _};
+    use std::time::Duration;
+    use tokio::runtime::current_thread::Runtime;
+
+    #[test]
+    fn finds_causes_beneath_context() {
+        let proxy = "10.0.0.1:3128".parse().unwrap();
+        let root = std::io::Error::new(std::io::ErrorKind::ConnectionRefused, "refused");
+        let e: Error = ContextError::new(
+            "web.ns.svc.cluster.local:8080",
+            UpstreamProxyError::new(proxy, root),
+        )
+        .into();
+
+        assert!(find::<ContextError<&'static str>>(&e).is_some());
+        assert_eq!(find::<UpstreamProxyError>(&e).map(|e| e.proxy), Some(proxy));
+        assert_eq!(
+            find::<std::io::Error>(&e).map(|e| e.kind()),
+            Some(std::io::ErrorKind::ConnectionRefused)
+        );
+        assert_eq!(map_err_to_5xx(e), http::StatusCode::BAD_GATEWAY);
+    }
+
+    #[test]
+    fn named_timeouts_are_gateway_timeouts() {
+        let mut rt = Runtime::new().unwrap();
+        let inner = svc::mk(|_: Request<()>| futures::future::empty::<Response<()>, Error>());
+        let mut svc = Service(
+            svc::timeout::layer_named("endpoint-response", Duration::from_millis(10)).layer(inner),
+        );
+
+        let rsp = rt
+            .block_on(svc.call(Request::new(())))
+            .expect("errors must be responses");
+        assert_eq!(rsp.status(), http::StatusCode::GATEWAY_TIMEOUT);
+        assert_eq!(
+            rsp.headers()[&*L5D_PROXY_ERROR],
+            "request timed out after 10ms waiting for endpoint response"
+        );
+    }
+
+    #[test]
+    fn other_errors_are_not_described() {
+        let e: Error = std::io::Error::new(std::io::ErrorKind::Other, "secret").into();
+        assert_eq!(describe(&e), None);
+        assert_eq!(map_err_to_5xx(e), http::StatusCode::BAD_GATEWAY);
+    }
+}
@@ -0,0 +1,570 @@
+//! Allows operators to freeze the discovery state of a destination.
+//!
+//! While a destination is frozen, profile and endpoint updates for it continue
+//! to be received so that the control plane's streams are never blocked, but
+//! they are not applied. Only the latest state is retained; it is applied once
+//! the destination is unfrozen.
+//!
+//! A watch that has not yet applied any state is not frozen, so that services
+//! built while a destination is frozen are still able to route requests.
+
+use crate::dst::DstAddr;
+use crate::proxy::core::resolve::{self, Update};
+use futures::{try_ready, Async, Future, Poll};
+use indexmap::IndexMap;
+use linkerd2_addr::NameAddr;
+use linkerd2_metrics::{metrics, Counter, FmtLabels, FmtMetric, FmtMetrics, Gauge};
+use std::collections::VecDeque;
+use std::fmt;
+use std::net::SocketAddr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use tokio::sync::watch;
+use tracing::debug;
+
+metrics! {
+    destination_frozen: Gauge {
+        "Indicates that discovery updates for a destination are not being applied"
+    },
+    destination_frozen_updates_total: Counter {
+        "Total count of discovery updates received while a destination is frozen"
+    }
+}
+
+/// The frozen destinations, each with a count of the updates they have held.
+type Frozen = Arc<IndexMap<NameAddr, Arc<AtomicU64>>>;
+
+/// Tracks which destinations are frozen.
+#[derive(Clone)]
+pub struct Registry {
+    state: Arc<Mutex<State>>,
+    rx: watch::Receiver<Frozen>,
+}
+
+struct State {
+    frozen: Frozen,
+    tx: watch::Sender<Frozen>,
+}
+
+/// Determines whether any of a set of destinations is frozen.
+pub struct Flag {
+    dsts: Vec<NameAddr>,
+    rx: watch::Receiver<Frozen>,
+}
+
+/// Holds the latest item of a stream of complete states while frozen.
+pub struct Stream<S: futures::Stream> {
+    inner: S,
+    flag: Flag,
+    latest: Option<S::Item>,
+    initialized: bool,
+    done: bool,
+}
+
+/// Wraps endpoint resolutions so that updates are not applied while frozen.
+#[derive(Clone, Debug)]
+pub struct Resolve<R> {
+    inner: R,
+    registry: Registry,
+}
+
+pub struct ResolveFuture<F> {
+    inner: F,
+    flag: Option<Flag>,
+}
+
+/// Holds endpoint updates while frozen.
+///
+/// When the destination is unfrozen, the difference between the endpoints
+/// that were last applied and those most recently observed is applied.
+pub struct Resolution<R: resolve::Resolution> {
+    inner: R,
+    flag: Flag,
+    observed: IndexMap<SocketAddr, R::Endpoint>,
+    /// The endpoints that were applied when the destination was frozen.
+    applied: Option<IndexMap<SocketAddr, R::Endpoint>>,
+    pending: VecDeque<Update<R::Endpoint>>,
+    initialized: bool,
+}
+
+struct DstLabel<'a>(&'a NameAddr);
+
+// === impl Registry ===
+
+impl Default for Registry {
+    fn default() -> Self {
+        let frozen = Frozen::default();
+        let (tx, rx) = watch::channel(frozen.clone());
+        Self {
+            state: Arc::new(Mutex::new(State { frozen, tx })),
+            rx,
+        }
+    }
+}
+
+impl Registry {
+    /// Freezes `dst`, returning false if it was already frozen.
+    pub fn freeze(&self, dst: NameAddr) -> bool {
+        self.update(|frozen| {
+            if frozen.contains_key(&dst) {
+                return false;
+            }
+            debug!(%dst, "freezing");
+            frozen.insert(dst, Arc::new(AtomicU64::new(0)));
+            true
+        })
+    }
+
+    /// Unfreezes `dst`, returning false if it was not frozen.
+    pub fn unfreeze(&self, dst: &NameAddr) -> bool {
+        self.update(|frozen| {
+            if frozen.remove(dst).is_none() {
+                return false;
+            }
+            debug!(%dst, "unfreezing");
+            true
+        })
+    }
+
+    /// Lists the frozen destinations.
+    pub fn frozen(&self) -> Vec<NameAddr> {
+        match self.state.lock() {
+            Ok(state) => state.frozen.keys().cloned().collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+
+    pub fn flag(&self, dsts: Vec<NameAddr>) -> Flag {
+        Flag {
+            dsts,
+            rx: self.rx.clone(),
+        }
+    }
+
+    pub fn stream<S: futures::Stream>(&self, dst: NameAddr, inner: S) -> Stream<S> {
+        Stream {
+            inner,
+            flag: self.flag(vec![dst]),
+            latest: None,
+            initialized: false,
+            done: false,
+        }
+    }
+
+    fn update(&self, f: impl FnOnce(&mut IndexMap<NameAddr, Arc<AtomicU64>>) -> bool) -> bool {
+        let mut state = match self.state.lock() {
+            Ok(state) => state,
+            Err(_) => return false,
+        };
+
+        let mut frozen = (*state.frozen).clone();
+        if !f(&mut frozen) {
+            return false;
+        }
+        state.frozen = Arc::new(frozen);
+        let frozen = state.frozen.clone();
+        // The registry holds a receiver, so the channel is never closed.
+        let _ = state.tx.broadcast(frozen);
+        true
+    }
+}
+
+impl fmt::Debug for Registry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Registry")
+            .field("frozen", &self.frozen())
+            .finish()
+    }
+}
+
+impl FmtMetrics for Registry {
+    fn fmt_metrics(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let frozen = match self.state.lock() {
+            Ok(state) => state.frozen.clone(),
+            Err(_) => return Ok(()),
+        };
+        if frozen.is_empty() {
+            return Ok(());
+        }
+
+        destination_frozen.fmt_help(f)?;
+        for dst in frozen.keys() {
+            Gauge::from(1).fmt_metric_labeled(f, destination_frozen.name, DstLabel(dst))?;
+        }
+
+        destination_frozen_updates_total.fmt_help(f)?;
+        for (dst, held) in frozen.iter() {
+            Counter::from(held.load(Ordering::Acquire)).fmt_metric_labeled(
+                f,
+                destination_frozen_updates_total.name,
+                DstLabel(dst),
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+// === impl Flag ===
+
+impl Flag {
+    /// Returns the held update counter of a frozen destination, if any of the
+    /// flag's destinations is frozen.
+    ///
+    /// The current task is notified when the set of frozen destinations
+    /// changes.
+    pub fn poll_frozen(&mut self) -> Option<Arc<AtomicU64>> {
+        while let Ok(Async::Ready(Some(_))) = self.rx.poll_ref() {}
+
+        let frozen = self.rx.get_ref();
+        let held = self.dsts.iter().filter_map(|dst| frozen.get(dst)).next();
+        held.cloned()
+    }
+}
+
+// === impl Stream ===
+
+impl<S: futures::Stream> futures::Stream for Stream<S> {
+    type Item = S::Item;
+    type Error = S::Error;
+
+    fn poll(&mut self) -> Poll<Option<Self::Item>, Self::Error> {
+        let held = if self.initialized {
+            self.flag.poll_frozen()
+        } else {
+            None
+        };
+
+        // The inner stream is always drained, even while frozen, so that its
+        // producer is never blocked.
+        while !self.done {
+            match self.inner.poll()? {
+                Async::Ready(Some(item)) => {
+                    if let Some(ref held) = held {
+                        held.fetch_add(1, Ordering::AcqRel);
+                    }
+                    self.latest = Some(item);
+                }
+                Async::Ready(None) => self.done = true,
+                Async::NotReady => break,
+            }
+        }
+
+        if held.is_some() {
+            return Ok(Async::NotReady);
+        }
+
+        if let Some(item) = self.latest.take() {
+            self.initialized = true;
+            return Ok(Async::Ready(Some(item)));
+        }
+
+        if self.done {
+            return Ok(Async::Ready(None));
+        }
+
+        Ok(Async::NotReady)
+    }
+}
+
+// === impl Resolve ===
+
+impl<R> Resolve<R> {
+    pub fn new(inner: R, registry: Registry) -> Self {
+        Self { inner, registry }
+    }
+}
+
+impl<R> tower::Service<DstAddr> for Resolve<R>
+where
+    R: resolve::Resolve<DstAddr>,
+    R::Endpoint: Clone + PartialEq,
+{
+    type Response = Resolution<R::Resolution>;
+    type Error = R::Error;
+    type Future = ResolveFuture<R::Future>;
+
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        resolve::Resolve::poll_ready(&mut self.inner)
+    }
+
+    fn call(&mut self, dst: DstAddr) -> Self::Future {
+        // Freezing a logical destination also freezes the endpoints of each
+        // of its concrete destinations.
+        let dsts = vec![dst.dst_logical(), dst.dst_concrete()]
+            .into_iter()
+            .filter_map(|addr| addr.name_addr().cloned())
+            .collect();
+        ResolveFuture {
+            flag: Some(self.registry.flag(dsts)),
+            inner: resolve::Resolve::resolve(&mut self.inner, dst),
+        }
+    }
+}
+
+impl<F> Future for ResolveFuture<F>
+where
+    F: Future,
+    F::Item: resolve::Resolution,
+{
+    type Item = Resolution<F::Item>;
+    type Error = F::Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        let inner = try_ready!(self.inner.poll());
+        let flag = self.flag.take().expect("polled after ready");
+        Ok(Async::Ready(Resolution {
+            inner,
+            flag,
+            observed: IndexMap::new(),
+            applied: None,
+            pending: VecDeque::new(),
+            initialized: false,
+        }))
+    }
+}
+
+// === impl Resolution ===
+
+impl<R> resolve::Resolution for Resolution<R>
+where
+    R: resolve::Resolution,
+    R::Endpoint: Clone + PartialEq,
+{
+    type Endpoint = R::Endpoint;
+    type Error = R::Error;
+
+    fn poll(&mut self) -> Poll<Update<Self::Endpoint>, Self::Error> {
+        loop {
+            if let Some(update) = self.pending.pop_front() {
+                return Ok(Async::Ready(update));
+            }
+
+            let held = if self.initialized {
+                self.flag.poll_frozen()
+            } else {
+                None
+            };
+
+            match held {
+                Some(held) => {
+                    if self.applied.is_none() {
+                        self.applied = Some(self.observed.clone());
+                    }
+                    // Continue to consume updates so that the resolution is
+                    // never blocked, but only record them.
+                    loop {
+                        let update = try_ready!(resolve::Resolution::poll(&mut self.inner));
+                        held.fetch_add(1, Ordering::AcqRel);
+                        self.observe(&update);
+                    }
+                }
+                None => {
+                    if let Some(applied) = self.applied.take() {
+                        self.pending = diff(applied, &self.observed);
+                        continue;
+                    }
+
+                    let update = try_ready!(resolve::Resolution::poll(&mut self.inner));
+                    self.observe(&update);
+                    self.initialized = true;
+                    return Ok(Async::Ready(update));
+                }
+            }
+        }
+    }
+}
+
+impl<R> Resolution<R>
+where
+    R: resolve::Resolution,
+    R::Endpoint: Clone,
+{
+    fn observe(&mut self, update: &Update<R::Endpoint>) {
+        match update {
+            Update::Add(endpoints) => {
+                for (addr, endpoint) in endpoints {
+                    self.observed.insert(*addr, endpoint.clone());
+                }
+            }
+            Update::Remove(addrs) => {
+                for addr in addrs {
+                    self.observed.remove(addr);
+                }
+            }
+            Update::Empty | Update::DoesNotExist => self.observed.clear(),
+        }
+    }
+}
+
+/// Builds the updates that change the `applied` endpoints to the `observed`
+/// endpoints.
+fn diff<E: Clone + PartialEq>(
+    applied: IndexMap<SocketAddr, E>,
+    observed: &IndexMap<SocketAddr, E>,
+) -> VecDeque<Update<E>> {
+    let removed = applied
+        .keys()
+        .filter(|addr| !observed.contains_key(*addr))
+        .cloned()
+        .collect::<Vec<_>>();
+    let added = observed
+        .iter()
+        .filter(|(addr, endpoint)| applied.get(*addr) != Some(endpoint))
+        .map(|(addr, endpoint)| (*addr, endpoint.clone()))
+        .collect::<Vec<_>>();
+
+    let mut updates = VecDeque::with_capacity(2);
+    if !removed.is_empty() {
+        updates.push_back(Update::Remove(removed));
+    }
+    if !added.is_empty() {
+        updates.push_back(Update::Add(added));
+    }
+    updates
+}
+
+// === impl DstLabel ===
+
+impl<'a> FmtLabels for DstLabel<'a> {
+    fn fmt_labels(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "dst=\"{}\"", self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{future, stream, Stream as _};
+    use linkerd2_proxy_core::resolve::Resolution as _;
+
+    fn dst() -> NameAddr {
+        NameAddr::from_str("web.ns.svc.cluster.local:8080").unwrap()
+    }
+
+    fn addr(port: u16) -> SocketAddr {
+        ([10, 1, 1, 1], port).into()
+    }
+
+    /// A resolution that yields scripted updates.
+    struct Scripted(futures::sync::mpsc::UnboundedReceiver<Update<u16>>);
+
+    impl resolve::Resolution for Scripted {
+        type Endpoint = u16;
+        type Error = ();
+
+        fn poll(&mut self) -> Poll<Update<u16>, ()> {
+            match try_ready!(self.0.poll()) {
+                Some(update) => Ok(Async::Ready(update)),
+                None => Ok(Async::NotReady),
+            }
+        }
+    }
+
+    #[test]
+    fn stream_applies_latest_item_when_unfrozen() {
+        future::lazy(|| {
+            let registry = Registry::default();
+            let (tx, rx) = futures::sync::mpsc::unbounded::<u32>();
+            let mut stream = registry.stream(dst(), rx);
+
+            // The first item is applied even though the destination is
+            // frozen.
+            registry.freeze(dst());
+            tx.unbounded_send(1).unwrap();
+            assert_eq!(stream.poll(), Ok(Async::Ready(Some(1))));
+
+            tx.unbounded_send(2).unwrap();
+            tx.unbounded_send(3).unwrap();
+            assert_eq!(stream.poll(), Ok(Async::NotReady));
+            assert_eq!(registry.frozen(), vec![dst()]);
+
+            assert!(registry.unfreeze(&dst()));
+            assert_eq!(stream.poll(), Ok(Async::Ready(Some(3))));
+            assert_eq!(stream.poll(), Ok(Async::NotReady));
+
+            // Other destinations are not frozen.
+            registry.freeze(NameAddr::from_str("other.ns.svc.cluster.local:80").unwrap());
+            tx.unbounded_send(4).unwrap();
+            assert_eq!(stream.poll(), Ok(Async::Ready(Some(4))));
+
+            Ok::<_, ()>(())
+        })
+        .wait()
+        .unwrap();
+    }
+
+    #[test]
+    fn stream_is_not_frozen_until_initialized() {
+        let registry = Registry::default();
+        registry.freeze(dst());
+        let stream = registry.stream(dst(), stream::iter_ok::<_, ()>(vec![1, 2]));
+        assert_eq!(stream.wait().next(), Some(Ok(2)));
+    }
+
+    #[test]
+    fn resolution_applies_diff_when_unfrozen() {
+        future::lazy(|| {
+            let registry = Registry::default();
+            let (tx, rx) = futures::sync::mpsc::unbounded();
+            let mut resolution = Resolution {
+                inner: Scripted(rx),
+                flag: registry.flag(vec![dst()]),
+                observed: IndexMap::new(),
+                applied: None,
+                pending: VecDeque::new(),
+                initialized: false,
+            };
+
+            let add = Update::Add(vec![(addr(1), 1), (addr(2), 2)]);
+            tx.unbounded_send(add.clone()).unwrap();
+            assert_eq!(resolution.poll(), Ok(Async::Ready(add)));
+
+            registry.freeze(dst());
+            tx.unbounded_send(Update::Remove(vec![addr(1)])).unwrap();
+            tx.unbounded_send(Update::Add(vec![(addr(2), 22), (addr(3), 3)]))
+                .unwrap();
+            assert_eq!(resolution.poll(), Ok(Async::NotReady));
+
+            registry.unfreeze(&dst());
+            assert_eq!(
+                resolution.poll(),
+                Ok(Async::Ready(Update::Remove(vec![addr(1)])))
+            );
+            assert_eq!(
+                resolution.poll(),
+                Ok(Async::Ready(Update::Add(vec![(addr(2), 22), (addr(3), 3)])))
+            );
+            assert_eq!(resolution.poll(), Ok(Async::NotReady));
+
+            Ok::<_, ()>(())
+        })
+        .wait()
+        .unwrap();
+    }
+
+    #[test]
+    fn reports_frozen_destinations() {
+        let registry = Registry::default();
+        let (tx, rx) = futures::sync::mpsc::unbounded::<u32>();
+        let mut stream = registry.stream(dst(), rx);
+        tx.unbounded_send(1).unwrap();
+        stream = future::lazy(move || stream.poll().map(|_| stream))
+            .wait()
+            .unwrap();
+
+        registry.freeze(dst());
+        tx.unbounded_send(2).unwrap();
+        let _stream = future::lazy(move || stream.poll().map(|_| stream))
+            .wait()
+            .unwrap();
+
+        let report = registry.as_display().to_string();
+        assert!(report.contains("destination_frozen{dst=\"web.ns.svc.cluster.local:8080\"} 1"));
+        assert!(report
+            .contains("destination_frozen_updates_total{dst=\"web.ns.svc.cluster.local:8080\"} 1"));
+
+        registry.unfreeze(&dst());
+        assert_eq!(registry.as_display().to_string(), "");
+    }
+}
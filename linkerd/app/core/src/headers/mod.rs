@@ -0,0 +1,324 @@
+//! Declares every `l5d-` header used by the proxy.
+//!
+//! Each header is registered in `REGISTRY` along with the messages that carry
+//! it and whether it must be removed as messages cross a proxy boundary. The
+//! `strip` layer applies these rules, so adding a new header only requires a
+//! new entry here.
+
+use crate::proxy::http::{orig_proto, request_timeout};
+use http::header::{HeaderMap, HeaderName};
+use lazy_static::lazy_static;
+
+pub mod strip;
+
+lazy_static! {
+    pub static ref L5D_DST_CANONICAL: HeaderName = HeaderName::from_static("l5d-dst-canonical");
+    pub static ref L5D_DST_OVERRIDE: HeaderName = HeaderName::from_static("l5d-dst-override");
+    pub static ref L5D_DST_CONCRETE: HeaderName = HeaderName::from_static("l5d-dst-concrete");
+    pub static ref L5D_DST_ENDPOINT: HeaderName = HeaderName::from_static("l5d-dst-endpoint");
+    pub static ref L5D_REMOTE_IP: HeaderName = HeaderName::from_static("l5d-remote-ip");
+    pub static ref L5D_SERVER_ID: HeaderName = HeaderName::from_static("l5d-server-id");
+    pub static ref L5D_CLIENT_ID: HeaderName = HeaderName::from_static("l5d-client-id");
+    pub static ref L5D_REQUIRE_ID: HeaderName = HeaderName::from_static("l5d-require-id");
+    pub static ref L5D_NO_UPGRADE: HeaderName = HeaderName::from_static("l5d-no-upgrade");
+    pub static ref L5D_PROXY_ERROR: HeaderName = HeaderName::from_static("l5d-proxy-error");
+    pub static ref L5D_ORIG_PROTO: HeaderName =
+        HeaderName::from_static(orig_proto::L5D_ORIG_PROTO);
+    pub static ref L5D_REQUEST_TIMEOUT: HeaderName =
+        HeaderName::from_static(request_timeout::L5D_REQUEST_TIMEOUT);
+
+    pub static ref REGISTRY: Vec<Header> = vec![
+        // Set by the outbound proxy so that the inbound proxy may discover the
+        // destination's profile.
+        Header {
+            name: L5D_DST_CANONICAL.clone(),
+            direction: Direction::Request,
+            trusted_source_only: false,
+            strip_on_egress: false,
+            strip_on_ingress_if_untrusted: true,
+        },
+        // Set by the application to override the request's destination. It is
+        // consumed when the request is routed.
+        Header {
+            name: L5D_DST_OVERRIDE.clone(),
+            direction: Direction::Request,
+            trusted_source_only: false,
+            strip_on_egress: true,
+            strip_on_ingress_if_untrusted: false,
+        },
+        // Optionally set by the outbound proxy to describe how a request was
+        // routed. Only the local proxy may set these.
+        Header {
+            name: L5D_DST_CONCRETE.clone(),
+            direction: Direction::Response,
+            trusted_source_only: true,
+            strip_on_egress: true,
+            strip_on_ingress_if_untrusted: false,
+        },
+        Header {
+            name: L5D_DST_ENDPOINT.clone(),
+            direction: Direction::Response,
+            trusted_source_only: true,
+            strip_on_egress: true,
+            strip_on_ingress_if_untrusted: false,
+        },
+        Header {
+            name: L5D_REMOTE_IP.clone(),
+            direction: Direction::Both,
+            trusted_source_only: true,
+            strip_on_egress: false,
+            strip_on_ingress_if_untrusted: false,
+        },
+        Header {
+            name: L5D_SERVER_ID.clone(),
+            direction: Direction::Response,
+            trusted_source_only: true,
+            strip_on_egress: true,
+            strip_on_ingress_if_untrusted: false,
+        },
+        Header {
+            name: L5D_CLIENT_ID.clone(),
+            direction: Direction::Request,
+            trusted_source_only: true,
+            strip_on_egress: true,
+            strip_on_ingress_if_untrusted: false,
+        },
+        // Set by the application to require a server identity. It is consumed
+        // when the endpoint is built.
+        Header {
+            name: L5D_REQUIRE_ID.clone(),
+            direction: Direction::Request,
+            trusted_source_only: false,
+            strip_on_egress: true,
+            strip_on_ingress_if_untrusted: false,
+        },
+        // Set by the application so that a request is not upgraded to HTTP/2. It
+        // is consumed by the orig-proto upgrade layer.
+        Header {
+            name: L5D_NO_UPGRADE.clone(),
+            direction: Direction::Request,
+            trusted_source_only: false,
+            strip_on_egress: true,
+            strip_on_ingress_if_untrusted: false,
+        },
+        // Set by a proxy on the responses that it synthesizes for errors, to
+        // describe the error.
+        Header {
+            name: L5D_PROXY_ERROR.clone(),
+            direction: Direction::Response,
+            trusted_source_only: false,
+            strip_on_egress: false,
+            strip_on_ingress_if_untrusted: false,
+        },
+        // Consumed by the orig-proto upgrade and downgrade layers.
+        Header {
+            name: L5D_ORIG_PROTO.clone(),
+            direction: Direction::Both,
+            trusted_source_only: false,
+            strip_on_egress: false,
+            strip_on_ingress_if_untrusted: false,
+        },
+        // Set by the application to bound how long the outbound proxy waits for a
+        // response. It is consumed by the request timeout layer.
+        Header {
+            name: L5D_REQUEST_TIMEOUT.clone(),
+            direction: Direction::Request,
+            trusted_source_only: false,
+            strip_on_egress: true,
+            strip_on_ingress_if_untrusted: false,
+        },
+    ];
+}
+
+/// Describes an `l5d-` header and when it is stripped.
+///
+/// Messages received from the network--inbound requests and outbound
+/// responses--are _ingress_ messages. Messages sent to the network--inbound
+/// responses and outbound requests--are _egress_ messages.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Header {
+    pub name: HeaderName,
+    pub direction: Direction,
+    /// Only the local proxy may set this header, so it is stripped from all
+    /// ingress messages.
+    pub trusted_source_only: bool,
+    /// The header is stripped from all egress messages.
+    pub strip_on_egress: bool,
+    /// The header is stripped from ingress messages unless the peer has a
+    /// verified identity.
+    pub strip_on_ingress_if_untrusted: bool,
+}
+
+/// The messages that carry a header.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Direction {
+    Request,
+    Response,
+    Both,
+}
+
+/// The proxy on which a message is handled.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Boundary {
+    Inbound,
+    Outbound,
+}
+
+// === impl Header ===
+
+impl Header {
+    /// Returns whether the header is stripped from a message of the given
+    /// kind as it crosses `boundary`.
+    pub fn is_stripped(&self, boundary: Boundary, message: Direction, trusted: bool) -> bool {
+        if !self.direction.carries(message) {
+            return false;
+        }
+
+        if boundary.is_ingress(message) {
+            self.trusted_source_only || (self.strip_on_ingress_if_untrusted && !trusted)
+        } else {
+            self.strip_on_egress
+        }
+    }
+}
+
+// === impl Direction ===
+
+impl Direction {
+    fn carries(&self, message: Direction) -> bool {
+        *self == Direction::Both || *self == message
+    }
+}
+
+// === impl Boundary ===
+
+impl Boundary {
+    fn is_ingress(&self, message: Direction) -> bool {
+        match (self, message) {
+            (Boundary::Inbound, Direction::Request) | (Boundary::Outbound, Direction::Response) => {
+                true
+            }
+            _ => false,
+        }
+    }
+}
+
+/// Removes registered headers from a request crossing `boundary`.
+pub fn strip_request<T>(boundary: Boundary, trusted: bool, headers: &mut HeaderMap<T>) {
+    strip(boundary, Direction::Request, trusted, headers)
+}
+
+/// Removes registered headers from a response crossing `boundary`.
+pub fn strip_response<T>(boundary: Boundary, trusted: bool, headers: &mut HeaderMap<T>) {
+    strip(boundary, Direction::Response, trusted, headers)
+}
+
+fn strip<T>(boundary: Boundary, message: Direction, trusted: bool, headers: &mut HeaderMap<T>) {
+    for header in REGISTRY.iter() {
+        if header.is_stripped(boundary, message, trusted) {
+            headers.remove(&header.name);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use http::header::HeaderValue;
+
+    fn all_headers() -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for h in REGISTRY.iter() {
+            headers.insert(h.name.clone(), HeaderValue::from_static("value"));
+        }
+        headers
+    }
+
+    #[test]
+    fn registry_names_are_unique_and_prefixed() {
+        for (i, h) in REGISTRY.iter().enumerate() {
+            assert!(
+                h.name.as_str().starts_with("l5d-"),
+                "{} is not an l5d header",
+                h.name
+            );
+            assert!(
+                REGISTRY[i + 1..].iter().all(|o| o.name != h.name),
+                "{} is registered twice",
+                h.name
+            );
+        }
+    }
+
+    #[test]
+    fn strips_each_header_at_both_boundaries() {
+        for &boundary in &[Boundary::Inbound, Boundary::Outbound] {
+            for &trusted in &[true, false] {
+                let mut req = all_headers();
+                strip_request(boundary, trusted, &mut req);
+                let mut rsp = all_headers();
+                strip_response(boundary, trusted, &mut rsp);
+
+                for h in REGISTRY.iter() {
+                    for &(message, ref headers) in
+                        &[(Direction::Request, &req), (Direction::Response, &rsp)]
+                    {
+                        let ingress = boundary.is_ingress(message);
+                        let expected = h.direction.carries(message)
+                            && if ingress {
+                                h.trusted_source_only
+                                    || (h.strip_on_ingress_if_untrusted && !trusted)
+                            } else {
+                                h.strip_on_egress
+                            };
+                        assert_eq!(
+                            !headers.contains_key(&h.name),
+                            expected,
+                            "{} on {:?} {:?} (trusted={})",
+                            h.name,
+                            boundary,
+                            message,
+                            trusted,
+                        );
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn strips_identity_headers_from_peers() {
+        let mut req = all_headers();
+        strip_request(Boundary::Inbound, true, &mut req);
+        assert!(!req.contains_key(&*L5D_CLIENT_ID));
+        assert!(!req.contains_key(&*L5D_REMOTE_IP));
+        assert!(req.contains_key(&*L5D_DST_CANONICAL));
+        assert!(req.contains_key(&*L5D_ORIG_PROTO));
+
+        let mut req = all_headers();
+        strip_request(Boundary::Inbound, false, &mut req);
+        assert!(!req.contains_key(&*L5D_DST_CANONICAL));
+
+        let mut rsp = all_headers();
+        strip_response(Boundary::Outbound, true, &mut rsp);
+        assert!(!rsp.contains_key(&*L5D_SERVER_ID));
+        assert!(!rsp.contains_key(&*L5D_REMOTE_IP));
+    }
+
+    #[test]
+    fn strips_consumed_headers_on_egress() {
+        let mut req = all_headers();
+        strip_request(Boundary::Outbound, true, &mut req);
+        assert!(!req.contains_key(&*L5D_DST_OVERRIDE));
+        assert!(!req.contains_key(&*L5D_REQUIRE_ID));
+        assert!(!req.contains_key(&*L5D_NO_UPGRADE));
+        assert!(!req.contains_key(&*L5D_CLIENT_ID));
+        assert!(req.contains_key(&*L5D_DST_CANONICAL));
+        assert!(req.contains_key(&*L5D_ORIG_PROTO));
+
+        let mut rsp = all_headers();
+        strip_response(Boundary::Inbound, true, &mut rsp);
+        assert!(!rsp.contains_key(&*L5D_SERVER_ID));
+        assert!(rsp.contains_key(&*L5D_ORIG_PROTO));
+    }
+}
@@ -0,0 +1,211 @@
+//! A layer that strips registered headers as messages cross a proxy boundary.
+
+use super::{strip_request, strip_response, Boundary};
+use crate::svc;
+use futures::{try_ready, Future, Poll};
+use http::{Request, Response};
+
+/// Strips registered headers from requests and responses at `boundary`.
+///
+/// `is_trusted` determines whether the target's peer has a verified identity.
+pub fn layer<T>(boundary: Boundary, is_trusted: fn(&T) -> bool) -> Layer<T> {
+    Layer {
+        boundary,
+        is_trusted,
+    }
+}
+
+pub struct Layer<T> {
+    boundary: Boundary,
+    is_trusted: fn(&T) -> bool,
+}
+
+pub struct Stack<T, M> {
+    boundary: Boundary,
+    is_trusted: fn(&T) -> bool,
+    inner: M,
+}
+
+pub struct MakeFuture<F> {
+    boundary: Boundary,
+    trusted: bool,
+    inner: F,
+}
+
+#[derive(Clone, Debug)]
+pub struct Service<S> {
+    boundary: Boundary,
+    trusted: bool,
+    inner: S,
+}
+
+pub struct ResponseFuture<F> {
+    boundary: Boundary,
+    trusted: bool,
+    inner: F,
+}
+
+// === impl Layer ===
+
+impl<T> Clone for Layer<T> {
+    fn clone(&self) -> Self {
+        Self {
+            boundary: self.boundary,
+            is_trusted: self.is_trusted,
+        }
+    }
+}
+
+impl<T, M> svc::Layer<M> for Layer<T> {
+    type Service = Stack<T, M>;
+
+    fn layer(&self, inner: M) -> Self::Service {
+        Stack {
+            boundary: self.boundary,
+            is_trusted: self.is_trusted,
+            inner,
+        }
+    }
+}
+
+// === impl Stack ===
+
+impl<T, M: Clone> Clone for Stack<T, M> {
+    fn clone(&self) -> Self {
+        Self {
+            boundary: self.boundary,
+            is_trusted: self.is_trusted,
+            inner: self.inner.clone(),
+        }
+    }
+}
+
+impl<T, M> svc::Service<T> for Stack<T, M>
+where
+    M: svc::Service<T>,
+{
+    type Response = Service<M::Response>;
+    type Error = M::Error;
+    type Future = MakeFuture<M::Future>;
+
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        self.inner.poll_ready()
+    }
+
+    fn call(&mut self, target: T) -> Self::Future {
+        let trusted = (self.is_trusted)(&target);
+        MakeFuture {
+            boundary: self.boundary,
+            trusted,
+            inner: self.inner.call(target),
+        }
+    }
+}
+
+// === impl MakeFuture ===
+
+impl<F: Future> Future for MakeFuture<F> {
+    type Item = Service<F::Item>;
+    type Error = F::Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        let inner = try_ready!(self.inner.poll());
+        Ok(Service {
+            boundary: self.boundary,
+            trusted: self.trusted,
+            inner,
+        }
+        .into())
+    }
+}
+
+// === impl Service ===
+
+impl<S, A, B> svc::Service<Request<A>> for Service<S>
+where
+    S: svc::Service<Request<A>, Response = Response<B>>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = ResponseFuture<S::Future>;
+
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        self.inner.poll_ready()
+    }
+
+    fn call(&mut self, mut req: Request<A>) -> Self::Future {
+        strip_request(self.boundary, self.trusted, req.headers_mut());
+        ResponseFuture {
+            boundary: self.boundary,
+            trusted: self.trusted,
+            inner: self.inner.call(req),
+        }
+    }
+}
+
+// === impl ResponseFuture ===
+
+impl<F, B> Future for ResponseFuture<F>
+where
+    F: Future<Item = Response<B>>,
+{
+    type Item = F::Item;
+    type Error = F::Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        let mut rsp = try_ready!(self.inner.poll());
+        strip_response(self.boundary, self.trusted, rsp.headers_mut());
+        Ok(rsp.into())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::headers::{L5D_CLIENT_ID, L5D_DST_CANONICAL, L5D_SERVER_ID};
+    use crate::svc::ServiceExt;
+    use futures::future;
+
+    #[test]
+    fn strips_at_inbound_boundary() {
+        let inner = svc::mk(|req: Request<()>| {
+            assert!(!req.headers().contains_key(&*L5D_CLIENT_ID));
+            assert!(req.headers().contains_key(&*L5D_DST_CANONICAL));
+            let rsp = Response::builder()
+                .header(&*L5D_SERVER_ID, "foo.ns.serviceaccount.identity.linkerd")
+                .body(())
+                .unwrap();
+            future::ok::<_, ()>(rsp)
+        });
+        let make = svc::mk(move |_: bool| future::ok::<_, ()>(inner.clone()));
+        let mut stack = svc::Layer::layer(&layer(Boundary::Inbound, |t: &bool| *t), make);
+
+        let svc = svc::Service::call(&mut stack, true).wait().unwrap();
+        let req = Request::builder()
+            .header(&*L5D_CLIENT_ID, "bar.ns.serviceaccount.identity.linkerd")
+            .header(&*L5D_DST_CANONICAL, "web.ns.svc.cluster.local:8080")
+            .body(())
+            .unwrap();
+        let rsp = svc.oneshot(req).wait().unwrap();
+        assert!(!rsp.headers().contains_key(&*L5D_SERVER_ID));
+    }
+
+    #[test]
+    fn strips_canonical_dst_from_untrusted_inbound_peers() {
+        let inner = svc::mk(|req: Request<()>| {
+            assert!(!req.headers().contains_key(&*L5D_CLIENT_ID));
+            assert!(!req.headers().contains_key(&*L5D_DST_CANONICAL));
+            future::ok::<_, ()>(Response::new(()))
+        });
+        let make = svc::mk(move |_: bool| future::ok::<_, ()>(inner.clone()));
+        let mut stack = svc::Layer::layer(&layer(Boundary::Inbound, |t: &bool| *t), make);
+
+        let svc = svc::Service::call(&mut stack, false).wait().unwrap();
+        let req = Request::builder()
+            .header(&*L5D_CLIENT_ID, "bar.ns.serviceaccount.identity.linkerd")
+            .header(&*L5D_DST_CANONICAL, "web.ns.svc.cluster.local:8080")
+            .body(())
+            .unwrap();
+        svc.oneshot(req).wait().unwrap();
+    }
+}
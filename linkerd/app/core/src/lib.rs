@@ -29,7 +29,9 @@ pub mod control;
 pub mod dns;
 pub mod dst;
 pub mod errors;
+pub mod freeze;
 pub mod handle_time;
+pub mod headers;
 pub mod metric_labels;
 pub mod profiles;
 pub mod proxy;
@@ -40,19 +42,53 @@ pub mod telemetry;
 pub mod trace;
 pub mod transport;
 
-pub const CANONICAL_DST_HEADER: &'static str = "l5d-dst-canonical";
-pub const DST_OVERRIDE_HEADER: &'static str = "l5d-dst-override";
-pub const L5D_REMOTE_IP: &'static str = "l5d-remote-ip";
-pub const L5D_SERVER_ID: &'static str = "l5d-server-id";
-pub const L5D_CLIENT_ID: &'static str = "l5d-client-id";
-pub const L5D_REQUIRE_ID: &'static str = "l5d-require-id";
-
 const DEFAULT_PORT: u16 = 80;
 
+/// Parses the `l5d-dst-override` header as an `Addr`.
+///
+/// If the override does not include a port, HTTP/1 requests default to port
+/// 80 while all other requests default to the port of the original
+/// destination.
 pub fn http_request_l5d_override_dst_addr<B>(req: &http::Request<B>) -> Result<Addr, addr::Error> {
-    proxy::http::authority_from_header(req, DST_OVERRIDE_HEADER)
+    proxy::http::authority_from_header(req, &*headers::L5D_DST_OVERRIDE)
         .ok_or(addr::Error::InvalidHost)
-        .and_then(|a| Addr::from_authority_and_default_port(&a, DEFAULT_PORT))
+        .and_then(|a| Addr::from_authority_and_default_port(&a, override_default_port(req)))
+}
+
+fn override_default_port<B>(req: &http::Request<B>) -> u16 {
+    use crate::transport::tls;
+
+    match proxy::http::Settings::from_request(req) {
+        proxy::http::Settings::Http1 { .. } => DEFAULT_PORT,
+        _ => req
+            .extensions()
+            .get::<tls::accept::Meta>()
+            .map(|m| m.addrs.target_addr().port())
+            .unwrap_or(DEFAULT_PORT),
+    }
+}
+
+/// Rejects requests that carry an `l5d-dst-override` header that cannot be
+/// parsed as an address, rather than routing them by another header.
+#[derive(Copy, Clone, Debug)]
+pub struct RejectInvalidDstOverride;
+
+impl<B> request_filter::RequestFilter<http::Request<B>> for RejectInvalidDstOverride {
+    type Error = errors::StatusError;
+
+    fn filter(&self, req: http::Request<B>) -> Result<http::Request<B>, Self::Error> {
+        if !req.headers().contains_key(&*headers::L5D_DST_OVERRIDE) {
+            return Ok(req);
+        }
+
+        match http_request_l5d_override_dst_addr(&req) {
+            Ok(_) => Ok(req),
+            Err(e) => Err(errors::StatusError {
+                status: http::StatusCode::BAD_REQUEST,
+                message: format!("invalid {} header: {:?}", *headers::L5D_DST_OVERRIDE, e),
+            }),
+        }
+    }
 }
 
 pub fn http_request_authority_addr<B>(req: &http::Request<B>) -> Result<Addr, addr::Error> {
@@ -70,6 +106,37 @@ pub fn http_request_host_addr<B>(req: &http::Request<B>) -> Result<Addr, addr::E
         .and_then(|a| Addr::from_authority_and_default_port(&a, DEFAULT_PORT))
 }
 
+/// Parses the request's URI authority or, failing that, its `Host` header as
+/// an `Addr`.
+///
+/// If the authority does not include a port, the port of the original
+/// destination is used, so that the logical destination agrees with the
+/// fallback endpoint. Requests without an original destination default to port
+/// 80, as do requests whose original destination is a loopback address, since
+/// loopback traffic is never redirected to the proxy and so does not name a
+/// service's port.
+pub fn http_request_addr_with_default_port<B>(req: &http::Request<B>) -> Result<Addr, addr::Error> {
+    use crate::proxy::http::h1;
+
+    let default_port = orig_dst_port(req).unwrap_or(DEFAULT_PORT);
+    req.uri()
+        .authority_part()
+        .cloned()
+        .or_else(|| h1::authority_from_host(req))
+        .ok_or(addr::Error::InvalidHost)
+        .and_then(|a| Addr::from_authority_and_default_port(&a, default_port))
+}
+
+fn orig_dst_port<B>(req: &http::Request<B>) -> Option<u16> {
+    use crate::transport::tls;
+
+    req.extensions()
+        .get::<tls::accept::Meta>()
+        .and_then(|m| m.addrs.target_addr_if_not_local())
+        .filter(|a| !a.ip().is_loopback())
+        .map(|a| a.port())
+}
+
 pub fn http_request_orig_dst_addr<B>(req: &http::Request<B>) -> Result<Addr, addr::Error> {
     use crate::transport::tls;
 
@@ -108,5 +175,204 @@ pub struct ProxyMetrics {
     pub http_route: HttpRouteMetricsRegistry,
     pub http_route_retry: HttpRouteMetricsRegistry,
     pub http_endpoint: HttpEndpointMetricsRegistry,
+    pub http_split: proxy::http::profiles::metrics::Registry,
+    pub http_response_validation: proxy::http::validate_response::Registry,
     pub transport: transport::MetricsRegistry,
+    /// Bounds the number of metadata labels included in endpoint metrics.
+    pub label_cap: metric_labels::CardinalityCap,
+    /// Records the size, evictions, and lookups of router caches.
+    pub router_cache: router::metrics::Registry,
+    /// Records the queue depth and wait times of buffers.
+    pub buffers: proxy::buffer::Registry,
+    /// Counts the transitions of services that are ejected by failure
+    /// accrual.
+    pub failure_accrual: proxy::failure_accrual::Metrics,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transport::{listen::Addrs, tls};
+    use std::net::SocketAddr;
+
+    fn override_req(version: http::Version, value: &str) -> http::Request<()> {
+        let orig_dst: SocketAddr = "10.1.1.1:8080".parse().unwrap();
+        let meta = tls::accept::Meta {
+            peer_identity: Conditional::None(tls::ReasonForNoPeerName::Loopback.into()),
+            addrs: Addrs::new(
+                "127.0.0.1:4140".parse().unwrap(),
+                "10.2.2.2:33333".parse().unwrap(),
+                Some(orig_dst),
+            ),
+        };
+        let mut req = http::Request::builder()
+            .version(version)
+            .uri("http://example.com/")
+            .header(&*headers::L5D_DST_OVERRIDE, value)
+            .body(())
+            .unwrap();
+        req.extensions_mut().insert(meta);
+        req
+    }
+
+    #[test]
+    fn override_name_without_port() {
+        let h1 = override_req(http::Version::HTTP_11, "web.ns.svc.cluster.local");
+        assert_eq!(
+            http_request_l5d_override_dst_addr(&h1).unwrap().to_string(),
+            "web.ns.svc.cluster.local:80"
+        );
+
+        let h2 = override_req(http::Version::HTTP_2, "web.ns.svc.cluster.local");
+        assert_eq!(
+            http_request_l5d_override_dst_addr(&h2).unwrap().to_string(),
+            "web.ns.svc.cluster.local:8080"
+        );
+    }
+
+    #[test]
+    fn override_name_with_port() {
+        let req = override_req(http::Version::HTTP_2, "web.ns.svc.cluster.local:9090");
+        assert_eq!(
+            http_request_l5d_override_dst_addr(&req)
+                .unwrap()
+                .to_string(),
+            "web.ns.svc.cluster.local:9090"
+        );
+    }
+
+    #[test]
+    fn override_ipv4() {
+        let req = override_req(http::Version::HTTP_11, "10.3.3.3");
+        assert_eq!(
+            http_request_l5d_override_dst_addr(&req).unwrap(),
+            Addr::Socket("10.3.3.3:80".parse().unwrap())
+        );
+    }
+
+    #[test]
+    fn override_ipv6_with_brackets() {
+        let req = override_req(http::Version::HTTP_11, "[::1]:9090");
+        assert_eq!(
+            http_request_l5d_override_dst_addr(&req).unwrap(),
+            Addr::Socket("[::1]:9090".parse().unwrap())
+        );
+
+        let req = override_req(http::Version::HTTP_2, "[fd00::1]");
+        assert_eq!(
+            http_request_l5d_override_dst_addr(&req).unwrap(),
+            Addr::Socket("[fd00::1]:8080".parse().unwrap())
+        );
+    }
+
+    fn host_req(uri: &str, host: Option<&str>) -> http::Request<()> {
+        let mut req = override_req(http::Version::HTTP_11, "");
+        req.headers_mut().remove(&*headers::L5D_DST_OVERRIDE);
+        *req.uri_mut() = uri.parse().unwrap();
+        if let Some(host) = host {
+            req.headers_mut()
+                .insert(http::header::HOST, host.parse().unwrap());
+        }
+        req
+    }
+
+    #[test]
+    fn addr_with_explicit_port() {
+        let req = host_req("/", Some("web.ns.svc.cluster.local:80"));
+        assert_eq!(
+            http_request_addr_with_default_port(&req)
+                .unwrap()
+                .to_string(),
+            "web.ns.svc.cluster.local:80"
+        );
+
+        let req = host_req("http://web.ns.svc.cluster.local:9090/", None);
+        assert_eq!(
+            http_request_addr_with_default_port(&req)
+                .unwrap()
+                .to_string(),
+            "web.ns.svc.cluster.local:9090"
+        );
+    }
+
+    #[test]
+    fn addr_with_defaulted_port() {
+        let req = host_req("/", Some("web.ns.svc.cluster.local"));
+        assert_eq!(
+            http_request_addr_with_default_port(&req)
+                .unwrap()
+                .to_string(),
+            "web.ns.svc.cluster.local:8080"
+        );
+
+        let req = host_req("http://web.ns.svc.cluster.local/", None);
+        assert_eq!(
+            http_request_addr_with_default_port(&req)
+                .unwrap()
+                .to_string(),
+            "web.ns.svc.cluster.local:8080"
+        );
+
+        // Without an original destination, the HTTP default port is used.
+        let mut req = host_req("/", Some("web.ns.svc.cluster.local"));
+        req.extensions_mut().remove::<tls::accept::Meta>();
+        assert_eq!(
+            http_request_addr_with_default_port(&req)
+                .unwrap()
+                .to_string(),
+            "web.ns.svc.cluster.local:80"
+        );
+
+        let mut req = host_req("/", Some("web.ns.svc.cluster.local"));
+        req.extensions_mut().insert(tls::accept::Meta {
+            peer_identity: Conditional::None(tls::ReasonForNoPeerName::Loopback.into()),
+            addrs: Addrs::new(
+                "127.0.0.1:4140".parse().unwrap(),
+                "127.0.0.1:33333".parse().unwrap(),
+                Some("127.0.0.1:8080".parse().unwrap()),
+            ),
+        });
+        assert_eq!(
+            http_request_addr_with_default_port(&req)
+                .unwrap()
+                .to_string(),
+            "web.ns.svc.cluster.local:80"
+        );
+    }
+
+    #[test]
+    fn addr_with_ip_literal() {
+        let req = host_req("/", Some("10.3.3.3"));
+        assert_eq!(
+            http_request_addr_with_default_port(&req).unwrap(),
+            Addr::Socket("10.3.3.3:8080".parse().unwrap())
+        );
+
+        let req = host_req("/", Some("[fd00::1]:9090"));
+        assert_eq!(
+            http_request_addr_with_default_port(&req).unwrap(),
+            Addr::Socket("[fd00::1]:9090".parse().unwrap())
+        );
+    }
+
+    #[test]
+    fn override_garbage_is_rejected() {
+        use request_filter::RequestFilter;
+
+        for value in &["", "not a name", "web!ns"] {
+            let req = override_req(http::Version::HTTP_11, value);
+            assert!(
+                http_request_l5d_override_dst_addr(&req).is_err(),
+                "{:?} must not parse",
+                value
+            );
+            let err = RejectInvalidDstOverride
+                .filter(req)
+                .expect_err("invalid override must be rejected");
+            assert_eq!(err.status, http::StatusCode::BAD_REQUEST);
+        }
+
+        let req = override_req(http::Version::HTTP_11, "web.ns.svc.cluster.local");
+        assert!(RejectInvalidDstOverride.filter(req).is_ok());
+    }
 }
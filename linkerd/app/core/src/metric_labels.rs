@@ -1,25 +1,52 @@
 use crate::proxy::identity;
 use crate::transport::{labels::TlsStatus, tls};
+use indexmap::IndexMap;
 use linkerd2_addr::{Addr, NameAddr};
 use linkerd2_conditional::Conditional;
-use linkerd2_metrics::FmtLabels;
+use linkerd2_metrics::{metrics, Counter, FmtLabels, FmtMetric, FmtMetrics};
 use std::fmt::{self, Write};
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex};
 
 use super::{classify, control, dst};
 
+/// The default number of metadata labels that are included in metrics.
+pub const DEFAULT_MAX_METRIC_LABELS: usize = 64;
+
+metrics! {
+    truncated_labels_total: Counter {
+        "Total count of label sets that were truncated to the maximum number of metric labels"
+    }
+}
+
+/// Bounds the number of labels that are formatted by `prefix_labels`, so
+/// that a misbehaving control plane cannot cause a label explosion.
+#[derive(Clone, Debug)]
+pub struct CardinalityCap {
+    max_labels: usize,
+    truncated: Arc<Mutex<Counter>>,
+}
+
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct ControlLabels {
     addr: Addr,
     tls_status: TlsStatus,
 }
 
-#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+#[derive(Clone, Debug, PartialEq, Eq)]
 pub struct EndpointLabels {
     pub direction: Direction,
     pub tls_id: Conditional<TlsId, tls::ReasonForNoIdentity>,
     pub dst_logical: Option<NameAddr>,
     pub dst_concrete: Option<NameAddr>,
     pub labels: Option<String>,
+    /// The labels of the route that a request matched, which are set from
+    /// the request's `dst::Route` extension. They are formatted with the
+    /// `rt_` prefix.
+    pub route_labels: Option<Arc<IndexMap<String, String>>>,
+    /// The namespace of the client that sent an inbound request, as encoded
+    /// in its identity.
+    pub src_namespace: Option<String>,
 }
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
@@ -88,6 +115,39 @@ impl FmtLabels for RouteLabels {
 
 // === impl EndpointLabels ===
 
+impl EndpointLabels {
+    /// Returns these labels with those of the request's route, if the
+    /// request has a labeled `dst::Route` extension.
+    ///
+    /// This may be used with `http::metrics::Layer::with_request_labels` to
+    /// break endpoint metrics down by route.
+    pub fn with_route(&self, extensions: &http::Extensions) -> Option<Self> {
+        let route = extensions.get::<dst::Route>()?;
+        if route.route_labels().is_empty() {
+            return None;
+        }
+
+        Some(Self {
+            route_labels: Some(route.route_labels().clone()),
+            ..self.clone()
+        })
+    }
+}
+
+impl Hash for EndpointLabels {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.direction.hash(state);
+        self.tls_id.hash(state);
+        self.dst_logical.hash(state);
+        self.dst_concrete.hash(state);
+        self.labels.hash(state);
+        // Route labels are compared without regard to their order, so only
+        // their number is hashed.
+        self.route_labels.as_ref().map(|l| l.len()).hash(state);
+        self.src_namespace.hash(state);
+    }
+}
+
 impl FmtLabels for EndpointLabels {
     fn fmt_labels(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         let authority = self.dst_logical.as_ref().map(Authority);
@@ -97,6 +157,14 @@ impl FmtLabels for EndpointLabels {
             write!(f, ",{}", labels)?;
         }
 
+        if let Some(labels) = self
+            .route_labels
+            .as_ref()
+            .and_then(|l| prefix_labels("rt", l.iter()))
+        {
+            write!(f, ",{}", labels)?;
+        }
+
         write!(f, ",")?;
         TlsStatus::from(self.tls_id.as_ref()).fmt_labels(f)?;
 
@@ -105,6 +173,10 @@ impl FmtLabels for EndpointLabels {
             id.fmt_labels(f)?;
         }
 
+        if let Some(ref ns) = self.src_namespace {
+            write!(f, ",src_namespace=\"{}\"", ns)?;
+        }
+
         Ok(())
     }
 }
@@ -174,6 +246,66 @@ impl FmtLabels for TlsId {
     }
 }
 
+// === impl CardinalityCap ===
+
+impl CardinalityCap {
+    pub fn new(max_labels: usize) -> Self {
+        Self {
+            max_labels,
+            truncated: Arc::new(Mutex::new(Counter::default())),
+        }
+    }
+
+    /// Like `prefix_labels`, but formats at most `max_labels` labels.
+    ///
+    /// When there are too many labels, those with the lowest keys are kept
+    /// so that the same labels are always formatted.
+    pub fn prefix_labels<'i, I>(&self, prefix: &str, labels_iter: I) -> Option<String>
+    where
+        I: Iterator<Item = (&'i String, &'i String)>,
+    {
+        let mut labels = labels_iter.collect::<Vec<_>>();
+        if labels.len() > self.max_labels {
+            labels.sort_by(|(a, _), (b, _)| a.cmp(b));
+            labels.truncate(self.max_labels);
+            if let Ok(mut truncated) = self.truncated.lock() {
+                truncated.incr();
+            }
+        }
+        prefix_labels(prefix, labels.into_iter())
+    }
+}
+
+impl Default for CardinalityCap {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_METRIC_LABELS)
+    }
+}
+
+impl FmtMetrics for CardinalityCap {
+    fn fmt_metrics(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let truncated = match self.truncated.lock() {
+            Err(_) => return Ok(()),
+            Ok(lock) => *lock,
+        };
+
+        truncated_labels_total.fmt_help(f)?;
+        truncated.fmt_metric(f, truncated_labels_total.name)
+    }
+}
+
+/// Extracts the namespace from a Linkerd identity, which has the form
+/// `<serviceaccount>.<namespace>.serviceaccount.identity.<control-ns>.<domain>`.
+pub fn extract_namespace(id: &identity::Name) -> Option<String> {
+    let mut labels = id.as_ref().split('.');
+    let _sa = labels.next()?;
+    let ns = labels.next()?;
+    if ns.is_empty() || labels.next() != Some("serviceaccount") {
+        return None;
+    }
+    Some(ns.to_owned())
+}
+
 pub fn prefix_labels<'i, I>(prefix: &str, mut labels_iter: I) -> Option<String>
 where
     I: Iterator<Item = (&'i String, &'i String)>,
@@ -186,3 +318,142 @@ where
     }
     Some(out)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn cardinality_cap_truncates_labels() {
+        let labels = (0..4)
+            .rev()
+            .map(|i| (format!("k{}", i), format!("v{}", i)))
+            .collect::<Vec<_>>();
+        let iter = || labels.iter().map(|(k, v)| (k, v));
+
+        let cap = CardinalityCap::new(4);
+        assert_eq!(
+            cap.prefix_labels("dst", iter()).unwrap(),
+            "dst_k3=\"v3\",dst_k2=\"v2\",dst_k1=\"v1\",dst_k0=\"v0\""
+        );
+        assert_eq!(*cap.truncated.lock().unwrap(), Counter::default());
+
+        let cap = CardinalityCap::new(2);
+        assert_eq!(
+            cap.prefix_labels("dst", iter()).unwrap(),
+            "dst_k0=\"v0\",dst_k1=\"v1\""
+        );
+        assert_eq!(cap.truncated.lock().unwrap().value(), 1);
+    }
+
+    #[test]
+    fn extracts_namespace_from_identity() {
+        let id = |s: &str| identity::Name::from_hostname(s.as_bytes()).unwrap();
+        assert_eq!(
+            extract_namespace(&id(
+                "web.emojivoto.serviceaccount.identity.linkerd.cluster.local"
+            )),
+            Some("emojivoto".to_owned())
+        );
+        assert_eq!(
+            extract_namespace(&id("web.emojivoto.svc.cluster.local")),
+            None
+        );
+        assert_eq!(extract_namespace(&id("localhost")), None);
+    }
+
+    #[test]
+    fn endpoint_labels_include_src_namespace() {
+        let id = identity::Name::from_hostname(
+            b"web.emojivoto.serviceaccount.identity.linkerd.cluster.local",
+        )
+        .unwrap();
+        let labels = EndpointLabels {
+            direction: Direction::In,
+            tls_id: Conditional::Some(TlsId::ClientId(id.clone())),
+            dst_logical: None,
+            dst_concrete: None,
+            labels: None,
+            route_labels: None,
+            src_namespace: extract_namespace(&id),
+        };
+        let out = format!("{}", Fmt(&labels));
+        assert!(
+            out.ends_with(",src_namespace=\"emojivoto\""),
+            "unexpected labels: {}",
+            out
+        );
+    }
+
+    #[test]
+    fn endpoint_metrics_include_route_labels() {
+        use futures::{future, Future};
+        use linkerd2_proxy_http::{metrics as http_metrics, profiles, settings};
+        use profiles::WithRoute;
+        use std::str::FromStr;
+        use std::time::Duration;
+        use tower::{layer::Layer, Service};
+
+        let (registry, report) =
+            http_metrics::new::<EndpointLabels, classify::Class>(Duration::from_secs(60));
+        let mut make = http_metrics::layer::<_, classify::Response>(registry)
+            .with_request_labels(EndpointLabels::with_route)
+            .layer(tower::service_fn(|_: EndpointLabels| {
+                future::ok::<_, crate::Error>(tower::service_fn(|_: http::Request<_>| {
+                    future::ok::<_, crate::Error>(http::Response::new(hyper::Body::empty()))
+                }))
+            }));
+
+        let labels = EndpointLabels {
+            direction: Direction::Out,
+            tls_id: Conditional::None(tls::ReasonForNoIdentity::Disabled),
+            dst_logical: None,
+            dst_concrete: None,
+            labels: None,
+            route_labels: None,
+            src_namespace: None,
+        };
+        let mut svc = make.call(labels).wait().unwrap();
+
+        let route = dst::DstAddr::outbound(
+            Addr::from_str("web.ns.svc.cluster.local:80").unwrap(),
+            settings::Settings::Http2,
+        )
+        .with_route(profiles::Route::new(
+            vec![("route".to_owned(), "header-match".to_owned())].into_iter(),
+            Vec::new(),
+        ));
+        let mut req = http::Request::new(hyper::Body::empty());
+        req.extensions_mut().insert(route);
+        drop(svc.call(req).wait().unwrap());
+        let req = http::Request::new(hyper::Body::empty());
+        drop(svc.call(req).wait().unwrap());
+
+        let out = report.as_display().to_string();
+        let totals = out
+            .lines()
+            .filter(|l| l.starts_with("request_total{"))
+            .collect::<Vec<_>>();
+        assert_eq!(totals.len(), 2, "unexpected metrics: {}", out);
+        assert!(
+            totals
+                .iter()
+                .any(|l| l.contains(",rt_route=\"header-match\"")),
+            "missing route labels: {}",
+            out
+        );
+        assert!(
+            totals.iter().any(|l| !l.contains("rt_")),
+            "missing endpoint labels: {}",
+            out
+        );
+    }
+
+    struct Fmt<'a>(&'a EndpointLabels);
+
+    impl fmt::Display for Fmt<'_> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            self.0.fmt_labels(f)
+        }
+    }
+}
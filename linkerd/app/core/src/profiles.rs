@@ -1,4 +1,5 @@
 use crate::dns;
+use crate::freeze;
 use crate::proxy::http::{profiles, retry::Budget};
 use futures::{Async, Future, Poll, Stream};
 use http;
@@ -6,7 +7,9 @@ use linkerd2_addr::NameAddr;
 use linkerd2_error::Never;
 use linkerd2_proxy_api::destination as api;
 use regex::Regex;
-use std::sync::Arc;
+use std::collections::HashMap;
+use std::convert::TryFrom;
+use std::sync::{Arc, Mutex, Weak};
 use std::time::Duration;
 use tokio::sync::{oneshot, watch};
 use tokio_timer::{clock, Delay};
@@ -20,11 +23,21 @@ pub struct Client<T> {
     backoff: Duration,
     context_token: String,
     suffixes: Vec<dns::Suffix>,
+    watches: Arc<Mutex<HashMap<dns::Name, Watch>>>,
+    freeze: freeze::Registry,
+    consistent_hash: Option<http::header::HeaderName>,
 }
 
 pub struct Rx {
+    rx: freeze::Stream<watch::Receiver<profiles::Routes>>,
+    _hangup: Arc<oneshot::Sender<Never>>,
+}
+
+/// A profile watch that may be shared while any of its `Rx`s are held.
+#[derive(Debug)]
+struct Watch {
     rx: watch::Receiver<profiles::Routes>,
-    _hangup: oneshot::Sender<Never>,
+    hangup: Weak<oneshot::Sender<Never>>,
 }
 
 struct Daemon<T>
@@ -37,6 +50,7 @@ where
     tx: watch::Sender<profiles::Routes>,
     hangup: oneshot::Receiver<Never>,
     request: api::GetDestination,
+    consistent_hash: Option<http::header::HeaderName>,
 }
 
 enum State<T>
@@ -72,6 +86,24 @@ where
             backoff,
             context_token,
             suffixes: suffixes.into_iter().collect(),
+            watches: Arc::new(Mutex::new(HashMap::new())),
+            freeze: freeze::Registry::default(),
+            consistent_hash: None,
+        }
+    }
+
+    /// Holds route updates for destinations that are frozen in `freeze`.
+    pub fn with_freeze(self, freeze: freeze::Registry) -> Self {
+        Self { freeze, ..self }
+    }
+
+    /// Selects each profile's dst_overrides by consistently hashing the value
+    /// of `header`, so that requests carrying the same value are routed to the
+    /// same backend.
+    pub fn with_consistent_hash(self, header: Option<http::header::HeaderName>) -> Self {
+        Self {
+            consistent_hash: header,
+            ..self
         }
     }
 }
@@ -90,12 +122,32 @@ where
             debug!("name not in profile suffixes");
             return None;
         }
-        debug!("watching routes");
 
-        // This oneshot allows the daemon to be notified when the Self::Stream
-        // is dropped.
+        // Profiles are configured per-name, so targets that differ only by
+        // port share a single watch.
+        let key = profile_key(dst);
+        // The watches are consistent between operations, so a poisoned lock
+        // is recovered rather than failing every later lookup.
+        let mut watches = self.watches.lock().unwrap_or_else(|e| e.into_inner());
+        if let Some(watch) = watches.get(&key) {
+            if let Some(hangup) = watch.hangup.upgrade() {
+                debug!(%key, "sharing routes watch");
+                return Some(Rx {
+                    rx: self.freeze.stream(dst.clone(), watch.rx.clone()),
+                    _hangup: hangup,
+                });
+            }
+        }
+        watches.retain(|_, w| w.hangup.upgrade().is_some());
+        debug!(%key, "watching routes");
+
+        // This oneshot allows the daemon to be notified when every
+        // Self::Stream for this key is dropped.
         let (hangup_tx, hangup_rx) = oneshot::channel();
-        let (tx, rx) = watch::channel(profiles::Routes::default());
+        let (tx, rx) = watch::channel(profiles::Routes {
+            consistent_hash: self.consistent_hash.clone(),
+            ..profiles::Routes::default()
+        });
         let daemon = Daemon {
             tx,
             hangup: hangup_rx,
@@ -107,16 +159,36 @@ where
                 context_token: self.context_token.clone(),
                 ..Default::default()
             },
+            consistent_hash: self.consistent_hash.clone(),
         };
 
         tokio::spawn(daemon.in_current_span().map_err(|never| match never {}));
+
+        let hangup_tx = Arc::new(hangup_tx);
+        watches.insert(
+            key,
+            Watch {
+                rx: rx.clone(),
+                hangup: Arc::downgrade(&hangup_tx),
+            },
+        );
         Some(Rx {
-            rx,
+            rx: self.freeze.stream(dst.clone(), rx),
             _hangup: hangup_tx,
         })
     }
 }
 
+/// Normalizes a destination to the key used to share its profile watch.
+///
+/// The port is dropped from the key but retained on the target, which is
+/// used for the lookup that starts the watch. Only named destinations have
+/// profiles, so IP-addressed targets never get here and keep their ports.
+fn profile_key(dst: &NameAddr) -> dns::Name {
+    dns::Name::try_from(dst.name().without_trailing_dot().as_bytes())
+        .unwrap_or_else(|_| dst.name().clone())
+}
+
 // === impl Rx ===
 
 impl Stream for Rx {
@@ -143,6 +215,7 @@ where
         rx: &mut grpc::Streaming<api::DestinationProfile, T::ResponseBody>,
         tx: &mut watch::Sender<profiles::Routes>,
         hangup: &mut oneshot::Receiver<Never>,
+        consistent_hash: &Option<http::header::HeaderName>,
     ) -> Async<StreamState> {
         loop {
             match rx.poll() {
@@ -176,6 +249,7 @@ where
                     let profile = profiles::Routes {
                         routes,
                         dst_overrides,
+                        consistent_hash: consistent_hash.clone(),
                     };
                     if tx.broadcast(profile).is_err() {
                         return StreamState::SendLost.into();
@@ -227,7 +301,12 @@ where
                     }
                 },
                 State::Streaming(ref mut s) => {
-                    match Self::proxy_stream(s, &mut self.tx, &mut self.hangup) {
+                    match Self::proxy_stream(
+                        s,
+                        &mut self.tx,
+                        &mut self.hangup,
+                        &self.consistent_hash,
+                    ) {
                         Async::NotReady => return Ok(Async::NotReady),
                         Async::Ready(StreamState::SendLost) => return Ok(().into()),
                         Async::Ready(StreamState::RecvDone) => {
@@ -441,4 +520,15 @@ mod tests {
             true
         }
     }
+
+    #[test]
+    fn profile_key_ignores_port_and_trailing_dot() {
+        let key = |s: &str| profile_key(&NameAddr::from_str(s).unwrap());
+
+        let web = key("web.ns.svc.cluster.local:8080");
+        assert_eq!(web, key("web.ns.svc.cluster.local.:8080"));
+        assert_eq!(web, key("web.ns.svc.cluster.local:80"));
+        assert_ne!(web, key("api.ns.svc.cluster.local:8080"));
+        assert_eq!(web.to_string(), "web.ns.svc.cluster.local");
+    }
 }
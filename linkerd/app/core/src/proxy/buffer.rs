@@ -1,6 +1,10 @@
 use crate::svc;
 use futures::{try_ready, Async, Future, Poll};
 use linkerd2_error::Error;
+use linkerd2_metrics::{
+    latency, metrics, Counter, FmtLabels, FmtMetric, FmtMetrics, Gauge, Histogram, NamedMetrics,
+    NamedRegistry,
+};
 use linkerd2_router as rt;
 use std::marker::PhantomData;
 use std::sync::{Arc, Mutex, Weak};
@@ -10,16 +14,54 @@ use tokio_timer::{clock, Delay};
 use tower::buffer;
 use tracing_futures::Instrument;
 
+metrics! {
+    buffer_queue_depth: Gauge { "The number of requests waiting in a buffer" },
+    buffer_wait_seconds: Histogram<latency::Ms, latency::MillisAsSeconds> {
+        "A histogram of the time in seconds that requests wait in a buffer before they are dispatched"
+    },
+    buffer_deadline_exceeded_total: Counter {
+        "Total count of requests that failed because they could not be dispatched before their deadline"
+    }
+}
+
 /// Determines the dispatch deadline for a request.
 pub trait Deadline<Req>: Clone {
     fn deadline(&self, req: &Req) -> Option<Instant>;
 }
 
+/// Holds the metrics of all named buffers.
+#[derive(Clone, Debug, Default)]
+pub struct Registry(NamedRegistry<BufferMetrics>);
+
+/// Records the depth of a buffer's queue and the time that requests wait in
+/// it.
+///
+/// Buffers created with the same name share metrics, so their metrics are
+/// aggregated.
+#[derive(Clone, Debug, Default)]
+pub struct Metrics(NamedMetrics<BufferMetrics>);
+
+#[derive(Clone, Debug)]
+struct BufferMetrics {
+    depth: Gauge,
+    wait: Histogram<latency::Ms, latency::MillisAsSeconds>,
+    deadline_exceeded: Counter,
+}
+
+/// Tracks a request while it waits in a buffer.
+struct Queued {
+    since: Instant,
+    metrics: Metrics,
+}
+
+struct BufferLabel(&'static str);
+
 /// Produces `MakeService`s where the output `Service` is wrapped with a `Buffer`
 #[derive(Debug)]
 pub struct Layer<D, Req> {
     capacity: usize,
     deadline: D,
+    metrics: Metrics,
     _marker: PhantomData<fn(Req)>,
 }
 
@@ -28,12 +70,13 @@ pub struct Layer<D, Req> {
 pub struct Make<M, D, Req> {
     capacity: usize,
     deadline: D,
+    metrics: Metrics,
     inner: M,
     _marker: PhantomData<fn(Req)>,
 }
 
-type Holder<Req> = Arc<Mutex<Option<Req>>>;
-type Stealer<Req> = Weak<Mutex<Option<Req>>>;
+type Holder<Req> = Arc<Mutex<Option<(Req, Queued)>>>;
+type Stealer<Req> = Weak<Mutex<Option<(Req, Queued)>>>;
 
 pub struct Enqueue<S, D, Req>
 where
@@ -41,6 +84,7 @@ where
     S::Error: Into<Error>,
 {
     deadline: D,
+    metrics: Metrics,
     inner: buffer::Buffer<Dequeue<S>, Stealer<Req>>,
 }
 
@@ -63,6 +107,7 @@ pub struct Aborted;
 pub struct MakeFuture<F, D, Req> {
     capacity: usize,
     deadline: D,
+    metrics: Metrics,
     inner: F,
     _marker: PhantomData<fn(Req)>,
 }
@@ -77,15 +122,24 @@ where
     Layer {
         capacity,
         deadline,
+        metrics: Metrics::default(),
         _marker: PhantomData,
     }
 }
 
+impl<D, Req> Layer<D, Req> {
+    /// Records the metrics of the buffers that are built by this layer.
+    pub fn with_metrics(self, metrics: Metrics) -> Self {
+        Self { metrics, ..self }
+    }
+}
+
 impl<D: Clone, Req> Clone for Layer<D, Req> {
     fn clone(&self) -> Self {
         Self {
             capacity: self.capacity,
             deadline: self.deadline.clone(),
+            metrics: self.metrics.clone(),
             _marker: PhantomData,
         }
     }
@@ -101,6 +155,7 @@ where
         Self::Service {
             capacity: self.capacity,
             deadline: self.deadline.clone(),
+            metrics: self.metrics.clone(),
             inner,
             _marker: PhantomData,
         }
@@ -114,6 +169,7 @@ impl<M: Clone, D: Clone, Req> Clone for Make<M, D, Req> {
         Self {
             capacity: self.capacity,
             deadline: self.deadline.clone(),
+            metrics: self.metrics.clone(),
             inner: self.inner.clone(),
             _marker: PhantomData,
         }
@@ -145,6 +201,7 @@ where
         Self::Future {
             capacity: self.capacity,
             deadline: self.deadline.clone(),
+            metrics: self.metrics.clone(),
             inner,
             _marker: PhantomData,
         }
@@ -169,6 +226,7 @@ where
             self.deadline.clone(),
             self.capacity,
         )
+        .with_metrics(self.metrics.clone())
     }
 }
 
@@ -189,6 +247,7 @@ impl<M, D, Req> Make<M, D, Req> {
             self.deadline.clone(),
             self.capacity,
         )
+        .with_metrics(self.metrics.clone())
     }
 }
 
@@ -209,7 +268,8 @@ where
 
     fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
         let svc = try_ready!(self.inner.poll().map_err(Into::into));
-        let enq = Enqueue::new(svc, self.deadline.clone(), self.capacity);
+        let enq = Enqueue::new(svc, self.deadline.clone(), self.capacity)
+            .with_metrics(self.metrics.clone());
         Ok(enq.into())
     }
 }
@@ -230,7 +290,17 @@ where
     pub fn new(svc: S, deadline: D, capacity: usize) -> Self {
         let mut exec = tokio::executor::DefaultExecutor::current().in_current_span();
         let inner = buffer::Buffer::with_executor(Dequeue(svc), capacity, &mut exec);
-        Self { deadline, inner }
+        Self {
+            deadline,
+            inner,
+            metrics: Metrics::default(),
+        }
+    }
+
+    /// Records the depth of the buffer's queue and the time that requests
+    /// wait in it.
+    pub fn with_metrics(self, metrics: Metrics) -> Self {
+        Self { metrics, ..self }
     }
 }
 
@@ -252,7 +322,8 @@ where
 
     fn call(&mut self, req: Req) -> Self::Future {
         let timeout = self.deadline.deadline(&req).map(Delay::new);
-        let holder = Arc::new(Mutex::new(Some(req)));
+        let queued = Queued::new(self.metrics.clone());
+        let holder = Arc::new(Mutex::new(Some((req, queued))));
         let stealer = Arc::downgrade(&holder);
 
         EnqueueFuture {
@@ -272,6 +343,7 @@ where
     fn clone(&self) -> Self {
         Self {
             deadline: self.deadline.clone(),
+            metrics: self.metrics.clone(),
             inner: self.inner.clone(),
         }
     }
@@ -298,7 +370,9 @@ where
         if h.is_some() {
             if let Some(t) = self.timeout.as_mut() {
                 if t.poll().map_err(Error::from)?.is_ready() {
-                    drop(h.take());
+                    if let Some((_, queued)) = h.take() {
+                        queued.deadline_exceeded();
+                    }
                     return Err(Aborted.into());
                 }
             }
@@ -329,7 +403,10 @@ where
     fn call(&mut self, req: Stealer<Req>) -> Self::Future {
         req.upgrade()
             .and_then(|l| l.lock().ok()?.take())
-            .map(|req| DequeueFuture::Inner(self.0.call(req)))
+            .map(|(req, queued)| {
+                queued.dispatched();
+                DequeueFuture::Inner(self.0.call(req))
+            })
             .unwrap_or(DequeueFuture::Lost)
     }
 }
@@ -352,6 +429,101 @@ where
     }
 }
 
+// === impl Registry ===
+
+impl Registry {
+    /// Returns the metrics for the buffer named `name`, creating them if
+    /// necessary.
+    pub fn buffer(&self, name: &'static str) -> Metrics {
+        Metrics(self.0.get(name))
+    }
+}
+
+impl FmtMetrics for Registry {
+    fn fmt_metrics(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let buffers = self.0.snapshot();
+        if buffers.is_empty() {
+            return Ok(());
+        }
+
+        buffer_queue_depth.fmt_help(f)?;
+        for &(name, ref m) in &buffers {
+            m.depth
+                .fmt_metric_labeled(f, buffer_queue_depth.name, BufferLabel(name))?;
+        }
+
+        buffer_wait_seconds.fmt_help(f)?;
+        for &(name, ref m) in &buffers {
+            m.wait
+                .fmt_metric_labeled(f, buffer_wait_seconds.name, BufferLabel(name))?;
+        }
+
+        buffer_deadline_exceeded_total.fmt_help(f)?;
+        for &(name, ref m) in &buffers {
+            m.deadline_exceeded.fmt_metric_labeled(
+                f,
+                buffer_deadline_exceeded_total.name,
+                BufferLabel(name),
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+// === impl Metrics ===
+
+impl Metrics {
+    fn update(&self, f: impl FnOnce(&mut BufferMetrics)) {
+        self.0.update(f)
+    }
+}
+
+impl Default for BufferMetrics {
+    fn default() -> Self {
+        Self {
+            depth: Gauge::default(),
+            wait: Histogram::new(latency::BOUNDS),
+            deadline_exceeded: Counter::default(),
+        }
+    }
+}
+
+// === impl Queued ===
+
+impl Queued {
+    fn new(metrics: Metrics) -> Self {
+        metrics.update(|m| m.depth.incr());
+        Self {
+            since: clock::now(),
+            metrics,
+        }
+    }
+
+    /// Records how long the request waited before it was dispatched.
+    fn dispatched(self) {
+        let wait = clock::now() - self.since;
+        self.metrics.update(|m| m.wait.add(wait));
+    }
+
+    /// Records that the request was not dispatched before its deadline.
+    fn deadline_exceeded(self) {
+        self.metrics.update(|m| m.deadline_exceeded.incr());
+    }
+}
+
+impl Drop for Queued {
+    fn drop(&mut self) {
+        self.metrics.update(|m| m.depth.decr());
+    }
+}
+
+impl FmtLabels for BufferLabel {
+    fn fmt_labels(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "buffer=\"{}\"", self.0)
+    }
+}
+
 // === Aborted ===
 
 impl fmt::Display for Aborted {
@@ -455,6 +627,68 @@ mod tests {
         }));
     }
 
+    /// Becomes ready once its delay elapses.
+    struct ReadyAfter(Delay);
+    impl svc::Service<()> for ReadyAfter {
+        type Response = ();
+        type Error = Error;
+        type Future = future::FutureResult<(), Error>;
+
+        fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+            self.0.poll().map_err(Into::into)
+        }
+
+        fn call(&mut self, _: ()) -> Self::Future {
+            future::ok(())
+        }
+    }
+
+    #[test]
+    fn wait_is_recorded_while_inner_service_is_not_ready() {
+        let registry = Registry::default();
+        let metrics = registry.buffer("test");
+
+        tokio::run(future::lazy(move || {
+            let inner = ReadyAfter(Delay::new(clock::now() + Duration::from_millis(100)));
+            let mut svc = Enqueue::new(inner, (), 1).with_metrics(metrics);
+
+            assert!(svc.poll_ready().ok().map(|r| r.is_ready()).unwrap_or(false));
+            svc.call(())
+                .map_err(|e| panic!("request must succeed: {}", e))
+                .map(move |()| drop(svc))
+        }));
+
+        let report = registry.as_display().to_string();
+        assert!(report.contains("buffer_queue_depth{buffer=\"test\"} 0\n"));
+        assert!(report.contains("buffer_wait_seconds_bucket{buffer=\"test\",le=\"0.05\"} 0\n"));
+        assert!(report.contains("buffer_wait_seconds_count{buffer=\"test\"} 1\n"));
+        assert!(report.contains("buffer_deadline_exceeded_total{buffer=\"test\"} 0\n"));
+    }
+
+    #[test]
+    fn deadline_failures_are_recorded() {
+        let registry = Registry::default();
+        let metrics = registry.buffer("test");
+
+        tokio::run(future::lazy(move || {
+            let mut svc = Enqueue::new(Idle(Arc::new(())), Duration::from_millis(10), 1)
+                .with_metrics(metrics);
+
+            assert!(svc.poll_ready().ok().map(|r| r.is_ready()).unwrap_or(false));
+            svc.call(()).then(|r| {
+                r.expect_err("request must fail")
+                    .downcast::<Aborted>()
+                    .expect("request must be aborted");
+                future::ok(())
+            })
+        }));
+
+        let report = registry.as_display().to_string();
+        assert!(report.contains("buffer_queue_depth{buffer=\"test\"} 0\n"));
+        assert!(report.contains("buffer_wait_seconds_count{buffer=\"test\"} 0\n"));
+        assert!(report.contains("buffer_deadline_exceeded_total{buffer=\"test\"} 1\n"));
+    }
+
     #[test]
     fn inner_service_dropped() {
         tokio::run(future::lazy(|| {
@@ -495,4 +729,44 @@ mod tests {
             })
         }));
     }
+
+    #[test]
+    fn request_aborted_when_per_make_limit_is_exhausted() {
+        use svc::{LayerExt, ServiceExt};
+        use tower::limit::concurrency::ConcurrencyLimitLayer;
+
+        tokio::run(future::lazy(|| {
+            // Responses never complete, so the first request holds the
+            // only permit.
+            let make = svc::mk(|_: &'static str| {
+                future::ok::<_, Error>(svc::mk(|_: ()| future::empty::<(), Error>()))
+            });
+            svc::stack(make)
+                .push(ConcurrencyLimitLayer::new(1).per_make())
+                .check_backpressure::<&'static str, ()>()
+                .push(layer::<_, ()>(10, Duration::from_millis(100)))
+                .into_inner()
+                .oneshot("target")
+                .map_err(|e| panic!("make failed: {}", e))
+                .and_then(|mut svc| {
+                    assert!(svc.poll_ready().expect("ready").is_ready());
+                    let first = svc.call(());
+                    assert!(svc.poll_ready().expect("ready").is_ready());
+                    let second = svc.call(());
+
+                    // The second request waits in the buffer, not in the
+                    // limit, so its dispatch deadline fires.
+                    second.then(move |r| {
+                        drop(first);
+                        match r {
+                            Ok(()) => panic!("unexpected response from limited service"),
+                            Err(e) => {
+                                e.downcast::<Aborted>().expect("request must be aborted");
+                                Ok(())
+                            }
+                        }
+                    })
+                })
+        }));
+    }
 }
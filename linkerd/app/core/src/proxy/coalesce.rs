@@ -0,0 +1,340 @@
+//! Coalesces concurrent requests that share a key into a single request.
+//!
+//! While a request is in flight, later requests with the same key are not
+//! dispatched to the inner service; instead, they share the in-flight
+//! request's response. Once that response is available, the key is forgotten
+//! so that subsequent requests are dispatched normally.
+//!
+//! This is only appropriate for idempotent requests whose responses are cheap
+//! to clone, e.g. name resolutions.
+
+use crate::svc;
+use futures::future::{self, Shared};
+use futures::{Async, Future, Poll};
+use linkerd2_error::Error;
+use std::collections::HashMap;
+use std::hash::Hash;
+use std::marker::PhantomData;
+use std::sync::{Arc, Mutex};
+use std::{error, fmt};
+use tracing::trace;
+
+/// Determines the key by which a request may be coalesced with others.
+///
+/// This is implemented for all functions `Fn(&Req) -> K`.
+pub trait CoalesceKey<Req> {
+    type Key: Clone + Eq + Hash;
+
+    fn key(&self, req: &Req) -> Self::Key;
+}
+
+pub fn layer<K, Req>(key: K, max_coalesced: usize) -> Layer<K, Req> {
+    assert!(max_coalesced > 0, "max_coalesced must be positive");
+    Layer {
+        key,
+        max_coalesced,
+        _req: PhantomData,
+    }
+}
+
+#[derive(Debug)]
+pub struct Layer<K, Req> {
+    key: K,
+    max_coalesced: usize,
+    _req: PhantomData<fn(Req)>,
+}
+
+pub struct Coalesce<S, K, Req>
+where
+    S: svc::Service<Req>,
+    K: CoalesceKey<Req>,
+{
+    inner: S,
+    key: K,
+    max_coalesced: usize,
+    in_flight: Arc<Mutex<InFlight<K::Key, DispatchFuture<S::Future>>>>,
+}
+
+pub struct ResponseFuture<F, Key>
+where
+    F: Future<Error = Error>,
+{
+    inner: Shared<F>,
+    /// Identifies the in-flight entry to be removed once the response is
+    /// available, if the request was coalescable.
+    entry: Option<(Key, u64, Arc<Mutex<InFlight<Key, F>>>)>,
+}
+
+/// Indicates that a (possibly shared) request failed.
+#[derive(Debug)]
+pub struct CoalescedError(future::SharedError<Error>);
+
+type DispatchFuture<F> = future::MapErr<F, fn(<F as Future>::Error) -> Error>;
+
+struct InFlight<Key, F: Future> {
+    next_id: u64,
+    entries: HashMap<Key, Entry<F>>,
+}
+
+struct Entry<F: Future> {
+    id: u64,
+    response: Shared<F>,
+    /// The number of requests that share the response, including the one
+    /// that was dispatched.
+    coalesced: usize,
+}
+
+// === impl CoalesceKey ===
+
+impl<F, Req, K> CoalesceKey<Req> for F
+where
+    F: Fn(&Req) -> K,
+    K: Clone + Eq + Hash,
+{
+    type Key = K;
+
+    fn key(&self, req: &Req) -> K {
+        (self)(req)
+    }
+}
+
+// === impl Layer ===
+
+impl<K: Clone, Req> Clone for Layer<K, Req> {
+    fn clone(&self) -> Self {
+        Self {
+            key: self.key.clone(),
+            max_coalesced: self.max_coalesced,
+            _req: PhantomData,
+        }
+    }
+}
+
+impl<S, K, Req> svc::Layer<S> for Layer<K, Req>
+where
+    S: svc::Service<Req>,
+    K: CoalesceKey<Req> + Clone,
+{
+    type Service = Coalesce<S, K, Req>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Coalesce {
+            inner,
+            key: self.key.clone(),
+            max_coalesced: self.max_coalesced,
+            in_flight: Arc::new(Mutex::new(InFlight::default())),
+        }
+    }
+}
+
+// === impl Coalesce ===
+
+impl<S, K, Req> Clone for Coalesce<S, K, Req>
+where
+    S: svc::Service<Req> + Clone,
+    K: CoalesceKey<Req> + Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            key: self.key.clone(),
+            max_coalesced: self.max_coalesced,
+            in_flight: self.in_flight.clone(),
+        }
+    }
+}
+
+impl<S, K, Req> svc::Service<Req> for Coalesce<S, K, Req>
+where
+    S: svc::Service<Req>,
+    S::Response: Clone,
+    S::Error: Into<Error>,
+    K: CoalesceKey<Req>,
+{
+    type Response = S::Response;
+    type Error = Error;
+    type Future = ResponseFuture<DispatchFuture<S::Future>, K::Key>;
+
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        self.inner.poll_ready().map_err(Into::into)
+    }
+
+    fn call(&mut self, req: Req) -> Self::Future {
+        let key = self.key.key(&req);
+        let mut in_flight = self.in_flight.lock().expect("coalesce lock poisoned");
+
+        if let Some(entry) = in_flight.entries.get_mut(&key) {
+            if entry.coalesced < self.max_coalesced {
+                entry.coalesced += 1;
+                trace!(coalesced = entry.coalesced, "coalescing request");
+                return ResponseFuture {
+                    inner: entry.response.clone(),
+                    entry: Some((key, entry.id, self.in_flight.clone())),
+                };
+            }
+
+            // Too many requests already share the in-flight response, so
+            // this one is dispatched independently.
+            trace!("dispatching request; too many coalesced");
+            let response = self.inner.call(req).map_err(Into::into as fn(_) -> _);
+            return ResponseFuture {
+                inner: response.shared(),
+                entry: None,
+            };
+        }
+
+        let id = in_flight.next_id;
+        in_flight.next_id += 1;
+        let response = self
+            .inner
+            .call(req)
+            .map_err(Into::into as fn(_) -> _)
+            .shared();
+        in_flight.entries.insert(
+            key.clone(),
+            Entry {
+                id,
+                response: response.clone(),
+                coalesced: 1,
+            },
+        );
+        ResponseFuture {
+            inner: response,
+            entry: Some((key, id, self.in_flight.clone())),
+        }
+    }
+}
+
+// === impl InFlight ===
+
+impl<Key: Eq + Hash, F: Future> Default for InFlight<Key, F> {
+    fn default() -> Self {
+        Self {
+            next_id: 0,
+            entries: HashMap::new(),
+        }
+    }
+}
+
+// === impl ResponseFuture ===
+
+impl<F, Key> Future for ResponseFuture<F, Key>
+where
+    F: Future<Error = Error>,
+    F::Item: Clone,
+    Key: Eq + Hash,
+{
+    type Item = F::Item;
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        let result = match self.inner.poll() {
+            Ok(Async::NotReady) => return Ok(Async::NotReady),
+            Ok(Async::Ready(rsp)) => Ok(Async::Ready((*rsp).clone())),
+            Err(e) => Err(CoalescedError(e).into()),
+        };
+
+        // The response is available, so later requests must not share it.
+        if let Some((key, id, in_flight)) = self.entry.take() {
+            if let Ok(mut in_flight) = in_flight.lock() {
+                if in_flight
+                    .entries
+                    .get(&key)
+                    .map(|e| e.id == id)
+                    .unwrap_or(false)
+                {
+                    in_flight.entries.remove(&key);
+                }
+            }
+        }
+
+        result
+    }
+}
+
+// === impl CoalescedError ===
+
+impl fmt::Display for CoalescedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&*self.0, f)
+    }
+}
+
+impl error::Error for CoalescedError {
+    fn source(&self) -> Option<&(dyn error::Error + 'static)> {
+        Some(&**self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Responds with the number of requests it has received.
+    fn counting(
+        calls: Arc<AtomicUsize>,
+    ) -> impl svc::Service<&'static str, Response = usize, Error = Error> {
+        svc::mk(move |_: &'static str| {
+            let n = calls.fetch_add(1, Ordering::SeqCst) + 1;
+            future::ok::<usize, Error>(n)
+        })
+    }
+
+    fn by_name(name: &&'static str) -> &'static str {
+        *name
+    }
+
+    #[test]
+    fn coalesces_in_flight_requests() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let mut svc = svc::Layer::layer(&layer(by_name, 10), counting(calls.clone()));
+
+        // Responses are not polled until all requests have been made, so the
+        // first request is still in flight when the others are made.
+        let a = svc.call("a");
+        let b = svc.call("a");
+        let c = svc.call("c");
+        assert_eq!(a.wait().unwrap(), 1);
+        assert_eq!(b.wait().unwrap(), 1);
+        assert_eq!(c.wait().unwrap(), 2);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        // Once the response is available, the key is no longer coalesced.
+        assert_eq!(svc.call("a").wait().unwrap(), 3);
+        assert_eq!(calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn limits_coalesced_requests() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let mut svc = svc::Layer::layer(&layer(by_name, 2), counting(calls.clone()));
+
+        let a = svc.call("a");
+        let b = svc.call("a");
+        let c = svc.call("a");
+        assert_eq!(a.wait().unwrap(), 1);
+        assert_eq!(b.wait().unwrap(), 1);
+        assert_eq!(c.wait().unwrap(), 2);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn failures_are_shared() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let inner = {
+            let calls = calls.clone();
+            svc::mk(move |_: &'static str| {
+                calls.fetch_add(1, Ordering::SeqCst);
+                future::err::<usize, Error>("failed".into())
+            })
+        };
+        let mut svc = svc::Layer::layer(&layer(by_name, 10), inner);
+
+        let a = svc.call("a");
+        let b = svc.call("a");
+        assert_eq!(a.wait().unwrap_err().to_string(), "failed");
+        assert_eq!(b.wait().unwrap_err().to_string(), "failed");
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+}
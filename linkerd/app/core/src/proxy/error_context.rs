@@ -0,0 +1,201 @@
+//! Annotates the errors of made services with the target they were made for.
+//!
+//! Errors from deep within a stack (e.g. "connection refused") otherwise do
+//! not describe which target they pertain to.
+
+use crate::Error;
+use futures::{try_ready, Future, Poll};
+use std::fmt;
+
+pub fn layer() -> Layer {
+    Layer(())
+}
+
+#[derive(Clone, Debug)]
+pub struct Layer(());
+
+#[derive(Clone, Debug)]
+pub struct MakeContext<M> {
+    inner: M,
+}
+
+pub struct MakeFuture<F, T> {
+    inner: F,
+    target: Option<T>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Context<S, T> {
+    inner: S,
+    target: T,
+}
+
+pub struct ResponseFuture<F, T> {
+    inner: F,
+    target: Option<T>,
+}
+
+/// An error that occurred in a service made for `target`.
+#[derive(Debug)]
+pub struct ContextError<T> {
+    target: T,
+    source: Error,
+}
+
+// === impl Layer ===
+
+impl<M> tower::layer::Layer<M> for Layer {
+    type Service = MakeContext<M>;
+
+    fn layer(&self, inner: M) -> Self::Service {
+        MakeContext { inner }
+    }
+}
+
+// === impl MakeContext ===
+
+impl<T, M> tower::Service<T> for MakeContext<M>
+where
+    T: Clone,
+    M: tower::Service<T>,
+{
+    type Response = Context<M::Response, T>;
+    type Error = M::Error;
+    type Future = MakeFuture<M::Future, T>;
+
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        self.inner.poll_ready()
+    }
+
+    fn call(&mut self, target: T) -> Self::Future {
+        MakeFuture {
+            target: Some(target.clone()),
+            inner: self.inner.call(target),
+        }
+    }
+}
+
+impl<F: Future, T> Future for MakeFuture<F, T> {
+    type Item = Context<F::Item, T>;
+    type Error = F::Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        let inner = try_ready!(self.inner.poll());
+        let target = self.target.take().expect("polled after ready");
+        Ok(Context { inner, target }.into())
+    }
+}
+
+// === impl Context ===
+
+impl<S, T, Req> tower::Service<Req> for Context<S, T>
+where
+    S: tower::Service<Req>,
+    S::Error: Into<Error>,
+    T: fmt::Display + fmt::Debug + Clone + Send + Sync + 'static,
+{
+    type Response = S::Response;
+    type Error = Error;
+    type Future = ResponseFuture<S::Future, T>;
+
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        let target = &self.target;
+        self.inner
+            .poll_ready()
+            .map_err(|e| ContextError::new(target.clone(), e).into())
+    }
+
+    fn call(&mut self, req: Req) -> Self::Future {
+        ResponseFuture {
+            inner: self.inner.call(req),
+            target: Some(self.target.clone()),
+        }
+    }
+}
+
+impl<F, T> Future for ResponseFuture<F, T>
+where
+    F: Future,
+    F::Error: Into<Error>,
+    T: fmt::Display + fmt::Debug + Send + Sync + 'static,
+{
+    type Item = F::Item;
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        self.inner.poll().map_err(|e| {
+            let target = self.target.take().expect("polled after error");
+            ContextError::new(target, e).into()
+        })
+    }
+}
+
+// === impl ContextError ===
+
+impl<T> ContextError<T> {
+    pub fn new(target: T, source: impl Into<Error>) -> Self {
+        Self {
+            target,
+            source: source.into(),
+        }
+    }
+
+    pub fn target(&self) -> &T {
+        &self.target
+    }
+}
+
+impl<T: fmt::Display> fmt::Display for ContextError<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "error from {}: {}", self.target, self.source)
+    }
+}
+
+impl<T: fmt::Display + fmt::Debug> std::error::Error for ContextError<T> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&*self.source)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::future;
+    use std::io;
+    use tower::layer::Layer as _;
+    use tower::Service as _;
+
+    fn refused() -> impl tower::Service<(), Response = (), Error = io::Error> {
+        tower::service_fn(|()| {
+            future::err::<(), _>(io::Error::new(
+                io::ErrorKind::ConnectionRefused,
+                "connection refused",
+            ))
+        })
+    }
+
+    #[test]
+    fn errors_carry_the_target_and_their_cause() {
+        let make = tower::service_fn(|_: &'static str| future::ok::<_, ()>(refused()));
+        let mut svc = layer()
+            .layer(make)
+            .call("web.ns.svc.cluster.local:8080")
+            .wait()
+            .unwrap();
+
+        let err = svc.call(()).wait().unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "error from web.ns.svc.cluster.local:8080: connection refused"
+        );
+
+        let ctx = err
+            .downcast_ref::<ContextError<&'static str>>()
+            .expect("error must have context");
+        assert_eq!(*ctx.target(), "web.ns.svc.cluster.local:8080");
+
+        let cause = std::error::Error::source(ctx).expect("error must have a cause");
+        let io = cause.downcast_ref::<io::Error>().expect("cause must be io");
+        assert_eq!(io.kind(), io::ErrorKind::ConnectionRefused);
+    }
+}
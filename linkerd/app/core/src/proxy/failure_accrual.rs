@@ -0,0 +1,772 @@
+//! Ejects a service that is consistently failing, so that a balancer sheds
+//! its load to its peers.
+//!
+//! Responses are classified with the `classify::Response` extension that is
+//! installed on each request. After `max_consecutive_failures` consecutive
+//! failures, or when the failure rate over the most recent `window` responses
+//! exceeds `max_failure_rate`, the service is ejected: it is not ready for the
+//! `backoff` duration. If a `sample_window` is configured, the failure rate is
+//! instead measured over the responses received within it. Once that elapses, a single probe request is permitted;
+//! the service is restored if the probe succeeds and ejected again otherwise.
+//!
+//! Responses are classified when their bodies complete, like the HTTP
+//! metrics, so that failures that are only indicated by trailers--e.g. a
+//! `grpc-status` trailer--are counted.
+
+use crate::classify::{self, Class};
+use crate::config::FailureAccrualConfig;
+use crate::proxy::http::metrics::classify::{ClassifyEos, ClassifyResponse};
+use crate::svc;
+use futures::{task, try_ready, Async, Future, Poll};
+use hyper::body::Payload;
+use linkerd2_error::Error;
+use linkerd2_metrics::{metrics, Counter, FmtLabels, FmtMetric, FmtMetrics};
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
+use tokio_timer::{clock, Delay};
+use tracing::{debug, trace};
+
+metrics! {
+    failure_accrual_transitions_total: Counter {
+        "Total count of the transitions of services between healthy and ejected states"
+    }
+}
+
+pub fn layer(config: Option<FailureAccrualConfig>, metrics: Metrics) -> Layer {
+    Layer { config, metrics }
+}
+
+#[derive(Clone, Debug)]
+pub struct Layer {
+    config: Option<FailureAccrualConfig>,
+    metrics: Metrics,
+}
+
+/// Counts the transitions of all failure-accrual services.
+#[derive(Clone, Debug, Default)]
+pub struct Metrics(Arc<Mutex<Transitions>>);
+
+pub struct FailureAccrual<S> {
+    inner: S,
+    state: Arc<Mutex<State>>,
+    /// Fires when the service's backoff elapses.
+    backoff: Option<Delay>,
+}
+
+pub struct ResponseFuture<F> {
+    inner: F,
+    classify: Option<classify::Response>,
+    state: Arc<Mutex<State>>,
+    is_probe: bool,
+}
+
+/// Classifies a response when its body completes.
+pub struct ResponseBody<B> {
+    inner: B,
+    eos: Option<classify::Eos>,
+    state: Option<Arc<Mutex<State>>>,
+    is_probe: bool,
+}
+
+#[derive(Clone, Debug, Default)]
+struct Transitions {
+    opened: Counter,
+    half_opened: Counter,
+    closed: Counter,
+}
+
+struct State {
+    config: Option<FailureAccrualConfig>,
+    metrics: Metrics,
+    status: Status,
+    consecutive_failures: usize,
+    /// The times and outcomes of the most recent responses, where `true`
+    /// indicates a failure.
+    recent: VecDeque<(Instant, bool)>,
+    /// The number of failures in `recent`.
+    recent_failures: usize,
+    /// Notified when a probe completes, so that every service waiting on the
+    /// probe is polled again.
+    tasks: Vec<task::Task>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+enum Status {
+    /// Requests are dispatched and their failures are accrued.
+    Closed,
+    /// The service is ejected until the given time.
+    Open(Instant),
+    /// A single probe request may be dispatched.
+    HalfOpen,
+    /// A probe request is in flight.
+    Probing,
+}
+
+// === impl Layer ===
+
+impl<S> svc::Layer<S> for Layer {
+    type Service = FailureAccrual<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        FailureAccrual {
+            inner,
+            state: Arc::new(Mutex::new(State {
+                config: self.config,
+                metrics: self.metrics.clone(),
+                status: Status::Closed,
+                consecutive_failures: 0,
+                recent: VecDeque::new(),
+                recent_failures: 0,
+                tasks: Vec::new(),
+            })),
+            backoff: None,
+        }
+    }
+}
+
+// === impl FailureAccrual ===
+
+impl<S, A, B> svc::Service<http::Request<A>> for FailureAccrual<S>
+where
+    S: svc::Service<http::Request<A>, Response = http::Response<B>>,
+    S::Error: Into<Error>,
+    B: Payload,
+{
+    type Response = http::Response<ResponseBody<B>>;
+    type Error = Error;
+    type Future = ResponseFuture<S::Future>;
+
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        loop {
+            let mut state = self.state.lock().expect("state lock poisoned");
+            match state.status {
+                Status::Closed | Status::HalfOpen => break,
+                Status::Probing => {
+                    if !state.tasks.iter().any(|t| t.will_notify_current()) {
+                        state.tasks.push(task::current());
+                    }
+                    return Ok(Async::NotReady);
+                }
+                Status::Open(until) => {
+                    let reset = match self.backoff {
+                        Some(ref delay) => delay.deadline() != until,
+                        None => true,
+                    };
+                    if reset {
+                        self.backoff = Some(Delay::new(until));
+                    }
+                    let backoff = self.backoff.as_mut().expect("backoff must be set");
+                    if backoff.poll().map_err(Error::from)?.is_not_ready() {
+                        return Ok(Async::NotReady);
+                    }
+                    self.backoff = None;
+                    state.half_open();
+                }
+            }
+        }
+
+        self.inner.poll_ready().map_err(Into::into)
+    }
+
+    fn call(&mut self, req: http::Request<A>) -> Self::Future {
+        let classify = req.extensions().get::<classify::Response>().cloned();
+
+        let is_probe = {
+            let mut state = self.state.lock().expect("state lock poisoned");
+            if state.status == Status::HalfOpen {
+                trace!("dispatching probe");
+                state.status = Status::Probing;
+                true
+            } else {
+                false
+            }
+        };
+
+        ResponseFuture {
+            inner: self.inner.call(req),
+            classify: Some(classify.unwrap_or_default()),
+            state: self.state.clone(),
+            is_probe,
+        }
+    }
+}
+
+impl<S: Clone> Clone for FailureAccrual<S> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            state: self.state.clone(),
+            backoff: None,
+        }
+    }
+}
+
+// === impl ResponseFuture ===
+
+impl<F, B> Future for ResponseFuture<F>
+where
+    F: Future<Item = http::Response<B>>,
+    F::Error: Into<Error>,
+    B: Payload,
+{
+    type Item = http::Response<ResponseBody<B>>;
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        let classify = self.classify.take().expect("polled after ready");
+        match self.inner.poll() {
+            Ok(Async::NotReady) => {
+                self.classify = Some(classify);
+                Ok(Async::NotReady)
+            }
+            Ok(Async::Ready(rsp)) => {
+                // The probe completes when the response body does.
+                let eos = classify.start(&rsp);
+                let is_probe = std::mem::replace(&mut self.is_probe, false);
+                let state = self.state.clone();
+                Ok(Async::Ready(rsp.map(move |inner| ResponseBody {
+                    inner,
+                    eos: Some(eos),
+                    state: Some(state),
+                    is_probe,
+                })))
+            }
+            Err(e) => {
+                let e = e.into();
+                let is_probe = std::mem::replace(&mut self.is_probe, false);
+                record(&self.state, classify.error(&e), is_probe);
+                Err(e)
+            }
+        }
+    }
+}
+
+impl<F> Drop for ResponseFuture<F> {
+    fn drop(&mut self) {
+        // If a probe is canceled, another probe is permitted.
+        if self.is_probe {
+            if let Ok(mut state) = self.state.lock() {
+                state.status = Status::HalfOpen;
+                state.notify();
+            }
+        }
+    }
+}
+
+// === impl ResponseBody ===
+
+impl<B> ResponseBody<B> {
+    fn record(&mut self, classify: impl FnOnce(classify::Eos) -> Class) {
+        if let Some(eos) = self.eos.take() {
+            let is_probe = std::mem::replace(&mut self.is_probe, false);
+            if let Some(state) = self.state.as_ref() {
+                record(state, classify(eos), is_probe);
+            }
+        }
+    }
+
+    fn record_err(&mut self, err: Error) -> Error {
+        self.record(|eos| eos.error(&err));
+        err
+    }
+}
+
+impl<B: Payload> Payload for ResponseBody<B> {
+    type Data = B::Data;
+    type Error = Error;
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn poll_data(&mut self) -> Poll<Option<Self::Data>, Self::Error> {
+        self.inner
+            .poll_data()
+            .map_err(|e| self.record_err(e.into()))
+    }
+
+    fn poll_trailers(&mut self) -> Poll<Option<http::HeaderMap>, Self::Error> {
+        let trailers = try_ready!(self
+            .inner
+            .poll_trailers()
+            .map_err(|e| self.record_err(e.into())));
+        self.record(|eos| eos.eos(trailers.as_ref()));
+        Ok(Async::Ready(trailers))
+    }
+}
+
+impl<B: Default> Default for ResponseBody<B> {
+    fn default() -> Self {
+        Self {
+            inner: B::default(),
+            eos: None,
+            state: None,
+            is_probe: false,
+        }
+    }
+}
+
+impl<B> Drop for ResponseBody<B> {
+    fn drop(&mut self) {
+        // If the body is dropped before its trailers are received, the
+        // response ends without them.
+        self.record(|eos| eos.eos(None));
+    }
+}
+
+fn record(state: &Arc<Mutex<State>>, class: Class, is_probe: bool) {
+    if let Ok(mut state) = state.lock() {
+        state.record(class.is_failure(), is_probe);
+    }
+}
+
+// === impl State ===
+
+impl State {
+    fn record(&mut self, failed: bool, is_probe: bool) {
+        let config = match self.config {
+            Some(config) => config,
+            None => return,
+        };
+
+        if is_probe {
+            if failed {
+                self.open(config, "probe failed");
+            } else {
+                self.close();
+            }
+            self.notify();
+            return;
+        }
+
+        // Responses to requests that were dispatched before the service was
+        // ejected are ignored.
+        if self.status != Status::Closed {
+            return;
+        }
+
+        if failed {
+            self.consecutive_failures += 1;
+            self.recent_failures += 1;
+        } else {
+            self.consecutive_failures = 0;
+        }
+        let now = clock::now();
+        self.recent.push_back((now, failed));
+        loop {
+            let expired = match (self.recent.front(), config.sample_window) {
+                (Some(&(at, _)), Some(sample_window)) => at + sample_window < now,
+                (Some(_), None) => self.recent.len() > config.window,
+                (None, _) => false,
+            };
+            if !expired {
+                break;
+            }
+            if let Some((_, true)) = self.recent.pop_front() {
+                self.recent_failures -= 1;
+            }
+        }
+
+        if self.consecutive_failures >= config.max_consecutive_failures {
+            self.open(config, "too many consecutive failures");
+        } else if let Some(max_rate) = config.max_failure_rate {
+            let rate = self.recent_failures as f64 / self.recent.len() as f64;
+            if self.recent.len() >= config.window && rate > max_rate {
+                self.open(config, "failure rate is too high");
+            }
+        }
+    }
+
+    fn open(&mut self, config: FailureAccrualConfig, reason: &'static str) {
+        debug!(%reason, backoff = ?config.backoff, "ejecting service");
+        self.status = Status::Open(clock::now() + config.backoff);
+        self.metrics.update(|t| t.opened.incr());
+    }
+
+    fn half_open(&mut self) {
+        debug!("permitting probe");
+        self.status = Status::HalfOpen;
+        self.metrics.update(|t| t.half_opened.incr());
+    }
+
+    fn close(&mut self) {
+        debug!("probe succeeded; restoring service");
+        self.status = Status::Closed;
+        self.consecutive_failures = 0;
+        self.recent.clear();
+        self.recent_failures = 0;
+        self.metrics.update(|t| t.closed.incr());
+    }
+
+    fn notify(&mut self) {
+        for task in self.tasks.drain(..) {
+            task.notify();
+        }
+    }
+}
+
+// === impl Metrics ===
+
+impl Metrics {
+    fn update(&self, f: impl FnOnce(&mut Transitions)) {
+        if let Ok(mut t) = self.0.lock() {
+            f(&mut *t);
+        }
+    }
+}
+
+impl FmtMetrics for Metrics {
+    fn fmt_metrics(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let t = match self.0.lock() {
+            Ok(t) => t.clone(),
+            Err(_) => return Ok(()),
+        };
+
+        failure_accrual_transitions_total.fmt_help(f)?;
+        for &(state, counter) in &[
+            ("open", t.opened),
+            ("half_open", t.half_opened),
+            ("closed", t.closed),
+        ] {
+            counter.fmt_metric_labeled(
+                f,
+                failure_accrual_transitions_total.name,
+                StateLabel(state),
+            )?;
+        }
+
+        Ok(())
+    }
+}
+
+struct StateLabel(&'static str);
+
+impl FmtLabels for StateLabel {
+    fn fmt_labels(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "state=\"{}\"", self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::CircuitBreakerConfig;
+    use futures::future;
+    use std::io;
+    use std::time::Duration;
+    use svc::Layer as _;
+    use tokio::runtime::current_thread::Runtime;
+
+    const BACKOFF: Duration = Duration::from_millis(20);
+
+    /// A response body without data that ends with the given `grpc-status`
+    /// trailer, if any.
+    #[derive(Default)]
+    struct Trailers(Option<u32>);
+
+    impl Payload for Trailers {
+        type Data = io::Cursor<&'static [u8]>;
+        type Error = Error;
+
+        fn poll_data(&mut self) -> Poll<Option<Self::Data>, Self::Error> {
+            Ok(Async::Ready(None))
+        }
+
+        fn poll_trailers(&mut self) -> Poll<Option<http::HeaderMap>, Self::Error> {
+            let trailers = self.0.take().map(|code| {
+                let mut trailers = http::HeaderMap::new();
+                trailers.insert("grpc-status", code.into());
+                trailers
+            });
+            Ok(Async::Ready(trailers))
+        }
+    }
+
+    type Rsp = http::Response<ResponseBody<Trailers>>;
+
+    /// Responds to each request with the next scripted status.
+    fn scripted(
+        statuses: &[u16],
+    ) -> impl svc::Service<http::Request<()>, Response = http::Response<Trailers>, Error = Error> + Clone
+    {
+        let statuses = Arc::new(Mutex::new(
+            statuses.iter().cloned().collect::<VecDeque<_>>(),
+        ));
+        svc::mk(move |_: http::Request<()>| {
+            let status = statuses
+                .lock()
+                .unwrap()
+                .pop_front()
+                .expect("no more scripted responses");
+            let rsp = http::Response::builder()
+                .status(status)
+                .body(Trailers::default())
+                .unwrap();
+            future::ok::<_, Error>(rsp)
+        })
+    }
+
+    /// Responds to each request with a `200 OK` whose body ends with the next
+    /// scripted `grpc-status` trailer.
+    fn scripted_trailers(
+        codes: &[u32],
+    ) -> impl svc::Service<http::Request<()>, Response = http::Response<Trailers>, Error = Error>
+    {
+        let codes = Arc::new(Mutex::new(codes.iter().cloned().collect::<VecDeque<_>>()));
+        svc::mk(move |_: http::Request<()>| {
+            let code = codes
+                .lock()
+                .unwrap()
+                .pop_front()
+                .expect("no more scripted responses");
+            future::ok::<_, Error>(http::Response::new(Trailers(Some(code))))
+        })
+    }
+
+    fn config(max_failure_rate: Option<f64>) -> FailureAccrualConfig {
+        FailureAccrualConfig {
+            max_consecutive_failures: 3,
+            max_failure_rate,
+            window: 4,
+            sample_window: None,
+            backoff: BACKOFF,
+        }
+    }
+
+    fn is_ready<S: svc::Service<http::Request<()>, Error = Error>>(
+        rt: &mut Runtime,
+        svc: &mut S,
+    ) -> bool {
+        rt.block_on(future::lazy(|| svc.poll_ready().map(|r| r.is_ready())))
+            .expect("poll_ready must not fail")
+    }
+
+    fn send<S>(rt: &mut Runtime, svc: &mut S) -> http::StatusCode
+    where
+        S: svc::Service<http::Request<()>, Response = Rsp, Error = Error>,
+    {
+        assert!(is_ready(rt, svc), "service must be ready");
+        rt.block_on(svc.call(http::Request::new(())))
+            .expect("request must succeed")
+            .status()
+    }
+
+    /// Sends a request and reads its response body through its trailers.
+    fn send_and_read<S>(rt: &mut Runtime, svc: &mut S)
+    where
+        S: svc::Service<http::Request<()>, Response = Rsp, Error = Error>,
+    {
+        assert!(is_ready(rt, svc), "service must be ready");
+        let mut body = rt
+            .block_on(svc.call(http::Request::new(())))
+            .expect("request must succeed")
+            .into_body();
+        rt.block_on(future::poll_fn(move || {
+            while try_ready!(body.poll_data()).is_some() {}
+            body.poll_trailers()
+        }))
+        .expect("body must succeed");
+    }
+
+    fn report(metrics: &Metrics) -> String {
+        metrics.as_display().to_string()
+    }
+
+    #[test]
+    fn consecutive_failures_eject_until_a_probe_succeeds() {
+        let mut rt = Runtime::new().unwrap();
+        let metrics = Metrics::default();
+        let mut svc = layer(Some(config(None)), metrics.clone())
+            .layer(scripted(&[500, 500, 200, 500, 500, 500, 200, 200]));
+
+        // A success resets the count of consecutive failures.
+        for _ in 0..3 {
+            send(&mut rt, &mut svc);
+        }
+        send(&mut rt, &mut svc);
+        send(&mut rt, &mut svc);
+        assert!(is_ready(&mut rt, &mut svc));
+
+        send(&mut rt, &mut svc);
+        assert!(!is_ready(&mut rt, &mut svc), "service must be ejected");
+
+        // Once the backoff elapses, a probe is permitted.
+        rt.block_on(tokio_timer::sleep(BACKOFF * 2)).unwrap();
+        assert!(is_ready(&mut rt, &mut svc));
+        let probe = svc.call(http::Request::new(()));
+        assert!(!is_ready(&mut rt, &mut svc), "only one probe is permitted");
+        assert!(rt.block_on(probe).unwrap().status().is_success());
+
+        assert!(is_ready(&mut rt, &mut svc), "service must be restored");
+        send(&mut rt, &mut svc);
+
+        let report = report(&metrics);
+        assert!(report.contains("failure_accrual_transitions_total{state=\"open\"} 1\n"));
+        assert!(report.contains("failure_accrual_transitions_total{state=\"half_open\"} 1\n"));
+        assert!(report.contains("failure_accrual_transitions_total{state=\"closed\"} 1\n"));
+    }
+
+    #[test]
+    fn probe_completion_notifies_every_waiter() {
+        let mut rt = Runtime::new().unwrap();
+        let mut svc =
+            layer(Some(config(None)), Metrics::default()).layer(scripted(&[500, 500, 500, 200]));
+
+        for _ in 0..3 {
+            send(&mut rt, &mut svc);
+        }
+        rt.block_on(tokio_timer::sleep(BACKOFF * 2)).unwrap();
+        assert!(is_ready(&mut rt, &mut svc));
+        let probe = svc.call(http::Request::new(()));
+
+        // Both the clone's task and this task wait on the probe.
+        let (tx, rx) = futures::sync::oneshot::channel();
+        let mut waiter = svc.clone();
+        rt.spawn(
+            future::poll_fn(move || waiter.poll_ready()).then(move |ready| {
+                let _ = tx.send(ready.is_ok());
+                Ok(())
+            }),
+        );
+        assert!(!is_ready(&mut rt, &mut svc), "only one probe is permitted");
+
+        assert!(rt.block_on(probe).unwrap().status().is_success());
+        let ready = rt
+            .block_on(tokio_timer::Timeout::new(rx, Duration::from_secs(1)))
+            .expect("waiter must be notified");
+        assert!(ready, "waiter must become ready");
+    }
+
+    #[test]
+    fn failed_probe_ejects_again() {
+        let mut rt = Runtime::new().unwrap();
+        let metrics = Metrics::default();
+        let mut svc =
+            layer(Some(config(None)), metrics.clone()).layer(scripted(&[500, 500, 500, 500, 200]));
+
+        for _ in 0..3 {
+            send(&mut rt, &mut svc);
+        }
+        assert!(!is_ready(&mut rt, &mut svc));
+
+        rt.block_on(tokio_timer::sleep(BACKOFF * 2)).unwrap();
+        send(&mut rt, &mut svc);
+        assert!(
+            !is_ready(&mut rt, &mut svc),
+            "a failed probe ejects the service"
+        );
+
+        rt.block_on(tokio_timer::sleep(BACKOFF * 2)).unwrap();
+        assert!(send(&mut rt, &mut svc).is_success());
+        assert!(is_ready(&mut rt, &mut svc));
+
+        let report = report(&metrics);
+        assert!(report.contains("failure_accrual_transitions_total{state=\"open\"} 2\n"));
+        assert!(report.contains("failure_accrual_transitions_total{state=\"half_open\"} 2\n"));
+        assert!(report.contains("failure_accrual_transitions_total{state=\"closed\"} 1\n"));
+    }
+
+    #[test]
+    fn failure_rate_ejects() {
+        let mut rt = Runtime::new().unwrap();
+        let mut svc = layer(Some(config(Some(0.5))), Metrics::default())
+            .layer(scripted(&[200, 500, 200, 500, 500]));
+
+        // Half of the window has failed, which does not exceed the rate.
+        for _ in 0..4 {
+            send(&mut rt, &mut svc);
+        }
+        assert!(is_ready(&mut rt, &mut svc));
+
+        send(&mut rt, &mut svc);
+        assert!(!is_ready(&mut rt, &mut svc), "service must be ejected");
+    }
+
+    #[test]
+    fn failures_in_trailers_eject() {
+        let mut rt = Runtime::new().unwrap();
+        // 14 is UNAVAILABLE, which is a failure; 0 is OK.
+        let mut svc = layer(Some(config(None)), Metrics::default())
+            .layer(scripted_trailers(&[14, 14, 0, 14, 14, 14]));
+
+        // Each response's headers indicate success, but the trailers of all
+        // but one indicate failure.
+        for _ in 0..5 {
+            send_and_read(&mut rt, &mut svc);
+        }
+        assert!(is_ready(&mut rt, &mut svc));
+
+        send_and_read(&mut rt, &mut svc);
+        assert!(!is_ready(&mut rt, &mut svc), "service must be ejected");
+    }
+
+    #[test]
+    fn circuit_breaker_opens_half_opens_and_closes() {
+        let mut rt = Runtime::new().unwrap();
+        let metrics = Metrics::default();
+        let config = CircuitBreakerConfig {
+            error_rate_threshold: 0.5,
+            sample_window: Duration::from_secs(60),
+            open_duration: BACKOFF,
+        };
+        let mut svc = layer(Some(config.into()), metrics.clone())
+            .layer(scripted(&[200, 500, 200, 500, 500, 200, 200]));
+
+        // The error rate is not measured until enough requests are observed.
+        for _ in 0..4 {
+            send(&mut rt, &mut svc);
+        }
+        assert!(is_ready(&mut rt, &mut svc));
+
+        send(&mut rt, &mut svc);
+        assert!(!is_ready(&mut rt, &mut svc), "circuit must be open");
+
+        rt.block_on(tokio_timer::sleep(BACKOFF * 2)).unwrap();
+        assert!(is_ready(&mut rt, &mut svc), "circuit must be half-open");
+        send(&mut rt, &mut svc);
+        assert!(is_ready(&mut rt, &mut svc), "circuit must be closed");
+        send(&mut rt, &mut svc);
+
+        let report = report(&metrics);
+        assert!(report.contains("failure_accrual_transitions_total{state=\"open\"} 1\n"));
+        assert!(report.contains("failure_accrual_transitions_total{state=\"half_open\"} 1\n"));
+        assert!(report.contains("failure_accrual_transitions_total{state=\"closed\"} 1\n"));
+    }
+
+    #[test]
+    fn circuit_breaker_forgets_failures_outside_the_sample_window() {
+        let mut rt = Runtime::new().unwrap();
+        let sample_window = Duration::from_millis(50);
+        let config = CircuitBreakerConfig {
+            error_rate_threshold: 0.5,
+            sample_window,
+            open_duration: BACKOFF,
+        };
+        let mut svc = layer(Some(config.into()), Metrics::default())
+            .layer(scripted(&[500, 500, 500, 500, 200, 500, 500, 200, 200]));
+
+        for _ in 0..4 {
+            send(&mut rt, &mut svc);
+        }
+        rt.block_on(tokio_timer::sleep(sample_window * 2)).unwrap();
+
+        // Only two of the five requests in the sample window have failed.
+        for _ in 0..5 {
+            send(&mut rt, &mut svc);
+        }
+        assert!(is_ready(&mut rt, &mut svc), "circuit must remain closed");
+    }
+
+    #[test]
+    fn disabled_without_config() {
+        let mut rt = Runtime::new().unwrap();
+        let mut svc = layer(None, Metrics::default()).layer(scripted(&[500, 500, 500, 500]));
+
+        for _ in 0..4 {
+            send(&mut rt, &mut svc);
+        }
+        assert!(is_ready(&mut rt, &mut svc));
+    }
+}
@@ -0,0 +1,249 @@
+//! Records a service's readiness, so that its liveness may be observed.
+//!
+//! A `HealthReport` obtained from a `HealthMonitor` observes when the service
+//! was last ready, how many consecutive times it has not been ready, and
+//! whether it has ever failed. Readiness is only recorded while a report is
+//! held, so the monitor costs little more than a reference count otherwise.
+//!
+//! All state is shared by clones of the service and is updated without locks.
+
+use crate::svc;
+use futures::{Async, Poll};
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio_timer::clock;
+use tracing::{debug, trace};
+
+/// The value of `State::last_ready` before the service has been ready.
+const NEVER: u64 = u64::max_value();
+
+pub fn layer() -> Layer {
+    Layer(())
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Layer(());
+
+#[derive(Clone, Debug)]
+pub struct HealthMonitor<S> {
+    inner: S,
+    state: Arc<State>,
+}
+
+/// Observes the readiness of a monitored service.
+#[derive(Clone, Debug)]
+pub struct HealthReport {
+    state: Arc<State>,
+    _handle: Arc<()>,
+}
+
+#[derive(Debug)]
+struct State {
+    /// Cloned by each `HealthReport`, so that readiness is only recorded
+    /// while a report is held.
+    handle: Arc<()>,
+
+    /// Times are recorded as milliseconds elapsed since `epoch`.
+    epoch: Instant,
+    last_ready: AtomicU64,
+    not_ready: AtomicUsize,
+    failed: AtomicBool,
+}
+
+// === impl Layer ===
+
+impl<S> svc::Layer<S> for Layer {
+    type Service = HealthMonitor<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        HealthMonitor {
+            inner,
+            state: Arc::new(State {
+                handle: Arc::new(()),
+                epoch: clock::now(),
+                last_ready: AtomicU64::new(NEVER),
+                not_ready: AtomicUsize::new(0),
+                failed: AtomicBool::new(false),
+            }),
+        }
+    }
+}
+
+// === impl HealthMonitor ===
+
+impl<S> HealthMonitor<S> {
+    /// Returns a report of the service's readiness, which is updated as the
+    /// service is polled.
+    ///
+    /// Readiness is not recorded while no reports are held.
+    pub fn report(&self) -> HealthReport {
+        HealthReport {
+            state: self.state.clone(),
+            _handle: self.state.handle.clone(),
+        }
+    }
+}
+
+impl<Req, S> svc::Service<Req> for HealthMonitor<S>
+where
+    S: svc::Service<Req>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        let poll = self.inner.poll_ready();
+        if self.state.is_observed() {
+            self.state.record(&poll);
+        }
+        poll
+    }
+
+    fn call(&mut self, req: Req) -> Self::Future {
+        self.inner.call(req)
+    }
+}
+
+// === impl HealthReport ===
+
+impl HealthReport {
+    /// Returns when the service was last ready, if it ever was.
+    pub fn last_ready(&self) -> Option<Instant> {
+        match self.state.last_ready.load(Ordering::Acquire) {
+            NEVER => None,
+            ms => Some(self.state.epoch + Duration::from_millis(ms)),
+        }
+    }
+
+    /// Returns the number of times the service has been polled without
+    /// becoming ready since it was last ready.
+    pub fn consecutive_not_ready(&self) -> usize {
+        self.state.not_ready.load(Ordering::Acquire)
+    }
+
+    /// Returns true if the service has ever failed.
+    pub fn has_failed(&self) -> bool {
+        self.state.failed.load(Ordering::Acquire)
+    }
+}
+
+// === impl State ===
+
+impl State {
+    fn is_observed(&self) -> bool {
+        Arc::strong_count(&self.handle) > 1
+    }
+
+    fn record<E>(&self, poll: &Poll<(), E>) {
+        match poll {
+            Ok(Async::Ready(())) => {
+                let now = clock::now().duration_since(self.epoch);
+                let now = now.as_secs() * 1_000 + u64::from(now.subsec_millis());
+                self.last_ready.store(now, Ordering::Release);
+                let not_ready = self.not_ready.swap(0, Ordering::AcqRel);
+                if not_ready > 0 {
+                    trace!(not_ready, "service became ready");
+                }
+            }
+            Ok(Async::NotReady) => {
+                let not_ready = self.not_ready.fetch_add(1, Ordering::AcqRel);
+                if not_ready == 0 && self.last_ready.load(Ordering::Acquire) != NEVER {
+                    debug!("service is no longer ready");
+                }
+            }
+            Err(_) => {
+                if !self.failed.swap(true, Ordering::AcqRel) {
+                    debug!("service failed");
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::svc::Service as _;
+    use futures::future;
+    use linkerd2_error::Error;
+
+    /// A service whose readiness is set by the test.
+    struct Readiness {
+        ready: bool,
+        failed: bool,
+    }
+
+    impl svc::Service<()> for Readiness {
+        type Response = ();
+        type Error = Error;
+        type Future = future::FutureResult<(), Error>;
+
+        fn poll_ready(&mut self) -> Poll<(), Error> {
+            if self.failed {
+                return Err("failed".into());
+            }
+            if self.ready {
+                Ok(Async::Ready(()))
+            } else {
+                Ok(Async::NotReady)
+            }
+        }
+
+        fn call(&mut self, _: ()) -> Self::Future {
+            future::ok(())
+        }
+    }
+
+    fn monitor() -> HealthMonitor<Readiness> {
+        let inner = Readiness {
+            ready: true,
+            failed: false,
+        };
+        svc::Layer::layer(&layer(), inner)
+    }
+
+    #[test]
+    fn records_readiness() {
+        let mut svc = monitor();
+        let report = svc.report();
+        assert!(report.last_ready().is_none());
+
+        assert!(svc.poll_ready().unwrap().is_ready());
+        let last_ready = report.last_ready().expect("service was ready");
+        assert_eq!(report.consecutive_not_ready(), 0);
+
+        svc.inner.ready = false;
+        assert!(svc.poll_ready().unwrap().is_not_ready());
+        assert!(svc.poll_ready().unwrap().is_not_ready());
+        assert_eq!(report.consecutive_not_ready(), 2);
+        assert_eq!(report.last_ready(), Some(last_ready));
+        assert!(!report.has_failed());
+
+        svc.inner.ready = true;
+        assert!(svc.poll_ready().unwrap().is_ready());
+        assert_eq!(report.consecutive_not_ready(), 0);
+
+        svc.inner.failed = true;
+        assert!(svc.poll_ready().is_err());
+        assert!(report.has_failed());
+    }
+
+    #[test]
+    fn does_not_record_without_report() {
+        let mut svc = monitor();
+        svc.inner.ready = false;
+        assert!(svc.poll_ready().unwrap().is_not_ready());
+
+        // Polls made before the report was held were not recorded.
+        let report = svc.report();
+        assert_eq!(report.consecutive_not_ready(), 0);
+        assert!(svc.poll_ready().unwrap().is_not_ready());
+        assert_eq!(report.consecutive_not_ready(), 1);
+
+        drop(report);
+        assert!(svc.poll_ready().unwrap().is_not_ready());
+        assert_eq!(svc.report().consecutive_not_ready(), 1);
+    }
+}
@@ -12,7 +12,13 @@ pub use linkerd2_proxy_tap as tap;
 pub use linkerd2_proxy_tcp as tcp;
 
 pub mod buffer;
+pub mod coalesce;
+pub mod error_context;
+pub mod failure_accrual;
+pub mod health_monitor;
 pub mod pending;
+pub mod rate_limit;
+pub mod retry;
 pub mod server;
 
 pub use self::server::Server;
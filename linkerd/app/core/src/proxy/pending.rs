@@ -1,41 +1,170 @@
 use crate::svc::{self, ServiceExt};
-use futures::{try_ready, Future, Poll};
+use futures::{sync::oneshot, try_ready, Async, Future, Poll, Stream};
 use linkerd2_error::Error;
+use linkerd2_exp_backoff::{ExponentialBackoff, ExponentialBackoffStream};
 use linkerd2_router as rt;
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio_timer::{clock, Delay};
+use tracing::{debug, info_span, warn};
+use tracing_futures::{Instrument, Instrumented};
+
+/// The longest backoff between attempts to make a service, unless the base
+/// backoff is longer.
+const MAX_RETRY_BACKOFF: Duration = Duration::from_secs(10);
 
 #[derive(Copy, Clone, Debug)]
-pub struct Layer(());
+pub struct Layer {
+    timeout: Option<Duration>,
+    retry: Option<Retry>,
+}
 
 #[derive(Clone, Debug)]
 pub struct MakePending<M> {
     inner: M,
+    timeout: Option<Duration>,
+    retry: Option<Retry>,
+    /// The number of services made by this `MakePending` and its clones that
+    /// are still being made.
+    pending: Arc<AtomicUsize>,
+}
+
+#[derive(Copy, Clone, Debug)]
+struct Retry {
+    backoff: ExponentialBackoff,
+    max_attempts: Option<u32>,
+}
+
+/// Makes a service, retrying failures with an exponential backoff when
+/// configured to do so.
+pub struct RetryPending<M: svc::Service<T>, T> {
+    make: M,
+    target: T,
+    /// Unset while backing off.
+    future: Option<svc::Oneshot<M, T>>,
+    backoff: Option<ExponentialBackoffStream>,
+    attempts: u32,
+    max_attempts: Option<u32>,
 }
 
 /// Creates a `Service` immediately, even while the future making the service
 /// is still pending.
 pub enum Pending<F, S> {
-    Making(F),
+    Making {
+        future: F,
+        timeout: Option<MakeTimeout>,
+        _pending: PendingHandle,
+    },
     Made(S),
+    /// A request was dispatched before the service was made, so the make was
+    /// moved into its response future, which returns the service once it has
+    /// been made.
+    Lent(oneshot::Receiver<S>),
 }
 
-pub type Svc<M, T> = Pending<svc::Oneshot<M, T>, <M as svc::Service<T>>::Response>;
+/// The response future of a `Pending` service.
+pub enum ResponseFuture<F, S, Req>
+where
+    S: svc::Service<Req>,
+{
+    /// The request was dispatched before the service was made, so the
+    /// service is made before the request is dispatched to it.
+    Making {
+        future: F,
+        timeout: Option<MakeTimeout>,
+        request: Option<Req>,
+        made: Option<S>,
+        lender: Option<oneshot::Sender<S>>,
+        _pending: PendingHandle,
+    },
+    Called(S::Future),
+    Failed(Option<Error>),
+}
+
+/// Counts a service as pending until it is dropped, i.e. when the `Pending`
+/// service transitions to `Made` or is itself dropped.
+pub struct PendingHandle(Arc<AtomicUsize>);
+
+/// Bounds how long a `Pending` service may wait for its service to be made.
+pub struct MakeTimeout {
+    delay: Delay,
+    duration: Duration,
+}
+
+/// Indicates that a service was not made before the pending timeout elapsed.
+#[derive(Copy, Clone, Debug)]
+pub struct PendingTimeout(Duration);
+
+/// Indicates that a request dispatched before its service was made was
+/// canceled, along with the making of the service.
+#[derive(Copy, Clone, Debug)]
+pub struct MakeCanceled(());
+
+pub type Svc<M, T> = Pending<Instrumented<RetryPending<M, T>>, <M as svc::Service<T>>::Response>;
 
+/// Waits indefinitely for services to be made.
 pub fn layer() -> Layer {
-    Layer(())
+    Layer {
+        timeout: None,
+        retry: None,
+    }
+}
+
+/// Fails services that are not made within `timeout`.
+pub fn layer_with_timeout(timeout: Duration) -> Layer {
+    Layer {
+        timeout: Some(timeout),
+        retry: None,
+    }
 }
 
 // === impl Layer ===
 
+impl Layer {
+    /// Retries failures to make a service after an exponential backoff,
+    /// starting at `base`, until `max_attempts` have been made.
+    ///
+    /// When a timeout is also configured, it bounds all attempts.
+    pub fn with_retry(self, base: Duration, max_attempts: Option<u32>) -> Self {
+        let backoff = ExponentialBackoff {
+            min: base,
+            max: base.max(MAX_RETRY_BACKOFF),
+            jitter: 0.5,
+        };
+        Self {
+            retry: Some(Retry {
+                backoff,
+                max_attempts,
+            }),
+            ..self
+        }
+    }
+}
+
 impl<M> svc::Layer<M> for Layer {
     type Service = MakePending<M>;
 
     fn layer(&self, inner: M) -> Self::Service {
-        MakePending { inner }
+        MakePending {
+            inner,
+            timeout: self.timeout,
+            retry: self.retry,
+            pending: Arc::new(AtomicUsize::new(0)),
+        }
     }
 }
 
 // === impl MakePending ===
 
+impl<M> MakePending<M> {
+    /// Returns the number of services that are still being made.
+    pub fn pending_count(&self) -> usize {
+        self.pending.load(Ordering::Acquire)
+    }
+}
+
 impl<T, M> rt::Make<T> for MakePending<M>
 where
     M: svc::Service<T> + Clone,
@@ -45,8 +174,18 @@ where
     type Value = Svc<M, T>;
 
     fn make(&self, target: &T) -> Self::Value {
-        let fut = self.inner.clone().oneshot(target.clone());
-        Pending::Making(fut)
+        let capacity = self.pending.fetch_add(1, Ordering::AcqRel) + 1;
+        let future = RetryPending::new(self.inner.clone(), target.clone(), self.retry)
+            .instrument(info_span!("pending_make", capacity = capacity));
+        let timeout = self.timeout.map(|duration| MakeTimeout {
+            delay: Delay::new(clock::now() + duration),
+            duration,
+        });
+        Pending::Making {
+            future,
+            timeout,
+            _pending: PendingHandle(self.pending.clone()),
+        }
     }
 }
 
@@ -61,11 +200,22 @@ where
 {
     type Response = S::Response;
     type Error = Error;
-    type Future = futures::future::MapErr<S::Future, fn(S::Error) -> Error>;
+    type Future = ResponseFuture<F, S, Req>;
 
     fn poll_ready(&mut self) -> Poll<(), Self::Error> {
         let mut svc = match self {
-            Pending::Making(fut) => try_ready!(fut.poll().map_err(Into::into)),
+            Pending::Making {
+                future, timeout, ..
+            } => match future.poll().map_err(Into::into)? {
+                Async::Ready(svc) => svc,
+                Async::NotReady => {
+                    if let Some(timeout) = timeout {
+                        timeout.poll()?;
+                    }
+                    return Ok(Async::NotReady);
+                }
+            },
+            Pending::Lent(rx) => try_ready!(rx.poll().map_err(|_| MakeCanceled(()))),
             Pending::Made(s) => return s.poll_ready().map_err(Into::into),
         };
 
@@ -76,8 +226,437 @@ where
 
     fn call(&mut self, req: Req) -> Self::Future {
         match self {
-            Pending::Making(_) => panic!("pending not ready yet"),
-            Pending::Made(s) => s.call(req).map_err(Into::into),
+            Pending::Made(s) => return ResponseFuture::Called(s.call(req)),
+            Pending::Lent(_) => {
+                let error = "service is being made for another request".into();
+                return ResponseFuture::Failed(Some(error));
+            }
+            Pending::Making { .. } => {}
+        }
+
+        // The request was dispatched before the service was made, e.g. by a
+        // layer that does not drive readiness. Rather than failing, the
+        // response future makes the service and lends it back once the
+        // request has been dispatched.
+        warn!("request dispatched before service was made");
+        let (tx, rx) = oneshot::channel();
+        match std::mem::replace(self, Pending::Lent(rx)) {
+            Pending::Making {
+                future,
+                timeout,
+                _pending,
+            } => ResponseFuture::Making {
+                future,
+                timeout,
+                request: Some(req),
+                made: None,
+                lender: Some(tx),
+                _pending,
+            },
+            Pending::Made(_) | Pending::Lent(_) => unreachable!(),
+        }
+    }
+}
+
+// === impl ResponseFuture ===
+
+impl<F, S, Req> Future for ResponseFuture<F, S, Req>
+where
+    F: Future<Item = S>,
+    F::Error: Into<Error>,
+    S: svc::Service<Req>,
+    S::Error: Into<Error>,
+{
+    type Item = S::Response;
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        loop {
+            *self = match self {
+                ResponseFuture::Called(future) => return future.poll().map_err(Into::into),
+                ResponseFuture::Failed(error) => {
+                    return Err(error.take().expect("polled after failure"));
+                }
+                ResponseFuture::Making {
+                    future,
+                    timeout,
+                    request,
+                    made,
+                    lender,
+                    ..
+                } => {
+                    if made.is_none() {
+                        match future.poll().map_err(Into::into)? {
+                            Async::Ready(svc) => *made = Some(svc),
+                            Async::NotReady => {
+                                if let Some(timeout) = timeout {
+                                    timeout.poll()?;
+                                }
+                                return Ok(Async::NotReady);
+                            }
+                        }
+                    }
+
+                    let mut svc = made.take().expect("service must be made");
+                    match svc.poll_ready() {
+                        Ok(Async::Ready(())) => {}
+                        Ok(Async::NotReady) => {
+                            *made = Some(svc);
+                            return Ok(Async::NotReady);
+                        }
+                        Err(e) => {
+                            // The service is returned so that the error is
+                            // observed by the `Pending` service as well.
+                            let _ = lender.take().expect("polled after ready").send(svc);
+                            return Err(e.into());
+                        }
+                    }
+
+                    let req = request.take().expect("polled after ready");
+                    let called = svc.call(req);
+                    let _ = lender.take().expect("polled after ready").send(svc);
+                    ResponseFuture::Called(called)
+                }
+            };
+        }
+    }
+}
+
+// === impl MakeTimeout ===
+
+impl MakeTimeout {
+    /// Fails once the timeout has elapsed.
+    fn poll(&mut self) -> Result<(), Error> {
+        if self.delay.poll().map_err(Error::from)?.is_ready() {
+            warn!(timeout = ?self.duration, "service was not made in time");
+            return Err(PendingTimeout(self.duration).into());
+        }
+        Ok(())
+    }
+}
+
+// === impl RetryPending ===
+
+impl<M, T> RetryPending<M, T>
+where
+    M: svc::Service<T> + Clone,
+    T: Clone,
+{
+    fn new(make: M, target: T, retry: Option<Retry>) -> Self {
+        let future = Some(make.clone().oneshot(target.clone()));
+        Self {
+            make,
+            target,
+            future,
+            backoff: retry.map(|r| r.backoff.stream()),
+            attempts: 1,
+            // Without a retry policy, only the first attempt is made.
+            max_attempts: retry.map(|r| r.max_attempts).unwrap_or(Some(1)),
+        }
+    }
+}
+
+impl<M, T> Future for RetryPending<M, T>
+where
+    M: svc::Service<T> + Clone,
+    M::Error: Into<Error>,
+    T: Clone,
+{
+    type Item = M::Response;
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        loop {
+            if let Some(future) = self.future.as_mut() {
+                let error: Error = match future.poll() {
+                    Ok(ready) => return Ok(ready),
+                    Err(e) => e.into(),
+                };
+                let exhausted = self
+                    .max_attempts
+                    .map(|max| self.attempts >= max)
+                    .unwrap_or(false);
+                if exhausted || self.backoff.is_none() {
+                    return Err(error);
+                }
+                debug!(%error, attempts = self.attempts, "retrying make");
+                self.future = None;
+            }
+
+            // The backoff stream only completes after an absurd number of
+            // iterations, in which case the make is attempted anyway.
+            if let Some(backoff) = self.backoff.as_mut() {
+                try_ready!(backoff.poll().map_err(Error::from));
+            }
+            self.attempts += 1;
+            self.future = Some(self.make.clone().oneshot(self.target.clone()));
+        }
+    }
+}
+
+// === impl PendingHandle ===
+
+impl Drop for PendingHandle {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+// === impl PendingTimeout ===
+
+impl PendingTimeout {
+    /// Returns how long the service was awaited.
+    pub fn duration(&self) -> Duration {
+        self.0
+    }
+}
+
+impl fmt::Display for PendingTimeout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "service was not made within {:?}", self.0)
+    }
+}
+
+impl std::error::Error for PendingTimeout {}
+
+// === impl MakeCanceled ===
+
+impl fmt::Display for MakeCanceled {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "service was not made because its request was canceled")
+    }
+}
+
+impl std::error::Error for MakeCanceled {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::future;
+    use rt::Make as _;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use std::sync::Arc;
+    use svc::{Layer as _, Service as _};
+    use tokio::runtime::current_thread::Runtime;
+
+    /// A service that is never made.
+    struct Unmade;
+
+    impl svc::Service<()> for Unmade {
+        type Response = ();
+        type Error = Error;
+        type Future = future::FutureResult<(), Error>;
+
+        fn poll_ready(&mut self) -> Poll<(), Error> {
+            unreachable!("service must not be made");
+        }
+
+        fn call(&mut self, (): ()) -> Self::Future {
+            unreachable!("service must not be made");
         }
     }
+
+    fn never_made(layer: Layer) -> Svc<impl svc::Service<(), Response = Unmade> + Clone, ()> {
+        layer
+            .layer(svc::mk(|()| future::empty::<Unmade, Error>()))
+            .make(&())
+    }
+
+    #[test]
+    fn times_out_when_make_does_not_complete() {
+        let mut svc = never_made(layer_with_timeout(Duration::from_millis(10)));
+
+        let ready = Runtime::new()
+            .unwrap()
+            .block_on(future::poll_fn(|| svc.poll_ready()));
+        let err = ready.err().expect("pending service must time out");
+        assert!(err.is::<PendingTimeout>(), "unexpected error: {}", err);
+    }
+
+    #[test]
+    fn waits_indefinitely_by_default() {
+        let mut svc = never_made(layer());
+
+        let mut rt = Runtime::new().unwrap();
+        rt.block_on(Delay::new(clock::now() + Duration::from_millis(20)))
+            .unwrap();
+        let ready = rt.block_on(future::lazy(|| svc.poll_ready())).unwrap();
+        assert!(ready.is_not_ready());
+    }
+
+    /// A service that is always ready.
+    struct Ready;
+
+    impl svc::Service<()> for Ready {
+        type Response = ();
+        type Error = Error;
+        type Future = future::FutureResult<(), Error>;
+
+        fn poll_ready(&mut self) -> Poll<(), Error> {
+            Ok(Async::Ready(()))
+        }
+
+        fn call(&mut self, (): ()) -> Self::Future {
+            future::ok(())
+        }
+    }
+
+    /// Makes a pending service whose first `failures` attempts to be made
+    /// fail, and returns it with the number of attempts that were made.
+    fn flaky(
+        layer: Layer,
+        failures: usize,
+    ) -> (
+        Svc<impl svc::Service<(), Response = Ready> + Clone, ()>,
+        Arc<AtomicUsize>,
+    ) {
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let make = {
+            let attempts = attempts.clone();
+            svc::mk(move |()| {
+                if attempts.fetch_add(1, Ordering::SeqCst) < failures {
+                    future::err::<Ready, Error>("unavailable".into())
+                } else {
+                    future::ok(Ready)
+                }
+            })
+        };
+        (layer.layer(make).make(&()), attempts)
+    }
+
+    #[test]
+    fn retries_failed_makes() {
+        let layer = layer().with_retry(Duration::from_millis(1), None);
+        let (mut svc, attempts) = flaky(layer, 2);
+
+        Runtime::new()
+            .unwrap()
+            .block_on(future::poll_fn(|| svc.poll_ready()))
+            .expect("service must be made");
+        assert_eq!(attempts.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn stops_retrying_after_max_attempts() {
+        let layer = layer().with_retry(Duration::from_millis(1), Some(2));
+        let (mut svc, attempts) = flaky(layer, 2);
+
+        Runtime::new()
+            .unwrap()
+            .block_on(future::poll_fn(|| svc.poll_ready()))
+            .err()
+            .expect("service must fail");
+        assert_eq!(attempts.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn does_not_retry_by_default() {
+        let (mut svc, attempts) = flaky(layer(), 1);
+
+        Runtime::new()
+            .unwrap()
+            .block_on(future::poll_fn(|| svc.poll_ready()))
+            .err()
+            .expect("service must fail");
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn call_before_ready_makes_the_service() {
+        let make = layer().layer(svc::mk(|()| future::ok::<_, Error>(Ready)));
+        let mut svc = make.make(&());
+        assert_eq!(make.pending_count(), 1);
+
+        // The request is dispatched without first driving readiness.
+        let rsp = svc.call(());
+        Runtime::new()
+            .unwrap()
+            .block_on(rsp)
+            .expect("request must be dispatched once the service is made");
+        assert_eq!(make.pending_count(), 0);
+
+        // The service is returned to the pending service once it is made.
+        Runtime::new()
+            .unwrap()
+            .block_on(future::poll_fn(|| svc.poll_ready()))
+            .expect("service must be ready");
+        Runtime::new()
+            .unwrap()
+            .block_on(svc.call(()))
+            .expect("request must succeed");
+    }
+
+    #[test]
+    fn call_before_ready_times_out() {
+        let mut svc = never_made(layer_with_timeout(Duration::from_millis(10)));
+
+        let err = Runtime::new()
+            .unwrap()
+            .block_on(svc.call(()))
+            .err()
+            .expect("request must time out");
+        assert!(err.is::<PendingTimeout>(), "unexpected error: {}", err);
+
+        // The make was canceled along with the request.
+        let err = Runtime::new()
+            .unwrap()
+            .block_on(future::poll_fn(|| svc.poll_ready()))
+            .err()
+            .expect("pending service must fail");
+        assert!(err.is::<MakeCanceled>(), "unexpected error: {}", err);
+    }
+
+    #[test]
+    fn counts_pending_services() {
+        let make = layer().layer(svc::mk(|()| future::ok::<_, Error>(Ready)));
+        assert_eq!(make.pending_count(), 0);
+
+        let mut made = make.make(&());
+        let unmade = make.make(&());
+        assert_eq!(make.pending_count(), 2);
+
+        Runtime::new()
+            .unwrap()
+            .block_on(future::poll_fn(|| made.poll_ready()))
+            .expect("service must be made");
+        assert_eq!(make.pending_count(), 1);
+
+        drop(unmade);
+        assert_eq!(make.pending_count(), 0);
+    }
+
+    #[test]
+    fn dropping_pending_service_cancels_make() {
+        /// Records when the make future is dropped.
+        struct OnDrop(Arc<AtomicUsize>);
+
+        impl Drop for OnDrop {
+            fn drop(&mut self) {
+                self.0.fetch_add(1, Ordering::SeqCst);
+            }
+        }
+
+        let dropped = Arc::new(AtomicUsize::new(0));
+        let make = {
+            let dropped = dropped.clone();
+            svc::mk(move |()| {
+                let on_drop = OnDrop(dropped.clone());
+                future::empty::<Unmade, Error>().map(move |unmade| {
+                    drop(on_drop);
+                    unmade
+                })
+            })
+        };
+        let mut svc = layer().layer(make).make(&());
+
+        let ready = Runtime::new()
+            .unwrap()
+            .block_on(future::lazy(|| svc.poll_ready()))
+            .unwrap();
+        assert!(ready.is_not_ready());
+        assert_eq!(dropped.load(Ordering::SeqCst), 0);
+
+        drop(svc);
+        assert_eq!(dropped.load(Ordering::SeqCst), 1);
+    }
 }
@@ -0,0 +1,214 @@
+//! Limits the rate at which requests are dispatched to a service.
+//!
+//! Each service built by the layer has its own token bucket, which holds up
+//! to `rate` tokens and is refilled at `rate` tokens per `per`. A request may
+//! only be dispatched once a token has been taken from the bucket; while the
+//! bucket is empty, the service is not ready. Clones of a service share its
+//! bucket.
+
+use crate::svc;
+use futures::{try_ready, Future, Poll};
+use linkerd2_error::Error;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio_timer::{clock, Delay};
+use tracing::trace;
+
+#[derive(Copy, Clone, Debug)]
+pub struct RateLimitLayer {
+    rate: u64,
+    per: Duration,
+}
+
+#[derive(Debug)]
+pub struct RateLimit<S> {
+    inner: S,
+    bucket: Arc<Mutex<Bucket>>,
+    /// Set when a token has been taken for the next request.
+    permit: bool,
+    sleep: Option<Delay>,
+}
+
+#[derive(Debug)]
+struct Bucket {
+    capacity: f64,
+    /// The number of tokens added per second.
+    refill_rate: f64,
+    tokens: f64,
+    refilled_at: Instant,
+}
+
+// === impl RateLimitLayer ===
+
+impl RateLimitLayer {
+    /// Permits `rate` requests per `per`.
+    pub fn new(rate: u64, per: Duration) -> Self {
+        assert!(rate > 0, "rate must be positive");
+        assert!(per > Duration::from_secs(0), "period must be positive");
+        Self { rate, per }
+    }
+}
+
+impl<S> svc::Layer<S> for RateLimitLayer {
+    type Service = RateLimit<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        let capacity = self.rate as f64;
+        let period = self.per.as_secs() as f64 + f64::from(self.per.subsec_nanos()) / 1e9;
+        let bucket = Bucket {
+            capacity,
+            refill_rate: capacity / period,
+            tokens: capacity,
+            refilled_at: clock::now(),
+        };
+        RateLimit {
+            inner,
+            bucket: Arc::new(Mutex::new(bucket)),
+            permit: false,
+            sleep: None,
+        }
+    }
+}
+
+// === impl RateLimit ===
+
+impl<S: Clone> Clone for RateLimit<S> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            bucket: self.bucket.clone(),
+            permit: false,
+            sleep: None,
+        }
+    }
+}
+
+impl<S, Req> svc::Service<Req> for RateLimit<S>
+where
+    S: svc::Service<Req>,
+    S::Error: Into<Error>,
+{
+    type Response = S::Response;
+    type Error = Error;
+    type Future = futures::future::MapErr<S::Future, fn(S::Error) -> Error>;
+
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        while !self.permit {
+            if let Some(sleep) = self.sleep.as_mut() {
+                try_ready!(sleep.poll());
+                self.sleep = None;
+            }
+
+            let now = clock::now();
+            let mut bucket = self.bucket.lock().expect("rate limit bucket poisoned");
+            bucket.refill(now);
+            if bucket.tokens >= 1.0 {
+                bucket.tokens -= 1.0;
+                self.permit = true;
+            } else {
+                // Wait until the bucket holds a whole token.
+                let wait = (1.0 - bucket.tokens) / bucket.refill_rate;
+                let wait = Duration::from_nanos((wait * 1e9).ceil() as u64);
+                trace!(?wait, "rate limited");
+                self.sleep = Some(Delay::new(now + wait));
+            }
+        }
+
+        self.inner.poll_ready().map_err(Into::into)
+    }
+
+    fn call(&mut self, req: Req) -> Self::Future {
+        assert!(self.permit, "called before ready");
+        self.permit = false;
+        self.inner.call(req).map_err(Into::into)
+    }
+}
+
+// === impl Bucket ===
+
+impl Bucket {
+    fn refill(&mut self, now: Instant) {
+        if now <= self.refilled_at {
+            return;
+        }
+        let elapsed = now - self.refilled_at;
+        let elapsed = elapsed.as_secs() as f64 + f64::from(elapsed.subsec_nanos()) / 1e9;
+        self.tokens = (self.tokens + elapsed * self.refill_rate).min(self.capacity);
+        self.refilled_at = now;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{future, Async};
+    use tokio::runtime::current_thread::Runtime;
+
+    fn inner() -> impl svc::Service<(), Response = (), Error = Error> + Clone {
+        svc::mk(|_: ()| future::ok::<(), Error>(()))
+    }
+
+    /// Dispatches `n` requests as quickly as the limit permits.
+    fn send<S>(limited: &mut S, n: usize) -> impl Future<Item = (), Error = Error> + '_
+    where
+        S: svc::Service<(), Response = (), Error = Error>,
+    {
+        let mut sent = 0;
+        future::poll_fn(move || {
+            while sent < n {
+                try_ready!(limited.poll_ready());
+                limited.call(()).wait()?;
+                sent += 1;
+            }
+            Ok(Async::Ready(()))
+        })
+    }
+
+    #[test]
+    fn respects_rate() {
+        const RATE: u64 = 100;
+        let mut rt = Runtime::new().unwrap();
+        let mut svc =
+            svc::Layer::layer(&RateLimitLayer::new(RATE, Duration::from_secs(1)), inner());
+
+        // Drain the initial burst, then measure the sustained throughput.
+        rt.block_on(send(&mut svc, RATE as usize)).unwrap();
+        let start = Instant::now();
+        rt.block_on(send(&mut svc, 50)).unwrap();
+        let elapsed = start.elapsed();
+
+        let expected = Duration::from_millis(500);
+        let tolerance = expected / 20;
+        assert!(
+            elapsed >= expected - tolerance && elapsed <= expected + tolerance,
+            "50 requests took {:?}; expected {:?}",
+            elapsed,
+            expected
+        );
+    }
+
+    #[test]
+    fn stacks_do_not_share_buckets() {
+        let mut rt = Runtime::new().unwrap();
+        let layer = RateLimitLayer::new(1, Duration::from_secs(60));
+        let mut a = svc::Layer::layer(&layer, inner());
+        let mut b = svc::Layer::layer(&layer, inner());
+
+        rt.block_on(future::lazy(|| {
+            assert!(a.poll_ready().unwrap().is_ready());
+            a.call(()).wait().unwrap();
+            assert!(a.poll_ready().unwrap().is_not_ready());
+
+            // Though `a` has exhausted its bucket, `b` has not.
+            assert!(b.poll_ready().unwrap().is_ready());
+            b.call(()).wait().unwrap();
+            assert!(b.poll_ready().unwrap().is_not_ready());
+
+            // Clones share a bucket.
+            let mut a2 = a.clone();
+            assert!(a2.poll_ready().unwrap().is_not_ready());
+            Ok::<(), ()>(())
+        }))
+        .unwrap();
+    }
+}
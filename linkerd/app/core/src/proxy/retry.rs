@@ -0,0 +1,419 @@
+//! Retries failed requests with a backoff, subject to a retry budget.
+//!
+//! A `RetryPolicy` decides whether each response (or error) should be
+//! retried and how long to wait before doing so. The policy is cloned for each
+//! request so that its backoff state is scoped to that request.
+//!
+//! A `RetryBudget` is shared by all services built by a layer. It tracks
+//! requests and retries over a sliding window, and a retry is only permitted
+//! while retries make up no more than a fixed fraction of that window's
+//! requests. This prevents retries from amplifying load on a service that is
+//! already failing.
+//!
+//! Requests are cloned before they are dispatched, so the inner service should
+//! typically be cheap to clone, e.g. a buffer.
+
+use crate::svc;
+use futures::{try_ready, Async, Future, Poll};
+use linkerd2_error::Error;
+use std::marker::PhantomData;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio_timer::{clock, Delay};
+use tracing::trace;
+
+/// The number of buckets in which a budget's window is tracked.
+const BUCKETS: usize = 10;
+
+/// Determines whether and when a request is retried.
+pub trait RetryPolicy<Req, Res, E> {
+    /// Returns true if the request should be retried given its result.
+    fn should_retry(&self, req: &Req, result: &Result<Res, E>) -> bool;
+
+    /// Returns how long to wait before the next retry.
+    fn backoff(&mut self) -> Duration;
+}
+
+/// Limits retries to a fraction of the requests in a sliding window.
+#[derive(Clone, Debug)]
+pub struct RetryBudget {
+    max_retry_ratio: f64,
+    bucket_width: Duration,
+    window: Arc<Mutex<Window>>,
+}
+
+/// Computes exponentially increasing backoffs, e.g. for use by a
+/// `RetryPolicy`.
+#[derive(Copy, Clone, Debug)]
+pub struct ExponentialBackoff {
+    min: Duration,
+    max: Duration,
+    next: Duration,
+}
+
+pub fn layer<P, Req>(policy: P) -> Layer<P, Req> {
+    Layer {
+        policy,
+        budget: RetryBudget::default(),
+        _req: PhantomData,
+    }
+}
+
+#[derive(Debug)]
+pub struct Layer<P, Req> {
+    policy: P,
+    budget: RetryBudget,
+    _req: PhantomData<fn(Req)>,
+}
+
+#[derive(Debug)]
+pub struct Retry<P, S, Req> {
+    policy: P,
+    budget: RetryBudget,
+    inner: S,
+    _req: PhantomData<fn(Req)>,
+}
+
+pub struct ResponseFuture<P, S, Req>
+where
+    S: svc::Service<Req>,
+{
+    policy: P,
+    budget: RetryBudget,
+    inner: S,
+    request: Req,
+    state: State<S::Future>,
+}
+
+enum State<F> {
+    Called(F),
+    Backoff(Delay),
+    Ready,
+}
+
+#[derive(Debug)]
+struct Window {
+    epoch: Instant,
+    buckets: [Bucket; BUCKETS],
+}
+
+#[derive(Copy, Clone, Debug, Default)]
+struct Bucket {
+    index: u64,
+    requests: u64,
+    retries: u64,
+}
+
+// === impl RetryBudget ===
+
+impl RetryBudget {
+    /// Permits retries up to `max_retry_ratio` of the requests observed within
+    /// `window`.
+    pub fn new(window: Duration, max_retry_ratio: f64) -> Self {
+        assert!(max_retry_ratio >= 0.0, "retry ratio must not be negative");
+        Self {
+            max_retry_ratio,
+            bucket_width: window / BUCKETS as u32,
+            window: Arc::new(Mutex::new(Window {
+                epoch: clock::now(),
+                buckets: [Bucket::default(); BUCKETS],
+            })),
+        }
+    }
+
+    /// Records an original request.
+    pub fn deposit(&self) {
+        let mut window = self.window.lock().expect("retry budget poisoned");
+        window.bucket(self.bucket_width).requests += 1;
+    }
+
+    /// Records a retry if the budget permits it.
+    pub fn try_withdraw(&self) -> bool {
+        let mut window = self.window.lock().expect("retry budget poisoned");
+        let (requests, retries) = window.totals(self.bucket_width);
+        if (retries + 1) as f64 > requests as f64 * self.max_retry_ratio {
+            return false;
+        }
+        window.bucket(self.bucket_width).retries += 1;
+        true
+    }
+}
+
+impl Default for RetryBudget {
+    /// Permits retries up to 20% of the requests in the last 10 seconds.
+    fn default() -> Self {
+        Self::new(Duration::from_secs(10), 0.2)
+    }
+}
+
+// === impl Window ===
+
+impl Window {
+    fn index(&self, bucket_width: Duration) -> u64 {
+        let elapsed = clock::now() - self.epoch;
+        let width = bucket_width.as_millis().max(1);
+        (elapsed.as_millis() / width) as u64
+    }
+
+    /// Returns the current bucket, clearing it if it was last used in an
+    /// earlier window.
+    fn bucket(&mut self, bucket_width: Duration) -> &mut Bucket {
+        let index = self.index(bucket_width);
+        let bucket = &mut self.buckets[(index % BUCKETS as u64) as usize];
+        if bucket.index != index {
+            *bucket = Bucket {
+                index,
+                ..Bucket::default()
+            };
+        }
+        bucket
+    }
+
+    /// Sums the requests and retries in buckets within the current window.
+    fn totals(&self, bucket_width: Duration) -> (u64, u64) {
+        let index = self.index(bucket_width);
+        self.buckets
+            .iter()
+            .filter(|b| b.index + BUCKETS as u64 > index)
+            .fold((0, 0), |(requests, retries), b| {
+                (requests + b.requests, retries + b.retries)
+            })
+    }
+}
+
+// === impl ExponentialBackoff ===
+
+impl ExponentialBackoff {
+    /// Backs off for `min`, doubling each time up to `max`.
+    pub fn new(min: Duration, max: Duration) -> Self {
+        assert!(min <= max, "minimum backoff must not exceed the maximum");
+        Self {
+            min,
+            max,
+            next: min,
+        }
+    }
+
+    pub fn next_backoff(&mut self) -> Duration {
+        let backoff = self.next;
+        self.next = (self.next * 2).min(self.max);
+        backoff
+    }
+
+    pub fn reset(&mut self) {
+        self.next = self.min;
+    }
+}
+
+// === impl Layer ===
+
+impl<P, Req> Layer<P, Req> {
+    /// Shares `budget` among the services built by this layer.
+    pub fn with_budget(self, budget: RetryBudget) -> Self {
+        Self { budget, ..self }
+    }
+}
+
+impl<P: Clone, Req> Clone for Layer<P, Req> {
+    fn clone(&self) -> Self {
+        Self {
+            policy: self.policy.clone(),
+            budget: self.budget.clone(),
+            _req: PhantomData,
+        }
+    }
+}
+
+impl<P: Clone, S, Req> svc::Layer<S> for Layer<P, Req> {
+    type Service = Retry<P, S, Req>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Retry {
+            policy: self.policy.clone(),
+            budget: self.budget.clone(),
+            inner,
+            _req: PhantomData,
+        }
+    }
+}
+
+// === impl Retry ===
+
+impl<P: Clone, S: Clone, Req> Clone for Retry<P, S, Req> {
+    fn clone(&self) -> Self {
+        Self {
+            policy: self.policy.clone(),
+            budget: self.budget.clone(),
+            inner: self.inner.clone(),
+            _req: PhantomData,
+        }
+    }
+}
+
+impl<P, S, Req> svc::Service<Req> for Retry<P, S, Req>
+where
+    P: RetryPolicy<Req, S::Response, Error> + Clone,
+    S: svc::Service<Req> + Clone,
+    S::Error: Into<Error>,
+    Req: Clone,
+{
+    type Response = S::Response;
+    type Error = Error;
+    type Future = ResponseFuture<P, S, Req>;
+
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        self.inner.poll_ready().map_err(Into::into)
+    }
+
+    fn call(&mut self, request: Req) -> Self::Future {
+        self.budget.deposit();
+        let future = self.inner.call(request.clone());
+        ResponseFuture {
+            policy: self.policy.clone(),
+            budget: self.budget.clone(),
+            inner: self.inner.clone(),
+            request,
+            state: State::Called(future),
+        }
+    }
+}
+
+// === impl ResponseFuture ===
+
+impl<P, S, Req> Future for ResponseFuture<P, S, Req>
+where
+    P: RetryPolicy<Req, S::Response, Error>,
+    S: svc::Service<Req>,
+    S::Error: Into<Error>,
+    Req: Clone,
+{
+    type Item = S::Response;
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        loop {
+            self.state = match self.state {
+                State::Called(ref mut future) => {
+                    let result = match future.poll() {
+                        Ok(Async::NotReady) => return Ok(Async::NotReady),
+                        Ok(Async::Ready(rsp)) => Ok(rsp),
+                        Err(e) => Err(e.into()),
+                    };
+
+                    if !self.policy.should_retry(&self.request, &result) {
+                        return result.map(Async::Ready);
+                    }
+                    if !self.budget.try_withdraw() {
+                        trace!("retry budget exhausted");
+                        return result.map(Async::Ready);
+                    }
+
+                    let backoff = self.policy.backoff();
+                    trace!(?backoff, "retrying");
+                    if backoff == Duration::from_secs(0) {
+                        State::Ready
+                    } else {
+                        State::Backoff(Delay::new(clock::now() + backoff))
+                    }
+                }
+                State::Backoff(ref mut delay) => {
+                    try_ready!(delay.poll());
+                    State::Ready
+                }
+                State::Ready => {
+                    try_ready!(self.inner.poll_ready().map_err(Into::into));
+                    State::Called(self.inner.call(self.request.clone()))
+                }
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::future;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// Retries every failure without backing off.
+    #[derive(Clone)]
+    struct RetryFailures;
+
+    impl RetryPolicy<(), (), Error> for RetryFailures {
+        fn should_retry(&self, _: &(), result: &Result<(), Error>) -> bool {
+            result.is_err()
+        }
+
+        fn backoff(&mut self) -> Duration {
+            Duration::from_secs(0)
+        }
+    }
+
+    fn failing(
+        calls: Arc<AtomicUsize>,
+    ) -> impl svc::Service<(), Response = (), Error = Error> + Clone {
+        svc::mk(move |_: ()| {
+            calls.fetch_add(1, Ordering::SeqCst);
+            future::err::<(), Error>("failed".into())
+        })
+    }
+
+    fn send<S>(svc: &mut S) -> Result<(), Error>
+    where
+        S: svc::Service<(), Response = (), Error = Error>,
+    {
+        svc.poll_ready()?;
+        svc.call(()).wait()
+    }
+
+    #[test]
+    fn retries_within_budget() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let mut svc = svc::Layer::layer(
+            &layer(RetryFailures).with_budget(RetryBudget::new(Duration::from_secs(10), 1.0)),
+            failing(calls.clone()),
+        );
+
+        // The first request is retried once, using the budget it deposited.
+        send(&mut svc).unwrap_err();
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn budget_exhaustion_stops_retries() {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let mut svc = svc::Layer::layer(
+            &layer(RetryFailures).with_budget(RetryBudget::new(Duration::from_secs(10), 0.2)),
+            failing(calls.clone()),
+        );
+
+        // Though every failure should be retried, only one retry is permitted
+        // per five requests.
+        for _ in 0..10 {
+            send(&mut svc).unwrap_err();
+        }
+        assert_eq!(calls.load(Ordering::SeqCst), 12);
+    }
+
+    #[test]
+    fn budget_is_shared_by_clones() {
+        let budget = RetryBudget::new(Duration::from_secs(10), 0.5);
+        let clone = budget.clone();
+        budget.deposit();
+        clone.deposit();
+        assert!(budget.try_withdraw());
+        assert!(!clone.try_withdraw());
+    }
+
+    #[test]
+    fn exponential_backoff_is_bounded() {
+        let mut backoff =
+            ExponentialBackoff::new(Duration::from_millis(10), Duration::from_millis(35));
+        assert_eq!(backoff.next_backoff(), Duration::from_millis(10));
+        assert_eq!(backoff.next_backoff(), Duration::from_millis(20));
+        assert_eq!(backoff.next_backoff(), Duration::from_millis(35));
+        assert_eq!(backoff.next_backoff(), Duration::from_millis(35));
+        backoff.reset();
+        assert_eq!(backoff.next_backoff(), Duration::from_millis(10));
+    }
+}
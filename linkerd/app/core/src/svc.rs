@@ -1,40 +1,99 @@
-use crate::proxy::{buffer, http, pending};
+use crate::config::{CircuitBreakerConfig, FailureAccrualConfig};
+use crate::proxy::{
+    buffer, coalesce, error_context, failure_accrual, health_monitor, http, pending, rate_limit,
+    retry,
+};
+use crate::transport;
 use crate::Error;
 pub use linkerd2_router::Make;
-pub use linkerd2_stack::{self as stack, layer, map_target, Layer, LayerExt, Shared};
+pub use linkerd2_stack::blueprint::{self, Blueprint};
+use linkerd2_stack::describe::Pair;
+pub use linkerd2_stack::describe::{self, DescribeStack};
+pub use linkerd2_stack::{
+    self as stack, layer, map_err, map_response, map_target, switch, when, Layer, LayerExt, Shared,
+};
+pub use linkerd2_timeout::connect as connect_timeout;
 pub use linkerd2_timeout::stack as timeout;
 use std::time::Duration;
-use tower::layer::util::{Identity, Stack as Pair};
+use tower::layer::util::Identity;
 use tower::limit::concurrency::ConcurrencyLimitLayer;
 use tower::load_shed::LoadShedLayer;
-use tower::timeout::TimeoutLayer;
 pub use tower::util::{Either, Oneshot};
 pub use tower::{service_fn as mk, MakeConnection, MakeService, Service, ServiceExt};
 use tower_spawn_ready::SpawnReadyLayer;
 
+/// Composes layers, recording the named layers and anchors that are pushed
+/// in a `Blueprint`.
 #[derive(Clone, Debug)]
-pub struct Layers<L>(L);
+pub struct Layers<L>(L, Blueprint);
 
+/// Builds a stack, recording the named layers and anchors that are pushed in
+/// a `Blueprint`.
+///
+/// The names of all pushed layers and anchors are also recorded, from
+/// innermost to outermost, so that the stack may be described.
 #[derive(Clone, Debug)]
-pub struct Stack<S>(S);
+pub struct Stack<S>(S, Blueprint, Vec<&'static str>);
+
+/// A composition of `L` with `O` pushed over it.
+pub type Pushed<L, O> = Pair<L, describe::Named<O>>;
 
 pub fn layers() -> Layers<Identity> {
-    Layers(Identity::new())
+    Layers(Identity::new(), Blueprint::default())
 }
 
 pub fn stack<S>(inner: S) -> Stack<S> {
-    Stack(inner)
+    Stack(inner, Blueprint::default(), Vec::new())
 }
 
 // Possibly unused, but useful during development.
 #[allow(dead_code)]
 impl<L> Layers<L> {
-    pub fn push<O>(self, outer: O) -> Layers<Pair<L, O>> {
-        Layers(Pair::new(self.0, outer))
+    pub fn push<O>(self, outer: O) -> Layers<Pushed<L, O>> {
+        Layers(Pair::new(self.0, describe::Named::unnamed(outer)), self.1)
+    }
+
+    /// Pushes a layer that is recorded by name, e.g. so that it may be
+    /// ordered relative to other named entries.
+    pub fn push_named<N, O>(self, named: N, outer: O) -> Layers<Pushed<L, O>>
+    where
+        N: Into<blueprint::Named>,
+    {
+        let named = named.into();
+        let outer = describe::Named::new(named.name(), outer);
+        Layers(Pair::new(self.0, outer), self.1.push(named))
+    }
+
+    /// Declares a named anchor at this point in the composition.
+    pub fn push_anchor(self, name: &'static str) -> Layers<Pushed<L, Identity>> {
+        let anchor = describe::Named::new(name, Identity::new());
+        Layers(Pair::new(self.0, anchor), self.1.push_anchor(name))
+    }
+
+    pub fn blueprint(&self) -> &Blueprint {
+        &self.1
+    }
+
+    /// Lists the composition's layers and anchors, from outermost to
+    /// innermost. Layers that were not pushed by name are listed as
+    /// `describe::UNNAMED`.
+    pub fn describe(&self) -> Vec<&'static str>
+    where
+        L: DescribeStack,
+    {
+        let mut names = Vec::new();
+        self.0.describe(&mut names);
+        names
+    }
+
+    /// Checks the composition against the constraints of its named layers.
+    pub fn validate(self) -> Result<Self, blueprint::Violation> {
+        self.1.validate()?;
+        Ok(self)
     }
 
     /// Buffer requests when when the next layer is out of capacity.
-    pub fn push_pending(self) -> Layers<Pair<L, pending::Layer>> {
+    pub fn push_pending(self) -> Layers<Pushed<L, pending::Layer>> {
         self.push(pending::layer())
     }
 
@@ -43,19 +102,118 @@ impl<L> Layers<L> {
         self,
         bound: usize,
         d: D,
-    ) -> Layers<Pair<Pair<L, pending::Layer>, buffer::Layer<D, Req>>>
+    ) -> Layers<Pushed<Pushed<Pushed<L, pending::Layer>, buffer::Layer<D, Req>>, Identity>>
+    where
+        D: buffer::Deadline<Req>,
+        Req: Send + 'static,
+    {
+        self.push_buffer_pending_with_metrics(bound, d, buffer::Metrics::default())
+    }
+
+    /// Buffer requests when when the next layer is out of capacity, recording
+    /// the buffer's queue depth and wait times to `metrics`.
+    pub fn push_buffer_pending_with_metrics<D, Req>(
+        self,
+        bound: usize,
+        d: D,
+        metrics: buffer::Metrics,
+    ) -> Layers<Pushed<Pushed<Pushed<L, pending::Layer>, buffer::Layer<D, Req>>, Identity>>
     where
         D: buffer::Deadline<Req>,
         Req: Send + 'static,
     {
-        self.push_pending().push(buffer::layer(bound, d))
+        self.push_pending()
+            .push(buffer::layer(bound, d).with_metrics(metrics))
+            .push_anchor(blueprint::BUFFER)
+    }
+
+    /// Maps the errors of the inner service, e.g. into an `Error` so that
+    /// it may be buffered.
+    pub fn push_map_err<F>(self, map_err: F) -> Layers<Pushed<L, map_err::Layer<F>>> {
+        self.push(map_err::layer(map_err))
+    }
+
+    /// Maps the responses of the inner service, e.g. to wrap them in another
+    /// type.
+    pub fn push_map_response<F>(
+        self,
+        map_response: F,
+    ) -> Layers<Pushed<L, map_response::Layer<F>>> {
+        self.push(map_response::layer(map_response))
+    }
+
+    /// Maps the responses of each service made by the inner `MakeService`.
+    pub fn push_map_made_response<F>(
+        self,
+        map_response: F,
+    ) -> Layers<Pushed<L, stack::per_make::Layer<map_response::Layer<F>>>> {
+        self.push(map_response::layer(map_response).per_make())
+    }
+
+    /// Applies `layer` only to services made for targets that match
+    /// `predicate`; other targets' services are made by the inner stack.
+    pub fn push_when<P, O>(self, predicate: P, layer: O) -> Layers<Pushed<L, when::Layer<P, O>>> {
+        self.push(when::layer(predicate, layer))
+    }
+
+    /// Makes services with the inner stack for targets that match
+    /// `predicate`, and with `other` for all other targets.
+    pub fn push_switch<P, O>(
+        self,
+        predicate: P,
+        other: O,
+    ) -> Layers<Pushed<L, switch::Layer<P, O>>> {
+        self.push(switch::layer(predicate, other))
     }
 
-    pub fn push_spawn_ready(self) -> Layers<Pair<L, SpawnReadyLayer>> {
+    pub fn push_spawn_ready(self) -> Layers<Pushed<L, SpawnReadyLayer>> {
         self.push(SpawnReadyLayer::new())
     }
 
-    pub fn boxed<A, B>(self) -> Layers<Pair<L, http::boxed::Layer<A, B>>>
+    /// Annotates the errors of each made service with its target.
+    pub fn push_on_error_context(self) -> Layers<Pushed<L, error_context::Layer>> {
+        self.push_named("error-context", error_context::layer())
+    }
+
+    /// Stops dispatching requests to the inner service while its error rate
+    /// is too high.
+    ///
+    /// The breaker's transitions are not reported.
+    pub fn push_circuit_breaker(
+        self,
+        config: CircuitBreakerConfig,
+    ) -> Layers<Pushed<L, failure_accrual::Layer>> {
+        self.push_named(
+            "circuit-breaker",
+            failure_accrual::layer(Some(config.into()), failure_accrual::Metrics::default()),
+        )
+    }
+
+    /// Ejects each made service while it is consistently failing, if
+    /// `config` is set.
+    pub fn push_failure_accrual(
+        self,
+        config: Option<FailureAccrualConfig>,
+        metrics: failure_accrual::Metrics,
+    ) -> Layers<Pushed<L, stack::per_make::Layer<failure_accrual::Layer>>> {
+        self.push_named(
+            "failure-accrual",
+            failure_accrual::layer(config, metrics).per_make(),
+        )
+    }
+
+    /// Retries requests as determined by `policy`, within a retry budget.
+    ///
+    /// Each request is cloned before it is dispatched, so this should
+    /// typically be pushed over a buffer.
+    pub fn push_retry<P, Req>(self, policy: P) -> Layers<Pushed<L, retry::Layer<P, Req>>>
+    where
+        Req: Clone,
+    {
+        self.push(retry::layer(policy))
+    }
+
+    pub fn boxed<A, B>(self) -> Layers<Pushed<L, http::boxed::Layer<A, B>>>
     where
         A: 'static,
         B: hyper::body::Payload<Data = http::boxed::Data, Error = Error> + 'static,
@@ -76,7 +234,49 @@ impl<M, L: Layer<M>> Layer<M> for Layers<L> {
 #[allow(dead_code)]
 impl<S> Stack<S> {
     pub fn push<L: Layer<S>>(self, layer: L) -> Stack<L::Service> {
-        Stack(layer.layer(self.0))
+        self.push_described(describe::Named::unnamed(layer))
+    }
+
+    /// Pushes a layer that is recorded by name, e.g. so that it may be
+    /// ordered relative to other named entries.
+    pub fn push_named<N, L>(self, named: N, layer: L) -> Stack<L::Service>
+    where
+        N: Into<blueprint::Named>,
+        L: Layer<S>,
+    {
+        let named = named.into();
+        let layer = describe::Named::new(named.name(), layer);
+        let Stack(inner, blueprint, names) = self.push_described(layer);
+        Stack(inner, blueprint.push(named), names)
+    }
+
+    fn push_described<L: Layer<S>>(self, layer: describe::Named<L>) -> Stack<L::Service> {
+        let Stack(inner, blueprint, mut names) = self;
+        names.push(layer.name());
+        Stack(layer.layer(inner), blueprint, names)
+    }
+
+    /// Declares a named anchor at this point in the stack.
+    pub fn push_anchor(self, name: &'static str) -> Self {
+        let Stack(inner, blueprint, mut names) = self;
+        names.push(name);
+        Stack(inner, blueprint.push_anchor(name), names)
+    }
+
+    pub fn blueprint(&self) -> &Blueprint {
+        &self.1
+    }
+
+    /// Lists the stack's layers and anchors, from outermost to innermost.
+    /// Layers that were not pushed by name are listed as `describe::UNNAMED`.
+    pub fn describe(&self) -> Vec<&'static str> {
+        self.2.iter().rev().cloned().collect()
+    }
+
+    /// Checks the stack against the constraints of its named layers.
+    pub fn validate(self) -> Result<Self, blueprint::Violation> {
+        self.1.validate()?;
+        Ok(self)
     }
 
     /// Buffer requests when when the next layer is out of capacity.
@@ -94,23 +294,202 @@ impl<S> Stack<S> {
         D: buffer::Deadline<Req>,
         Req: Send + 'static,
     {
-        self.push_pending().push(buffer::layer(bound, d))
+        self.push_buffer_pending_with_metrics(bound, d, buffer::Metrics::default())
+    }
+
+    /// Buffer requests when when the next layer is out of capacity, recording
+    /// the buffer's queue depth and wait times to `metrics`.
+    pub fn push_buffer_pending_with_metrics<D, Req>(
+        self,
+        bound: usize,
+        d: D,
+        metrics: buffer::Metrics,
+    ) -> Stack<buffer::Make<pending::MakePending<S>, D, Req>>
+    where
+        D: buffer::Deadline<Req>,
+        Req: Send + 'static,
+    {
+        self.push_pending()
+            .push(buffer::layer(bound, d).with_metrics(metrics))
+            .push_anchor(blueprint::BUFFER)
+    }
+
+    /// Maps the errors of the inner service, e.g. into an `Error` so that
+    /// it may be buffered.
+    pub fn push_map_err<F: Clone>(self, map_err: F) -> Stack<map_err::MapErr<S, F>> {
+        self.push(map_err::layer(map_err))
+    }
+
+    /// Maps the responses of the inner service, e.g. to wrap them in another
+    /// type.
+    pub fn push_map_response<F: Clone>(
+        self,
+        map_response: F,
+    ) -> Stack<map_response::MapResponse<S, F>> {
+        self.push(map_response::layer(map_response))
+    }
+
+    /// Maps the responses of each service made by the inner `MakeService`.
+    pub fn push_map_made_response<F: Clone>(
+        self,
+        map_response: F,
+    ) -> Stack<stack::per_make::PerMake<map_response::Layer<F>, S>> {
+        self.push(map_response::layer(map_response).per_make())
+    }
+
+    /// Applies `layer` only to services made for targets that match
+    /// `predicate`; other targets' services are made by the inner stack.
+    pub fn push_when<P, L>(self, predicate: P, layer: L) -> Stack<when::MakeWhen<P, L::Service, S>>
+    where
+        P: Clone,
+        L: Layer<S>,
+        S: Clone,
+    {
+        self.push(when::layer(predicate, layer))
+    }
+
+    /// Makes services with this stack for targets that match `predicate`,
+    /// and with `other` for all other targets.
+    pub fn push_switch<P, O>(self, predicate: P, other: O) -> Stack<switch::MakeSwitch<P, S, O>>
+    where
+        P: Clone,
+        O: Clone,
+    {
+        self.push(switch::layer(predicate, other))
     }
 
     pub fn push_spawn_ready(self) -> Stack<tower_spawn_ready::MakeSpawnReady<S>> {
         self.push(SpawnReadyLayer::new())
     }
 
+    /// Limits the number of in-flight requests on this service. When the
+    /// service is shared, e.g. by a server, all of its requests share the
+    /// limit.
     pub fn push_concurrency_limit(self, max: usize) -> Stack<tower::limit::ConcurrencyLimit<S>> {
-        self.push(ConcurrencyLimitLayer::new(max))
+        self.push_named("concurrency-limit", ConcurrencyLimitLayer::new(max))
+    }
+
+    /// Limits the number of in-flight requests on each made service, so
+    /// that each target has its own limit.
+    pub fn push_per_make_concurrency_limit(
+        self,
+        max: usize,
+    ) -> Stack<stack::per_make::PerMake<ConcurrencyLimitLayer, S>> {
+        self.push_named(
+            "concurrency-limit",
+            ConcurrencyLimitLayer::new(max).per_make(),
+        )
+    }
+
+    /// Shares the inner service between clones of this stack, which acquire
+    /// it in the order that they wait for it. If the inner service fails
+    /// while it is held, waiters fail with its error but the lock is released
+    /// so that later requests retry the inner service.
+    pub fn push_lock(self) -> Stack<linkerd2_lock::Lock<S>> {
+        self.push_named("lock", linkerd2_lock::layer())
     }
 
     pub fn push_load_shed(self) -> Stack<tower::load_shed::LoadShed<S>> {
         self.push(LoadShedLayer::new())
     }
 
-    pub fn push_timeout(self, timeout: Duration) -> Stack<tower::timeout::Timeout<S>> {
-        self.push(TimeoutLayer::new(timeout))
+    /// Permits `rate` requests per `per`, using a token bucket that is not
+    /// shared with other stacks.
+    pub fn push_rate_limit(self, rate: u64, per: Duration) -> Stack<rate_limit::RateLimit<S>> {
+        self.push(rate_limit::RateLimitLayer::new(rate, per))
+    }
+
+    /// Stops dispatching requests to the inner service while its error rate
+    /// is too high.
+    ///
+    /// The breaker's transitions are not reported.
+    pub fn push_circuit_breaker(
+        self,
+        config: CircuitBreakerConfig,
+    ) -> Stack<failure_accrual::FailureAccrual<S>> {
+        self.push_named(
+            "circuit-breaker",
+            failure_accrual::layer(Some(config.into()), failure_accrual::Metrics::default()),
+        )
+    }
+
+    /// Ejects each made service while it is consistently failing, if
+    /// `config` is set.
+    pub fn push_failure_accrual(
+        self,
+        config: Option<FailureAccrualConfig>,
+        metrics: failure_accrual::Metrics,
+    ) -> Stack<stack::per_make::PerMake<failure_accrual::Layer, S>> {
+        self.push_named(
+            "failure-accrual",
+            failure_accrual::layer(config, metrics).per_make(),
+        )
+    }
+
+    /// Records the inner service's readiness, which may be observed with
+    /// `HealthMonitor::report`.
+    pub fn push_health_monitor(self) -> Stack<health_monitor::HealthMonitor<S>> {
+        self.push(health_monitor::layer())
+    }
+
+    /// Retries requests as determined by `policy`, within a retry budget.
+    ///
+    /// Each request is cloned before it is dispatched, so this should
+    /// typically be pushed over a buffer.
+    pub fn push_retry<P, Req>(self, policy: P) -> Stack<retry::Retry<P, S, Req>>
+    where
+        P: Clone,
+        Req: Clone,
+    {
+        self.push(retry::layer(policy))
+    }
+
+    /// Shares the response of an in-flight request with up to
+    /// `max_coalesced - 1` concurrent requests that have the same key.
+    ///
+    /// This should only be used for idempotent requests.
+    pub fn push_coalesce<K, Req>(
+        self,
+        key: K,
+        max_coalesced: usize,
+    ) -> Stack<coalesce::Coalesce<S, K, Req>>
+    where
+        S: Service<Req>,
+        K: coalesce::CoalesceKey<Req> + Clone,
+    {
+        self.push(coalesce::layer(key, max_coalesced))
+    }
+
+    /// Fails requests that do not complete within `timeout`. The error names
+    /// what the request was waiting for, e.g. "endpoint-response", so that it
+    /// may be reported.
+    pub fn push_timeout(
+        self,
+        name: &'static str,
+        timeout: Duration,
+    ) -> Stack<linkerd2_timeout::Timeout<S>> {
+        self.push(timeout::layer_named(name, timeout))
+    }
+
+    /// Fails each connection that is not established within its target's
+    /// connect timeout.
+    pub fn push_connect_timeout(self) -> Stack<connect_timeout::MakeConnect<S>> {
+        self.push(connect_timeout::layer())
+    }
+
+    /// Limits the number of concurrent connections to each peer address.
+    /// Targets without their own limit use `default`, if it is set.
+    pub fn push_connection_limit(
+        self,
+        default: Option<usize>,
+    ) -> Stack<transport::limit::MakeLimit<S>> {
+        self.push(transport::limit::layer(default))
+    }
+
+    /// Annotates the errors of each made service with its target, e.g. so
+    /// that connection errors describe the endpoint that refused them.
+    pub fn push_on_error_context(self) -> Stack<error_context::MakeContext<S>> {
+        self.push_named("error-context", error_context::layer())
     }
 
     pub fn boxed<T, A, B>(self) -> Stack<http::boxed::Make<S, A, B>>
@@ -138,6 +517,84 @@ impl<S> Stack<S> {
         self
     }
 
+    /// Validates that this stack makes `Req`-serving services for `T`-typed
+    /// targets.
+    pub fn routes<T, Req>(self) -> Self
+    where
+        S: Service<T>,
+        S::Response: Service<Req>,
+    {
+        self
+    }
+
+    /// Validates that this stack makes services for `T`-typed targets that
+    /// may be spawned onto another task.
+    ///
+    /// Missing `Send` or `'static` bounds otherwise only surface where the
+    /// stack is eventually spawned, far from the layer that introduced them:
+    ///
+    /// ```compile_fail
+    /// use linkerd2_app_core::svc;
+    /// use std::rc::Rc;
+    ///
+    /// let make = |_: &()| Rc::new(());
+    /// svc::stack(make).makes_spawnable::<()>();
+    /// ```
+    pub fn makes_spawnable<T>(self) -> Self
+    where
+        S: Make<T> + Send + 'static,
+        S::Value: Send + 'static,
+    {
+        self
+    }
+
+    /// Validates that this stack serves `T`-typed targets with services that
+    /// may be spawned onto another task.
+    ///
+    /// ```compile_fail
+    /// use futures::future;
+    /// use linkerd2_app_core::svc;
+    /// use std::rc::Rc;
+    ///
+    /// let make = svc::mk(|_: ()| future::ok::<_, ()>(Rc::new(())));
+    /// svc::stack(make).serves_spawnable::<()>();
+    /// ```
+    pub fn serves_spawnable<T>(self) -> Self
+    where
+        S: Service<T> + Send + 'static,
+        S::Response: Send + 'static,
+        S::Future: Send + 'static,
+    {
+        self
+    }
+
+    /// Validates that this stack makes cloneable services for `T`-typed
+    /// targets.
+    pub fn clones<T>(self) -> Self
+    where
+        S: Make<T>,
+        S::Value: Clone,
+    {
+        self
+    }
+
+    /// Validates that this stack makes `Req`-serving services for `T`-typed
+    /// targets.
+    ///
+    /// In debug builds, each made service asserts that no request is
+    /// dispatched to it before it is ready, i.e. that the layers pushed onto
+    /// this stack propagate its backpressure rather than queueing requests
+    /// within it.
+    pub fn check_backpressure<T, Req>(
+        self,
+    ) -> Stack<stack::per_make::PerMake<stack::check_ready::Layer, S>>
+    where
+        S: Service<T>,
+        S::Response: Service<Req>,
+    {
+        self.push(stack::per_make::layer(stack::check_ready::layer()))
+    }
+
     pub fn into_inner(self) -> S {
         self.0
     }
@@ -159,3 +616,139 @@ where
         self.0.call(t)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{future, Future};
+
+    #[derive(Debug, PartialEq)]
+    struct Wrapped(u16);
+
+    fn echo(
+    ) -> impl Service<u16, Response = u16, Error = (), Future = future::FutureResult<u16, ()>> + Clone
+    {
+        mk(|req: u16| future::ok::<_, ()>(req))
+    }
+
+    #[test]
+    fn maps_service_responses() {
+        let mut svc = stack(echo())
+            .push_map_response(Wrapped)
+            .serves::<u16>()
+            .into_inner();
+        assert_eq!(svc.call(7).wait(), Ok(Wrapped(7)));
+    }
+
+    #[test]
+    fn maps_made_service_responses() {
+        let make = mk(|_: ()| future::ok::<_, ()>(echo()));
+        let mut make = stack(make)
+            .push_map_made_response(Wrapped)
+            .routes::<(), u16>()
+            .into_inner();
+        let mut svc = make.call(()).wait().unwrap();
+        assert_eq!(svc.call(7).wait(), Ok(Wrapped(7)));
+    }
+
+    #[test]
+    fn layers_map_made_service_responses() {
+        let make = mk(|_: ()| future::ok::<_, ()>(echo()));
+        let mut make = stack(make)
+            .push(layers().push_map_made_response(Wrapped))
+            .routes::<(), u16>()
+            .into_inner();
+        let mut svc = make.call(()).wait().unwrap();
+        assert_eq!(svc.call(7).wait(), Ok(Wrapped(7)));
+    }
+
+    #[test]
+    fn validates_spawnable_stacks() {
+        let make = mk(|_: ()| future::ok::<_, ()>(echo()));
+        stack(make).serves_spawnable::<()>().routes::<(), u16>();
+
+        let make = |_: &()| echo();
+        stack(make).makes_spawnable::<()>().clones::<()>();
+    }
+
+    #[test]
+    fn switches_between_stacks() {
+        let make = mk(|_: u16| future::ok::<_, ()>(echo()));
+        let other = mk(|_: u16| future::ok::<_, ()>(Wrapped(0)));
+        let mut make = stack(make)
+            .push_switch(|n: &u16| *n > 0, other)
+            .into_inner();
+
+        match make.call(1).wait() {
+            Ok(Either::A(mut svc)) => assert_eq!(svc.call(7).wait(), Ok(7)),
+            _ => panic!("matching targets must be made by this stack"),
+        }
+        match make.call(0).wait() {
+            Ok(Either::B(wrapped)) => assert_eq!(wrapped, Wrapped(0)),
+            _ => panic!("other targets must be made by the alternate stack"),
+        }
+    }
+
+    #[test]
+    fn describes_stack_layers_from_outermost() {
+        let make = mk(|_: u16| future::ok::<_, ()>(echo()));
+        let stack = stack(make)
+            .push_on_error_context()
+            .push_named("tap", Identity::new())
+            .push_map_made_response(Wrapped)
+            .push_failure_accrual(None, failure_accrual::Metrics::default())
+            .push_per_make_concurrency_limit(1)
+            .push_anchor(blueprint::BUFFER);
+
+        assert_eq!(
+            stack.describe(),
+            vec![
+                blueprint::BUFFER,
+                "concurrency-limit",
+                "failure-accrual",
+                describe::UNNAMED,
+                "tap",
+                "error-context",
+            ]
+        );
+        assert_eq!(
+            stack.blueprint().names().collect::<Vec<_>>(),
+            vec![
+                "error-context",
+                "tap",
+                "failure-accrual",
+                "concurrency-limit",
+                blueprint::BUFFER,
+            ]
+        );
+    }
+
+    #[test]
+    fn per_make_concurrency_limits_are_per_target() {
+        let make = mk(|_: u16| future::ok::<_, ()>(echo()));
+        let mut make = stack(make).push_per_make_concurrency_limit(1).into_inner();
+        let mut a = make.call(1).wait().unwrap();
+        let mut b = make.call(2).wait().unwrap();
+
+        future::lazy(|| {
+            assert!(a.poll_ready().unwrap().is_ready());
+            let rsp = a.call(7);
+            assert!(
+                a.poll_ready().unwrap().is_not_ready(),
+                "a target is limited"
+            );
+
+            assert!(
+                b.poll_ready().unwrap().is_ready(),
+                "another target has its own limit"
+            );
+            assert_eq!(b.call(8).wait().unwrap(), 8);
+
+            assert_eq!(rsp.wait().unwrap(), 7);
+            assert!(a.poll_ready().unwrap().is_ready());
+            Ok::<_, ()>(())
+        })
+        .wait()
+        .unwrap();
+    }
+}
@@ -75,6 +75,14 @@ impl http::settings::HasSettings for Endpoint {
     }
 }
 
+impl http::h2::HasH2Settings for Endpoint {
+    /// Inbound clients connect to the local application, so they use the
+    /// configured settings.
+    fn h2_settings(&self) -> http::h2::Settings {
+        http::h2::Settings::default()
+    }
+}
+
 impl classify::CanClassify for Endpoint {
     type Classify = classify::Request;
 
@@ -116,7 +124,9 @@ impl tap::Inspect for Endpoint {
     }
 
     fn route_labels<B>(&self, req: &http::Request<B>) -> Option<Arc<IndexMap<String, String>>> {
-        req.extensions().get::<Route>().map(|r| r.labels().clone())
+        req.extensions()
+            .get::<Route>()
+            .map(|r| r.route_labels().clone())
     }
 
     fn is_outbound<B>(&self, _: &http::Request<B>) -> bool {
@@ -220,13 +230,19 @@ mod tests {
 
 impl Into<EndpointLabels> for Endpoint {
     fn into(self) -> EndpointLabels {
-        use linkerd2_app_core::metric_labels::{Direction, TlsId};
+        use linkerd2_app_core::metric_labels::{extract_namespace, Direction, TlsId};
+        let src_namespace = match self.tls_client_id {
+            Conditional::Some(ref id) => extract_namespace(id),
+            Conditional::None(_) => None,
+        };
         EndpointLabels {
             dst_logical: self.dst_name.clone(),
             dst_concrete: self.dst_name,
             direction: Direction::In,
             tls_id: self.tls_client_id.map(TlsId::ClientId),
             labels: None,
+            route_labels: None,
+            src_namespace,
         }
     }
 }
@@ -11,8 +11,9 @@ use linkerd2_app_core::{
     config::{ProxyConfig, ServerConfig},
     drain,
     dst::DstAddr,
-    errors, http_request_authority_addr, http_request_host_addr,
+    errors, headers, http_request_authority_addr, http_request_host_addr,
     http_request_l5d_override_dst_addr, http_request_orig_dst_addr,
+    metric_labels::EndpointLabels,
     opencensus::proto::trace::v1 as oc,
     proxy::{
         self,
@@ -28,8 +29,7 @@ use linkerd2_app_core::{
     spans::SpanConverter,
     svc, trace, trace_context,
     transport::{self, connect, tls, OrigDstAddr, SysOrigDstAddr},
-    Addr, DispatchDeadline, Error, ProxyMetrics, CANONICAL_DST_HEADER, DST_OVERRIDE_HEADER,
-    L5D_CLIENT_ID, L5D_REMOTE_IP, L5D_SERVER_ID,
+    Addr, DispatchDeadline, Error, ProxyMetrics,
 };
 use std::collections::HashMap;
 use std::net::SocketAddr;
@@ -94,6 +94,7 @@ impl<A: OrigDstAddr> Config<A> {
                     router_capacity,
                     router_max_idle_age,
                     disable_protocol_detection_for_ports,
+                    continue_ttfb,
                 },
         } = self;
 
@@ -108,14 +109,14 @@ impl<A: OrigDstAddr> Config<A> {
             // TCP forwarding and HTTP proxying).
             let connect_stack = svc::stack(connect::svc(connect.keepalive))
                 .push(tls::client::layer(local_identity.clone()))
-                .push_timeout(connect.timeout)
+                .push_timeout("application-connection", connect.timeout)
                 .push(metrics.transport.layer_connect(TransportLabels))
                 .push(rewrite_loopback_addr::layer());
 
             // Instantiates an HTTP client for a `client::Config`
             let client_stack = connect_stack
                 .clone()
-                .push(client::layer(connect.h2_settings))
+                .push(client::layer(connect.h1_settings, connect.h2_settings))
                 .push(reconnect::layer({
                     let backoff = connect.backoff.clone();
                     move |_| Ok(backoff.stream())
@@ -129,17 +130,25 @@ impl<A: OrigDstAddr> Config<A> {
             // a router made of route stacks configured by `inbound::Endpoint`.
             let endpoint_router = client_stack
                 .push(tap_layer)
-                .push(http_metrics::layer::<_, classify::Response>(
-                    metrics.http_endpoint,
-                ))
-                .serves::<Endpoint>()
+                .push(
+                    http_metrics::layer::<_, classify::Response>(metrics.http_endpoint)
+                        .with_continue_ttfb(continue_ttfb)
+                        .with_request_labels(EndpointLabels::with_route),
+                )
+                .serves_spawnable::<Endpoint>()
                 .push(trace::layer(
                     |endpoint: &Endpoint| info_span!("endpoint", peer.addr = %endpoint.addr),
                 ))
-                .push_buffer_pending(buffer.max_in_flight, DispatchDeadline::extract)
-                .makes::<Endpoint>()
+                .push_buffer_pending_with_metrics(
+                    buffer.max_in_flight,
+                    DispatchDeadline::extract,
+                    metrics.buffers.buffer("inbound_endpoint"),
+                )
+                .makes_spawnable::<Endpoint>()
+                .clones::<Endpoint>()
                 .push(router::Layer::new(
-                    router::Config::new(router_capacity, router_max_idle_age),
+                    router::Config::new(router_capacity, router_max_idle_age)
+                        .with_metrics("inbound_endpoint", &metrics.router_cache),
                     RecognizeEndpoint::default(),
                 ))
                 .into_inner()
@@ -153,11 +162,16 @@ impl<A: OrigDstAddr> Config<A> {
             // implementations can use the route-specific configuration.
             let dst_route_layer = svc::layers()
                 .push(insert::target::layer())
-                .push(http_metrics::layer::<_, classify::Response>(
-                    metrics.http_route,
-                ))
+                .push(
+                    http_metrics::layer::<_, classify::Response>(metrics.http_route)
+                        .with_continue_ttfb(continue_ttfb),
+                )
                 .push(classify::layer())
-                .push_buffer_pending(buffer.max_in_flight, DispatchDeadline::extract);
+                .push_buffer_pending_with_metrics(
+                    buffer.max_in_flight,
+                    DispatchDeadline::extract,
+                    metrics.buffers.buffer("inbound_route"),
+                );
 
             // A per-`DstAddr` stack that does the following:
             //
@@ -167,17 +181,28 @@ impl<A: OrigDstAddr> Config<A> {
             //    `RecognizeEndpoint` can use the value.
             let dst_stack = svc::stack(svc::Shared::new(endpoint_router))
                 .push(insert::target::layer())
-                .push_buffer_pending(buffer.max_in_flight, DispatchDeadline::extract)
-                .push(profiles::router::layer(profiles_client, dst_route_layer))
-                .push(strip_header::request::layer(DST_OVERRIDE_HEADER))
+                .push_buffer_pending_with_metrics(
+                    buffer.max_in_flight,
+                    DispatchDeadline::extract,
+                    metrics.buffers.buffer("inbound_concrete"),
+                )
+                .push(profiles::router::layer(
+                    profiles_client,
+                    dst_route_layer,
+                    metrics.http_split,
+                ))
+                .push(strip_header::request::layer(
+                    headers::L5D_DST_OVERRIDE.clone(),
+                ))
                 .push(trace::layer(
-                    |dst: &DstAddr| info_span!("logical", dst = %dst.dst_logical()),
+                    |dst: &DstAddr| info_span!("logical", dst = %dst.logical()),
                 ));
 
             // Routes requests to a `DstAddr`.
             //
-            // 1. If the CANONICAL_DST_HEADER is set by the remote peer,
-            // this value is used to construct a DstAddr.
+            // 1. If the `l5d-dst-canonical` header is set by the remote peer,
+            // this value is used to construct a DstAddr. The header is only
+            // honored from peers with a verified identity.
             //
             // 2. If the OVERRIDE_DST_HEADER is set by the remote peer,
             // this value is used.
@@ -193,17 +218,22 @@ impl<A: OrigDstAddr> Config<A> {
             // 6. Finally, if the tls::accept::Meta had an SO_ORIGINAL_DST, this TCP
             // address is used.
             let dst_router = dst_stack
-                .push_buffer_pending(buffer.max_in_flight, DispatchDeadline::extract)
+                .push_buffer_pending_with_metrics(
+                    buffer.max_in_flight,
+                    DispatchDeadline::extract,
+                    metrics.buffers.buffer("inbound_logical"),
+                )
                 .push(router::Layer::new(
-                    router::Config::new(router_capacity, router_max_idle_age),
+                    router::Config::new(router_capacity, router_max_idle_age)
+                        .with_metrics("inbound_logical", &metrics.router_cache),
                     |req: &http::Request<_>| {
                         let dst = req
                             .headers()
-                            .get(CANONICAL_DST_HEADER)
+                            .get(&*headers::L5D_DST_CANONICAL)
                             .and_then(|dst| {
                                 dst.to_str().ok().and_then(|d| {
                                     Addr::from_str(d).ok().map(|a| {
-                                        debug!("using {}", CANONICAL_DST_HEADER);
+                                        debug!("using {}", *headers::L5D_DST_CANONICAL);
                                         a
                                     })
                                 })
@@ -212,7 +242,7 @@ impl<A: OrigDstAddr> Config<A> {
                                 http_request_l5d_override_dst_addr(req)
                                     .ok()
                                     .map(|override_addr| {
-                                        debug!("using {}", DST_OVERRIDE_HEADER);
+                                        debug!("using {}", *headers::L5D_DST_OVERRIDE);
                                         override_addr
                                     })
                             })
@@ -220,7 +250,17 @@ impl<A: OrigDstAddr> Config<A> {
                             .or_else(|| http_request_host_addr(req).ok())
                             .or_else(|| http_request_orig_dst_addr(req).ok())
                             .map(|addr| {
+                                let src_identity = req
+                                    .extensions()
+                                    .get::<tls::accept::Meta>()
+                                    .map(|meta| meta.peer_identity.clone())
+                                    .unwrap_or_else(|| {
+                                        tls::Conditional::None(
+                                            tls::ReasonForNoPeerName::NotProvidedByRemote.into(),
+                                        )
+                                    });
                                 DstAddr::inbound(addr, settings::Settings::from_request(req))
+                                    .with_src_identity(src_identity)
                             });
                         debug!(dst.logical = ?dst);
                         dst
@@ -248,9 +288,10 @@ impl<A: OrigDstAddr> Config<A> {
                 // disabled due to information leagkage
                 //.push(set_remote_ip_on_req::layer())
                 //.push(set_client_id_on_req::layer())
-                .push(strip_header::request::layer(L5D_REMOTE_IP))
-                .push(strip_header::request::layer(L5D_CLIENT_ID))
-                .push(strip_header::response::layer(L5D_SERVER_ID))
+                .push(headers::strip::layer(
+                    headers::Boundary::Inbound,
+                    |src: &tls::accept::Meta| src.peer_identity.is_some(),
+                ))
                 .push(insert::layer(move || {
                     DispatchDeadline::after(buffer.dispatch_timeout)
                 }))
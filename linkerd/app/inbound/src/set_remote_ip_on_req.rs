@@ -4,13 +4,13 @@
 use bytes::Bytes;
 use http::header::HeaderValue;
 use linkerd2_app_core::{
+    headers::L5D_REMOTE_IP,
     proxy::http::add_header::{self, request::ReqHeader, Layer},
     transport::tls,
-    L5D_REMOTE_IP,
 };
 
 pub fn layer() -> Layer<&'static str, tls::accept::Meta, ReqHeader> {
-    add_header::request::layer(L5D_REMOTE_IP, |source: &tls::accept::Meta| {
+    add_header::request::layer(L5D_REMOTE_IP.clone(), |source: &tls::accept::Meta| {
         HeaderValue::from_shared(Bytes::from(source.addrs.peer().ip().to_string())).ok()
     })
 }
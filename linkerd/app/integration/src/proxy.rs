@@ -23,6 +23,8 @@ pub struct Proxy {
     outbound_disable_ports_protocol_detection: Option<Vec<u16>>,
 
     shutdown_signal: Option<Box<dyn Future<Item = (), Error = ()> + Send>>,
+
+    dns_resolver: Option<Arc<dyn app::core::dns::DnsResolver>>,
 }
 
 pub struct Listening {
@@ -52,6 +54,7 @@ impl Proxy {
             inbound_disable_ports_protocol_detection: None,
             outbound_disable_ports_protocol_detection: None,
             shutdown_signal: None,
+            dns_resolver: None,
         }
     }
 
@@ -127,6 +130,12 @@ impl Proxy {
         self
     }
 
+    /// Resolve names with `resolver` rather than the system's DNS.
+    pub fn dns_resolver(mut self, resolver: Arc<dyn app::core::dns::DnsResolver>) -> Self {
+        self.dns_resolver = Some(resolver);
+        self
+    }
+
     pub fn run(self) -> Listening {
         self.run_with_test_env(TestEnv::new())
     }
@@ -232,7 +241,10 @@ fn run(proxy: Proxy, mut env: TestEnv) -> Listening {
         );
     }
 
-    let config = app::env::parse_config(&env).unwrap();
+    let mut config = app::env::parse_config(&env).unwrap();
+    if let Some(resolver) = proxy.dns_resolver {
+        config.dns.resolver = Some(resolver);
+    }
     let (trace, trace_handle) = super::trace_init();
 
     let (running_tx, running_rx) = oneshot::channel();
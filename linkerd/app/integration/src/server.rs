@@ -43,12 +43,17 @@ pub struct Listening {
     pub addr: SocketAddr,
     pub(super) _shutdown: Shutdown,
     pub(super) conn_count: Arc<AtomicUsize>,
+    pub(super) closed_count: Arc<AtomicUsize>,
 }
 
 impl Listening {
     pub fn connections(&self) -> usize {
         self.conn_count.load(Ordering::Acquire)
     }
+
+    pub fn closed_connections(&self) -> usize {
+        self.closed_count.load(Ordering::Acquire)
+    }
 }
 
 impl Drop for Listening {
@@ -142,6 +147,8 @@ impl Server {
         let mut listening_tx = Some(listening_tx);
         let conn_count = Arc::new(AtomicUsize::from(0));
         let srv_conn_count = Arc::clone(&conn_count);
+        let closed_count = Arc::new(AtomicUsize::from(0));
+        let srv_closed_count = Arc::clone(&closed_count);
         let version = self.version;
         let tname = format!("support {:?} server (test={})", version, thread_name(),);
 
@@ -184,6 +191,7 @@ impl Server {
                     .for_each(move |sock| {
                         let http_clone = http.clone();
                         let srv_conn_count = Arc::clone(&srv_conn_count);
+                        let srv_closed_count = Arc::clone(&srv_closed_count);
                         let fut = new_svc
                             .call(())
                             .inspect(move |_| {
@@ -194,6 +202,10 @@ impl Server {
                                 http_clone
                                     .serve_connection(sock, svc)
                                     .map_err(|e| println!("support/server error: {}", e))
+                                    .then(move |res| {
+                                        srv_closed_count.fetch_add(1, Ordering::Release);
+                                        res
+                                    })
                             })
                             .map(|_| ());
                         current_thread::TaskExecutor::current()
@@ -223,6 +235,7 @@ impl Server {
             addr,
             _shutdown: tx,
             conn_count,
+            closed_count,
         }
     }
 }
@@ -274,5 +274,7 @@ fn run_server(tcp: TcpServer) -> server::Listening {
         addr,
         _shutdown: tx,
         conn_count,
+        // Sockets are owned by the accept callbacks, so closes aren't tracked.
+        closed_count: Arc::new(AtomicUsize::from(0)),
     }
 }
@@ -750,6 +750,42 @@ mod proxy_to_proxy {
         assert_eq!(res.version(), http::Version::HTTP_11);
     }
 
+    #[test]
+    fn outbound_http1_no_upgrade() {
+        let _ = trace_init();
+
+        let srv = server::http1()
+            .route_fn("/no-upgrade", |req| {
+                assert_eq!(req.version(), http::Version::HTTP_11);
+                assert!(
+                    !req.headers().contains_key("l5d-no-upgrade"),
+                    "server shouldn't receive l5d-no-upgrade header"
+                );
+                Response::default()
+            })
+            .run();
+
+        // The outbound proxy's endpoint is another proxy's inbound listener,
+        // which accepts both HTTP/1 and HTTP/2.
+        let inbound = proxy::new().inbound(srv).run();
+
+        let ctrl = controller::new();
+        let dst = ctrl.destination_tx("disco.test.svc.cluster.local");
+        dst.send_h2_hinted(inbound.inbound);
+
+        let outbound = proxy::new().controller(ctrl.run()).run();
+
+        let client = client::http1(outbound.outbound, "disco.test.svc.cluster.local");
+
+        let res = client.request(
+            client
+                .request_builder("/no-upgrade")
+                .header("l5d-no-upgrade", "true"),
+        );
+        assert_eq!(res.status(), 200);
+        assert_eq!(res.version(), http::Version::HTTP_11);
+    }
+
     #[test]
     fn inbound_http1() {
         let _ = trace_init();
@@ -934,3 +970,79 @@ mod proxy_to_proxy {
         }
     }
 }
+
+mod dns {
+    use super::*;
+    use app::core::dns::{DnsResolver, Error, Lookup, LookupFuture, Name, Srv};
+    use std::net::{Ipv4Addr, Ipv6Addr};
+    use std::sync::Mutex;
+    use std::time::Instant;
+
+    /// Resolves A records from a fixed table and records each query.
+    #[derive(Debug, Default)]
+    struct MockResolver {
+        a: HashMap<String, Ipv4Addr>,
+        queries: Mutex<Vec<String>>,
+    }
+
+    impl MockResolver {
+        fn a(mut self, name: &str, ip: [u8; 4]) -> Self {
+            self.a.insert(name.to_owned(), ip.into());
+            self
+        }
+
+        fn queries(&self) -> Vec<String> {
+            self.queries.lock().unwrap().clone()
+        }
+    }
+
+    impl DnsResolver for MockResolver {
+        fn resolve_a(&self, name: &Name) -> LookupFuture<Ipv4Addr> {
+            self.queries.lock().unwrap().push(name.to_string());
+            let res = match self.a.get(name.as_ref()) {
+                Some(ip) => Ok(Lookup {
+                    records: vec![*ip],
+                    valid_until: Instant::now() + Duration::from_secs(60),
+                }),
+                None => Err(Error::NoRecordsFound { valid_until: None }),
+            };
+            Box::new(future::result(res))
+        }
+
+        fn resolve_aaaa(&self, _: &Name) -> LookupFuture<Ipv6Addr> {
+            Box::new(future::err(Error::NoRecordsFound { valid_until: None }))
+        }
+
+        fn resolve_srv(&self, _: &Name) -> LookupFuture<Srv> {
+            Box::new(future::err(Error::NoRecordsFound { valid_until: None }))
+        }
+    }
+
+    #[test]
+    fn outbound_routes_names_canonicalized_by_injected_resolver() {
+        let _ = trace_init();
+        let srv = server::http1().route("/", "hello").run();
+
+        // The destination is only known by its fully-qualified name, so the
+        // request is only routed if `web` is expanded on the search path.
+        let ctrl = controller::new().destination_and_close("web.test.svc.cluster.local", srv.addr);
+
+        let resolver =
+            Arc::new(MockResolver::default().a("web.test.svc.cluster.local.", [10, 1, 2, 3]));
+
+        let mut env = TestEnv::new();
+        env.put(
+            app::env::ENV_DNS_SEARCH,
+            "test.svc.cluster.local".to_owned(),
+        );
+
+        let proxy = proxy::new()
+            .controller(ctrl.run())
+            .dns_resolver(resolver.clone())
+            .run_with_test_env(env);
+
+        let client = client::http1(proxy.outbound, "web");
+        assert_eq!(client.get("/"), "hello");
+        assert_eq!(resolver.queries()[0], "web.test.svc.cluster.local.");
+    }
+}
@@ -0,0 +1,133 @@
+#![deny(warnings, rust_2018_idioms)]
+#![recursion_limit = "128"]
+#![type_length_limit = "1110183"]
+
+use linkerd2_app_integration::*;
+use linkerd2_proxy_api::destination as pb;
+
+fn server(name: &'static str) -> server::Listening {
+    server::http1()
+        .route_fn("/load-profile", |_| {
+            Response::builder().status(201).body("".into()).unwrap()
+        })
+        .route("/", name)
+        .run()
+}
+
+fn authority(name: &str, srv: &server::Listening) -> String {
+    format!("{}.svc.cluster.local:{}", name, srv.addr.port())
+}
+
+fn profile(stage: &str, overrides: Vec<pb::WeightedDst>) -> pb::DestinationProfile {
+    controller::profile(
+        vec![
+            controller::route()
+                .request_path("/load-profile")
+                .label("load_profile", stage),
+            controller::route().request_any(),
+        ],
+        None,
+        overrides,
+    )
+}
+
+fn freeze(admin: &client::Client, method: &str, dst: &str) {
+    let rsp = admin.request(
+        admin
+            .request_builder(&format!("/freeze?dst={}", dst))
+            .method(method),
+    );
+    assert_eq!(rsp.status(), http::StatusCode::NO_CONTENT);
+}
+
+fn frozen_updates(dst: &str, n: usize) -> String {
+    format!("destination_frozen_updates_total{{dst=\"{}\"}} {}", dst, n)
+}
+
+#[test]
+fn frozen_split_is_applied_when_unfrozen() {
+    let _ = trace_init();
+
+    let apex_svc = server("apex");
+    let apex = authority("apex", &apex_svc);
+    let leaf_svc = server("leaf");
+    let leaf = authority("leaf", &leaf_svc);
+
+    let ctrl = controller::new_unordered()
+        .destination_and_close(&apex, apex_svc.addr)
+        .destination_and_close(&leaf, leaf_svc.addr);
+    let profile_tx = ctrl.profile_tx(&apex);
+    let proxy = proxy::new().controller(ctrl.run()).run();
+
+    let client = client::http1(proxy.outbound, apex.clone());
+    let admin = client::http1(proxy.metrics, "localhost");
+
+    profile_tx.send(profile("initial", vec![]));
+    assert_eventually!(
+        client.get("/load-profile") == ""
+            && admin
+                .get("/metrics")
+                .contains("rt_load_profile=\"initial\""),
+        "initial profile was not applied"
+    );
+    assert_eq!(client.get("/"), "apex");
+
+    freeze(&admin, "POST", &apex);
+    assert_eq!(admin.get("/freeze"), format!("{}\n", apex));
+    assert_eventually_contains!(
+        admin.get("/metrics"),
+        &format!("destination_frozen{{dst=\"{}\"}} 1", apex)
+    );
+
+    // The split is received but not applied.
+    profile_tx.send(profile(
+        "override",
+        vec![controller::dst_override(leaf.clone(), 10000)],
+    ));
+    assert_eventually_contains!(admin.get("/metrics"), &frozen_updates(&apex, 1));
+    for _ in 0..10 {
+        assert_eq!(client.get("/"), "apex");
+    }
+
+    freeze(&admin, "DELETE", &apex);
+    assert_eq!(admin.get("/freeze"), "");
+    assert_eventually!(
+        client.get("/") == "leaf",
+        "the latest split was not applied"
+    );
+}
+
+#[test]
+fn frozen_endpoints_are_applied_when_unfrozen() {
+    let _ = trace_init();
+
+    let srv_a = server("apex-a");
+    let srv_b = server("apex-b");
+    let apex = authority("apex", &srv_a);
+
+    let ctrl = controller::new();
+    let dst_tx = ctrl.destination_tx(&apex);
+    dst_tx.send_addr(srv_a.addr);
+    let proxy = proxy::new().controller(ctrl.run()).run();
+
+    let client = client::http1(proxy.outbound, apex.clone());
+    let admin = client::http1(proxy.metrics, "localhost");
+
+    assert_eq!(client.get("/"), "apex-a");
+
+    freeze(&admin, "POST", &apex);
+
+    // The endpoints are replaced, but the change is not applied.
+    dst_tx.send(controller::destination_exists_with_no_endpoints());
+    dst_tx.send_addr(srv_b.addr);
+    assert_eventually_contains!(admin.get("/metrics"), &frozen_updates(&apex, 2));
+    for _ in 0..10 {
+        assert_eq!(client.get("/"), "apex-a");
+    }
+
+    freeze(&admin, "DELETE", &apex);
+    assert_eventually!(
+        client.get("/") == "apex-b",
+        "the latest endpoints were not applied"
+    );
+}
@@ -333,3 +333,193 @@ fn remove_a_dst_override() {
     assert_eq!(apex_svc.response_counter.load(Ordering::SeqCst), n);
     assert_eq!(leaf_svc.response_counter.load(Ordering::SeqCst), 0);
 }
+
+#[test]
+fn dst_headers_describe_concrete_override() {
+    let _ = trace_init();
+    let ctrl = controller::new_unordered();
+
+    let apex_svc = Service::new("apex");
+    let ctrl = ctrl.destination_and_close(&apex_svc.authority(), apex_svc.svc.addr);
+
+    let leaf_svc = Service::new("leaf");
+    let ctrl = ctrl.destination_and_close(&leaf_svc.authority(), leaf_svc.svc.addr);
+
+    let profile_tx = ctrl.profile_tx(&apex_svc.authority());
+    profile_tx.send(profile(
+        "override",
+        vec![controller::dst_override(leaf_svc.authority(), 10000)],
+    ));
+
+    let mut env = TestEnv::new();
+    env.put(app::env::ENV_OUTBOUND_EXPOSE_DST_HEADERS, "true".to_owned());
+    let proxy = proxy::new().controller(ctrl.run()).run_with_test_env(env);
+
+    let client = client::http1(proxy.outbound, apex_svc.authority());
+    let metrics = client::http1(proxy.metrics, "localhost");
+    wait_for_profile_stage(&client, &metrics, "override");
+
+    // The headers name the concrete destination selected by the split, not
+    // the logical destination.
+    let rsp = client.request(&mut client.request_builder("/"));
+    assert_eq!(rsp.status(), 200);
+    assert_eq!(
+        rsp.headers().get("l5d-dst-concrete").unwrap(),
+        leaf_svc.authority().as_str()
+    );
+    assert_eq!(
+        rsp.headers().get("l5d-dst-endpoint").unwrap(),
+        leaf_svc.svc.addr.to_string().as_str()
+    );
+}
+
+#[test]
+fn dst_headers_are_disabled_by_default() {
+    let _ = trace_init();
+    let ctrl = controller::new_unordered();
+
+    let apex_svc = Service::new("apex");
+    let ctrl = ctrl.destination_and_close(&apex_svc.authority(), apex_svc.svc.addr);
+    let _profile_tx = ctrl.profile_tx(&apex_svc.authority());
+
+    let proxy = proxy::new().controller(ctrl.run()).run();
+    let client = client::http1(proxy.outbound, apex_svc.authority());
+
+    let rsp = client.request(&mut client.request_builder("/"));
+    assert_eq!(rsp.status(), 200);
+    assert!(rsp.headers().get("l5d-dst-concrete").is_none());
+    assert!(rsp.headers().get("l5d-dst-endpoint").is_none());
+}
+
+#[test]
+fn dst_override_keeps_the_requested_port() {
+    let _ = trace_init();
+    let ctrl = controller::new_unordered();
+
+    let apex_svc = Service::new("apex");
+    let apex = "apex.svc.cluster.local:8080";
+    let ctrl = ctrl.destination_and_close(apex, apex_svc.svc.addr);
+
+    let leaf_svc = Service::new("leaf");
+    let leaf = "leaf.svc.cluster.local:8080";
+    let ctrl = ctrl.destination_and_close(leaf, leaf_svc.svc.addr);
+
+    // The profile is only served for the `:8080` authority.
+    let profile_tx = ctrl.profile_tx(apex);
+    profile_tx.send(profile(
+        "override",
+        vec![controller::dst_override(leaf.into(), 10000)],
+    ));
+
+    let mut env = TestEnv::new();
+    env.put(app::env::ENV_OUTBOUND_EXPOSE_DST_HEADERS, "true".to_owned());
+    let proxy = proxy::new().controller(ctrl.run()).run_with_test_env(env);
+
+    let client = client::http1(proxy.outbound, apex);
+    let metrics = client::http1(proxy.metrics, "localhost");
+    wait_for_profile_stage(&client, &metrics, "override");
+
+    let rsp = client.request(&mut client.request_builder("/"));
+    assert_eq!(rsp.status(), 200);
+    assert_eq!(rsp.headers().get("l5d-dst-concrete").unwrap(), leaf);
+    assert_eq!(apex_svc.response_counter.load(Ordering::SeqCst), 0);
+    assert_eq!(leaf_svc.response_counter.load(Ordering::SeqCst), 1);
+}
+
+#[test]
+fn affinity_header_pins_requests_to_one_backend() {
+    let _ = trace_init();
+    let ctrl = controller::new_unordered();
+
+    let apex_svc = Service::new("apex");
+    let ctrl = ctrl.destination_and_close(&apex_svc.authority(), apex_svc.svc.addr);
+    let leaf_a_svc = Service::new("leaf-a");
+    let ctrl = ctrl.destination_and_close(&leaf_a_svc.authority(), leaf_a_svc.svc.addr);
+    let leaf_b_svc = Service::new("leaf-b");
+    let ctrl = ctrl.destination_and_close(&leaf_b_svc.authority(), leaf_b_svc.svc.addr);
+
+    let profile_tx = ctrl.profile_tx(&apex_svc.authority());
+    profile_tx.send(profile(
+        "overrides",
+        vec![
+            controller::dst_override(leaf_a_svc.authority(), 5000),
+            controller::dst_override(leaf_b_svc.authority(), 5000),
+        ],
+    ));
+
+    let mut env = TestEnv::new();
+    env.put(
+        app::env::ENV_DESTINATION_PROFILE_AFFINITY_HEADER,
+        "x-session-id".to_owned(),
+    );
+    let proxy = proxy::new().controller(ctrl.run()).run_with_test_env(env);
+
+    let client = client::http1(proxy.outbound, apex_svc.authority());
+    let metrics = client::http1(proxy.metrics, "localhost");
+    wait_for_profile_stage(&client, &metrics, "overrides");
+
+    let n = 100;
+    for _ in 0..n {
+        let rsp = client.request(client.request_builder("/").header("x-session-id", "abc"));
+        assert_eq!(rsp.status(), 200);
+    }
+    let a = leaf_a_svc.response_counter.load(Ordering::SeqCst);
+    let b = leaf_b_svc.response_counter.load(Ordering::SeqCst);
+    assert!(
+        (a, b) == (n, 0) || (a, b) == (0, n),
+        "requests were split: {} and {}",
+        a,
+        b
+    );
+}
+
+#[test]
+fn slow_start_ramps_up_an_added_dst_override() {
+    let _ = trace_init();
+    let ctrl = controller::new_unordered();
+
+    let apex_svc = Service::new("apex");
+    let ctrl = ctrl.destination_and_close(&apex_svc.authority(), apex_svc.svc.addr);
+    let leaf_a_svc = Service::new("leaf-a");
+    let ctrl = ctrl.destination_and_close(&leaf_a_svc.authority(), leaf_a_svc.svc.addr);
+    let leaf_b_svc = Service::new("leaf-b");
+    let ctrl = ctrl.destination_and_close(&leaf_b_svc.authority(), leaf_b_svc.svc.addr);
+
+    let profile_tx = ctrl.profile_tx(&apex_svc.authority());
+    profile_tx.send(profile(
+        "override",
+        vec![controller::dst_override(leaf_a_svc.authority(), 10000)],
+    ));
+
+    // The added backend starts with the smallest possible weight and is not
+    // ramped up for the duration of the test.
+    let mut env = TestEnv::new();
+    env.put(app::env::ENV_OUTBOUND_SPLIT_SLOW_START, "1h".to_owned());
+    env.put(
+        app::env::ENV_OUTBOUND_SPLIT_SLOW_START_MIN_WEIGHT,
+        "0".to_owned(),
+    );
+    let proxy = proxy::new().controller(ctrl.run()).run_with_test_env(env);
+
+    let client = client::http1(proxy.outbound, apex_svc.authority());
+    let metrics = client::http1(proxy.metrics, "localhost");
+    wait_for_profile_stage(&client, &metrics, "override");
+
+    // Without slow start, the two backends would split requests evenly.
+    profile_tx.send(profile(
+        "added",
+        vec![
+            controller::dst_override(leaf_a_svc.authority(), 10000),
+            controller::dst_override(leaf_b_svc.authority(), 10000),
+        ],
+    ));
+    wait_for_profile_stage(&client, &metrics, "added");
+
+    let n = 100;
+    for _ in 0..n {
+        let rsp = client.get("/");
+        assert!(rsp == "leaf-a" || rsp == "leaf-b");
+    }
+    assert!(leaf_a_svc.response_counter.load(Ordering::SeqCst) >= n - 5);
+    assert!(leaf_b_svc.response_counter.load(Ordering::SeqCst) <= 5);
+}
@@ -20,6 +20,76 @@ fn outbound_http1() {
     assert_eq!(client.get("/"), "hello h1");
 }
 
+#[test]
+fn outbound_request_timeout_header_is_not_forwarded() {
+    let _ = trace_init();
+
+    let srv = server::http1()
+        .route_fn("/", |req| {
+            assert!(!req.headers().contains_key("l5d-request-timeout"));
+            Response::new("hello h1".into())
+        })
+        .run();
+    let ctrl = controller::new()
+        .destination_and_close("transparency.test.svc.cluster.local", srv.addr)
+        .run();
+    let proxy = proxy::new().controller(ctrl).outbound(srv).run();
+    let client = client::http1(proxy.outbound, "transparency.test.svc.cluster.local");
+
+    let rsp = client.request(
+        client
+            .request_builder("/")
+            .header("l5d-request-timeout", "10s"),
+    );
+    assert_eq!(rsp.status(), http::StatusCode::OK);
+}
+
+#[test]
+fn inbound_http1_expect_continue_rejected_before_body() {
+    let _ = trace_init();
+
+    let (tx, rx) = mpsc::channel();
+    let srv = server::tcp()
+        .accept(move |req| {
+            tx.send(req).unwrap();
+            "HTTP/1.1 417 Expectation Failed\r\ncontent-length: 0\r\n\r\n"
+        })
+        .run();
+    let proxy = proxy::new().inbound(srv).run();
+
+    // A TCP client is used so that the body is only sent if the client
+    // receives `100 Continue`.
+    let client = client::tcp(proxy.inbound);
+    let tcp_client = client.connect();
+    tcp_client.write(
+        "POST / HTTP/1.1\r\n\
+         Host: transparency.test.svc.cluster.local\r\n\
+         Expect: 100-continue\r\n\
+         Content-Length: 5\r\n\r\n",
+    );
+
+    // The server's rejection is returned without an interim response.
+    let rsp = String::from_utf8(tcp_client.read()).unwrap();
+    assert!(
+        rsp.starts_with("HTTP/1.1 417 Expectation Failed\r\n"),
+        "expected a 417 response: {:?}",
+        rsp
+    );
+
+    // The server only received the request's head.
+    let req = String::from_utf8(rx.recv().unwrap()).unwrap();
+    assert!(
+        req.to_lowercase().contains("expect: 100-continue\r\n"),
+        "expect header should be forwarded: {:?}",
+        req
+    );
+    assert!(
+        req.ends_with("\r\n\r\n"),
+        "request body should not be sent: {:?}",
+        req
+    );
+}
+
 #[test]
 fn inbound_http1() {
     let _ = trace_init();
@@ -960,6 +1030,65 @@ fn http10_without_host() {
     assert_eq!(s(&tcp_client.read()[..expected.len()]), expected);
 }
 
+#[test]
+fn http10_without_keep_alive_receives_close_delimited_body() {
+    let _ = trace_init();
+
+    let srv = server::tcp()
+        .accept(move |_read| {
+            "\
+             HTTP/1.1 200 OK\r\n\
+             transfer-encoding: chunked\r\n\
+             \r\n\
+             5\r\n\
+             hello\r\n\
+             0\r\n\
+             \r\n\
+             "
+        })
+        .run();
+    let proxy = proxy::new().inbound(srv).run();
+
+    let client = client::tcp(proxy.inbound);
+    let tcp_client = client.connect();
+
+    tcp_client.write(
+        "\
+         GET / HTTP/1.0\r\n\
+         Host: transparency.test.svc.cluster.local\r\n\
+         \r\n\
+         ",
+    );
+
+    // The response body is delimited by the proxy closing the connection.
+    let mut rsp = Vec::new();
+    loop {
+        let buf = tcp_client.read();
+        if buf.is_empty() {
+            break;
+        }
+        rsp.extend(buf);
+    }
+    let rsp = String::from_utf8(rsp).unwrap();
+
+    assert!(
+        rsp.starts_with("HTTP/1.0 200 OK\r\n"),
+        "expected an HTTP/1.0 response: {:?}",
+        rsp
+    );
+    assert!(
+        rsp.to_lowercase().contains("connection: close\r\n"),
+        "connection should be closed: {:?}",
+        rsp
+    );
+    assert!(
+        !rsp.to_lowercase().contains("transfer-encoding"),
+        "body should not be chunked: {:?}",
+        rsp
+    );
+    assert!(rsp.ends_with("\r\n\r\nhello"), "unexpected body: {:?}", rsp);
+}
+
 #[test]
 fn http1_one_connection_per_host() {
     let _ = trace_init();
@@ -1006,6 +1135,32 @@ fn http1_one_connection_per_host() {
     run_request("quuuux.com", 3);
 }
 
+#[test]
+fn http1_idle_connections_are_closed_after_pool_idle_timeout() {
+    let _ = trace_init();
+
+    let srv = server::http1().route("/", "hello").run();
+    let mut env = TestEnv::new();
+    env.put(app::env::ENV_HTTP1_POOL_IDLE_TIMEOUT, "100ms".to_owned());
+    let proxy = proxy::new().inbound(srv).run_with_test_env(env);
+
+    let client = client::http1(proxy.inbound, "foo.bar");
+    let inbound = &proxy.inbound_server.as_ref().expect("no inbound server!");
+
+    assert_eq!(client.get("/"), "hello");
+    assert_eq!(inbound.connections(), 1);
+
+    // The pooled connection is closed once it has been idle for too long...
+    assert_eventually!(
+        inbound.closed_connections() == 1,
+        "the idle connection was not closed"
+    );
+
+    // ...and the next request transparently opens a new connection.
+    assert_eq!(client.get("/"), "hello");
+    assert_eq!(inbound.connections(), 2);
+}
+
 #[test]
 fn http1_requests_without_host_have_unique_connections() {
     let _ = trace_init();
@@ -1301,3 +1456,61 @@ fn http1_orig_proto_does_not_propagate_rst_stream() {
 
     assert_eq!(res.status(), http::StatusCode::BAD_GATEWAY);
 }
+
+fn respond_raw(
+    sock: tokio::net::TcpStream,
+    rsp: &'static str,
+) -> impl Future<Item = (), Error = ()> {
+    tokio_io::io::read(sock, vec![0; 1024])
+        .and_then(move |(sock, _, _)| tokio_io::io::write_all(sock, rsp))
+        .map(|_| ())
+        .map_err(|e| panic!("tcp server error: {}", e))
+}
+
+fn test_invalid_upstream_response(invalid: &'static str, metric: &str) {
+    let _ = trace_init();
+
+    let valid = "HTTP/1.1 200 OK\r\ncontent-length: 0\r\n\r\n";
+    let srv = server::tcp()
+        .accept_fut(move |sock| respond_raw(sock, invalid))
+        .accept_fut(move |sock| respond_raw(sock, valid))
+        .run();
+    let proxy = proxy::new().outbound(srv).run();
+    let client = client::http1(proxy.outbound, "transparency.test.svc.cluster.local");
+    let metrics = client::http1(proxy.metrics, "localhost");
+
+    let res = client.request(&mut client.request_builder("/"));
+    assert_eq!(res.status(), http::StatusCode::BAD_GATEWAY);
+
+    // The downstream connection remains usable.
+    let res = client.request(&mut client.request_builder("/"));
+    assert_eq!(res.status(), http::StatusCode::OK);
+
+    assert_eventually_contains!(metrics.get("/metrics"), metric);
+}
+
+#[test]
+fn outbound_http1_rejects_out_of_range_status() {
+    test_invalid_upstream_response(
+        "HTTP/1.1 600 Weird\r\ncontent-length: 0\r\n\r\n",
+        "response_validation_failure_total{reason=\"invalid_status\"} 1",
+    );
+}
+
+#[test]
+fn outbound_http1_rejects_cr_lf_in_header_values() {
+    // Hyper refuses to parse a bare CR in a header value, so this is
+    // failed before it reaches validation and is not counted.
+    test_invalid_upstream_response(
+        "HTTP/1.1 200 OK\r\nx-injected: a\rset-cookie: evil\r\ncontent-length: 0\r\n\r\n",
+        "response_validation_failure_total{reason=\"invalid_header_value\"} 0",
+    );
+}
+
+#[test]
+fn outbound_http1_rejects_obs_fold() {
+    test_invalid_upstream_response(
+        "HTTP/1.1 200 OK\r\nx-folded: a\r\n b\r\ncontent-length: 0\r\n\r\n",
+        "response_validation_failure_total{reason=\"invalid_header_value\"} 0",
+    );
+}
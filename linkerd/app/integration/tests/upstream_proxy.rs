@@ -0,0 +1,165 @@
+#![deny(warnings, rust_2018_idioms)]
+#![recursion_limit = "128"]
+#![type_length_limit = "1110183"]
+
+use linkerd2_app_integration::*;
+use std::sync::mpsc;
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Runs an upstream proxy that accepts a single connection, reports the
+/// request head that it receives, and then handles the connection with
+/// `handle`.
+fn upstream_proxy<F, U>(heads: mpsc::Sender<String>, handle: F) -> server::Listening
+where
+    F: FnOnce(tokio::net::TcpStream) -> U + Send + 'static,
+    U: IntoFuture<Item = (), Error = ()> + 'static,
+{
+    server::tcp()
+        .accept_fut(move |sock| {
+            tokio_io::io::read(sock, vec![0; 1024])
+                .map_err(|e| panic!("upstream proxy read error: {}", e))
+                .and_then(move |(sock, buf, n)| {
+                    let head = String::from_utf8(buf[..n].to_vec()).expect("request head");
+                    heads.send(head).unwrap();
+                    handle(sock)
+                })
+        })
+        .run()
+}
+
+#[test]
+fn outbound_http1_absolute_form_via_upstream_proxy() {
+    let _ = trace_init();
+
+    let (heads_tx, heads) = mpsc::channel();
+    let upstream = upstream_proxy(heads_tx, |sock| {
+        let rsp = "HTTP/1.1 200 OK\r\ncontent-length: 14\r\n\r\nvia upstream!!";
+        tokio_io::io::write_all(sock, rsp)
+            .map(|_| ())
+            .map_err(|e| panic!("upstream proxy write error: {}", e))
+    });
+
+    let creds = std::env::temp_dir().join("linkerd2-proxy-test-upstream-credentials");
+    std::fs::write(&creds, "user:pass").unwrap();
+
+    let mut env = TestEnv::new();
+    env.put(
+        app::env::ENV_OUTBOUND_UPSTREAM_PROXIES,
+        format!(
+            "egress.test.svc.cluster.local={}@{}",
+            upstream.addr,
+            creds.display()
+        ),
+    );
+    let proxy = proxy::new().run_with_test_env(env);
+    let client = client::http1(proxy.outbound, "egress.test.svc.cluster.local");
+    let metrics = client::http1(proxy.metrics, "localhost");
+
+    assert_eq!(client.get("/hello"), "via upstream!!");
+    let _ = std::fs::remove_file(&creds);
+
+    let head = heads.recv_timeout(TIMEOUT).unwrap().to_lowercase();
+    assert!(
+        head.starts_with("get http://egress.test.svc.cluster.local/hello http/1.1\r\n"),
+        "request must be sent in absolute-form: {:?}",
+        head
+    );
+    assert!(
+        head.contains("proxy-authorization: basic dxnlcjpwyxnz\r\n"),
+        "request must carry credentials: {:?}",
+        head
+    );
+
+    // Traffic is attributed to the logical destination, via the upstream
+    // proxy.
+    assert_eventually_contains!(
+        metrics.get("/metrics"),
+        &format!(
+            "response_total{{authority=\"egress.test.svc.cluster.local\",direction=\"outbound\",via=\"{}\",tls=\"no_identity\",no_tls_reason=\"not_provided_by_service_discovery\",status_code=\"200\",classification=\"success\"}} 1",
+            upstream.addr
+        )
+    );
+}
+
+#[test]
+fn outbound_tcp_tunneled_via_upstream_proxy() {
+    let _ = trace_init();
+
+    let msg1 = "custom tcp hello";
+    let msg2 = "custom tcp bye";
+
+    let srv = server::tcp()
+        .accept(move |read| {
+            assert_eq!(read, msg1.as_bytes());
+            msg2
+        })
+        .run();
+    let dst = srv.addr;
+
+    // Establishes the tunnel and then relays one message in each direction
+    // to the destination.
+    let (heads_tx, heads) = mpsc::channel();
+    let upstream = upstream_proxy(heads_tx, move |sock| {
+        tokio_io::io::write_all(sock, "HTTP/1.1 200 Connection established\r\n\r\n")
+            .and_then(|(sock, _)| tokio_io::io::read(sock, vec![0; 1024]))
+            .and_then(move |(sock, buf, n)| {
+                tokio::net::TcpStream::connect(&dst)
+                    .and_then(move |dst| tokio_io::io::write_all(dst, buf[..n].to_vec()))
+                    .and_then(|(dst, _)| tokio_io::io::read(dst, vec![0; 1024]))
+                    .and_then(move |(_dst, buf, n)| {
+                        tokio_io::io::write_all(sock, buf[..n].to_vec())
+                    })
+            })
+            .map(|_| ())
+            .map_err(|e| panic!("upstream proxy tunnel error: {}", e))
+    });
+
+    let mut env = TestEnv::new();
+    env.put(
+        app::env::ENV_OUTBOUND_UPSTREAM_PROXIES,
+        format!("{}={}", dst, upstream.addr),
+    );
+    let proxy = proxy::new().outbound(srv).run_with_test_env(env);
+
+    let client = client::tcp(proxy.outbound);
+    let tcp_client = client.connect();
+    tcp_client.write(msg1);
+    assert_eq!(tcp_client.read_timeout(TIMEOUT), msg2.as_bytes());
+
+    let head = heads.recv_timeout(TIMEOUT).unwrap();
+    assert_eq!(
+        head,
+        format!("CONNECT {} HTTP/1.1\r\nHost: {}\r\n\r\n", dst, dst)
+    );
+}
+
+#[test]
+fn outbound_tcp_tunnel_refused_by_upstream_proxy() {
+    let _ = trace_init();
+
+    let srv = server::tcp().run();
+    let dst = srv.addr;
+
+    let (heads_tx, heads) = mpsc::channel();
+    let upstream = upstream_proxy(heads_tx, |sock| {
+        tokio_io::io::write_all(sock, "HTTP/1.1 403 Forbidden\r\n\r\n")
+            .map(|_| ())
+            .map_err(|e| panic!("upstream proxy write error: {}", e))
+    });
+
+    let mut env = TestEnv::new();
+    env.put(
+        app::env::ENV_OUTBOUND_UPSTREAM_PROXIES,
+        format!("{}={}", dst, upstream.addr),
+    );
+    let proxy = proxy::new().outbound(srv).run_with_test_env(env);
+
+    let client = client::tcp(proxy.outbound);
+    let tcp_client = client.connect();
+    tcp_client.write("custom tcp hello");
+
+    assert!(heads.recv_timeout(TIMEOUT).unwrap().starts_with("CONNECT "));
+    // The connection is closed without reaching the destination.
+    assert_eq!(tcp_client.read_timeout(TIMEOUT), b"");
+}
@@ -0,0 +1,220 @@
+//! Adds `l5d-dst-concrete` and `l5d-dst-endpoint` headers to http::Responses
+//! derived from the `Endpoint` that served the request.
+//!
+//! These headers describe how a request was routed--after traffic splits are
+//! applied--so that clients may debug routing decisions. Because they reveal
+//! details about the destination, they are only added when enabled.
+
+use super::Endpoint;
+use futures::{try_ready, Future, Poll};
+use http::header::HeaderValue;
+use linkerd2_app_core::{
+    headers::{L5D_DST_CONCRETE, L5D_DST_ENDPOINT},
+    svc,
+};
+use tracing::warn;
+
+pub fn layer(enabled: bool) -> Layer {
+    Layer { enabled }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Layer {
+    enabled: bool,
+}
+
+#[derive(Clone, Debug)]
+pub struct MakeSvc<M> {
+    enabled: bool,
+    inner: M,
+}
+
+pub struct MakeFuture<F> {
+    headers: Option<Headers>,
+    inner: F,
+}
+
+#[derive(Clone, Debug)]
+pub struct Service<S> {
+    headers: Headers,
+    inner: S,
+}
+
+pub struct ResponseFuture<F> {
+    headers: Headers,
+    inner: F,
+}
+
+#[derive(Clone, Debug)]
+struct Headers {
+    concrete: Option<HeaderValue>,
+    endpoint: HeaderValue,
+}
+
+// === impl Layer ===
+
+impl<M> svc::Layer<M> for Layer {
+    type Service = MakeSvc<M>;
+
+    fn layer(&self, inner: M) -> Self::Service {
+        MakeSvc {
+            enabled: self.enabled,
+            inner,
+        }
+    }
+}
+
+// === impl MakeSvc ===
+
+impl<M> svc::Service<Endpoint> for MakeSvc<M>
+where
+    M: svc::Service<Endpoint>,
+{
+    type Response = svc::Either<Service<M::Response>, M::Response>;
+    type Error = M::Error;
+    type Future = MakeFuture<M::Future>;
+
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        self.inner.poll_ready()
+    }
+
+    fn call(&mut self, endpoint: Endpoint) -> Self::Future {
+        let headers = if self.enabled {
+            Some(Headers::new(&endpoint))
+        } else {
+            None
+        };
+
+        MakeFuture {
+            headers,
+            inner: self.inner.call(endpoint),
+        }
+    }
+}
+
+// === impl MakeFuture ===
+
+impl<F: Future> Future for MakeFuture<F> {
+    type Item = svc::Either<Service<F::Item>, F::Item>;
+    type Error = F::Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        let inner = try_ready!(self.inner.poll());
+        let svc = match self.headers.take() {
+            Some(headers) => svc::Either::A(Service { headers, inner }),
+            None => svc::Either::B(inner),
+        };
+        Ok(svc.into())
+    }
+}
+
+// === impl Service ===
+
+impl<S, Req, B> svc::Service<Req> for Service<S>
+where
+    S: svc::Service<Req, Response = http::Response<B>>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = ResponseFuture<S::Future>;
+
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        self.inner.poll_ready()
+    }
+
+    fn call(&mut self, req: Req) -> Self::Future {
+        ResponseFuture {
+            headers: self.headers.clone(),
+            inner: self.inner.call(req),
+        }
+    }
+}
+
+// === impl ResponseFuture ===
+
+impl<F, B> Future for ResponseFuture<F>
+where
+    F: Future<Item = http::Response<B>>,
+{
+    type Item = F::Item;
+    type Error = F::Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        let mut rsp = try_ready!(self.inner.poll());
+        if let Some(concrete) = self.headers.concrete.clone() {
+            rsp.headers_mut().insert(&*L5D_DST_CONCRETE, concrete);
+        }
+        rsp.headers_mut()
+            .insert(&*L5D_DST_ENDPOINT, self.headers.endpoint.clone());
+        Ok(rsp.into())
+    }
+}
+
+// === impl Headers ===
+
+impl Headers {
+    fn new(endpoint: &Endpoint) -> Self {
+        let concrete = endpoint.dst_concrete.as_ref().and_then(|concrete| {
+            HeaderValue::from_str(&concrete.to_string())
+                .map_err(|_| warn!("{} is not a valid header value", concrete))
+                .ok()
+        });
+        let endpoint = HeaderValue::from_str(&endpoint.socket_addr().to_string())
+            .expect("socket addresses must be valid header values");
+        Self { concrete, endpoint }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::future;
+    use linkerd2_app_core::{svc::ServiceExt, Error, NameAddr};
+
+    fn endpoint(concrete: Option<&str>) -> Endpoint {
+        let mut ep = Endpoint::from("10.1.1.1:8080".parse::<std::net::SocketAddr>().unwrap());
+        ep.dst_concrete = concrete.map(|c| NameAddr::from_str(c).unwrap());
+        ep
+    }
+
+    fn response(enabled: bool, ep: Endpoint) -> http::Response<()> {
+        let make = svc::mk(|_: Endpoint| {
+            future::ok::<_, ()>(svc::mk(|_: http::Request<()>| {
+                future::ok::<_, Error>(http::Response::new(()))
+            }))
+        });
+        let svc = svc::Layer::layer(&layer(enabled), make)
+            .oneshot(ep)
+            .wait()
+            .unwrap();
+        svc.oneshot(http::Request::new(())).wait().unwrap()
+    }
+
+    #[test]
+    fn adds_headers_when_enabled() {
+        let rsp = response(true, endpoint(Some("leaf.ns.svc.cluster.local:8080")));
+        assert_eq!(
+            rsp.headers().get(&*L5D_DST_CONCRETE).unwrap(),
+            "leaf.ns.svc.cluster.local:8080"
+        );
+        assert_eq!(
+            rsp.headers().get(&*L5D_DST_ENDPOINT).unwrap(),
+            "10.1.1.1:8080"
+        );
+
+        // Endpoints that were not discovered have no concrete name.
+        let rsp = response(true, endpoint(None));
+        assert!(rsp.headers().get(&*L5D_DST_CONCRETE).is_none());
+        assert_eq!(
+            rsp.headers().get(&*L5D_DST_ENDPOINT).unwrap(),
+            "10.1.1.1:8080"
+        );
+    }
+
+    #[test]
+    fn adds_no_headers_when_disabled() {
+        let rsp = response(false, endpoint(Some("leaf.ns.svc.cluster.local:8080")));
+        assert!(rsp.headers().get(&*L5D_DST_CONCRETE).is_none());
+        assert!(rsp.headers().get(&*L5D_DST_ENDPOINT).is_none());
+    }
+}
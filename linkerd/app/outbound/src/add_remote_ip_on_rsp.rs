@@ -5,12 +5,12 @@ use super::Endpoint;
 use bytes::Bytes;
 use http::header::HeaderValue;
 use linkerd2_app_core::{
+    headers::L5D_REMOTE_IP,
     proxy::http::add_header::{self, response::ResHeader, Layer},
-    L5D_REMOTE_IP,
 };
 
 pub fn layer() -> Layer<&'static str, Endpoint, ResHeader> {
-    add_header::response::layer(L5D_REMOTE_IP, |endpoint: &Endpoint| {
+    add_header::response::layer(L5D_REMOTE_IP.clone(), |endpoint: &Endpoint| {
         HeaderValue::from_shared(Bytes::from(endpoint.addr.ip().to_string())).ok()
     })
 }
@@ -4,13 +4,14 @@
 use super::Endpoint;
 use http::header::HeaderValue;
 use linkerd2_app_core::{
+    headers::L5D_SERVER_ID,
     proxy::http::add_header::{self, response::ResHeader, Layer},
-    Conditional, L5D_SERVER_ID,
+    Conditional,
 };
 use tracing::{debug, warn};
 
 pub fn layer() -> Layer<&'static str, Endpoint, ResHeader> {
-    add_header::response::layer(L5D_SERVER_ID, |endpoint: &Endpoint| {
+    add_header::response::layer(L5D_SERVER_ID.clone(), |endpoint: &Endpoint| {
         if let Conditional::Some(id) = endpoint.identity.as_ref() {
             match HeaderValue::from_str(id.as_ref()) {
                 Ok(value) => {
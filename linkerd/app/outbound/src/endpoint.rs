@@ -1,46 +1,145 @@
-use indexmap::IndexMap;
+use crate::upstream_proxy::Via;
+use indexmap::{IndexMap, IndexSet};
 use linkerd2_app_core::{
     dst::{DstAddr, Route},
-    metric_labels::{prefix_labels, EndpointLabels},
+    headers::L5D_REQUIRE_ID,
+    metric_labels::{CardinalityCap, EndpointLabels},
     proxy::{
         api_resolve::{Metadata, ProtocolHint},
-        http::{self, identity_from_header},
+        http::{self, balance::load_report::LoadReport, identities_from_header},
         identity,
         resolve::map_endpoint::MapEndpoint,
         tap,
     },
-    transport::{connect, tls},
-    Addr, Conditional, NameAddr, L5D_REQUIRE_ID,
+    svc::connect_timeout::HasConnectTimeout,
+    transport::{connect, limit, tls},
+    Addr, Conditional, NameAddr,
 };
 use std::net::SocketAddr;
 use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::watch;
 
-#[derive(Clone, Debug, Eq, PartialEq)]
+#[derive(Clone, Debug)]
 pub struct Endpoint {
     pub dst_logical: Option<NameAddr>,
     pub dst_concrete: Option<NameAddr>,
+    /// The address to dial, which may use a port hinted by service
+    /// discovery rather than the endpoint's own port.
     pub addr: SocketAddr,
+    /// The endpoint's own port, which is reported by telemetry even when
+    /// `addr` dials a different port.
+    pub orig_dst_port: u16,
     pub identity: tls::PeerIdentity,
+    /// Additional identities the peer may present when `identity` is not
+    /// accepted, e.g. from a multi-valued `l5d-require-id` header.
+    pub alternate_identities: Vec<identity::Name>,
     pub metadata: Metadata,
+    /// The configured metadata labels that, unlike the rest of `metadata`,
+    /// distinguish this endpoint from others at the same address.
+    pub key_labels: Vec<(String, String)>,
+    /// The topology zone in which the endpoint runs, if it is known.
+    pub zone: Option<String>,
+    /// Limits the number of concurrent connections to the endpoint, if set.
+    pub max_connections: Option<usize>,
+    /// How long the endpoint receives reduced traffic after it is added to a
+    /// balancer, if it needs to warm up.
+    pub warmup: Option<Duration>,
+    /// Live load reports that bias the balancer away from the endpoint while
+    /// it is busy, if they are available.
+    pub load_report: Option<watch::Receiver<LoadReport>>,
+    /// The fraction of its traffic that the balancer sends to the endpoint,
+    /// e.g. when it runs in another zone, if it is weighted.
+    pub weight: Option<f64>,
+    /// Bounds the number of metadata labels included in the endpoint's
+    /// metrics.
+    pub label_cap: CardinalityCap,
     pub http_settings: http::Settings,
+    /// Set when `addr` is an upstream proxy through which the destination is
+    /// reached.
+    pub via: Option<Via>,
+    pub connect_timeouts: ConnectTimeouts,
+    /// Overrides the client's HTTP/2 settings for endpoints that are known
+    /// to be meshed.
+    pub meshed_h2_settings: http::h2::Settings,
 }
 
+/// The metadata label that describes the topology zone of an endpoint.
+const ZONE_LABEL: &str = "topology.kubernetes.io/zone";
+
+/// The metadata label that limits the number of concurrent connections to an
+/// endpoint.
+const MAX_CONNECTIONS_LABEL: &str = "l5d-max-connections";
+
+/// The metadata label that describes how long an endpoint takes to warm up,
+/// e.g. `30s`.
+const WARMUP_DURATION_LABEL: &str = "l5d-warmup-duration";
+
+/// The connect timeouts used for endpoints when service discovery does not
+/// provide a hint.
 #[derive(Copy, Clone, Debug)]
-pub struct FromMetadata;
+pub struct ConnectTimeouts {
+    /// Used for endpoints with an identity and an HTTP/2 protocol hint, i.e.
+    /// endpoints that are known to be meshed.
+    pub meshed: Duration,
+    pub unmeshed: Duration,
+}
+
+/// Builds endpoints from service discovery metadata.
+#[derive(Clone, Debug, Default)]
+pub struct FromMetadata {
+    key_labels: Arc<IndexSet<String>>,
+    connect_timeouts: ConnectTimeouts,
+    meshed_h2_settings: http::h2::Settings,
+    label_cap: CardinalityCap,
+}
+
+/// A concrete destination that is resolved through its own address and then
+/// through its addresses in other clusters.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Profile {
+    dst: DstAddr,
+    /// The destination's addresses in other clusters, in the order they are
+    /// resolved.
+    extra_clusters: Vec<Addr>,
+}
+
+/// Builds the `Profile` of each concrete destination from the configured
+/// extra clusters.
+#[derive(Clone, Debug, Default)]
+pub struct ProfileTarget {
+    extra_clusters: Arc<IndexMap<NameAddr, Vec<Addr>>>,
+}
 
 impl Endpoint {
+    /// Balances requests to this endpoint according to its load reports.
+    pub fn with_load_report(self, rx: watch::Receiver<LoadReport>) -> Self {
+        Self {
+            load_report: Some(rx),
+            ..self
+        }
+    }
+
     pub fn can_use_orig_proto(&self) -> bool {
         match self.metadata.protocol_hint() {
             ProtocolHint::Unknown => return false,
             ProtocolHint::Http2 => (),
         }
 
+        // HTTP/1.0 `CONNECT` requests are not considered upgrades, but they
+        // establish tunnels all the same.
+        if self.http_settings.is_connect_tunnel() {
+            return false;
+        }
+
         match self.http_settings {
             http::Settings::Http2 => false,
             http::Settings::Http1 {
                 keep_alive: _,
                 wants_h1_upgrade,
                 was_absolute_form: _,
+                is_http_1_0: _,
+                is_connect: _,
             } => !wants_h1_upgrade,
             http::Settings::NotHttp => {
                 unreachable!(
@@ -51,6 +150,35 @@ impl Endpoint {
         }
     }
 
+    pub fn with_connect_timeouts(self, connect_timeouts: ConnectTimeouts) -> Self {
+        Self {
+            connect_timeouts,
+            ..self
+        }
+    }
+
+    /// Returns how long a connection to this endpoint may take to be
+    /// established.
+    ///
+    /// Service discovery's hint is preferred. Otherwise, endpoints that are
+    /// known to be meshed use the (typically shorter) meshed timeout.
+    pub fn connect_timeout(&self) -> Duration {
+        if let Some(timeout) = self.metadata.connect_timeout() {
+            return timeout;
+        }
+
+        match (self.metadata.protocol_hint(), &self.identity) {
+            (ProtocolHint::Http2, Conditional::Some(_)) => self.connect_timeouts.meshed,
+            _ => self.connect_timeouts.unmeshed,
+        }
+    }
+
+    /// Returns the endpoint's socket address, which, unlike the endpoint's
+    /// `Display` output, may be parsed back into a `SocketAddr`.
+    pub fn socket_addr(&self) -> SocketAddr {
+        self.addr
+    }
+
     pub fn from_request<B>(req: &http::Request<B>) -> Option<Self> {
         let addr = req
             .extensions()
@@ -59,20 +187,34 @@ impl Endpoint {
             .target_addr_if_not_local()?;
 
         let http_settings = http::Settings::from_request(req);
-        let identity = match identity_from_header(req, L5D_REQUIRE_ID) {
+        let mut require_ids = identities_from_header(req, &*L5D_REQUIRE_ID).into_iter();
+        let identity = match require_ids.next() {
             Some(require_id) => Conditional::Some(require_id),
             None => {
                 Conditional::None(tls::ReasonForNoPeerName::NotProvidedByServiceDiscovery.into())
             }
         };
+        let alternate_identities = require_ids.collect();
 
         Some(Self {
             addr,
+            orig_dst_port: addr.port(),
             dst_logical: None,
             dst_concrete: None,
             identity,
+            alternate_identities,
             metadata: Metadata::empty(),
+            key_labels: Vec::new(),
+            zone: None,
+            max_connections: None,
+            warmup: None,
+            load_report: None,
+            weight: None,
+            label_cap: CardinalityCap::default(),
             http_settings,
+            via: None,
+            connect_timeouts: ConnectTimeouts::default(),
+            meshed_h2_settings: http::h2::Settings::default(),
         })
     }
 }
@@ -81,18 +223,67 @@ impl From<SocketAddr> for Endpoint {
     fn from(addr: SocketAddr) -> Self {
         Self {
             addr,
+            orig_dst_port: addr.port(),
             dst_logical: None,
             dst_concrete: None,
             identity: Conditional::None(tls::ReasonForNoPeerName::NotHttp.into()),
+            alternate_identities: Vec::new(),
             metadata: Metadata::empty(),
+            key_labels: Vec::new(),
+            zone: None,
+            max_connections: None,
+            warmup: None,
+            load_report: None,
+            weight: None,
+            label_cap: CardinalityCap::default(),
             http_settings: http::Settings::NotHttp,
+            via: None,
+            connect_timeouts: ConnectTimeouts::default(),
+            meshed_h2_settings: http::h2::Settings::default(),
+        }
+    }
+}
+
+impl Default for ConnectTimeouts {
+    fn default() -> Self {
+        let timeout = Duration::from_secs(1);
+        Self {
+            meshed: timeout,
+            unmeshed: timeout,
         }
     }
 }
 
+/// Describes the endpoint for logs and errors, e.g.
+/// `10.4.2.8:8080 (id=web.ns.serviceaccount.identity.linkerd.cluster.local, h2-hint, dst=web.ns.svc.cluster.local:8080)`.
+///
+/// Use `Endpoint::socket_addr` where only the address is needed.
 impl std::fmt::Display for Endpoint {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        self.addr.fmt(f)
+        std::fmt::Display::fmt(&self.addr, f)?;
+
+        let mut sep = " (";
+        if let Conditional::Some(ref id) = self.identity {
+            write!(f, "{}id={}", sep, id)?;
+            sep = ", ";
+        }
+        if let ProtocolHint::Http2 = self.metadata.protocol_hint() {
+            write!(f, "{}h2-hint", sep)?;
+            sep = ", ";
+        }
+        if let Some(ref dst) = self.dst_logical {
+            write!(f, "{}dst={}", sep, dst)?;
+            sep = ", ";
+        }
+        if let Some(ref via) = self.via {
+            write!(f, "{}proxy-for={}", sep, via.dst)?;
+            sep = ", ";
+        }
+        if sep == ", " {
+            f.write_str(")")?;
+        }
+
+        Ok(())
     }
 }
 
@@ -101,9 +292,73 @@ impl std::hash::Hash for Endpoint {
         self.dst_logical.hash(state);
         self.dst_concrete.hash(state);
         self.addr.hash(state);
+        self.orig_dst_port.hash(state);
         self.identity.hash(state);
+        self.alternate_identities.hash(state);
         self.http_settings.hash(state);
-        // Ignore metadata.
+        self.via.hash(state);
+        // Ignore metadata, except for the configured key labels.
+        self.key_labels.hash(state);
+    }
+}
+
+/// Like `Hash`, equality ignores metadata other than the key labels, so that
+/// an endpoint's client is reused across metadata updates.
+impl PartialEq for Endpoint {
+    fn eq(&self, other: &Self) -> bool {
+        self.dst_logical == other.dst_logical
+            && self.dst_concrete == other.dst_concrete
+            && self.addr == other.addr
+            && self.orig_dst_port == other.orig_dst_port
+            && self.identity == other.identity
+            && self.alternate_identities == other.alternate_identities
+            && self.http_settings == other.http_settings
+            && self.via == other.via
+            && self.key_labels == other.key_labels
+    }
+}
+
+impl Eq for Endpoint {}
+
+/// Endpoints that are hinted to be Linkerd peers may use settings (e.g.
+/// larger flow control windows) that are not appropriate for arbitrary
+/// HTTP/2 servers.
+impl http::h2::HasH2Settings for Endpoint {
+    fn h2_settings(&self) -> http::h2::Settings {
+        match self.metadata.protocol_hint() {
+            ProtocolHint::Http2 => self.meshed_h2_settings,
+            ProtocolHint::Unknown => http::h2::Settings::default(),
+        }
+    }
+}
+
+impl HasConnectTimeout for Endpoint {
+    fn connect_timeout(&self) -> Duration {
+        Endpoint::connect_timeout(self)
+    }
+}
+
+impl limit::HasConnectionLimit for Endpoint {
+    fn connection_limit(&self) -> Option<usize> {
+        self.max_connections
+    }
+}
+
+impl http::balance::warmup::HasWarmup for Endpoint {
+    fn warmup(&self) -> Option<Duration> {
+        self.warmup
+    }
+}
+
+impl http::balance::warmup::HasWeight for Endpoint {
+    fn weight(&self) -> Option<f64> {
+        self.weight
+    }
+}
+
+impl http::balance::load_report::HasLoadReport for Endpoint {
+    fn load_report(&self) -> Option<watch::Receiver<LoadReport>> {
+        self.load_report.clone()
     }
 }
 
@@ -111,16 +366,40 @@ impl tls::HasPeerIdentity for Endpoint {
     fn peer_identity(&self) -> tls::PeerIdentity {
         self.identity.clone()
     }
+
+    fn alternate_peer_identities(&self) -> Vec<identity::Name> {
+        self.alternate_identities.clone()
+    }
+}
+
+impl http::profiles::CanGetDestination for Endpoint {
+    fn get_destination(&self) -> Option<&NameAddr> {
+        self.dst_logical.as_ref()
+    }
 }
 
 impl connect::HasPeerAddr for Endpoint {
     fn peer_addr(&self) -> SocketAddr {
         self.addr
     }
+
+    fn fmt_peer(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Display::fmt(self, f)
+    }
 }
 
 impl http::normalize_uri::ShouldNormalizeUri for Endpoint {
     fn should_normalize_uri(&self) -> Option<http::uri::Authority> {
+        // Requests to an upstream proxy must name their destination.
+        if let Some(ref via) = self.via {
+            return Some(
+                self.dst_logical
+                    .as_ref()
+                    .map(|dst| dst.as_http_authority())
+                    .unwrap_or_else(|| via.dst.to_http_authority()),
+            );
+        }
+
         if let http::Settings::Http1 {
             was_absolute_form: false,
             ..
@@ -158,13 +437,17 @@ impl tap::Inspect for Endpoint {
     }
 
     fn dst_addr<B>(&self, _: &http::Request<B>) -> Option<SocketAddr> {
-        Some(self.addr)
+        Some(SocketAddr::new(self.addr.ip(), self.orig_dst_port))
     }
 
     fn dst_labels<B>(&self, _: &http::Request<B>) -> Option<&IndexMap<String, String>> {
         Some(self.metadata.labels())
     }
 
+    fn dst_summary<B>(&self, _: &http::Request<B>) -> Option<String> {
+        Some(self.to_string())
+    }
+
     fn dst_tls<B>(
         &self,
         _: &http::Request<B>,
@@ -173,7 +456,9 @@ impl tap::Inspect for Endpoint {
     }
 
     fn route_labels<B>(&self, req: &http::Request<B>) -> Option<Arc<IndexMap<String, String>>> {
-        req.extensions().get::<Route>().map(|r| r.labels().clone())
+        req.extensions()
+            .get::<Route>()
+            .map(|r| r.route_labels().clone())
     }
 
     fn is_outbound<B>(&self, _: &http::Request<B>) -> bool {
@@ -181,6 +466,81 @@ impl tap::Inspect for Endpoint {
     }
 }
 
+impl FromMetadata {
+    /// Distinguishes endpoints at the same address by the values of the
+    /// `key_labels` in their metadata, and connects to them within
+    /// `connect_timeouts` unless their metadata hints otherwise.
+    pub fn new(key_labels: Arc<IndexSet<String>>, connect_timeouts: ConnectTimeouts) -> Self {
+        Self {
+            key_labels,
+            connect_timeouts,
+            meshed_h2_settings: http::h2::Settings::default(),
+            label_cap: CardinalityCap::default(),
+        }
+    }
+
+    /// Bounds the number of metadata labels included in endpoints' metrics.
+    pub fn with_label_cap(self, label_cap: CardinalityCap) -> Self {
+        Self { label_cap, ..self }
+    }
+
+    /// Overrides the HTTP/2 settings of clients for endpoints that are hinted
+    /// to be meshed.
+    pub fn with_meshed_h2_settings(self, meshed_h2_settings: http::h2::Settings) -> Self {
+        Self {
+            meshed_h2_settings,
+            ..self
+        }
+    }
+}
+
+impl ProfileTarget {
+    /// Resolves each concrete name in `extra_clusters` through its addresses
+    /// in other clusters, as well as through the name itself.
+    pub fn new(extra_clusters: IndexMap<NameAddr, Vec<Addr>>) -> Self {
+        Self {
+            extra_clusters: Arc::new(extra_clusters),
+        }
+    }
+
+    /// Returns the profile of `dst`, including the extra clusters that are
+    /// configured for its concrete name.
+    pub fn key(&self, dst: DstAddr) -> Profile {
+        let extra_clusters = dst
+            .dst_concrete()
+            .name_addr()
+            .and_then(|name| self.extra_clusters.get(name))
+            .cloned()
+            .unwrap_or_default();
+        Self::key_with_clusters(dst, extra_clusters)
+    }
+
+    /// Returns a profile that resolves `dst` and then each of
+    /// `extra_clusters`.
+    pub fn key_with_clusters(dst: DstAddr, extra_clusters: Vec<Addr>) -> Profile {
+        Profile {
+            dst,
+            extra_clusters,
+        }
+    }
+}
+
+impl Profile {
+    /// Returns the target through which `addr`, one of the profile's
+    /// destinations, is resolved.
+    pub fn target(&self, addr: Addr) -> DstAddr {
+        self.dst.clone().with_concrete(addr)
+    }
+}
+
+impl http::profiles::HasMultiDestination for Profile {
+    fn destinations(&self) -> Vec<Addr> {
+        std::iter::once(self.dst.dst_concrete().clone())
+            .chain(self.extra_clusters.iter().cloned())
+            .collect()
+    }
+}
+
 impl MapEndpoint<DstAddr, Metadata> for FromMetadata {
     type Out = Endpoint;
 
@@ -192,26 +552,477 @@ impl MapEndpoint<DstAddr, Metadata> for FromMetadata {
             .unwrap_or_else(|| {
                 Conditional::None(tls::ReasonForNoPeerName::NotProvidedByServiceDiscovery.into())
             });
+
+        // Only meshed endpoints may be dialed on an alternate port.
+        let orig_dst_port = addr.port();
+        let addr = match metadata.dst_override_port() {
+            Some(port) if identity.is_some() => SocketAddr::new(addr.ip(), port),
+            _ => addr,
+        };
+
+        let key_labels = self
+            .key_labels
+            .iter()
+            .filter_map(|k| {
+                let v = metadata.labels().get(k)?;
+                Some((k.clone(), v.clone()))
+            })
+            .collect();
+        let zone = metadata.labels().get(ZONE_LABEL).cloned();
+        let max_connections = metadata
+            .labels()
+            .get(MAX_CONNECTIONS_LABEL)
+            .and_then(|max| max.parse().ok());
+        let warmup = metadata
+            .labels()
+            .get(WARMUP_DURATION_LABEL)
+            .and_then(|warmup| parse_duration(warmup));
+
         Endpoint {
             addr,
+            orig_dst_port,
             identity,
+            alternate_identities: Vec::new(),
             metadata,
+            key_labels,
+            zone,
+            max_connections,
+            warmup,
+            load_report: None,
+            weight: None,
+            label_cap: self.label_cap.clone(),
             dst_logical: target.dst_logical().name_addr().cloned(),
             dst_concrete: target.dst_concrete().name_addr().cloned(),
             http_settings: target.http_settings.clone(),
+            via: None,
+            connect_timeouts: self.connect_timeouts,
+            meshed_h2_settings: self.meshed_h2_settings,
         }
     }
 }
 
+/// Parses durations like `500ms`, `30s`, or `2m`.
+fn parse_duration(s: &str) -> Option<Duration> {
+    let s = s.trim();
+    let split = s
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or_else(|| s.len());
+    let magnitude = s[..split].parse::<u64>().ok()?;
+    match &s[split..] {
+        "ms" => Some(Duration::from_millis(magnitude)),
+        "s" => Some(Duration::from_secs(magnitude)),
+        "m" => Some(Duration::from_secs(magnitude * 60)),
+        _ => None,
+    }
+}
+
 impl Into<EndpointLabels> for Endpoint {
     fn into(self) -> EndpointLabels {
         use linkerd2_app_core::metric_labels::{Direction, TlsId};
+        let mut labels = self
+            .label_cap
+            .prefix_labels("dst", self.metadata.labels().into_iter());
+        if let Some(ref via) = self.via {
+            let via = format!("via=\"{}\"", via.proxy.addr);
+            labels = Some(match labels {
+                Some(labels) => format!("{},{}", labels, via),
+                None => via,
+            });
+        }
         EndpointLabels {
             dst_logical: self.dst_logical,
             dst_concrete: self.dst_concrete,
             direction: Direction::Out,
             tls_id: self.identity.as_ref().map(|id| TlsId::ServerId(id.clone())),
-            labels: prefix_labels("dst", self.metadata.labels().into_iter()),
+            labels,
+            route_labels: None,
+            src_namespace: None,
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use linkerd2_app_core::transport::listen::Addrs;
+
+    fn require_id_req(values: &[&str]) -> http::Request<()> {
+        let meta = tls::accept::Meta {
+            peer_identity: Conditional::None(tls::ReasonForNoPeerName::Loopback.into()),
+            addrs: Addrs::new(
+                "127.0.0.1:4140".parse().unwrap(),
+                "10.2.2.2:33333".parse().unwrap(),
+                Some("10.1.1.1:8080".parse().unwrap()),
+            ),
+        };
+        let mut req = http::Request::builder();
+        for v in values {
+            req.header(&*L5D_REQUIRE_ID, *v);
+        }
+        let mut req = req.body(()).unwrap();
+        req.extensions_mut().insert(meta);
+        req
+    }
+
+    fn name(s: &str) -> identity::Name {
+        identity::Name::from_hostname(s.as_bytes()).unwrap()
+    }
+
+    #[test]
+    fn from_request_collects_all_required_identities() {
+        let req = require_id_req(&[
+            "foo.ns1.serviceaccount.identity.linkerd.cluster.local",
+            "bar.ns1.serviceaccount.identity.linkerd.cluster.local",
+        ]);
+        let ep = Endpoint::from_request(&req).expect("endpoint");
+        assert_eq!(
+            ep.identity,
+            Conditional::Some(name(
+                "foo.ns1.serviceaccount.identity.linkerd.cluster.local"
+            ))
+        );
+        assert_eq!(
+            tls::HasPeerIdentity::alternate_peer_identities(&ep),
+            vec![name(
+                "bar.ns1.serviceaccount.identity.linkerd.cluster.local"
+            )]
+        );
+    }
+
+    #[test]
+    fn from_request_splits_comma_separated_identities() {
+        let req = require_id_req(&["foo.ns1.serviceaccount.identity.linkerd.cluster.local, \
+                                    bar.ns1.serviceaccount.identity.linkerd.cluster.local"]);
+        let ep = Endpoint::from_request(&req).expect("endpoint");
+        assert_eq!(
+            ep.identity,
+            Conditional::Some(name(
+                "foo.ns1.serviceaccount.identity.linkerd.cluster.local"
+            ))
+        );
+        assert_eq!(
+            ep.alternate_identities,
+            vec![name(
+                "bar.ns1.serviceaccount.identity.linkerd.cluster.local"
+            )]
+        );
+    }
+
+    #[test]
+    fn display_includes_identity_hint_and_dst() {
+        let mut ep = Endpoint::from("10.4.2.8:8080".parse::<SocketAddr>().unwrap());
+        assert_eq!(ep.to_string(), "10.4.2.8:8080");
+        assert_eq!(ep.socket_addr().to_string(), "10.4.2.8:8080");
+
+        ep.identity =
+            Conditional::Some(name("web.ns.serviceaccount.identity.linkerd.cluster.local"));
+        ep.metadata = Metadata::new(Default::default(), ProtocolHint::Http2, None, 10_000);
+        ep.dst_logical = Some(NameAddr::from_str("web.ns.svc.cluster.local:8080").unwrap());
+        assert_eq!(
+            ep.to_string(),
+            "10.4.2.8:8080 (id=web.ns.serviceaccount.identity.linkerd.cluster.local, \
+             h2-hint, dst=web.ns.svc.cluster.local:8080)"
+        );
+        assert_eq!(ep.socket_addr().to_string(), "10.4.2.8:8080");
+    }
+
+    fn dst_addr() -> DstAddr {
+        let addr = Addr::from_str("web.ns.svc.cluster.local:8080").unwrap();
+        DstAddr::outbound(addr, http::Settings::Http2)
+    }
+
+    #[test]
+    fn map_endpoint_overrides_port_for_meshed_endpoints() {
+        let id = name("web.ns.serviceaccount.identity.linkerd.cluster.local");
+        let meta = Metadata::new(Default::default(), ProtocolHint::Http2, Some(id), 10_000)
+            .with_dst_override_port(Some(4143));
+        let ep = FromMetadata::default().map_endpoint(
+            &dst_addr(),
+            "10.4.2.8:8080".parse().unwrap(),
+            meta,
+        );
+        assert_eq!(ep.addr, "10.4.2.8:4143".parse::<SocketAddr>().unwrap());
+        assert_eq!(ep.orig_dst_port, 8080);
+
+        let req = http::Request::new(());
+        assert_eq!(
+            tap::Inspect::dst_addr(&ep, &req),
+            Some("10.4.2.8:8080".parse().unwrap())
+        );
+    }
+
+    #[test]
+    fn map_endpoint_never_overrides_port_for_unmeshed_endpoints() {
+        let meta = Metadata::new(Default::default(), ProtocolHint::Unknown, None, 10_000)
+            .with_dst_override_port(Some(4143));
+        let ep = FromMetadata::default().map_endpoint(
+            &dst_addr(),
+            "10.4.2.8:8080".parse().unwrap(),
+            meta,
+        );
+        assert_eq!(ep.addr, "10.4.2.8:8080".parse::<SocketAddr>().unwrap());
+        assert_eq!(ep.orig_dst_port, 8080);
+    }
+
+    fn labeled(pod: &str, version: &str, zone: &str) -> Metadata {
+        let labels = vec![
+            ("pod".to_owned(), pod.to_owned()),
+            ("version".to_owned(), version.to_owned()),
+            ("zone".to_owned(), zone.to_owned()),
+        ];
+        Metadata::new(
+            labels.into_iter().collect(),
+            ProtocolHint::Unknown,
+            None,
+            10_000,
+        )
+    }
+
+    fn hash(ep: &Endpoint) -> u64 {
+        use std::hash::{Hash, Hasher};
+        let mut hasher = std::collections::hash_map::DefaultHasher::new();
+        ep.hash(&mut hasher);
+        hasher.finish()
+    }
+
+    #[test]
+    fn map_endpoint_sets_zone() {
+        let addr = "10.4.2.8:8080".parse().unwrap();
+        let from = FromMetadata::default();
+
+        let ep = from.map_endpoint(&dst_addr(), addr, labeled("web-1", "v1", "a"));
+        assert_eq!(ep.zone, None);
+
+        let labels = vec![(ZONE_LABEL.to_owned(), "us-west-1a".to_owned())];
+        let meta = Metadata::new(
+            labels.into_iter().collect(),
+            ProtocolHint::Unknown,
+            None,
+            10_000,
+        );
+        let ep = from.map_endpoint(&dst_addr(), addr, meta);
+        assert_eq!(ep.zone, Some("us-west-1a".to_owned()));
+    }
+
+    #[test]
+    fn map_endpoint_sets_max_connections() {
+        let addr = "10.4.2.8:8080".parse().unwrap();
+        let from = FromMetadata::default();
+
+        let ep = from.map_endpoint(&dst_addr(), addr, labeled("web-1", "v1", "a"));
+        assert_eq!(ep.max_connections, None);
+
+        let labels = vec![(MAX_CONNECTIONS_LABEL.to_owned(), "100".to_owned())];
+        let meta = Metadata::new(
+            labels.into_iter().collect(),
+            ProtocolHint::Unknown,
+            None,
+            10_000,
+        );
+        let ep = from.map_endpoint(&dst_addr(), addr, meta);
+        assert_eq!(ep.max_connections, Some(100));
+    }
+
+    #[test]
+    fn load_reports_are_opt_in() {
+        use http::balance::load_report::HasLoadReport;
+
+        let addr = "10.4.2.8:8080".parse().unwrap();
+        let from = FromMetadata::default();
+        let ep = from.map_endpoint(&dst_addr(), addr, labeled("web-1", "v1", "a"));
+        assert!(ep.load_report().is_none());
+
+        let (_tx, rx) = watch::channel(LoadReport {
+            cpu_utilization: 0.5,
+            request_rate: 10.0,
+        });
+        let reported = ep.clone().with_load_report(rx);
+        assert_eq!(
+            reported.load_report().map(|rx| *rx.get_ref()),
+            Some(LoadReport {
+                cpu_utilization: 0.5,
+                request_rate: 10.0,
+            })
+        );
+        // Load reports do not distinguish endpoints.
+        assert_eq!(reported, ep);
+    }
+
+    #[test]
+    fn map_endpoint_sets_warmup() {
+        let addr = "10.4.2.8:8080".parse().unwrap();
+        let from = FromMetadata::default();
+
+        let ep = from.map_endpoint(&dst_addr(), addr, labeled("web-1", "v1", "a"));
+        assert_eq!(ep.warmup, None);
+
+        for &(label, warmup) in &[
+            ("30s", Some(Duration::from_secs(30))),
+            ("500ms", Some(Duration::from_millis(500))),
+            ("2m", Some(Duration::from_secs(120))),
+            ("soon", None),
+        ] {
+            let labels = vec![(WARMUP_DURATION_LABEL.to_owned(), label.to_owned())];
+            let meta = Metadata::new(
+                labels.into_iter().collect(),
+                ProtocolHint::Unknown,
+                None,
+                10_000,
+            );
+            let ep = from.map_endpoint(&dst_addr(), addr, meta);
+            assert_eq!(ep.warmup, warmup, "{}", label);
+        }
+    }
+
+    #[test]
+    fn endpoints_ignore_labels_by_default() {
+        let addr = "10.4.2.8:8080".parse().unwrap();
+        let from = FromMetadata::default();
+        let old = from.map_endpoint(&dst_addr(), addr, labeled("web-1", "v1", "a"));
+        let new = from.map_endpoint(&dst_addr(), addr, labeled("web-2", "v2", "a"));
+
+        // A redeployed pod at the same address reuses the old endpoint's
+        // cache key and, with it, the old labels.
+        assert_eq!(old, new);
+        assert_eq!(hash(&old), hash(&new));
+    }
+
+    #[test]
+    fn endpoints_distinguished_by_key_labels() {
+        let addr = "10.4.2.8:8080".parse().unwrap();
+        let keys = vec!["pod".to_owned(), "version".to_owned()];
+        let from = FromMetadata::new(
+            Arc::new(keys.into_iter().collect()),
+            ConnectTimeouts::default(),
+        );
+
+        let old = from.map_endpoint(&dst_addr(), addr, labeled("web-1", "v1", "a"));
+        let new = from.map_endpoint(&dst_addr(), addr, labeled("web-2", "v2", "a"));
+        assert_ne!(old, new);
+        assert_ne!(hash(&old), hash(&new));
+
+        // Labels that are not configured do not change the key.
+        let moved = from.map_endpoint(&dst_addr(), addr, labeled("web-1", "v1", "b"));
+        assert_eq!(old, moved);
+        assert_eq!(hash(&old), hash(&moved));
+    }
+
+    #[test]
+    fn connect_tunnels_cannot_use_orig_proto() {
+        let mut ep = Endpoint::from("10.4.2.8:8080".parse::<SocketAddr>().unwrap());
+        ep.metadata = Metadata::new(Default::default(), ProtocolHint::Http2, None, 10_000);
+
+        let req = |method: ::http::Method| {
+            http::Request::builder()
+                .method(method)
+                .version(::http::Version::HTTP_10)
+                .uri("web.ns.svc.cluster.local:8080")
+                .header(http::header::HOST, "web.ns.svc.cluster.local:8080")
+                .body(())
+                .unwrap()
+        };
+
+        ep.http_settings = http::Settings::from_request(&req(::http::Method::GET));
+        assert!(!ep.http_settings.is_connect_tunnel());
+        assert!(ep.can_use_orig_proto());
+
+        ep.http_settings = http::Settings::from_request(&req(::http::Method::CONNECT));
+        assert!(ep.http_settings.is_connect_tunnel());
+        assert!(!ep.can_use_orig_proto());
+    }
+
+    #[test]
+    fn from_request_without_required_identity() {
+        let ep = Endpoint::from_request(&require_id_req(&[])).expect("endpoint");
+        assert!(ep.identity.is_none());
+        assert!(ep.alternate_identities.is_empty());
+    }
+
+    fn connect_timeouts() -> ConnectTimeouts {
+        ConnectTimeouts {
+            meshed: Duration::from_millis(100),
+            unmeshed: Duration::from_secs(3),
+        }
+    }
+
+    #[test]
+    fn connect_timeout_prefers_metadata_hint() {
+        let id = name("web.ns.serviceaccount.identity.linkerd.cluster.local");
+        let meta = Metadata::new(Default::default(), ProtocolHint::Http2, Some(id), 10_000)
+            .with_connect_timeout(Some(Duration::from_millis(250)));
+        let ep = FromMetadata::new(Default::default(), connect_timeouts()).map_endpoint(
+            &dst_addr(),
+            "10.4.2.8:8080".parse().unwrap(),
+            meta,
+        );
+        assert_eq!(
+            HasConnectTimeout::connect_timeout(&ep),
+            Duration::from_millis(250)
+        );
+    }
+
+    #[test]
+    fn connect_timeout_falls_back_to_meshed_default() {
+        let id = name("web.ns.serviceaccount.identity.linkerd.cluster.local");
+        let meta = Metadata::new(Default::default(), ProtocolHint::Http2, Some(id), 10_000);
+        let ep = FromMetadata::new(Default::default(), connect_timeouts()).map_endpoint(
+            &dst_addr(),
+            "10.4.2.8:8080".parse().unwrap(),
+            meta,
+        );
+        assert_eq!(
+            HasConnectTimeout::connect_timeout(&ep),
+            connect_timeouts().meshed
+        );
+    }
+
+    #[test]
+    fn connect_timeout_falls_back_to_unmeshed_default() {
+        let from = FromMetadata::new(Default::default(), connect_timeouts());
+        let addr = "10.4.2.8:8080".parse().unwrap();
+
+        // An identity without a protocol hint is not known to be meshed.
+        let id = name("web.ns.serviceaccount.identity.linkerd.cluster.local");
+        let meta = Metadata::new(Default::default(), ProtocolHint::Unknown, Some(id), 10_000);
+        let ep = from.map_endpoint(&dst_addr(), addr, meta);
+        assert_eq!(
+            HasConnectTimeout::connect_timeout(&ep),
+            connect_timeouts().unmeshed
+        );
+
+        let meta = Metadata::new(Default::default(), ProtocolHint::Http2, None, 10_000);
+        let ep = from.map_endpoint(&dst_addr(), addr, meta);
+        assert_eq!(
+            HasConnectTimeout::connect_timeout(&ep),
+            connect_timeouts().unmeshed
+        );
+
+        let ep = Endpoint::from(addr).with_connect_timeouts(connect_timeouts());
+        assert_eq!(
+            HasConnectTimeout::connect_timeout(&ep),
+            connect_timeouts().unmeshed
+        );
+    }
+
+    #[test]
+    fn meshed_endpoints_override_h2_settings() {
+        use http::h2::{HasH2Settings, Settings};
+
+        let meshed = Settings {
+            initial_stream_window_size: Some(1_048_576),
+            initial_connection_window_size: Some(4_194_304),
+            adaptive_window: None,
+            max_concurrent_streams: None,
+        };
+        let from = FromMetadata::default().with_meshed_h2_settings(meshed);
+        let addr = "10.4.2.8:8080".parse().unwrap();
+
+        let meta = Metadata::new(Default::default(), ProtocolHint::Http2, None, 10_000);
+        let ep = from.map_endpoint(&dst_addr(), addr, meta);
+        assert_eq!(ep.h2_settings(), meshed);
+
+        let meta = Metadata::new(Default::default(), ProtocolHint::Unknown, None, 10_000);
+        let ep = from.map_endpoint(&dst_addr(), addr, meta);
+        assert_eq!(ep.h2_settings(), Settings::default());
+    }
+}
@@ -6,41 +6,47 @@
 #![deny(warnings, rust_2018_idioms)]
 
 use futures::future;
+use indexmap::IndexSet;
 use linkerd2_app_core::{
     self as core, classify,
-    config::{ProxyConfig, ServerConfig},
+    config::{FailureAccrualConfig, ProxyConfig, ServerConfig},
     dns, drain,
     dst::DstAddr,
-    errors, http_request_authority_addr, http_request_host_addr,
-    http_request_l5d_override_dst_addr, http_request_orig_dst_addr,
+    errors, headers, http_request_addr_with_default_port, http_request_l5d_override_dst_addr,
+    http_request_orig_dst_addr,
+    metric_labels::EndpointLabels,
     opencensus::proto::trace::v1 as oc,
     proxy::{
         self, core::resolve::Resolve, discover, fallback, http, identity, resolve::map_endpoint,
         tap, tcp, Server,
     },
-    reconnect, router, serve,
+    reconnect, request_filter, router, serve,
     spans::SpanConverter,
     svc, trace, trace_context,
     transport::{self, connect, tls, OrigDstAddr, SysOrigDstAddr},
-    Addr, Conditional, DispatchDeadline, Error, ProxyMetrics, CANONICAL_DST_HEADER,
-    DST_OVERRIDE_HEADER, L5D_CLIENT_ID, L5D_REMOTE_IP, L5D_REQUIRE_ID, L5D_SERVER_ID,
+    Addr, Conditional, DispatchDeadline, Error, ProxyMetrics, RejectInvalidDstOverride,
 };
 use std::collections::HashMap;
 use std::net::SocketAddr;
+use std::sync::Arc;
 use std::time::Duration;
 use tokio::sync::mpsc;
 use tower_grpc::{self as grpc, generic::client::GrpcService};
-use tracing::{debug, info_span};
+use tracing::{debug, debug_span, info_span};
 
+mod add_dst_on_rsp;
 #[allow(dead_code)] // TODO #2597
 mod add_remote_ip_on_rsp;
 #[allow(dead_code)] // TODO #2597
 mod add_server_id_on_rsp;
 mod endpoint;
+mod multi_cluster;
 mod orig_proto_upgrade;
 mod require_identity_on_endpoint;
+pub mod upstream_proxy;
+mod zone_affinity;
 
-pub use self::endpoint::Endpoint;
+pub use self::endpoint::{Endpoint, ProfileTarget};
 
 const EWMA_DEFAULT_RTT: Duration = Duration::from_millis(30);
 const EWMA_DECAY: Duration = Duration::from_secs(10);
@@ -49,6 +55,46 @@ const EWMA_DECAY: Duration = Duration::from_secs(10);
 pub struct Config<A: OrigDstAddr = SysOrigDstAddr> {
     pub proxy: ProxyConfig<A>,
     pub canonicalize_timeout: Duration,
+    pub response_validation_allowlist: Arc<IndexSet<dns::Suffix>>,
+    /// Endpoint metadata labels that distinguish otherwise identical
+    /// endpoints.
+    pub endpoint_key_labels: Arc<IndexSet<String>>,
+    /// Destinations that are reached through an upstream HTTP proxy.
+    pub upstream_proxies: Arc<upstream_proxy::Rules>,
+    /// Destinations that are also resolved through their addresses in other
+    /// clusters.
+    pub extra_clusters: ProfileTarget,
+    /// Whether responses describe the concrete destination and endpoint that
+    /// served each request.
+    pub expose_dst_headers: bool,
+    /// The connect timeout for endpoints that are known to be meshed, unless
+    /// service discovery hints otherwise. Other endpoints use the proxy's
+    /// connect timeout.
+    pub meshed_connect_timeout: Duration,
+    /// Overrides the HTTP/2 client settings for endpoints that are known to
+    /// be meshed.
+    pub meshed_h2_settings: http::h2::Settings,
+    /// The largest request body that is buffered so that the request may be
+    /// retried.
+    pub max_replay_body_bytes: usize,
+    /// When set, backends that are added to a traffic split are given up to
+    /// this long to become ready before requests are routed to them.
+    pub split_prewarm_timeout: Option<Duration>,
+    /// When set, the weights of backends that are added to a traffic split
+    /// are ramped up gradually.
+    pub split_slow_start: Option<http::profiles::SlowStartConfig>,
+    /// When set, a request's destination is only overridden by a traffic
+    /// split if the request's headers satisfy the condition.
+    pub split_override_condition: Option<http::profiles::recognize::OverrideCondition>,
+    /// The topology zone in which the proxy runs. When set, endpoints in the
+    /// same zone are preferred.
+    pub pod_zone: Option<String>,
+    /// Limits the number of concurrent connections to each endpoint, unless
+    /// service discovery sets an endpoint's limit.
+    pub max_endpoint_connections: Option<usize>,
+    /// Ejects endpoints from their balancer while they are consistently
+    /// failing, if set.
+    pub failure_accrual: Option<FailureAccrualConfig>,
 }
 
 pub struct Outbound {
@@ -61,6 +107,20 @@ impl<A: OrigDstAddr> Config<A> {
         Config {
             proxy: self.proxy.with_orig_dst_addr(orig_dst_addr),
             canonicalize_timeout: self.canonicalize_timeout,
+            response_validation_allowlist: self.response_validation_allowlist,
+            endpoint_key_labels: self.endpoint_key_labels,
+            upstream_proxies: self.upstream_proxies,
+            extra_clusters: self.extra_clusters,
+            expose_dst_headers: self.expose_dst_headers,
+            meshed_connect_timeout: self.meshed_connect_timeout,
+            meshed_h2_settings: self.meshed_h2_settings,
+            max_replay_body_bytes: self.max_replay_body_bytes,
+            split_prewarm_timeout: self.split_prewarm_timeout,
+            split_slow_start: self.split_slow_start,
+            split_override_condition: self.split_override_condition,
+            pod_zone: self.pod_zone,
+            max_endpoint_connections: self.max_endpoint_connections,
+            failure_accrual: self.failure_accrual,
         }
     }
 
@@ -92,6 +152,20 @@ impl<A: OrigDstAddr> Config<A> {
         use proxy::core::listen::{Bind, Listen};
         let Config {
             canonicalize_timeout,
+            response_validation_allowlist,
+            endpoint_key_labels,
+            upstream_proxies,
+            extra_clusters,
+            expose_dst_headers,
+            meshed_connect_timeout,
+            meshed_h2_settings,
+            max_replay_body_bytes,
+            split_prewarm_timeout,
+            split_slow_start,
+            split_override_condition,
+            pod_zone,
+            max_endpoint_connections,
+            failure_accrual,
             proxy:
                 ProxyConfig {
                     server:
@@ -104,9 +178,15 @@ impl<A: OrigDstAddr> Config<A> {
                     router_capacity,
                     router_max_idle_age,
                     disable_protocol_detection_for_ports,
+                    continue_ttfb,
                 },
         } = self;
 
+        let connect_timeouts = endpoint::ConnectTimeouts {
+            meshed: meshed_connect_timeout,
+            unmeshed: connect.timeout,
+        };
+
         let listen = bind.bind().map_err(Error::from)?;
         let listen_addr = listen.listen_addr();
 
@@ -115,16 +195,25 @@ impl<A: OrigDstAddr> Config<A> {
         // spawned on the same runtime as the proxy.
         let serve = Box::new(future::lazy(move || {
             // Establishes connections to remote peers (for both TCP
-            // forwarding and HTTP proxying).
-            let connect_stack = svc::stack(connect::svc(connect.keepalive))
-                .push(tls::client::layer(local_identity))
-                .push_timeout(connect.timeout)
-                .push(metrics.transport.layer_connect(TransportLabels));
+            // forwarding and HTTP proxying). Connections to addresses that are
+            // configured to use an upstream proxy are tunneled through it with
+            // `CONNECT`.
+            let connect_stack = svc::stack(upstream_proxy::tunnel(
+                upstream_proxies.clone(),
+                connect::svc(connect.keepalive),
+            ))
+            .push(tls::client::layer(local_identity))
+            .push_connect_timeout()
+            .push(metrics.transport.layer_connect(TransportLabels))
+            .push_connection_limit(max_endpoint_connections);
 
             // Instantiates an HTTP client for for a `client::Config`
             let client_stack = connect_stack
                 .clone()
-                .push(http::client::layer(connect.h2_settings))
+                .push(http::client::layer(
+                    connect.h1_settings,
+                    connect.h2_settings,
+                ))
                 .push(reconnect::layer({
                     let backoff = connect.backoff.clone();
                     move |_| Ok(backoff.stream())
@@ -139,31 +228,68 @@ impl<A: OrigDstAddr> Config<A> {
             // 1. Records http metrics  with per-endpoint labels.
             // 2. Instruments `tap` inspection.
             // 3. Changes request/response versions when the endpoint
-            //    supports protocol upgrade (and the request may be upgraded).
+            //    supports protocol upgrade (and the request may be upgraded),
+            //    unless the request sets `l5d-no-upgrade`.
             // 4. Appends `l5d-server-id` to responses coming back iff meshed
             //    TLS was used on the connection.
-            // 5. Routes requests to the correct client (based on the
+            // 5. If enabled, appends `l5d-dst-concrete` and `l5d-dst-endpoint`
+            //    to responses, describing the endpoint that served the
+            //    request.
+            // 6. Routes requests to the correct client (based on the
             //    request version and headers).
-            // 6. Strips any `l5d-server-id` that may have been received from
-            //    the server, before we apply our own.
+            // 7. Strips `l5d-` headers that may not leave the proxy from
+            //    requests, and any that the server may not set (e.g.
+            //    `l5d-server-id`) from responses, before we apply our own.
+            // 8. Rejects responses that cannot be safely forwarded, unless
+            //    the destination is exempted by the allowlist.
+            // 9. Annotates errors with the endpoint they pertain to.
+            // 10. Ejects endpoints that are consistently failing so the
+            //     balancer sheds load to their peers.
+            // 11. Limits the number of in-flight requests to each endpoint,
+            //     so that a slow endpoint cannot consume the whole proxy's
+            //     capacity.
             let endpoint_stack = client_stack
                 .serves::<Endpoint>()
-                .push(http::strip_header::response::layer(L5D_REMOTE_IP))
-                .push(http::strip_header::response::layer(L5D_SERVER_ID))
-                .push(http::strip_header::request::layer(L5D_REQUIRE_ID))
+                .push_on_error_context()
+                .push_named(
+                    "validate-response",
+                    http::validate_response::layer(
+                        response_validation_allowlist,
+                        metrics.http_response_validation.clone(),
+                    ),
+                )
+                .push_named(
+                    "strip-headers",
+                    headers::strip::layer(headers::Boundary::Outbound, |endpoint: &Endpoint| {
+                        endpoint.identity.is_some()
+                    }),
+                )
+                .push_named("add-dst-headers", add_dst_on_rsp::layer(expose_dst_headers))
                 // disabled due to information leagkage
                 //.push(add_remote_ip_on_rsp::layer())
                 //.push(add_server_id_on_rsp::layer())
-                .push(orig_proto_upgrade::layer())
-                .push(tap_layer.clone())
-                .push(http::metrics::layer::<_, classify::Response>(
-                    metrics.http_endpoint,
-                ))
-                .push(require_identity_on_endpoint::layer())
+                .push_named(
+                    "orig-proto-upgrade",
+                    svc::when::layer(
+                        |endpoint: &Endpoint| endpoint.can_use_orig_proto(),
+                        orig_proto_upgrade::layer(),
+                    ),
+                )
+                .push_named("tap", tap_layer.clone())
+                .push_named(
+                    "endpoint-metrics",
+                    http::metrics::layer::<_, classify::Response>(metrics.http_endpoint)
+                        .with_continue_ttfb(continue_ttfb)
+                        .with_request_labels(EndpointLabels::with_route),
+                )
+                .push_named("require-identity", require_identity_on_endpoint::layer())
+                .push_failure_accrual(failure_accrual, metrics.failure_accrual.clone())
+                .push_per_make_concurrency_limit(buffer.max_in_flight)
                 .push(trace::layer(|endpoint: &Endpoint| {
                     info_span!("endpoint", peer.addr = %endpoint.addr, peer.id = ?endpoint.identity)
                 }))
                 .serves::<Endpoint>();
+            debug!(layers = ?endpoint_stack.describe(), "endpoint stack");
 
             // A per-`dst::Route` layer that uses profile data to configure
             // a per-route layer.
@@ -176,18 +302,48 @@ impl<A: OrigDstAddr> Config<A> {
             //    retries.
             // 3. Retries are optionally enabled depending on if the route
             //    is retryable.
+            // 4. Small request bodies are buffered on retryable routes so that
+            //    they may be replayed. This goes outside of the buffer so that
+            //    bodies are read by the caller rather than the buffer's task.
+            //
+            // The order of these layers is validated against the route
+            // stack's blueprint.
             let dst_route_layer = svc::layers()
                 .push(http::insert::target::layer())
-                .push(http::metrics::layer::<_, classify::Response>(
-                    metrics.http_route_retry.clone(),
-                ))
-                .push(http::retry::layer(metrics.http_route_retry))
-                .push(http::timeout::layer())
-                .push(http::metrics::layer::<_, classify::Response>(
-                    metrics.http_route,
-                ))
+                .push(
+                    http::metrics::layer::<_, classify::Response>(metrics.http_route_retry.clone())
+                        .with_continue_ttfb(continue_ttfb),
+                )
+                .push_named(
+                    svc::blueprint::Named::new("retry")
+                        .inside(svc::blueprint::ROUTE_METRICS)
+                        .inside(svc::blueprint::BUFFER),
+                    http::retry::layer(metrics.http_route_retry),
+                )
+                .push_named(
+                    svc::blueprint::Named::new("timeout")
+                        .outside("retry")
+                        .inside(svc::blueprint::ROUTE_METRICS),
+                    http::timeout::layer(),
+                )
+                .push(
+                    http::metrics::layer::<_, classify::Response>(metrics.http_route)
+                        .with_continue_ttfb(continue_ttfb),
+                )
+                .push_anchor(svc::blueprint::ROUTE_METRICS)
                 .push(classify::layer())
-                .push_buffer_pending(buffer.max_in_flight, DispatchDeadline::extract);
+                .push_buffer_pending_with_metrics(
+                    buffer.max_in_flight,
+                    DispatchDeadline::extract,
+                    metrics.buffers.buffer("outbound_route"),
+                )
+                .push_named(
+                    svc::blueprint::Named::new("replay").outside(svc::blueprint::BUFFER),
+                    http::replay::layer(max_replay_body_bytes),
+                )
+                .validate()
+                .unwrap_or_else(|e| panic!("invalid route stack: {}", e));
+            debug!(layers = ?dst_route_layer.describe(), "route stack");
 
             // Routes requests to their original destination endpoints. Used as
             // a fallback when service discovery has no endpoints for a destination.
@@ -195,71 +351,136 @@ impl<A: OrigDstAddr> Config<A> {
             // If the `l5d-require-id` header is present, then that identity is
             // used as the server name when connecting to the endpoint.
             let orig_dst_router_layer = svc::layers()
-                .push_buffer_pending(buffer.max_in_flight, DispatchDeadline::extract)
+                .push_buffer_pending_with_metrics(
+                    buffer.max_in_flight,
+                    DispatchDeadline::extract,
+                    metrics.buffers.buffer("outbound_orig_dst"),
+                )
                 .push(router::Layer::new(
-                    router::Config::new(router_capacity, router_max_idle_age),
-                    Endpoint::from_request,
+                    router::Config::new(router_capacity, router_max_idle_age)
+                        .with_metrics("outbound_orig_dst", &metrics.router_cache),
+                    move |req: &http::Request<_>| {
+                        Endpoint::from_request(req)
+                            .map(|ep| ep.with_connect_timeouts(connect_timeouts))
+                    },
                 ));
 
-            // Resolves the target via the control plane and balances requests
-            // over all endpoints returned from the destination service.
+            // Balancers share the resolver, acquiring it in the order that
+            // they wait for it. If the resolver fails, waiting balancers fail
+            // with its error, but later balancers retry it.
+            let resolve = svc::stack(resolve.into_service()).push_lock().into_inner();
+
+            // Resolves the target via the control plane, through each of its
+            // clusters, and balances requests over all endpoints returned from
+            // the destination service.
             const DISCOVER_UPDATE_BUFFER_CAPACITY: usize = 10;
             let balancer_layer = svc::layers()
                 .push_spawn_ready()
+                .push(http::balance::warmup::layer())
                 .push(discover::Layer::new(
                     DISCOVER_UPDATE_BUFFER_CAPACITY,
                     router_max_idle_age,
-                    map_endpoint::Resolve::new(endpoint::FromMetadata, resolve.clone()),
+                    zone_affinity::Resolve::new(
+                        pod_zone,
+                        map_endpoint::Resolve::new(
+                            endpoint::FromMetadata::new(endpoint_key_labels, connect_timeouts)
+                                .with_meshed_h2_settings(meshed_h2_settings)
+                                .with_label_cap(metrics.label_cap.clone()),
+                            multi_cluster::Resolve::new(extra_clusters, resolve.clone()),
+                        ),
+                    ),
                 ))
                 .push(http::balance::layer(EWMA_DEFAULT_RTT, EWMA_DECAY));
 
             // If the balancer fails to be created, i.e., because it is unresolvable,
             // fall back to using a router that dispatches request to the
             // application-selected original destination.
+            //
+            // Destinations that are configured to use an upstream proxy bypass
+            // both, and are sent to the upstream proxy in absolute-form.
+            let upstream_proxy_layer = upstream_proxy::layer(
+                upstream_proxies,
+                endpoint_stack
+                    .clone()
+                    .push(http::boxed::Layer::new())
+                    .push(svc::map_target::layer(move |ep: Endpoint| {
+                        ep.with_connect_timeouts(connect_timeouts)
+                    }))
+                    .into_inner(),
+            );
             let distributor = endpoint_stack
                 .serves::<Endpoint>()
                 .push(fallback::layer(
                     balancer_layer.boxed(),
                     orig_dst_router_layer.boxed(),
                 ))
+                .push(upstream_proxy_layer)
                 .push(trace::layer(
-                    |dst: &DstAddr| info_span!("concrete", dst.concrete = %dst.dst_concrete()),
+                    |dst: &DstAddr| info_span!("concrete", dst.concrete = %dst.concrete()),
                 ));
 
             // A per-`DstAddr` stack that does the following:
             //
-            // 1. Adds the `CANONICAL_DST_HEADER` from the `DstAddr`.
+            // 1. Adds the `l5d-dst-canonical` header from the `DstAddr`.
             // 2. Determines the profile of the destination and applies
             //    per-route policy.
             // 3. Creates a load balancer , configured by resolving the
             //   `DstAddr` with a resolver.
+            let profiles_layer =
+                http::profiles::router::layer(profiles_client, dst_route_layer, metrics.http_split);
+            let profiles_layer = match split_prewarm_timeout {
+                Some(timeout) => profiles_layer.with_prewarm(timeout),
+                None => profiles_layer,
+            };
+            let profiles_layer = match split_slow_start {
+                Some(slow_start) => profiles_layer.with_slow_start(slow_start),
+                None => profiles_layer,
+            };
+            let profiles_layer = match split_override_condition {
+                Some(condition) => profiles_layer.with_override_condition(condition),
+                None => profiles_layer,
+            };
             let dst_stack = distributor
-                .serves::<DstAddr>()
-                .push_buffer_pending(buffer.max_in_flight, DispatchDeadline::extract)
-                .makes::<DstAddr>()
-                .push(http::profiles::router::layer(
-                    profiles_client,
-                    dst_route_layer,
-                ))
-                .push(http::header_from_target::layer(CANONICAL_DST_HEADER));
+                .serves_spawnable::<DstAddr>()
+                .push_buffer_pending_with_metrics(
+                    buffer.max_in_flight,
+                    DispatchDeadline::extract,
+                    metrics.buffers.buffer("outbound_concrete"),
+                )
+                .makes_spawnable::<DstAddr>()
+                .clones::<DstAddr>()
+                .push(profiles_layer)
+                .push(http::header_from_target::layer(
+                    headers::L5D_DST_CANONICAL.clone(),
+                ));
 
             // Routes request using the `DstAddr` extension.
             //
             // This is shared across addr-stacks so that multiple addrs that
             // canonicalize to the same DstAddr use the same dst-stack service.
+            // A dst-stack service is not evicted while a response body from
+            // it is still streaming.
             let dst_router = dst_stack
                 .push(trace::layer(
-                    |dst: &DstAddr| info_span!("logical", dst.logical = %dst.dst_logical()),
-                ))
-                .push_buffer_pending(buffer.max_in_flight, DispatchDeadline::extract)
-                .push(router::Layer::new(
-                    router::Config::new(router_capacity, router_max_idle_age),
-                    |req: &http::Request<_>| {
-                        req.extensions().get::<Addr>().cloned().map(|addr| {
-                            DstAddr::outbound(addr, http::settings::Settings::from_request(req))
-                        })
-                    },
+                    |dst: &DstAddr| info_span!("logical", dst.logical = %dst.logical()),
                 ))
+                .push_buffer_pending_with_metrics(
+                    buffer.max_in_flight,
+                    DispatchDeadline::extract,
+                    metrics.buffers.buffer("outbound_logical"),
+                )
+                .push(
+                    router::Layer::new(
+                        router::Config::new(router_capacity, router_max_idle_age)
+                            .with_metrics("outbound_logical", &metrics.router_cache),
+                        |req: &http::Request<_>| {
+                            req.extensions().get::<Addr>().cloned().map(|addr| {
+                                DstAddr::outbound(addr, http::settings::Settings::from_request(req))
+                            })
+                        },
+                    )
+                    .with_instrument(http::balance::PendingUntilEos::default()),
+                )
                 .into_inner()
                 .spawn();
 
@@ -273,7 +494,8 @@ impl<A: OrigDstAddr> Config<A> {
             // Routes requests to an `Addr`:
             //
             // 1. If the request had an `l5d-override-dst` header, this value
-            // is used.
+            // is used. Requests with an override that cannot be parsed are
+            // rejected before they are routed.
             //
             // 2. If the request is HTTP/2 and has an :authority, this value
             // is used.
@@ -283,34 +505,37 @@ impl<A: OrigDstAddr> Config<A> {
             //
             // 4. If the request has an HTTP/1 Host header, it is used.
             //
+            // When the authority in 2-4 omits a port, the port of the
+            // SO_ORIGINAL_DST is used so that the logical destination agrees
+            // with the fallback endpoint, which targets the SO_ORIGINAL_DST.
+            //
             // 5. Finally, if the tls::accept::Meta had an SO_ORIGINAL_DST, this TCP
             // address is used.
             let addr_router = addr_stack
-                .push(http::strip_header::request::layer(L5D_CLIENT_ID))
-                .push(http::strip_header::request::layer(DST_OVERRIDE_HEADER))
                 .push(http::insert::target::layer())
                 .push(trace::layer(|addr: &Addr| info_span!("addr", %addr)))
-                .push_buffer_pending(buffer.max_in_flight, DispatchDeadline::extract)
+                .push_buffer_pending_with_metrics(
+                    buffer.max_in_flight,
+                    DispatchDeadline::extract,
+                    metrics.buffers.buffer("outbound_addr"),
+                )
                 .push(router::Layer::new(
-                    router::Config::new(router_capacity, router_max_idle_age),
-                    |req: &http::Request<_>| {
-                        http_request_l5d_override_dst_addr(req)
-                            .map(|override_addr| {
-                                debug!("using dst-override");
-                                override_addr
-                            })
-                            .or_else(|_| http_request_authority_addr(req))
-                            .or_else(|_| http_request_host_addr(req))
-                            .or_else(|_| http_request_orig_dst_addr(req))
-                            .ok()
-                    },
+                    router::Config::new(router_capacity, router_max_idle_age)
+                        .with_metrics("outbound_addr", &metrics.router_cache),
+                    |req: &http::Request<_>| resolve_dst_addr(req),
                 ))
                 .into_inner()
                 .spawn();
 
             // Share a single semaphore across all requests to signal when
             // the proxy is overloaded.
+            //
+            // Requests that set `l5d-request-timeout` are bounded above the
+            // router so that the timeout covers connecting to the endpoint as
+            // well as awaiting its response.
             let admission_control = svc::stack(addr_router)
+                .push(http::request_timeout::layer())
+                .push(request_filter::layer(RejectInvalidDstOverride))
                 .push_concurrency_limit(buffer.max_in_flight)
                 .push_load_shed();
 
@@ -333,8 +558,9 @@ impl<A: OrigDstAddr> Config<A> {
 
             let forward_tcp = tcp::Forward::new(
                 svc::stack(connect_stack)
-                    .push(svc::map_target::layer(|meta: tls::accept::Meta| {
+                    .push(svc::map_target::layer(move |meta: tls::accept::Meta| {
                         Endpoint::from(meta.addrs.target_addr())
+                            .with_connect_timeouts(connect_timeouts)
                     }))
                     .into_inner(),
             );
@@ -385,3 +611,32 @@ pub fn trace_labels() -> HashMap<String, String> {
     l.insert("direction".to_string(), "outbound".to_string());
     l
 }
+
+/// Determines the logical destination of a request, recording how it was
+/// determined in a `dst_resolution` span.
+fn resolve_dst_addr<B>(req: &http::Request<B>) -> Option<Addr> {
+    let span = debug_span!(
+        "dst_resolution",
+        uri = %req.uri(),
+        is_absolute_form = http::h1::is_absolute_form(req.uri()),
+    );
+    let _enter = span.enter();
+
+    let (method, addr) = if let Ok(addr) = http_request_l5d_override_dst_addr(req) {
+        ("override", addr)
+    } else if let Ok(addr) = http_request_addr_with_default_port(req) {
+        if req.uri().authority_part().is_some() {
+            ("authority", addr)
+        } else {
+            ("host", addr)
+        }
+    } else if let Ok(addr) = http_request_orig_dst_addr(req) {
+        ("socket", addr)
+    } else {
+        debug!("no destination address");
+        return None;
+    };
+
+    debug!(%addr, method, "resolved destination address");
+    Some(addr)
+}
@@ -0,0 +1,392 @@
+//! Resolves a concrete destination through each of its clusters.
+//!
+//! Each of a destination's `Profile` destinations is resolved in order, and
+//! the balancer is offered the endpoints of every destination that resolves.
+//! A destination that fails to resolve is skipped, so long as another
+//! destination resolves; and a resolution only becomes empty when none of its
+//! destinations have endpoints.
+
+use crate::endpoint::ProfileTarget;
+use futures::{Async, Future, Poll};
+use indexmap::IndexSet;
+use linkerd2_app_core::{
+    dst::DstAddr,
+    proxy::{
+        core::resolve::{self, Update},
+        http::profiles::HasMultiDestination,
+    },
+    Addr, Error,
+};
+use std::net::SocketAddr;
+use tracing::debug;
+
+#[derive(Clone, Debug)]
+pub struct Resolve<R> {
+    resolve: R,
+    profiles: ProfileTarget,
+}
+
+pub struct ResolveFuture<R: resolve::Resolve<DstAddr>> {
+    destinations: Vec<Destination<R>>,
+}
+
+pub struct Resolution<R> {
+    clusters: Vec<Cluster<R>>,
+}
+
+/// Resolves one of a profile's destinations.
+struct Destination<R: resolve::Resolve<DstAddr>> {
+    addr: Addr,
+    state: State<R>,
+}
+
+enum State<R: resolve::Resolve<DstAddr>> {
+    NotReady(R, Option<DstAddr>),
+    Resolving(R::Future),
+    Done(Result<R::Resolution, Error>),
+}
+
+/// The resolution of one of a profile's destinations, with the endpoints it
+/// has added.
+struct Cluster<R> {
+    addr: Addr,
+    resolution: R,
+    endpoints: IndexSet<SocketAddr>,
+    exists: bool,
+}
+
+// === impl Resolve ===
+
+impl<R> Resolve<R> {
+    pub fn new(profiles: ProfileTarget, resolve: R) -> Self {
+        Self { resolve, profiles }
+    }
+}
+
+impl<R> tower::Service<DstAddr> for Resolve<R>
+where
+    R: resolve::Resolve<DstAddr> + Clone,
+{
+    type Response = Resolution<R::Resolution>;
+    type Error = Error;
+    type Future = ResolveFuture<R>;
+
+    #[inline]
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        self.resolve.poll_ready().map_err(Into::into)
+    }
+
+    fn call(&mut self, dst: DstAddr) -> Self::Future {
+        let profile = self.profiles.key(dst);
+        let destinations = profile
+            .destinations()
+            .into_iter()
+            .map(|addr| Destination {
+                state: State::NotReady(self.resolve.clone(), Some(profile.target(addr.clone()))),
+                addr,
+            })
+            .collect();
+        ResolveFuture { destinations }
+    }
+}
+
+// === impl ResolveFuture ===
+
+impl<R: resolve::Resolve<DstAddr>> Future for ResolveFuture<R> {
+    type Item = Resolution<R::Resolution>;
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        let mut done = true;
+        for dst in self.destinations.iter_mut() {
+            done = dst.poll() && done;
+        }
+        if !done {
+            return Ok(Async::NotReady);
+        }
+
+        let mut clusters = Vec::with_capacity(self.destinations.len());
+        let mut error = None;
+        for dst in self.destinations.drain(..) {
+            match dst.state {
+                State::Done(Ok(resolution)) => clusters.push(Cluster {
+                    addr: dst.addr,
+                    resolution,
+                    endpoints: IndexSet::new(),
+                    exists: true,
+                }),
+                State::Done(Err(e)) => {
+                    debug!(addr = %dst.addr, error = %e, "failed to resolve destination");
+                    error.get_or_insert(e);
+                }
+                _ => unreachable!("destinations must be resolved"),
+            }
+        }
+
+        match error {
+            Some(e) if clusters.is_empty() => Err(e),
+            _ => Ok(Async::Ready(Resolution { clusters })),
+        }
+    }
+}
+
+// === impl Destination ===
+
+impl<R: resolve::Resolve<DstAddr>> Destination<R> {
+    /// Drives the destination's resolution, returning true once it has
+    /// completed.
+    fn poll(&mut self) -> bool {
+        loop {
+            self.state = match self.state {
+                State::NotReady(ref mut resolve, ref mut target) => match resolve.poll_ready() {
+                    Ok(Async::NotReady) => return false,
+                    Ok(Async::Ready(())) => {
+                        let target = target.take().expect("resolved once");
+                        State::Resolving(resolve.resolve(target))
+                    }
+                    Err(e) => State::Done(Err(e.into())),
+                },
+                State::Resolving(ref mut future) => match future.poll() {
+                    Ok(Async::NotReady) => return false,
+                    Ok(Async::Ready(resolution)) => State::Done(Ok(resolution)),
+                    Err(e) => State::Done(Err(e.into())),
+                },
+                State::Done(_) => return true,
+            };
+        }
+    }
+}
+
+// === impl Resolution ===
+
+impl<R> Resolution<R> {
+    /// Returns an update that removes `removed`, except for the endpoints
+    /// that other clusters still provide. The update is empty if no cluster
+    /// has any endpoints.
+    fn remove<E>(&self, removed: IndexSet<SocketAddr>) -> Option<Update<E>> {
+        if self.clusters.iter().all(|c| c.endpoints.is_empty()) {
+            if self.clusters.iter().all(|c| !c.exists) {
+                return Some(Update::DoesNotExist);
+            }
+            return Some(Update::Empty);
+        }
+
+        let removed = removed
+            .into_iter()
+            .filter(|addr| !self.clusters.iter().any(|c| c.endpoints.contains(addr)))
+            .collect::<Vec<_>>();
+        if removed.is_empty() {
+            return None;
+        }
+        Some(Update::Remove(removed))
+    }
+}
+
+impl<R: resolve::Resolution> resolve::Resolution for Resolution<R> {
+    type Endpoint = R::Endpoint;
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Update<R::Endpoint>, Self::Error> {
+        let mut i = 0;
+        while i < self.clusters.len() {
+            let update = match self.clusters[i].resolution.poll() {
+                Ok(Async::NotReady) => {
+                    i += 1;
+                    continue;
+                }
+                Ok(Async::Ready(update)) => update,
+                Err(e) => {
+                    let e = e.into();
+                    if self.clusters.len() == 1 {
+                        return Err(e);
+                    }
+                    let cluster = self.clusters.remove(i);
+                    debug!(addr = %cluster.addr, error = %e, "destination resolution failed");
+                    match self.remove(cluster.endpoints) {
+                        Some(update) => return Ok(Async::Ready(update)),
+                        None => continue,
+                    }
+                }
+            };
+
+            let cluster = &mut self.clusters[i];
+            let removed = match update {
+                Update::Add(eps) => {
+                    cluster.exists = true;
+                    cluster.endpoints.extend(eps.iter().map(|(addr, _)| *addr));
+                    return Ok(Async::Ready(Update::Add(eps)));
+                }
+                Update::Remove(addrs) => {
+                    for addr in addrs.iter() {
+                        cluster.endpoints.remove(addr);
+                    }
+                    addrs.into_iter().collect()
+                }
+                Update::Empty => {
+                    cluster.exists = true;
+                    cluster.endpoints.drain(..).collect()
+                }
+                Update::DoesNotExist => {
+                    cluster.exists = false;
+                    cluster.endpoints.drain(..).collect()
+                }
+            };
+            if let Some(update) = self.remove(removed) {
+                return Ok(Async::Ready(update));
+            }
+        }
+
+        Ok(Async::NotReady)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::future;
+    use linkerd2_app_core::proxy::core::resolve::Resolution as _;
+    use std::collections::VecDeque;
+    use std::str::FromStr;
+    use tower::Service as _;
+
+    #[derive(Clone, Debug)]
+    struct Updates(VecDeque<Result<Update<()>, &'static str>>);
+
+    impl resolve::Resolution for Updates {
+        type Endpoint = ();
+        type Error = &'static str;
+
+        fn poll(&mut self) -> Poll<Update<()>, Self::Error> {
+            match self.0.pop_front() {
+                Some(update) => update.map(Async::Ready),
+                None => Ok(Async::NotReady),
+            }
+        }
+    }
+
+    fn addr(s: &str) -> SocketAddr {
+        s.parse().unwrap()
+    }
+
+    fn resolution(clusters: Vec<Vec<Result<Update<()>, &'static str>>>) -> Resolution<Updates> {
+        Resolution {
+            clusters: clusters
+                .into_iter()
+                .enumerate()
+                .map(|(i, updates)| Cluster {
+                    addr: Addr::from_str(&format!("web.ns.svc.cluster-{}.local:80", i)).unwrap(),
+                    resolution: Updates(updates.into_iter().collect()),
+                    endpoints: IndexSet::new(),
+                    exists: true,
+                })
+                .collect(),
+        }
+    }
+
+    fn poll(resolution: &mut Resolution<Updates>) -> Option<Update<()>> {
+        match resolution.poll().unwrap() {
+            Async::Ready(update) => Some(update),
+            Async::NotReady => None,
+        }
+    }
+
+    #[test]
+    fn aggregates_endpoints_of_each_cluster() {
+        let a = addr("10.0.0.1:80");
+        let (b, c) = (addr("10.1.0.1:80"), addr("10.1.0.2:80"));
+        let mut resolution = resolution(vec![
+            vec![Ok(Update::Add(vec![(a, ())]))],
+            vec![Ok(Update::Add(vec![(b, ()), (c, ())]))],
+        ]);
+
+        assert_eq!(poll(&mut resolution), Some(Update::Add(vec![(a, ())])));
+        assert_eq!(
+            poll(&mut resolution),
+            Some(Update::Add(vec![(b, ()), (c, ())]))
+        );
+        assert_eq!(poll(&mut resolution), None);
+    }
+
+    #[test]
+    fn empty_only_when_no_cluster_has_endpoints() {
+        let (a, b) = (addr("10.0.0.1:80"), addr("10.1.0.1:80"));
+        let mut resolution = resolution(vec![
+            vec![Ok(Update::Add(vec![(a, ())]))],
+            vec![Ok(Update::Add(vec![(b, ())]))],
+        ]);
+        assert_eq!(poll(&mut resolution), Some(Update::Add(vec![(a, ())])));
+        assert_eq!(poll(&mut resolution), Some(Update::Add(vec![(b, ())])));
+
+        // The first cluster no longer exists, but the second cluster's
+        // endpoint is retained.
+        let push = |r: &mut Resolution<Updates>, i: usize, update| {
+            r.clusters[i].resolution.0.push_back(Ok(update));
+        };
+        push(&mut resolution, 0, Update::DoesNotExist);
+        assert_eq!(poll(&mut resolution), Some(Update::Remove(vec![a])));
+
+        push(&mut resolution, 1, Update::Empty);
+        assert_eq!(poll(&mut resolution), Some(Update::Empty));
+        push(&mut resolution, 1, Update::DoesNotExist);
+        assert_eq!(poll(&mut resolution), Some(Update::DoesNotExist));
+    }
+
+    #[test]
+    fn drops_clusters_that_fail() {
+        let (a, b) = (addr("10.0.0.1:80"), addr("10.1.0.1:80"));
+        let mut resolution = resolution(vec![
+            vec![Ok(Update::Add(vec![(a, ())]))],
+            vec![Ok(Update::Add(vec![(b, ())]))],
+        ]);
+        assert_eq!(poll(&mut resolution), Some(Update::Add(vec![(a, ())])));
+        assert_eq!(poll(&mut resolution), Some(Update::Add(vec![(b, ())])));
+
+        resolution.clusters[0].resolution.0.push_back(Err("failed"));
+        assert_eq!(poll(&mut resolution), Some(Update::Remove(vec![a])));
+        assert_eq!(resolution.clusters.len(), 1);
+
+        // The last cluster's errors are not hidden.
+        resolution.clusters[0].resolution.0.push_back(Err("failed"));
+        assert!(resolution.poll().is_err());
+    }
+
+    #[test]
+    fn resolves_each_destination_in_order() {
+        let dst = DstAddr::outbound(
+            Addr::from_str("web.ns.svc.cluster.local:80").unwrap(),
+            linkerd2_app_core::proxy::http::Settings::Http2,
+        );
+        let east = Addr::from_str("web.ns.svc.east.example.com:80").unwrap();
+        let west = Addr::from_str("web.ns.svc.west.example.com:80").unwrap();
+        let mut extra_clusters = indexmap::IndexMap::new();
+        let name = dst.dst_concrete().name_addr().unwrap().clone();
+        extra_clusters.insert(name, vec![east.clone(), west.clone()]);
+
+        let resolved = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+        let inner = {
+            let resolved = resolved.clone();
+            tower::service_fn(move |dst: DstAddr| {
+                let addr = dst.dst_concrete().clone();
+                resolved.lock().unwrap().push(addr.clone());
+                if addr.to_string().contains("east") {
+                    return future::err::<Updates, Error>("no such cluster".into());
+                }
+                let update = Update::Add(vec![(SocketAddr::from(([10, 0, 0, 1], 80)), ())]);
+                future::ok(Updates(vec![Ok(update)].into_iter().collect()))
+            })
+        };
+
+        let mut resolve = Resolve::new(ProfileTarget::new(extra_clusters), inner);
+        let resolution = resolve.call(dst.clone()).wait().unwrap();
+        assert_eq!(
+            *resolved.lock().unwrap(),
+            vec![dst.dst_concrete().clone(), east, west.clone()]
+        );
+        let clusters = resolution
+            .clusters
+            .iter()
+            .map(|c| c.addr.clone())
+            .collect::<Vec<_>>();
+        assert_eq!(clusters, vec![dst.dst_concrete().clone(), west]);
+    }
+}
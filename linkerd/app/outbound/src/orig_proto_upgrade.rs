@@ -1,33 +1,84 @@
 use super::Endpoint;
 use crate::proxy::http::{orig_proto, settings::Settings};
 use crate::svc;
-use futures::{try_ready, Future, Poll};
-use http;
+use futures::{future, try_ready, Async, Future, Poll};
+use http::{self, header::HeaderName};
+use linkerd2_app_core::headers::L5D_NO_UPGRADE;
 use std::marker::PhantomData;
-use tracing::trace;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::Instant;
+use tracing::{debug, info_span, trace};
+use tracing_futures::{Instrument, Instrumented};
 
 #[derive(Debug)]
-pub struct Layer<A, B>(PhantomData<fn(A) -> B>);
+pub struct Layer<A, B> {
+    header: Option<HeaderName>,
+    _marker: PhantomData<fn(A) -> B>,
+}
 
 #[derive(Debug)]
 pub struct MakeSvc<M, A, B> {
     inner: M,
+    header: Option<HeaderName>,
+    stats: Arc<UpgradeStats>,
     _marker: PhantomData<fn(A) -> B>,
 }
 
-pub struct MakeFuture<F, A, B> {
-    can_upgrade: bool,
-    inner: F,
+/// Builds an upgrading client and a client for requests that opt out of the
+/// upgrade.
+///
+/// The passthrough client is made by a clone of the inner `MakeService`, so
+/// that each make is preceded by its own readiness check.
+pub struct MakeFuture<M: svc::Service<Endpoint>, A, B> {
+    inner: future::Join<M::Future, svc::Oneshot<M, Endpoint>>,
+    header: Option<HeaderName>,
+    stats: Arc<UpgradeStats>,
+    started: Instant,
     _marker: PhantomData<fn(A) -> B>,
 }
 
+/// Upgrades requests to HTTP/2 unless they carry the `l5d-no-upgrade`
+/// header, in which case they are sent on their original protocol.
+#[derive(Clone, Debug)]
+pub struct Service<S> {
+    upgrade: orig_proto::Upgrade<S>,
+    passthrough: S,
+    stats: Arc<UpgradeStats>,
+}
+
+/// Counts the requests that were upgraded and the requests that bypassed the
+/// upgrade, across all of the services made by a `MakeSvc`.
+#[derive(Debug, Default)]
+pub struct UpgradeStats {
+    upgraded: AtomicU64,
+    bypassed: AtomicU64,
+}
+
+/// Upgrades requests for all endpoints, so this should only be applied to
+/// endpoints that support it (see `Endpoint::can_use_orig_proto`).
 pub fn layer<A, B>() -> Layer<A, B> {
-    Layer(PhantomData)
+    Layer {
+        header: None,
+        _marker: PhantomData,
+    }
+}
+
+/// Like `layer`, but carries the original protocol in `header` rather than in
+/// `l5d-orig-proto`.
+pub fn layer_with_header<A, B>(header: HeaderName) -> Layer<A, B> {
+    Layer {
+        header: Some(header),
+        _marker: PhantomData,
+    }
 }
 
 impl<A, B> Clone for Layer<A, B> {
     fn clone(&self) -> Self {
-        Layer(PhantomData)
+        Layer {
+            header: self.header.clone(),
+            _marker: PhantomData,
+        }
     }
 }
 
@@ -40,6 +91,8 @@ where
     fn layer(&self, inner: M) -> Self::Service {
         MakeSvc {
             inner,
+            header: self.header.clone(),
+            stats: Arc::new(UpgradeStats::default()),
             _marker: PhantomData,
         }
     }
@@ -47,10 +100,20 @@ where
 
 // === impl MakeSvc ===
 
+impl<M, A, B> MakeSvc<M, A, B> {
+    /// Returns the upgrade statistics of all services made by this `MakeSvc`
+    /// and its clones.
+    pub fn stats(&self) -> Arc<UpgradeStats> {
+        self.stats.clone()
+    }
+}
+
 impl<M: Clone, A, B> Clone for MakeSvc<M, A, B> {
     fn clone(&self) -> Self {
         MakeSvc {
             inner: self.inner.clone(),
+            header: self.header.clone(),
+            stats: self.stats.clone(),
             _marker: PhantomData,
         }
     }
@@ -58,54 +121,250 @@ impl<M: Clone, A, B> Clone for MakeSvc<M, A, B> {
 
 impl<M, A, B> svc::Service<Endpoint> for MakeSvc<M, A, B>
 where
-    M: svc::MakeService<Endpoint, http::Request<A>, Response = http::Response<B>>,
+    M: svc::Service<Endpoint> + Clone,
+    M::Response: svc::Service<http::Request<A>, Response = http::Response<B>>,
 {
-    type Response = svc::Either<orig_proto::Upgrade<M::Service>, M::Service>;
-    type Error = M::MakeError;
-    type Future = MakeFuture<M::Future, A, B>;
+    type Response = Service<M::Response>;
+    type Error = M::Error;
+    type Future = Instrumented<MakeFuture<M, A, B>>;
 
     fn poll_ready(&mut self) -> Poll<(), Self::Error> {
         self.inner.poll_ready()
     }
 
-    fn call(&mut self, mut endpoint: Endpoint) -> Self::Future {
-        let can_upgrade = endpoint.can_use_orig_proto();
-
-        if can_upgrade {
-            trace!(
-                "supporting {} upgrades for endpoint={:?}",
-                orig_proto::L5D_ORIG_PROTO,
-                endpoint,
-            );
-            endpoint.http_settings = Settings::Http2;
-        }
-
-        let inner = self.inner.make_service(endpoint);
+    fn call(&mut self, endpoint: Endpoint) -> Self::Future {
+        trace!(
+            "supporting {} upgrades for endpoint={:?}",
+            self.header
+                .as_ref()
+                .map(HeaderName::as_str)
+                .unwrap_or(orig_proto::L5D_ORIG_PROTO),
+            endpoint,
+        );
+        let span = info_span!("orig_proto_upgrade", addr = %endpoint.addr);
+        let mut h2 = endpoint.clone();
+        h2.http_settings = Settings::Http2;
+        let passthrough = svc::Oneshot::new(self.inner.clone(), endpoint);
+        let upgrade = self.inner.call(h2);
         MakeFuture {
-            can_upgrade,
-            inner,
+            inner: upgrade.join(passthrough),
+            header: self.header.clone(),
+            stats: self.stats.clone(),
+            started: Instant::now(),
             _marker: PhantomData,
         }
+        .instrument(span)
     }
 }
 
 // === impl MakeFuture ===
 
-impl<F, A, B> Future for MakeFuture<F, A, B>
+impl<M, A, B> Future for MakeFuture<M, A, B>
 where
-    F: Future,
-    F::Item: svc::Service<http::Request<A>, Response = http::Response<B>>,
+    M: svc::Service<Endpoint>,
+    M::Response: svc::Service<http::Request<A>, Response = http::Response<B>>,
 {
-    type Item = svc::Either<orig_proto::Upgrade<F::Item>, F::Item>;
-    type Error = F::Error;
+    type Item = Service<M::Response>;
+    type Error = M::Error;
 
     fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
-        let inner = try_ready!(self.inner.poll());
+        let (upgrade, passthrough) = try_ready!(self.inner.poll());
+        let elapsed = self.started.elapsed();
+        debug!(
+            elapsed_ms = elapsed.as_millis() as u64,
+            "made upgrade clients"
+        );
+        let mut upgrade = orig_proto::Upgrade::new(upgrade);
+        if let Some(header) = self.header.take() {
+            upgrade = upgrade.with_header_name(header);
+        }
+        Ok(Service {
+            upgrade,
+            passthrough,
+            stats: self.stats.clone(),
+        }
+        .into())
+    }
+}
+
+// === impl Service ===
+
+impl<S, A, B> svc::Service<http::Request<A>> for Service<S>
+where
+    S: svc::Service<http::Request<A>, Response = http::Response<B>>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = future::Either<
+        <orig_proto::Upgrade<S> as svc::Service<http::Request<A>>>::Future,
+        S::Future,
+    >;
 
-        if self.can_upgrade {
-            Ok(svc::Either::A(orig_proto::Upgrade::new(inner)).into())
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        let upgrade = svc::Service::poll_ready(&mut self.upgrade)?;
+        let passthrough = self.passthrough.poll_ready()?;
+        if upgrade.is_ready() && passthrough.is_ready() {
+            Ok(Async::Ready(()))
         } else {
-            Ok(svc::Either::B(inner).into())
+            Ok(Async::NotReady)
         }
     }
+
+    fn call(&mut self, mut req: http::Request<A>) -> Self::Future {
+        if req.headers_mut().remove(&*L5D_NO_UPGRADE).is_some() {
+            debug!("{} set; not upgrading", *L5D_NO_UPGRADE);
+            self.stats.bypassed.fetch_add(1, Ordering::Relaxed);
+            return future::Either::B(self.passthrough.call(req));
+        }
+
+        self.stats.upgraded.fetch_add(1, Ordering::Relaxed);
+        future::Either::A(svc::Service::call(&mut self.upgrade, req))
+    }
+}
+
+// === impl UpgradeStats ===
+
+impl UpgradeStats {
+    /// Returns the number of requests that were upgraded to HTTP/2.
+    pub fn upgraded(&self) -> u64 {
+        self.upgraded.load(Ordering::Relaxed)
+    }
+
+    /// Returns the number of requests that were sent on their original
+    /// protocol because they set `l5d-no-upgrade`.
+    pub fn bypassed(&self) -> u64 {
+        self.bypassed.load(Ordering::Relaxed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::svc::{Layer as _, Service as _};
+    use linkerd2_app_core::{
+        transport::{listen::Addrs, tls},
+        Conditional, Error,
+    };
+    use std::sync::{Arc, Mutex};
+
+    /// Records the headers of each request it receives.
+    #[derive(Clone, Default)]
+    struct Recorder(Arc<Mutex<Vec<http::HeaderMap>>>);
+
+    impl svc::Service<http::Request<()>> for Recorder {
+        type Response = http::Response<()>;
+        type Error = Error;
+        type Future = future::FutureResult<http::Response<()>, Error>;
+
+        fn poll_ready(&mut self) -> Poll<(), Error> {
+            Ok(Async::Ready(()))
+        }
+
+        fn call(&mut self, req: http::Request<()>) -> Self::Future {
+            self.0.lock().unwrap().push(req.headers().clone());
+            let mut rsp = http::Response::new(());
+            *rsp.version_mut() = http::Version::HTTP_2;
+            future::ok(rsp)
+        }
+    }
+
+    fn endpoint() -> Endpoint {
+        let meta = tls::accept::Meta {
+            peer_identity: Conditional::None(tls::ReasonForNoPeerName::Loopback.into()),
+            addrs: Addrs::new(
+                "127.0.0.1:4140".parse().unwrap(),
+                "10.2.2.2:33333".parse().unwrap(),
+                Some("10.1.1.1:8080".parse().unwrap()),
+            ),
+        };
+        let mut req = http::Request::new(());
+        req.extensions_mut().insert(meta);
+        Endpoint::from_request(&req).expect("endpoint")
+    }
+
+    /// Returns the headers of a request sent through `layer`.
+    fn upgraded_headers(layer: Layer<(), ()>) -> http::HeaderMap {
+        let recorder = Recorder::default();
+        let make = {
+            let recorder = recorder.clone();
+            svc::mk(move |_: Endpoint| future::ok::<_, Error>(recorder.clone()))
+        };
+        let mut svc = layer.layer(make).call(endpoint()).wait().expect("make");
+
+        let req = http::Request::builder()
+            .uri("/")
+            .header(http::header::HOST, "foo.example.com")
+            .body(())
+            .unwrap();
+        let rsp = svc.call(req).wait().expect("response");
+        assert_eq!(rsp.version(), http::Version::HTTP_2);
+
+        let mut requests = recorder.0.lock().unwrap();
+        assert_eq!(requests.len(), 1);
+        requests.pop().unwrap()
+    }
+
+    #[test]
+    fn upgrades_with_default_header() {
+        let headers = upgraded_headers(layer());
+        assert_eq!(headers[orig_proto::L5D_ORIG_PROTO], "HTTP/1.1");
+    }
+
+    #[test]
+    fn upgrades_with_custom_header() {
+        let header = HeaderName::from_static("x-orig-proto");
+        let headers = upgraded_headers(layer_with_header(header.clone()));
+        assert_eq!(headers[&header], "HTTP/1.1");
+        assert!(!headers.contains_key(orig_proto::L5D_ORIG_PROTO));
+    }
+
+    /// Makes `Recorder`s, but fails if it is called without being ready.
+    #[derive(Clone, Default)]
+    struct StrictMake {
+        ready: bool,
+    }
+
+    impl svc::Service<Endpoint> for StrictMake {
+        type Response = Recorder;
+        type Error = Error;
+        type Future = future::FutureResult<Recorder, Error>;
+
+        fn poll_ready(&mut self) -> Poll<(), Error> {
+            self.ready = true;
+            Ok(Async::Ready(()))
+        }
+
+        fn call(&mut self, _: Endpoint) -> Self::Future {
+            assert!(self.ready, "called before poll_ready");
+            self.ready = false;
+            future::ok(Recorder::default())
+        }
+    }
+
+    #[test]
+    fn polls_readiness_for_each_make() {
+        let mut make = layer::<(), ()>().layer(StrictMake::default());
+        assert!(make.poll_ready().expect("ready").is_ready());
+        make.call(endpoint()).wait().expect("make");
+    }
+
+    #[test]
+    fn counts_upgraded_and_bypassed_requests() {
+        let make = svc::mk(|_: Endpoint| future::ok::<_, Error>(Recorder::default()));
+        let mut make = layer::<(), ()>().layer(make);
+        let stats = make.stats();
+        let mut svc = make.call(endpoint()).wait().expect("make");
+
+        for no_upgrade in &[false, true, false] {
+            let mut req = http::Request::builder();
+            req.uri("/").header(http::header::HOST, "foo.example.com");
+            if *no_upgrade {
+                req.header(&*L5D_NO_UPGRADE, "true");
+            }
+            svc.call(req.body(()).unwrap()).wait().expect("response");
+        }
+
+        assert_eq!(stats.upgraded(), 2);
+        assert_eq!(stats.bypassed(), 1);
+    }
 }
@@ -5,10 +5,11 @@ use futures::{
 };
 use linkerd2_app_core::{
     errors,
-    proxy::http::identity_from_header,
+    headers::L5D_REQUIRE_ID,
+    proxy::http::identities_from_header,
     svc,
     transport::tls::{self, HasPeerIdentity},
-    Conditional, Error, L5D_REQUIRE_ID,
+    Conditional, Error,
 };
 use std::marker::PhantomData;
 use tracing::debug;
@@ -145,16 +146,18 @@ where
 
     fn call(&mut self, request: http::Request<A>) -> Self::Future {
         // If the `l5d-require-id` header is present, then we should expect
-        // the target's `peer_identity` to match; if the two values do not
-        // match or there is no `peer_identity`, then we fail the request
-        if let Some(require_identity) = identity_from_header(&request, L5D_REQUIRE_ID) {
-            debug!("found l5d-require-id={:?}", require_identity.as_ref());
+        // the target's `peer_identity` to match; if the peer does not
+        // match any of its values or there is no `peer_identity`, then we
+        // fail the request
+        let require_identities = identities_from_header(&request, &*L5D_REQUIRE_ID);
+        if !require_identities.is_empty() {
+            debug!("found l5d-require-id={:?}", require_identities);
             match self.peer_identity {
                 Conditional::Some(ref peer_identity) => {
-                    if require_identity != *peer_identity {
+                    if !require_identities.contains(peer_identity) {
                         let message = format!(
                             "require identity check failed; require={:?} found={:?}",
-                            require_identity, peer_identity
+                            require_identities, peer_identity
                         );
                         let e = errors::StatusError {
                             message,
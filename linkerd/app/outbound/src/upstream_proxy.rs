@@ -0,0 +1,598 @@
+//! Forwards traffic for configured destinations through an upstream HTTP
+//! proxy, e.g. an egress proxy that must be used to leave the network.
+//!
+//! Plaintext HTTP/1 requests are sent to the upstream proxy in absolute-form,
+//! using the upstream proxy as the endpoint. All other connections to a
+//! matching address are tunneled through the upstream proxy with `CONNECT`.
+//!
+//! Endpoints built for an upstream proxy retain the logical destination, so
+//! that metrics are attributed to it; the upstream proxy is recorded in the
+//! `via` label.
+
+use super::Endpoint;
+use futures::{try_ready, Async, Future, Poll};
+use http::header::{HeaderValue, PROXY_AUTHORIZATION};
+use linkerd2_app_core::{
+    dns,
+    dst::DstAddr,
+    errors::UpstreamProxyError,
+    metric_labels::CardinalityCap,
+    proxy::{api_resolve::Metadata, http::Settings},
+    svc,
+    transport::tls,
+    Addr, Conditional, Error,
+};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use std::{io, mem};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tracing::debug;
+
+/// The largest `CONNECT` response head that is read from an upstream proxy.
+const MAX_CONNECT_RESPONSE_LEN: usize = 8 * 1024;
+
+/// Designates the upstream proxies through which destinations are reached.
+///
+/// Rules are evaluated in order; the first matching rule is used.
+#[derive(Clone, Debug, Default)]
+pub struct Rules(Vec<Rule>);
+
+#[derive(Clone, Debug)]
+pub struct Rule {
+    pub dst: Match,
+    pub proxy: UpstreamProxy,
+}
+
+/// Matches the destinations that are forwarded through an upstream proxy.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Match {
+    /// Matches names with the given suffix. Because connections carry no
+    /// name, these rules only apply to HTTP/1 requests.
+    Suffix(dns::Suffix),
+    /// Matches an original destination address.
+    Addr(SocketAddr),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct UpstreamProxy {
+    pub addr: SocketAddr,
+    /// A `Proxy-Authorization` value that is sent with each request to the
+    /// upstream proxy.
+    pub authorization: Option<HeaderValue>,
+}
+
+/// Describes an endpoint that is reached through an upstream proxy.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Via {
+    pub proxy: UpstreamProxy,
+    /// The destination that the upstream proxy forwards to.
+    pub dst: Addr,
+}
+
+/// Routes HTTP/1 requests for matching destinations to an endpoint stack,
+/// using the upstream proxy as the endpoint.
+pub fn layer<E>(rules: Arc<Rules>, endpoint: E) -> Layer<E> {
+    Layer { rules, endpoint }
+}
+
+#[derive(Clone, Debug)]
+pub struct Layer<E> {
+    rules: Arc<Rules>,
+    endpoint: E,
+}
+
+#[derive(Clone, Debug)]
+pub struct MakeSvc<E, M> {
+    rules: Arc<Rules>,
+    endpoint: E,
+    inner: M,
+}
+
+pub enum MakeFuture<E, M> {
+    Upstream { proxy: UpstreamProxy, future: E },
+    Inner(M),
+}
+
+#[derive(Clone, Debug)]
+pub struct Service<S> {
+    proxy: UpstreamProxy,
+    inner: S,
+}
+
+pub struct ResponseFuture<F> {
+    proxy: SocketAddr,
+    inner: F,
+}
+
+/// Tunnels connections to matching addresses through an upstream proxy.
+///
+/// This wraps the TCP connector so that TLS, if any, is negotiated with the
+/// destination through the tunnel.
+pub fn tunnel<C>(rules: Arc<Rules>, inner: C) -> Tunnel<C> {
+    Tunnel { rules, inner }
+}
+
+#[derive(Clone, Debug)]
+pub struct Tunnel<C> {
+    rules: Arc<Rules>,
+    inner: C,
+}
+
+pub enum TunnelFuture<F: Future> {
+    Direct(F),
+    Connect {
+        proxy: SocketAddr,
+        request: Vec<u8>,
+        future: F,
+    },
+    Handshake(Handshake<F::Item>),
+}
+
+/// Issues a `CONNECT` request on a connection to an upstream proxy and
+/// completes with the connection once the tunnel is established.
+pub struct Handshake<I> {
+    proxy: SocketAddr,
+    io: Option<I>,
+    request: Vec<u8>,
+    written: usize,
+    response: Vec<u8>,
+}
+
+// === impl Rules ===
+
+impl Rules {
+    pub fn new(rules: Vec<Rule>) -> Self {
+        Rules(rules)
+    }
+
+    /// Returns the upstream proxy for `dst`, if one is configured.
+    pub fn find(&self, dst: &Addr) -> Option<&UpstreamProxy> {
+        self.0
+            .iter()
+            .find(|rule| rule.dst.matches(dst))
+            .map(|rule| &rule.proxy)
+    }
+}
+
+// === impl Match ===
+
+impl Match {
+    pub fn matches(&self, dst: &Addr) -> bool {
+        match (self, dst) {
+            (Match::Suffix(sfx), Addr::Name(n)) => sfx.contains(n.name()),
+            (Match::Addr(a), Addr::Socket(s)) => a == s,
+            _ => false,
+        }
+    }
+}
+
+// === impl UpstreamProxy ===
+
+impl UpstreamProxy {
+    /// Configures an upstream proxy, optionally authenticating with basic
+    /// `user:password` credentials.
+    pub fn new(addr: SocketAddr, credentials: Option<&str>) -> Self {
+        let authorization = credentials.map(|creds| {
+            let encoded = base64::encode(creds.trim().as_bytes());
+            let mut value = HeaderValue::from_str(&format!("Basic {}", encoded))
+                .expect("base64-encoded credentials must be a valid header value");
+            value.set_sensitive(true);
+            value
+        });
+        Self {
+            addr,
+            authorization,
+        }
+    }
+}
+
+// === impl Via ===
+
+impl Via {
+    /// Builds an endpoint for a destination's upstream proxy.
+    ///
+    /// Only HTTP/1 requests that do not upgrade the connection may be sent
+    /// to the upstream proxy in absolute-form.
+    fn endpoint(dst: &DstAddr, proxy: &UpstreamProxy) -> Option<Endpoint> {
+        let (keep_alive, is_http_1_0) = match dst.http_settings {
+            Settings::Http1 {
+                keep_alive,
+                wants_h1_upgrade: false,
+                is_http_1_0,
+                is_connect: false,
+                ..
+            } => (keep_alive, is_http_1_0),
+            _ => return None,
+        };
+
+        let via = Via {
+            proxy: proxy.clone(),
+            dst: dst.dst_concrete().clone(),
+        };
+        Some(Endpoint {
+            addr: proxy.addr,
+            orig_dst_port: proxy.addr.port(),
+            dst_logical: dst.dst_logical().name_addr().cloned(),
+            dst_concrete: dst.dst_concrete().name_addr().cloned(),
+            identity: Conditional::None(
+                tls::ReasonForNoPeerName::NotProvidedByServiceDiscovery.into(),
+            ),
+            alternate_identities: Vec::new(),
+            metadata: Metadata::empty(),
+            key_labels: Vec::new(),
+            zone: None,
+            max_connections: None,
+            warmup: None,
+            load_report: None,
+            weight: None,
+            label_cap: CardinalityCap::default(),
+            http_settings: Settings::Http1 {
+                keep_alive,
+                wants_h1_upgrade: false,
+                was_absolute_form: true,
+                is_http_1_0,
+                is_connect: false,
+            },
+            via: Some(via),
+            connect_timeouts: Default::default(),
+            meshed_h2_settings: Default::default(),
+        })
+    }
+}
+
+// === impl Layer ===
+
+impl<E: Clone, M> svc::Layer<M> for Layer<E> {
+    type Service = MakeSvc<E, M>;
+
+    fn layer(&self, inner: M) -> Self::Service {
+        MakeSvc {
+            rules: self.rules.clone(),
+            endpoint: self.endpoint.clone(),
+            inner,
+        }
+    }
+}
+
+// === impl MakeSvc ===
+
+impl<E, M> svc::Service<DstAddr> for MakeSvc<E, M>
+where
+    E: svc::Service<Endpoint>,
+    E::Error: Into<Error>,
+    M: svc::Service<DstAddr>,
+    M::Error: Into<Error>,
+{
+    type Response = svc::Either<Service<E::Response>, M::Response>;
+    type Error = Error;
+    type Future = MakeFuture<E::Future, M::Future>;
+
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        let endpoint = self.endpoint.poll_ready().map_err(Into::into)?;
+        let inner = self.inner.poll_ready().map_err(Into::into)?;
+        if endpoint.is_ready() && inner.is_ready() {
+            Ok(Async::Ready(()))
+        } else {
+            Ok(Async::NotReady)
+        }
+    }
+
+    fn call(&mut self, dst: DstAddr) -> Self::Future {
+        if let Some(proxy) = self.rules.find(dst.dst_concrete()) {
+            if let Some(endpoint) = Via::endpoint(&dst, proxy) {
+                debug!(proxy.addr = %proxy.addr, "forwarding through upstream proxy");
+                return MakeFuture::Upstream {
+                    proxy: proxy.clone(),
+                    future: self.endpoint.call(endpoint),
+                };
+            }
+        }
+
+        MakeFuture::Inner(self.inner.call(dst))
+    }
+}
+
+// === impl MakeFuture ===
+
+impl<E, M> Future for MakeFuture<E, M>
+where
+    E: Future,
+    E::Error: Into<Error>,
+    M: Future,
+    M::Error: Into<Error>,
+{
+    type Item = svc::Either<Service<E::Item>, M::Item>;
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        match self {
+            MakeFuture::Upstream {
+                ref proxy,
+                ref mut future,
+            } => {
+                let inner = try_ready!(future.poll().map_err(Into::into));
+                let svc = Service {
+                    proxy: proxy.clone(),
+                    inner,
+                };
+                Ok(svc::Either::A(svc).into())
+            }
+            MakeFuture::Inner(ref mut future) => {
+                let inner = try_ready!(future.poll().map_err(Into::into));
+                Ok(svc::Either::B(inner).into())
+            }
+        }
+    }
+}
+
+// === impl Service ===
+
+impl<S, B> svc::Service<http::Request<B>> for Service<S>
+where
+    S: svc::Service<http::Request<B>>,
+    S::Error: Into<Error>,
+{
+    type Response = S::Response;
+    type Error = Error;
+    type Future = ResponseFuture<S::Future>;
+
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        let proxy = self.proxy.addr;
+        self.inner
+            .poll_ready()
+            .map_err(|e| UpstreamProxyError::new(proxy, e).into())
+    }
+
+    fn call(&mut self, mut req: http::Request<B>) -> Self::Future {
+        if let Some(ref authorization) = self.proxy.authorization {
+            req.headers_mut()
+                .insert(PROXY_AUTHORIZATION, authorization.clone());
+        }
+
+        ResponseFuture {
+            proxy: self.proxy.addr,
+            inner: self.inner.call(req),
+        }
+    }
+}
+
+impl<F> Future for ResponseFuture<F>
+where
+    F: Future,
+    F::Error: Into<Error>,
+{
+    type Item = F::Item;
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        let proxy = self.proxy;
+        self.inner
+            .poll()
+            .map_err(|e| UpstreamProxyError::new(proxy, e).into())
+    }
+}
+
+// === impl Tunnel ===
+
+impl<C> svc::Service<Endpoint> for Tunnel<C>
+where
+    C: svc::Service<Endpoint, Error = io::Error>,
+    C::Response: AsyncRead + AsyncWrite,
+{
+    type Response = C::Response;
+    type Error = io::Error;
+    type Future = TunnelFuture<C::Future>;
+
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        self.inner.poll_ready()
+    }
+
+    fn call(&mut self, mut endpoint: Endpoint) -> Self::Future {
+        // Endpoints that are already built for an upstream proxy are
+        // connected to directly.
+        let dst = Addr::Socket(endpoint.addr);
+        let proxy = match self.rules.find(&dst) {
+            Some(proxy) if endpoint.via.is_none() => proxy.clone(),
+            _ => return TunnelFuture::Direct(self.inner.call(endpoint)),
+        };
+
+        debug!(proxy.addr = %proxy.addr, "tunneling through upstream proxy");
+        let request = connect_request(&dst, &proxy);
+        let proxy_addr = proxy.addr;
+        endpoint.addr = proxy.addr;
+        endpoint.via = Some(Via { proxy, dst });
+        TunnelFuture::Connect {
+            proxy: proxy_addr,
+            request,
+            future: self.inner.call(endpoint),
+        }
+    }
+}
+
+fn connect_request(dst: &Addr, proxy: &UpstreamProxy) -> Vec<u8> {
+    let mut request = format!("CONNECT {} HTTP/1.1\r\nHost: {}\r\n", dst, dst).into_bytes();
+    if let Some(ref authorization) = proxy.authorization {
+        request.extend_from_slice(b"Proxy-Authorization: ");
+        request.extend_from_slice(authorization.as_bytes());
+        request.extend_from_slice(b"\r\n");
+    }
+    request.extend_from_slice(b"\r\n");
+    request
+}
+
+// === impl TunnelFuture ===
+
+impl<F> Future for TunnelFuture<F>
+where
+    F: Future<Error = io::Error>,
+    F::Item: AsyncRead + AsyncWrite,
+{
+    type Item = F::Item;
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        loop {
+            *self = match self {
+                TunnelFuture::Direct(ref mut future) => return future.poll(),
+                TunnelFuture::Connect {
+                    proxy,
+                    ref mut request,
+                    ref mut future,
+                } => {
+                    let proxy = *proxy;
+                    let io = try_ready!(future.poll().map_err(|e| upstream_proxy_error(proxy, e)));
+                    let request = mem::replace(request, Vec::new());
+                    TunnelFuture::Handshake(Handshake::new(proxy, io, request))
+                }
+                TunnelFuture::Handshake(ref mut handshake) => {
+                    let proxy = handshake.proxy;
+                    return handshake.poll().map_err(|e| upstream_proxy_error(proxy, e));
+                }
+            };
+        }
+    }
+}
+
+/// Connection errors must be `io::Error`s, so the upstream proxy's failure is
+/// wrapped to remain distinguishable.
+fn upstream_proxy_error(proxy: SocketAddr, error: io::Error) -> io::Error {
+    io::Error::new(error.kind(), UpstreamProxyError::new(proxy, error))
+}
+
+// === impl Handshake ===
+
+impl<I> Handshake<I> {
+    fn new(proxy: SocketAddr, io: I, request: Vec<u8>) -> Self {
+        Self {
+            proxy,
+            io: Some(io),
+            request,
+            written: 0,
+            response: Vec::new(),
+        }
+    }
+}
+
+impl<I: AsyncRead + AsyncWrite> Future for Handshake<I> {
+    type Item = I;
+    type Error = io::Error;
+
+    fn poll(&mut self) -> Poll<I, io::Error> {
+        let io = self.io.as_mut().expect("polled after ready");
+
+        while self.written < self.request.len() {
+            let n = try_ready!(io.poll_write(&self.request[self.written..]));
+            if n == 0 {
+                return Err(io::ErrorKind::WriteZero.into());
+            }
+            self.written += n;
+        }
+        try_ready!(io.poll_flush());
+
+        // The response head is read one byte at a time so that no tunneled
+        // bytes are consumed.
+        while !self.response.ends_with(b"\r\n\r\n") {
+            if self.response.len() == MAX_CONNECT_RESPONSE_LEN {
+                return Err(io::Error::new(
+                    io::ErrorKind::InvalidData,
+                    "CONNECT response is too large",
+                ));
+            }
+            let mut byte = [0u8];
+            if try_ready!(io.poll_read(&mut byte)) == 0 {
+                return Err(io::Error::new(
+                    io::ErrorKind::UnexpectedEof,
+                    "upstream proxy closed the connection",
+                ));
+            }
+            self.response.push(byte[0]);
+        }
+
+        let status = connect_status(&self.response)?;
+        if !status.is_success() {
+            return Err(io::Error::new(
+                io::ErrorKind::ConnectionRefused,
+                format!("CONNECT failed with status {}", status),
+            ));
+        }
+
+        Ok(Async::Ready(self.io.take().expect("polled after ready")))
+    }
+}
+
+/// Parses the status of a `CONNECT` response head.
+fn connect_status(head: &[u8]) -> io::Result<http::StatusCode> {
+    let invalid = || io::Error::new(io::ErrorKind::InvalidData, "invalid CONNECT response");
+    let line = head.split(|b| *b == b'\n').next().ok_or_else(invalid)?;
+    let line = std::str::from_utf8(line).map_err(|_| invalid())?;
+    let mut parts = line.trim_end().splitn(3, ' ');
+    match parts.next() {
+        Some(version) if version.starts_with("HTTP/1.") => {}
+        _ => return Err(invalid()),
+    }
+    parts
+        .next()
+        .and_then(|code| http::StatusCode::from_bytes(code.as_bytes()).ok())
+        .ok_or_else(invalid)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::convert::TryFrom;
+
+    fn rules() -> Rules {
+        let proxy = "10.0.0.1:3128".parse().unwrap();
+        Rules::new(vec![
+            Rule {
+                dst: Match::Suffix(dns::Suffix::try_from("example.com").unwrap()),
+                proxy: UpstreamProxy::new(proxy, Some("user:pass")),
+            },
+            Rule {
+                dst: Match::Addr("10.1.1.1:443".parse().unwrap()),
+                proxy: UpstreamProxy::new(proxy, None),
+            },
+        ])
+    }
+
+    #[test]
+    fn rules_match_suffixes_and_addrs() {
+        let rules = rules();
+        let find = |dst: &str| rules.find(&Addr::from_str(dst).unwrap()).cloned();
+
+        assert!(find("example.com:80").is_some());
+        assert!(find("web.example.com:8080").is_some());
+        assert!(find("badexample.com:80").is_none());
+        assert!(find("10.1.1.1:443").is_some());
+        assert!(find("10.1.1.1:80").is_none());
+    }
+
+    #[test]
+    fn connect_request_carries_credentials() {
+        let rules = rules();
+        let dst = Addr::from_str("web.example.com:443").unwrap();
+        let request = connect_request(&dst, rules.find(&dst).unwrap());
+        assert_eq!(
+            std::str::from_utf8(&request).unwrap(),
+            "CONNECT web.example.com:443 HTTP/1.1\r\n\
+             Host: web.example.com:443\r\n\
+             Proxy-Authorization: Basic dXNlcjpwYXNz\r\n\
+             \r\n"
+        );
+    }
+
+    #[test]
+    fn parses_connect_status() {
+        let status = |head: &str| connect_status(head.as_bytes()).ok();
+        assert_eq!(
+            status("HTTP/1.1 200 Connection established\r\n\r\n"),
+            Some(http::StatusCode::OK)
+        );
+        assert_eq!(
+            status("HTTP/1.0 407 Proxy Authentication Required\r\n\r\n"),
+            Some(http::StatusCode::PROXY_AUTHENTICATION_REQUIRED)
+        );
+        assert_eq!(status("HTTP/1.1 200\r\n\r\n"), Some(http::StatusCode::OK));
+        assert_eq!(status("SSH-2.0-OpenSSH\r\n\r\n"), None);
+    }
+}
@@ -0,0 +1,201 @@
+//! Prefers endpoints in the proxy's own topology zone.
+//!
+//! Every endpoint is offered to the balancer, but endpoints that are not known
+//! to be in the proxy's zone are weighted so that they receive a fraction of
+//! the traffic of endpoints in the proxy's zone. Endpoints are not weighted
+//! when the proxy's zone is not known.
+
+use crate::endpoint::Endpoint;
+use futures::{try_ready, Async, Future, Poll};
+use linkerd2_app_core::proxy::core::resolve::{self, Update};
+
+/// The weight of endpoints outside the proxy's zone, relative to endpoints in
+/// the proxy's zone.
+const REMOTE_WEIGHT: f64 = 0.25;
+
+#[derive(Clone, Debug)]
+pub struct Resolve<R> {
+    resolve: R,
+    pod_zone: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct ResolveFuture<F> {
+    future: F,
+    pod_zone: Option<String>,
+}
+
+#[derive(Debug)]
+pub struct Resolution<R> {
+    resolution: R,
+    pod_zone: Option<String>,
+}
+
+// === impl Resolve ===
+
+impl<R> Resolve<R> {
+    pub fn new(pod_zone: Option<String>, resolve: R) -> Self {
+        Self { resolve, pod_zone }
+    }
+}
+
+impl<T, R> tower::Service<T> for Resolve<R>
+where
+    R: resolve::Resolve<T, Endpoint = Endpoint>,
+{
+    type Response = Resolution<R::Resolution>;
+    type Error = R::Error;
+    type Future = ResolveFuture<R::Future>;
+
+    #[inline]
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        self.resolve.poll_ready()
+    }
+
+    #[inline]
+    fn call(&mut self, target: T) -> Self::Future {
+        ResolveFuture {
+            future: self.resolve.resolve(target),
+            pod_zone: self.pod_zone.clone(),
+        }
+    }
+}
+
+// === impl ResolveFuture ===
+
+impl<F> Future for ResolveFuture<F>
+where
+    F: Future,
+    F::Item: resolve::Resolution<Endpoint = Endpoint>,
+{
+    type Item = Resolution<F::Item>;
+    type Error = F::Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        let resolution = try_ready!(self.future.poll());
+        Ok(Async::Ready(Resolution::new(
+            resolution,
+            self.pod_zone.take(),
+        )))
+    }
+}
+
+// === impl Resolution ===
+
+impl<R> Resolution<R> {
+    fn new(resolution: R, pod_zone: Option<String>) -> Self {
+        Self {
+            resolution,
+            pod_zone,
+        }
+    }
+
+    /// Weights the endpoint unless it is in the proxy's zone.
+    fn weigh(&self, ep: Endpoint) -> Endpoint {
+        let pod_zone = match self.pod_zone.as_ref() {
+            Some(pod_zone) => pod_zone,
+            None => return ep,
+        };
+        if ep.zone.as_ref() == Some(pod_zone) {
+            return ep;
+        }
+        Endpoint {
+            weight: Some(REMOTE_WEIGHT),
+            ..ep
+        }
+    }
+}
+
+impl<R> resolve::Resolution for Resolution<R>
+where
+    R: resolve::Resolution<Endpoint = Endpoint>,
+{
+    type Endpoint = Endpoint;
+    type Error = R::Error;
+
+    fn poll(&mut self) -> Poll<Update<Endpoint>, Self::Error> {
+        let update = match try_ready!(self.resolution.poll()) {
+            Update::Add(eps) => Update::Add(
+                eps.into_iter()
+                    .map(|(addr, ep)| (addr, self.weigh(ep)))
+                    .collect(),
+            ),
+            update => update,
+        };
+        Ok(Async::Ready(update))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use linkerd2_app_core::{proxy::core::resolve::Resolution as _, Never};
+    use std::collections::VecDeque;
+    use std::net::SocketAddr;
+
+    struct Updates(VecDeque<Update<Endpoint>>);
+
+    impl resolve::Resolution for Updates {
+        type Endpoint = Endpoint;
+        type Error = Never;
+
+        fn poll(&mut self) -> Poll<Update<Endpoint>, Never> {
+            Ok(self
+                .0
+                .pop_front()
+                .map(Async::Ready)
+                .unwrap_or(Async::NotReady))
+        }
+    }
+
+    fn endpoint(addr: &str, zone: &str) -> (SocketAddr, Endpoint) {
+        let addr = addr.parse::<SocketAddr>().unwrap();
+        let mut ep = Endpoint::from(addr);
+        ep.zone = Some(zone.to_owned());
+        (addr, ep)
+    }
+
+    fn weights(resolution: &mut Resolution<Updates>) -> Vec<(SocketAddr, Option<f64>)> {
+        match resolution.poll().unwrap() {
+            Async::Ready(Update::Add(eps)) => eps
+                .into_iter()
+                .map(|(addr, ep)| (addr, ep.weight))
+                .collect(),
+            update => panic!("unexpected update: {:?}", update),
+        }
+    }
+
+    #[test]
+    fn weights_endpoints_in_other_zones() {
+        let (a, ep_a) = endpoint("10.0.0.1:80", "zone-a");
+        let (b, ep_b) = endpoint("10.0.0.2:80", "zone-b");
+        let (c, ep_c) = endpoint("10.0.0.3:80", "zone-a");
+
+        let updates = vec![
+            Update::Add(vec![(b, ep_b)]),
+            Update::Add(vec![(a, ep_a), (c, ep_c)]),
+        ];
+        let mut resolution = Resolution::new(
+            Updates(updates.into_iter().collect()),
+            Some("zone-a".to_owned()),
+        );
+
+        // Remote endpoints are offered to the balancer, so they still receive
+        // some traffic, but less than local endpoints.
+        let remote = weights(&mut resolution);
+        assert_eq!(remote, vec![(b, Some(REMOTE_WEIGHT))]);
+        assert!(remote[0].1.unwrap() > 0.0 && remote[0].1.unwrap() < 1.0);
+        assert_eq!(weights(&mut resolution), vec![(a, None), (c, None)]);
+    }
+
+    #[test]
+    fn does_not_weight_endpoints_without_a_pod_zone() {
+        let (a, ep_a) = endpoint("10.0.0.1:80", "zone-a");
+        let (b, ep_b) = endpoint("10.0.0.2:80", "zone-b");
+
+        let updates = vec![Update::Add(vec![(a, ep_a), (b, ep_b)])];
+        let mut resolution = Resolution::new(Updates(updates.into_iter().collect()), None);
+
+        assert_eq!(weights(&mut resolution), vec![(a, None), (b, None)]);
+    }
+}
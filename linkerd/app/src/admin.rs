@@ -1,6 +1,6 @@
 use crate::identity::LocalIdentity;
 use linkerd2_app_core::{
-    admin, config::ServerConfig, drain, metrics::FmtMetrics, serve, trace::LevelHandle,
+    admin, config::ServerConfig, drain, freeze, metrics::FmtMetrics, serve, trace::LevelHandle,
     transport::tls, Error,
 };
 use std::net::SocketAddr;
@@ -10,6 +10,8 @@ use std::time::Duration;
 pub struct Config {
     pub server: ServerConfig,
     pub metrics_retain_idle: Duration,
+    /// The maximum number of metadata labels included in each metric.
+    pub max_metric_labels: usize,
 }
 
 pub struct Admin {
@@ -24,6 +26,7 @@ impl Config {
         identity: LocalIdentity,
         report: R,
         log_level: LevelHandle,
+        freeze: freeze::Registry,
         drain: drain::Watch,
     ) -> Result<Admin, Error>
     where
@@ -35,7 +38,7 @@ impl Config {
         let listen_addr = listen.listen_addr();
 
         let (ready, latch) = admin::Readiness::new();
-        let admin = admin::Admin::new(report, ready, log_level);
+        let admin = admin::Admin::new(report, ready, log_level, freeze);
         let accept = tls::AcceptTls::new(identity, admin.into_accept());
         let serve = serve::serve(listen, accept, drain);
         Ok(Admin {
@@ -3,7 +3,7 @@ mod resolve;
 use indexmap::IndexSet;
 use linkerd2_app_core::{
     config::{ControlAddr, ControlConfig},
-    dns, profiles, Error,
+    dns, freeze, profiles, Error,
 };
 use std::time::Duration;
 use tower_grpc::{generic::client::GrpcService, Body, BoxBody};
@@ -15,6 +15,7 @@ pub struct Config {
     pub get_suffixes: IndexSet<dns::Suffix>,
     pub get_networks: IndexSet<ipnet::IpNet>,
     pub profile_suffixes: IndexSet<dns::Suffix>,
+    pub profile_affinity_header: Option<http::header::HeaderName>,
 }
 
 /// Handles to destination service clients.
@@ -23,24 +24,27 @@ pub struct Config {
 pub struct Dst<S> {
     pub addr: ControlAddr,
     pub profiles: profiles::Client<S>,
-    pub resolve: resolve::Resolve<S>,
+    pub resolve: freeze::Resolve<resolve::Resolve<S>>,
 }
 
 impl Config {
     // XXX This is unfortunate -- the service should be built here, but it's annoying to name.
-    pub fn build<S>(self, svc: S) -> Result<Dst<S>, Error>
+    pub fn build<S>(self, svc: S, freeze: freeze::Registry) -> Result<Dst<S>, Error>
     where
         S: GrpcService<BoxBody> + Clone + Send + 'static,
         S::ResponseBody: Send,
         <S::ResponseBody as Body>::Data: Send,
         S::Future: Send,
     {
-        let resolve = resolve::new(
-            svc.clone(),
-            self.get_suffixes,
-            self.get_networks,
-            &self.context,
-            self.control.connect.backoff,
+        let resolve = freeze::Resolve::new(
+            resolve::new(
+                svc.clone(),
+                self.get_suffixes,
+                self.get_networks,
+                &self.context,
+                self.control.connect.backoff,
+            ),
+            freeze.clone(),
         );
 
         const DUMB_PROFILE_BACKOFF: Duration = Duration::from_secs(3);
@@ -49,7 +53,9 @@ impl Config {
             DUMB_PROFILE_BACKOFF,
             self.context,
             self.profile_suffixes,
-        );
+        )
+        .with_freeze(freeze)
+        .with_consistent_hash(self.profile_affinity_header);
 
         Ok(Dst {
             addr: self.control.addr,
@@ -1,15 +1,15 @@
 use crate::core::{
     addr,
     config::*,
-    proxy::http::h2,
+    proxy::http::{h1, h2, profiles},
     transport::{listen, tls},
     Addr,
 };
 use crate::{dns, identity, inbound, oc_collector, outbound};
-use indexmap::IndexSet;
+use indexmap::{IndexMap, IndexSet};
 use std::convert::TryFrom;
 use std::iter::FromIterator;
-use std::net::SocketAddr;
+use std::net::{IpAddr, SocketAddr};
 use std::path::PathBuf;
 use std::str::FromStr;
 use std::time::Duration;
@@ -45,6 +45,12 @@ pub enum ParseError {
     NameError,
     InvalidTokenSource,
     InvalidTrustAnchors,
+    InvalidUpstreamProxy,
+    InvalidExtraClusters,
+    NotABool,
+    InvalidFoldedHeaderPolicy,
+    InvalidHeaderName,
+    InvalidHeaderMatch,
 }
 
 // Environment variables to look at when loading the configuration
@@ -53,10 +59,20 @@ pub const ENV_INBOUND_LISTEN_ADDR: &str = "LINKERD2_PROXY_INBOUND_LISTEN_ADDR";
 pub const ENV_CONTROL_LISTEN_ADDR: &str = "LINKERD2_PROXY_CONTROL_LISTEN_ADDR";
 pub const ENV_ADMIN_LISTEN_ADDR: &str = "LINKERD2_PROXY_ADMIN_LISTEN_ADDR";
 pub const ENV_METRICS_RETAIN_IDLE: &str = "LINKERD2_PROXY_METRICS_RETAIN_IDLE";
+pub const ENV_MAX_METRIC_LABELS: &str = "LINKERD2_MAX_METRIC_LABELS";
+/// If true, the time to first byte of a response to an `Expect: 100-continue`
+/// request is measured when the first body frame is received, rather than
+/// when the final response headers are received.
+pub const ENV_METRICS_CONTINUE_TTFB_AT_FIRST_FRAME: &str =
+    "LINKERD2_PROXY_METRICS_CONTINUE_TTFB_AT_FIRST_FRAME";
 const ENV_INBOUND_DISPATCH_TIMEOUT: &str = "LINKERD2_PROXY_INBOUND_DISPATCH_TIMEOUT";
 const ENV_OUTBOUND_DISPATCH_TIMEOUT: &str = "LINKERD2_PROXY_OUTBOUND_DISPATCH_TIMEOUT";
 const ENV_INBOUND_CONNECT_TIMEOUT: &str = "LINKERD2_PROXY_INBOUND_CONNECT_TIMEOUT";
 const ENV_OUTBOUND_CONNECT_TIMEOUT: &str = "LINKERD2_PROXY_OUTBOUND_CONNECT_TIMEOUT";
+/// The connect timeout for outbound endpoints that are known to be meshed,
+/// unless the destination service provides a hint. Defaults to the outbound
+/// connect timeout.
+const ENV_OUTBOUND_MESHED_CONNECT_TIMEOUT: &str = "LINKERD2_PROXY_OUTBOUND_MESHED_CONNECT_TIMEOUT";
 const ENV_INBOUND_ACCEPT_KEEPALIVE: &str = "LINKERD2_PROXY_INBOUND_ACCEPT_KEEPALIVE";
 const ENV_OUTBOUND_ACCEPT_KEEPALIVE: &str = "LINKERD2_PROXY_OUTBOUND_ACCEPT_KEEPALIVE";
 
@@ -75,6 +91,48 @@ pub const ENV_OUTBOUND_ROUTER_MAX_IDLE_AGE: &str = "LINKERD2_PROXY_OUTBOUND_ROUT
 pub const ENV_INBOUND_MAX_IN_FLIGHT: &str = "LINKERD2_PROXY_INBOUND_MAX_IN_FLIGHT";
 pub const ENV_OUTBOUND_MAX_IN_FLIGHT: &str = "LINKERD2_PROXY_OUTBOUND_MAX_IN_FLIGHT";
 
+/// The largest outbound request body that is buffered so that the request may
+/// be retried.
+const ENV_OUTBOUND_MAX_REPLAY_BODY_BYTES: &str = "LINKERD2_PROXY_OUTBOUND_MAX_REPLAY_BODY_BYTES";
+
+/// When set, backends that are added to a traffic split are given up to this
+/// long to become ready before requests are routed to them.
+const ENV_OUTBOUND_SPLIT_PREWARM_TIMEOUT: &str = "LINKERD2_PROXY_OUTBOUND_SPLIT_PREWARM_TIMEOUT";
+
+/// When set, backends that are added to a traffic split start with a fraction
+/// of their weight, which is ramped up to their full weight over this
+/// duration.
+pub const ENV_OUTBOUND_SPLIT_SLOW_START: &str = "LINKERD2_PROXY_OUTBOUND_SPLIT_SLOW_START";
+
+/// The fraction of its weight, between 0 and 1, that a backend starts with
+/// when it is added to a traffic split. Only used when slow start is enabled.
+pub const ENV_OUTBOUND_SPLIT_SLOW_START_MIN_WEIGHT: &str =
+    "LINKERD2_PROXY_OUTBOUND_SPLIT_SLOW_START_MIN_WEIGHT";
+
+/// When set, as `<header>=<value>`, a request's destination is only overridden
+/// by a traffic split if the request has the header with the value. Other
+/// requests, e.g. health checks, are routed to the canonical destination.
+pub const ENV_OUTBOUND_SPLIT_OVERRIDE_HEADER: &str =
+    "LINKERD2_PROXY_OUTBOUND_SPLIT_OVERRIDE_HEADER";
+
+/// Limits the number of concurrent connections to each outbound endpoint,
+/// unless service discovery sets an endpoint's limit.
+const ENV_OUTBOUND_MAX_ENDPOINT_CONNECTIONS: &str =
+    "LINKERD2_PROXY_OUTBOUND_MAX_ENDPOINT_CONNECTIONS";
+
+/// Enables failure accrual for outbound endpoints: an endpoint is ejected
+/// from its balancer after this many consecutive failures.
+const ENV_OUTBOUND_FAILURE_ACCRUAL_CONSECUTIVE_FAILURES: &str =
+    "LINKERD2_PROXY_OUTBOUND_FAILURE_ACCRUAL_CONSECUTIVE_FAILURES";
+
+/// When failure accrual is enabled, an endpoint is also ejected when the
+/// proportion of failures among its recent responses exceeds this rate.
+const ENV_OUTBOUND_FAILURE_ACCRUAL_FAILURE_RATE: &str =
+    "LINKERD2_PROXY_OUTBOUND_FAILURE_ACCRUAL_FAILURE_RATE";
+const ENV_OUTBOUND_FAILURE_ACCRUAL_WINDOW: &str = "LINKERD2_PROXY_OUTBOUND_FAILURE_ACCRUAL_WINDOW";
+const ENV_OUTBOUND_FAILURE_ACCRUAL_BACKOFF: &str =
+    "LINKERD2_PROXY_OUTBOUND_FAILURE_ACCRUAL_BACKOFF";
+
 /// Constrains which destination names are resolved through the destination
 /// service.
 ///
@@ -109,6 +167,72 @@ pub const ENV_DESTINATION_GET_NETWORKS: &str = "LINKERD2_PROXY_DESTINATION_GET_N
 /// If unspecified, a default value is used.
 pub const ENV_DESTINATION_PROFILE_SUFFIXES: &str = "LINKERD2_PROXY_DESTINATION_PROFILE_SUFFIXES";
 
+/// Names a request header used for session affinity in traffic splits.
+///
+/// When set, requests to a destination whose profile splits traffic are
+/// assigned to a backend by consistently hashing the value of this header, so
+/// requests carrying the same value reach the same backend. Requests without
+/// the header are split by weight.
+///
+/// If unspecified, all requests are split by weight.
+pub const ENV_DESTINATION_PROFILE_AFFINITY_HEADER: &str =
+    "LINKERD2_PROXY_DESTINATION_PROFILE_AFFINITY_HEADER";
+
+/// Exempts destinations from outbound response validation.
+///
+/// The value is a comma-separated list of domain name suffixes. Responses
+/// from destinations matching any of these suffixes are forwarded even if
+/// their status or headers could not otherwise be forwarded safely.
+///
+/// If unspecified, all destinations are validated.
+pub const ENV_OUTBOUND_RESPONSE_VALIDATION_ALLOWLIST: &str =
+    "LINKERD2_PROXY_OUTBOUND_RESPONSE_VALIDATION_ALLOWLIST";
+
+/// Endpoint metadata labels that identify an outbound endpoint.
+///
+/// The value is a comma-separated list of label keys, e.g. `pod,version`.
+/// When one of these labels changes for an endpoint address, a new client is
+/// built for it rather than reusing the client (and labels) of the old one.
+///
+/// If unspecified, endpoints are identified without their labels.
+pub const ENV_OUTBOUND_ENDPOINT_KEY_LABELS: &str = "LINKERD2_PROXY_OUTBOUND_ENDPOINT_KEY_LABELS";
+
+/// Destinations that are reached through an upstream HTTP proxy.
+///
+/// The value is a comma-separated list of `DST=PROXY` rules, where `DST` is
+/// either a domain name suffix or an `IP:PORT`, and `PROXY` is the upstream
+/// proxy's `IP:PORT`. `PROXY` may be followed by `@PATH`, naming a file that
+/// contains `user:password` credentials for the upstream proxy, e.g.
+/// `example.com=10.0.0.1:3128@/var/run/proxy/credentials,10.1.1.1:443=10.0.0.1:3128`.
+///
+/// Plaintext HTTP/1 requests for a matching destination are sent to the
+/// upstream proxy in absolute-form. Other connections to a matching `IP:PORT`
+/// are tunneled through the upstream proxy with `CONNECT`.
+///
+/// If unspecified, no upstream proxies are used.
+pub const ENV_OUTBOUND_UPSTREAM_PROXIES: &str = "LINKERD2_PROXY_OUTBOUND_UPSTREAM_PROXIES";
+
+/// Outbound destinations that are also resolved through their addresses in
+/// other clusters.
+///
+/// The value is a comma-separated list of `DST=ADDR|ADDR...` rules, where
+/// `DST` is a concrete `NAME:PORT` and each `ADDR` is a `NAME:PORT` or
+/// `IP:PORT` of the same service in another cluster, e.g.
+/// `web.ns.svc.cluster.local:80=web.ns.svc.east.example.com:80|10.2.0.1:80`.
+///
+/// Each destination is resolved in order, and requests are balanced over the
+/// endpoints of all destinations that resolve. If unspecified, destinations
+/// are only resolved through their own names.
+pub const ENV_OUTBOUND_EXTRA_CLUSTERS: &str = "LINKERD2_PROXY_OUTBOUND_EXTRA_CLUSTERS";
+
+/// If true, outbound responses carry `l5d-dst-concrete` and `l5d-dst-endpoint`
+/// headers that describe the concrete destination and endpoint that served
+/// each request.
+///
+/// Disabled by default, since these headers reveal details about the
+/// destination to the client.
+pub const ENV_OUTBOUND_EXPOSE_DST_HEADERS: &str = "LINKERD2_PROXY_OUTBOUND_EXPOSE_DST_HEADERS";
+
 // These *disable* our protocol detection for connections whose SO_ORIGINAL_DST
 // has a port in the provided list.
 pub const ENV_INBOUND_PORTS_DISABLE_PROTOCOL_DETECTION: &str =
@@ -130,6 +254,10 @@ pub const ENV_DESTINATION_SVC_BASE: &str = "LINKERD2_PROXY_DESTINATION_SVC";
 
 pub const ENV_HOSTNAME: &str = "HOSTNAME";
 
+/// The topology zone in which the proxy's pod runs, used to prefer endpoints
+/// in the same zone.
+pub const ENV_POD_ZONE: &str = "POD_ZONE";
+
 pub const ENV_TRACE_COLLECTOR_SVC_BASE: &str = "LINKERD2_PROXY_TRACE_COLLECTOR_SVC";
 
 pub const ENV_DESTINATION_CONTEXT: &str = "LINKERD2_PROXY_DESTINATION_CONTEXT";
@@ -147,6 +275,21 @@ const ENV_DNS_MIN_TTL: &str = "LINKERD2_PROXY_DNS_MIN_TTL";
 /// Lookups with TTLs above this value will use this value instead.
 const ENV_DNS_MAX_TTL: &str = "LINKERD2_PROXY_DNS_MAX_TTL";
 
+/// Comma-separated nameservers, as `IP` or `IP:PORT`, to use instead of those
+/// in the system DNS configuration.
+///
+/// When set, the system DNS configuration is not read.
+pub const ENV_DNS_NAMESERVERS: &str = "LINKERD2_PROXY_DNS_NAMESERVERS";
+/// Comma-separated domains to search instead of those in the system DNS
+/// configuration.
+pub const ENV_DNS_SEARCH: &str = "LINKERD2_PROXY_DNS_SEARCH";
+/// Names with at least this many dots are not qualified by search domains.
+const ENV_DNS_NDOTS: &str = "LINKERD2_PROXY_DNS_NDOTS";
+/// The amount of time to wait for a response from a nameserver.
+const ENV_DNS_TIMEOUT: &str = "LINKERD2_PROXY_DNS_TIMEOUT";
+/// If true, queries are distributed across all nameservers.
+const ENV_DNS_ROTATE: &str = "LINKERD2_PROXY_DNS_ROTATE";
+
 /// The amount of time to wait for a DNS query to succeed before falling back to
 /// an uncanonicalized address.
 const ENV_DNS_CANONICALIZE_TIMEOUT: &str = "LINKERD2_PROXY_DNS_CANONICALIZE_TIMEOUT";
@@ -158,12 +301,43 @@ const ENV_INITIAL_STREAM_WINDOW_SIZE: &str = "LINKERD2_PROXY_HTTP2_INITIAL_STREA
 const ENV_INITIAL_CONNECTION_WINDOW_SIZE: &str =
     "LINKERD2_PROXY_HTTP2_INITIAL_CONNECTION_WINDOW_SIZE";
 
+/// Limits the number of concurrent streams on each HTTP2 client connection.
+///
+/// If unspecified, only the server's limit applies.
+const ENV_MAX_CONCURRENT_STREAMS: &str = "LINKERD2_PROXY_HTTP2_MAX_CONCURRENT_STREAMS";
+
+/// Override the HTTP2 flow control settings for outbound endpoints that are
+/// known to be meshed.
+///
+/// If unspecified, the settings above are used.
+const ENV_OUTBOUND_MESHED_INITIAL_STREAM_WINDOW_SIZE: &str =
+    "LINKERD2_PROXY_OUTBOUND_MESHED_HTTP2_INITIAL_STREAM_WINDOW_SIZE";
+const ENV_OUTBOUND_MESHED_INITIAL_CONNECTION_WINDOW_SIZE: &str =
+    "LINKERD2_PROXY_OUTBOUND_MESHED_HTTP2_INITIAL_CONNECTION_WINDOW_SIZE";
+
+/// Configure the pool of HTTP/1 connections maintained for each endpoint.
+///
+/// If unspecified, an unbounded number of idle connections are retained for
+/// up to 90 seconds.
+pub const ENV_HTTP1_MAX_IDLE_PER_ENDPOINT: &str = "LINKERD2_PROXY_HTTP1_MAX_IDLE_PER_ENDPOINT";
+pub const ENV_HTTP1_POOL_IDLE_TIMEOUT: &str = "LINKERD2_PROXY_HTTP1_POOL_IDLE_TIMEOUT";
+/// How long endpoints keep idle HTTP/1 connections open, if known. Idle
+/// connections are discarded before the endpoint would close them.
+pub const ENV_HTTP1_KEEP_ALIVE_TIMEOUT: &str = "LINKERD2_PROXY_HTTP1_KEEP_ALIVE_TIMEOUT";
+/// How long the body of a request with `Expect: 100-continue` is held back
+/// while waiting for the endpoint to respond. Defaults to 1 second.
+pub const ENV_HTTP1_EXPECT_CONTINUE_TIMEOUT: &str = "LINKERD2_PROXY_HTTP1_EXPECT_CONTINUE_TIMEOUT";
+/// Whether HTTP/1 messages with folded header values are rejected (`reject`,
+/// the default) or unfolded (`unfold`).
+pub const ENV_HTTP1_FOLDED_HEADERS: &str = "LINKERD2_PROXY_HTTP1_FOLDED_HEADERS";
+
 // Default values for various configuration fields
 const DEFAULT_OUTBOUND_LISTEN_ADDR: &str = "127.0.0.1:4140";
 const DEFAULT_INBOUND_LISTEN_ADDR: &str = "0.0.0.0:4143";
 const DEFAULT_CONTROL_LISTEN_ADDR: &str = "0.0.0.0:4190";
 const DEFAULT_ADMIN_LISTEN_ADDR: &str = "127.0.0.1:4191";
 const DEFAULT_METRICS_RETAIN_IDLE: Duration = Duration::from_secs(10 * 60);
+const DEFAULT_MAX_METRIC_LABELS: usize = crate::core::metric_labels::DEFAULT_MAX_METRIC_LABELS;
 const DEFAULT_INBOUND_DISPATCH_TIMEOUT: Duration = Duration::from_secs(1);
 const DEFAULT_INBOUND_CONNECT_TIMEOUT: Duration = Duration::from_millis(100);
 const DEFAULT_INBOUND_CONNECT_BACKOFF: ExponentialBackoff = ExponentialBackoff {
@@ -196,6 +370,13 @@ const DEFAULT_OUTBOUND_ROUTER_MAX_IDLE_AGE: Duration = Duration::from_secs(60);
 const DEFAULT_INBOUND_MAX_IN_FLIGHT: usize = 10_000;
 const DEFAULT_OUTBOUND_MAX_IN_FLIGHT: usize = 10_000;
 
+const DEFAULT_OUTBOUND_MAX_REPLAY_BODY_BYTES: usize = 64 * 1024;
+
+const DEFAULT_OUTBOUND_SPLIT_SLOW_START_MIN_WEIGHT: f64 = 0.1;
+
+const DEFAULT_OUTBOUND_FAILURE_ACCRUAL_WINDOW: usize = 100;
+const DEFAULT_OUTBOUND_FAILURE_ACCRUAL_BACKOFF: Duration = Duration::from_secs(10);
+
 const DEFAULT_DESTINATION_GET_SUFFIXES: &str = "svc.cluster.local.";
 const DEFAULT_DESTINATION_PROFILE_SUFFIXES: &str = "svc.cluster.local.";
 
@@ -227,6 +408,8 @@ pub fn parse_config<S: Strings>(strings: &S) -> Result<super::Config, EnvError>
 
     let outbound_dispatch_timeout = parse(strings, ENV_OUTBOUND_DISPATCH_TIMEOUT, parse_duration);
     let outbound_connect_timeout = parse(strings, ENV_OUTBOUND_CONNECT_TIMEOUT, parse_duration);
+    let outbound_meshed_connect_timeout =
+        parse(strings, ENV_OUTBOUND_MESHED_CONNECT_TIMEOUT, parse_duration);
 
     let inbound_accept_keepalive = parse(strings, ENV_INBOUND_ACCEPT_KEEPALIVE, parse_duration);
     let outbound_accept_keepalive = parse(strings, ENV_OUTBOUND_ACCEPT_KEEPALIVE, parse_duration);
@@ -255,8 +438,52 @@ pub fn parse_config<S: Strings>(strings: &S) -> Result<super::Config, EnvError>
 
     let inbound_max_in_flight = parse(strings, ENV_INBOUND_MAX_IN_FLIGHT, parse_number);
     let outbound_max_in_flight = parse(strings, ENV_OUTBOUND_MAX_IN_FLIGHT, parse_number);
+    let outbound_max_replay_body_bytes =
+        parse(strings, ENV_OUTBOUND_MAX_REPLAY_BODY_BYTES, parse_number);
+    let outbound_split_prewarm_timeout =
+        parse(strings, ENV_OUTBOUND_SPLIT_PREWARM_TIMEOUT, parse_duration);
+    let outbound_split_slow_start = parse(strings, ENV_OUTBOUND_SPLIT_SLOW_START, parse_duration);
+    let outbound_split_slow_start_min_weight = parse(
+        strings,
+        ENV_OUTBOUND_SPLIT_SLOW_START_MIN_WEIGHT,
+        parse_number::<f64>,
+    );
+    let outbound_split_override_header = parse(
+        strings,
+        ENV_OUTBOUND_SPLIT_OVERRIDE_HEADER,
+        parse_header_match,
+    );
+    let outbound_max_endpoint_connections =
+        parse(strings, ENV_OUTBOUND_MAX_ENDPOINT_CONNECTIONS, parse_number);
+    let outbound_failure_accrual_consecutive_failures = parse(
+        strings,
+        ENV_OUTBOUND_FAILURE_ACCRUAL_CONSECUTIVE_FAILURES,
+        parse_number,
+    );
+    let outbound_failure_accrual_failure_rate = parse(
+        strings,
+        ENV_OUTBOUND_FAILURE_ACCRUAL_FAILURE_RATE,
+        parse_number::<f64>,
+    );
+    let outbound_failure_accrual_window =
+        parse(strings, ENV_OUTBOUND_FAILURE_ACCRUAL_WINDOW, parse_number);
+    let outbound_failure_accrual_backoff = parse(
+        strings,
+        ENV_OUTBOUND_FAILURE_ACCRUAL_BACKOFF,
+        parse_duration,
+    );
 
     let metrics_retain_idle = parse(strings, ENV_METRICS_RETAIN_IDLE, parse_duration);
+    let max_metric_labels = parse(strings, ENV_MAX_METRIC_LABELS, parse_number);
+    let continue_ttfb = parse(
+        strings,
+        ENV_METRICS_CONTINUE_TTFB_AT_FIRST_FRAME,
+        parse_bool,
+    )
+    .map(|at_first_frame| match at_first_frame {
+        Some(true) => ContinueTtfb::FirstBodyFrame,
+        _ => ContinueTtfb::ResponseHeaders,
+    });
 
     // DNS
 
@@ -264,6 +491,11 @@ pub fn parse_config<S: Strings>(strings: &S) -> Result<super::Config, EnvError>
 
     let dns_min_ttl = parse(strings, ENV_DNS_MIN_TTL, parse_duration);
     let dns_max_ttl = parse(strings, ENV_DNS_MAX_TTL, parse_duration);
+    let dns_nameservers = parse(strings, ENV_DNS_NAMESERVERS, parse_nameservers);
+    let dns_search = parse(strings, ENV_DNS_SEARCH, parse_dns_names);
+    let dns_ndots = parse(strings, ENV_DNS_NDOTS, parse_number);
+    let dns_timeout = parse(strings, ENV_DNS_TIMEOUT, parse_duration);
+    let dns_rotate = parse(strings, ENV_DNS_ROTATE, parse_bool);
 
     let dns_canonicalize_timeout = parse(strings, ENV_DNS_CANONICALIZE_TIMEOUT, parse_duration);
 
@@ -282,6 +514,8 @@ pub fn parse_config<S: Strings>(strings: &S) -> Result<super::Config, EnvError>
 
     let hostname = strings.get(ENV_HOSTNAME);
 
+    let pod_zone = strings.get(ENV_POD_ZONE);
+
     let trace_collector_addr = if id_disabled {
         parse_control_addr_disable_identity(strings, ENV_TRACE_COLLECTOR_SVC_BASE)
     } else {
@@ -290,6 +524,22 @@ pub fn parse_config<S: Strings>(strings: &S) -> Result<super::Config, EnvError>
 
     let dst_token = strings.get(ENV_DESTINATION_CONTEXT);
 
+    let outbound_response_validation_allowlist = parse(
+        strings,
+        ENV_OUTBOUND_RESPONSE_VALIDATION_ALLOWLIST,
+        parse_dns_suffixes,
+    );
+
+    let outbound_endpoint_key_labels =
+        parse(strings, ENV_OUTBOUND_ENDPOINT_KEY_LABELS, parse_label_keys);
+    let outbound_upstream_proxies = parse(
+        strings,
+        ENV_OUTBOUND_UPSTREAM_PROXIES,
+        parse_upstream_proxies,
+    );
+    let outbound_extra_clusters = parse(strings, ENV_OUTBOUND_EXTRA_CLUSTERS, parse_extra_clusters);
+    let outbound_expose_dst_headers = parse(strings, ENV_OUTBOUND_EXPOSE_DST_HEADERS, parse_bool);
+
     let dst_get_suffixes = parse(strings, ENV_DESTINATION_GET_SUFFIXES, parse_dns_suffixes);
     let dst_get_networks = parse(strings, ENV_DESTINATION_GET_NETWORKS, parse_networks);
     let dst_profile_suffixes = parse(
@@ -297,13 +547,52 @@ pub fn parse_config<S: Strings>(strings: &S) -> Result<super::Config, EnvError>
         ENV_DESTINATION_PROFILE_SUFFIXES,
         parse_dns_suffixes,
     );
+    let dst_profile_affinity_header = parse(
+        strings,
+        ENV_DESTINATION_PROFILE_AFFINITY_HEADER,
+        parse_header_name,
+    );
 
     let initial_stream_window_size = parse(strings, ENV_INITIAL_STREAM_WINDOW_SIZE, parse_number);
     let initial_connection_window_size =
         parse(strings, ENV_INITIAL_CONNECTION_WINDOW_SIZE, parse_number);
+    let max_concurrent_streams = parse(strings, ENV_MAX_CONCURRENT_STREAMS, parse_number);
+    let outbound_meshed_initial_stream_window_size = parse(
+        strings,
+        ENV_OUTBOUND_MESHED_INITIAL_STREAM_WINDOW_SIZE,
+        parse_number,
+    );
+    let outbound_meshed_initial_connection_window_size = parse(
+        strings,
+        ENV_OUTBOUND_MESHED_INITIAL_CONNECTION_WINDOW_SIZE,
+        parse_number,
+    );
+
+    let http1_max_idle_per_endpoint = parse(strings, ENV_HTTP1_MAX_IDLE_PER_ENDPOINT, parse_number);
+    let http1_pool_idle_timeout = parse(strings, ENV_HTTP1_POOL_IDLE_TIMEOUT, parse_duration);
+    let http1_keep_alive_timeout = parse(strings, ENV_HTTP1_KEEP_ALIVE_TIMEOUT, parse_duration);
+    let http1_expect_continue_timeout =
+        parse(strings, ENV_HTTP1_EXPECT_CONTINUE_TIMEOUT, parse_duration);
+    let http1_folded_headers = parse(
+        strings,
+        ENV_HTTP1_FOLDED_HEADERS,
+        parse_folded_header_policy,
+    );
 
     let tap = parse_tap_config(strings, id_disabled);
 
+    let h1_settings = {
+        let default = h1::Settings::default();
+        h1::Settings {
+            max_idle_per_host: http1_max_idle_per_endpoint?.unwrap_or(default.max_idle_per_host),
+            pool_idle_timeout: http1_pool_idle_timeout?.or(default.pool_idle_timeout),
+            keep_alive_timeout: http1_keep_alive_timeout?,
+            expect_continue_timeout: http1_expect_continue_timeout?
+                .unwrap_or(default.expect_continue_timeout),
+            folded_headers: http1_folded_headers?.unwrap_or(default.folded_headers),
+        }
+    };
+
     let h2_settings = h2::Settings {
         initial_stream_window_size: Some(
             initial_stream_window_size?.unwrap_or(DEFAULT_INITIAL_STREAM_WINDOW_SIZE),
@@ -311,6 +600,8 @@ pub fn parse_config<S: Strings>(strings: &S) -> Result<super::Config, EnvError>
         initial_connection_window_size: Some(
             initial_connection_window_size?.unwrap_or(DEFAULT_INITIAL_CONNECTION_WINDOW_SIZE),
         ),
+        adaptive_window: None,
+        max_concurrent_streams: max_concurrent_streams?,
     };
 
     let outbound = {
@@ -336,11 +627,58 @@ pub fn parse_config<S: Strings>(strings: &S) -> Result<super::Config, EnvError>
                 OUTBOUND_CONNECT_BASE,
                 DEFAULT_OUTBOUND_CONNECT_BACKOFF,
             )?,
+            h1_settings,
             h2_settings,
         };
         outbound::Config {
             canonicalize_timeout: dns_canonicalize_timeout?
                 .unwrap_or(DEFAULT_DNS_CANONICALIZE_TIMEOUT),
+            response_validation_allowlist: outbound_response_validation_allowlist?
+                .unwrap_or_default()
+                .into(),
+            endpoint_key_labels: outbound_endpoint_key_labels?.unwrap_or_default().into(),
+            upstream_proxies: outbound_upstream_proxies?.unwrap_or_default().into(),
+            extra_clusters: outbound_extra_clusters?.unwrap_or_default(),
+            expose_dst_headers: outbound_expose_dst_headers?.unwrap_or(false),
+            meshed_connect_timeout: outbound_meshed_connect_timeout?.unwrap_or(connect.timeout),
+            meshed_h2_settings: h2::Settings {
+                initial_stream_window_size: outbound_meshed_initial_stream_window_size?,
+                initial_connection_window_size: outbound_meshed_initial_connection_window_size?,
+                adaptive_window: None,
+                max_concurrent_streams: None,
+            },
+            max_replay_body_bytes: outbound_max_replay_body_bytes?
+                .unwrap_or(DEFAULT_OUTBOUND_MAX_REPLAY_BODY_BYTES),
+            split_prewarm_timeout: outbound_split_prewarm_timeout?,
+            split_slow_start: {
+                let min_weight_fraction = outbound_split_slow_start_min_weight?
+                    .unwrap_or(DEFAULT_OUTBOUND_SPLIT_SLOW_START_MIN_WEIGHT);
+                outbound_split_slow_start?.map(|duration| profiles::SlowStartConfig {
+                    duration,
+                    min_weight_fraction,
+                })
+            },
+            split_override_condition: outbound_split_override_header?.map(|(name, value)| {
+                profiles::recognize::OverrideCondition::header_eq(name, value)
+            }),
+            pod_zone: pod_zone?,
+            max_endpoint_connections: outbound_max_endpoint_connections?,
+            failure_accrual: {
+                let max_failure_rate = outbound_failure_accrual_failure_rate?;
+                let window = outbound_failure_accrual_window?
+                    .unwrap_or(DEFAULT_OUTBOUND_FAILURE_ACCRUAL_WINDOW);
+                let backoff = outbound_failure_accrual_backoff?
+                    .unwrap_or(DEFAULT_OUTBOUND_FAILURE_ACCRUAL_BACKOFF);
+                outbound_failure_accrual_consecutive_failures?.map(|max_consecutive_failures| {
+                    FailureAccrualConfig {
+                        max_consecutive_failures,
+                        max_failure_rate,
+                        window,
+                        sample_window: None,
+                        backoff,
+                    }
+                })
+            },
             proxy: ProxyConfig {
                 server,
                 connect,
@@ -351,6 +689,7 @@ pub fn parse_config<S: Strings>(strings: &S) -> Result<super::Config, EnvError>
                     .unwrap_or(DEFAULT_OUTBOUND_ROUTER_MAX_IDLE_AGE),
                 router_capacity: outbound_router_capacity?
                     .unwrap_or(DEFAULT_OUTBOUND_ROUTER_CAPACITY),
+                continue_ttfb: continue_ttfb.clone()?,
             },
         }
     };
@@ -378,6 +717,7 @@ pub fn parse_config<S: Strings>(strings: &S) -> Result<super::Config, EnvError>
                 INBOUND_CONNECT_BASE,
                 DEFAULT_INBOUND_CONNECT_BACKOFF,
             )?,
+            h1_settings,
             h2_settings,
         };
         inbound::Config {
@@ -391,6 +731,7 @@ pub fn parse_config<S: Strings>(strings: &S) -> Result<super::Config, EnvError>
                     .unwrap_or(DEFAULT_INBOUND_ROUTER_MAX_IDLE_AGE),
                 router_capacity: inbound_router_capacity?
                     .unwrap_or(DEFAULT_INBOUND_ROUTER_CAPACITY),
+                continue_ttfb: continue_ttfb?,
             },
         }
     };
@@ -414,6 +755,7 @@ pub fn parse_config<S: Strings>(strings: &S) -> Result<super::Config, EnvError>
             get_networks: dst_get_networks?.unwrap_or_default(),
             profile_suffixes: dst_profile_suffixes?
                 .unwrap_or(parse_dns_suffixes(DEFAULT_DESTINATION_PROFILE_SUFFIXES).unwrap()),
+            profile_affinity_header: dst_profile_affinity_header?,
             control: ControlConfig {
                 addr,
                 connect,
@@ -424,6 +766,7 @@ pub fn parse_config<S: Strings>(strings: &S) -> Result<super::Config, EnvError>
 
     let admin = super::admin::Config {
         metrics_retain_idle: metrics_retain_idle?.unwrap_or(DEFAULT_METRICS_RETAIN_IDLE),
+        max_metric_labels: max_metric_labels?.unwrap_or(DEFAULT_MAX_METRIC_LABELS),
         server: ServerConfig {
             bind: listen::Bind::new(
                 admin_listener_addr?
@@ -441,6 +784,12 @@ pub fn parse_config<S: Strings>(strings: &S) -> Result<super::Config, EnvError>
         resolv_conf_path: resolv_conf_path?
             .unwrap_or(DEFAULT_RESOLV_CONF.into())
             .into(),
+        nameservers: dns_nameservers?,
+        search: dns_search?,
+        ndots: dns_ndots?,
+        timeout: dns_timeout?,
+        rotate: dns_rotate?.unwrap_or(false),
+        resolver: None,
     };
 
     let oc_collector = match trace_collector_addr? {
@@ -593,6 +942,30 @@ where
     s.parse().map_err(|_| ParseError::NotANumber)
 }
 
+fn parse_bool(s: &str) -> Result<bool, ParseError> {
+    s.parse().map_err(|_| ParseError::NotABool)
+}
+
+fn parse_header_name(s: &str) -> Result<http::header::HeaderName, ParseError> {
+    s.parse().map_err(|_| ParseError::InvalidHeaderName)
+}
+
+/// Parses a `<header>=<value>` pair.
+fn parse_header_match(
+    s: &str,
+) -> Result<(http::header::HeaderName, http::header::HeaderValue), ParseError> {
+    let mut parts = s.splitn(2, '=');
+    let name = parts.next().map(str::trim).unwrap_or_default();
+    let value = parts.next().ok_or(ParseError::InvalidHeaderMatch)?.trim();
+    let name = parse_header_name(name)?;
+    let value = value.parse().map_err(|_| ParseError::InvalidHeaderMatch)?;
+    Ok((name, value))
+}
+
+fn parse_folded_header_policy(s: &str) -> Result<h1::FoldedHeaderPolicy, ParseError> {
+    s.parse().map_err(|_| ParseError::InvalidFoldedHeaderPolicy)
+}
+
 fn parse_duration(s: &str) -> Result<Duration, ParseError> {
     use regex::Regex;
 
@@ -710,6 +1083,111 @@ fn parse_dns_suffix(s: &str) -> Result<dns::Suffix, ParseError> {
         .map_err(|_| ParseError::NotADomainSuffix)
 }
 
+fn parse_label_keys(list: &str) -> Result<IndexSet<String>, ParseError> {
+    Ok(list
+        .split(',')
+        .map(str::trim)
+        .filter(|key| !key.is_empty())
+        .map(String::from)
+        .collect())
+}
+
+fn parse_upstream_proxies(list: &str) -> Result<outbound::upstream_proxy::Rules, ParseError> {
+    use outbound::upstream_proxy::{Match, Rule, UpstreamProxy};
+
+    let mut rules = Vec::new();
+    for item in list.split(',') {
+        let item = item.trim();
+        if item.is_empty() {
+            continue;
+        }
+
+        let mut parts = item.splitn(2, '=');
+        let dst = parts.next().unwrap_or_default().trim();
+        let proxy = parts.next().ok_or(ParseError::InvalidUpstreamProxy)?.trim();
+        let dst = match dst.parse::<SocketAddr>() {
+            Ok(addr) => Match::Addr(addr),
+            Err(_) => Match::Suffix(parse_dns_suffix(dst)?),
+        };
+
+        let mut parts = proxy.splitn(2, '@');
+        let addr = parse_socket_addr(parts.next().unwrap_or_default())?;
+        let credentials = match parts.next() {
+            Some(path) => Some(fs::read_to_string(path).map_err(|e| {
+                error!(
+                    "Failed to read upstream proxy credentials from {}: {}",
+                    path, e
+                );
+                ParseError::InvalidUpstreamProxy
+            })?),
+            None => None,
+        };
+
+        rules.push(Rule {
+            dst,
+            proxy: UpstreamProxy::new(addr, credentials.as_ref().map(String::as_str)),
+        });
+    }
+
+    Ok(outbound::upstream_proxy::Rules::new(rules))
+}
+
+fn parse_extra_clusters(list: &str) -> Result<outbound::ProfileTarget, ParseError> {
+    let mut extra_clusters = IndexMap::new();
+    for item in list.split(',') {
+        let item = item.trim();
+        if item.is_empty() {
+            continue;
+        }
+
+        let mut parts = item.splitn(2, '=');
+        let dst = match parse_addr(parts.next().unwrap_or_default().trim())? {
+            Addr::Name(name) => name,
+            Addr::Socket(_) => {
+                error!("Expected NAME:PORT; found: {}", item);
+                return Err(ParseError::InvalidExtraClusters);
+            }
+        };
+        let addrs = parts.next().ok_or(ParseError::InvalidExtraClusters)?;
+        let addrs = addrs
+            .split('|')
+            .map(|addr| parse_addr(addr.trim()))
+            .collect::<Result<Vec<_>, _>>()?;
+        extra_clusters.insert(dst, addrs);
+    }
+
+    Ok(outbound::ProfileTarget::new(extra_clusters))
+}
+
+fn parse_nameservers(list: &str) -> Result<Vec<SocketAddr>, ParseError> {
+    let mut addrs = Vec::new();
+    for item in list.split(',') {
+        let item = item.trim();
+        if !item.is_empty() {
+            // Nameservers are reachable on the standard DNS port by default.
+            let addr = match IpAddr::from_str(item) {
+                Ok(ip) => SocketAddr::new(ip, 53),
+                Err(_) => parse_socket_addr(item)?,
+            };
+            addrs.push(addr);
+        }
+    }
+    Ok(addrs)
+}
+
+fn parse_dns_names(list: &str) -> Result<Vec<dns::Name>, ParseError> {
+    let mut names = Vec::new();
+    for item in list.split(',') {
+        let item = item.trim();
+        if !item.is_empty() {
+            let name =
+                dns::Name::try_from(item.as_bytes()).map_err(|_| ParseError::NotADomainSuffix)?;
+            names.push(name);
+        }
+    }
+    Ok(names)
+}
+
 fn parse_networks(list: &str) -> Result<IndexSet<ipnet::IpNet>, ParseError> {
     let mut nets = IndexSet::new();
     for input in list.split(',') {
@@ -1047,4 +1525,137 @@ mod tests {
             "names are coerced to lowercase"
         );
     }
+
+    #[test]
+    fn dns_nameservers() {
+        assert_eq!(parse_nameservers(""), Ok(vec![]));
+        assert_eq!(
+            parse_nameservers("10.0.0.53, 10.0.0.54:5353"),
+            Ok(vec![
+                SocketAddr::from(([10, 0, 0, 53], 53)),
+                SocketAddr::from(([10, 0, 0, 54], 5353)),
+            ]),
+            "a bare IP uses the standard DNS port"
+        );
+        assert!(parse_nameservers("dns.example.com").is_err());
+    }
+
+    #[test]
+    fn parse_bool_values() {
+        assert_eq!(parse_bool("true"), Ok(true));
+        assert_eq!(parse_bool("false"), Ok(false));
+        assert_eq!(parse_bool("yes"), Err(ParseError::NotABool));
+    }
+
+    #[test]
+    fn parse_header_names() {
+        assert_eq!(
+            parse_header_name("X-Session-Id"),
+            Ok(http::header::HeaderName::from_static("x-session-id"))
+        );
+        assert_eq!(
+            parse_header_name("x session"),
+            Err(ParseError::InvalidHeaderName)
+        );
+    }
+
+    #[test]
+    fn parse_header_matches() {
+        assert_eq!(
+            parse_header_match("l5d-override-ns = canary"),
+            Ok((
+                http::header::HeaderName::from_static("l5d-override-ns"),
+                http::header::HeaderValue::from_static("canary")
+            ))
+        );
+        assert_eq!(
+            parse_header_match("l5d-override-ns"),
+            Err(ParseError::InvalidHeaderMatch)
+        );
+        assert_eq!(
+            parse_header_match("l5d override=canary"),
+            Err(ParseError::InvalidHeaderName)
+        );
+    }
+
+    #[test]
+    fn parse_folded_header_policies() {
+        assert_eq!(
+            parse_folded_header_policy("reject"),
+            Ok(h1::FoldedHeaderPolicy::Reject)
+        );
+        assert_eq!(
+            parse_folded_header_policy("UNFOLD"),
+            Ok(h1::FoldedHeaderPolicy::Unfold)
+        );
+        assert_eq!(
+            parse_folded_header_policy("fold"),
+            Err(ParseError::InvalidFoldedHeaderPolicy)
+        );
+    }
+
+    #[test]
+    fn upstream_proxies() {
+        let rules = parse_upstream_proxies("example.com=10.0.0.1:3128, 10.1.1.1:443=10.0.0.2:3128")
+            .unwrap();
+        let proxy = |dst: &str| {
+            rules
+                .find(&Addr::from_str(dst).unwrap())
+                .map(|p| p.addr.to_string())
+        };
+        assert_eq!(proxy("web.example.com:80"), Some("10.0.0.1:3128".into()));
+        assert_eq!(proxy("10.1.1.1:443"), Some("10.0.0.2:3128".into()));
+        assert_eq!(proxy("10.1.1.1:80"), None);
+        assert_eq!(proxy("example.org:80"), None);
+
+        assert!(parse_upstream_proxies("example.com").is_err());
+        assert!(parse_upstream_proxies("example.com=proxy.example.com:3128").is_err());
+        assert!(parse_upstream_proxies("example.com=10.0.0.1:3128@/does/not/exist").is_err());
+    }
+
+    #[test]
+    fn extra_clusters() {
+        use crate::core::{dst::DstAddr, proxy::http::Settings};
+
+        let target = parse_extra_clusters(
+            "web.ns.svc.cluster.local:80=web.ns.svc.east.example.com:80|10.2.0.1:80",
+        )
+        .unwrap();
+        let dst = |s: &str| DstAddr::outbound(Addr::from_str(s).unwrap(), Settings::Http2);
+        assert_eq!(
+            target.key(dst("web.ns.svc.cluster.local:80")),
+            outbound::ProfileTarget::key_with_clusters(
+                dst("web.ns.svc.cluster.local:80"),
+                vec![
+                    Addr::from_str("web.ns.svc.east.example.com:80").unwrap(),
+                    Addr::from_str("10.2.0.1:80").unwrap(),
+                ],
+            )
+        );
+        assert_eq!(
+            target.key(dst("api.ns.svc.cluster.local:80")),
+            outbound::ProfileTarget::key_with_clusters(dst("api.ns.svc.cluster.local:80"), vec![])
+        );
+
+        assert!(parse_extra_clusters("web.ns.svc.cluster.local:80").is_err());
+        assert!(parse_extra_clusters("10.1.1.1:80=10.2.0.1:80").is_err());
+        assert!(parse_extra_clusters("web.ns.svc.cluster.local:80=web").is_err());
+    }
+
+    #[test]
+    fn upstream_proxy_credentials() {
+        let path = std::env::temp_dir().join("linkerd2-proxy-upstream-proxy-credentials");
+        fs::write(&path, "user:pass\n").unwrap();
+        let rules =
+            parse_upstream_proxies(&format!("example.com=10.0.0.1:3128@{}", path.display()))
+                .unwrap();
+        let _ = fs::remove_file(&path);
+
+        let proxy = rules
+            .find(&Addr::from_str("example.com:80").unwrap())
+            .unwrap();
+        let authorization = proxy.authorization.as_ref().expect("credentials");
+        assert_eq!(authorization.to_str().unwrap(), "Basic dXNlcjpwYXNz");
+        assert!(authorization.is_sensitive());
+    }
 }
@@ -46,7 +46,7 @@ impl Config {
                     .push(tls::client::layer(tls::Conditional::Some(
                         certify.trust_anchors.clone(),
                     )))
-                    .push_timeout(control.connect.timeout)
+                    .push_timeout("identity-connection", control.connect.timeout)
                     .push(control::client::layer())
                     .push(control::resolve::layer(dns))
                     .push(reconnect::layer({
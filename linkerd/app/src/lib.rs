@@ -98,7 +98,7 @@ impl<A: OrigDstAddr + Send + 'static> Config<A> {
             tap,
         } = self;
         debug!("building app");
-        let (metrics, report) = Metrics::new(admin.metrics_retain_idle);
+        let (metrics, report) = Metrics::new(admin.metrics_retain_idle, admin.max_metric_labels);
 
         let dns = info_span!("dns").in_scope(|| dns.build())?;
 
@@ -118,6 +118,7 @@ impl<A: OrigDstAddr + Send + 'static> Config<A> {
                 transport::{connect, tls},
             };
 
+            let freeze = metrics.freeze.clone();
             let metrics = metrics.control.clone();
             let dns = dns.resolver.clone();
             info_span!("dst").in_scope(|| {
@@ -127,7 +128,7 @@ impl<A: OrigDstAddr + Send + 'static> Config<A> {
                 // into a task so consumers can be ignorant.
                 let svc = svc::stack(connect::svc(dst.control.connect.keepalive))
                     .push(tls::client::layer(identity.local()))
-                    .push_timeout(dst.control.connect.timeout)
+                    .push_timeout("destination-connection", dst.control.connect.timeout)
                     .push(control::client::layer())
                     .push(control::resolve::layer(dns))
                     .push(reconnect::layer({
@@ -143,7 +144,7 @@ impl<A: OrigDstAddr + Send + 'static> Config<A> {
                     )
                     .into_inner()
                     .make(dst.control.addr.clone());
-                dst.build(svc)
+                dst.build(svc, freeze)
             })
         }?;
 
@@ -157,7 +158,9 @@ impl<A: OrigDstAddr + Send + 'static> Config<A> {
         let admin = {
             let identity = identity.local();
             let drain = drain_rx.clone();
-            info_span!("admin").in_scope(move || admin.build(identity, report, log_level, drain))?
+            let freeze = metrics.freeze.clone();
+            info_span!("admin")
+                .in_scope(move || admin.build(identity, report, log_level, freeze, drain))?
         };
 
         let dst_addr = dst.addr.clone();
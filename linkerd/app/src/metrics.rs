@@ -1,9 +1,9 @@
 pub use linkerd2_app_core::{
     classify::Class,
-    handle_time,
-    metric_labels::{ControlLabels, EndpointLabels, RouteLabels},
+    freeze, handle_time,
+    metric_labels::{CardinalityCap, ControlLabels, EndpointLabels, RouteLabels},
     metrics::FmtMetrics,
-    opencensus, proxy, telemetry, transport, ControlHttpMetricsRegistry, ProxyMetrics,
+    opencensus, proxy, router, telemetry, transport, ControlHttpMetricsRegistry, ProxyMetrics,
 };
 use std::time::{Duration, SystemTime};
 
@@ -12,10 +12,14 @@ pub struct Metrics {
     pub outbound: ProxyMetrics,
     pub control: ControlHttpMetricsRegistry,
     pub opencensus: opencensus::metrics::Registry,
+    pub freeze: freeze::Registry,
 }
 
 impl Metrics {
-    pub fn new(retain_idle: Duration) -> (Self, impl FmtMetrics + Clone + Send + 'static) {
+    pub fn new(
+        retain_idle: Duration,
+        max_labels: usize,
+    ) -> (Self, impl FmtMetrics + Clone + Send + 'static) {
         let process = telemetry::process::Report::new(SystemTime::now());
 
         let (control, control_report) = {
@@ -36,6 +40,11 @@ impl Metrics {
             (m, r.with_prefix("route_actual"))
         };
 
+        let (http_split, split_report) = proxy::http::profiles::metrics::new();
+
+        let (http_response_validation, response_validation_report) =
+            proxy::http::validate_response::new();
+
         let handle_time_report = handle_time::Metrics::new();
         let inbound_handle_time = handle_time_report.inbound();
         let outbound_handle_time = handle_time_report.outbound();
@@ -44,32 +53,62 @@ impl Metrics {
 
         let (opencensus, opencensus_report) = opencensus::metrics::new();
 
+        let freeze = freeze::Registry::default();
+
+        let label_cap = CardinalityCap::new(max_labels);
+
+        let router_cache = router::metrics::Registry::default();
+
+        let buffers = proxy::buffer::Registry::default();
+
+        let failure_accrual = proxy::failure_accrual::Metrics::default();
+
         let metrics = Metrics {
             inbound: ProxyMetrics {
                 http_handle_time: inbound_handle_time,
                 http_endpoint: http_endpoint.clone(),
                 http_route: http_route.clone(),
                 http_route_retry: http_route_retry.clone(),
+                http_split: http_split.clone(),
+                http_response_validation: http_response_validation.clone(),
                 transport: transport.clone(),
+                label_cap: label_cap.clone(),
+                router_cache: router_cache.clone(),
+                buffers: buffers.clone(),
+                failure_accrual: failure_accrual.clone(),
             },
             outbound: ProxyMetrics {
                 http_handle_time: outbound_handle_time,
                 http_endpoint,
                 http_route,
                 http_route_retry,
+                http_split,
+                http_response_validation,
                 transport,
+                label_cap: label_cap.clone(),
+                router_cache: router_cache.clone(),
+                buffers: buffers.clone(),
+                failure_accrual: failure_accrual.clone(),
             },
             control,
             opencensus,
+            freeze: freeze.clone(),
         };
 
         let report = endpoint_report
             .and_then(route_report)
             .and_then(retry_report)
+            .and_then(split_report)
+            .and_then(response_validation_report)
             .and_then(control_report)
             .and_then(handle_time_report)
             .and_then(transport_report)
             .and_then(opencensus_report)
+            .and_then(freeze)
+            .and_then(label_cap)
+            .and_then(router_cache)
+            .and_then(buffers)
+            .and_then(failure_accrual)
             .and_then(process);
 
         (metrics, report)
@@ -50,7 +50,7 @@ impl Config {
                 let addr = control.addr;
                 let svc = svc::stack(connect::svc(control.connect.keepalive))
                     .push(tls::client::layer(identity))
-                    .push_timeout(control.connect.timeout)
+                    .push_timeout("opencensus-connection", control.connect.timeout)
                     // TODO: perhaps rename from "control" to "grpc"
                     .push(control::client::layer())
                     .push(control::resolve::layer(dns.clone()))
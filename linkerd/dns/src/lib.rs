@@ -1,35 +1,98 @@
 #![deny(warnings, rust_2018_idioms)]
 
-use futures::{prelude::*, try_ready};
+use futures::{future, prelude::*, try_ready};
 pub use linkerd2_dns_name::{InvalidName, Name, Suffix};
 use std::convert::TryFrom;
+use std::sync::Arc;
 use std::time::Instant;
 use std::{fmt, net};
 use tracing::{info_span, trace};
 use tracing_futures::Instrument;
 pub use trust_dns_resolver::config::ResolverOpts;
 pub use trust_dns_resolver::error::{ResolveError, ResolveErrorKind};
-use trust_dns_resolver::lookup_ip::LookupIp;
-use trust_dns_resolver::{config::ResolverConfig, system_conf, AsyncResolver};
+use trust_dns_resolver::{
+    config::{NameServerConfigGroup, ResolverConfig},
+    system_conf, AsyncResolver,
+};
 
-#[derive(Clone)]
+mod search;
+
+pub use self::search::SearchPath;
+
+/// Resolves DNS records for fully-qualified names.
+///
+/// Search domains are applied by the `Resolver` before a `DnsResolver` is
+/// consulted, so implementations should not expand names.
+pub trait DnsResolver: fmt::Debug + Send + Sync + 'static {
+    fn resolve_a(&self, name: &Name) -> LookupFuture<net::Ipv4Addr>;
+
+    fn resolve_aaaa(&self, name: &Name) -> LookupFuture<net::Ipv6Addr>;
+
+    fn resolve_srv(&self, name: &Name) -> LookupFuture<Srv>;
+}
+
+/// The records found for a name and the time until which they may be used.
+#[derive(Clone, Debug)]
+pub struct Lookup<T> {
+    pub records: Vec<T>,
+    pub valid_until: Instant,
+}
+
+pub type LookupFuture<T> = Box<dyn Future<Item = Lookup<T>, Error = Error> + Send + 'static>;
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Srv {
+    pub priority: u16,
+    pub weight: u16,
+    pub port: u16,
+    pub target: Name,
+}
+
+/// Resolves names on a search path.
+#[derive(Clone, Debug)]
 pub struct Resolver {
-    resolver: AsyncResolver,
+    dns: Arc<dyn DnsResolver>,
+    search: SearchPath,
 }
 
+/// A `DnsResolver` backed by Trust-DNS.
+#[derive(Clone)]
+pub struct TrustDns(AsyncResolver);
+
 pub trait ConfigureResolver {
     fn configure_resolver(&self, _: &mut ResolverOpts);
+
+    /// Nameservers to use instead of those in the system configuration.
+    ///
+    /// When set, the system configuration is not read at all.
+    fn nameservers(&self) -> Option<Vec<net::SocketAddr>> {
+        None
+    }
+
+    /// Search domains to use instead of those in the system configuration.
+    fn search(&self) -> Option<Vec<Name>> {
+        None
+    }
 }
 
 #[derive(Debug)]
 pub enum Error {
-    NoAddressesFound,
+    /// The name does not exist or has no records of the requested type.
+    ///
+    /// This may be cached until `valid_until`, if it is known.
+    NoRecordsFound {
+        valid_until: Option<Instant>,
+    },
     ResolutionFailed(ResolveError),
 }
 
-pub struct IpAddrFuture(Box<dyn Future<Item = LookupIp, Error = ResolveError> + Send + 'static>);
+type Searching<T> = Box<dyn Future<Item = (Name, Lookup<T>), Error = Error> + Send + 'static>;
+
+pub struct IpAddrFuture(Searching<net::IpAddr>);
 
-pub struct RefineFuture(Box<dyn Future<Item = LookupIp, Error = ResolveError> + Send + 'static>);
+pub struct RefineFuture(Searching<net::IpAddr>);
+
+pub struct SrvFuture(Searching<Srv>);
 
 pub struct Refine {
     pub name: Name,
@@ -52,29 +115,22 @@ impl Resolver {
     pub fn from_system_config_with<C: ConfigureResolver>(
         c: &C,
     ) -> Result<(Self, Task), ResolveError> {
-        let (config, mut opts) = system_conf::read_system_conf()?;
-        c.configure_resolver(&mut opts);
+        let (config, opts, search) = configure(c, system_conf::read_system_conf)?;
         trace!("DNS config: {:?}", &config);
         trace!("DNS opts: {:?}", &opts);
-        Ok(Self::new(config, opts))
+        trace!("DNS search: {:?}", &search);
+        let (dns, task) = TrustDns::new(config, opts);
+        Ok((Self::new(Arc::new(dns), search), task))
     }
 
-    /// NOTE: It would be nice to be able to return a named type rather than
-    ///       `impl Future` for the background future; it would be called
-    ///       `Background` or `ResolverBackground` if that were possible.
-    pub fn new(config: ResolverConfig, mut opts: ResolverOpts) -> (Self, Task) {
-        // Disable Trust-DNS's caching.
-        opts.cache_size = 0;
-        let (resolver, task) = AsyncResolver::new(config, opts);
-        let resolver = Resolver { resolver };
-        (resolver, Box::new(task))
+    /// Resolves names on the `search` path with `dns`.
+    pub fn new(dns: Arc<dyn DnsResolver>, search: SearchPath) -> Self {
+        Self { dns, search }
     }
 
     pub fn resolve_one_ip(&self, name: &Name) -> IpAddrFuture {
-        let name = name.clone();
         let f = self
-            .resolver
-            .lookup_ip(name.as_ref())
+            .search(name, lookup_ip)
             .instrument(info_span!("resolve_one_ip", %name));
         IpAddrFuture(Box::new(f))
     }
@@ -87,59 +143,278 @@ impl Resolver {
     /// For example, a name like `web` may be refined to `web.example.com.`,
     /// depending on the DNS search path.
     pub fn refine(&self, name: &Name) -> RefineFuture {
-        let name = name.clone();
         let f = self
-            .resolver
-            .lookup_ip(name.as_ref())
+            .search(name, lookup_ip)
             .instrument(info_span!("refine", %name));
         RefineFuture(Box::new(f))
     }
+
+    /// Resolves the SRV records for `name`.
+    pub fn resolve_srv(&self, name: &Name) -> SrvFuture {
+        let f = self
+            .search(name, |dns, name| dns.resolve_srv(name))
+            .instrument(info_span!("resolve_srv", %name));
+        SrvFuture(Box::new(f))
+    }
+
+    fn search<T>(
+        &self,
+        name: &Name,
+        lookup: fn(&Arc<dyn DnsResolver>, &Name) -> LookupFuture<T>,
+    ) -> search::Search<T> {
+        let candidates = self.search.candidates(name);
+        trace!(?candidates);
+        search::Search::new(self.dns.clone(), lookup, candidates)
+    }
+}
+
+/// Resolves A records for `name`, falling back to AAAA records if there are
+/// none.
+fn lookup_ip(dns: &Arc<dyn DnsResolver>, name: &Name) -> LookupFuture<net::IpAddr> {
+    let aaaa = {
+        let dns = dns.clone();
+        let name = name.clone();
+        move || dns.resolve_aaaa(&name).map(|l| l.map(net::IpAddr::from))
+    };
+    let f = dns.resolve_a(name).then(move |res| match res {
+        Ok(a) => {
+            if a.records.is_empty() {
+                future::Either::B(aaaa())
+            } else {
+                future::Either::A(future::ok(a.map(net::IpAddr::from)))
+            }
+        }
+        Err(Error::NoRecordsFound { .. }) => future::Either::B(aaaa()),
+        Err(e) => future::Either::A(future::err(e)),
+    });
+    Box::new(f)
+}
+
+/// Builds a Trust-DNS configuration, applying `c`'s overrides to the system
+/// configuration.
+///
+/// Search domains are applied by the `Resolver` rather than by Trust-DNS, so
+/// they are returned separately.
+fn configure<C: ConfigureResolver, E>(
+    c: &C,
+    read_system_conf: impl FnOnce() -> Result<(ResolverConfig, ResolverOpts), E>,
+) -> Result<(ResolverConfig, ResolverOpts, SearchPath), ResolveError>
+where
+    ResolveError: From<E>,
+{
+    let nameservers = c.nameservers();
+    let (system, mut opts) = match nameservers {
+        Some(_) => (ResolverConfig::new(), ResolverOpts::default()),
+        None => read_system_conf()?,
+    };
+    c.configure_resolver(&mut opts);
+
+    let mut config = ResolverConfig::new();
+    match nameservers {
+        Some(addrs) => {
+            for addr in addrs {
+                let group = NameServerConfigGroup::from_ips_clear(&[addr.ip()], addr.port());
+                for ns in group.iter() {
+                    config.add_name_server(ns.clone());
+                }
+            }
+        }
+        None => {
+            for ns in system.name_servers() {
+                config.add_name_server(ns.clone());
+            }
+        }
+    }
+
+    let domains = c.search().unwrap_or_else(|| {
+        // As in resolv.conf(5), the local domain is only searched if no
+        // search list is configured.
+        let search = if system.search().is_empty() {
+            system.domain().into_iter().collect::<Vec<_>>()
+        } else {
+            system.search().iter().collect::<Vec<_>>()
+        };
+        search
+            .into_iter()
+            .filter_map(|n| Name::try_from(n.to_ascii().as_bytes()).ok())
+            .collect()
+    });
+    let search = SearchPath::new(domains, opts.ndots);
+
+    Ok((config, opts, search))
+}
+
+// === impl TrustDns ===
+
+impl TrustDns {
+    /// NOTE: It would be nice to be able to return a named type rather than
+    ///       `impl Future` for the background future; it would be called
+    ///       `Background` or `ResolverBackground` if that were possible.
+    pub fn new(config: ResolverConfig, mut opts: ResolverOpts) -> (Self, Task) {
+        // Disable Trust-DNS's caching.
+        opts.cache_size = 0;
+        let (resolver, task) = AsyncResolver::new(config, opts);
+        (TrustDns(resolver), Box::new(task))
+    }
+}
+
+impl DnsResolver for TrustDns {
+    fn resolve_a(&self, name: &Name) -> LookupFuture<net::Ipv4Addr> {
+        let f = self
+            .0
+            .ipv4_lookup(fqdn(name).as_str())
+            .map_err(Error::from)
+            .map(|l| Lookup {
+                records: l
+                    .iter()
+                    .map(|ip| net::Ipv4Addr::from(ip.octets()))
+                    .collect(),
+                valid_until: l.valid_until(),
+            });
+        Box::new(f)
+    }
+
+    fn resolve_aaaa(&self, name: &Name) -> LookupFuture<net::Ipv6Addr> {
+        let f = self
+            .0
+            .ipv6_lookup(fqdn(name).as_str())
+            .map_err(Error::from)
+            .map(|l| Lookup {
+                records: l
+                    .iter()
+                    .map(|ip| net::Ipv6Addr::from(ip.segments()))
+                    .collect(),
+                valid_until: l.valid_until(),
+            });
+        Box::new(f)
+    }
+
+    fn resolve_srv(&self, name: &Name) -> LookupFuture<Srv> {
+        let f = self
+            .0
+            .srv_lookup(fqdn(name).as_str())
+            .map_err(Error::from)
+            .map(|l| Lookup {
+                records: l
+                    .iter()
+                    .filter_map(|srv| {
+                        let target = Name::try_from(srv.target().to_ascii().as_bytes()).ok()?;
+                        Some(Srv {
+                            priority: srv.priority(),
+                            weight: srv.weight(),
+                            port: srv.port(),
+                            target,
+                        })
+                    })
+                    .collect(),
+                valid_until: l.valid_until(),
+            });
+        Box::new(f)
+    }
+}
+
+/// Ensures that Trust-DNS does not expand `name` against its own search path.
+fn fqdn(name: &Name) -> String {
+    format!("{}.", name.without_trailing_dot())
 }
 
 /// Note: `AsyncResolver` does not implement `Debug`, so we must manually
 ///       implement this.
-impl fmt::Debug for Resolver {
+impl fmt::Debug for TrustDns {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        f.debug_struct("Resolver")
-            .field("resolver", &"...")
-            .finish()
+        f.debug_tuple("TrustDns").field(&"...").finish()
     }
 }
 
+// === impl Lookup ===
+
+impl<T> Lookup<T> {
+    pub fn map<U, F: FnMut(T) -> U>(self, f: F) -> Lookup<U> {
+        Lookup {
+            records: self.records.into_iter().map(f).collect(),
+            valid_until: self.valid_until,
+        }
+    }
+}
+
+// === impl Error ===
+
+impl From<ResolveError> for Error {
+    fn from(e: ResolveError) -> Self {
+        if let ResolveErrorKind::NoRecordsFound { valid_until, .. } = e.kind() {
+            return Error::NoRecordsFound {
+                valid_until: *valid_until,
+            };
+        }
+        Error::ResolutionFailed(e)
+    }
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Error::NoRecordsFound { .. } => write!(f, "no records found"),
+            Error::ResolutionFailed(e) => fmt::Display::fmt(e, f),
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
+// === impl IpAddrFuture ===
+
 impl Future for IpAddrFuture {
     type Item = net::IpAddr;
     type Error = Error;
 
     fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
-        let ips = try_ready!(self.0.poll().map_err(Error::ResolutionFailed));
-        ips.iter()
+        let (_, lookup) = try_ready!(self.0.poll());
+        let valid_until = lookup.valid_until;
+        lookup
+            .records
+            .into_iter()
             .next()
             .map(Async::Ready)
-            .ok_or_else(|| Error::NoAddressesFound)
+            .ok_or_else(|| Error::NoRecordsFound {
+                valid_until: Some(valid_until),
+            })
     }
 }
 
+// === impl RefineFuture ===
+
 impl Future for RefineFuture {
     type Item = Refine;
-    type Error = ResolveError;
+    type Error = Error;
 
     fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
-        let lookup = try_ready!(self.0.poll());
-        let valid_until = lookup.valid_until();
+        let (name, lookup) = try_ready!(self.0.poll());
+        let refine = Refine {
+            name,
+            valid_until: lookup.valid_until,
+        };
+        Ok(Async::Ready(refine))
+    }
+}
 
-        let n = lookup.query().name();
-        let name = Name::try_from(n.to_ascii().as_bytes())
-            .expect("Name returned from resolver must be valid");
+// === impl SrvFuture ===
 
-        let refine = Refine { name, valid_until };
-        Ok(Async::Ready(refine))
+impl Future for SrvFuture {
+    type Item = Lookup<Srv>;
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        let (_, lookup) = try_ready!(self.0.poll());
+        Ok(Async::Ready(lookup))
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{Name, Suffix};
+    use super::*;
+    use std::collections::HashMap;
     use std::convert::TryFrom;
+    use std::sync::Mutex;
 
     #[test]
     fn test_dns_name_parsing() {
@@ -248,4 +523,175 @@ mod tests {
 
         assert!(Suffix::try_from("").is_err(), "suffix must not be empty");
     }
+
+    #[derive(Debug, Default)]
+    struct MockDns {
+        a: HashMap<String, net::Ipv4Addr>,
+        queries: Mutex<Vec<String>>,
+    }
+
+    impl MockDns {
+        fn with_a(mut self, name: &str, ip: [u8; 4]) -> Self {
+            self.a.insert(name.to_owned(), ip.into());
+            self
+        }
+
+        fn queries(&self) -> Vec<String> {
+            self.queries.lock().unwrap().clone()
+        }
+    }
+
+    impl DnsResolver for MockDns {
+        fn resolve_a(&self, name: &Name) -> LookupFuture<net::Ipv4Addr> {
+            self.queries.lock().unwrap().push(name.to_string());
+            let res = match self.a.get(name.as_ref()) {
+                Some(ip) => Ok(Lookup {
+                    records: vec![*ip],
+                    valid_until: Instant::now(),
+                }),
+                None => Err(Error::NoRecordsFound { valid_until: None }),
+            };
+            Box::new(future::result(res))
+        }
+
+        fn resolve_aaaa(&self, _: &Name) -> LookupFuture<net::Ipv6Addr> {
+            Box::new(future::err(Error::NoRecordsFound { valid_until: None }))
+        }
+
+        fn resolve_srv(&self, _: &Name) -> LookupFuture<Srv> {
+            Box::new(future::err(Error::NoRecordsFound { valid_until: None }))
+        }
+    }
+
+    fn name(s: &str) -> Name {
+        Name::try_from(s.as_bytes()).unwrap()
+    }
+
+    fn names(ns: Vec<Name>) -> Vec<String> {
+        ns.iter().map(ToString::to_string).collect()
+    }
+
+    fn cluster_search(ndots: usize) -> SearchPath {
+        SearchPath::new(
+            vec![name("ns.svc.cluster.local"), name("svc.cluster.local")],
+            ndots,
+        )
+    }
+
+    #[test]
+    fn search_path_candidates() {
+        let search = cluster_search(2);
+
+        assert_eq!(
+            names(search.candidates(&name("web"))),
+            vec![
+                "web.ns.svc.cluster.local.",
+                "web.svc.cluster.local.",
+                "web."
+            ]
+        );
+        assert_eq!(
+            names(search.candidates(&name("web.ns"))),
+            vec![
+                "web.ns.ns.svc.cluster.local.",
+                "web.ns.svc.cluster.local.",
+                "web.ns."
+            ]
+        );
+
+        // Names with at least `ndots` dots or a trailing dot are absolute.
+        assert_eq!(
+            names(search.candidates(&name("web.ns.svc"))),
+            vec!["web.ns.svc."]
+        );
+        assert_eq!(names(search.candidates(&name("web."))), vec!["web."]);
+    }
+
+    #[test]
+    fn refine_uses_first_resolved_candidate() {
+        let dns = Arc::new(MockDns::default().with_a("web.svc.cluster.local.", [10, 0, 0, 1]));
+        let resolver = Resolver::new(dns.clone(), cluster_search(5));
+
+        let refine = resolver.refine(&name("web")).wait().expect("must refine");
+        assert_eq!(refine.name, name("web.svc.cluster.local."));
+        assert_eq!(
+            dns.queries(),
+            vec!["web.ns.svc.cluster.local.", "web.svc.cluster.local."]
+        );
+    }
+
+    #[test]
+    fn absolute_names_skip_search() {
+        let dns = Arc::new(MockDns::default().with_a("web.ns.svc.cluster.local.", [10, 0, 0, 1]));
+        let resolver = Resolver::new(dns.clone(), cluster_search(5));
+
+        let ip = resolver
+            .resolve_one_ip(&name("web.ns.svc.cluster.local."))
+            .wait()
+            .expect("must resolve");
+        assert_eq!(ip, net::IpAddr::from([10, 0, 0, 1]));
+        assert_eq!(dns.queries(), vec!["web.ns.svc.cluster.local."]);
+    }
+
+    #[test]
+    fn unresolved_names_try_every_candidate() {
+        let dns = Arc::new(MockDns::default());
+        let resolver = Resolver::new(dns.clone(), cluster_search(5));
+
+        match resolver.resolve_one_ip(&name("db")).wait() {
+            Err(Error::NoRecordsFound { .. }) => {}
+            res => panic!("unexpected result: {:?}", res),
+        }
+        assert_eq!(
+            dns.queries(),
+            vec!["db.ns.svc.cluster.local.", "db.svc.cluster.local.", "db."]
+        );
+    }
+
+    #[test]
+    fn overrides_replace_system_config() {
+        struct Overrides;
+        impl ConfigureResolver for Overrides {
+            fn configure_resolver(&self, opts: &mut ResolverOpts) {
+                opts.ndots = 2;
+            }
+
+            fn nameservers(&self) -> Option<Vec<net::SocketAddr>> {
+                Some(vec![
+                    ([10, 0, 0, 53], 53).into(),
+                    ([10, 0, 0, 54], 5353).into(),
+                ])
+            }
+
+            fn search(&self) -> Option<Vec<Name>> {
+                Some(vec![name("ns.svc.cluster.local")])
+            }
+        }
+
+        let (config, opts, search) = configure(&Overrides, || -> Result<_, ResolveError> {
+            panic!("system configuration must not be read")
+        })
+        .expect("must configure");
+
+        let nameservers = Overrides.nameservers().unwrap();
+        assert!(!config.name_servers().is_empty());
+        for ns in config.name_servers() {
+            assert!(nameservers.contains(&ns.socket_addr));
+        }
+        for addr in &nameservers {
+            assert!(config
+                .name_servers()
+                .iter()
+                .any(|ns| ns.socket_addr == *addr));
+        }
+        // Trust-DNS must not apply search domains itself.
+        assert!(config.search().is_empty());
+
+        assert_eq!(opts.ndots, 2);
+        assert_eq!(
+            names(search.candidates(&name("web"))),
+            vec!["web.ns.svc.cluster.local.", "web."]
+        );
+        assert_eq!(names(search.candidates(&name("a.b.c"))), vec!["a.b.c."]);
+    }
 }
@@ -0,0 +1,120 @@
+use super::{DnsResolver, Error, Lookup, LookupFuture, Name};
+use futures::{Async, Future, Poll};
+use std::convert::TryFrom;
+use std::sync::Arc;
+use std::vec;
+use tracing::trace;
+
+/// Determines which fully-qualified names are queried for a name, as
+/// described in resolv.conf(5).
+#[derive(Clone, Debug, Default)]
+pub struct SearchPath {
+    domains: Vec<Name>,
+    ndots: usize,
+}
+
+/// Queries each candidate name in turn until one has records.
+pub(crate) struct Search<T> {
+    dns: Arc<dyn DnsResolver>,
+    lookup: fn(&Arc<dyn DnsResolver>, &Name) -> LookupFuture<T>,
+    candidates: vec::IntoIter<Name>,
+    current: Option<(Name, LookupFuture<T>)>,
+    error: Option<Error>,
+}
+
+// === impl SearchPath ===
+
+impl SearchPath {
+    pub fn new(domains: Vec<Name>, ndots: usize) -> Self {
+        Self { domains, ndots }
+    }
+
+    /// Returns whether `name` is queried as-is, without search domains.
+    ///
+    /// This is the case for names with a trailing dot or with at least
+    /// `ndots` dots.
+    pub fn is_absolute(&self, name: &Name) -> bool {
+        let name: &str = name.as_ref();
+        name.ends_with('.') || name.matches('.').count() >= self.ndots
+    }
+
+    /// Returns the fully-qualified names to query for `name`, in order.
+    ///
+    /// Unless `name` is absolute, it is first qualified by each of the search
+    /// domains and then tried on its own.
+    pub fn candidates(&self, name: &Name) -> Vec<Name> {
+        let base = name.without_trailing_dot();
+        let mut candidates = if self.is_absolute(name) {
+            vec![]
+        } else {
+            self.domains
+                .iter()
+                .filter_map(|d| fqdn(&format!("{}.{}", base, d.without_trailing_dot())))
+                .collect()
+        };
+        candidates.extend(fqdn(base));
+        candidates
+    }
+}
+
+fn fqdn(name: &str) -> Option<Name> {
+    Name::try_from(format!("{}.", name).as_bytes()).ok()
+}
+
+// === impl Search ===
+
+impl<T> Search<T> {
+    pub(crate) fn new(
+        dns: Arc<dyn DnsResolver>,
+        lookup: fn(&Arc<dyn DnsResolver>, &Name) -> LookupFuture<T>,
+        candidates: Vec<Name>,
+    ) -> Self {
+        Self {
+            dns,
+            lookup,
+            candidates: candidates.into_iter(),
+            current: None,
+            error: None,
+        }
+    }
+}
+
+impl<T> Future for Search<T> {
+    type Item = (Name, Lookup<T>);
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        loop {
+            if let Some((ref name, ref mut future)) = self.current {
+                match future.poll() {
+                    Ok(Async::NotReady) => return Ok(Async::NotReady),
+                    Ok(Async::Ready(lookup)) => {
+                        if !lookup.records.is_empty() {
+                            return Ok(Async::Ready((name.clone(), lookup)));
+                        }
+                        trace!(%name, "no records");
+                        self.error = Some(Error::NoRecordsFound {
+                            valid_until: Some(lookup.valid_until),
+                        });
+                    }
+                    Err(e) => {
+                        trace!(%name, error = %e);
+                        self.error = Some(e);
+                    }
+                }
+            }
+
+            // Fall through to the next candidate name, if there is one.
+            self.current = match self.candidates.next() {
+                Some(name) => {
+                    let future = (self.lookup)(&self.dns, &name);
+                    Some((name, future))
+                }
+                None => {
+                    let error = self.error.take();
+                    return Err(error.unwrap_or(Error::NoRecordsFound { valid_until: None }));
+                }
+            };
+        }
+    }
+}
@@ -0,0 +1,385 @@
+//! A middleware that serializes access to a shared inner service.
+//!
+//! Each clone of a `Lock` contends for the inner service. A clone acquires
+//! the lock in `poll_ready` and holds it until it dispatches a request (or is
+//! dropped). Clones that wait for the lock acquire it in the order in which
+//! they began waiting, so that no clone is starved under contention.
+//!
+//! If the inner service fails while the lock is held, the error is returned
+//! to the holder and to every clone that is waiting for the lock. The lock is
+//! then released, so that later acquisitions retry the inner service rather
+//! than observing the failure indefinitely.
+
+#![deny(warnings, rust_2018_idioms)]
+
+use futures::{future, task, Async, Future, Poll};
+use linkerd2_error::Error;
+use std::collections::VecDeque;
+use std::fmt;
+use std::sync::{Arc, Mutex, MutexGuard};
+use tracing::trace;
+
+pub fn layer() -> Layer {
+    Layer(())
+}
+
+#[derive(Clone, Debug)]
+pub struct Layer(());
+
+/// Guards access to an inner service that is shared by all of its clones.
+pub struct Lock<S> {
+    id: usize,
+    shared: Arc<Mutex<Shared<S>>>,
+    /// The inner service, while this clone holds the lock.
+    locked: Option<S>,
+}
+
+/// The error of an inner service that failed while the lock was held.
+#[derive(Clone, Debug)]
+pub struct ServiceError(Arc<Error>);
+
+struct Shared<S> {
+    /// The inner service, while the lock is not held.
+    service: Option<S>,
+    /// The clones that are waiting for the lock, in the order in which they
+    /// began waiting.
+    waiters: VecDeque<Waiter>,
+    next_id: usize,
+}
+
+struct Waiter {
+    id: usize,
+    task: task::Task,
+    /// Set when the inner service fails while this clone is waiting.
+    error: Option<ServiceError>,
+}
+
+// === impl Layer ===
+
+impl<S> tower::layer::Layer<S> for Layer {
+    type Service = Lock<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Lock::new(inner)
+    }
+}
+
+// === impl Lock ===
+
+impl<S> Lock<S> {
+    pub fn new(service: S) -> Self {
+        Self {
+            id: 0,
+            shared: Arc::new(Mutex::new(Shared {
+                service: Some(service),
+                waiters: VecDeque::new(),
+                next_id: 1,
+            })),
+            locked: None,
+        }
+    }
+}
+
+impl<S> Clone for Lock<S> {
+    fn clone(&self) -> Self {
+        let id = {
+            let mut shared = lock(&self.shared);
+            let id = shared.next_id;
+            shared.next_id += 1;
+            id
+        };
+        Self {
+            id,
+            shared: self.shared.clone(),
+            locked: None,
+        }
+    }
+}
+
+impl<S, Req> tower::Service<Req> for Lock<S>
+where
+    S: tower::Service<Req>,
+    S::Error: Into<Error>,
+{
+    type Response = S::Response;
+    type Error = Error;
+    type Future = future::MapErr<S::Future, fn(S::Error) -> Error>;
+
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        if self.locked.is_none() {
+            let mut shared = lock(&self.shared);
+            if let Some(error) = shared.take_error(self.id) {
+                trace!(id = self.id, "inner service failed while waiting");
+                return Err(error.into());
+            }
+            match shared.acquire(self.id) {
+                Some(service) => self.locked = Some(service),
+                None => return Ok(Async::NotReady),
+            }
+        }
+
+        let ready = self
+            .locked
+            .as_mut()
+            .expect("lock must be held")
+            .poll_ready();
+        match ready {
+            Ok(ready) => Ok(ready),
+            Err(e) => {
+                let error = ServiceError(Arc::new(e.into()));
+                let service = self.locked.take().expect("lock must be held");
+                let mut shared = lock(&self.shared);
+                shared.fail(&error);
+                shared.release(service);
+                Err(error.into())
+            }
+        }
+    }
+
+    fn call(&mut self, req: Req) -> Self::Future {
+        let mut service = self
+            .locked
+            .take()
+            .expect("poll_ready must be called before call");
+        let future = service.call(req);
+        lock(&self.shared).release(service);
+        future.map_err(Into::into)
+    }
+}
+
+impl<S> Drop for Lock<S> {
+    fn drop(&mut self) {
+        let mut shared = lock(&self.shared);
+        if let Some(idx) = shared.waiters.iter().position(|w| w.id == self.id) {
+            shared.waiters.remove(idx);
+        }
+        match self.locked.take() {
+            Some(service) => shared.release(service),
+            // If this clone was notified that the lock was released, the
+            // next waiter must be notified in its place.
+            None if shared.service.is_some() => shared.notify_next(),
+            None => {}
+        }
+    }
+}
+
+impl<S> fmt::Debug for Lock<S> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Lock")
+            .field("id", &self.id)
+            .field("locked", &self.locked.is_some())
+            .finish()
+    }
+}
+
+/// Locks the shared state, even if another thread panicked while holding it,
+/// since the state is consistent between operations.
+fn lock<S>(shared: &Mutex<Shared<S>>) -> MutexGuard<'_, Shared<S>> {
+    shared.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+// === impl Shared ===
+
+impl<S> Shared<S> {
+    /// Takes the inner service if `id` is the next waiter; otherwise, `id`
+    /// waits to be notified.
+    fn acquire(&mut self, id: usize) -> Option<S> {
+        let is_next = self.next_waiter().map(|w| w.id == id).unwrap_or(true);
+        if is_next {
+            if let Some(service) = self.service.take() {
+                if let Some(idx) = self.waiters.iter().position(|w| w.id == id) {
+                    self.waiters.remove(idx);
+                }
+                trace!(%id, "acquired");
+                return Some(service);
+            }
+        }
+
+        match self.waiters.iter_mut().find(|w| w.id == id) {
+            Some(waiter) => waiter.task = task::current(),
+            None => {
+                trace!(%id, waiters = self.waiters.len(), "waiting");
+                self.waiters.push_back(Waiter {
+                    id,
+                    task: task::current(),
+                    error: None,
+                });
+            }
+        }
+        None
+    }
+
+    fn release(&mut self, service: S) {
+        self.service = Some(service);
+        self.notify_next();
+    }
+
+    /// Fails all current waiters with `error`.
+    fn fail(&mut self, error: &ServiceError) {
+        for waiter in self.waiters.iter_mut().filter(|w| w.error.is_none()) {
+            waiter.error = Some(error.clone());
+            waiter.task.notify();
+        }
+    }
+
+    fn take_error(&mut self, id: usize) -> Option<ServiceError> {
+        let idx = self
+            .waiters
+            .iter()
+            .position(|w| w.id == id && w.error.is_some())?;
+        self.waiters.remove(idx).and_then(|w| w.error)
+    }
+
+    /// Returns the longest-waiting clone that has not failed.
+    fn next_waiter(&self) -> Option<&Waiter> {
+        self.waiters.iter().find(|w| w.error.is_none())
+    }
+
+    fn notify_next(&self) {
+        if let Some(waiter) = self.next_waiter() {
+            waiter.task.notify();
+        }
+    }
+}
+
+// === impl ServiceError ===
+
+impl fmt::Display for ServiceError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.0, f)
+    }
+}
+
+impl std::error::Error for ServiceError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&**self.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::executor::{self, Notify};
+    use tower::Service;
+
+    /// An inner service that fails readiness once with the configured error.
+    #[derive(Clone, Default)]
+    struct Mock(Arc<Mutex<Option<&'static str>>>);
+
+    impl Service<()> for Mock {
+        type Response = ();
+        type Error = Error;
+        type Future = future::FutureResult<(), Error>;
+
+        fn poll_ready(&mut self) -> Poll<(), Error> {
+            match self.0.lock().unwrap().take() {
+                Some(msg) => Err(msg.into()),
+                None => Ok(Async::Ready(())),
+            }
+        }
+
+        fn call(&mut self, _: ()) -> Self::Future {
+            future::ok(())
+        }
+    }
+
+    /// Records the IDs of the tasks that are notified.
+    #[derive(Default)]
+    struct Notified(Mutex<Vec<usize>>);
+
+    impl Notify for Notified {
+        fn notify(&self, id: usize) {
+            self.0.lock().unwrap().push(id);
+        }
+    }
+
+    impl Notified {
+        fn ids(&self) -> Vec<usize> {
+            self.0.lock().unwrap().clone()
+        }
+    }
+
+    /// Polls `lock` for readiness on a task that is identified by `id`.
+    fn poll_ready(lock: &mut Lock<Mock>, notified: &Arc<Notified>, id: usize) -> Poll<(), Error> {
+        executor::spawn(future::poll_fn(|| lock.poll_ready())).poll_future_notify(notified, id)
+    }
+
+    #[test]
+    fn waiters_acquire_in_fifo_order() {
+        let notified = Arc::new(Notified::default());
+        let mut a = Lock::new(Mock::default());
+        let mut b = a.clone();
+        let mut c = a.clone();
+        let mut d = a.clone();
+
+        assert!(poll_ready(&mut a, &notified, 0).unwrap().is_ready());
+        assert!(poll_ready(&mut b, &notified, 1).unwrap().is_not_ready());
+        assert!(poll_ready(&mut c, &notified, 2).unwrap().is_not_ready());
+        assert!(poll_ready(&mut d, &notified, 3).unwrap().is_not_ready());
+
+        a.call(()).wait().unwrap();
+        assert_eq!(notified.ids(), vec![1]);
+        assert!(
+            poll_ready(&mut d, &notified, 3).unwrap().is_not_ready(),
+            "d must wait for b and c"
+        );
+        assert!(
+            poll_ready(&mut c, &notified, 2).unwrap().is_not_ready(),
+            "c must wait for b"
+        );
+        assert!(poll_ready(&mut b, &notified, 1).unwrap().is_ready());
+
+        b.call(()).wait().unwrap();
+        assert_eq!(notified.ids(), vec![1, 2]);
+        assert!(poll_ready(&mut d, &notified, 3).unwrap().is_not_ready());
+        assert!(poll_ready(&mut c, &notified, 2).unwrap().is_ready());
+
+        c.call(()).wait().unwrap();
+        assert_eq!(notified.ids(), vec![1, 2, 3]);
+        assert!(poll_ready(&mut d, &notified, 3).unwrap().is_ready());
+    }
+
+    #[test]
+    fn recovers_after_inner_error() {
+        let notified = Arc::new(Notified::default());
+        let mock = Mock::default();
+        let mut a = Lock::new(mock.clone());
+        let mut b = a.clone();
+
+        assert!(poll_ready(&mut a, &notified, 0).unwrap().is_ready());
+        assert!(poll_ready(&mut b, &notified, 1).unwrap().is_not_ready());
+
+        // The inner service fails while `a` holds the lock.
+        *mock.0.lock().unwrap() = Some("boom");
+        let e = poll_ready(&mut a, &notified, 0).expect_err("holder must fail");
+        assert_eq!(e.to_string(), "boom");
+
+        // The waiter is notified and fails with the same error.
+        assert_eq!(notified.ids(), vec![1]);
+        let e = poll_ready(&mut b, &notified, 1).expect_err("waiter must fail");
+        assert_eq!(e.to_string(), "boom");
+
+        // The lock is not poisoned: the next acquisition retries the inner
+        // service.
+        assert!(poll_ready(&mut b, &notified, 1).unwrap().is_ready());
+        b.call(()).wait().unwrap();
+        assert!(poll_ready(&mut a, &notified, 0).unwrap().is_ready());
+    }
+
+    #[test]
+    fn dropped_waiters_do_not_block_others() {
+        let notified = Arc::new(Notified::default());
+        let mut a = Lock::new(Mock::default());
+        let mut b = a.clone();
+        let mut c = a.clone();
+
+        assert!(poll_ready(&mut a, &notified, 0).unwrap().is_ready());
+        assert!(poll_ready(&mut b, &notified, 1).unwrap().is_not_ready());
+        assert!(poll_ready(&mut c, &notified, 2).unwrap().is_not_ready());
+
+        a.call(()).wait().unwrap();
+        drop(b);
+        assert_eq!(notified.ids(), vec![1, 2]);
+        assert!(poll_ready(&mut c, &notified, 2).unwrap().is_ready());
+    }
+}
@@ -0,0 +1,118 @@
+//! Compares the time taken to scrape endpoint series whose labels are
+//! formatted on each scrape with series whose labels are prerendered.
+//!
+//! Run with `cargo bench -p linkerd2-metrics`.
+
+#![deny(warnings, rust_2018_idioms)]
+
+use indexmap::IndexMap;
+use linkerd2_metrics::{latency, Counter, FmtLabels, FmtMetric, Histogram, Prerendered};
+use std::fmt::{self, Write};
+use std::time::{Duration, Instant};
+
+const SERIES: usize = 5_000;
+const SCRAPES: u32 = 20;
+
+/// Resembles the labels of an outbound endpoint series.
+struct EndpointLabels {
+    authority: String,
+    dst: IndexMap<String, String>,
+    tls_id: Option<String>,
+}
+
+/// The metrics recorded for each series.
+struct Series<L> {
+    labels: L,
+    total: Counter,
+    latency: Histogram<latency::Ms>,
+}
+
+struct Scrape<'a, L>(&'a [Series<L>]);
+
+impl FmtLabels for EndpointLabels {
+    fn fmt_labels(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "direction=\"outbound\",authority=\"{}\"", self.authority)?;
+        for (k, v) in &self.dst {
+            write!(f, ",dst_{}=\"{}\"", k, v)?;
+        }
+        match self.tls_id {
+            Some(ref id) => write!(f, ",tls=\"true\",server_id=\"{}\"", id),
+            None => f.write_str(",tls=\"no_identity\""),
+        }
+    }
+}
+
+impl<'a, L: FmtLabels> fmt::Display for Scrape<'a, L> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for s in self.0 {
+            s.total.fmt_metric_labeled(f, "request_total", &s.labels)?;
+        }
+        for s in self.0 {
+            s.latency
+                .fmt_metric_labeled(f, "response_latency_ms", &s.labels)?;
+        }
+        Ok(())
+    }
+}
+
+fn endpoint_labels(i: usize) -> EndpointLabels {
+    let mut dst = IndexMap::new();
+    dst.insert("namespace".to_owned(), "emojivoto".to_owned());
+    dst.insert("service".to_owned(), format!("web-{}", i % 50));
+    dst.insert("deployment".to_owned(), format!("web-{}", i % 50));
+    dst.insert(
+        "pod".to_owned(),
+        format!("web-{}-7d9f8c6b5-x{:04}", i % 50, i),
+    );
+    dst.insert("pod_template_hash".to_owned(), "7d9f8c6b5".to_owned());
+    dst.insert("serviceaccount".to_owned(), "web".to_owned());
+    EndpointLabels {
+        authority: format!("web-{}.emojivoto.svc.cluster.local:8080", i % 50),
+        dst,
+        tls_id: Some("web.emojivoto.serviceaccount.identity.linkerd.cluster.local".to_owned()),
+    }
+}
+
+fn series<L>(labels: impl Fn(usize) -> L) -> Vec<Series<L>> {
+    (0..SERIES)
+        .map(|i| {
+            let mut total = Counter::default();
+            total += i as u64;
+            let mut latency = Histogram::default();
+            latency.add(Duration::from_millis((i % 1_000) as u64));
+            Series {
+                labels: labels(i),
+                total,
+                latency,
+            }
+        })
+        .collect()
+}
+
+/// Returns the mean time taken to render a scrape, along with its output.
+fn bench<L: FmtLabels>(series: &[Series<L>]) -> (Duration, String) {
+    let mut out = String::new();
+    let start = Instant::now();
+    for _ in 0..SCRAPES {
+        out.clear();
+        write!(out, "{}", Scrape(series)).expect("scrape must render");
+    }
+    (start.elapsed() / SCRAPES, out)
+}
+
+fn main() {
+    let formatted = series(endpoint_labels);
+    let prerendered = series(|i| Prerendered::new(&endpoint_labels(i)));
+
+    let (formatted_time, formatted_out) = bench(&formatted);
+    let (prerendered_time, prerendered_out) = bench(&prerendered);
+    assert_eq!(formatted_out, prerendered_out, "scrapes must be identical");
+
+    println!(
+        "scrape of {} series ({} bytes): formatted labels {:?}, prerendered labels {:?}",
+        SERIES,
+        formatted_out.len(),
+        formatted_time,
+        prerendered_time,
+    );
+}
@@ -5,8 +5,11 @@ use std::{cmp, iter, slice};
 use super::{Counter, FmtLabels, FmtMetric};
 
 /// A series of latency values and counts.
+///
+/// Values are recorded as `u64`s and reported according to `F`, so that, for
+/// instance, values recorded in milliseconds may be reported in seconds.
 #[derive(Debug, Clone)]
-pub struct Histogram<V: Into<u64>> {
+pub struct Histogram<V: Into<u64>, F = ()> {
     bounds: &'static Bounds,
     buckets: Box<[Counter]>,
 
@@ -32,7 +35,12 @@ pub struct Histogram<V: Into<u64>> {
     //       bits.
     sum: Counter,
 
-    _p: PhantomData<V>,
+    _p: PhantomData<(V, F)>,
+}
+
+/// Determines how a histogram's bucket bounds and sum are reported.
+pub trait Factor {
+    fn fmt_value(value: u64, f: &mut fmt::Formatter<'_>) -> fmt::Result;
 }
 
 #[derive(Debug, Eq, PartialEq, Copy, Clone)]
@@ -51,9 +59,15 @@ struct Key<A: fmt::Display, B: fmt::Display>(A, B);
 /// Helper that lazily formats an `{K}="{V}"`" label.
 struct Label<K: fmt::Display, V: fmt::Display>(K, V);
 
+/// Helper that formats a value according to the factor `F`.
+struct Value<F>(u64, PhantomData<F>);
+
+/// Helper that formats a bucket's upper bound according to the factor `F`.
+struct Le<'a, F>(&'a Bucket, PhantomData<F>);
+
 // ===== impl Histogram =====
 
-impl<V: Into<u64>> Histogram<V> {
+impl<V: Into<u64>, F> Histogram<V, F> {
     pub fn new(bounds: &'static Bounds) -> Self {
         let mut buckets = Vec::with_capacity(bounds.0.len());
         let mut prior = &Bucket::Le(0);
@@ -168,7 +182,7 @@ impl<V: Into<u64>> Histogram<V> {
     }
 }
 
-impl<'a, V: Into<u64>> IntoIterator for &'a Histogram<V> {
+impl<'a, V: Into<u64>, F> IntoIterator for &'a Histogram<V, F> {
     type Item = (&'a Bucket, &'a Counter);
     type IntoIter = iter::Zip<slice::Iter<'a, Bucket>, slice::Iter<'a, Counter>>;
 
@@ -177,17 +191,17 @@ impl<'a, V: Into<u64>> IntoIterator for &'a Histogram<V> {
     }
 }
 
-impl<V: Into<u64>> FmtMetric for Histogram<V> {
+impl<V: Into<u64>, F: Factor> FmtMetric for Histogram<V, F> {
     const KIND: &'static str = "histogram";
 
     fn fmt_metric<N: fmt::Display>(&self, f: &mut fmt::Formatter<'_>, name: N) -> fmt::Result {
         let mut total = Counter::default();
         for (le, count) in self {
             total += *count;
-            total.fmt_metric_labeled(f, Key(&name, "bucket"), Label("le", le))?;
+            total.fmt_metric_labeled(f, Key(&name, "bucket"), Label("le", Le::<F>::new(le)))?;
         }
         total.fmt_metric(f, Key(&name, "count"))?;
-        self.sum.fmt_metric(f, Key(&name, "sum"))?;
+        writeln!(f, "{} {}", Key(&name, "sum"), Value::<F>::new(self.sum))?;
 
         Ok(())
     }
@@ -205,15 +219,58 @@ impl<V: Into<u64>> FmtMetric for Histogram<V> {
         let mut total = Counter::default();
         for (le, count) in self {
             total += *count;
-            total.fmt_metric_labeled(f, Key(&name, "bucket"), (&labels, Label("le", le)))?;
+            let le = Label("le", Le::<F>::new(le));
+            total.fmt_metric_labeled(f, Key(&name, "bucket"), (&labels, le))?;
         }
         total.fmt_metric_labeled(f, Key(&name, "count"), &labels)?;
-        self.sum.fmt_metric_labeled(f, Key(&name, "sum"), &labels)?;
+        write!(f, "{}{{", Key(&name, "sum"))?;
+        labels.fmt_labels(f)?;
+        writeln!(f, "}} {}", Value::<F>::new(self.sum))?;
 
         Ok(())
     }
 }
 
+// ===== impl Factor =====
+
+/// Reports values as they are recorded.
+impl Factor for () {
+    fn fmt_value(value: u64, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", value)
+    }
+}
+
+// ===== impl Value =====
+
+impl<F> Value<F> {
+    fn new(value: Counter) -> Self {
+        Value(value.into(), PhantomData)
+    }
+}
+
+impl<F: Factor> fmt::Display for Value<F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        F::fmt_value(self.0, f)
+    }
+}
+
+// ===== impl Le =====
+
+impl<'a, F> Le<'a, F> {
+    fn new(bucket: &'a Bucket) -> Self {
+        Le(bucket, PhantomData)
+    }
+}
+
+impl<'a, F: Factor> fmt::Display for Le<'a, F> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match *self.0 {
+            Bucket::Le(v) => F::fmt_value(v, f),
+            Bucket::Inf => write!(f, "+Inf"),
+        }
+    }
+}
+
 // ===== impl Key =====
 
 impl<A: fmt::Display, B: fmt::Display> fmt::Display for Key<A, B> {
@@ -338,6 +395,32 @@ mod tests {
         Bucket::Inf,
     ]);
 
+    #[test]
+    fn millis_are_reported_as_seconds() {
+        use crate::latency::{self, MillisAsSeconds};
+        use std::time::Duration;
+
+        struct Fmt<'a>(&'a Histogram<latency::Ms, MillisAsSeconds>);
+
+        impl fmt::Display for Fmt<'_> {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                self.0.fmt_metric(f, "wait_seconds")
+            }
+        }
+
+        let mut hist = Histogram::<latency::Ms, MillisAsSeconds>::new(latency::BOUNDS);
+        hist.add(Duration::from_millis(50));
+        hist.add(Duration::from_millis(1_500));
+
+        let out = Fmt(&hist).to_string();
+        assert!(out.contains("wait_seconds_bucket{le=\"0.001\"} 0\n"));
+        assert!(out.contains("wait_seconds_bucket{le=\"0.05\"} 1\n"));
+        assert!(out.contains("wait_seconds_bucket{le=\"2\"} 2\n"));
+        assert!(out.contains("wait_seconds_bucket{le=\"+Inf\"} 2\n"));
+        assert!(out.contains("wait_seconds_count 2\n"));
+        assert!(out.contains("wait_seconds_sum 1.55\n"));
+    }
+
     quickcheck! {
         fn bucket_incremented(obs: u64) -> bool {
             let mut hist = Histogram::<u64>::new(&BOUNDS);
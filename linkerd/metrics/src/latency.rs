@@ -1,6 +1,7 @@
+use std::fmt;
 use std::time::Duration;
 
-use super::histogram::{Bounds, Bucket, Histogram};
+use super::histogram::{Bounds, Bucket, Factor, Histogram};
 
 /// The maximum value (inclusive) for each latency bucket in
 /// milliseconds.
@@ -38,6 +39,11 @@ pub const BOUNDS: &Bounds = &Bounds(&[
 #[derive(Debug, Default, Clone)]
 pub struct Ms(Duration);
 
+/// Reports a histogram of durations that are recorded in milliseconds in
+/// seconds, e.g. `le="0.05"` for a 50ms bucket.
+#[derive(Copy, Clone, Debug, Default)]
+pub struct MillisAsSeconds;
+
 /// A duration in microseconds.
 #[derive(Debug, Default, Clone)]
 pub struct Us(Duration);
@@ -85,3 +91,9 @@ impl Default for Histogram<Ms> {
         Histogram::new(BOUNDS)
     }
 }
+
+impl Factor for MillisAsSeconds {
+    fn fmt_value(value: u64, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", value as f64 / 1_000.0)
+    }
+}
@@ -6,14 +6,16 @@ mod counter;
 mod gauge;
 mod histogram;
 pub mod latency;
+mod named;
 mod prom;
 mod scopes;
 mod serve;
 
 pub use self::counter::Counter;
 pub use self::gauge::Gauge;
-pub use self::histogram::Histogram;
-pub use self::prom::{FmtLabels, FmtMetric, FmtMetrics, Metric};
+pub use self::histogram::{Factor, Histogram};
+pub use self::named::{NamedMetrics, NamedRegistry};
+pub use self::prom::{FmtLabels, FmtMetric, FmtMetrics, Metric, Prerendered};
 pub use self::scopes::Scopes;
 pub use self::serve::Serve;
 
@@ -0,0 +1,94 @@
+use indexmap::IndexMap;
+use std::sync::{Arc, Mutex};
+
+/// Holds an `M`-typed set of metrics for each named component, e.g. each
+/// router cache or buffer.
+///
+/// Components created with the same name share metrics, so their metrics are
+/// aggregated.
+#[derive(Debug)]
+pub struct NamedRegistry<M>(Arc<Mutex<IndexMap<&'static str, NamedMetrics<M>>>>);
+
+/// Records the metrics of a single named component.
+#[derive(Debug)]
+pub struct NamedMetrics<M>(Arc<Mutex<M>>);
+
+// === impl NamedRegistry ===
+
+impl<M: Default> NamedRegistry<M> {
+    /// Returns the metrics for the component named `name`, creating them if
+    /// necessary.
+    pub fn get(&self, name: &'static str) -> NamedMetrics<M> {
+        match self.0.lock() {
+            Ok(mut named) => named
+                .entry(name)
+                .or_insert_with(NamedMetrics::default)
+                .clone(),
+            // If the registry is poisoned, metrics are recorded but not
+            // reported.
+            Err(_) => NamedMetrics::default(),
+        }
+    }
+}
+
+impl<M: Clone> NamedRegistry<M> {
+    /// Copies the metrics of each named component, in the order that the
+    /// components were first created.
+    pub fn snapshot(&self) -> Vec<(&'static str, M)> {
+        match self.0.lock() {
+            Ok(named) => named
+                .iter()
+                .filter_map(|(name, m)| m.0.lock().ok().map(|m| (*name, m.clone())))
+                .collect(),
+            Err(_) => Vec::new(),
+        }
+    }
+}
+
+impl<M> Clone for NamedRegistry<M> {
+    fn clone(&self) -> Self {
+        NamedRegistry(self.0.clone())
+    }
+}
+
+impl<M> Default for NamedRegistry<M> {
+    fn default() -> Self {
+        NamedRegistry(Arc::new(Mutex::new(IndexMap::new())))
+    }
+}
+
+// === impl NamedMetrics ===
+
+impl<M> NamedMetrics<M> {
+    pub fn update(&self, f: impl FnOnce(&mut M)) {
+        if let Ok(mut m) = self.0.lock() {
+            f(&mut *m);
+        }
+    }
+}
+
+impl<M> Clone for NamedMetrics<M> {
+    fn clone(&self) -> Self {
+        NamedMetrics(self.0.clone())
+    }
+}
+
+impl<M: Default> Default for NamedMetrics<M> {
+    fn default() -> Self {
+        NamedMetrics(Arc::new(Mutex::new(M::default())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn components_with_the_same_name_share_metrics() {
+        let registry = NamedRegistry::<u64>::default();
+        registry.get("a").update(|n| *n += 1);
+        registry.get("b").update(|n| *n += 1);
+        registry.get("a").update(|n| *n += 1);
+        assert_eq!(registry.snapshot(), vec![("a", 2), ("b", 1)]);
+    }
+}
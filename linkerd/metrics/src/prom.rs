@@ -1,5 +1,6 @@
 use std::fmt;
 use std::marker::{PhantomData, Sized};
+use std::sync::Arc;
 
 /// Writes a block of metrics in prometheus-formatted output.
 pub trait FmtMetrics {
@@ -38,6 +39,14 @@ pub trait FmtLabels {
     fn fmt_labels(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result;
 }
 
+/// Labels that are rendered once, when a series is created, so that they need
+/// not be formatted again each time the series is written.
+///
+/// A series' labels are immutable, so the rendered labels are valid for the
+/// lifetime of the series.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Prerendered(Arc<str>);
+
 /// Writes a metric in prometheus-formatted output.
 ///
 /// This trait is implemented by `Counter`, `Gauge`, and `Histogram` to account for the
@@ -114,6 +123,32 @@ impl<'a, M: FmtMetric> Metric<'a, M> {
     }
 }
 
+// ===== impl Prerendered =====
+
+impl Prerendered {
+    pub fn new<L: FmtLabels>(labels: &L) -> Self {
+        struct Render<'l, L>(&'l L);
+
+        impl<'l, L: FmtLabels> fmt::Display for Render<'l, L> {
+            fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+                self.0.fmt_labels(f)
+            }
+        }
+
+        Prerendered(Render(labels).to_string().into())
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+}
+
+impl FmtLabels for Prerendered {
+    fn fmt_labels(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
 // ===== impl FmtLabels =====
 
 impl<'a, A: FmtLabels + 'a> FmtLabels for &'a A {
@@ -178,3 +213,59 @@ impl FmtMetrics for () {
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{latency, Counter, Histogram};
+
+    struct Labeled<M, L>(M, L);
+
+    impl<M: FmtMetric, L: FmtLabels> fmt::Display for Labeled<&M, &L> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            self.0.fmt_metric_labeled(f, "test_metric", self.1)
+        }
+    }
+
+    struct Kv(&'static str, &'static str);
+
+    impl FmtLabels for Kv {
+        fn fmt_labels(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(f, "{}=\"{}\"", self.0, self.1)
+        }
+    }
+
+    #[test]
+    fn prerendered_labels_are_byte_identical() {
+        let labels = (
+            Some(Kv("authority", "web.ns.svc.cluster.local:8080")),
+            (Kv("direction", "outbound"), Some(Kv("tls", "true"))),
+        );
+        let pre = Prerendered::new(&labels);
+        assert_eq!(
+            pre.as_str(),
+            "authority=\"web.ns.svc.cluster.local:8080\",direction=\"outbound\",tls=\"true\""
+        );
+
+        let mut counter = Counter::default();
+        counter += 3;
+        assert_eq!(
+            Labeled(&counter, &labels).to_string(),
+            Labeled(&counter, &pre).to_string(),
+        );
+
+        let mut hist = Histogram::<latency::Ms>::default();
+        hist.add(std::time::Duration::from_millis(12));
+        assert_eq!(
+            Labeled(&hist, &labels).to_string(),
+            Labeled(&hist, &pre).to_string(),
+        );
+
+        // Prerendered labels compose with other labels like any other.
+        let status = Kv("status_code", "200");
+        assert_eq!(
+            Labeled(&counter, &(&labels, &status)).to_string(),
+            Labeled(&counter, &(&pre, &status)).to_string(),
+        );
+    }
+}
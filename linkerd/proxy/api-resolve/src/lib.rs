@@ -8,5 +8,5 @@ mod metadata;
 mod pb;
 mod resolve;
 
-pub use self::metadata::{Metadata, ProtocolHint};
+pub use self::metadata::{Metadata, ProtocolHint, DST_OVERRIDE_PORT_LABEL};
 pub use self::resolve::Resolve;
@@ -1,5 +1,6 @@
 use crate::identity;
 use indexmap::IndexMap;
+use std::time::Duration;
 
 /// Metadata describing an endpoint.
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -24,8 +25,22 @@ pub struct Metadata {
 
     /// How to verify TLS for the endpoint.
     identity: Option<identity::Name>,
+
+    /// A port to dial instead of the endpoint's port when the connection is
+    /// meshed, e.g. when the application is exposed through a sidecar port.
+    dst_override_port: Option<u16>,
+
+    /// How long connections to the endpoint may take to be established, if
+    /// the controller provided a hint.
+    connect_timeout: Option<Duration>,
 }
 
+/// The endpoint label that carries the `dst_override_port` hint.
+pub const DST_OVERRIDE_PORT_LABEL: &str = "dst_override_port";
+
+/// The endpoint label that carries the `connect_timeout` hint, in milliseconds.
+pub const CONNECT_TIMEOUT_LABEL: &str = "connect_timeout_ms";
+
 #[derive(Clone, Copy, Debug, Eq, PartialEq)]
 pub enum ProtocolHint {
     /// We don't what the destination understands, so forward messages in the
@@ -44,6 +59,8 @@ impl Metadata {
             protocol_hint: ProtocolHint::Unknown,
             identity: None,
             weight: 10_000,
+            dst_override_port: None,
+            connect_timeout: None,
         }
     }
 
@@ -58,6 +75,24 @@ impl Metadata {
             protocol_hint,
             identity,
             weight,
+            dst_override_port: None,
+            connect_timeout: None,
+        }
+    }
+
+    /// Sets the port to dial when the connection to this endpoint is meshed.
+    pub fn with_dst_override_port(self, dst_override_port: Option<u16>) -> Self {
+        Self {
+            dst_override_port,
+            ..self
+        }
+    }
+
+    /// Sets how long connections to this endpoint may take to be established.
+    pub fn with_connect_timeout(self, connect_timeout: Option<Duration>) -> Self {
+        Self {
+            connect_timeout,
+            ..self
         }
     }
 
@@ -73,4 +108,12 @@ impl Metadata {
     pub fn identity(&self) -> Option<&identity::Name> {
         self.identity.as_ref()
     }
+
+    pub fn dst_override_port(&self) -> Option<u16> {
+        self.dst_override_port
+    }
+
+    pub fn connect_timeout(&self) -> Option<Duration> {
+        self.connect_timeout
+    }
 }
@@ -1,9 +1,9 @@
 use crate::api::destination::{protocol_hint::Protocol, TlsIdentity, WeightedAddr};
 use crate::api::net::TcpAddress;
 use crate::identity;
-use crate::metadata::{Metadata, ProtocolHint};
+use crate::metadata::{Metadata, ProtocolHint, CONNECT_TIMEOUT_LABEL, DST_OVERRIDE_PORT_LABEL};
 use indexmap::IndexMap;
-use std::{collections::HashMap, net::SocketAddr};
+use std::{collections::HashMap, net::SocketAddr, time::Duration};
 
 /// Construct a new labeled `SocketAddr `from a protobuf `WeightedAddr`.
 pub(in crate) fn to_addr_meta(
@@ -12,10 +12,27 @@ pub(in crate) fn to_addr_meta(
 ) -> Option<(SocketAddr, Metadata)> {
     let addr = pb.addr.and_then(to_sock_addr)?;
 
+    // The override port and connect timeout are dialing hints rather than
+    // metric labels.
+    let dst_override_port = pb
+        .metric_labels
+        .get(DST_OVERRIDE_PORT_LABEL)
+        .or_else(|| set_labels.get(DST_OVERRIDE_PORT_LABEL))
+        .and_then(|port| port.parse::<u16>().ok());
+    let connect_timeout = pb
+        .metric_labels
+        .get(CONNECT_TIMEOUT_LABEL)
+        .or_else(|| set_labels.get(CONNECT_TIMEOUT_LABEL))
+        .and_then(|ms| ms.parse::<u64>().ok())
+        .map(Duration::from_millis);
+
     let meta = {
         let mut t = set_labels
             .iter()
             .chain(pb.metric_labels.iter())
+            .filter(|(k, _)| {
+                k.as_str() != DST_OVERRIDE_PORT_LABEL && k.as_str() != CONNECT_TIMEOUT_LABEL
+            })
             .collect::<Vec<(&String, &String)>>();
         t.sort_by(|(k0, _), (k1, _)| k0.cmp(k1));
 
@@ -39,7 +56,9 @@ pub(in crate) fn to_addr_meta(
     }
 
     let tls_id = pb.tls_identity.and_then(to_id);
-    let meta = Metadata::new(meta, proto_hint, tls_id, pb.weight);
+    let meta = Metadata::new(meta, proto_hint, tls_id, pb.weight)
+        .with_dst_override_port(dst_override_port)
+        .with_connect_timeout(connect_timeout);
     Some((addr, meta))
 }
 
@@ -0,0 +1,98 @@
+//! Measures the time taken to pick a traffic split's backend for a request,
+//! with and without concurrent weight-only updates, as when a canary's
+//! weight is ramped up.
+//!
+//! Run with `cargo bench -p linkerd2-proxy-http`.
+
+#![deny(warnings, rust_2018_idioms)]
+
+use linkerd2_addr::NameAddr;
+use linkerd2_proxy_http::profiles::{recognize::ConcreteDstRecognize, WeightedAddr, WithAddr};
+use linkerd2_router::Recognize;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread;
+use std::time::{Duration, Instant};
+
+const THREADS: usize = 4;
+const REQUESTS: u32 = 1_000_000;
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct Target(NameAddr);
+
+impl WithAddr for Target {
+    fn with_addr(self, addr: NameAddr) -> Self {
+        Target(addr)
+    }
+}
+
+fn split(canary_weight: u32) -> Vec<WeightedAddr> {
+    vec![
+        WeightedAddr {
+            addr: NameAddr::from_str("web.ns.svc.cluster.local:8080").unwrap(),
+            weight: 100 - canary_weight,
+        },
+        WeightedAddr {
+            addr: NameAddr::from_str("web-canary.ns.svc.cluster.local:8080").unwrap(),
+            weight: canary_weight,
+        },
+    ]
+}
+
+/// Returns the mean time taken to recognize a request on each of `THREADS`
+/// threads.
+fn bench(recognize: &ConcreteDstRecognize<Target>) -> Duration {
+    let threads = (0..THREADS)
+        .map(|_| {
+            let recognize = recognize.clone();
+            thread::spawn(move || {
+                let req = http::Request::new(());
+                let start = Instant::now();
+                for _ in 0..REQUESTS {
+                    recognize
+                        .recognize(&req)
+                        .expect("request must be recognized");
+                }
+                start.elapsed() / REQUESTS
+            })
+        })
+        .collect::<Vec<_>>();
+    let total = threads
+        .into_iter()
+        .map(|t| t.join().expect("thread must not panic"))
+        .sum::<Duration>();
+    total / THREADS as u32
+}
+
+fn main() {
+    let target = Target(NameAddr::from_str("web.ns.svc.cluster.local:8080").unwrap());
+    let recognize = ConcreteDstRecognize::new(target, split(1)).unwrap();
+    let idle = bench(&recognize);
+
+    // Ramp the canary's weight for as long as requests are recognized. Each
+    // update only changes weights, so the split is never rebuilt.
+    let done = Arc::new(AtomicBool::new(false));
+    let updater = {
+        let recognize = recognize.clone();
+        let done = done.clone();
+        thread::spawn(move || {
+            let mut updates = 0u32;
+            while !done.load(Ordering::Relaxed) {
+                let updated = recognize
+                    .update_weights(None, &split(1 + updates % 99))
+                    .expect("weights must be valid");
+                assert!(updated, "weight-only updates must not rebuild the split");
+                updates += 1;
+            }
+            updates
+        })
+    };
+    let updating = bench(&recognize);
+    done.store(true, Ordering::Relaxed);
+    let updates = updater.join().expect("updater must not panic");
+
+    println!(
+        "recognize on {} threads: {:?} without updates, {:?} during {} weight updates",
+        THREADS, idle, updating, updates,
+    );
+}
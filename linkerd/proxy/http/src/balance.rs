@@ -2,13 +2,20 @@ use crate::Error;
 use futures::{try_ready, Async, Future, Poll};
 use http;
 use hyper::body::Payload;
-pub use hyper_balance::{PendingUntilFirstData, PendingUntilFirstDataBody};
+pub use hyper_balance::{
+    PendingUntilEos, PendingUntilEosBody, PendingUntilFirstData, PendingUntilFirstDataBody,
+};
 use rand::{rngs::SmallRng, SeedableRng};
 use std::{marker::PhantomData, time::Duration};
 pub use tower_balance::p2c::Balance;
 use tower_discover::Discover;
 pub use tower_load::{Load, PeakEwmaDiscover};
 
+pub mod load_report;
+pub mod warmup;
+
+type Loaded<D> = warmup::Warmup<PeakEwmaDiscover<warmup::Record<D>, PendingUntilFirstData>>;
+
 /// Configures a stack to resolve `T` typed targets to balance requests over
 /// `M`-typed endpoint stacks.
 #[derive(Debug)]
@@ -87,15 +94,17 @@ impl<T, M, A, B> tower::Service<T> for MakeSvc<M, A, B>
 where
     M: tower::Service<T>,
     M::Response: Discover,
-    <M::Response as Discover>::Service:
-        tower::Service<http::Request<A>, Response = http::Response<B>>,
+    <M::Response as Discover>::Key: Clone,
+    <M::Response as Discover>::Service: tower::Service<http::Request<A>, Response = http::Response<B>>
+        + warmup::HasWarmup
+        + warmup::HasWeight
+        + load_report::HasLoadReport,
     <<M::Response as Discover>::Service as tower::Service<http::Request<A>>>::Error: Into<Error>,
     A: Payload,
     B: Payload,
-    Balance<PeakEwmaDiscover<M::Response, PendingUntilFirstData>, http::Request<A>>:
-        tower::Service<http::Request<A>>,
+    Balance<Loaded<M::Response>, http::Request<A>>: tower::Service<http::Request<A>>,
 {
-    type Response = Balance<PeakEwmaDiscover<M::Response, PendingUntilFirstData>, http::Request<A>>;
+    type Response = Balance<Loaded<M::Response>, http::Request<A>>;
     type Error = M::Error;
     type Future = MakeSvc<M::Future, A, B>;
 
@@ -120,20 +129,26 @@ impl<F, A, B> Future for MakeSvc<F, A, B>
 where
     F: Future,
     F::Item: Discover,
-    <F::Item as Discover>::Service: tower::Service<http::Request<A>, Response = http::Response<B>>,
+    <F::Item as Discover>::Key: Clone,
+    <F::Item as Discover>::Service: tower::Service<http::Request<A>, Response = http::Response<B>>
+        + warmup::HasWarmup
+        + warmup::HasWeight
+        + load_report::HasLoadReport,
     <<F::Item as Discover>::Service as tower::Service<http::Request<A>>>::Error: Into<Error>,
     A: Payload,
     B: Payload,
-    Balance<PeakEwmaDiscover<F::Item, PendingUntilFirstData>, http::Request<A>>:
-        tower::Service<http::Request<A>>,
+    Balance<Loaded<F::Item>, http::Request<A>>: tower::Service<http::Request<A>>,
 {
-    type Item = Balance<PeakEwmaDiscover<F::Item, PendingUntilFirstData>, http::Request<A>>;
+    type Item = Balance<Loaded<F::Item>, http::Request<A>>;
     type Error = F::Error;
 
     fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
         let discover = try_ready!(self.inner.poll());
         let instrument = PendingUntilFirstData::default();
-        let loaded = PeakEwmaDiscover::new(discover, self.default_rtt, self.decay, instrument);
+        let (default_rtt, decay) = (self.default_rtt, self.decay);
+        let loaded = warmup::discover(discover, move |discover| {
+            PeakEwmaDiscover::new(discover, default_rtt, decay, instrument)
+        });
         let balance = Balance::new(loaded, self.rng.clone());
         Ok(Async::Ready(balance))
     }
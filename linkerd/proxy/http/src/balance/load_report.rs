@@ -0,0 +1,44 @@
+//! Live load reports that bias the balancer away from busy endpoints.
+
+use tokio::sync::watch;
+
+/// An endpoint's most recently reported load.
+#[derive(Copy, Clone, Debug, Default, PartialEq)]
+pub struct LoadReport {
+    /// The fraction of the endpoint's CPU that is in use, from 0.0 to 1.0.
+    pub cpu_utilization: f64,
+    /// The number of requests per second that the endpoint is serving.
+    pub request_rate: f64,
+}
+
+/// Provides a watch on an endpoint's load reports, if they are available.
+pub trait HasLoadReport {
+    fn load_report(&self) -> Option<watch::Receiver<LoadReport>>;
+}
+
+// === impl LoadReport ===
+
+impl LoadReport {
+    /// The fraction of its traffic that an endpoint should receive given its
+    /// reported CPU utilization.
+    pub fn weight(&self) -> f64 {
+        (1.0 - self.cpu_utilization).max(0.0).min(1.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn weight_decreases_with_cpu_utilization() {
+        let report = |cpu_utilization| LoadReport {
+            cpu_utilization,
+            request_rate: 100.0,
+        };
+        assert_eq!(report(0.0).weight(), 1.0);
+        assert_eq!(report(0.75).weight(), 0.25);
+        assert_eq!(report(1.5).weight(), 0.0);
+        assert_eq!(report(-1.0).weight(), 1.0);
+    }
+}
@@ -0,0 +1,496 @@
+//! Reduces the traffic that endpoints receive while they warm up after being
+//! added to a balancer.
+//!
+//! The balancer picks the less loaded of two randomly-chosen endpoints. While
+//! an endpoint warms up, its load is reported as greater than that of any warm
+//! endpoint with probability `1 - WarmupWeight`, so that it is picked
+//! proportionally less often.
+//!
+//! Endpoints that provide load reports are weighted the same way, by the
+//! share of their CPU that is idle, so that busy endpoints are picked less
+//! often as well. Endpoints may also have a fixed weight, e.g. so that
+//! endpoints in other zones are picked less often.
+
+use super::load_report::{HasLoadReport, LoadReport};
+use futures::{try_ready, Async, Future, Poll};
+use rand::Rng;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::watch;
+use tokio_timer::clock;
+use tower_discover::{Change, Discover};
+use tower_load::Load;
+
+/// Determines how long an endpoint takes to warm up.
+pub trait HasWarmup {
+    fn warmup(&self) -> Option<Duration>;
+}
+
+/// Determines the fixed fraction of its traffic that an endpoint receives.
+pub trait HasWeight {
+    fn weight(&self) -> Option<f64>;
+}
+
+/// The fraction of its traffic that a warming endpoint receives.
+#[derive(Copy, Clone, Debug, PartialEq, PartialOrd)]
+pub struct WarmupWeight(f64);
+
+pub fn layer() -> Layer {
+    Layer(())
+}
+
+/// Annotates each endpoint service with its target's warmup.
+#[derive(Clone, Debug)]
+pub struct Layer(());
+
+#[derive(Clone, Debug)]
+pub struct MakeWarmup<M> {
+    inner: M,
+}
+
+pub struct MakeFuture<F> {
+    inner: F,
+    warmup: Option<Duration>,
+    load_report: Option<watch::Receiver<LoadReport>>,
+    weight: Option<f64>,
+}
+
+#[derive(Clone, Debug)]
+pub struct WithWarmup<S> {
+    inner: S,
+    warmup: Option<Duration>,
+    load_report: Option<watch::Receiver<LoadReport>>,
+    weight: Option<f64>,
+}
+
+/// Records the warmup and load reports of each discovered service so that it may be applied
+/// once the service has been instrumented with a load.
+pub struct Record<D: Discover> {
+    inner: D,
+    warmups: Warmups<D::Key>,
+}
+
+/// Applies the recorded warmup and load reports to each discovered service.
+pub struct Warmup<D: Discover> {
+    inner: D,
+    warmups: Warmups<D::Key>,
+}
+
+pub struct Warming<S> {
+    inner: S,
+    warmup: Option<(Instant, Duration)>,
+    load_report: Option<watch::Receiver<LoadReport>>,
+    /// The most recent load report, updated as the service is polled.
+    report: Option<LoadReport>,
+    fixed: Option<f64>,
+}
+
+struct Weighting {
+    warmup: Option<Duration>,
+    load_report: Option<watch::Receiver<LoadReport>>,
+    weight: Option<f64>,
+}
+
+type Warmups<K> = Arc<Mutex<HashMap<K, Weighting>>>;
+
+/// Wraps `discover` so that the services discovered by the result of `load`
+/// are warmed up.
+pub fn discover<D, L, F>(discover: D, load: F) -> Warmup<L>
+where
+    D: Discover,
+    L: Discover<Key = D::Key>,
+    F: FnOnce(Record<D>) -> L,
+{
+    let warmups = Warmups::default();
+    let inner = load(Record {
+        inner: discover,
+        warmups: warmups.clone(),
+    });
+    Warmup { inner, warmups }
+}
+
+// === impl WarmupWeight ===
+
+impl WarmupWeight {
+    pub fn new(elapsed: Duration, warmup: Duration) -> Self {
+        if warmup == Duration::from_secs(0) {
+            return WarmupWeight(1.0);
+        }
+        WarmupWeight((elapsed.as_secs_f64() / warmup.as_secs_f64()).min(1.0))
+    }
+
+    pub fn is_warm(&self) -> bool {
+        self.0 >= 1.0
+    }
+
+    pub fn weight(&self, weight: f64) -> f64 {
+        weight * self.0
+    }
+}
+
+// === impl Layer ===
+
+impl<M> tower::layer::Layer<M> for Layer {
+    type Service = MakeWarmup<M>;
+
+    fn layer(&self, inner: M) -> Self::Service {
+        MakeWarmup { inner }
+    }
+}
+
+// === impl MakeWarmup ===
+
+impl<T, M> tower::Service<T> for MakeWarmup<M>
+where
+    T: HasWarmup + HasLoadReport + HasWeight,
+    M: tower::Service<T>,
+{
+    type Response = WithWarmup<M::Response>;
+    type Error = M::Error;
+    type Future = MakeFuture<M::Future>;
+
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        self.inner.poll_ready()
+    }
+
+    fn call(&mut self, target: T) -> Self::Future {
+        let warmup = target.warmup();
+        let load_report = target.load_report();
+        let weight = target.weight();
+        MakeFuture {
+            inner: self.inner.call(target),
+            warmup,
+            load_report,
+            weight,
+        }
+    }
+}
+
+impl<F: Future> Future for MakeFuture<F> {
+    type Item = WithWarmup<F::Item>;
+    type Error = F::Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        let inner = try_ready!(self.inner.poll());
+        Ok(WithWarmup {
+            inner,
+            warmup: self.warmup,
+            load_report: self.load_report.take(),
+            weight: self.weight,
+        }
+        .into())
+    }
+}
+
+// === impl WithWarmup ===
+
+impl<S> HasWarmup for WithWarmup<S> {
+    fn warmup(&self) -> Option<Duration> {
+        self.warmup
+    }
+}
+
+impl<S> HasLoadReport for WithWarmup<S> {
+    fn load_report(&self) -> Option<watch::Receiver<LoadReport>> {
+        self.load_report.clone()
+    }
+}
+
+impl<S> HasWeight for WithWarmup<S> {
+    fn weight(&self) -> Option<f64> {
+        self.weight
+    }
+}
+
+impl<S, Req> tower::Service<Req> for WithWarmup<S>
+where
+    S: tower::Service<Req>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        self.inner.poll_ready()
+    }
+
+    fn call(&mut self, req: Req) -> Self::Future {
+        self.inner.call(req)
+    }
+}
+
+// === impl Record ===
+
+impl<D> Discover for Record<D>
+where
+    D: Discover,
+    D::Key: Clone,
+    D::Service: HasWarmup + HasLoadReport + HasWeight,
+{
+    type Key = D::Key;
+    type Service = D::Service;
+    type Error = D::Error;
+
+    fn poll(&mut self) -> Poll<Change<Self::Key, Self::Service>, Self::Error> {
+        let change = try_ready!(self.inner.poll());
+        let mut warmups = self.warmups.lock().expect("warmups poisoned");
+        match change {
+            Change::Insert(ref key, ref svc) => {
+                match (svc.warmup(), svc.load_report(), svc.weight()) {
+                    (None, None, None) => {
+                        warmups.remove(key);
+                    }
+                    (warmup, load_report, weight) => {
+                        let weighting = Weighting {
+                            warmup,
+                            load_report,
+                            weight,
+                        };
+                        warmups.insert(key.clone(), weighting);
+                    }
+                }
+            }
+            Change::Remove(ref key) => {
+                warmups.remove(key);
+            }
+        }
+        Ok(change.into())
+    }
+}
+
+// === impl Warmup ===
+
+impl<D> Discover for Warmup<D>
+where
+    D: Discover,
+{
+    type Key = D::Key;
+    type Service = Warming<D::Service>;
+    type Error = D::Error;
+
+    fn poll(&mut self) -> Poll<Change<Self::Key, Self::Service>, Self::Error> {
+        let change = match try_ready!(self.inner.poll()) {
+            Change::Insert(key, inner) => {
+                let weighting = self.warmups.lock().expect("warmups poisoned").remove(&key);
+                let (warmup, load_report, fixed) = match weighting {
+                    Some(Weighting {
+                        warmup,
+                        load_report,
+                        weight,
+                    }) => (warmup.map(|w| (clock::now(), w)), load_report, weight),
+                    None => (None, None, None),
+                };
+                let report = load_report.as_ref().map(|rx| *rx.get_ref());
+                Change::Insert(
+                    key,
+                    Warming {
+                        inner,
+                        warmup,
+                        load_report,
+                        report,
+                        fixed,
+                    },
+                )
+            }
+            Change::Remove(key) => Change::Remove(key),
+        };
+        Ok(change.into())
+    }
+}
+
+// === impl Warming ===
+
+impl<S> Warming<S> {
+    fn warmup_weight(&self) -> Option<WarmupWeight> {
+        let (added, warmup) = self.warmup?;
+        let elapsed = clock::now().saturating_duration_since(added);
+        Some(WarmupWeight::new(elapsed, warmup))
+    }
+
+    /// The fraction of its traffic that the endpoint should receive, if it is
+    /// weighted at all.
+    fn weight(&self) -> Option<f64> {
+        let warmup = self.warmup_weight().map(|w| w.weight(1.0));
+        let report = self.report.map(|r| r.weight());
+        if warmup.is_none() && report.is_none() && self.fixed.is_none() {
+            return None;
+        }
+        Some(warmup.unwrap_or(1.0) * report.unwrap_or(1.0) * self.fixed.unwrap_or(1.0))
+    }
+}
+
+impl<S: Load> Load for Warming<S> {
+    /// Whether the endpoint is considered cold, and its load.
+    type Metric = (bool, S::Metric);
+
+    fn load(&self) -> Self::Metric {
+        let cold = match self.weight() {
+            Some(weight) => rand::thread_rng().gen::<f64>() >= weight,
+            None => false,
+        };
+        (cold, self.inner.load())
+    }
+}
+
+impl<S, Req> tower::Service<Req> for Warming<S>
+where
+    S: tower::Service<Req>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        // Once the endpoint is warm, it is no longer weighted by its warmup.
+        if self.warmup_weight().map(|w| w.is_warm()).unwrap_or(false) {
+            self.warmup = None;
+        }
+
+        if let Some(ref mut rx) = self.load_report {
+            while let Ok(Async::Ready(Some(_))) = rx.poll_ref() {}
+            self.report = Some(*rx.get_ref());
+        }
+
+        self.inner.poll_ready()
+    }
+
+    fn call(&mut self, req: Req) -> Self::Future {
+        self.inner.call(req)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tower::Service as _;
+
+    struct Constant(u32);
+
+    impl Load for Constant {
+        type Metric = u32;
+
+        fn load(&self) -> u32 {
+            self.0
+        }
+    }
+
+    impl tower::Service<()> for Constant {
+        type Response = ();
+        type Error = ();
+        type Future = futures::future::FutureResult<(), ()>;
+
+        fn poll_ready(&mut self) -> Poll<(), ()> {
+            Ok(Async::Ready(()))
+        }
+
+        fn call(&mut self, _: ()) -> Self::Future {
+            futures::future::ok(())
+        }
+    }
+
+    #[test]
+    fn weight_ramps_linearly() {
+        let warmup = Duration::from_secs(10);
+        assert_eq!(
+            WarmupWeight::new(Duration::from_secs(0), warmup).weight(100.0),
+            0.0
+        );
+        assert_eq!(
+            WarmupWeight::new(Duration::from_secs(5), warmup).weight(100.0),
+            50.0
+        );
+        assert!(WarmupWeight::new(Duration::from_secs(10), warmup).is_warm());
+        assert!(WarmupWeight::new(Duration::from_secs(20), warmup).is_warm());
+        assert!(WarmupWeight::new(Duration::from_secs(0), Duration::from_secs(0)).is_warm());
+    }
+
+    #[test]
+    fn warming_endpoints_are_picked_less_often() {
+        // The warming endpoint is less loaded, so it would always be picked
+        // if it were warm.
+        let warm = warming(Constant(1), None, None);
+        let warming = warming(
+            Constant(0),
+            Some((
+                clock::now() - Duration::from_secs(25),
+                Duration::from_secs(100),
+            )),
+            None,
+        );
+
+        let picked = picks(&warming, &warm);
+        // The expected count is 250 with a standard deviation of ~14.
+        assert!(
+            picked > 180 && picked < 320,
+            "{} of {} requests were sent to the warming endpoint",
+            picked,
+            REQUESTS
+        );
+    }
+
+    #[test]
+    fn busy_endpoints_are_picked_less_often() {
+        let (tx, rx) = watch::channel(LoadReport::default());
+        let idle = warming(Constant(1), None, None);
+        let mut busy = warming(Constant(0), None, Some(rx));
+
+        busy.poll_ready().unwrap();
+        assert_eq!(picks(&busy, &idle), REQUESTS);
+
+        tx.broadcast(LoadReport {
+            cpu_utilization: 0.75,
+            request_rate: 100.0,
+        })
+        .unwrap();
+        busy.poll_ready().unwrap();
+        // The expected count is 250 with a standard deviation of ~14.
+        let picked = picks(&busy, &idle);
+        assert!(
+            picked > 180 && picked < 320,
+            "{} of {} requests were sent to the busy endpoint",
+            picked,
+            REQUESTS
+        );
+    }
+
+    #[test]
+    fn weighted_endpoints_are_picked_less_often() {
+        let unweighted = warming(Constant(1), None, None);
+        let weighted = Warming {
+            fixed: Some(0.25),
+            ..warming(Constant(0), None, None)
+        };
+
+        // The expected count is 250 with a standard deviation of ~14.
+        let picked = picks(&weighted, &unweighted);
+        assert!(
+            picked > 180 && picked < 320,
+            "{} of {} requests were sent to the weighted endpoint",
+            picked,
+            REQUESTS
+        );
+    }
+
+    const REQUESTS: usize = 1_000;
+
+    /// Counts how often `a` is less loaded than `b`.
+    fn picks(a: &Warming<Constant>, b: &Warming<Constant>) -> usize {
+        (0..REQUESTS).filter(|_| a.load() < b.load()).count()
+    }
+
+    fn warming(
+        inner: Constant,
+        warmup: Option<(Instant, Duration)>,
+        load_report: Option<watch::Receiver<LoadReport>>,
+    ) -> Warming<Constant> {
+        let report = load_report.as_ref().map(|rx| *rx.get_ref());
+        Warming {
+            inner,
+            warmup,
+            load_report,
+            report,
+            fixed: None,
+        }
+    }
+}
@@ -270,10 +270,8 @@ impl Future for Task {
 
                             let valid_until = e
                                 .into_inner()
-                                .and_then(|e| match e.kind() {
-                                    dns::ResolveErrorKind::NoRecordsFound {
-                                        valid_until, ..
-                                    } => *valid_until,
+                                .and_then(|e| match e {
+                                    dns::Error::NoRecordsFound { valid_until } => valid_until,
                                     _ => None,
                                 })
                                 .unwrap_or_else(|| clock::now() + DNS_ERROR_TTL);
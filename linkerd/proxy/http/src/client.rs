@@ -1,4 +1,5 @@
-use super::glue::{HttpBody, HyperConnect};
+use super::expect_continue;
+use super::glue::{FirstUse, HttpBody, HyperConnect};
 use super::upgrade::{Http11Upgrade, HttpConnect};
 use super::{
     h1, h2,
@@ -6,11 +7,15 @@ use super::{
 };
 use futures::{try_ready, Async, Future, Poll};
 use http;
+use http::header::{HeaderValue, CONNECTION};
 use hyper;
 use linkerd2_error::Error;
+use linkerd2_exp_backoff::{ExponentialBackoff, ExponentialBackoffStream};
 use linkerd2_proxy_transport::connect;
 use std::fmt;
 use std::marker::PhantomData;
+use std::time::{Duration, Instant};
+use tokio_timer::clock;
 use tower::ServiceExt;
 use tracing::{debug, info_span, trace};
 use tracing_futures::Instrument;
@@ -20,15 +25,28 @@ use tracing_futures::Instrument;
 /// The `span` is used for diagnostics (logging, mostly).
 #[derive(Debug)]
 pub struct Layer<T, B> {
+    h1_settings: crate::h1::Settings,
     h2_settings: crate::h2::Settings,
     _p: PhantomData<fn(T) -> B>,
 }
 
-type HyperClient<C, T, B> = hyper::Client<HyperConnect<C, T>, B>;
+type HyperClient<C, T, B> = hyper::Client<HyperConnect<C, T>, expect_continue::Body<B>>;
+
+/// Transient failures to establish an HTTP/2 connection, e.g. while the
+/// endpoint restarts, are retried up to this many times before the client
+/// fails to be built.
+const H2_CONNECT_RETRIES: usize = 3;
+
+const H2_CONNECT_BACKOFF: ExponentialBackoff = ExponentialBackoff {
+    min: Duration::from_millis(10),
+    max: Duration::from_millis(500),
+    jitter: 0.5,
+};
 
 /// A `MakeService` that can speak either HTTP/1 or HTTP/2.
 pub struct Client<C, T, B> {
     connect: C,
+    h1_settings: crate::h1::Settings,
     h2_settings: crate::h2::Settings,
     _p: PhantomData<fn(T) -> B>,
 }
@@ -42,18 +60,89 @@ where
     C::Connection: Send + 'static,
     C::Error: Into<Error>,
 {
-    Http1(Option<HyperClient<C, T, B>>),
-    Http2(::tower_util::Oneshot<h2::Connect<C, B>, T>),
+    Http1(Option<ClientService<C, T, B>>),
+    Http2(ConnectRetry<h2::Connect<C, B>, T>),
 }
 
 /// The `Service` yielded by `Client::new_service()`.
 pub enum ClientService<C, T, B>
 where
+    T: connect::HasPeerAddr,
     B: hyper::body::Payload + 'static,
     C: tower::MakeConnection<T> + 'static,
+    C::Connection: Send + 'static,
+    C::Error: Into<Error>,
 {
-    Http1(HyperClient<C, T, B>),
-    Http2(h2::Connection<B>),
+    Http1 {
+        client: HyperClient<C, T, B>,
+        /// Request bodies that expect `100 Continue` are held back for up to
+        /// this duration.
+        expect_continue_timeout: Duration,
+        /// Whether requests are HTTP/1.0, in which case the downstream
+        /// connection is closed after each response unless the downstream
+        /// client asked to keep it alive.
+        is_http_1_0: bool,
+        /// Determines how folded header values are handled in requests and
+        /// responses.
+        folded_headers: h1::FoldedHeaderPolicy,
+    },
+    Http2(Reconnect<h2::Connect<C, B>, T>),
+}
+
+/// Establishes a connection, retrying transient connect failures with a
+/// jittered backoff.
+///
+/// Failures that are not expected to resolve themselves, like a TLS
+/// handshake failure, are not retried.
+pub struct ConnectRetry<M: tower::Service<T>, T> {
+    connect: M,
+    target: T,
+    /// Unset while backing off.
+    future: Option<::tower_util::Oneshot<M, T>>,
+    backoff: ExponentialBackoffStream,
+    retries: usize,
+}
+
+/// A connection that is re-established with `ConnectRetry` when it is lost,
+/// e.g. because the endpoint restarted.
+///
+/// The service is not ready while it reconnects. If the connection cannot be
+/// re-established, the service fails.
+pub struct Reconnect<M: tower::Service<T>, T> {
+    connect: M,
+    target: T,
+    retries: usize,
+    backoff: ExponentialBackoff,
+    state: ReconnectState<M, T>,
+}
+
+enum ReconnectState<M: tower::Service<T>, T> {
+    Connected(M::Response),
+    Reconnecting(ConnectRetry<M, T>),
+}
+
+/// A response extension that records when the response's headers were
+/// received from the server, so that outer layers may measure the time to the
+/// response's first byte.
+#[derive(Copy, Clone, Debug)]
+pub struct ResponseHeadersAt(pub Instant);
+
+/// A response extension that describes the connection on which the response
+/// was received, so that outer layers may distinguish responses that paid the
+/// cost of establishing a connection from those on a warm connection.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct ConnectionInfo {
+    /// False if this was the first response received on the connection.
+    pub reused: bool,
+    pub negotiated_protocol: http::Version,
+}
+
+/// Distinguishes failures of an HTTP/2 client's connection, which fail all of
+/// the connection's in-flight requests, from failures of individual streams.
+#[derive(Debug)]
+pub enum ClientError {
+    Connection(Error),
+    Stream(Error),
 }
 
 pub enum ClientServiceFuture {
@@ -61,17 +150,26 @@ pub enum ClientServiceFuture {
         future: hyper::client::ResponseFuture,
         upgrade: Option<Http11Upgrade>,
         is_http_connect: bool,
+        expect_continue: Option<expect_continue::ResponseReceived>,
+        close_downstream: bool,
+        folded_headers: h1::FoldedHeaderPolicy,
     },
     Http2(h2::ResponseFuture),
+    /// The request was rejected before it was sent.
+    Rejected(Option<Error>),
 }
 
 // === impl Layer ===
 
-pub fn layer<T, B>(h2_settings: crate::h2::Settings) -> Layer<T, B>
+pub fn layer<T, B>(
+    h1_settings: crate::h1::Settings,
+    h2_settings: crate::h2::Settings,
+) -> Layer<T, B>
 where
     B: hyper::body::Payload + Send + 'static,
 {
     Layer {
+        h1_settings,
         h2_settings,
         _p: PhantomData,
     }
@@ -83,6 +181,7 @@ where
 {
     fn clone(&self) -> Self {
         Self {
+            h1_settings: self.h1_settings,
             h2_settings: self.h2_settings,
             _p: PhantomData,
         }
@@ -99,6 +198,7 @@ where
     fn layer(&self, connect: C) -> Self::Service {
         Client {
             connect,
+            h1_settings: self.h1_settings,
             h2_settings: self.h2_settings,
             _p: PhantomData,
         }
@@ -114,7 +214,7 @@ where
     C::Future: Send + 'static,
     <C::Future as Future>::Error: Into<Error>,
     C::Connection: Send + 'static,
-    T: connect::HasPeerAddr + HasSettings + fmt::Debug + Clone + Send + Sync,
+    T: connect::HasPeerAddr + HasSettings + h2::HasH2Settings + fmt::Debug + Clone + Send + Sync,
     B: hyper::body::Payload + 'static,
 {
     type Response = ClientService<C, T, B>;
@@ -135,20 +235,35 @@ where
                 keep_alive,
                 wants_h1_upgrade: _,
                 was_absolute_form,
+                is_http_1_0,
+                is_connect: _,
             } => {
                 let exec = tokio::executor::DefaultExecutor::current()
                     .instrument(info_span!("http1", %peer_addr));
                 let h1 = hyper::Client::builder()
                     .executor(exec)
                     .keep_alive(keep_alive)
+                    .max_idle_per_host(self.h1_settings.max_idle_per_host)
+                    .keep_alive_timeout(self.h1_settings.idle_timeout())
                     // hyper should never try to automatically set the Host
                     // header, instead always just passing whatever we received.
                     .set_host(false)
                     .build(HyperConnect::new(connect, config, was_absolute_form));
-                ClientNewServiceFuture::Http1(Some(h1))
+                ClientNewServiceFuture::Http1(Some(ClientService::Http1 {
+                    client: h1,
+                    expect_continue_timeout: self.h1_settings.expect_continue_timeout,
+                    is_http_1_0,
+                    folded_headers: self.h1_settings.folded_headers,
+                }))
             }
             Settings::Http2 => {
-                let h2 = h2::Connect::new(connect, self.h2_settings.clone()).oneshot(config);
+                let h2_settings = self.h2_settings_for(&config);
+                let h2 = ConnectRetry::new(
+                    h2::Connect::new(connect, h2_settings),
+                    config,
+                    H2_CONNECT_RETRIES,
+                    H2_CONNECT_BACKOFF,
+                );
                 ClientNewServiceFuture::Http2(h2)
             }
             Settings::NotHttp => {
@@ -158,6 +273,14 @@ where
     }
 }
 
+impl<C, T: h2::HasH2Settings, B> Client<C, T, B> {
+    /// Returns the layer's HTTP/2 settings with the target's overrides
+    /// applied.
+    fn h2_settings_for(&self, target: &T) -> h2::Settings {
+        self.h2_settings.merge(target.h2_settings())
+    }
+}
+
 impl<C, T, B> Clone for Client<C, T, B>
 where
     C: Clone,
@@ -165,6 +288,7 @@ where
     fn clone(&self) -> Self {
         Client {
             connect: self.connect.clone(),
+            h1_settings: self.h1_settings,
             h2_settings: self.h2_settings,
             _p: PhantomData,
         }
@@ -175,8 +299,8 @@ where
 
 impl<C, T, B> Future for ClientNewServiceFuture<C, T, B>
 where
-    T: connect::HasPeerAddr,
-    C: tower::MakeConnection<T> + Send + Sync + 'static,
+    T: connect::HasPeerAddr + Clone,
+    C: tower::MakeConnection<T> + Clone + Send + Sync + 'static,
     C::Connection: Send + 'static,
     C::Future: Send + 'static,
     C::Error: Into<Error>,
@@ -187,27 +311,169 @@ where
 
     fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
         let svc = match *self {
-            ClientNewServiceFuture::Http1(ref mut h1) => {
-                ClientService::Http1(h1.take().expect("poll more than once"))
-            }
+            ClientNewServiceFuture::Http1(ref mut h1) => h1.take().expect("poll more than once"),
             ClientNewServiceFuture::Http2(ref mut h2) => {
-                let svc = try_ready!(h2.poll());
-                ClientService::Http2(svc)
+                let conn = try_ready!(h2.poll());
+                ClientService::Http2(Reconnect::new(
+                    h2.connect.clone(),
+                    h2.target.clone(),
+                    conn,
+                    H2_CONNECT_RETRIES,
+                    H2_CONNECT_BACKOFF,
+                ))
             }
         };
         Ok(Async::Ready(svc))
     }
 }
 
+// === impl ConnectRetry ===
+
+impl<M, T> ConnectRetry<M, T>
+where
+    M: tower::Service<T> + Clone,
+    T: Clone,
+{
+    fn new(connect: M, target: T, retries: usize, backoff: ExponentialBackoff) -> Self {
+        let future = Some(connect.clone().oneshot(target.clone()));
+        Self {
+            connect,
+            target,
+            future,
+            backoff: backoff.stream(),
+            retries,
+        }
+    }
+}
+
+impl<M, T> Future for ConnectRetry<M, T>
+where
+    M: tower::Service<T> + Clone,
+    M::Error: Into<Error>,
+    T: Clone,
+{
+    type Item = M::Response;
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        loop {
+            if let Some(future) = self.future.as_mut() {
+                let error: Error = match future.poll() {
+                    Ok(ready) => return Ok(ready),
+                    Err(e) => e.into(),
+                };
+                if self.retries == 0 || !is_transient_connect_error(&*error) {
+                    return Err(error);
+                }
+                debug!(%error, retries = self.retries, "retrying connect");
+                self.retries -= 1;
+                self.future = None;
+            }
+
+            // The backoff stream only completes after an absurd number of
+            // iterations, in which case the connection is attempted anyway.
+            try_ready!(self.backoff.poll().map_err(Error::from));
+            self.future = Some(self.connect.clone().oneshot(self.target.clone()));
+        }
+    }
+}
+
+// === impl Reconnect ===
+
+impl<M, T> Reconnect<M, T>
+where
+    M: tower::Service<T>,
+{
+    fn new(
+        connect: M,
+        target: T,
+        connection: M::Response,
+        retries: usize,
+        backoff: ExponentialBackoff,
+    ) -> Self {
+        Self {
+            connect,
+            target,
+            retries,
+            backoff,
+            state: ReconnectState::Connected(connection),
+        }
+    }
+}
+
+impl<M, T, Req> tower::Service<Req> for Reconnect<M, T>
+where
+    M: tower::Service<T> + Clone,
+    M::Error: Into<Error>,
+    M::Response: tower::Service<Req>,
+    <M::Response as tower::Service<Req>>::Error: Into<Error>,
+    T: Clone,
+{
+    type Response = <M::Response as tower::Service<Req>>::Response;
+    type Error = Error;
+    type Future = <M::Response as tower::Service<Req>>::Future;
+
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        loop {
+            self.state = match self.state {
+                ReconnectState::Connected(ref mut conn) => match conn.poll_ready() {
+                    Ok(ready) => return Ok(ready),
+                    Err(e) => {
+                        let error: Error = e.into();
+                        debug!(%error, "connection lost; reconnecting");
+                        ReconnectState::Reconnecting(ConnectRetry::new(
+                            self.connect.clone(),
+                            self.target.clone(),
+                            self.retries,
+                            self.backoff,
+                        ))
+                    }
+                },
+                ReconnectState::Reconnecting(ref mut future) => {
+                    ReconnectState::Connected(try_ready!(future.poll()))
+                }
+            };
+        }
+    }
+
+    fn call(&mut self, req: Req) -> Self::Future {
+        match self.state {
+            ReconnectState::Connected(ref mut conn) => conn.call(req),
+            ReconnectState::Reconnecting(_) => panic!("called before ready"),
+        }
+    }
+}
+
+/// Returns true if `err` was caused by a connection failure that may resolve
+/// itself, e.g. because the endpoint is restarting.
+fn is_transient_connect_error(err: &(dyn std::error::Error + 'static)) -> bool {
+    use std::io::ErrorKind;
+
+    let mut cause = Some(err);
+    while let Some(e) = cause {
+        if let Some(e) = e.downcast_ref::<std::io::Error>() {
+            return match e.kind() {
+                ErrorKind::ConnectionRefused
+                | ErrorKind::ConnectionReset
+                | ErrorKind::ConnectionAborted
+                | ErrorKind::NotConnected => true,
+                _ => false,
+            };
+        }
+        cause = e.source();
+    }
+    false
+}
+
 // === impl ClientService ===
 
 impl<C, T, B> tower::Service<http::Request<B>> for ClientService<C, T, B>
 where
     C: tower::MakeConnection<T> + Clone + Send + Sync + 'static,
-    C::Connection: Send,
+    C::Connection: Send + 'static,
     C::Future: Send + 'static,
-    <C::Future as Future>::Error: Into<Error>,
-    T: Clone + Send + Sync + 'static,
+    C::Error: Into<Error>,
+    T: connect::HasPeerAddr + Clone + Send + Sync + 'static,
     B: hyper::body::Payload + 'static,
 {
     type Response = http::Response<HttpBody>;
@@ -216,8 +482,9 @@ where
 
     fn poll_ready(&mut self) -> Poll<(), Self::Error> {
         match *self {
-            ClientService::Http1(_) => Ok(Async::Ready(())),
-            ClientService::Http2(ref mut h2) => h2.poll_ready().map_err(Into::into),
+            ClientService::Http1 { .. } => Ok(Async::Ready(())),
+            ClientService::Http2(ref mut h2) => tower::Service::<http::Request<B>>::poll_ready(h2)
+                .map_err(|e| ClientError::Connection(e).into()),
         }
     }
 
@@ -230,17 +497,54 @@ where
             req.headers()
         );
         match *self {
-            ClientService::Http1(ref h1) => {
+            ClientService::Http1 {
+                client: ref h1,
+                expect_continue_timeout,
+                is_http_1_0,
+                folded_headers,
+            } => {
+                if let Err(e) = folded_headers.apply(req.headers_mut()) {
+                    debug!("rejecting request: {}", e);
+                    return ClientServiceFuture::Rejected(Some(e.into()));
+                }
+
                 let upgrade = req.extensions_mut().remove::<Http11Upgrade>();
                 let is_http_connect = if upgrade.is_some() {
                     req.method() == &http::Method::CONNECT
                 } else {
                     false
                 };
+
+                // HTTP/1.0 connections are closed after each response unless
+                // keep-alive is requested. The downstream client's preference
+                // is honored when its response is returned, but the upstream
+                // connection is kept alive so that it may be reused.
+                let close_downstream = is_http_1_0
+                    && req
+                        .extensions_mut()
+                        .remove::<h1::Http10KeepAlive>()
+                        .is_none();
+                if is_http_1_0 {
+                    req.headers_mut()
+                        .insert(CONNECTION, HeaderValue::from_static("keep-alive"));
+                }
+
+                let (req, expect_continue) = if expect_continue::expects_continue(&req) {
+                    trace!("holding back request body until a response is expected");
+                    let (parts, body) = req.into_parts();
+                    let (body, rsp) = expect_continue::Body::gated(body, expect_continue_timeout);
+                    (http::Request::from_parts(parts, body), Some(rsp))
+                } else {
+                    (req.map(expect_continue::Body::ungated), None)
+                };
+
                 ClientServiceFuture::Http1 {
                     future: h1.request(req),
                     upgrade,
                     is_http_connect,
+                    expect_continue,
+                    close_downstream,
+                    folded_headers,
                 }
             }
             ClientService::Http2(ref mut h2) => ClientServiceFuture::Http2(h2.call(req)),
@@ -260,23 +564,537 @@ impl Future for ClientServiceFuture {
                 future,
                 upgrade,
                 is_http_connect,
+                expect_continue,
+                close_downstream,
+                folded_headers,
             } => {
                 let mut res = try_ready!(future.poll()).map(|b| HttpBody {
                     body: Some(b),
                     upgrade: upgrade.take(),
+                    buffered: None,
+                    stream: None,
                 });
+                // If the request body has not yet been sent, it never will be.
+                if let Some(rsp) = expect_continue.take() {
+                    rsp.notify();
+                }
+                folded_headers.apply(res.headers_mut())?;
                 if *is_http_connect {
                     res.extensions_mut().insert(HttpConnect);
                 }
 
+                let reused = res
+                    .extensions_mut()
+                    .remove::<FirstUse>()
+                    .map(|first| !first.is_first())
+                    .unwrap_or(false);
+                let negotiated_protocol = res.version();
+                res.extensions_mut().insert(ConnectionInfo {
+                    reused,
+                    negotiated_protocol,
+                });
+
                 if h1::is_upgrade(&res) {
                     trace!("client response is HTTP/1.1 upgrade");
                 } else {
-                    h1::strip_connection_headers(res.headers_mut());
+                    h1::strip_connection_headers_from_response(&mut res);
+                    if *close_downstream {
+                        trace!("closing HTTP/1.0 connection after response");
+                        res.headers_mut()
+                            .insert(CONNECTION, HeaderValue::from_static("close"));
+                    }
                 }
+                res.extensions_mut().insert(ResponseHeadersAt(clock::now()));
                 Ok(Async::Ready(res))
             }
-            ClientServiceFuture::Http2(f) => f.poll().map_err(Into::into),
+            ClientServiceFuture::Http2(f) => {
+                let mut res = match f.poll() {
+                    Ok(Async::Ready(res)) => res,
+                    Ok(Async::NotReady) => return Ok(Async::NotReady),
+                    Err(e) => {
+                        let closed = f.is_connection_closed();
+                        return Err(ClientError::h2(e.into(), closed).into());
+                    }
+                };
+                res.extensions_mut().insert(ConnectionInfo {
+                    reused: f.is_reused(),
+                    negotiated_protocol: http::Version::HTTP_2,
+                });
+                res.extensions_mut().insert(ResponseHeadersAt(clock::now()));
+                Ok(Async::Ready(res))
+            }
+            ClientServiceFuture::Rejected(e) => Err(e
+                .take()
+                .expect("rejected future must not be polled after failing")),
+        }
+    }
+}
+
+// === impl ClientError ===
+
+impl ClientError {
+    /// Classifies an HTTP/2 request failure.
+    ///
+    /// A failure is attributed to the connection if the connection has been
+    /// closed (e.g. by a GOAWAY), if the request could not be sent because
+    /// the connection closed, or if the connection's transport failed.
+    fn h2(err: Error, connection_closed: bool) -> Self {
+        let mut is_connection = connection_closed;
+        let mut cause: Option<&(dyn std::error::Error + 'static)> = Some(&*err);
+        while let (false, Some(e)) = (is_connection, cause) {
+            if let Some(e) = e.downcast_ref::<hyper::Error>() {
+                is_connection = e.is_closed() || e.is_canceled();
+            } else if let Some(e) = e.downcast_ref::<::h2::Error>() {
+                is_connection = e.is_io();
+            } else {
+                is_connection = e.is::<std::io::Error>();
+            }
+            cause = e.source();
+        }
+
+        if is_connection {
+            ClientError::Connection(err)
+        } else {
+            ClientError::Stream(err)
+        }
+    }
+
+    pub fn is_connection(&self) -> bool {
+        match self {
+            ClientError::Connection(_) => true,
+            ClientError::Stream(_) => false,
+        }
+    }
+
+    /// Returns true if `err`, or any of its sources, is a connection-level
+    /// client error.
+    pub fn is_connection_error(err: &(dyn std::error::Error + 'static)) -> bool {
+        let mut cause = Some(err);
+        while let Some(e) = cause {
+            if let Some(e) = e.downcast_ref::<ClientError>() {
+                return e.is_connection();
+            }
+            cause = e.source();
         }
+        false
+    }
+}
+
+impl fmt::Display for ClientError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ClientError::Connection(e) | ClientError::Stream(e) => fmt::Display::fmt(e, f),
+        }
+    }
+}
+
+impl std::error::Error for ClientError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ClientError::Connection(e) | ClientError::Stream(e) => Some(&**e),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{future, Stream};
+    use std::net::SocketAddr;
+    use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+    use std::sync::{Arc, Mutex};
+    use tokio::net::{TcpListener, TcpStream};
+    use tokio::runtime::current_thread::Runtime;
+
+    #[derive(Clone, Debug)]
+    struct Target(h2::Settings);
+
+    impl h2::HasH2Settings for Target {
+        fn h2_settings(&self) -> h2::Settings {
+            self.0
+        }
+    }
+
+    #[test]
+    fn target_overrides_h2_settings() {
+        let defaults = h2::Settings {
+            initial_stream_window_size: Some(65_535),
+            initial_connection_window_size: Some(1_048_576),
+            adaptive_window: None,
+            max_concurrent_streams: None,
+        };
+        let client: Client<(), Target, hyper::Body> = Client {
+            connect: (),
+            h1_settings: crate::h1::Settings::default(),
+            h2_settings: defaults,
+            _p: PhantomData,
+        };
+
+        assert_eq!(
+            client.h2_settings_for(&Target(h2::Settings::default())),
+            defaults
+        );
+        assert_eq!(
+            client.h2_settings_for(&Target(h2::Settings {
+                initial_stream_window_size: Some(1_048_576),
+                initial_connection_window_size: None,
+                adaptive_window: None,
+                max_concurrent_streams: None,
+            })),
+            h2::Settings {
+                initial_stream_window_size: Some(1_048_576),
+                initial_connection_window_size: Some(1_048_576),
+                adaptive_window: None,
+                max_concurrent_streams: None,
+            }
+        );
+    }
+
+    /// Fails to connect with the given error kind until `fail` attempts have
+    /// been made.
+    #[derive(Clone)]
+    struct FailingConnect {
+        attempts: Arc<AtomicUsize>,
+        fail: usize,
+        kind: std::io::ErrorKind,
+    }
+
+    impl tower::Service<()> for FailingConnect {
+        type Response = ();
+        type Error = Error;
+        type Future = futures::future::FutureResult<(), Error>;
+
+        fn poll_ready(&mut self) -> Poll<(), Error> {
+            Ok(Async::Ready(()))
+        }
+
+        fn call(&mut self, _: ()) -> Self::Future {
+            if self.attempts.fetch_add(1, Ordering::SeqCst) < self.fail {
+                futures::future::err(std::io::Error::from(self.kind).into())
+            } else {
+                futures::future::ok(())
+            }
+        }
+    }
+
+    fn connect_retry(connect: FailingConnect) -> ConnectRetry<FailingConnect, ()> {
+        let backoff = ExponentialBackoff {
+            min: Duration::from_millis(1),
+            max: Duration::from_millis(1),
+            jitter: 0.0,
+        };
+        ConnectRetry::new(connect, (), 3, backoff)
+    }
+
+    #[test]
+    fn transient_connect_failures_are_retried() {
+        let mut rt = Runtime::new().unwrap();
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let connect = FailingConnect {
+            attempts: attempts.clone(),
+            fail: 1,
+            kind: std::io::ErrorKind::ConnectionRefused,
+        };
+
+        rt.block_on(connect_retry(connect)).expect("must connect");
+        assert_eq!(attempts.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn permanent_connect_failures_are_not_retried() {
+        let mut rt = Runtime::new().unwrap();
+        let attempts = Arc::new(AtomicUsize::new(0));
+        let connect = FailingConnect {
+            attempts: attempts.clone(),
+            fail: 1,
+            kind: std::io::ErrorKind::InvalidData,
+        };
+
+        rt.block_on(connect_retry(connect))
+            .expect_err("must not connect");
+        assert_eq!(attempts.load(Ordering::SeqCst), 1);
+    }
+
+    /// Connects to a `LosableConnection`, failing with `fail_next` instead if
+    /// it is set.
+    #[derive(Clone, Default)]
+    struct LosableConnect {
+        attempts: Arc<AtomicUsize>,
+        fail_next: Arc<Mutex<Option<std::io::ErrorKind>>>,
+        lost: Arc<AtomicBool>,
+    }
+
+    /// Fails once `lost` is set.
+    struct LosableConnection(Arc<AtomicBool>);
+
+    impl tower::Service<()> for LosableConnect {
+        type Response = LosableConnection;
+        type Error = Error;
+        type Future = futures::future::FutureResult<LosableConnection, Error>;
+
+        fn poll_ready(&mut self) -> Poll<(), Error> {
+            Ok(Async::Ready(()))
+        }
+
+        fn call(&mut self, _: ()) -> Self::Future {
+            self.attempts.fetch_add(1, Ordering::SeqCst);
+            if let Some(kind) = self.fail_next.lock().unwrap().take() {
+                return futures::future::err(std::io::Error::from(kind).into());
+            }
+            self.lost.store(false, Ordering::SeqCst);
+            futures::future::ok(LosableConnection(self.lost.clone()))
+        }
+    }
+
+    impl tower::Service<()> for LosableConnection {
+        type Response = ();
+        type Error = Error;
+        type Future = futures::future::FutureResult<(), Error>;
+
+        fn poll_ready(&mut self) -> Poll<(), Error> {
+            if self.0.load(Ordering::SeqCst) {
+                return Err(std::io::Error::from(std::io::ErrorKind::ConnectionReset).into());
+            }
+            Ok(Async::Ready(()))
+        }
+
+        fn call(&mut self, _: ()) -> Self::Future {
+            futures::future::ok(())
+        }
+    }
+
+    #[test]
+    fn lost_connections_are_reestablished() {
+        let mut rt = Runtime::new().unwrap();
+        let connect = LosableConnect::default();
+        let backoff = ExponentialBackoff {
+            min: Duration::from_millis(1),
+            max: Duration::from_millis(1),
+            jitter: 0.0,
+        };
+        let conn = rt.block_on(connect.clone().oneshot(())).unwrap();
+        let mut svc = Reconnect::new(connect.clone(), (), conn, 3, backoff);
+        let mut poll_ready = move || tower::Service::<()>::poll_ready(&mut svc);
+        rt.block_on(future::poll_fn(&mut poll_ready))
+            .expect("must be ready");
+
+        // The connection is lost and the first attempt to reconnect fails, so
+        // the service is not ready while it backs off.
+        connect.lost.store(true, Ordering::SeqCst);
+        *connect.fail_next.lock().unwrap() = Some(std::io::ErrorKind::ConnectionRefused);
+        rt.block_on(future::lazy(|| {
+            assert!(poll_ready().expect("must reconnect").is_not_ready());
+            Ok::<_, ()>(())
+        }))
+        .unwrap();
+        rt.block_on(future::poll_fn(&mut poll_ready))
+            .expect("must reconnect");
+        assert_eq!(connect.attempts.load(Ordering::SeqCst), 3);
+
+        // Permanent failures are not retried.
+        connect.lost.store(true, Ordering::SeqCst);
+        *connect.fail_next.lock().unwrap() = Some(std::io::ErrorKind::InvalidData);
+        rt.block_on(future::poll_fn(&mut poll_ready))
+            .expect_err("must not reconnect");
+        assert_eq!(connect.attempts.load(Ordering::SeqCst), 4);
+    }
+
+    #[derive(Clone, Debug)]
+    struct Endpoint {
+        addr: SocketAddr,
+        settings: Settings,
+    }
+
+    impl connect::HasPeerAddr for Endpoint {
+        fn peer_addr(&self) -> SocketAddr {
+            self.addr
+        }
+    }
+
+    impl HasSettings for Endpoint {
+        fn http_settings(&self) -> &Settings {
+            &self.settings
+        }
+    }
+
+    impl h2::HasH2Settings for Endpoint {
+        fn h2_settings(&self) -> h2::Settings {
+            h2::Settings::default()
+        }
+    }
+
+    /// Connects to an endpoint over TCP.
+    #[derive(Clone, Debug)]
+    struct TcpConnect;
+
+    impl tower::Service<Endpoint> for TcpConnect {
+        type Response = TcpStream;
+        type Error = std::io::Error;
+        type Future = tokio::net::tcp::ConnectFuture;
+
+        fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+            Ok(Async::Ready(()))
+        }
+
+        fn call(&mut self, endpoint: Endpoint) -> Self::Future {
+            TcpStream::connect(&endpoint.addr)
+        }
+    }
+
+    /// Accepts a single connection on the runtime, returning the address on
+    /// which it is accepted.
+    fn serve<F, S>(rt: &mut Runtime, accept: F) -> SocketAddr
+    where
+        F: FnOnce(TcpStream) -> S + 'static,
+        S: Future<Item = (), Error = ()> + 'static,
+    {
+        let listener = TcpListener::bind(&([127, 0, 0, 1], 0).into()).expect("must bind");
+        let addr = listener.local_addr().expect("must have an address");
+        rt.spawn(
+            listener
+                .incoming()
+                .into_future()
+                .map_err(|(e, _)| panic!("accept failed: {}", e))
+                .and_then(move |(tcp, _)| accept(tcp.expect("must accept"))),
+        );
+        addr
+    }
+
+    /// Builds a client service for an endpoint.
+    fn connect(
+        rt: &mut Runtime,
+        endpoint: Endpoint,
+    ) -> ClientService<TcpConnect, Endpoint, hyper::Body> {
+        let client = Client {
+            connect: TcpConnect,
+            h1_settings: crate::h1::Settings::default(),
+            h2_settings: h2::Settings::default(),
+            _p: PhantomData,
+        };
+        rt.block_on(client.oneshot(endpoint)).expect("must connect")
+    }
+
+    fn send<S>(rt: &mut Runtime, svc: &mut S) -> Result<S::Response, S::Error>
+    where
+        S: tower::Service<http::Request<hyper::Body>>,
+    {
+        rt.block_on(future::poll_fn(|| svc.poll_ready()))?;
+        let req = http::Request::get("http://test.example/")
+            .body(hyper::Body::empty())
+            .unwrap();
+        rt.block_on(svc.call(req))
+    }
+
+    #[test]
+    fn h2_requests_fail_with_connection_errors_after_goaway() {
+        let mut rt = Runtime::new().unwrap();
+        let addr = serve(&mut rt, |tcp| {
+            ::h2::server::handshake(tcp)
+                .and_then(|conn| conn.into_future().map_err(|(e, _)| e))
+                .and_then(|(req, mut conn)| {
+                    // The request was received before the GOAWAY, so it is
+                    // not refused; it fails when the connection closes.
+                    let req = req.expect("must receive a request");
+                    conn.abrupt_shutdown(::h2::Reason::NO_ERROR);
+                    future::poll_fn(move || conn.poll_close()).map(move |()| drop(req))
+                })
+                .map_err(|_| ())
+        });
+
+        let endpoint = Endpoint {
+            addr,
+            settings: Settings::Http2,
+        };
+        let mut svc = connect(&mut rt, endpoint);
+        let err = send(&mut rt, &mut svc).expect_err("request must fail");
+        assert!(
+            ClientError::is_connection_error(&*err),
+            "GOAWAY must fail the request with a connection error: {}",
+            err
+        );
+    }
+
+    #[test]
+    fn h2_stream_resets_are_stream_errors() {
+        let mut rt = Runtime::new().unwrap();
+        let addr = serve(&mut rt, |tcp| {
+            ::h2::server::handshake(tcp)
+                .and_then(|conn| conn.into_future().map_err(|(e, _)| e))
+                .and_then(|(req, conn)| {
+                    let (_, mut respond) = req.expect("must receive a request");
+                    respond.send_reset(::h2::Reason::CANCEL);
+                    // The connection remains open for other requests.
+                    conn.for_each(|_| Ok(()))
+                })
+                .map_err(|_| ())
+        });
+
+        let endpoint = Endpoint {
+            addr,
+            settings: Settings::Http2,
+        };
+        let mut svc = connect(&mut rt, endpoint);
+        let err = send(&mut rt, &mut svc).expect_err("request must fail");
+        assert!(
+            !ClientError::is_connection_error(&*err),
+            "a stream reset must not be a connection error: {}",
+            err
+        );
+    }
+
+    #[test]
+    fn map_response_applies_to_each_client() {
+        use linkerd2_stack::map_response;
+        use tower::layer::Layer as _;
+
+        #[derive(Debug)]
+        struct Wrapped(http::Response<HttpBody>);
+
+        let http1 = Settings::Http1 {
+            keep_alive: true,
+            wants_h1_upgrade: false,
+            was_absolute_form: false,
+            is_http_1_0: false,
+            is_connect: false,
+        };
+        for &(settings, version) in &[
+            (http1, http::Version::HTTP_11),
+            (Settings::Http2, http::Version::HTTP_2),
+        ] {
+            let mut rt = Runtime::new().unwrap();
+            let addr = serve(&mut rt, move |tcp| {
+                hyper::server::conn::Http::new()
+                    .http2_only(version == http::Version::HTTP_2)
+                    .serve_connection(
+                        tcp,
+                        hyper::service::service_fn_ok(|_| {
+                            hyper::Response::new(hyper::Body::empty())
+                        }),
+                    )
+                    .map_err(|_| ())
+            });
+
+            let client = connect(&mut rt, Endpoint { addr, settings });
+            let mut svc = map_response::layer(Wrapped).layer(client);
+            let Wrapped(rsp) = send(&mut rt, &mut svc).expect("request must succeed");
+            assert_eq!(rsp.status(), http::StatusCode::OK);
+            assert_eq!(
+                rsp.extensions()
+                    .get::<ConnectionInfo>()
+                    .map(|i| i.negotiated_protocol),
+                Some(version)
+            );
+        }
+    }
+
+    #[test]
+    fn h2_transport_errors_are_connection_errors() {
+        let io = std::io::Error::new(std::io::ErrorKind::ConnectionReset, "reset");
+        let err = ClientError::h2(::h2::Error::from(io).into(), false);
+        assert!(err.is_connection());
+
+        // The classification is found through wrapping errors.
+        let wrapped: Error = Box::new(err);
+        assert!(ClientError::is_connection_error(&*wrapped));
     }
 }
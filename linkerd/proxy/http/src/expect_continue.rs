@@ -0,0 +1,357 @@
+//! Holds back the body of an HTTP/1 request that expects `100 Continue`.
+//!
+//! hyper's server sends `100 Continue` to the downstream client when the
+//! request body is first polled, and hyper's client sends a request body as
+//! soon as it is available, discarding any interim responses. So, in order for
+//! the upstream server to be able to reject a request before its body is
+//! transferred, the body is not polled until the upstream server sends an
+//! interim response, the expect timeout elapses, or a final response is
+//! received. In the last case, the body is never sent.
+//!
+//! Because hyper does not expose interim responses, the client connection's
+//! transport is wrapped in an `Io` that reads the status line of each response
+//! while a body is held back. Both the transport and the request body are
+//! polled on the connection's task, so a gated body registers itself in a
+//! task-local when it is first polled and the `Io` releases it when an interim
+//! response is read. Releasing the body polls the downstream request body, so
+//! that hyper's server forwards `100 Continue` to the client.
+
+use futures::sync::oneshot;
+use futures::{task_local, try_ready, Async, Future, Poll};
+use http::header::{HeaderMap, EXPECT};
+use hyper::body::Payload;
+use linkerd2_error::Error;
+use std::cell::RefCell;
+use std::io::{self, Read, Write};
+use std::time::Duration;
+use std::{error, fmt};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tokio_timer::{clock, Delay};
+use tracing::debug;
+
+/// The length of an HTTP/1 status line up to and including its status code,
+/// e.g. `HTTP/1.1 100`.
+const STATUS_LINE_LEN: usize = 12;
+
+task_local! {
+    /// Releases the body that is held back on the current connection, if any.
+    static AWAITING_CONTINUE: RefCell<Option<oneshot::Sender<()>>> = RefCell::new(None)
+}
+
+/// A request body that may be held back until the server is expected to have
+/// responded to `Expect: 100-continue`.
+#[derive(Debug)]
+pub struct Body<B> {
+    inner: B,
+    gate: Option<Gate>,
+}
+
+/// Notifies a gated body that a final response was received.
+#[derive(Debug)]
+pub struct ResponseReceived(oneshot::Sender<()>);
+
+/// Indicates that a request body was not sent because the server responded
+/// before the body was expected.
+#[derive(Debug)]
+pub struct BodyNotSent(());
+
+/// A client connection's transport, which releases a held-back request body
+/// when an interim response is read.
+#[derive(Debug)]
+pub struct Io<T> {
+    io: T,
+    status_line: Vec<u8>,
+}
+
+#[derive(Debug)]
+struct Gate {
+    response: oneshot::Receiver<()>,
+    /// Registered in `AWAITING_CONTINUE` when the body is first polled.
+    await_continue: Option<oneshot::Sender<()>>,
+    continued: oneshot::Receiver<()>,
+    timeout: Delay,
+}
+
+/// Returns true if the request expects a `100 Continue` interim response
+/// before its body is sent.
+pub fn expects_continue<B>(req: &http::Request<B>) -> bool {
+    req.version() == http::Version::HTTP_11
+        && req
+            .headers()
+            .get(EXPECT)
+            .and_then(|v| v.to_str().ok())
+            .map(|v| v.eq_ignore_ascii_case("100-continue"))
+            .unwrap_or(false)
+}
+
+// === impl Body ===
+
+impl<B> Body<B> {
+    /// Holds back `inner` until an interim response is received or `timeout`
+    /// elapses, unless a final response is received first.
+    pub fn gated(inner: B, timeout: Duration) -> (Self, ResponseReceived) {
+        let (tx, rx) = oneshot::channel();
+        let (continue_tx, continue_rx) = oneshot::channel();
+        let gate = Gate {
+            response: rx,
+            await_continue: Some(continue_tx),
+            continued: continue_rx,
+            timeout: Delay::new(clock::now() + timeout),
+        };
+        let body = Self {
+            inner,
+            gate: Some(gate),
+        };
+        (body, ResponseReceived(tx))
+    }
+
+    pub fn ungated(inner: B) -> Self {
+        Self { inner, gate: None }
+    }
+
+    fn poll_gate(&mut self) -> Poll<(), Error> {
+        if let Some(gate) = self.gate.as_mut() {
+            // If the response future was dropped, the request was canceled.
+            let responded = gate.response.poll().map(|a| a.is_ready()).unwrap_or(true);
+            if responded {
+                debug!("response received before the request body was sent");
+                return Err(BodyNotSent(()).into());
+            }
+
+            if let Some(tx) = gate.await_continue.take() {
+                AWAITING_CONTINUE.with(|awaiting| *awaiting.borrow_mut() = Some(tx));
+            }
+
+            // If the sender was dropped, e.g. because another body was
+            // registered on this task, only the timeout releases the body.
+            let continued = gate.continued.poll().map(|a| a.is_ready()).unwrap_or(false);
+            if continued {
+                debug!("received interim response; sending request body");
+            } else {
+                try_ready!(gate.timeout.poll().map_err(Error::from));
+                debug!("sending request body");
+            }
+            self.gate = None;
+        }
+
+        Ok(Async::Ready(()))
+    }
+}
+
+impl<B: Payload> Payload for Body<B> {
+    type Data = B::Data;
+    type Error = Error;
+
+    fn is_end_stream(&self) -> bool {
+        self.inner.is_end_stream()
+    }
+
+    fn poll_data(&mut self) -> Poll<Option<Self::Data>, Self::Error> {
+        try_ready!(self.poll_gate());
+        self.inner.poll_data().map_err(Into::into)
+    }
+
+    fn poll_trailers(&mut self) -> Poll<Option<HeaderMap>, Self::Error> {
+        try_ready!(self.poll_gate());
+        self.inner.poll_trailers().map_err(Into::into)
+    }
+
+    fn content_length(&self) -> Option<u64> {
+        self.inner.content_length()
+    }
+}
+
+// === impl Io ===
+
+impl<T> Io<T> {
+    pub fn new(io: T) -> Self {
+        Self {
+            io,
+            status_line: Vec::with_capacity(STATUS_LINE_LEN),
+        }
+    }
+
+    /// Inspects the beginning of each response that is read while a body is
+    /// held back on the current task.
+    fn observe(&mut self, read: &[u8]) {
+        let status_line = &mut self.status_line;
+        AWAITING_CONTINUE.with(|awaiting| {
+            let mut awaiting = awaiting.borrow_mut();
+            if awaiting.is_none() {
+                status_line.clear();
+                return;
+            }
+
+            let n = (STATUS_LINE_LEN - status_line.len()).min(read.len());
+            status_line.extend_from_slice(&read[..n]);
+            if status_line.len() < STATUS_LINE_LEN {
+                return;
+            }
+
+            if is_interim(status_line) {
+                if let Some(tx) = awaiting.take() {
+                    let _ = tx.send(());
+                }
+            }
+            status_line.clear();
+        });
+    }
+}
+
+/// Returns true if `status_line` begins an informational response other than
+/// `101 Switching Protocols`, which is final for the purposes of a request body.
+fn is_interim(status_line: &[u8]) -> bool {
+    status_line.starts_with(b"HTTP/1.")
+        && status_line[8] == b' '
+        && status_line[9] == b'1'
+        && &status_line[9..12] != b"101"
+}
+
+impl<T: Read> Read for Io<T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.io.read(buf)?;
+        self.observe(&buf[..n]);
+        Ok(n)
+    }
+}
+
+impl<T: AsyncRead> AsyncRead for Io<T> {}
+
+impl<T: Write> Write for Io<T> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.io.write(buf)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.io.flush()
+    }
+}
+
+impl<T: AsyncWrite> AsyncWrite for Io<T> {
+    fn shutdown(&mut self) -> Poll<(), io::Error> {
+        self.io.shutdown()
+    }
+}
+
+// === impl ResponseReceived ===
+
+impl ResponseReceived {
+    pub fn notify(self) {
+        let _ = self.0.send(());
+    }
+}
+
+// === impl BodyNotSent ===
+
+impl fmt::Display for BodyNotSent {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "the server responded before the request body was sent")
+    }
+}
+
+impl error::Error for BodyNotSent {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::future;
+    use tokio::runtime::current_thread::Runtime;
+
+    #[test]
+    fn detects_expect_continue() {
+        let req = http::Request::post("/")
+            .header(EXPECT, "100-Continue")
+            .body(())
+            .unwrap();
+        assert!(expects_continue(&req));
+
+        let req = http::Request::post("/")
+            .version(http::Version::HTTP_10)
+            .header(EXPECT, "100-continue")
+            .body(())
+            .unwrap();
+        assert!(!expects_continue(&req));
+
+        let req = http::Request::post("/").body(()).unwrap();
+        assert!(!expects_continue(&req));
+    }
+
+    #[test]
+    fn body_is_sent_after_timeout() {
+        let mut rt = Runtime::new().unwrap();
+        let (mut body, _rsp) = Body::gated(hyper::Body::from("hello"), Duration::from_millis(10));
+
+        rt.block_on(future::lazy(|| {
+            assert!(body.poll_data().unwrap().is_not_ready());
+            Ok::<_, ()>(())
+        }))
+        .unwrap();
+
+        let data = rt
+            .block_on(future::poll_fn(|| body.poll_data()))
+            .unwrap()
+            .expect("data");
+        assert_eq!(data.as_ref(), b"hello");
+    }
+
+    #[test]
+    fn body_is_not_sent_after_response() {
+        let mut rt = Runtime::new().unwrap();
+        let (mut body, rsp) = Body::gated(hyper::Body::from("hello"), Duration::from_secs(60));
+
+        rsp.notify();
+        let err = rt
+            .block_on(future::poll_fn(|| body.poll_data()))
+            .unwrap_err();
+        assert!(err.is::<BodyNotSent>());
+    }
+
+    #[test]
+    fn body_is_sent_after_continue() {
+        let mut rt = Runtime::new().unwrap();
+        let (mut body, _rsp) = Body::gated(hyper::Body::from("hello"), Duration::from_secs(60));
+
+        // The body and the transport are polled on the same task, as they are
+        // by a hyper client connection.
+        let data = rt
+            .block_on(future::lazy(move || {
+                assert!(body.poll_data().unwrap().is_not_ready());
+
+                let mut buf = [0u8; 64];
+                let mut io = Io::new(&b"HTTP/1.1 100 Continue\r\n\r\n"[..]);
+                // The status line may be split across reads.
+                io.read(&mut buf[..4]).unwrap();
+                assert!(body.poll_data().unwrap().is_not_ready());
+                io.read(&mut buf[4..]).unwrap();
+
+                match body.poll_data().unwrap() {
+                    Async::Ready(data) => Ok::<_, ()>(data),
+                    Async::NotReady => panic!("body should be released"),
+                }
+            }))
+            .unwrap()
+            .expect("data");
+        assert_eq!(data.as_ref(), b"hello");
+    }
+
+    #[test]
+    fn body_is_held_back_after_other_responses() {
+        let mut rt = Runtime::new().unwrap();
+        let (mut body, _rsp) = Body::gated(hyper::Body::from("hello"), Duration::from_secs(60));
+
+        rt.block_on(future::lazy(move || {
+            assert!(body.poll_data().unwrap().is_not_ready());
+
+            let mut buf = [0u8; 64];
+            let mut io = Io::new(&b"HTTP/1.1 101 Switching Protocols\r\n\r\n"[..]);
+            io.read(&mut buf).unwrap();
+            assert!(body.poll_data().unwrap().is_not_ready());
+
+            let mut io = Io::new(&b"HTTP/1.1 417 Expectation Failed\r\n\r\n"[..]);
+            io.read(&mut buf).unwrap();
+            assert!(body.poll_data().unwrap().is_not_ready());
+            Ok::<_, ()>(())
+        }))
+        .unwrap();
+    }
+}
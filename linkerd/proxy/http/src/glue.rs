@@ -1,9 +1,12 @@
-use crate::{upgrade::Http11Upgrade, HasH2Reason};
+use crate::{expect_continue, upgrade::Http11Upgrade, HasH2Reason};
+use bytes::Bytes;
 use futures::{try_ready, Async, Future, Poll};
 use http;
 use hyper::client::connect as hyper_connect;
 use hyper::{self, body::Payload};
 use linkerd2_error::Error;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 use tracing::debug;
 
 /// Provides optional HTTP/1.1 upgrade support on the body.
@@ -13,6 +16,19 @@ pub struct HttpBody {
     /// to be inserted into the Http11Upgrade half.
     pub(super) body: Option<hyper::Body>,
     pub(super) upgrade: Option<Http11Upgrade>,
+    /// Set when the body was read into memory so that it may be replayed.
+    pub(super) buffered: Option<Buffered>,
+    /// Holds the HTTP/2 stream that carries a response body, so that the
+    /// stream counts against its connection's limit until the body is
+    /// dropped.
+    pub(super) stream: Option<crate::h2::ActiveStream>,
+}
+
+/// A request body that has been read into memory.
+#[derive(Clone, Debug)]
+pub(super) struct Buffered {
+    pub(super) data: Bytes,
+    pub(super) trailers: Option<http::HeaderMap>,
 }
 
 /// Glue for a `tower::Service` to used as a `hyper::server::Service`.
@@ -29,6 +45,12 @@ pub struct HyperConnect<C, T> {
     target: T,
 }
 
+/// Set by `HyperConnect` in the extensions of every response received on a
+/// connection, so that the first response on each connection can be
+/// distinguished from those on a reused connection.
+#[derive(Clone, Debug, Default)]
+pub(super) struct FirstUse(Arc<AtomicBool>);
+
 /// Future returned by `HyperConnect`.
 pub struct HyperConnectFuture<F> {
     inner: F,
@@ -42,10 +64,17 @@ impl Payload for HttpBody {
     type Error = hyper::Error;
 
     fn is_end_stream(&self) -> bool {
-        self.body
+        let trailers_sent = self
+            .buffered
             .as_ref()
-            .expect("only taken in drop")
-            .is_end_stream()
+            .map(|b| b.trailers.is_none())
+            .unwrap_or(true);
+        trailers_sent
+            && self
+                .body
+                .as_ref()
+                .expect("only taken in drop")
+                .is_end_stream()
     }
 
     fn poll_data(&mut self) -> Poll<Option<Self::Data>, Self::Error> {
@@ -60,6 +89,10 @@ impl Payload for HttpBody {
     }
 
     fn poll_trailers(&mut self) -> Poll<Option<http::HeaderMap>, Self::Error> {
+        if let Some(buffered) = self.buffered.as_mut() {
+            return Ok(Async::Ready(buffered.trailers.take()));
+        }
+
         self.body
             .as_mut()
             .expect("only taken in drop")
@@ -93,12 +126,30 @@ impl Default for HttpBody {
         HttpBody {
             body: Some(hyper::Body::empty()),
             upgrade: None,
+            buffered: None,
+            stream: None,
+        }
+    }
+}
+
+impl HttpBody {
+    /// Returns a body that replays data that has already been read.
+    pub(super) fn buffered(buffered: Buffered) -> Self {
+        HttpBody {
+            body: Some(buffered.data.clone().into()),
+            upgrade: None,
+            buffered: Some(buffered),
+            stream: None,
         }
     }
 }
 
 impl super::retry::TryClone for HttpBody {
     fn try_clone(&self) -> Option<Self> {
+        if let Some(buffered) = self.buffered.as_ref() {
+            return Some(HttpBody::buffered(buffered.clone()));
+        }
+
         if self.is_end_stream() {
             Some(HttpBody::default())
         } else {
@@ -144,6 +195,8 @@ where
         self.service.call(req.map(|b| HttpBody {
             body: Some(b),
             upgrade: None,
+            buffered: None,
+            stream: None,
         }))
     }
 }
@@ -168,7 +221,7 @@ where
     C::Connection: Send + 'static,
     T: Clone + Send + Sync,
 {
-    type Transport = C::Connection;
+    type Transport = expect_continue::Io<C::Connection>;
     type Error = <C::Future as Future>::Error;
     type Future = HyperConnectFuture<C::Future>;
 
@@ -185,16 +238,27 @@ where
     F: Future + 'static,
     F::Error: Into<Error>,
 {
-    type Item = (F::Item, hyper_connect::Connected);
+    type Item = (expect_continue::Io<F::Item>, hyper_connect::Connected);
     type Error = F::Error;
 
     fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
-        let transport = try_ready!(self.inner.poll());
-        let connected = hyper_connect::Connected::new().proxy(self.absolute_form);
+        let transport = expect_continue::Io::new(try_ready!(self.inner.poll()));
+        let connected = hyper_connect::Connected::new()
+            .proxy(self.absolute_form)
+            .extra(FirstUse::default());
         Ok(Async::Ready((transport, connected)))
     }
 }
 
+// === impl FirstUse ===
+
+impl FirstUse {
+    /// Returns true only the first time it is called for a connection.
+    pub(super) fn is_first(&self) -> bool {
+        !self.0.swap(true, Ordering::AcqRel)
+    }
+}
+
 // === impl Error ===
 
 impl HasH2Reason for hyper::Error {
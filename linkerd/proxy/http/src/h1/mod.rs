@@ -0,0 +1,474 @@
+use super::upgrade::HttpConnect;
+use http;
+use http::header::{HeaderName, HeaderValue, CONNECTION, HOST, UPGRADE};
+use http::uri::{Authority, Parts, Scheme, Uri};
+use std::time::Duration;
+use std::{fmt, mem};
+use tracing::{debug, trace};
+
+mod pipeline;
+
+pub use self::pipeline::H1Pipeline;
+
+/// Configures the pool of HTTP/1 connections that a client maintains for
+/// each endpoint.
+#[derive(Copy, Clone, Debug)]
+pub struct Settings {
+    /// The maximum number of idle connections retained for the endpoint.
+    pub max_idle_per_host: usize,
+    /// How long an idle connection is retained in the pool.
+    pub pool_idle_timeout: Option<Duration>,
+    /// How long the endpoint keeps idle connections open, if known. Idle
+    /// connections are discarded before then, so that a request is not sent
+    /// on a connection that the server is closing.
+    pub keep_alive_timeout: Option<Duration>,
+    /// How long a request body is held back while waiting for the endpoint to
+    /// respond to `Expect: 100-continue`.
+    pub expect_continue_timeout: Duration,
+    /// How header values that are folded across lines are handled.
+    pub folded_headers: FoldedHeaderPolicy,
+}
+
+/// Determines how header values that contain obsolete line folding (RFC 7230
+/// §3.2.4), i.e. a CRLF followed by a space or tab, are handled.
+///
+/// Folded values must not be forwarded as-is: a recipient that does not
+/// unfold them may interpret the continuation line as a distinct header.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum FoldedHeaderPolicy {
+    /// Messages with folded headers are rejected.
+    Reject,
+    /// Each fold is replaced with a single space.
+    Unfold,
+}
+
+/// Indicates that a message has a folded header value.
+#[derive(Clone, Debug)]
+pub struct FoldedHeaderError(HeaderName);
+
+// === impl Settings ===
+
+impl Settings {
+    /// Returns how long an idle connection may be retained, i.e. the shorter
+    /// of the pool's idle timeout and the endpoint's keep-alive timeout.
+    pub fn idle_timeout(&self) -> Option<Duration> {
+        match (self.pool_idle_timeout, self.keep_alive_timeout) {
+            (Some(pool), Some(keep_alive)) => Some(pool.min(keep_alive)),
+            (pool, keep_alive) => pool.or(keep_alive),
+        }
+    }
+}
+
+/// Matches hyper's defaults. Like curl, request bodies are held back for up
+/// to a second when a response to `Expect: 100-continue` is expected.
+impl Default for Settings {
+    fn default() -> Self {
+        Self {
+            max_idle_per_host: std::usize::MAX,
+            pool_idle_timeout: Some(Duration::from_secs(90)),
+            keep_alive_timeout: None,
+            expect_continue_timeout: Duration::from_secs(1),
+            folded_headers: FoldedHeaderPolicy::Reject,
+        }
+    }
+}
+
+// === impl FoldedHeaderPolicy ===
+
+impl FoldedHeaderPolicy {
+    /// Rejects or unfolds any folded values in `headers`.
+    pub fn apply(self, headers: &mut http::HeaderMap) -> Result<(), FoldedHeaderError> {
+        match self {
+            FoldedHeaderPolicy::Reject => validate_headers(headers),
+            FoldedHeaderPolicy::Unfold => unfold_headers(headers),
+        }
+    }
+}
+
+impl std::str::FromStr for FoldedHeaderPolicy {
+    type Err = ();
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s.eq_ignore_ascii_case("reject") {
+            Ok(FoldedHeaderPolicy::Reject)
+        } else if s.eq_ignore_ascii_case("unfold") {
+            Ok(FoldedHeaderPolicy::Unfold)
+        } else {
+            Err(())
+        }
+    }
+}
+
+// === impl FoldedHeaderError ===
+
+impl FoldedHeaderError {
+    pub fn header(&self) -> &HeaderName {
+        &self.0
+    }
+}
+
+impl fmt::Display for FoldedHeaderError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "the {} header has a folded value", self.0)
+    }
+}
+
+impl std::error::Error for FoldedHeaderError {}
+
+/// Fails if any header value is folded across lines.
+pub fn validate_headers(headers: &http::HeaderMap) -> Result<(), FoldedHeaderError> {
+    match headers.iter().find(|(_, v)| is_folded(v.as_bytes())) {
+        Some((name, _)) => Err(FoldedHeaderError(name.clone())),
+        None => Ok(()),
+    }
+}
+
+/// Replaces each fold in `headers` with a single space.
+///
+/// Fails if a value is not valid once it has been unfolded, e.g. because it
+/// contains a bare CR or LF.
+fn unfold_headers(headers: &mut http::HeaderMap) -> Result<(), FoldedHeaderError> {
+    for (name, value) in headers.iter_mut() {
+        if !is_folded(value.as_bytes()) {
+            continue;
+        }
+
+        let unfolded = unfold(value.as_bytes());
+        trace!(header = %name, "unfolding header value");
+        *value = HeaderValue::from_bytes(&unfolded).map_err(|_| FoldedHeaderError(name.clone()))?;
+    }
+    Ok(())
+}
+
+fn is_folded(value: &[u8]) -> bool {
+    value
+        .windows(3)
+        .any(|w| w[0] == b'\r' && w[1] == b'\n' && is_fold_whitespace(w[2]))
+}
+
+fn unfold(value: &[u8]) -> Vec<u8> {
+    let mut unfolded = Vec::with_capacity(value.len());
+    let mut i = 0;
+    while i < value.len() {
+        let is_fold = value[i..].starts_with(b"\r\n")
+            && value.get(i + 2).cloned().map(is_fold_whitespace) == Some(true);
+        if is_fold {
+            i += 2;
+            while value.get(i).cloned().map(is_fold_whitespace) == Some(true) {
+                i += 1;
+            }
+            unfolded.push(b' ');
+        } else {
+            unfolded.push(value[i]);
+            i += 1;
+        }
+    }
+    unfolded
+}
+
+fn is_fold_whitespace(b: u8) -> bool {
+    b == b' ' || b == b'\t'
+}
+
+/// Tries to make sure the `Uri` of the request is in a form needed by
+/// hyper's Client.
+pub fn normalize_our_view_of_uri<B>(req: &mut http::Request<B>) {
+    trace!(uri = %req.uri());
+    debug_assert!(
+        req.uri().scheme_part().is_none(),
+        "normalize_uri shouldn't be called with absolute URIs: {:?}",
+        req.uri()
+    );
+
+    // try to parse the Host header
+    if let Some(host) = authority_from_host(&req) {
+        trace!(%host, "normalizing URI");
+        set_authority(req.uri_mut(), host);
+        return;
+    }
+
+    trace!("not normalizing");
+}
+
+/// Convert any URI into its origin-form (relative path part only).
+pub fn set_origin_form(uri: &mut Uri) {
+    trace!(%uri, "Setting origin form");
+    let mut parts = mem::replace(uri, Uri::default()).into_parts();
+    parts.scheme = None;
+    parts.authority = None;
+    *uri = Uri::from_parts(parts).expect("path only is valid origin-form uri")
+}
+
+/// Returns an Authority from a request's Host header.
+pub fn authority_from_host<B>(req: &http::Request<B>) -> Option<Authority> {
+    super::authority_from_header(req, HOST)
+}
+
+pub fn set_authority(uri: &mut http::Uri, auth: Authority) {
+    let mut parts = Parts::from(mem::replace(uri, Uri::default()));
+
+    parts.authority = Some(auth);
+
+    // If this was an origin-form target (path only),
+    // then we can't *only* set the authority, as that's
+    // an illegal target (such as `example.com/docs`).
+    //
+    // But don't set a scheme if this was authority-form (CONNECT),
+    // since that would change its meaning (like `https://example.com`).
+    if parts.path_and_query.is_some() {
+        parts.scheme = Some(Scheme::HTTP);
+    }
+
+    let new = Uri::from_parts(parts).expect("absolute uri");
+
+    *uri = new;
+}
+
+/// Headers that only apply to a single connection, whether or not they are
+/// listed by the `Connection` header (RFC 7230 section 6.1, RFC 7235 sections
+/// 4.3 and 4.4). They are also illegal in HTTP/2.
+const HOP_BY_HOP_HEADERS: &[&str] = &[
+    "keep-alive",
+    "proxy-authenticate",
+    "proxy-authorization",
+    "proxy-connection",
+    "te",
+    "trailer",
+    "transfer-encoding",
+    "upgrade",
+];
+
+/// Strips hop-by-hop headers from a request before it is forwarded.
+pub fn strip_connection_headers_from_request<B>(req: &mut http::Request<B>) {
+    strip_connection_headers(req.headers_mut());
+}
+
+/// Strips hop-by-hop headers from a response before it is forwarded.
+pub fn strip_connection_headers_from_response<B>(res: &mut http::Response<B>) {
+    strip_connection_headers(res.headers_mut());
+}
+
+/// Strips the headers that are listed by `Connection`, `Connection` itself,
+/// and the headers that are always hop-by-hop.
+pub fn strip_connection_headers(headers: &mut http::HeaderMap) {
+    // A `Connection` header may have a comma-separated list of names of
+    // other headers that are meant for only this specific connection, and
+    // it may be repeated.
+    let listed = headers
+        .get_all(CONNECTION)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .map(|name| name.trim().to_owned())
+        .filter(|name| !name.is_empty())
+        .collect::<Vec<_>>();
+    headers.remove(CONNECTION);
+    for name in listed {
+        headers.remove(name.as_str());
+    }
+
+    for name in HOP_BY_HOP_HEADERS {
+        headers.remove(*name);
+    }
+}
+
+/// A request extension indicating that an HTTP/1.0 client asked for its
+/// connection to be kept alive.
+///
+/// Connection headers are stripped when a request is received, so this
+/// records the client's preference until its response is returned.
+#[derive(Copy, Clone, Debug)]
+pub struct Http10KeepAlive;
+
+/// Checks HTTP/1.0 requests to determine if they ask for their connection to
+/// be kept alive, which HTTP/1.0 connections are not by default.
+pub fn is_http10_keep_alive<B>(req: &http::Request<B>) -> bool {
+    if req.version() != http::Version::HTTP_10 {
+        return false;
+    }
+
+    req.headers()
+        .get_all(CONNECTION)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .any(|name| name.trim().eq_ignore_ascii_case("keep-alive"))
+}
+
+/// Checks requests to determine if they want to perform an HTTP upgrade.
+pub fn wants_upgrade<B>(req: &http::Request<B>) -> bool {
+    // HTTP upgrades were added in 1.1, not 1.0.
+    if req.version() != http::Version::HTTP_11 {
+        return false;
+    }
+
+    if let Some(upgrade) = req.headers().get(UPGRADE) {
+        // If an `h2` upgrade over HTTP/1.1 were to go by the proxy,
+        // and it succeeded, there would an h2 connection, but it would
+        // be opaque-to-the-proxy, acting as just a TCP proxy.
+        //
+        // A user wouldn't be able to see any usual HTTP telemetry about
+        // requests going over that connection. Instead of that confusion,
+        // the proxy strips h2 upgrade headers.
+        //
+        // Eventually, the proxy will support h2 upgrades directly.
+        return upgrade != "h2c";
+    }
+
+    // HTTP/1.1 CONNECT requests are just like upgrades!
+    req.method() == &http::Method::CONNECT
+}
+
+/// Checks responses to determine if they are successful HTTP upgrades.
+pub fn is_upgrade<B>(res: &http::Response<B>) -> bool {
+    // Upgrades were introduced in HTTP/1.1
+    if res.version() != http::Version::HTTP_11 {
+        return false;
+    }
+
+    // 101 Switching Protocols
+    if res.status() == http::StatusCode::SWITCHING_PROTOCOLS {
+        return true;
+    }
+
+    // CONNECT requests are complete if status code is 2xx.
+    if res.extensions().get::<HttpConnect>().is_some() && res.status().is_success() {
+        return true;
+    }
+
+    // Just a regular HTTP response...
+    false
+}
+
+/// Returns if the request target is in `absolute-form`.
+///
+/// This is `absolute-form`: `https://example.com/docs`
+///
+/// This is not:
+///
+/// - `/docs`
+/// - `example.com`
+pub fn is_absolute_form(uri: &Uri) -> bool {
+    // It's sufficient just to check for a scheme, since in HTTP1,
+    // it's required in absolute-form, and `http::Uri` doesn't
+    // allow URIs with the other parts missing when the scheme is set.
+    debug_assert!(
+        uri.scheme_part().is_none()
+            || (uri.authority_part().is_some() && uri.path_and_query().is_some()),
+        "is_absolute_form http::Uri invariants: {:?}",
+        uri
+    );
+
+    uri.scheme_part().is_some()
+}
+
+/// Returns if the request target is in `origin-form`.
+///
+/// This is `origin-form`: `example.com`
+fn is_origin_form(uri: &Uri) -> bool {
+    uri.scheme_part().is_none() && uri.path_and_query().is_none()
+}
+
+/// Returns if the received request is definitely bad.
+///
+/// Just because a request parses doesn't mean it's correct. For examples:
+///
+/// - `GET example.com`
+/// - `CONNECT /just-a-path
+pub fn is_bad_request<B>(req: &http::Request<B>) -> bool {
+    if req.method() == &http::Method::CONNECT {
+        // CONNECT is only valid over HTTP/1.1
+        if req.version() != http::Version::HTTP_11 {
+            debug!("CONNECT request not valid for HTTP/1.0: {:?}", req.uri());
+            return true;
+        }
+
+        // CONNECT requests are only valid in authority-form.
+        if !is_origin_form(req.uri()) {
+            debug!("CONNECT request with illegal URI: {:?}", req.uri());
+            return true;
+        }
+    // If not CONNECT, refuse any origin-form URIs
+    } else if is_origin_form(req.uri()) {
+        debug!("{} request with illegal URI: {:?}", req.method(), req.uri());
+        return true;
+    }
+
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn folded(value: &'static [u8]) -> HeaderValue {
+        // `HeaderValue`'s checked constructors reject CR and LF, so folded
+        // values can only be constructed unchecked.
+        unsafe { HeaderValue::from_shared_unchecked(bytes::Bytes::from_static(value)) }
+    }
+
+    #[test]
+    fn rejects_folded_headers() {
+        let mut headers = http::HeaderMap::new();
+        headers.insert("x-plain", HeaderValue::from_static("a b"));
+        assert!(validate_headers(&headers).is_ok());
+
+        headers.insert("x-folded", folded(b"a\r\n\tb"));
+        let e = validate_headers(&headers).expect_err("folded header must be rejected");
+        assert_eq!(e.header(), "x-folded");
+        assert!(FoldedHeaderPolicy::Reject.apply(&mut headers).is_err());
+    }
+
+    #[test]
+    fn unfolds_folded_headers() {
+        let mut headers = http::HeaderMap::new();
+        headers.insert("x-plain", HeaderValue::from_static("a  b"));
+        headers.insert("x-folded", folded(b"a\r\n \t b\r\n\tc"));
+        FoldedHeaderPolicy::Unfold.apply(&mut headers).unwrap();
+        assert_eq!(headers["x-plain"], "a  b");
+        assert_eq!(headers["x-folded"], "a b c");
+        assert!(validate_headers(&headers).is_ok());
+
+        headers.insert("x-injected", folded(b"a\r\n b\r\nx-evil: c"));
+        assert!(
+            FoldedHeaderPolicy::Unfold.apply(&mut headers).is_err(),
+            "a bare line break must not survive unfolding"
+        );
+    }
+
+    #[test]
+    fn strips_listed_and_hop_by_hop_headers_from_requests() {
+        let mut req = http::Request::builder()
+            .header(CONNECTION, "X-Foo, keep-alive")
+            .header(CONNECTION, "x-bar")
+            .header("x-foo", "1")
+            .header("x-bar", "2")
+            .header("x-baz", "3")
+            .header("te", "trailers")
+            .header("transfer-encoding", "chunked")
+            .header("proxy-authorization", "Basic Zm9vOmJhcg==")
+            .header("trailer", "x-checksum")
+            .body(())
+            .unwrap();
+        strip_connection_headers_from_request(&mut req);
+
+        let names = req.headers().keys().map(|k| k.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, vec!["x-baz"]);
+    }
+
+    #[test]
+    fn strips_hop_by_hop_headers_from_responses() {
+        let mut res = http::Response::builder()
+            .header(CONNECTION, "close")
+            .header("keep-alive", "timeout=5")
+            .header("proxy-authenticate", "Basic")
+            .header("upgrade", "websocket")
+            .header("content-type", "text/plain")
+            .body(())
+            .unwrap();
+        strip_connection_headers_from_response(&mut res);
+
+        let names = res.headers().keys().map(|k| k.as_str()).collect::<Vec<_>>();
+        assert_eq!(names, vec!["content-type"]);
+    }
+}
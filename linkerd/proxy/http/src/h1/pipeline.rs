@@ -0,0 +1,240 @@
+use futures::sync::oneshot;
+use futures::{task, Async, Future, Poll};
+use linkerd2_error::Error;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, MutexGuard};
+use tracing::trace;
+
+/// Pipelines requests on an HTTP/1.1 connection.
+///
+/// Up to `max_pipelined` requests may be in flight at once. Responses are
+/// dispatched to their callers in the order that the requests were sent,
+/// even if the inner service completes them in another order: a completed
+/// response is held until all earlier responses have been dispatched.
+///
+/// This is meant for a client that dispatches requests on a single
+/// connection. `ClientService` does not use it, since hyper's pooled HTTP/1
+/// client only sends a request on a connection once the previous response has
+/// completed, and ordering responses across its connections would only delay
+/// them behind unrelated requests.
+pub struct H1Pipeline<S, Req>
+where
+    S: tower::Service<Req>,
+{
+    inner: S,
+    max_pipelined: usize,
+    queue: Arc<Mutex<Queue<S::Future>>>,
+}
+
+pub struct ResponseFuture<F: Future> {
+    rx: oneshot::Receiver<Result<F::Item, Error>>,
+    queue: Arc<Mutex<Queue<F>>>,
+}
+
+struct Queue<F: Future> {
+    in_flight: VecDeque<InFlight<F>>,
+    /// Notified when a response is dispatched while the pipeline is full.
+    task: Option<task::Task>,
+}
+
+struct InFlight<F: Future> {
+    future: F,
+    /// Holds the response once it completes, until all earlier responses
+    /// have been dispatched.
+    result: Option<Result<F::Item, Error>>,
+    tx: oneshot::Sender<Result<F::Item, Error>>,
+}
+
+// === impl H1Pipeline ===
+
+impl<S, Req> H1Pipeline<S, Req>
+where
+    S: tower::Service<Req>,
+{
+    pub fn new(inner: S, max_pipelined: usize) -> Self {
+        debug_assert!(max_pipelined > 0, "at least one request must be permitted");
+        Self {
+            inner,
+            max_pipelined,
+            queue: Arc::new(Mutex::new(Queue {
+                in_flight: VecDeque::new(),
+                task: None,
+            })),
+        }
+    }
+}
+
+impl<S, Req> tower::Service<Req> for H1Pipeline<S, Req>
+where
+    S: tower::Service<Req>,
+    S::Error: Into<Error>,
+{
+    type Response = S::Response;
+    type Error = Error;
+    type Future = ResponseFuture<S::Future>;
+
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        {
+            let mut queue = lock(&self.queue);
+            // Responses are dispatched here as well as by their futures so
+            // that callers that have dropped their futures do not hold the
+            // pipeline's capacity.
+            queue.drive();
+            if queue.in_flight.len() >= self.max_pipelined {
+                trace!(in_flight = queue.in_flight.len(), "pipeline is full");
+                queue.task = Some(task::current());
+                return Ok(Async::NotReady);
+            }
+        }
+
+        self.inner.poll_ready().map_err(Into::into)
+    }
+
+    fn call(&mut self, req: Req) -> Self::Future {
+        let future = self.inner.call(req);
+        let (tx, rx) = oneshot::channel();
+        lock(&self.queue).in_flight.push_back(InFlight {
+            future,
+            result: None,
+            tx,
+        });
+        ResponseFuture {
+            rx,
+            queue: self.queue.clone(),
+        }
+    }
+}
+
+fn lock<F: Future>(queue: &Mutex<Queue<F>>) -> MutexGuard<'_, Queue<F>> {
+    queue.lock().unwrap_or_else(|e| e.into_inner())
+}
+
+// === impl ResponseFuture ===
+
+impl<F> Future for ResponseFuture<F>
+where
+    F: Future,
+    F::Error: Into<Error>,
+{
+    type Item = F::Item;
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        lock(&self.queue).drive();
+        match self.rx.poll() {
+            Ok(Async::NotReady) => Ok(Async::NotReady),
+            Ok(Async::Ready(Ok(rsp))) => Ok(Async::Ready(rsp)),
+            Ok(Async::Ready(Err(e))) => Err(e),
+            Err(canceled) => Err(canceled.into()),
+        }
+    }
+}
+
+// === impl Queue ===
+
+impl<F> Queue<F>
+where
+    F: Future,
+    F::Error: Into<Error>,
+{
+    /// Polls each in-flight request and dispatches the responses at the
+    /// front of the pipeline.
+    fn drive(&mut self) {
+        for in_flight in self.in_flight.iter_mut().filter(|f| f.result.is_none()) {
+            match in_flight.future.poll() {
+                Ok(Async::NotReady) => {}
+                Ok(Async::Ready(rsp)) => in_flight.result = Some(Ok(rsp)),
+                Err(e) => in_flight.result = Some(Err(e.into())),
+            }
+        }
+
+        while self
+            .in_flight
+            .front()
+            .map(|f| f.result.is_some())
+            .unwrap_or(false)
+        {
+            let InFlight { tx, result, .. } =
+                self.in_flight.pop_front().expect("must be in flight");
+            // The caller may have dropped its response future.
+            let _ = tx.send(result.expect("response must be complete"));
+            if let Some(task) = self.task.take() {
+                task.notify();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::future;
+    use tower::Service;
+
+    #[test]
+    fn responses_are_dispatched_in_request_order() {
+        let (tx_a, rx_a) = oneshot::channel::<u32>();
+        let (tx_b, rx_b) = oneshot::channel::<u32>();
+        let mut responses = vec![rx_b, rx_a];
+        let inner = tower::service_fn(move |_: ()| {
+            responses
+                .pop()
+                .expect("unexpected request")
+                .map_err(|_| "canceled")
+        });
+        let mut pipeline = H1Pipeline::new(inner, 2);
+
+        future::lazy(|| {
+            assert!(pipeline.poll_ready().unwrap().is_ready());
+            let mut a = pipeline.call(());
+            assert!(pipeline.poll_ready().unwrap().is_ready());
+            let mut b = pipeline.call(());
+            assert!(
+                pipeline.poll_ready().unwrap().is_not_ready(),
+                "the pipeline is full"
+            );
+
+            // The second response completes first, but is held until the
+            // first response is dispatched.
+            tx_b.send(2).unwrap();
+            assert!(b.poll().unwrap().is_not_ready());
+            assert!(pipeline.poll_ready().unwrap().is_not_ready());
+
+            tx_a.send(1).unwrap();
+            assert_eq!(b.poll().unwrap(), Async::Ready(2));
+            assert_eq!(a.poll().unwrap(), Async::Ready(1));
+            assert!(pipeline.poll_ready().unwrap().is_ready());
+
+            Ok::<_, ()>(())
+        })
+        .wait()
+        .unwrap();
+    }
+
+    #[test]
+    fn errors_are_dispatched_to_their_callers() {
+        let mut pipeline = H1Pipeline::new(
+            tower::service_fn(|fail: bool| {
+                if fail {
+                    future::err("failed")
+                } else {
+                    future::ok(())
+                }
+            }),
+            2,
+        );
+
+        future::lazy(|| {
+            assert!(pipeline.poll_ready().unwrap().is_ready());
+            let mut a = pipeline.call(true);
+            assert!(pipeline.poll_ready().unwrap().is_ready());
+            let mut b = pipeline.call(false);
+
+            assert_eq!(b.poll().unwrap(), Async::Ready(()));
+            assert_eq!(a.poll().unwrap_err().to_string(), "failed");
+            Ok::<_, ()>(())
+        })
+        .wait()
+        .unwrap();
+    }
+}
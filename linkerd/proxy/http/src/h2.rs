@@ -1,5 +1,5 @@
 use super::Body;
-use futures::{try_ready, Future, Poll};
+use futures::{task, try_ready, Async, Future, Poll};
 use http;
 use hyper::{
     body::Payload,
@@ -9,15 +9,50 @@ use linkerd2_error::Error;
 use linkerd2_proxy_transport::connect;
 use std::marker::PhantomData;
 use std::net::SocketAddr;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 use tokio::executor::{DefaultExecutor, Executor};
 use tokio::io::{AsyncRead, AsyncWrite};
-use tracing::{debug, info_span};
+use tokio_timer::clock;
+use tracing::{debug, info_span, trace};
 use tracing_futures::Instrument;
 
-#[derive(Copy, Clone, Debug, Default)]
+#[derive(Copy, Clone, Debug, Default, PartialEq, Eq)]
 pub struct Settings {
     pub initial_stream_window_size: Option<u32>,
     pub initial_connection_window_size: Option<u32>,
+    /// When set, the initial stream window size is chosen for each connection
+    /// from the time it took to establish, instead of
+    /// `initial_stream_window_size`.
+    pub adaptive_window: Option<AdaptiveWindow>,
+    /// Limits the number of streams that may be active on each connection,
+    /// in addition to the limit advertised by the server.
+    pub max_concurrent_streams: Option<u32>,
+}
+
+/// Bounds the initial stream window size that is chosen for a connection.
+///
+/// The window is sized to the bandwidth-delay product of the connection,
+/// assuming `ASSUMED_BANDWIDTH`, using the time taken to establish the
+/// connection (including its TLS handshake) as an estimate of its round-trip
+/// time.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct AdaptiveWindow {
+    pub min: u32,
+    pub max: u32,
+}
+
+/// The bandwidth, in bytes per second, used to size adaptive windows
+/// (1Gbps).
+const ASSUMED_BANDWIDTH: u64 = 125_000_000;
+
+/// Implemented by targets that override some of a client's default HTTP/2
+/// settings.
+pub trait HasH2Settings {
+    /// Returns the settings that override the client's defaults. Settings that
+    /// are unset use the defaults.
+    fn h2_settings(&self) -> Settings;
 }
 
 #[derive(Debug)]
@@ -30,12 +65,36 @@ pub struct Connect<C, B> {
 #[derive(Debug)]
 pub struct Connection<B> {
     tx: SendRequest<B>,
+    /// Set once a request has been sent on the connection.
+    used: bool,
+    closed: Closed,
+    /// Set when the number of concurrent streams is limited.
+    streams: Option<Arc<Mutex<Streams>>>,
 }
 
+/// Tracks the streams that are active on a connection.
+#[derive(Debug)]
+struct Streams {
+    active: usize,
+    max: usize,
+    /// Notified when a stream completes while the connection is at its limit.
+    task: Option<task::Task>,
+}
+
+/// Counts against its connection's stream limit until it is dropped.
+#[derive(Debug)]
+pub(crate) struct ActiveStream(Arc<Mutex<Streams>>);
+
+/// Set when the connection's task completes, i.e. when the connection has
+/// been closed or has failed.
+#[derive(Clone, Debug, Default)]
+struct Closed(Arc<AtomicBool>);
+
 pub struct ConnectFuture<F: Future, B> {
     state: ConnectState<F, B>,
     peer_addr: SocketAddr,
     h2_settings: Settings,
+    started: Instant,
 }
 
 enum ConnectState<F: Future, B> {
@@ -45,6 +104,69 @@ enum ConnectState<F: Future, B> {
 
 pub struct ResponseFuture {
     inner: conn::ResponseFuture,
+    reused: bool,
+    closed: Closed,
+    stream: Option<ActiveStream>,
+}
+
+// ===== impl Settings =====
+
+impl Settings {
+    /// Returns these settings with the initial stream window size chosen from
+    /// each connection's round-trip time, between `min` and `max`.
+    pub fn adaptive_window(self, min: u32, max: u32) -> Self {
+        debug_assert!(min <= max, "adaptive window min must not exceed max");
+        Settings {
+            adaptive_window: Some(AdaptiveWindow { min, max }),
+            ..self
+        }
+    }
+
+    /// Returns these settings, with any settings that are set in `overrides`
+    /// replaced.
+    ///
+    /// A fixed initial stream window size in `overrides` also replaces an
+    /// adaptive window in these settings.
+    pub fn merge(self, overrides: Settings) -> Settings {
+        let adaptive_window = match overrides.initial_stream_window_size {
+            Some(_) => overrides.adaptive_window,
+            None => overrides.adaptive_window.or(self.adaptive_window),
+        };
+        Settings {
+            initial_stream_window_size: overrides
+                .initial_stream_window_size
+                .or(self.initial_stream_window_size),
+            initial_connection_window_size: overrides
+                .initial_connection_window_size
+                .or(self.initial_connection_window_size),
+            adaptive_window,
+            max_concurrent_streams: overrides
+                .max_concurrent_streams
+                .or(self.max_concurrent_streams),
+        }
+    }
+
+    /// Returns the initial stream window size for a connection with the given
+    /// round-trip time.
+    fn initial_stream_window_size(&self, rtt: Duration) -> Option<u32> {
+        match self.adaptive_window {
+            Some(window) => Some(window.size(rtt)),
+            None => self.initial_stream_window_size,
+        }
+    }
+}
+
+// ===== impl AdaptiveWindow =====
+
+impl AdaptiveWindow {
+    fn size(&self, rtt: Duration) -> u32 {
+        let bdp = rtt.as_micros() * u128::from(ASSUMED_BANDWIDTH) / 1_000_000;
+        if bdp > u128::from(self.max) {
+            self.max
+        } else {
+            (bdp as u32).max(self.min)
+        }
+    }
 }
 
 // ===== impl Connect =====
@@ -90,6 +212,7 @@ where
             peer_addr: target.peer_addr(),
             state: ConnectState::Connect(self.connect.make_connection(target)),
             h2_settings: self.h2_settings,
+            started: clock::now(),
         }
     }
 }
@@ -113,21 +236,49 @@ where
                 ConnectState::Handshake(ref mut hs) => {
                     let (tx, conn) = try_ready!(hs.poll());
 
+                    let closed = Closed::default();
+                    let conn = {
+                        let closed = closed.clone();
+                        conn.then(move |res| {
+                            closed.0.store(true, Ordering::Release);
+                            if let Err(error) = res {
+                                debug!(%error, "failed");
+                            }
+                            Ok::<(), ()>(())
+                        })
+                    };
                     DefaultExecutor::current()
                         .instrument(info_span!("h2", peer_addr=%self.peer_addr))
-                        .spawn(Box::new(conn.map_err(|error| debug!(%error, "failed"))))
+                        .spawn(Box::new(conn))
                         .map_err(Error::from)?;
 
-                    return Ok(Connection { tx }.into());
+                    let streams = self.h2_settings.max_concurrent_streams.map(|max| {
+                        Arc::new(Mutex::new(Streams {
+                            active: 0,
+                            max: max as usize,
+                            task: None,
+                        }))
+                    });
+                    return Ok(Connection {
+                        tx,
+                        used: false,
+                        closed,
+                        streams,
+                    }
+                    .into());
                 }
             };
 
+            let rtt = clock::now() - self.started;
+            let initial_stream_window_size = self.h2_settings.initial_stream_window_size(rtt);
+            trace!(?rtt, ?initial_stream_window_size, "connected");
+
             let exec =
                 DefaultExecutor::current().instrument(info_span!("h2", peer_addr=%self.peer_addr));
             let hs = conn::Builder::new()
                 .executor(exec)
                 .http2_only(true)
-                .http2_initial_stream_window_size(self.h2_settings.initial_stream_window_size)
+                .http2_initial_stream_window_size(initial_stream_window_size)
                 .http2_initial_connection_window_size(
                     self.h2_settings.initial_connection_window_size,
                 )
@@ -148,6 +299,15 @@ where
     type Future = ResponseFuture;
 
     fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        if let Some(ref streams) = self.streams {
+            let mut streams = streams.lock().expect("streams lock poisoned");
+            if streams.active >= streams.max {
+                trace!(active = streams.active, "at max concurrent streams");
+                streams.task = Some(task::current());
+                return Ok(Async::NotReady);
+            }
+        }
+
         self.tx.poll_ready().map_err(From::from)
     }
 
@@ -168,22 +328,162 @@ where
 
         ResponseFuture {
             inner: self.tx.send_request(req),
+            reused: std::mem::replace(&mut self.used, true),
+            closed: self.closed.clone(),
+            stream: self.streams.as_ref().map(ActiveStream::new),
         }
     }
 }
 
 // ===== impl ResponseFuture =====
 
+impl ResponseFuture {
+    /// Returns false if this was the first request sent on its connection.
+    pub fn is_reused(&self) -> bool {
+        self.reused
+    }
+
+    /// Returns true if the connection on which the request was sent has been
+    /// closed, e.g. after the server sent a GOAWAY.
+    pub fn is_connection_closed(&self) -> bool {
+        self.closed.0.load(Ordering::Acquire)
+    }
+}
+
 impl Future for ResponseFuture {
     type Item = http::Response<Body>;
     type Error = hyper::Error;
 
     fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
         let res = try_ready!(self.inner.poll());
+        let stream = self.stream.take();
         let res = res.map(|body| Body {
             body: Some(body),
             upgrade: None,
+            buffered: None,
+            stream,
         });
         Ok(res.into())
     }
 }
+
+// ===== impl ActiveStream =====
+
+impl ActiveStream {
+    fn new(streams: &Arc<Mutex<Streams>>) -> Self {
+        if let Ok(mut s) = streams.lock() {
+            s.active += 1;
+        }
+        ActiveStream(streams.clone())
+    }
+}
+
+impl Drop for ActiveStream {
+    fn drop(&mut self) {
+        if let Ok(mut s) = self.0.lock() {
+            s.active -= 1;
+            if let Some(task) = s.task.take() {
+                task.notify();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{future, Stream};
+    use tokio::net::{TcpListener, TcpStream};
+    use tokio::runtime::current_thread::Runtime;
+    use tower::Service;
+
+    #[test]
+    fn adaptive_window_is_bounded() {
+        let settings = Settings::default().adaptive_window(64 * 1024, 4 * 1024 * 1024);
+
+        // Loopback connections get the smallest window.
+        let size = settings.initial_stream_window_size(Duration::from_micros(50));
+        assert_eq!(size, Some(64 * 1024));
+
+        // A 10ms link at 1Gbps has a bandwidth-delay product of 1.25MB.
+        let size = settings.initial_stream_window_size(Duration::from_millis(10));
+        assert_eq!(size, Some(1_250_000));
+
+        // Distant peers get the largest window.
+        let size = settings.initial_stream_window_size(Duration::from_secs(1));
+        assert_eq!(size, Some(4 * 1024 * 1024));
+    }
+
+    #[test]
+    fn fixed_window_overrides_adaptive_window() {
+        let settings = Settings::default().adaptive_window(64 * 1024, 4 * 1024 * 1024);
+
+        let merged = settings.merge(Settings {
+            initial_stream_window_size: Some(1024),
+            ..Settings::default()
+        });
+        assert_eq!(merged.adaptive_window, None);
+        assert_eq!(
+            merged.initial_stream_window_size(Duration::from_millis(10)),
+            Some(1024)
+        );
+
+        let merged = settings.merge(Settings {
+            initial_connection_window_size: Some(1024),
+            ..Settings::default()
+        });
+        assert_eq!(merged.adaptive_window, settings.adaptive_window);
+    }
+
+    #[test]
+    fn max_concurrent_streams_applies_backpressure() {
+        let mut rt = Runtime::new().unwrap();
+
+        // A server that responds to each request immediately.
+        let addr: SocketAddr = ([127, 0, 0, 1], 0).into();
+        let listener = TcpListener::bind(&addr).expect("must bind");
+        let addr = listener.local_addr().expect("must have an address");
+        rt.spawn(listener.incoming().take(1).map_err(|_| ()).for_each(|io| {
+            hyper::server::conn::Http::new()
+                .http2_only(true)
+                .serve_connection(
+                    io,
+                    hyper::service::service_fn_ok(|_| http::Response::new(hyper::Body::empty())),
+                )
+                .map_err(|_| ())
+        }));
+
+        let settings = Settings {
+            max_concurrent_streams: Some(1),
+            ..Settings::default()
+        };
+        let mut connect = Connect::<_, hyper::Body>::new(
+            tower::service_fn(|addr: SocketAddr| TcpStream::connect(&addr)),
+            settings,
+        );
+        let mut conn = rt.block_on(connect.call(addr)).expect("must connect");
+
+        assert!(is_ready(&mut rt, &mut conn));
+        let rsp = conn.call(request());
+        assert!(!is_ready(&mut rt, &mut conn), "the stream is in flight");
+
+        let rsp = rt.block_on(rsp).expect("request must succeed");
+        assert!(!is_ready(&mut rt, &mut conn), "the body holds the stream");
+
+        drop(rsp);
+        assert!(is_ready(&mut rt, &mut conn), "the stream has completed");
+    }
+
+    fn request() -> http::Request<hyper::Body> {
+        http::Request::builder()
+            .version(http::Version::HTTP_2)
+            .uri("http://example.com/")
+            .body(hyper::Body::empty())
+            .unwrap()
+    }
+
+    fn is_ready(rt: &mut Runtime, conn: &mut Connection<hyper::Body>) -> bool {
+        rt.block_on(future::lazy(|| conn.poll_ready().map(|r| r.is_ready())))
+            .expect("poll_ready must not fail")
+    }
+}
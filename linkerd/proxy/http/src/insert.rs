@@ -1,5 +1,6 @@
 use futures::{try_ready, Future, Poll};
 use http;
+use linkerd2_error::Error;
 use linkerd2_stack::layer;
 use std::marker::PhantomData;
 
@@ -35,111 +36,874 @@ pub struct Service<S, L, V> {
     _marker: PhantomData<fn() -> V>,
 }
 
+/// Wraps an HTTP `Service` so that the `V`-typed value is inserted into the
+/// extensions of each request that does not already have a `V`-typed value.
+#[derive(Clone, Debug)]
+pub struct IfAbsentLayer<L, V> {
+    lazy: L,
+    _marker: PhantomData<fn() -> V>,
+}
+
+#[derive(Clone)]
+pub struct MakeInsertIfAbsent<M, L, V> {
+    inner: M,
+    lazy: L,
+    _marker: PhantomData<fn() -> V>,
+}
+
+pub struct MakeInsertIfAbsentFuture<F, L, V> {
+    inner: F,
+    lazy: L,
+    _marker: PhantomData<fn() -> V>,
+}
+
+#[derive(Clone)]
+pub struct InsertIfAbsent<S, L, V> {
+    inner: S,
+    lazy: L,
+    _marker: PhantomData<fn() -> V>,
+}
+
+/// Wraps an HTTP `Service` so that the `V`-typed value is inserted into the
+/// extensions of each request that matches a predicate.
+#[derive(Clone, Debug)]
+pub struct ConditionalLayer<L, V, P> {
+    lazy: L,
+    predicate: P,
+    _marker: PhantomData<fn() -> V>,
+}
+
+#[derive(Clone)]
+pub struct MakeConditional<M, L, V, P> {
+    inner: M,
+    lazy: L,
+    predicate: P,
+    _marker: PhantomData<fn() -> V>,
+}
+
+pub struct MakeConditionalFuture<F, L, V, P> {
+    inner: F,
+    lazy: L,
+    predicate: P,
+    _marker: PhantomData<fn() -> V>,
+}
+
+#[derive(Clone)]
+pub struct ConditionalInsert<S, L, V, P> {
+    inner: S,
+    lazy: L,
+    predicate: P,
+    _marker: PhantomData<fn() -> V>,
+}
+
+/// Wraps an HTTP `Service` so that the `V`-typed value is inserted into each
+/// response's extensions.
+#[derive(Clone, Debug)]
+pub struct ResponseLayer<L, V> {
+    lazy: L,
+    _marker: PhantomData<fn() -> V>,
+}
+
+#[derive(Clone)]
+pub struct MakeInsertResponse<M, L, V> {
+    inner: M,
+    lazy: L,
+    _marker: PhantomData<fn() -> V>,
+}
+
+pub struct MakeInsertResponseFuture<F, L, V> {
+    inner: F,
+    lazy: L,
+    _marker: PhantomData<fn() -> V>,
+}
+
+#[derive(Clone)]
+pub struct InsertResponse<S, L, V> {
+    inner: S,
+    lazy: L,
+    _marker: PhantomData<fn() -> V>,
+}
+
+pub struct InsertResponseFuture<F, L, V> {
+    inner: F,
+    lazy: L,
+    _marker: PhantomData<fn() -> V>,
+}
+
+/// Wraps an HTTP `Service` so that any `V`-typed value is removed from each
+/// request's extensions and passed to `on_remove`.
+#[derive(Debug)]
+pub struct RemoveLayer<V, F = fn(V)> {
+    on_remove: F,
+    _marker: PhantomData<fn() -> V>,
+}
+
+pub struct MakeRemove<M, V, F = fn(V)> {
+    inner: M,
+    on_remove: F,
+    _marker: PhantomData<fn() -> V>,
+}
+
+pub struct MakeRemoveFuture<Fut, V, F> {
+    inner: Fut,
+    on_remove: F,
+    _marker: PhantomData<fn() -> V>,
+}
+
+pub struct Remove<S, V, F = fn(V)> {
+    inner: S,
+    on_remove: F,
+    _marker: PhantomData<fn() -> V>,
+}
+
+/// Wraps an HTTP `Service` so that an existing `V`-typed value in each
+/// request's extensions is replaced by the result of a transformation.
+#[derive(Clone, Debug)]
+pub struct TransformLayer<F, V> {
+    transform: F,
+    default: Option<FnLazy<fn() -> V>>,
+}
+
+#[derive(Clone)]
+pub struct MakeTransform<M, F, V> {
+    inner: M,
+    transform: F,
+    default: Option<FnLazy<fn() -> V>>,
+}
+
+pub struct MakeTransformFuture<Fut, F, V> {
+    inner: Fut,
+    transform: F,
+    default: Option<FnLazy<fn() -> V>>,
+}
+
+#[derive(Clone)]
+pub struct Transform<S, F, V> {
+    inner: S,
+    transform: F,
+    default: Option<FnLazy<fn() -> V>>,
+}
+
 #[derive(Clone, Debug)]
 pub struct FnLazy<F>(F);
 
-#[derive(Clone, Debug)]
-pub struct ValLazy<V>(V);
+#[derive(Clone, Debug)]
+pub struct ValLazy<V>(V);
+
+pub fn layer<F, V>(f: F) -> Layer<FnLazy<F>, V>
+where
+    F: Fn() -> V + Clone,
+    V: Send + Sync + 'static,
+{
+    Layer::new(FnLazy(f))
+}
+
+/// Inserts the value returned by `f` only into requests for which `predicate`
+/// returns true. Other requests are passed through unmodified.
+///
+/// The predicate is evaluated before the request is modified.
+pub fn conditional<F, P, V>(f: F, predicate: P) -> ConditionalLayer<FnLazy<F>, V, P>
+where
+    F: Fn() -> V + Clone,
+    P: Clone,
+    V: Send + Sync + 'static,
+{
+    ConditionalLayer::new(FnLazy(f), predicate)
+}
+
+/// Inserts the value returned by `f` into each response's extensions.
+pub fn response_layer<F, V>(f: F) -> ResponseLayer<FnLazy<F>, V>
+where
+    F: Fn() -> V + Clone,
+    V: Send + Sync + 'static,
+{
+    ResponseLayer::new(FnLazy(f))
+}
+
+/// Replaces each request's `V`-typed extension with the value returned by `f`,
+/// e.g. to append a hop to a list or to increment a counter.
+///
+/// Requests without a `V`-typed extension are passed through unmodified,
+/// unless a default is configured with `TransformLayer::with_default`.
+pub fn transform_layer<F, V>(f: F) -> TransformLayer<F, V>
+where
+    F: Fn(V) -> V + Clone,
+    V: Send + Sync + 'static,
+{
+    TransformLayer {
+        transform: f,
+        default: None,
+    }
+}
+
+/// Removes `V`-typed values from request extensions, e.g. so that routing
+/// hints do not cross a trust boundary. Other extensions are untouched.
+pub fn remove_layer<V>() -> RemoveLayer<V>
+where
+    V: Send + Sync + 'static,
+{
+    remove_layer_with(drop as fn(V))
+}
+
+/// Like `remove_layer`, but each removed value is passed to `on_remove`, e.g.
+/// so that it may be recorded before it is discarded.
+pub fn remove_layer_with<V, F>(on_remove: F) -> RemoveLayer<V, F>
+where
+    F: Fn(V) + Clone,
+    V: Send + Sync + 'static,
+{
+    RemoveLayer {
+        on_remove,
+        _marker: PhantomData,
+    }
+}
+
+// === impl Layer ===
+
+impl<L, V> Layer<L, V>
+where
+    L: Lazy<V>,
+    V: Send + Sync + 'static,
+{
+    pub fn new(lazy: L) -> Self {
+        Self {
+            lazy,
+            _marker: PhantomData,
+        }
+    }
+
+    /// Like `new`, but values that were already inserted into a request's
+    /// extensions, e.g. by an outer layer, are not overwritten.
+    pub fn new_if_absent(lazy: L) -> IfAbsentLayer<L, V> {
+        IfAbsentLayer {
+            lazy,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<M, L, V> layer::Layer<M> for Layer<L, V>
+where
+    L: Lazy<V>,
+    V: Send + Sync + 'static,
+{
+    type Service = Make<M, L, V>;
+
+    fn layer(&self, inner: M) -> Self::Service {
+        Self::Service {
+            inner,
+            lazy: self.lazy.clone(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+// === impl Make ===
+
+impl<T, M, L, V> tower::Service<T> for Make<M, L, V>
+where
+    M: tower::Service<T>,
+    L: Lazy<V>,
+    V: Send + Sync + 'static,
+{
+    type Response = Service<M::Response, L, V>;
+    type Error = M::Error;
+    type Future = MakeFuture<M::Future, L, V>;
+
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        self.inner.poll_ready()
+    }
+
+    fn call(&mut self, t: T) -> Self::Future {
+        Self::Future {
+            inner: self.inner.call(t),
+            lazy: self.lazy.clone(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+// === impl MakeFuture ===
+
+impl<F, L, V> Future for MakeFuture<F, L, V>
+where
+    F: Future,
+    L: Lazy<V>,
+    V: Send + Sync + 'static,
+{
+    type Item = Service<F::Item, L, V>;
+    type Error = F::Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        let inner = try_ready!(self.inner.poll());
+        let svc = Service::new(inner, self.lazy.clone());
+        Ok(svc.into())
+    }
+}
+
+// === impl Service ===
+
+impl<S, L, V> Service<S, L, V> {
+    fn new(inner: S, lazy: L) -> Self {
+        Self {
+            inner,
+            lazy,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<S, L, V, B> tower::Service<http::Request<B>> for Service<S, L, V>
+where
+    S: tower::Service<http::Request<B>>,
+    L: Lazy<V>,
+    V: Clone + Send + Sync + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        self.inner.poll_ready()
+    }
+
+    fn call(&mut self, mut req: http::Request<B>) -> Self::Future {
+        req.extensions_mut().insert(self.lazy.value());
+        self.inner.call(req)
+    }
+}
+
+// === impl IfAbsentLayer ===
+
+impl<M, L, V> layer::Layer<M> for IfAbsentLayer<L, V>
+where
+    L: Lazy<V>,
+    V: Send + Sync + 'static,
+{
+    type Service = MakeInsertIfAbsent<M, L, V>;
+
+    fn layer(&self, inner: M) -> Self::Service {
+        Self::Service {
+            inner,
+            lazy: self.lazy.clone(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+// === impl MakeInsertIfAbsent ===
+
+impl<T, M, L, V> tower::Service<T> for MakeInsertIfAbsent<M, L, V>
+where
+    M: tower::Service<T>,
+    L: Lazy<V>,
+    V: Send + Sync + 'static,
+{
+    type Response = InsertIfAbsent<M::Response, L, V>;
+    type Error = M::Error;
+    type Future = MakeInsertIfAbsentFuture<M::Future, L, V>;
+
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        self.inner.poll_ready()
+    }
+
+    fn call(&mut self, t: T) -> Self::Future {
+        Self::Future {
+            inner: self.inner.call(t),
+            lazy: self.lazy.clone(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+// === impl MakeInsertIfAbsentFuture ===
+
+impl<F, L, V> Future for MakeInsertIfAbsentFuture<F, L, V>
+where
+    F: Future,
+    L: Lazy<V>,
+    V: Send + Sync + 'static,
+{
+    type Item = InsertIfAbsent<F::Item, L, V>;
+    type Error = F::Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        let inner = try_ready!(self.inner.poll());
+        let svc = InsertIfAbsent {
+            inner,
+            lazy: self.lazy.clone(),
+            _marker: PhantomData,
+        };
+        Ok(svc.into())
+    }
+}
+
+// === impl InsertIfAbsent ===
+
+impl<S, L, V, B> tower::Service<http::Request<B>> for InsertIfAbsent<S, L, V>
+where
+    S: tower::Service<http::Request<B>>,
+    L: Lazy<V>,
+    V: Send + Sync + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        self.inner.poll_ready()
+    }
+
+    fn call(&mut self, mut req: http::Request<B>) -> Self::Future {
+        if req.extensions().get::<V>().is_none() {
+            req.extensions_mut().insert(self.lazy.value());
+        }
+        self.inner.call(req)
+    }
+}
+
+// === impl ConditionalLayer ===
+
+impl<L, V, P> ConditionalLayer<L, V, P>
+where
+    L: Lazy<V>,
+    P: Clone,
+    V: Send + Sync + 'static,
+{
+    pub fn new(lazy: L, predicate: P) -> Self {
+        Self {
+            lazy,
+            predicate,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<M, L, V, P> layer::Layer<M> for ConditionalLayer<L, V, P>
+where
+    L: Lazy<V>,
+    P: Clone,
+    V: Send + Sync + 'static,
+{
+    type Service = MakeConditional<M, L, V, P>;
+
+    fn layer(&self, inner: M) -> Self::Service {
+        Self::Service {
+            inner,
+            lazy: self.lazy.clone(),
+            predicate: self.predicate.clone(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+// === impl MakeConditional ===
+
+impl<T, M, L, V, P> tower::Service<T> for MakeConditional<M, L, V, P>
+where
+    M: tower::Service<T>,
+    L: Lazy<V>,
+    P: Clone,
+    V: Send + Sync + 'static,
+{
+    type Response = ConditionalInsert<M::Response, L, V, P>;
+    type Error = M::Error;
+    type Future = MakeConditionalFuture<M::Future, L, V, P>;
+
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        self.inner.poll_ready()
+    }
+
+    fn call(&mut self, t: T) -> Self::Future {
+        Self::Future {
+            inner: self.inner.call(t),
+            lazy: self.lazy.clone(),
+            predicate: self.predicate.clone(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+// === impl MakeConditionalFuture ===
+
+impl<F, L, V, P> Future for MakeConditionalFuture<F, L, V, P>
+where
+    F: Future,
+    L: Lazy<V>,
+    P: Clone,
+    V: Send + Sync + 'static,
+{
+    type Item = ConditionalInsert<F::Item, L, V, P>;
+    type Error = F::Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        let inner = try_ready!(self.inner.poll());
+        let svc = ConditionalInsert {
+            inner,
+            lazy: self.lazy.clone(),
+            predicate: self.predicate.clone(),
+            _marker: PhantomData,
+        };
+        Ok(svc.into())
+    }
+}
+
+// === impl ConditionalInsert ===
+
+impl<S, L, V, P, B> tower::Service<http::Request<B>> for ConditionalInsert<S, L, V, P>
+where
+    S: tower::Service<http::Request<B>>,
+    L: Lazy<V>,
+    P: Fn(&http::Request<B>) -> bool + Clone,
+    V: Clone + Send + Sync + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        self.inner.poll_ready()
+    }
+
+    fn call(&mut self, mut req: http::Request<B>) -> Self::Future {
+        if (self.predicate)(&req) {
+            req.extensions_mut().insert(self.lazy.value());
+        }
+        self.inner.call(req)
+    }
+}
+
+// === impl ResponseLayer ===
+
+impl<L, V> ResponseLayer<L, V>
+where
+    L: Lazy<V>,
+    V: Send + Sync + 'static,
+{
+    pub fn new(lazy: L) -> Self {
+        Self {
+            lazy,
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<M, L, V> layer::Layer<M> for ResponseLayer<L, V>
+where
+    L: Lazy<V>,
+    V: Send + Sync + 'static,
+{
+    type Service = MakeInsertResponse<M, L, V>;
+
+    fn layer(&self, inner: M) -> Self::Service {
+        Self::Service {
+            inner,
+            lazy: self.lazy.clone(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+// === impl MakeInsertResponse ===
+
+impl<T, M, L, V> tower::Service<T> for MakeInsertResponse<M, L, V>
+where
+    M: tower::Service<T>,
+    L: Lazy<V>,
+    V: Send + Sync + 'static,
+{
+    type Response = InsertResponse<M::Response, L, V>;
+    type Error = M::Error;
+    type Future = MakeInsertResponseFuture<M::Future, L, V>;
+
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        self.inner.poll_ready()
+    }
+
+    fn call(&mut self, t: T) -> Self::Future {
+        Self::Future {
+            inner: self.inner.call(t),
+            lazy: self.lazy.clone(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+// === impl MakeInsertResponseFuture ===
+
+impl<F, L, V> Future for MakeInsertResponseFuture<F, L, V>
+where
+    F: Future,
+    L: Lazy<V>,
+    V: Send + Sync + 'static,
+{
+    type Item = InsertResponse<F::Item, L, V>;
+    type Error = F::Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        let inner = try_ready!(self.inner.poll());
+        let svc = InsertResponse {
+            inner,
+            lazy: self.lazy.clone(),
+            _marker: PhantomData,
+        };
+        Ok(svc.into())
+    }
+}
+
+// === impl InsertResponse ===
 
-pub fn layer<F, V>(f: F) -> Layer<FnLazy<F>, V>
+impl<S, L, V, Req, B> tower::Service<Req> for InsertResponse<S, L, V>
 where
-    F: Fn() -> V + Clone,
+    S: tower::Service<Req, Response = http::Response<B>>,
+    L: Lazy<V>,
     V: Send + Sync + 'static,
 {
-    Layer::new(FnLazy(f))
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = InsertResponseFuture<S::Future, L, V>;
+
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        self.inner.poll_ready()
+    }
+
+    fn call(&mut self, req: Req) -> Self::Future {
+        InsertResponseFuture {
+            inner: self.inner.call(req),
+            lazy: self.lazy.clone(),
+            _marker: PhantomData,
+        }
+    }
 }
 
-// === impl Layer ===
+// === impl InsertResponseFuture ===
 
-impl<L, V> Layer<L, V>
+impl<F, L, V, B> Future for InsertResponseFuture<F, L, V>
 where
+    F: Future<Item = http::Response<B>>,
     L: Lazy<V>,
     V: Send + Sync + 'static,
 {
-    pub fn new(lazy: L) -> Self {
+    type Item = F::Item;
+    type Error = F::Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        let mut rsp = try_ready!(self.inner.poll());
+        rsp.extensions_mut().insert(self.lazy.value());
+        Ok(rsp.into())
+    }
+}
+
+// === impl TransformLayer ===
+
+impl<F, V> TransformLayer<F, V>
+where
+    F: Fn(V) -> V + Clone,
+    V: Send + Sync + 'static,
+{
+    /// Transforms the value returned by `default` when a request has no
+    /// `V`-typed extension.
+    pub fn with_default(self, default: fn() -> V) -> Self {
         Self {
-            lazy,
+            default: Some(FnLazy(default)),
+            ..self
+        }
+    }
+}
+
+impl<M, F, V> layer::Layer<M> for TransformLayer<F, V>
+where
+    F: Fn(V) -> V + Clone,
+    V: Send + Sync + 'static,
+{
+    type Service = MakeTransform<M, F, V>;
+
+    fn layer(&self, inner: M) -> Self::Service {
+        MakeTransform {
+            inner,
+            transform: self.transform.clone(),
+            default: self.default.clone(),
+        }
+    }
+}
+
+// === impl MakeTransform ===
+
+impl<T, M, F, V> tower::Service<T> for MakeTransform<M, F, V>
+where
+    M: tower::Service<T>,
+    F: Fn(V) -> V + Clone,
+    V: Send + Sync + 'static,
+{
+    type Response = Transform<M::Response, F, V>;
+    type Error = M::Error;
+    type Future = MakeTransformFuture<M::Future, F, V>;
+
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        self.inner.poll_ready()
+    }
+
+    fn call(&mut self, t: T) -> Self::Future {
+        MakeTransformFuture {
+            inner: self.inner.call(t),
+            transform: self.transform.clone(),
+            default: self.default.clone(),
+        }
+    }
+}
+
+// === impl MakeTransformFuture ===
+
+impl<Fut, F, V> Future for MakeTransformFuture<Fut, F, V>
+where
+    Fut: Future,
+    F: Fn(V) -> V + Clone,
+    V: Send + Sync + 'static,
+{
+    type Item = Transform<Fut::Item, F, V>;
+    type Error = Fut::Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        let inner = try_ready!(self.inner.poll());
+        let svc = Transform {
+            inner,
+            transform: self.transform.clone(),
+            default: self.default.clone(),
+        };
+        Ok(svc.into())
+    }
+}
+
+// === impl Transform ===
+
+impl<S, F, V, B> tower::Service<http::Request<B>> for Transform<S, F, V>
+where
+    S: tower::Service<http::Request<B>>,
+    F: Fn(V) -> V + Clone,
+    V: Send + Sync + 'static,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        self.inner.poll_ready()
+    }
+
+    fn call(&mut self, mut req: http::Request<B>) -> Self::Future {
+        let prior = req
+            .extensions_mut()
+            .remove::<V>()
+            .or_else(|| self.default.as_ref().map(Lazy::value));
+        if let Some(prior) = prior {
+            req.extensions_mut().insert((self.transform)(prior));
+        }
+        self.inner.call(req)
+    }
+}
+
+// === impl RemoveLayer ===
+
+impl<V, F: Clone> Clone for RemoveLayer<V, F> {
+    fn clone(&self) -> Self {
+        Self {
+            on_remove: self.on_remove.clone(),
             _marker: PhantomData,
         }
     }
 }
 
-impl<M, L, V> layer::Layer<M> for Layer<L, V>
+impl<M, V, F> layer::Layer<M> for RemoveLayer<V, F>
 where
-    L: Lazy<V>,
+    F: Fn(V) + Clone,
     V: Send + Sync + 'static,
 {
-    type Service = Make<M, L, V>;
+    type Service = MakeRemove<M, V, F>;
 
     fn layer(&self, inner: M) -> Self::Service {
-        Self::Service {
+        MakeRemove {
             inner,
-            lazy: self.lazy.clone(),
+            on_remove: self.on_remove.clone(),
             _marker: PhantomData,
         }
     }
 }
 
-// === impl Make ===
+// === impl MakeRemove ===
 
-impl<T, M, L, V> tower::Service<T> for Make<M, L, V>
+impl<M: Clone, V, F: Clone> Clone for MakeRemove<M, V, F> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            on_remove: self.on_remove.clone(),
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T, M, V, F> tower::Service<T> for MakeRemove<M, V, F>
 where
     M: tower::Service<T>,
-    L: Lazy<V>,
+    F: Fn(V) + Clone,
     V: Send + Sync + 'static,
 {
-    type Response = Service<M::Response, L, V>;
+    type Response = Remove<M::Response, V, F>;
     type Error = M::Error;
-    type Future = MakeFuture<M::Future, L, V>;
+    type Future = MakeRemoveFuture<M::Future, V, F>;
 
     fn poll_ready(&mut self) -> Poll<(), Self::Error> {
         self.inner.poll_ready()
     }
 
     fn call(&mut self, t: T) -> Self::Future {
-        Self::Future {
+        MakeRemoveFuture {
             inner: self.inner.call(t),
-            lazy: self.lazy.clone(),
+            on_remove: self.on_remove.clone(),
             _marker: PhantomData,
         }
     }
 }
 
-// === impl MakeFuture ===
+// === impl MakeRemoveFuture ===
 
-impl<F, L, V> Future for MakeFuture<F, L, V>
+impl<Fut, V, F> Future for MakeRemoveFuture<Fut, V, F>
 where
-    F: Future,
-    L: Lazy<V>,
+    Fut: Future,
+    F: Fn(V) + Clone,
     V: Send + Sync + 'static,
 {
-    type Item = Service<F::Item, L, V>;
-    type Error = F::Error;
+    type Item = Remove<Fut::Item, V, F>;
+    type Error = Fut::Error;
 
     fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
         let inner = try_ready!(self.inner.poll());
-        let svc = Service::new(inner, self.lazy.clone());
-        Ok(svc.into())
+        Ok(Remove::new(inner, self.on_remove.clone()).into())
     }
 }
 
-// === impl Service ===
+// === impl Remove ===
 
-impl<S, L, V> Service<S, L, V> {
-    fn new(inner: S, lazy: L) -> Self {
+impl<S, V, F> Remove<S, V, F> {
+    pub fn new(inner: S, on_remove: F) -> Self {
         Self {
             inner,
-            lazy,
+            on_remove,
             _marker: PhantomData,
         }
     }
 }
 
-impl<S, L, V, B> tower::Service<http::Request<B>> for Service<S, L, V>
+impl<S: Clone, V, F: Clone> Clone for Remove<S, V, F> {
+    fn clone(&self) -> Self {
+        Self::new(self.inner.clone(), self.on_remove.clone())
+    }
+}
+
+impl<S, V, F, B> tower::Service<http::Request<B>> for Remove<S, V, F>
 where
     S: tower::Service<http::Request<B>>,
-    L: Lazy<V>,
-    V: Clone + Send + Sync + 'static,
+    F: Fn(V),
+    V: Send + Sync + 'static,
 {
     type Response = S::Response;
     type Error = S::Error;
@@ -150,7 +914,9 @@ where
     }
 
     fn call(&mut self, mut req: http::Request<B>) -> Self::Future {
-        req.extensions_mut().insert(self.lazy.value());
+        if let Some(removed) = req.extensions_mut().remove::<V>() {
+            (self.on_remove)(removed);
+        }
         self.inner.call(req)
     }
 }
@@ -188,12 +954,40 @@ pub mod target {
         target: T,
     }
 
+    /// Like `Make`, but inserts a value derived from the target, e.g. so that
+    /// requests do not carry (or clone) more of the target than they need.
+    #[derive(Clone, Debug)]
+    pub struct MakeMap<M, F> {
+        inner: M,
+        map: F,
+    }
+
+    /// Wraps an HTTP `Service` so that the Stack's `T`-typed target is cloned
+    /// into each response's extensions, e.g. to describe which endpoint served
+    /// the response.
+    #[derive(Clone, Debug)]
+    pub struct MakeResponse<M>(M);
+
     // === impl Layer ===
 
     pub fn layer<M>() -> impl layer::Layer<M, Service = Make<M>> + Copy {
         layer::mk(Make)
     }
 
+    /// Inserts the value returned by `map` into each request's extensions.
+    ///
+    /// `map` is called once for each service that is made, rather than for
+    /// each request.
+    pub fn layer_map<M, F>(map: F) -> impl layer::Layer<M, Service = MakeMap<M, F>> + Clone
+    where
+        F: Clone,
+    {
+        layer::mk(move |inner| MakeMap {
+            inner,
+            map: map.clone(),
+        })
+    }
+
     // === impl Stack ===
 
     impl<T, M> tower::Service<T> for Make<M>
@@ -232,4 +1026,390 @@ pub mod target {
             Ok(svc.into())
         }
     }
+
+    pub fn response_layer<M>() -> impl layer::Layer<M, Service = MakeResponse<M>> + Copy {
+        layer::mk(MakeResponse)
+    }
+
+    // === impl MakeResponse ===
+
+    impl<T, M> tower::Service<T> for MakeResponse<M>
+    where
+        T: Clone + Send + Sync + 'static,
+        M: tower::Service<T>,
+    {
+        type Response = super::InsertResponse<M::Response, super::ValLazy<T>, T>;
+        type Error = M::Error;
+        type Future = super::MakeInsertResponseFuture<M::Future, super::ValLazy<T>, T>;
+
+        fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+            self.0.poll_ready()
+        }
+
+        fn call(&mut self, t: T) -> Self::Future {
+            let lazy = super::ValLazy(t.clone());
+            super::MakeInsertResponseFuture {
+                inner: self.0.call(t),
+                lazy,
+                _marker: PhantomData,
+            }
+        }
+    }
+
+    // === impl MakeMap ===
+
+    impl<T, U, M, F> tower::Service<T> for MakeMap<M, F>
+    where
+        F: Fn(&T) -> U,
+        U: Clone + Send + Sync + 'static,
+        M: tower::Service<T>,
+    {
+        type Response = super::Service<M::Response, super::ValLazy<U>, U>;
+        type Error = M::Error;
+        type Future = MakeFuture<M::Future, U>;
+
+        fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+            self.inner.poll_ready()
+        }
+
+        fn call(&mut self, t: T) -> Self::Future {
+            let target = (self.map)(&t);
+            let inner = self.inner.call(t);
+            MakeFuture { inner, target }
+        }
+    }
+
+    /// Like `Make`, but for future-valued targets: the target is resolved
+    /// before the inner service is made, and its resolved value is cloned
+    /// into each request's extensions.
+    #[derive(Clone, Debug)]
+    pub struct AsyncTargetMake<M>(M);
+
+    pub enum AsyncMakeFuture<T: Future, M: tower::Service<T::Item>> {
+        Resolve { target: T, make: Option<M> },
+        Ready { target: Option<T::Item>, make: M },
+        Make { inner: M::Future, target: T::Item },
+    }
+
+    pub fn async_target_layer<M>() -> impl layer::Layer<M, Service = AsyncTargetMake<M>> + Copy {
+        layer::mk(AsyncTargetMake)
+    }
+
+    // === impl AsyncTargetMake ===
+
+    impl<T, M> tower::Service<T> for AsyncTargetMake<M>
+    where
+        T: Future,
+        T::Item: Clone + Send + Sync + 'static,
+        T::Error: Into<Error>,
+        M: tower::Service<T::Item> + Clone,
+        M::Error: Into<Error>,
+    {
+        type Response = super::Service<M::Response, super::ValLazy<T::Item>, T::Item>;
+        type Error = Error;
+        type Future = AsyncMakeFuture<T, M>;
+
+        fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+            self.0.poll_ready().map_err(Into::into)
+        }
+
+        fn call(&mut self, target: T) -> Self::Future {
+            // The inner service is only called once the target is resolved,
+            // so the future drives its own clone to readiness.
+            AsyncMakeFuture::Resolve {
+                target,
+                make: Some(self.0.clone()),
+            }
+        }
+    }
+
+    // === impl AsyncMakeFuture ===
+
+    impl<T, M> Future for AsyncMakeFuture<T, M>
+    where
+        T: Future,
+        T::Item: Clone,
+        T::Error: Into<Error>,
+        M: tower::Service<T::Item>,
+        M::Error: Into<Error>,
+    {
+        type Item = super::Service<M::Response, ValLazy<T::Item>, T::Item>;
+        type Error = Error;
+
+        fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+            loop {
+                *self = match self {
+                    AsyncMakeFuture::Resolve {
+                        ref mut target,
+                        ref mut make,
+                    } => {
+                        let target = try_ready!(target.poll().map_err(Into::into));
+                        AsyncMakeFuture::Ready {
+                            target: Some(target),
+                            make: make.take().expect("polled after ready"),
+                        }
+                    }
+                    AsyncMakeFuture::Ready {
+                        ref mut target,
+                        ref mut make,
+                    } => {
+                        try_ready!(make.poll_ready().map_err(Into::into));
+                        let target = target.take().expect("polled after ready");
+                        let inner = make.call(target.clone());
+                        AsyncMakeFuture::Make { inner, target }
+                    }
+                    AsyncMakeFuture::Make {
+                        ref mut inner,
+                        ref target,
+                    } => {
+                        let inner = try_ready!(inner.poll().map_err(Into::into));
+                        let svc = super::Service::new(inner, super::ValLazy(target.clone()));
+                        return Ok(svc.into());
+                    }
+                };
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::future;
+    use tower::Service as _;
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct Marker;
+
+    #[test]
+    fn conditional_inserts_only_matching_requests() {
+        let layer = conditional(
+            || Marker,
+            |req: &http::Request<()>| !req.headers().contains_key("x-skip"),
+        );
+        let inner = tower::service_fn(|req: http::Request<()>| {
+            future::ok::<_, ()>(req.extensions().get::<Marker>().cloned())
+        });
+        let make = tower::service_fn(move |()| future::ok::<_, ()>(inner.clone()));
+        let mut svc = layer::Layer::layer(&layer, make).call(()).wait().unwrap();
+
+        let req = http::Request::new(());
+        assert_eq!(svc.call(req).wait().unwrap(), Some(Marker));
+
+        let req = http::Request::builder()
+            .header("x-skip", "1")
+            .body(())
+            .unwrap();
+        assert_eq!(svc.call(req).wait().unwrap(), None);
+    }
+
+    #[test]
+    fn if_absent_preserves_outer_values() {
+        #[derive(Clone, Debug, PartialEq)]
+        struct Name(&'static str);
+
+        let inner = tower::service_fn(|req: http::Request<()>| {
+            future::ok::<_, ()>(req.extensions().get::<Name>().cloned())
+        });
+        let outer = inner.clone();
+        let make = tower::service_fn(move |()| future::ok::<_, ()>(outer.clone()));
+        let make = layer::Layer::layer(&Layer::new_if_absent(FnLazy(|| Name("inner"))), make);
+        let make = layer::Layer::layer(&Layer::new(FnLazy(|| Name("outer"))), make);
+        let mut svc = make_svc(make);
+
+        let req = http::Request::new(());
+        assert_eq!(svc.call(req).wait().unwrap(), Some(Name("outer")));
+
+        // Without an outer value, the inner value is inserted.
+        let make = tower::service_fn(move |()| future::ok::<_, ()>(inner.clone()));
+        let mut svc = make_svc(layer::Layer::layer(
+            &Layer::new_if_absent(FnLazy(|| Name("inner"))),
+            make,
+        ));
+        let req = http::Request::new(());
+        assert_eq!(svc.call(req).wait().unwrap(), Some(Name("inner")));
+    }
+
+    #[test]
+    fn response_layer_inserts_into_responses() {
+        let inner = tower::service_fn(|req: http::Request<()>| {
+            assert!(req.extensions().get::<Marker>().is_none());
+            future::ok::<_, ()>(http::Response::new(()))
+        });
+        let make = tower::service_fn(move |()| future::ok::<_, ()>(inner.clone()));
+        let mut svc = layer::Layer::layer(&response_layer(|| Marker), make)
+            .call(())
+            .wait()
+            .unwrap();
+
+        let rsp = svc.call(http::Request::new(())).wait().unwrap();
+        assert_eq!(rsp.extensions().get::<Marker>(), Some(&Marker));
+    }
+
+    #[test]
+    fn target_response_layer_inserts_target_into_responses() {
+        let inner =
+            tower::service_fn(|_: http::Request<()>| future::ok::<_, ()>(http::Response::new(())));
+        let make = tower::service_fn(move |_: Marker| future::ok::<_, ()>(inner.clone()));
+        let mut svc = layer::Layer::layer(&target::response_layer(), make)
+            .call(Marker)
+            .wait()
+            .unwrap();
+
+        // An outer layer observes the target on the response.
+        let rsp = svc.call(http::Request::new(())).wait().unwrap();
+        assert_eq!(rsp.extensions().get::<Marker>(), Some(&Marker));
+    }
+
+    #[test]
+    fn async_target_inserts_resolved_target() {
+        let inner = tower::service_fn(|req: http::Request<()>| {
+            future::ok::<_, ()>(req.extensions().get::<Marker>().cloned())
+        });
+        let make = tower::service_fn(move |_: Marker| future::ok::<_, ()>(inner.clone()));
+        let (tx, rx) = futures::sync::oneshot::channel::<Marker>();
+        let mut make = layer::Layer::layer(&target::async_target_layer(), make);
+
+        // The inner service is not made until the target is resolved.
+        let mut making = make.call(rx);
+        let poll = future::lazy(|| making.poll()).wait().unwrap();
+        assert!(poll.is_not_ready());
+
+        tx.send(Marker).unwrap();
+        let mut svc = making.wait().unwrap();
+        let req = http::Request::new(());
+        assert_eq!(svc.call(req).wait().unwrap(), Some(Marker));
+    }
+
+    #[test]
+    fn target_map_runs_once_per_service() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+
+        let maps = Arc::new(AtomicUsize::new(0));
+        let map = {
+            let maps = maps.clone();
+            move |t: &usize| {
+                maps.fetch_add(1, Ordering::SeqCst);
+                Hops(*t)
+            }
+        };
+
+        let inner = tower::service_fn(|req: http::Request<()>| {
+            future::ok::<_, ()>(req.extensions().get::<Hops>().cloned())
+        });
+        let make = tower::service_fn(move |_: usize| future::ok::<_, ()>(inner.clone()));
+        let mut make = layer::Layer::layer(&target::layer_map(map), make);
+
+        let mut svc = make.call(2).wait().unwrap();
+        for _ in 0..3 {
+            let req = http::Request::new(());
+            assert_eq!(svc.call(req).wait().unwrap(), Some(Hops(2)));
+        }
+        assert_eq!(maps.load(Ordering::SeqCst), 1);
+
+        let mut svc = make.call(5).wait().unwrap();
+        let req = http::Request::new(());
+        assert_eq!(svc.call(req).wait().unwrap(), Some(Hops(5)));
+        assert_eq!(maps.load(Ordering::SeqCst), 2);
+    }
+
+    #[derive(Clone, Debug, PartialEq)]
+    struct Hops(usize);
+
+    fn make_svc<M>(make: M) -> M::Response
+    where
+        M: tower::Service<()>,
+        M::Error: std::fmt::Debug,
+    {
+        let mut make = make;
+        make.call(()).wait().unwrap()
+    }
+
+    #[test]
+    fn transform_replaces_prior_value() {
+        let inner = tower::service_fn(|req: http::Request<()>| {
+            future::ok::<_, ()>(req.extensions().get::<Hops>().cloned())
+        });
+        let make = tower::service_fn(move |()| future::ok::<_, ()>(inner.clone()));
+        let mut svc = make_svc(layer::Layer::layer(
+            &transform_layer(|Hops(n)| Hops(n + 1)),
+            make,
+        ));
+
+        let mut req = http::Request::new(());
+        req.extensions_mut().insert(Hops(2));
+        assert_eq!(svc.call(req).wait().unwrap(), Some(Hops(3)));
+    }
+
+    #[test]
+    fn transform_without_prior_value() {
+        let inner = tower::service_fn(|req: http::Request<()>| {
+            future::ok::<_, ()>(req.extensions().get::<Hops>().cloned())
+        });
+        let make = tower::service_fn(move |()| future::ok::<_, ()>(inner.clone()));
+
+        // Without a default, the request is not modified.
+        let layer = transform_layer(|Hops(n)| Hops(n + 1));
+        let mut svc = make_svc(layer::Layer::layer(&layer, make.clone()));
+        assert_eq!(svc.call(http::Request::new(())).wait().unwrap(), None);
+
+        // Otherwise, the default is transformed.
+        let layer = layer.with_default(|| Hops(0));
+        let mut svc = make_svc(layer::Layer::layer(&layer, make));
+        assert_eq!(
+            svc.call(http::Request::new(())).wait().unwrap(),
+            Some(Hops(1))
+        );
+    }
+
+    #[test]
+    fn remove_clears_only_the_removed_type() {
+        #[derive(Clone, Debug, PartialEq)]
+        struct Other(usize);
+
+        let inner = tower::service_fn(|req: http::Request<()>| {
+            let exts = req.extensions();
+            future::ok::<_, ()>((exts.get::<Marker>().cloned(), exts.get::<Other>().cloned()))
+        });
+        let make = tower::service_fn(move |()| future::ok::<_, ()>(inner.clone()));
+        let mut svc = layer::Layer::layer(&remove_layer::<Marker>(), make)
+            .call(())
+            .wait()
+            .unwrap();
+
+        let mut req = http::Request::new(());
+        req.extensions_mut().insert(Marker);
+        req.extensions_mut().insert(Other(7));
+        assert_eq!(svc.call(req).wait().unwrap(), (None, Some(Other(7))));
+
+        let req = http::Request::new(());
+        assert_eq!(svc.call(req).wait().unwrap(), (None, None));
+    }
+
+    #[test]
+    fn remove_with_observes_removed_values() {
+        use std::sync::{Arc, Mutex};
+
+        let removed = Arc::new(Mutex::new(Vec::new()));
+        let on_remove = {
+            let removed = removed.clone();
+            move |hops: Hops| removed.lock().unwrap().push(hops)
+        };
+
+        let inner = tower::service_fn(|req: http::Request<()>| {
+            future::ok::<_, ()>(req.extensions().get::<Hops>().cloned())
+        });
+        let make = tower::service_fn(move |()| future::ok::<_, ()>(inner.clone()));
+        let mut svc = make_svc(layer::Layer::layer(&remove_layer_with(on_remove), make));
+
+        let mut req = http::Request::new(());
+        req.extensions_mut().insert(Hops(3));
+        assert_eq!(svc.call(req).wait().unwrap(), None);
+
+        // Requests without a value are passed through.
+        assert_eq!(svc.call(http::Request::new(())).wait().unwrap(), None);
+
+        assert_eq!(*removed.lock().unwrap(), vec![Hops(3)]);
+    }
 }
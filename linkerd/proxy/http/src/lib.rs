@@ -10,6 +10,7 @@ pub mod balance;
 pub mod boxed;
 pub mod canonicalize;
 pub mod client;
+pub mod expect_continue;
 pub mod glue;
 pub mod grpc;
 pub mod h1;
@@ -20,11 +21,14 @@ pub mod metrics;
 pub mod normalize_uri;
 pub mod orig_proto;
 pub mod profiles;
+pub mod replay;
+pub mod request_timeout;
 pub mod retry;
 pub mod settings;
 pub mod strip_header;
 pub mod timeout;
 pub mod upgrade;
+pub mod validate_response;
 mod version;
 
 pub use self::{
@@ -84,6 +88,23 @@ where
     })
 }
 
+/// Returns every identity named by `header`.
+///
+/// The header may be repeated, and each value may hold a comma-separated
+/// list of names. Values that are not valid names are skipped.
+pub fn identities_from_header<B, K>(req: &http::Request<B>, header: K) -> Vec<identity::Name>
+where
+    K: AsHeaderName,
+{
+    req.headers()
+        .get_all(header)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|s| s.split(','))
+        .filter_map(|s| identity::Name::from_hostname(s.trim().as_bytes()).ok())
+        .collect()
+}
+
 fn header_value_from_request<B, K, F, T>(
     req: &http::Request<B>,
     header: K,
@@ -1,6 +1,6 @@
 use http;
-use indexmap::IndexMap;
-use linkerd2_metrics::{latency, Counter, FmtLabels, Histogram};
+use indexmap::{map::Entry, IndexMap};
+use linkerd2_metrics::{latency, Counter, FmtLabels, Histogram, Prerendered};
 use std::hash::Hash;
 use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
@@ -11,7 +11,10 @@ pub mod handle_time;
 mod report;
 mod service;
 
-pub use self::{report::Report, service::layer};
+pub use self::{
+    report::Report,
+    service::{layer, Layer},
+};
 
 pub type SharedRegistry<T, C> = Arc<Mutex<Registry<T, C>>>;
 
@@ -30,7 +33,28 @@ where
     T: Hash + Eq,
     C: Hash + Eq,
 {
-    by_target: IndexMap<T, Arc<Mutex<RequestMetrics<C>>>>,
+    by_target: IndexMap<T, TargetMetrics<C>>,
+}
+
+/// Holds a target's metrics along with its labels, which are rendered when
+/// the target is registered so that they are not formatted on each scrape.
+#[derive(Debug)]
+struct TargetMetrics<C>
+where
+    C: Hash + Eq,
+{
+    labels: Prerendered,
+    metrics: Arc<Mutex<RequestMetrics<C>>>,
+}
+
+/// Determines when the first byte of a response to an `Expect: 100-continue`
+/// request is considered to have been received.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum ContinueTtfb {
+    /// When the final response headers are received.
+    ResponseHeaders,
+    /// When the first frame of the response body is received.
+    FirstBodyFrame,
 }
 
 pub trait Scoped<T> {
@@ -49,6 +73,8 @@ where
 {
     last_update: Instant,
     total: Counter,
+    request_bytes: Counter,
+    response_bytes: Counter,
     by_retry_skipped: IndexMap<RetrySkipped, Counter>,
     by_status: IndexMap<Option<http::StatusCode>, StatusMetrics<C>>,
 }
@@ -58,7 +84,11 @@ struct StatusMetrics<C>
 where
     C: Hash + Eq,
 {
-    latency: Histogram<latency::Ms>,
+    /// Latencies and times to first byte distinguish responses that were
+    /// received on a reused connection, if the client described the
+    /// connection.
+    latency: IndexMap<Option<ConnectionReused>, Histogram<latency::Ms>>,
+    ttfb: IndexMap<Option<ConnectionReused>, Histogram<latency::Ms>>,
     by_class: IndexMap<C, ClassMetrics>,
 }
 
@@ -72,6 +102,9 @@ enum RetrySkipped {
     Budget,
 }
 
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
+struct ConnectionReused(bool);
+
 impl<T, C> Default for Registry<T, C>
 where
     T: Hash + Eq,
@@ -92,15 +125,41 @@ where
     /// Retains metrics for all targets that (1) no longer have an active
     /// reference to the `RequestMetrics` structure and (2) have not been updated since `epoch`.
     fn retain_since(&mut self, epoch: Instant) {
-        self.by_target.retain(|_, m| {
-            Arc::strong_count(&m) > 1 || m.lock().map(|m| m.last_update >= epoch).unwrap_or(false)
+        self.by_target.retain(|_, t| {
+            Arc::strong_count(&t.metrics) > 1
+                || t.metrics
+                    .lock()
+                    .map(|m| m.last_update >= epoch)
+                    .unwrap_or(false)
         })
     }
 }
 
+impl<T, C> Registry<T, C>
+where
+    T: FmtLabels + Hash + Eq,
+    C: Hash + Eq,
+{
+    /// Returns the metrics for `target`, registering the target if it is new.
+    fn get_or_insert(&mut self, target: T) -> Arc<Mutex<RequestMetrics<C>>> {
+        match self.by_target.entry(target) {
+            Entry::Occupied(e) => e.get().metrics.clone(),
+            Entry::Vacant(e) => {
+                let labels = Prerendered::new(e.key());
+                let metrics = Arc::new(Mutex::new(RequestMetrics::default()));
+                e.insert(TargetMetrics {
+                    labels,
+                    metrics: metrics.clone(),
+                });
+                metrics
+            }
+        }
+    }
+}
+
 impl<T, C> Scoped<T> for Arc<Mutex<Registry<T, C>>>
 where
-    T: Hash + Eq,
+    T: FmtLabels + Hash + Eq,
     C: Hash + Eq,
 {
     type Scope = Arc<Mutex<RequestMetrics<C>>>;
@@ -108,10 +167,7 @@ where
     fn scoped(&self, target: T) -> Self::Scope {
         self.lock()
             .expect("metrics Registry lock")
-            .by_target
-            .entry(target)
-            .or_insert_with(|| Arc::new(Mutex::new(RequestMetrics::default())))
-            .clone()
+            .get_or_insert(target)
     }
 }
 
@@ -135,6 +191,8 @@ where
         Self {
             last_update: clock::now(),
             total: Counter::default(),
+            request_bytes: Counter::default(),
+            response_bytes: Counter::default(),
             by_retry_skipped: IndexMap::default(),
             by_status: IndexMap::default(),
         }
@@ -159,7 +217,8 @@ where
 {
     fn default() -> Self {
         Self {
-            latency: Histogram::default(),
+            latency: IndexMap::default(),
+            ttfb: IndexMap::default(),
             by_class: IndexMap::default(),
         }
     }
@@ -203,11 +262,7 @@ mod tests {
         let mut registry = r.lock().unwrap();
 
         let before_update = clock::now();
-        let metrics = registry
-            .by_target
-            .entry(Target(123))
-            .or_insert_with(|| Default::default())
-            .clone();
+        let metrics = registry.get_or_insert(Target(123));
         assert_eq!(registry.by_target.len(), 1, "target should be registered");
         let after_update = clock::now();
 
@@ -1,5 +1,8 @@
-use super::{ClassMetrics, Registry, RequestMetrics, RetrySkipped, StatusMetrics};
+use super::{
+    ClassMetrics, ConnectionReused, Registry, RequestMetrics, RetrySkipped, StatusMetrics,
+};
 use http;
+use indexmap::IndexMap;
 use linkerd2_metrics::{latency, Counter, FmtLabels, FmtMetric, FmtMetrics, Histogram, Metric};
 use std::fmt;
 use std::hash::Hash;
@@ -26,8 +29,11 @@ struct Status(http::StatusCode);
 #[derive(Clone, Debug)]
 struct Scope {
     request_total_key: String,
+    request_bytes_total_key: String,
     response_total_key: String,
+    response_bytes_total_key: String,
     response_latency_ms_key: String,
+    response_ttfb_ms_key: String,
     retry_skipped_total_key: String,
 }
 
@@ -95,8 +101,17 @@ where
         self.scope.request_total().fmt_help(f)?;
         registry.fmt_by_target(f, self.scope.request_total(), |s| &s.total)?;
 
+        self.scope.request_bytes_total().fmt_help(f)?;
+        registry.fmt_by_target(f, self.scope.request_bytes_total(), |s| &s.request_bytes)?;
+
+        self.scope.response_bytes_total().fmt_help(f)?;
+        registry.fmt_by_target(f, self.scope.response_bytes_total(), |s| &s.response_bytes)?;
+
         self.scope.response_latency_ms().fmt_help(f)?;
-        registry.fmt_by_status(f, self.scope.response_latency_ms(), |s| &s.latency)?;
+        registry.fmt_by_connection(f, self.scope.response_latency_ms(), |s| &s.latency)?;
+
+        self.scope.response_ttfb_ms().fmt_help(f)?;
+        registry.fmt_by_connection(f, self.scope.response_ttfb_ms(), |s| &s.ttfb)?;
 
         self.scope.response_total().fmt_help(f)?;
         registry.fmt_by_class(f, self.scope.response_total(), |s| &s.total)?;
@@ -123,9 +138,9 @@ where
         M: FmtMetric,
         F: Fn(&RequestMetrics<C>) -> &M,
     {
-        for (tgt, tm) in &self.by_target {
-            if let Ok(m) = tm.lock() {
-                get_metric(&*m).fmt_metric_labeled(f, metric.name, tgt)?;
+        for tm in self.by_target.values() {
+            if let Ok(m) = tm.metrics.lock() {
+                get_metric(&*m).fmt_metric_labeled(f, metric.name, &tm.labels)?;
             }
         }
 
@@ -136,8 +151,9 @@ where
     where
         M: FmtMetric,
     {
-        for (tgt, tm) in &self.by_target {
-            if let Ok(tm) = tm.lock() {
+        for t in self.by_target.values() {
+            let tgt = &t.labels;
+            if let Ok(tm) = t.metrics.lock() {
                 for (retry, m) in &tm.by_retry_skipped {
                     let labels = (tgt, retry);
                     m.fmt_metric_labeled(f, metric.name, labels)?;
@@ -148,7 +164,7 @@ where
         Ok(())
     }
 
-    fn fmt_by_status<M, F>(
+    fn fmt_by_connection<M, F>(
         &self,
         f: &mut fmt::Formatter<'_>,
         metric: Metric<'_, M>,
@@ -156,14 +172,17 @@ where
     ) -> fmt::Result
     where
         M: FmtMetric,
-        F: Fn(&StatusMetrics<C>) -> &M,
+        F: Fn(&StatusMetrics<C>) -> &IndexMap<Option<ConnectionReused>, M>,
     {
-        for (tgt, tm) in &self.by_target {
-            if let Ok(tm) = tm.lock() {
-                for (status, m) in &tm.by_status {
-                    let status = status.as_ref().map(|s| Status(*s));
-                    let labels = (tgt, status);
-                    get_metric(&*m).fmt_metric_labeled(f, metric.name, labels)?;
+        for t in self.by_target.values() {
+            let tgt = &t.labels;
+            if let Ok(tm) = t.metrics.lock() {
+                for (status, sm) in &tm.by_status {
+                    for (reused, m) in get_metric(sm) {
+                        let status = status.as_ref().map(|s| Status(*s));
+                        let labels = ((tgt, status), reused.as_ref());
+                        m.fmt_metric_labeled(f, metric.name, labels)?;
+                    }
                 }
             }
         }
@@ -181,8 +200,9 @@ where
         M: FmtMetric,
         F: Fn(&ClassMetrics) -> &M,
     {
-        for (tgt, tm) in &self.by_target {
-            if let Ok(tm) = tm.lock() {
+        for t in self.by_target.values() {
+            let tgt = &t.labels;
+            if let Ok(tm) = t.metrics.lock() {
                 for (status, sm) in &tm.by_status {
                     for (cls, m) in &sm.by_class {
                         let status = status.as_ref().map(|s| Status(*s));
@@ -203,8 +223,11 @@ impl Default for Scope {
     fn default() -> Self {
         Self {
             request_total_key: "request_total".to_owned(),
+            request_bytes_total_key: "request_bytes_total".to_owned(),
             response_total_key: "response_total".to_owned(),
+            response_bytes_total_key: "response_bytes_total".to_owned(),
             response_latency_ms_key: "response_latency_ms".to_owned(),
+            response_ttfb_ms_key: "response_ttfb_ms".to_owned(),
             retry_skipped_total_key: "retry_skipped_total".to_owned(),
         }
     }
@@ -218,8 +241,11 @@ impl Scope {
 
         Self {
             request_total_key: format!("{}_request_total", prefix),
+            request_bytes_total_key: format!("{}_request_bytes_total", prefix),
             response_total_key: format!("{}_response_total", prefix),
+            response_bytes_total_key: format!("{}_response_bytes_total", prefix),
             response_latency_ms_key: format!("{}_response_latency_ms", prefix),
+            response_ttfb_ms_key: format!("{}_response_ttfb_ms", prefix),
             retry_skipped_total_key: format!("{}_retry_skipped_total", prefix),
         }
     }
@@ -228,10 +254,24 @@ impl Scope {
         Metric::new(&self.request_total_key, &Self::REQUEST_TOTAL_HELP)
     }
 
+    fn request_bytes_total(&self) -> Metric<'_, Counter> {
+        Metric::new(
+            &self.request_bytes_total_key,
+            &Self::REQUEST_BYTES_TOTAL_HELP,
+        )
+    }
+
     fn response_total(&self) -> Metric<'_, Counter> {
         Metric::new(&self.response_total_key, &Self::RESPONSE_TOTAL_HELP)
     }
 
+    fn response_bytes_total(&self) -> Metric<'_, Counter> {
+        Metric::new(
+            &self.response_bytes_total_key,
+            &Self::RESPONSE_BYTES_TOTAL_HELP,
+        )
+    }
+
     fn response_latency_ms(&self) -> Metric<'_, Histogram<latency::Ms>> {
         Metric::new(
             &self.response_latency_ms_key,
@@ -239,6 +279,10 @@ impl Scope {
         )
     }
 
+    fn response_ttfb_ms(&self) -> Metric<'_, Histogram<latency::Ms>> {
+        Metric::new(&self.response_ttfb_ms_key, &Self::RESPONSE_TTFB_MS_HELP)
+    }
+
     fn retry_skipped_total(&self) -> Metric<'_, Counter> {
         Metric::new(
             &self.retry_skipped_total_key,
@@ -248,12 +292,22 @@ impl Scope {
 
     const REQUEST_TOTAL_HELP: &'static str = "Total count of HTTP requests.";
 
+    const REQUEST_BYTES_TOTAL_HELP: &'static str =
+        "Total count of HTTP request body bytes forwarded.";
+
     const RESPONSE_TOTAL_HELP: &'static str = "Total count of HTTP responses.";
 
+    const RESPONSE_BYTES_TOTAL_HELP: &'static str =
+        "Total count of HTTP response body bytes forwarded.";
+
     const RESPONSE_LATENCY_MS_HELP: &'static str =
         "Elapsed times between a request's headers being received \
          and its response stream completing";
 
+    const RESPONSE_TTFB_MS_HELP: &'static str =
+        "Elapsed times between a request being dispatched and its response \
+         headers being received";
+
     const RETRY_SKIPPED_TOTAL_HELP: &'static str =
         "Total count of retryable HTTP responses that were not retried.";
 }
@@ -264,6 +318,12 @@ impl FmtLabels for Status {
     }
 }
 
+impl FmtLabels for ConnectionReused {
+    fn fmt_labels(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "connection_reused=\"{}\"", self.0)
+    }
+}
+
 impl FmtLabels for RetrySkipped {
     fn fmt_labels(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
         write!(
@@ -1,15 +1,20 @@
+use super::super::client::{ConnectionInfo, ResponseHeadersAt};
 use super::super::retry::TryClone;
 use super::classify::{ClassifyEos, ClassifyResponse};
-use super::{ClassMetrics, Registry, RequestMetrics, StatusMetrics};
+use super::{
+    ClassMetrics, ConnectionReused, ContinueTtfb, Registry, RequestMetrics, StatusMetrics,
+};
+use bytes::Buf;
 use futures::{try_ready, Async, Future, Poll};
 use http;
 use hyper::body::Payload;
 use linkerd2_error::Error;
-use std::fmt::Debug;
+use linkerd2_metrics::{Counter, FmtLabels, Histogram};
+use std::fmt::{self, Debug};
 use std::hash::Hash;
 use std::marker::PhantomData;
 use std::sync::{Arc, Mutex};
-use std::time::Instant;
+use std::time::{Duration, Instant};
 use tokio_timer::clock;
 use tracing::trace;
 
@@ -22,9 +27,15 @@ where
     C::Class: Hash + Eq,
 {
     registry: Arc<Mutex<Registry<K, C::Class>>>,
+    continue_ttfb: ContinueTtfb,
+    label_request: Option<LabelRequest<K>>,
     _p: PhantomData<fn() -> C>,
 }
 
+/// Determines the labels of an individual request from its target's labels
+/// and the request's extensions, if they differ from the target's.
+struct LabelRequest<K>(fn(&K, &http::Extensions) -> Option<K>);
+
 /// Wraps services to record metrics.
 #[derive(Debug)]
 pub struct MakeSvc<M, K, C>
@@ -34,6 +45,8 @@ where
     C::Class: Hash + Eq,
 {
     registry: Arc<Mutex<Registry<K, C::Class>>>,
+    continue_ttfb: ContinueTtfb,
+    label_request: Option<LabelRequest<K>>,
     inner: M,
     _p: PhantomData<fn() -> C>,
 }
@@ -44,6 +57,8 @@ where
     C::Class: Hash + Eq,
 {
     metrics: Option<Arc<Mutex<RequestMetrics<C::Class>>>>,
+    by_request: Option<ByRequest<C::Class>>,
+    continue_ttfb: ContinueTtfb,
     inner: F,
     _p: PhantomData<fn() -> C>,
 }
@@ -56,10 +71,17 @@ where
     C::Class: Hash + Eq,
 {
     metrics: Option<Arc<Mutex<RequestMetrics<C::Class>>>>,
+    by_request: Option<ByRequest<C::Class>>,
+    continue_ttfb: ContinueTtfb,
     inner: S,
     _p: PhantomData<fn() -> C>,
 }
 
+/// Looks up the metrics of a request whose labels differ from its target's.
+struct ByRequest<C: Hash + Eq>(
+    Arc<dyn Fn(&http::Extensions) -> Option<Arc<Mutex<RequestMetrics<C>>>> + Send + Sync>,
+);
+
 pub struct ResponseFuture<F, C>
 where
     C: ClassifyResponse,
@@ -68,6 +90,9 @@ where
     classify: Option<C>,
     metrics: Option<Arc<Mutex<RequestMetrics<C::Class>>>>,
     stream_open_at: Instant,
+    /// If true, the time to first byte is measured when the first body frame
+    /// is received, rather than when the response headers are received.
+    ttfb_at_first_frame: bool,
     inner: F,
 }
 
@@ -78,6 +103,8 @@ where
     C: Hash + Eq,
 {
     metrics: Option<Arc<Mutex<RequestMetrics<C>>>>,
+    /// Counts the body's bytes; held for the lifetime of the body.
+    bytes: Option<Arc<Mutex<RequestMetrics<C>>>>,
     inner: B,
 }
 
@@ -91,8 +118,13 @@ where
     status: http::StatusCode,
     classify: Option<C>,
     metrics: Option<Arc<Mutex<RequestMetrics<C::Class>>>>,
+    /// Counts the body's bytes; held for the lifetime of the body.
+    bytes: Option<Arc<Mutex<RequestMetrics<C::Class>>>>,
     stream_open_at: Instant,
+    /// Whether the response was received on a reused connection, if known.
+    connection_reused: Option<bool>,
     latency_recorded: bool,
+    ttfb_recorded: bool,
     inner: B,
 }
 
@@ -106,10 +138,43 @@ where
 {
     Layer {
         registry,
+        continue_ttfb: ContinueTtfb::ResponseHeaders,
+        label_request: None,
         _p: PhantomData,
     }
 }
 
+impl<K, C> Layer<K, C>
+where
+    K: Hash + Eq,
+    C: ClassifyResponse,
+    C::Class: Hash + Eq,
+{
+    /// Configures when the time to first byte is measured for responses to
+    /// `Expect: 100-continue` requests. By default, it is measured when the
+    /// final response headers are received.
+    pub fn with_continue_ttfb(self, continue_ttfb: ContinueTtfb) -> Self {
+        Self {
+            continue_ttfb,
+            ..self
+        }
+    }
+
+    /// Records each request's metrics with the labels returned by
+    /// `label_request`, e.g. to break a target's metrics down by route.
+    /// Requests for which it returns `None` are recorded with the target's
+    /// labels.
+    pub fn with_request_labels(
+        self,
+        label_request: fn(&K, &http::Extensions) -> Option<K>,
+    ) -> Self {
+        Self {
+            label_request: Some(LabelRequest(label_request)),
+            ..self
+        }
+    }
+}
+
 impl<K, C> Clone for Layer<K, C>
 where
     K: Hash + Eq,
@@ -119,6 +184,8 @@ where
     fn clone(&self) -> Self {
         Self {
             registry: self.registry.clone(),
+            continue_ttfb: self.continue_ttfb,
+            label_request: self.label_request.clone(),
             _p: PhantomData,
         }
     }
@@ -136,11 +203,27 @@ where
         MakeSvc {
             inner,
             registry: self.registry.clone(),
+            continue_ttfb: self.continue_ttfb,
+            label_request: self.label_request.clone(),
             _p: PhantomData,
         }
     }
 }
 
+// === impl LabelRequest ===
+
+impl<K> Clone for LabelRequest<K> {
+    fn clone(&self) -> Self {
+        LabelRequest(self.0)
+    }
+}
+
+impl<K> Debug for LabelRequest<K> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("LabelRequest").finish()
+    }
+}
+
 // === impl MakeSvc ===
 
 impl<M, K, C> Clone for MakeSvc<M, K, C>
@@ -154,6 +237,8 @@ where
         Self {
             inner: self.inner.clone(),
             registry: self.registry.clone(),
+            continue_ttfb: self.continue_ttfb,
+            label_request: self.label_request.clone(),
             _p: PhantomData,
         }
     }
@@ -162,10 +247,10 @@ where
 impl<T, M, K, C> tower::Service<T> for MakeSvc<M, K, C>
 where
     T: Clone + Debug + Into<K>,
-    K: Hash + Eq,
+    K: FmtLabels + Clone + Hash + Eq + Send + Sync + 'static,
     M: tower::Service<T>,
     C: ClassifyResponse + Default + Send + Sync + 'static,
-    C::Class: Hash + Eq,
+    C::Class: Hash + Eq + Send + 'static,
 {
     type Response = Service<M::Response, C>;
     type Error = M::Error;
@@ -177,13 +262,17 @@ where
 
     fn call(&mut self, target: T) -> Self::Future {
         trace!("make: target={:?}", target);
+        let labels: K = target.clone().into();
+        let by_request = self.label_request.clone().map(|LabelRequest(label)| {
+            let registry = self.registry.clone();
+            let labels = labels.clone();
+            ByRequest(Arc::new(move |ext: &http::Extensions| {
+                let labels = label(&labels, ext)?;
+                registry.lock().ok().map(|mut r| r.get_or_insert(labels))
+            }))
+        });
         let metrics = match self.registry.lock() {
-            Ok(mut r) => Some(
-                r.by_target
-                    .entry(target.clone().into())
-                    .or_insert_with(|| Arc::new(Mutex::new(RequestMetrics::default())))
-                    .clone(),
-            ),
+            Ok(mut r) => Some(r.get_or_insert(labels)),
             Err(_) => None,
         };
         trace!("make: metrics={}", metrics.is_some());
@@ -192,12 +281,28 @@ where
 
         MakeFuture {
             metrics,
+            by_request,
+            continue_ttfb: self.continue_ttfb,
             inner,
             _p: PhantomData,
         }
     }
 }
 
+// === impl ByRequest ===
+
+impl<C: Hash + Eq> Clone for ByRequest<C> {
+    fn clone(&self) -> Self {
+        ByRequest(self.0.clone())
+    }
+}
+
+impl<C: Hash + Eq> Debug for ByRequest<C> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("ByRequest").finish()
+    }
+}
+
 // === impl MakeFuture ===
 
 impl<C, F> Future for MakeFuture<F, C>
@@ -214,6 +319,8 @@ where
         Ok(Service {
             inner,
             metrics: self.metrics.clone(),
+            by_request: self.by_request.clone(),
+            continue_ttfb: self.continue_ttfb,
             _p: PhantomData,
         }
         .into())
@@ -232,6 +339,8 @@ where
         Self {
             inner: self.inner.clone(),
             metrics: self.metrics.clone(),
+            by_request: self.by_request.clone(),
+            continue_ttfb: self.continue_ttfb,
             _p: PhantomData,
         }
     }
@@ -255,7 +364,19 @@ where
     }
 
     fn call(&mut self, req: http::Request<A>) -> Self::Future {
-        let mut req_metrics = self.metrics.clone();
+        let metrics = self
+            .by_request
+            .as_ref()
+            .and_then(|ByRequest(by_request)| by_request(req.extensions()))
+            .or_else(|| self.metrics.clone());
+        let mut req_metrics = metrics.clone();
+        let bytes = metrics.clone();
+        let ttfb_at_first_frame = self.continue_ttfb == ContinueTtfb::FirstBodyFrame
+            && req
+                .headers()
+                .get(http::header::EXPECT)
+                .map(|v| v.as_bytes().eq_ignore_ascii_case(b"100-continue"))
+                .unwrap_or(false);
 
         if req.body().is_end_stream() {
             if let Some(lock) = req_metrics.take() {
@@ -271,6 +392,7 @@ where
             let (head, inner) = req.into_parts();
             let body = RequestBody {
                 metrics: req_metrics,
+                bytes,
                 inner,
             };
             http::Request::from_parts(head, body)
@@ -280,8 +402,9 @@ where
 
         ResponseFuture {
             classify: Some(classify),
-            metrics: self.metrics.clone(),
+            metrics,
             stream_open_at: clock::now(),
+            ttfb_at_first_frame,
             inner: self.inner.call(req),
         }
     }
@@ -309,14 +432,35 @@ where
         let metrics = self.metrics.take();
         match rsp {
             Ok(rsp) => {
+                let connection_reused = rsp
+                    .extensions()
+                    .get::<ConnectionInfo>()
+                    .map(|info| info.reused);
+                // A response without a body has no first frame to wait for.
+                let ttfb_at_first_frame = self.ttfb_at_first_frame && !rsp.body().is_end_stream();
+                if !ttfb_at_first_frame {
+                    if let Some(lock) = metrics.as_ref() {
+                        let received_at = rsp
+                            .extensions()
+                            .get::<ResponseHeadersAt>()
+                            .map(|at| at.0)
+                            .unwrap_or_else(clock::now);
+                        let ttfb = received_at - self.stream_open_at;
+                        record_ttfb(lock, rsp.status(), connection_reused, ttfb);
+                    }
+                }
+
                 let classify = classify.map(|c| c.start(&rsp));
                 let (head, inner) = rsp.into_parts();
                 let body = ResponseBody {
                     status: head.status,
                     classify,
+                    bytes: metrics.clone(),
                     metrics,
                     stream_open_at: self.stream_open_at,
+                    connection_reused,
                     latency_recorded: false,
+                    ttfb_recorded: !ttfb_at_first_frame,
                     inner,
                 };
                 Ok(http::Response::from_parts(head, body).into())
@@ -350,6 +494,10 @@ where
     fn poll_data(&mut self) -> Poll<Option<Self::Data>, Self::Error> {
         let frame = try_ready!(self.inner.poll_data());
 
+        if let Some(ref data) = frame {
+            record_bytes(&self.bytes, data.remaining(), |m| &mut m.request_bytes);
+        }
+
         if let Some(lock) = self.metrics.take() {
             let now = clock::now();
             if let Ok(mut metrics) = lock.lock() {
@@ -396,6 +544,7 @@ where
         self.inner.try_clone().map(|inner| RequestBody {
             inner,
             metrics: self.metrics.clone(),
+            bytes: self.bytes.clone(),
         })
     }
 }
@@ -411,9 +560,12 @@ where
             status: http::StatusCode::OK,
             inner: B::default(),
             stream_open_at: clock::now(),
+            connection_reused: None,
             classify: None,
             metrics: None,
+            bytes: None,
             latency_recorded: false,
+            ttfb_recorded: true,
         }
     }
 }
@@ -443,11 +595,23 @@ where
             .entry(Some(self.status))
             .or_insert_with(|| StatusMetrics::default());
 
-        status_metrics.latency.add(now - self.stream_open_at);
+        status_metrics
+            .latency
+            .entry(self.connection_reused.map(ConnectionReused))
+            .or_insert_with(Histogram::default)
+            .add(now - self.stream_open_at);
 
         self.latency_recorded = true;
     }
 
+    fn record_ttfb(&mut self) {
+        if let Some(lock) = self.metrics.as_ref() {
+            let ttfb = clock::now() - self.stream_open_at;
+            record_ttfb(lock, self.status, self.connection_reused, ttfb);
+        }
+        self.ttfb_recorded = true;
+    }
+
     fn record_class(&mut self, class: C::Class) {
         if let Some(lock) = self.metrics.take() {
             measure_class(&lock, class, Some(self.status));
@@ -462,6 +626,39 @@ where
     }
 }
 
+fn record_bytes<C, F>(lock: &Option<Arc<Mutex<RequestMetrics<C>>>>, len: usize, get_counter: F)
+where
+    C: Hash + Eq,
+    F: FnOnce(&mut RequestMetrics<C>) -> &mut Counter,
+{
+    if let Some(Ok(mut metrics)) = lock.as_ref().map(|l| l.lock()) {
+        (*metrics).last_update = clock::now();
+        *get_counter(&mut *metrics) += len as u64;
+    }
+}
+
+fn record_ttfb<C: Hash + Eq>(
+    lock: &Arc<Mutex<RequestMetrics<C>>>,
+    status: http::StatusCode,
+    connection_reused: Option<bool>,
+    ttfb: Duration,
+) {
+    let mut metrics = match lock.lock() {
+        Ok(m) => m,
+        Err(_) => return,
+    };
+
+    (*metrics).last_update = clock::now();
+    metrics
+        .by_status
+        .entry(Some(status))
+        .or_insert_with(|| StatusMetrics::default())
+        .ttfb
+        .entry(connection_reused.map(ConnectionReused))
+        .or_insert_with(Histogram::default)
+        .add(ttfb);
+}
+
 fn measure_class<C: Hash + Eq>(
     lock: &Arc<Mutex<RequestMetrics<C>>>,
     class: C,
@@ -507,6 +704,14 @@ where
             .poll_data()
             .map_err(|e| self.measure_err(e.into())));
 
+        if let Some(ref data) = frame {
+            record_bytes(&self.bytes, data.remaining(), |m| &mut m.response_bytes);
+        }
+
+        if !self.ttfb_recorded {
+            self.record_ttfb();
+        }
+
         if !self.latency_recorded {
             self.record_latency();
         }
@@ -557,6 +762,12 @@ where
     C::Class: Hash + Eq,
 {
     fn drop(&mut self) {
+        // If the body is dropped before a frame is received, the response
+        // ends without one.
+        if !self.ttfb_recorded {
+            self.record_ttfb();
+        }
+
         if !self.latency_recorded {
             self.record_latency();
         }
@@ -566,3 +777,244 @@ where
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{future, sync::oneshot};
+    use linkerd2_metrics::{latency, Histogram};
+    use std::io;
+    use tower::Service as _;
+
+    #[derive(Clone, Debug, Hash, PartialEq, Eq)]
+    struct Class;
+
+    #[derive(Clone, Debug, Default)]
+    struct Classify;
+
+    impl ClassifyResponse for Classify {
+        type Class = Class;
+        type ClassifyEos = Self;
+
+        fn start<B>(self, _: &http::Response<B>) -> Self {
+            self
+        }
+
+        fn error(self, _: &Error) -> Class {
+            Class
+        }
+    }
+
+    impl ClassifyEos for Classify {
+        type Class = Class;
+
+        fn eos(self, _: Option<&http::HeaderMap>) -> Class {
+            Class
+        }
+
+        fn error(self, _: &Error) -> Class {
+            Class
+        }
+    }
+
+    /// A response body with at most a single frame.
+    struct Frame(Option<io::Cursor<&'static [u8]>>);
+
+    impl Payload for Frame {
+        type Data = io::Cursor<&'static [u8]>;
+        type Error = Error;
+
+        fn is_end_stream(&self) -> bool {
+            self.0.is_none()
+        }
+
+        fn poll_data(&mut self) -> Poll<Option<Self::Data>, Self::Error> {
+            Ok(Async::Ready(self.0.take()))
+        }
+    }
+
+    /// A clock that only advances when it is told to.
+    #[derive(Clone)]
+    struct MockNow(Arc<Mutex<Instant>>);
+
+    impl clock::Now for MockNow {
+        fn now(&self) -> Instant {
+            *self.0.lock().unwrap()
+        }
+    }
+
+    impl MockNow {
+        fn advance(&self, by: Duration) {
+            *self.0.lock().unwrap() += by;
+        }
+    }
+
+    fn count_le(histogram: &Histogram<latency::Ms>, le: u64) -> u64 {
+        histogram
+            .into_iter()
+            .filter(|&(bucket, _)| *bucket <= le)
+            .map(|(_, &count)| -> u64 { count.into() })
+            .sum()
+    }
+
+    /// Sends a request whose response headers are received after 50ms and
+    /// whose response body is read after another 200ms, if `read_body` is
+    /// set. The response body is dropped after 250ms.
+    ///
+    /// Returns the request's TTFB and latency histograms.
+    fn delayed_response(
+        continue_ttfb: ContinueTtfb,
+        req: http::Request<hyper::Body>,
+        body: Option<&'static [u8]>,
+        read_body: bool,
+    ) -> (Histogram<latency::Ms>, Histogram<latency::Ms>) {
+        let (tx, rx) = oneshot::channel::<ResponseHeadersAt>();
+        let rx = Arc::new(Mutex::new(Some(rx)));
+        let inner = tower::service_fn(move |_: http::Request<RequestBody<hyper::Body, Class>>| {
+            let rx = rx.lock().unwrap().take().expect("called once");
+            rx.map_err(Error::from).map(move |headers_at| {
+                let mut rsp = http::Response::new(Frame(body.map(io::Cursor::new)));
+                rsp.extensions_mut().insert(headers_at);
+                rsp
+            })
+        });
+
+        let metrics = Arc::new(Mutex::new(RequestMetrics::<Class>::default()));
+        let mut svc = Service::<_, Classify> {
+            metrics: Some(metrics.clone()),
+            by_request: None,
+            continue_ttfb,
+            inner,
+            _p: PhantomData,
+        };
+
+        let now = MockNow(Arc::new(Mutex::new(Instant::now())));
+        let clock = clock::Clock::new_with_now(now.clone());
+        let mut enter = tokio_executor::enter().expect("must not be in an executor");
+        clock::with_default(&clock, &mut enter, |_| {
+            let rsp = svc.call(req);
+            now.advance(Duration::from_millis(50));
+            tx.send(ResponseHeadersAt(clock::now())).unwrap();
+
+            // The response is not observed until well after its headers were
+            // received.
+            now.advance(Duration::from_millis(100));
+            let mut body = rsp.wait().unwrap().into_body();
+
+            now.advance(Duration::from_millis(100));
+            if read_body {
+                let frame = future::lazy(|| Payload::poll_data(&mut body)).wait();
+                assert!(frame.unwrap().is_some());
+            }
+            drop(body);
+        });
+
+        let mut metrics = metrics.lock().unwrap();
+        let status = metrics
+            .by_status
+            .remove(&Some(http::StatusCode::OK))
+            .expect("status metrics");
+        let ttfb = status.ttfb.into_iter().next().expect("ttfb").1;
+        let latency = status.latency.into_iter().next().expect("latency").1;
+        (ttfb, latency)
+    }
+
+    fn continue_req() -> http::Request<hyper::Body> {
+        http::Request::builder()
+            .header(http::header::EXPECT, "100-continue")
+            .body(hyper::Body::empty())
+            .unwrap()
+    }
+
+    #[test]
+    fn ttfb_is_distinguished_by_connection_reuse() {
+        let inner =
+            tower::service_fn(move |req: http::Request<RequestBody<hyper::Body, Class>>| {
+                let reused = req.uri().path() == "/reused";
+                let mut rsp = http::Response::new(Frame(None));
+                rsp.extensions_mut().insert(ConnectionInfo {
+                    reused,
+                    negotiated_protocol: http::Version::HTTP_11,
+                });
+                future::ok::<_, Error>(rsp)
+            });
+
+        let metrics = Arc::new(Mutex::new(RequestMetrics::<Class>::default()));
+        let mut svc = Service::<_, Classify> {
+            metrics: Some(metrics.clone()),
+            by_request: None,
+            continue_ttfb: ContinueTtfb::ResponseHeaders,
+            inner,
+            _p: PhantomData,
+        };
+
+        for path in &["/first", "/reused", "/reused"] {
+            let req = http::Request::get(*path)
+                .body(hyper::Body::empty())
+                .unwrap();
+            drop(svc.call(req).wait().unwrap());
+        }
+
+        let metrics = metrics.lock().unwrap();
+        let ttfb = &metrics.by_status[&Some(http::StatusCode::OK)].ttfb;
+        let count = |reused| count_le(&ttfb[&Some(ConnectionReused(reused))], 1_000);
+        assert_eq!(count(false), 1);
+        assert_eq!(count(true), 2);
+        assert!(!ttfb.contains_key(&None));
+    }
+
+    #[test]
+    fn ttfb_is_measured_when_headers_are_received() {
+        let req = http::Request::new(hyper::Body::empty());
+        let (ttfb, latency) =
+            delayed_response(ContinueTtfb::ResponseHeaders, req, Some(b"hello"), true);
+        assert_eq!(count_le(&ttfb, 40), 0, "ttfb must be 50ms");
+        assert_eq!(count_le(&ttfb, 50), 1, "ttfb must be 50ms");
+        assert_eq!(count_le(&latency, 200), 0, "latency must be 250ms");
+        assert_eq!(count_le(&latency, 300), 1, "latency must be 250ms");
+    }
+
+    #[test]
+    fn ttfb_is_measured_at_first_frame_for_continue_requests() {
+        let (ttfb, _) = delayed_response(
+            ContinueTtfb::ResponseHeaders,
+            continue_req(),
+            Some(b"hello"),
+            true,
+        );
+        assert_eq!(count_le(&ttfb, 50), 1, "ttfb must be 50ms");
+
+        let (ttfb, _) = delayed_response(
+            ContinueTtfb::FirstBodyFrame,
+            continue_req(),
+            Some(b"hello"),
+            true,
+        );
+        assert_eq!(count_le(&ttfb, 200), 0, "ttfb must be 250ms");
+        assert_eq!(count_le(&ttfb, 300), 1, "ttfb must be 250ms");
+
+        // Other requests are unaffected.
+        let req = http::Request::new(hyper::Body::empty());
+        let (ttfb, _) = delayed_response(ContinueTtfb::FirstBodyFrame, req, Some(b"hello"), true);
+        assert_eq!(count_le(&ttfb, 50), 1, "ttfb must be 50ms");
+    }
+
+    #[test]
+    fn ttfb_is_recorded_for_continue_responses_without_frames() {
+        // A response without a body is measured when its headers are
+        // received.
+        let (ttfb, _) = delayed_response(ContinueTtfb::FirstBodyFrame, continue_req(), None, false);
+        assert_eq!(count_le(&ttfb, 50), 1, "ttfb must be 50ms");
+
+        // A response whose body is dropped before a frame is received is
+        // measured when the body is dropped.
+        let (ttfb, _) = delayed_response(
+            ContinueTtfb::FirstBodyFrame,
+            continue_req(),
+            Some(b"hello"),
+            false,
+        );
+        assert_eq!(count_le(&ttfb, 200), 0, "ttfb must be 250ms");
+        assert_eq!(count_le(&ttfb, 300), 1, "ttfb must be 250ms");
+    }
+}
@@ -1,7 +1,7 @@
 use super::h1;
-use futures::{future, Future, Poll};
+use futures::{try_ready, Future, Poll};
 use http;
-use http::header::{HeaderValue, TRANSFER_ENCODING};
+use http::header::{HeaderName, HeaderValue, TRANSFER_ENCODING};
 use tracing::{debug, warn};
 
 pub const L5D_ORIG_PROTO: &str = "l5d-orig-proto";
@@ -10,6 +10,13 @@ pub const L5D_ORIG_PROTO: &str = "l5d-orig-proto";
 #[derive(Clone, Debug)]
 pub struct Upgrade<S> {
     inner: S,
+    header: HeaderName,
+}
+
+/// Downgrades the response to an upgraded request to its original protocol.
+pub struct UpgradeFuture<F> {
+    inner: F,
+    header: HeaderName,
 }
 
 /// Downgrades HTTP2 requests that were previousl upgraded to their original
@@ -17,6 +24,31 @@ pub struct Upgrade<S> {
 #[derive(Clone, Debug)]
 pub struct Downgrade<S> {
     inner: S,
+    header: HeaderName,
+}
+
+/// Upgrades the response to a downgraded request back to HTTP2.
+pub struct DowngradeFuture<F> {
+    inner: F,
+    /// Set if the request was downgraded.
+    header: Option<HeaderName>,
+}
+
+/// Wraps services in `Downgrade`, e.g. so that inbound HTTP2 requests are
+/// sent to the application on their original protocol.
+#[derive(Clone, Debug)]
+pub struct DowngradeLayer {
+    header: HeaderName,
+}
+
+pub fn downgrade_layer() -> DowngradeLayer {
+    downgrade_layer_with_header(HeaderName::from_static(L5D_ORIG_PROTO))
+}
+
+/// Like `downgrade_layer`, but expects the original protocol in `header`,
+/// as set by an `Upgrade` with the same header name.
+pub fn downgrade_layer_with_header(header: HeaderName) -> DowngradeLayer {
+    DowngradeLayer { header }
 }
 
 // ==== impl Upgrade =====
@@ -26,7 +58,18 @@ impl<S> Upgrade<S> {
     where
         S: tower::Service<http::Request<A>, Response = http::Response<B>>,
     {
-        Self { inner }
+        Self {
+            inner,
+            header: HeaderName::from_static(L5D_ORIG_PROTO),
+        }
+    }
+
+    /// Carries the original protocol in `header` rather than in
+    /// `l5d-orig-proto`, e.g. to avoid conflicting with another mesh.
+    ///
+    /// The peer must expect the same header.
+    pub fn with_header_name(self, header: HeaderName) -> Self {
+        Self { header, ..self }
     }
 }
 
@@ -36,7 +79,7 @@ where
 {
     type Response = S::Response;
     type Error = S::Error;
-    type Future = future::Map<S::Future, fn(S::Response) -> S::Response>;
+    type Future = UpgradeFuture<S::Future>;
 
     fn poll_ready(&mut self) -> Poll<(), Self::Error> {
         self.inner.poll_ready()
@@ -69,31 +112,57 @@ where
             (v, _) => unreachable!("bad orig-proto version: {:?}", v),
         };
         req.headers_mut()
-            .insert(L5D_ORIG_PROTO, HeaderValue::from_static(val));
+            .insert(self.header.clone(), HeaderValue::from_static(val));
 
         // transfer-encoding is illegal in HTTP2
         req.headers_mut().remove(TRANSFER_ENCODING);
 
         *req.version_mut() = http::Version::HTTP_2;
 
-        self.inner.call(req).map(|mut res| {
-            debug_assert_eq!(res.version(), http::Version::HTTP_2);
-            let version = if let Some(orig_proto) = res.headers_mut().remove(L5D_ORIG_PROTO) {
-                debug!("downgrading {} response: {:?}", L5D_ORIG_PROTO, orig_proto);
-                if orig_proto == "HTTP/1.1" {
-                    http::Version::HTTP_11
-                } else if orig_proto == "HTTP/1.0" {
-                    http::Version::HTTP_10
-                } else {
-                    warn!("unknown {} header value: {:?}", L5D_ORIG_PROTO, orig_proto);
-                    res.version()
-                }
+        UpgradeFuture {
+            inner: self.inner.call(req),
+            header: self.header.clone(),
+        }
+    }
+}
+
+// ===== impl UpgradeFuture =====
+
+impl<F, B> Future for UpgradeFuture<F>
+where
+    F: Future<Item = http::Response<B>>,
+{
+    type Item = F::Item;
+    type Error = F::Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        let mut res = try_ready!(self.inner.poll());
+        debug_assert_eq!(res.version(), http::Version::HTTP_2);
+        let version = if let Some(orig_proto) = res.headers_mut().remove(&self.header) {
+            debug!("downgrading {} response: {:?}", self.header, orig_proto);
+            if orig_proto == "HTTP/1.1" {
+                http::Version::HTTP_11
+            } else if orig_proto == "HTTP/1.0" {
+                http::Version::HTTP_10
             } else {
+                warn!("unknown {} header value: {:?}", self.header, orig_proto);
                 res.version()
-            };
-            *res.version_mut() = version;
-            res
-        })
+            }
+        } else {
+            res.version()
+        };
+        *res.version_mut() = version;
+        Ok(res.into())
+    }
+}
+
+// ===== impl DowngradeLayer =====
+
+impl<S> tower::layer::Layer<S> for DowngradeLayer {
+    type Service = Downgrade<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Downgrade::new(inner).with_header_name(self.header.clone())
     }
 }
 
@@ -104,7 +173,18 @@ impl<S> Downgrade<S> {
     where
         S: tower::Service<http::Request<A>, Response = http::Response<B>>,
     {
-        Self { inner }
+        Self {
+            inner,
+            header: HeaderName::from_static(L5D_ORIG_PROTO),
+        }
+    }
+
+    /// Expects the original protocol in `header` rather than in
+    /// `l5d-orig-proto`.
+    ///
+    /// The peer's `Upgrade` must use the same header.
+    pub fn with_header_name(self, header: HeaderName) -> Self {
+        Self { header, ..self }
     }
 }
 
@@ -114,7 +194,7 @@ where
 {
     type Response = S::Response;
     type Error = S::Error;
-    type Future = future::Map<S::Future, fn(S::Response) -> S::Response>;
+    type Future = DowngradeFuture<S::Future>;
 
     fn poll_ready(&mut self) -> Poll<(), Self::Error> {
         self.inner.poll_ready()
@@ -124,7 +204,7 @@ where
         let mut upgrade_response = false;
 
         if req.version() == http::Version::HTTP_2 {
-            if let Some(orig_proto) = req.headers_mut().remove(L5D_ORIG_PROTO) {
+            if let Some(orig_proto) = req.headers_mut().remove(&self.header) {
                 debug!("translating HTTP2 to orig-proto: {:?}", orig_proto);
 
                 let val: &[u8] = orig_proto.as_bytes();
@@ -133,8 +213,11 @@ where
                     *req.version_mut() = http::Version::HTTP_11;
                 } else if val.starts_with(b"HTTP/1.0") {
                     *req.version_mut() = http::Version::HTTP_10;
+                    // The downstream connection is HTTP/2, so it must not be
+                    // closed after the response.
+                    req.extensions_mut().insert(h1::Http10KeepAlive);
                 } else {
-                    warn!("unknown {} header value: {:?}", L5D_ORIG_PROTO, orig_proto,);
+                    warn!("unknown {} header value: {:?}", self.header, orig_proto);
                 }
 
                 if !was_absolute_form(val) {
@@ -144,33 +227,128 @@ where
             }
         }
 
-        let fut = self.inner.call(req);
+        DowngradeFuture {
+            inner: self.inner.call(req),
+            header: if upgrade_response {
+                Some(self.header.clone())
+            } else {
+                None
+            },
+        }
+    }
+}
 
-        if upgrade_response {
-            fut.map(|mut res| {
-                let orig_proto = if res.version() == http::Version::HTTP_11 {
-                    "HTTP/1.1"
-                } else if res.version() == http::Version::HTTP_10 {
-                    "HTTP/1.0"
-                } else {
-                    return res;
-                };
+// ===== impl DowngradeFuture =====
 
-                res.headers_mut()
-                    .insert(L5D_ORIG_PROTO, HeaderValue::from_static(orig_proto));
+impl<F, B> Future for DowngradeFuture<F>
+where
+    F: Future<Item = http::Response<B>>,
+{
+    type Item = F::Item;
+    type Error = F::Error;
 
-                // transfer-encoding is illegal in HTTP2
-                res.headers_mut().remove(TRANSFER_ENCODING);
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        let mut res = try_ready!(self.inner.poll());
+        if let Some(header) = self.header.take() {
+            let orig_proto = if res.version() == http::Version::HTTP_11 {
+                "HTTP/1.1"
+            } else if res.version() == http::Version::HTTP_10 {
+                "HTTP/1.0"
+            } else {
+                return Ok(res.into());
+            };
 
-                *res.version_mut() = http::Version::HTTP_2;
-                res
-            })
-        } else {
-            fut.map(|res| res)
+            res.headers_mut()
+                .insert(header, HeaderValue::from_static(orig_proto));
+
+            // transfer-encoding is illegal in HTTP2
+            res.headers_mut().remove(TRANSFER_ENCODING);
+
+            *res.version_mut() = http::Version::HTTP_2;
         }
+        Ok(res.into())
     }
 }
 
 fn was_absolute_form(val: &[u8]) -> bool {
     val.len() >= "HTTP/1.1; absolute-form".len() && &val[10..23] == b"absolute-form"
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::future;
+    use tower::layer::Layer as _;
+    use tower::Service as _;
+
+    const CUSTOM_ORIG_PROTO: &str = "x-mesh-orig-proto";
+
+    /// Responds with the version and URI of each request it receives.
+    fn echo() -> impl tower::Service<
+        http::Request<()>,
+        Response = http::Response<()>,
+        Error = (),
+        Future = future::FutureResult<http::Response<()>, ()>,
+    > {
+        tower::service_fn(|req: http::Request<()>| {
+            assert!(!req.headers().contains_key(L5D_ORIG_PROTO));
+            assert!(!req.headers().contains_key(CUSTOM_ORIG_PROTO));
+            let rsp = http::Response::builder()
+                .version(req.version())
+                .header("x-uri", req.uri().to_string().as_str())
+                .body(())
+                .unwrap();
+            future::ok(rsp)
+        })
+    }
+
+    fn h2_request(uri: &str, orig_proto: &str) -> http::Request<()> {
+        http::Request::builder()
+            .version(http::Version::HTTP_2)
+            .uri(uri)
+            .header(L5D_ORIG_PROTO, orig_proto)
+            .body(())
+            .unwrap()
+    }
+
+    #[test]
+    fn downgrades_to_origin_form() {
+        let mut svc = downgrade_layer().layer(echo());
+        let req = h2_request("http://foo.example.com/path", "HTTP/1.1");
+        let rsp = svc.call(req).wait().unwrap();
+
+        assert_eq!(rsp.headers()["x-uri"], "/path");
+        assert_eq!(rsp.headers()[L5D_ORIG_PROTO], "HTTP/1.1");
+        assert_eq!(rsp.version(), http::Version::HTTP_2);
+    }
+
+    #[test]
+    fn downgrades_to_absolute_form() {
+        let mut svc = downgrade_layer().layer(echo());
+        let req = h2_request("http://foo.example.com/path", "HTTP/1.0; absolute-form");
+        let rsp = svc.call(req).wait().unwrap();
+
+        assert_eq!(rsp.headers()["x-uri"], "http://foo.example.com/path");
+        assert_eq!(rsp.headers()[L5D_ORIG_PROTO], "HTTP/1.0");
+        assert_eq!(rsp.version(), http::Version::HTTP_2);
+    }
+
+    #[test]
+    fn round_trips_with_custom_header() {
+        let header = HeaderName::from_static(CUSTOM_ORIG_PROTO);
+        let downgrade = downgrade_layer_with_header(header.clone()).layer(echo());
+        let mut svc = Upgrade::new(downgrade).with_header_name(header);
+
+        let req = http::Request::builder()
+            .version(http::Version::HTTP_11)
+            .uri("http://foo.example.com/path")
+            .body(())
+            .unwrap();
+        let rsp = svc.call(req).wait().unwrap();
+
+        assert_eq!(rsp.headers()["x-uri"], "http://foo.example.com/path");
+        assert_eq!(rsp.version(), http::Version::HTTP_11);
+        assert!(!rsp.headers().contains_key(CUSTOM_ORIG_PROTO));
+        assert!(!rsp.headers().contains_key(L5D_ORIG_PROTO));
+    }
+}
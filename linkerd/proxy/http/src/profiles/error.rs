@@ -0,0 +1,22 @@
+use std::fmt;
+
+/// Indicates that a traffic split's weights cannot be used to distribute
+/// requests.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum InvalidDistribution {
+    /// Every backend has a weight of zero.
+    AllWeightsZero,
+    /// The sum of the backends' weights overflows a `u32`.
+    Overflow,
+}
+
+impl fmt::Display for InvalidDistribution {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            InvalidDistribution::AllWeightsZero => f.pad("all traffic split weights are zero"),
+            InvalidDistribution::Overflow => f.pad("traffic split weights overflow"),
+        }
+    }
+}
+
+impl std::error::Error for InvalidDistribution {}
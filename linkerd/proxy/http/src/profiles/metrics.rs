@@ -0,0 +1,537 @@
+//! Per-backend metrics for traffic splits.
+//!
+//! Each concrete service in a profile's split is wrapped so that requests and
+//! failures are recorded against the backend that served them. Metrics are
+//! keyed by logical and concrete destination so that, when a split update
+//! retains a backend, its service and its metrics are reused as-is.
+//!
+//! Each service also tracks its in-flight requests so that a backend that is
+//! removed from a split may be drained before it is dropped.
+//!
+//! Each profile also records when it was last updated, so that profiles that
+//! have gone stale (e.g. because the destination controller is unavailable)
+//! may be detected.
+
+use futures::{Future, Poll};
+use indexmap::IndexMap;
+use linkerd2_addr::NameAddr;
+use linkerd2_metrics::{metrics, Counter, FmtLabels, FmtMetric, FmtMetrics, Gauge};
+use std::fmt;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
+use tokio_timer::clock;
+
+metrics! {
+    split_backend_request_total: Counter {
+        "Total count of requests dispatched to a traffic split backend"
+    },
+    split_backend_error_total: Counter {
+        "Total count of requests to a traffic split backend that failed"
+    },
+    split_backend_last_error_seconds: Gauge {
+        "Unix timestamp of the most recent failed request to a traffic split backend"
+    },
+    split_backend_weight: Gauge {
+        "The weight currently configured for a traffic split backend"
+    },
+    profile_last_update_age_seconds: Gauge {
+        "Seconds since a profile was last updated, or since it was first watched"
+    },
+    profile_update_errors_total: Counter {
+        "Total count of profile updates that could not be applied or profile watches that were lost"
+    }
+}
+
+pub fn new() -> (Registry, Report) {
+    let backends = Arc::new(Mutex::new(IndexMap::default()));
+    let profiles = Arc::new(Mutex::new(IndexMap::default()));
+    let registry = Registry {
+        backends: backends.clone(),
+        profiles: profiles.clone(),
+    };
+    (registry, Report { backends, profiles })
+}
+
+#[derive(Debug, Default)]
+pub struct BackendMetrics {
+    requests: Counter,
+    errors: Counter,
+    last_error: Option<SystemTime>,
+    weight: u32,
+}
+
+#[derive(Debug)]
+pub struct ProfileMetrics {
+    last_update: Instant,
+    errors: Counter,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct Labels {
+    logical: NameAddr,
+    concrete: NameAddr,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+struct ProfileLabels(NameAddr);
+
+type Backends = Arc<Mutex<IndexMap<Labels, Arc<Mutex<BackendMetrics>>>>>;
+
+type Profiles = Arc<Mutex<IndexMap<ProfileLabels, Arc<Mutex<ProfileMetrics>>>>>;
+
+/// Hands out the metrics for each profile and for each backend in a split.
+#[derive(Clone, Debug, Default)]
+pub struct Registry {
+    backends: Backends,
+    profiles: Profiles,
+}
+
+/// Formats per-profile and per-backend split metrics.
+#[derive(Clone, Debug)]
+pub struct Report {
+    backends: Backends,
+    profiles: Profiles,
+}
+
+/// Records requests and failures for a single split backend.
+#[derive(Clone, Debug)]
+pub struct Service<S> {
+    inner: S,
+    metrics: Option<Arc<Mutex<BackendMetrics>>>,
+    in_flight: Arc<AtomicUsize>,
+}
+
+pub struct ResponseFuture<F> {
+    inner: F,
+    metrics: Option<Arc<Mutex<BackendMetrics>>>,
+    in_flight: Arc<AtomicUsize>,
+}
+
+// === impl BackendMetrics ===
+
+impl BackendMetrics {
+    pub fn requests(&self) -> u64 {
+        self.requests.value()
+    }
+
+    pub fn errors(&self) -> u64 {
+        self.errors.value()
+    }
+
+    pub fn last_error(&self) -> Option<SystemTime> {
+        self.last_error
+    }
+
+    pub fn weight(&self) -> u32 {
+        self.weight
+    }
+
+    pub fn set_weight(&mut self, weight: u32) {
+        self.weight = weight;
+    }
+}
+
+// === impl ProfileMetrics ===
+
+impl ProfileMetrics {
+    fn new() -> Self {
+        Self {
+            last_update: clock::now(),
+            errors: Counter::default(),
+        }
+    }
+
+    /// Returns when the profile was last updated or, if it has never been
+    /// updated, when it was first watched.
+    pub fn last_update(&self) -> Instant {
+        self.last_update
+    }
+
+    pub fn errors(&self) -> u64 {
+        self.errors.value()
+    }
+
+    /// Records that an update was applied.
+    pub fn updated(&mut self) {
+        self.last_update = clock::now();
+    }
+
+    /// Records that an update could not be applied, or that the profile's
+    /// watch was lost.
+    pub fn error(&mut self) {
+        self.errors.incr();
+    }
+}
+
+// === impl Registry ===
+
+impl Registry {
+    /// Returns the metrics for the profile of the `logical` destination,
+    /// creating them if this profile has not been seen.
+    ///
+    /// A profile's metrics are reported until all references to them are
+    /// dropped.
+    pub fn profile(&self, logical: &NameAddr) -> Arc<Mutex<ProfileMetrics>> {
+        self.profiles
+            .lock()
+            .expect("profile metrics registry lock")
+            .entry(ProfileLabels(logical.clone()))
+            .or_insert_with(|| Arc::new(Mutex::new(ProfileMetrics::new())))
+            .clone()
+    }
+
+    /// Returns the metrics for the `concrete` backend of the `logical`
+    /// destination, creating them if this backend has not been seen.
+    pub fn backend(&self, logical: &NameAddr, concrete: &NameAddr) -> Arc<Mutex<BackendMetrics>> {
+        let labels = Labels {
+            logical: logical.clone(),
+            concrete: concrete.clone(),
+        };
+        self.backends
+            .lock()
+            .expect("split metrics registry lock")
+            .entry(labels)
+            .or_insert_with(|| Arc::new(Mutex::new(BackendMetrics::default())))
+            .clone()
+    }
+
+    /// Wraps `inner` so that its requests are recorded against the
+    /// `concrete` backend of the `logical` destination.
+    ///
+    /// Targets without a logical or concrete name are not recorded.
+    pub fn instrument<S>(
+        &self,
+        logical: Option<&NameAddr>,
+        concrete: Option<&NameAddr>,
+        inner: S,
+    ) -> Service<S> {
+        let metrics = match (logical, concrete) {
+            (Some(l), Some(c)) => Some(self.backend(l, c)),
+            _ => None,
+        };
+        Service {
+            inner,
+            metrics,
+            in_flight: Arc::new(AtomicUsize::new(0)),
+        }
+    }
+}
+
+// === impl Report ===
+
+impl Report {
+    fn fmt_profiles(&self, f: &mut fmt::Formatter<'_>, now: Instant) -> fmt::Result {
+        let mut profiles = match self.profiles.lock() {
+            Err(_) => return Ok(()),
+            Ok(lock) => lock,
+        };
+
+        // Profiles that are no longer watched are dropped.
+        profiles.retain(|_, m| Arc::strong_count(m) > 1);
+        if profiles.is_empty() {
+            return Ok(());
+        }
+
+        profile_last_update_age_seconds.fmt_help(f)?;
+        for (labels, m) in profiles.iter() {
+            if let Ok(m) = m.lock() {
+                let age = if now > m.last_update {
+                    (now - m.last_update).as_secs()
+                } else {
+                    0
+                };
+                Gauge::from(age).fmt_metric_labeled(
+                    f,
+                    profile_last_update_age_seconds.name,
+                    labels,
+                )?;
+            }
+        }
+
+        profile_update_errors_total.fmt_help(f)?;
+        for (labels, m) in profiles.iter() {
+            if let Ok(m) = m.lock() {
+                m.errors
+                    .fmt_metric_labeled(f, profile_update_errors_total.name, labels)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    fn fmt_backends(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut backends = match self.backends.lock() {
+            Err(_) => return Ok(()),
+            Ok(lock) => lock,
+        };
+
+        // Backends that are no longer referenced by any split are dropped.
+        backends.retain(|_, m| Arc::strong_count(m) > 1);
+        if backends.is_empty() {
+            return Ok(());
+        }
+
+        split_backend_request_total.fmt_help(f)?;
+        for (labels, m) in backends.iter() {
+            if let Ok(m) = m.lock() {
+                m.requests
+                    .fmt_metric_labeled(f, split_backend_request_total.name, labels)?;
+            }
+        }
+
+        split_backend_error_total.fmt_help(f)?;
+        for (labels, m) in backends.iter() {
+            if let Ok(m) = m.lock() {
+                m.errors
+                    .fmt_metric_labeled(f, split_backend_error_total.name, labels)?;
+            }
+        }
+
+        split_backend_last_error_seconds.fmt_help(f)?;
+        for (labels, m) in backends.iter() {
+            if let Ok(m) = m.lock() {
+                let secs = m
+                    .last_error
+                    .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+                    .map(|d| d.as_secs())
+                    .unwrap_or(0);
+                Gauge::from(secs).fmt_metric_labeled(
+                    f,
+                    split_backend_last_error_seconds.name,
+                    labels,
+                )?;
+            }
+        }
+
+        split_backend_weight.fmt_help(f)?;
+        for (labels, m) in backends.iter() {
+            if let Ok(m) = m.lock() {
+                Gauge::from(u64::from(m.weight)).fmt_metric_labeled(
+                    f,
+                    split_backend_weight.name,
+                    labels,
+                )?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl FmtMetrics for Report {
+    fn fmt_metrics(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.fmt_profiles(f, clock::now())?;
+        self.fmt_backends(f)
+    }
+}
+
+impl FmtLabels for Labels {
+    fn fmt_labels(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "dst=\"{}\",backend=\"{}\"", self.logical, self.concrete)
+    }
+}
+
+impl FmtLabels for ProfileLabels {
+    fn fmt_labels(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "dst=\"{}\"", self.0)
+    }
+}
+
+// === impl Service ===
+
+impl<S> Service<S> {
+    /// Returns the number of requests dispatched to this backend, by this
+    /// service or any of its clones, that have not yet completed.
+    pub fn in_flight(&self) -> usize {
+        self.in_flight.load(Ordering::Acquire)
+    }
+
+    /// Records the weight currently configured for this backend.
+    pub fn set_weight(&self, weight: u32) {
+        if let Some(Ok(mut m)) = self.metrics.as_ref().map(|m| m.lock()) {
+            m.set_weight(weight);
+        }
+    }
+}
+
+impl<S, Req> tower::Service<Req> for Service<S>
+where
+    S: tower::Service<Req>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = ResponseFuture<S::Future>;
+
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        self.inner.poll_ready()
+    }
+
+    fn call(&mut self, req: Req) -> Self::Future {
+        if let Some(Ok(mut m)) = self.metrics.as_ref().map(|m| m.lock()) {
+            m.requests.incr();
+        }
+
+        self.in_flight.fetch_add(1, Ordering::AcqRel);
+        ResponseFuture {
+            inner: self.inner.call(req),
+            metrics: self.metrics.clone(),
+            in_flight: self.in_flight.clone(),
+        }
+    }
+}
+
+impl<F: Future> Future for ResponseFuture<F> {
+    type Item = F::Item;
+    type Error = F::Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        self.inner.poll().map_err(|e| {
+            if let Some(Ok(mut m)) = self.metrics.as_ref().map(|m| m.lock()) {
+                m.errors.incr();
+                m.last_error = Some(SystemTime::now());
+            }
+            e
+        })
+    }
+}
+
+impl<F> Drop for ResponseFuture<F> {
+    fn drop(&mut self) {
+        self.in_flight.fetch_sub(1, Ordering::AcqRel);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn addr(s: &str) -> NameAddr {
+        NameAddr::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn backend_metrics_survive_updates() {
+        let (registry, report) = new();
+        let logical = addr("web.ns.svc.cluster.local:8080");
+        let canary = addr("web-canary.ns.svc.cluster.local:8080");
+
+        let metrics = registry.backend(&logical, &canary);
+        metrics.lock().unwrap().requests.incr();
+        metrics.lock().unwrap().errors.incr();
+
+        // A weight update asks for the same backend again and must observe
+        // the existing counts.
+        let updated = registry.backend(&logical, &canary);
+        assert!(Arc::ptr_eq(&metrics, &updated));
+        assert_eq!(updated.lock().unwrap().requests(), 1);
+        assert_eq!(updated.lock().unwrap().errors(), 1);
+
+        let out = format!("{}", report.as_display());
+        assert!(out.contains(
+            "split_backend_request_total{dst=\"web.ns.svc.cluster.local:8080\",\
+             backend=\"web-canary.ns.svc.cluster.local:8080\"} 1"
+        ));
+    }
+
+    #[test]
+    fn in_flight_requests_are_tracked() {
+        let (registry, _) = new();
+        let logical = addr("web.ns.svc.cluster.local:8080");
+        let mut svc = registry.instrument(
+            Some(&logical),
+            Some(&logical),
+            tower::service_fn(|()| futures::future::ok::<(), ()>(())),
+        );
+        let clone = svc.clone();
+
+        let f0 = tower::Service::call(&mut svc, ());
+        let f1 = tower::Service::call(&mut svc, ());
+        assert_eq!(clone.in_flight(), 2);
+
+        drop(f0);
+        assert_eq!(clone.in_flight(), 1);
+
+        f1.wait().unwrap();
+        assert_eq!(clone.in_flight(), 0);
+    }
+
+    #[test]
+    fn dropped_backends_are_evicted() {
+        let (registry, report) = new();
+        let logical = addr("web.ns.svc.cluster.local:8080");
+
+        let metrics = registry.backend(&logical, &logical);
+        drop(metrics);
+
+        assert_eq!(format!("{}", report.as_display()), "");
+        assert!(report.backends.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn backend_weights_are_reported() {
+        let (registry, report) = new();
+        let logical = addr("web.ns.svc.cluster.local:8080");
+        let canary = addr("web-canary.ns.svc.cluster.local:8080");
+        let mut svc = registry.instrument(
+            Some(&logical),
+            Some(&canary),
+            tower::service_fn(|()| futures::future::ok::<(), ()>(())),
+        );
+        svc.set_weight(90);
+        tower::Service::call(&mut svc, ()).wait().unwrap();
+
+        let out = format!("{}", report.as_display());
+        assert!(out.contains(
+            "split_backend_request_total{dst=\"web.ns.svc.cluster.local:8080\",\
+             backend=\"web-canary.ns.svc.cluster.local:8080\"} 1"
+        ));
+        assert!(out.contains(
+            "split_backend_weight{dst=\"web.ns.svc.cluster.local:8080\",\
+             backend=\"web-canary.ns.svc.cluster.local:8080\"} 90"
+        ));
+
+        // Once the backend is removed from the split, it is not reported.
+        drop(svc);
+        assert_eq!(format!("{}", report.as_display()), "");
+    }
+
+    /// Formats profile metrics as if the clock read `now`.
+    struct ProfilesAt<'a>(&'a Report, Instant);
+
+    impl fmt::Display for ProfilesAt<'_> {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            self.0.fmt_profiles(f, self.1)
+        }
+    }
+
+    #[test]
+    fn profile_update_age_is_reported() {
+        let (registry, report) = new();
+        let logical = addr("web.ns.svc.cluster.local:8080");
+
+        let metrics = registry.profile(&logical);
+        metrics.lock().unwrap().updated();
+        let updated = metrics.lock().unwrap().last_update();
+
+        let out = format!("{}", ProfilesAt(&report, updated));
+        assert!(out
+            .contains("profile_last_update_age_seconds{dst=\"web.ns.svc.cluster.local:8080\"} 0"));
+
+        // As the clock advances without updates, the profile ages.
+        let later = updated + std::time::Duration::from_secs(30);
+        let out = format!("{}", ProfilesAt(&report, later));
+        assert!(out
+            .contains("profile_last_update_age_seconds{dst=\"web.ns.svc.cluster.local:8080\"} 30"));
+
+        metrics.lock().unwrap().error();
+        let out = format!("{}", ProfilesAt(&report, later));
+        assert!(
+            out.contains("profile_update_errors_total{dst=\"web.ns.svc.cluster.local:8080\"} 1")
+        );
+
+        // Once the profile is no longer watched, it is not reported.
+        drop(metrics);
+        assert_eq!(format!("{}", ProfilesAt(&report, later)), "");
+    }
+}
@@ -2,7 +2,7 @@ use super::retry::Budget;
 use futures::Stream;
 use http;
 use indexmap::IndexMap;
-use linkerd2_addr::NameAddr;
+use linkerd2_addr::{Addr, NameAddr};
 use linkerd2_error::Never;
 use regex::Regex;
 use std::fmt;
@@ -12,6 +12,8 @@ use std::ops::Deref;
 use std::sync::Arc;
 use std::time::Duration;
 
+pub mod error;
+pub mod metrics;
 pub mod recognize;
 /// A stack module that produces a Service that routes requests through alternate
 /// middleware configurations
@@ -32,10 +34,24 @@ pub struct WeightedAddr {
     pub weight: u32,
 }
 
+/// Configures how backends that are newly added to a traffic split are
+/// ramped up to their configured weights.
+#[derive(Copy, Clone, Debug)]
+pub struct SlowStartConfig {
+    /// How long a new backend takes to reach its configured weight.
+    pub duration: Duration,
+    /// The fraction of its configured weight that a new backend starts with.
+    pub min_weight_fraction: f64,
+}
+
 #[derive(Clone, Debug, Default)]
 pub struct Routes {
     pub routes: Vec<(RequestMatch, Route)>,
     pub dst_overrides: Vec<WeightedAddr>,
+    /// When set, `dst_overrides` are selected by consistently hashing the
+    /// value of this request header rather than at random, so that requests
+    /// with the same value are routed to the same backend.
+    pub consistent_hash: Option<http::header::HeaderName>,
 }
 
 /// Watches a destination's Routes.
@@ -67,6 +83,25 @@ pub trait CanGetDestination {
     fn get_destination(&self) -> Option<&NameAddr>;
 }
 
+/// Implemented by target types that may be resolved through several
+/// destinations, e.g. the same service in several clusters.
+///
+/// Targets with a single destination implement this via `CanGetDestination`.
+pub trait HasMultiDestination {
+    /// Returns the target's destinations in the order they should be
+    /// resolved.
+    fn destinations(&self) -> Vec<Addr>;
+}
+
+impl<T: CanGetDestination> HasMultiDestination for T {
+    fn destinations(&self) -> Vec<Addr> {
+        self.get_destination()
+            .map(|dst| Addr::Name(dst.clone()))
+            .into_iter()
+            .collect()
+    }
+}
+
 #[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
 pub struct Route {
     labels: Labels,
@@ -137,6 +172,14 @@ impl Route {
         &self.labels.0
     }
 
+    /// Adds a label to the route, e.g. to describe an individual request.
+    ///
+    /// The labels are copied only if they are shared with another route.
+    pub fn with_extra_label(mut self, key: impl Into<String>, value: impl Into<String>) -> Self {
+        Arc::make_mut(&mut self.labels.0).insert(key.into(), value.into());
+        self
+    }
+
     pub fn response_classes(&self) -> &ResponseClasses {
         &self.response_classes
     }
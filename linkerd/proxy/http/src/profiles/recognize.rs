@@ -1,9 +1,16 @@
-use super::{RequestMatch, Route, WeightedAddr, WithAddr, WithRoute};
+use super::error::InvalidDistribution;
+use super::{RequestMatch, Route, SlowStartConfig, WeightedAddr, WithAddr, WithRoute};
+use arc_swap::ArcSwap;
 use http;
+use linkerd2_addr::NameAddr;
 use linkerd2_router as rt;
 use rand::distributions::{Distribution, WeightedIndex};
-use std::hash::Hash;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::sync::{Arc, Mutex};
+use std::time::Instant;
 use tracing::trace;
+use twox_hash::XxHash64;
 
 #[derive(Clone)]
 pub struct RouteRecognize<T> {
@@ -15,12 +22,47 @@ pub struct RouteRecognize<T> {
 #[derive(Clone)]
 pub struct ConcreteDstRecognize<T> {
     target: T,
+    // Shared by all clones so that weight-only updates may be applied to
+    // every router using this recognizer without rebuilding it. Updates
+    // replace the split, so that recognizing a request never waits on a lock.
+    split: Arc<ArcSwap<Split>>,
+    // When set, requests carrying this header are assigned a backend by
+    // rendezvous hashing of the header's value so that requests with the
+    // same value are consistently routed to the same backend.
+    hash_header: Option<http::header::HeaderName>,
+    // Set while newly-added backends are ramping up to their configured
+    // weights. Shared by all clones so that the ramp advances for every
+    // router using this recognizer.
+    slow_start: Option<Arc<Mutex<SlowStart>>>,
+    // When set, only requests whose headers satisfy the condition are
+    // routed to the split's backends.
+    condition: Option<OverrideCondition>,
+}
+
+/// Determines, from a request's headers, whether its destination may be
+/// overridden by a traffic split.
+#[derive(Clone)]
+pub struct OverrideCondition(Arc<dyn Fn(&http::HeaderMap) -> bool + Send + Sync>);
+
+struct Split {
     dst_overrides: Vec<WeightedAddr>,
     // A weighted index of the `dst_overrides` weights.  This must only be
     // None if `dst_overrides` is empty.
     distribution: Option<WeightedIndex<u32>>,
 }
 
+#[derive(Debug)]
+struct SlowStart {
+    config: SlowStartConfig,
+    started: Instant,
+    // Whether each of the `dst_overrides` is new and must be ramped up.
+    ramping: Vec<bool>,
+    // A weighted index of the ramped weights. `None` once the ramp is
+    // complete or if no backend has a non-zero weight.
+    distribution: Option<WeightedIndex<u32>>,
+    done: bool,
+}
+
 impl<T> RouteRecognize<T> {
     pub fn new(target: T, routes: Vec<(RequestMatch, Route)>, default_route: Route) -> Self {
         RouteRecognize {
@@ -52,23 +94,175 @@ where
 }
 
 impl<T> ConcreteDstRecognize<T> {
-    pub fn new(target: T, dst_overrides: Vec<WeightedAddr>) -> Self {
-        let distribution = Self::make_dist(&dst_overrides);
-        ConcreteDstRecognize {
+    /// Distributes requests over `dst_overrides` by weight.
+    ///
+    /// Fails if the backends' weights are all zero or if their sum overflows.
+    pub fn new(target: T, dst_overrides: Vec<WeightedAddr>) -> Result<Self, InvalidDistribution> {
+        let distribution = Self::make_dist(&dst_overrides)?;
+        Ok(ConcreteDstRecognize {
             target,
-            dst_overrides,
+            split: Arc::new(ArcSwap::from_pointee(Split {
+                dst_overrides,
+                distribution,
+            })),
+            hash_header: None,
+            slow_start: None,
+            condition: None,
+        })
+    }
+
+    /// Only routes requests to the split's backends if their headers satisfy
+    /// `condition`. Other requests are routed to the target.
+    pub fn with_condition(self, condition: Option<OverrideCondition>) -> Self {
+        Self { condition, ..self }
+    }
+
+    /// Updates the weights of the split's backends in place, for this
+    /// recognizer and all of its clones.
+    ///
+    /// Returns false, without updating any weights, unless the update has
+    /// the same `hash_header` and the same backends in the same order, with
+    /// the same backends having zero weights.
+    pub fn update_weights(
+        &self,
+        hash_header: Option<&http::header::HeaderName>,
+        dst_overrides: &[WeightedAddr],
+    ) -> Result<bool, InvalidDistribution> {
+        if self.hash_header.as_ref() != hash_header {
+            return Ok(false);
+        }
+
+        let split = self.split.load();
+        let same_backends = split.dst_overrides.len() == dst_overrides.len()
+            && split
+                .dst_overrides
+                .iter()
+                .zip(dst_overrides)
+                .all(|(a, b)| a.addr == b.addr && (a.weight == 0) == (b.weight == 0));
+        if !same_backends {
+            return Ok(false);
+        }
+
+        let distribution = Self::make_dist(dst_overrides)?;
+        self.split.store(Arc::new(Split {
+            dst_overrides: dst_overrides.to_vec(),
             distribution,
+        }));
+        Ok(true)
+    }
+
+    /// Ramps up the weights of backends for which `is_new` returns true,
+    /// according to `config`.
+    ///
+    /// The ramp only advances when `poll_slow_start` is called.
+    pub fn with_slow_start<F>(mut self, config: SlowStartConfig, is_new: F) -> Self
+    where
+        F: Fn(&NameAddr) -> bool,
+    {
+        let slow_start = {
+            let split = self.split.load();
+            let ramping = split
+                .dst_overrides
+                .iter()
+                .map(|dst| is_new(&dst.addr))
+                .collect::<Vec<_>>();
+            if ramping.iter().any(|r| *r) {
+                let mut slow_start = SlowStart {
+                    config,
+                    started: Instant::now(),
+                    ramping,
+                    distribution: None,
+                    done: false,
+                };
+                slow_start.update(&split.dst_overrides, slow_start.started);
+                Some(Arc::new(Mutex::new(slow_start)))
+            } else {
+                None
+            }
+        };
+        self.slow_start = slow_start;
+        self
+    }
+
+    /// Recomputes the ramped weights of new backends, if any are still
+    /// ramping up.
+    pub fn poll_slow_start(&self) {
+        if let Some(ref slow_start) = self.slow_start {
+            let split = self.split.load();
+            if let Ok(mut slow_start) = slow_start.lock() {
+                if !slow_start.done {
+                    slow_start.update(&split.dst_overrides, Instant::now());
+                }
+            }
         }
     }
 
-    fn make_dist(dst_overrides: &Vec<WeightedAddr>) -> Option<WeightedIndex<u32>> {
-        let mut weights = dst_overrides.iter().map(|dst| dst.weight).peekable();
-        if weights.peek().is_none() {
-            // Weights list is empty.
-            None
-        } else {
-            Some(WeightedIndex::new(weights).expect("invalid weight distribution"))
+    /// Selects backends by consistently hashing the value of `hash_header`.
+    ///
+    /// Requests without the header are distributed by weight, as with `new`.
+    pub fn consistent_hash(
+        target: T,
+        hash_header: http::header::HeaderName,
+        dst_overrides: Vec<WeightedAddr>,
+    ) -> Result<Self, InvalidDistribution> {
+        Ok(ConcreteDstRecognize {
+            hash_header: Some(hash_header),
+            ..Self::new(target, dst_overrides)?
+        })
+    }
+
+    /// Returns true if `other` shares this recognizer's split, i.e. if it is
+    /// updated by this recognizer's weight-only updates.
+    #[cfg(test)]
+    pub(super) fn shares_split(&self, other: &Self) -> bool {
+        Arc::ptr_eq(&self.split, &other.split)
+    }
+
+    /// Picks the backend with the highest weighted rendezvous score for `key`.
+    ///
+    /// Each backend's score depends only on the key and the backend itself,
+    /// so adding or removing a backend only remaps the keys that move to or
+    /// from that backend.
+    fn rendezvous<'a>(dst_overrides: &'a [WeightedAddr], key: &[u8]) -> Option<&'a WeightedAddr> {
+        let mut best: Option<(f64, &WeightedAddr)> = None;
+        for dst in dst_overrides.iter().filter(|dst| dst.weight > 0) {
+            let mut hasher = XxHash64::with_seed(0);
+            hasher.write(key);
+            hasher.write(dst.addr.name().as_ref().as_bytes());
+            hasher.write_u16(dst.addr.port());
+
+            // Map the hash onto (0, 1) and weight it so that each backend
+            // wins a share of keys proportional to its weight.
+            let unit = ((hasher.finish() >> 11) as f64 + 0.5) / (1u64 << 53) as f64;
+            let score = -f64::from(dst.weight) / unit.ln();
+            if best.map(|(s, _)| score > s).unwrap_or(true) {
+                best = Some((score, dst));
+            }
+        }
+        best.map(|(_, dst)| dst)
+    }
+
+    fn make_dist(
+        dst_overrides: &[WeightedAddr],
+    ) -> Result<Option<WeightedIndex<u32>>, InvalidDistribution> {
+        if dst_overrides.is_empty() {
+            return Ok(None);
         }
+
+        // `WeightedIndex` does not check that the total weight fits in a
+        // `u32`, so the sum must be checked before it is built.
+        let total = dst_overrides
+            .iter()
+            .try_fold(0u32, |total, dst| total.checked_add(dst.weight))
+            .ok_or(InvalidDistribution::Overflow)?;
+        if total == 0 {
+            return Err(InvalidDistribution::AllWeightsZero);
+        }
+
+        let weights = dst_overrides.iter().map(|dst| dst.weight);
+        WeightedIndex::new(weights)
+            .map(Some)
+            .map_err(|_| InvalidDistribution::AllWeightsZero)
     }
 }
 
@@ -78,15 +272,373 @@ where
 {
     type Target = T;
 
-    fn recognize(&self, _req: &http::Request<Body>) -> Option<Self::Target> {
-        match self.distribution {
+    fn recognize(&self, req: &http::Request<Body>) -> Option<Self::Target> {
+        if let Some(ref condition) = self.condition {
+            if !condition.matches(req.headers()) {
+                trace!("destination is not overridden");
+                return Some(self.target.clone());
+            }
+        }
+
+        let split = self.split.load();
+
+        if let Some(ref header) = self.hash_header {
+            if let Some(value) = req.headers().get(header) {
+                if let Some(dst) = Self::rendezvous(&split.dst_overrides, value.as_bytes()) {
+                    trace!(%header, dst = %dst.addr, "using consistent hash");
+                    return Some(self.target.clone().with_addr(dst.addr.clone()));
+                }
+            }
+        }
+
+        if let Some(ref slow_start) = self.slow_start {
+            if let Ok(slow_start) = slow_start.lock() {
+                if !slow_start.done {
+                    return match slow_start.distribution {
+                        Some(ref distribution) => {
+                            let mut rng = rand::thread_rng();
+                            let idx = distribution.sample(&mut rng);
+                            let addr = split.dst_overrides[idx].addr.clone();
+                            trace!(dst = %addr, "using slow start weights");
+                            Some(self.target.clone().with_addr(addr))
+                        }
+                        None => Some(self.target.clone()),
+                    };
+                }
+            }
+        }
+
+        match split.distribution {
             Some(ref distribution) => {
                 let mut rng = rand::thread_rng();
                 let idx = distribution.sample(&mut rng);
-                let addr = self.dst_overrides[idx].addr.clone();
+                let addr = split.dst_overrides[idx].addr.clone();
                 Some(self.target.clone().with_addr(addr))
             }
             None => Some(self.target.clone()),
         }
     }
 }
+
+// === impl OverrideCondition ===
+
+impl OverrideCondition {
+    pub fn new<P>(condition: P) -> Self
+    where
+        P: Fn(&http::HeaderMap) -> bool + Send + Sync + 'static,
+    {
+        OverrideCondition(Arc::new(condition))
+    }
+
+    /// Overrides destinations only for requests on which `header` has the
+    /// given `value`.
+    pub fn header_eq(header: http::header::HeaderName, value: http::header::HeaderValue) -> Self {
+        Self::new(move |headers| headers.get(&header) == Some(&value))
+    }
+
+    pub fn matches(&self, headers: &http::HeaderMap) -> bool {
+        (self.0)(headers)
+    }
+}
+
+impl fmt::Debug for OverrideCondition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_tuple("OverrideCondition").finish()
+    }
+}
+
+// === impl SlowStart ===
+
+impl SlowStart {
+    fn update(&mut self, dst_overrides: &[WeightedAddr], now: Instant) {
+        let weights = self.weights(dst_overrides, now);
+        if self.done {
+            self.distribution = None;
+        } else {
+            self.distribution = WeightedIndex::new(weights).ok();
+        }
+    }
+
+    /// Linearly interpolates each new backend's weight from
+    /// `min_weight_fraction` of its configured weight to its configured
+    /// weight over the configured duration.
+    fn weights(&mut self, dst_overrides: &[WeightedAddr], now: Instant) -> Vec<u32> {
+        let elapsed = now.saturating_duration_since(self.started);
+        let progress = if elapsed >= self.config.duration {
+            self.done = true;
+            1.0
+        } else {
+            elapsed.as_secs_f64() / self.config.duration.as_secs_f64()
+        };
+        let min = self.config.min_weight_fraction.max(0.0).min(1.0);
+        let fraction = min + (1.0 - min) * progress;
+
+        dst_overrides
+            .iter()
+            .zip(self.ramping.iter())
+            .map(|(dst, ramping)| {
+                if *ramping && dst.weight > 0 {
+                    ((f64::from(dst.weight) * fraction).round() as u32).max(1)
+                } else {
+                    dst.weight
+                }
+            })
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[derive(Clone, Debug, PartialEq, Eq, Hash)]
+    struct Target(NameAddr);
+
+    impl WithAddr for Target {
+        fn with_addr(self, addr: NameAddr) -> Self {
+            Target(addr)
+        }
+    }
+
+    const HEADER: &str = "x-session-id";
+
+    fn backends(names: &[&str]) -> Vec<WeightedAddr> {
+        names
+            .iter()
+            .map(|n| WeightedAddr {
+                addr: NameAddr::from_str(n).unwrap(),
+                weight: 1,
+            })
+            .collect()
+    }
+
+    fn recognizer(names: &[&str]) -> ConcreteDstRecognize<Target> {
+        let target = Target(NameAddr::from_str("web.ns.svc.cluster.local:8080").unwrap());
+        ConcreteDstRecognize::consistent_hash(
+            target,
+            http::header::HeaderName::from_static(HEADER),
+            backends(names),
+        )
+        .unwrap()
+    }
+
+    fn pick(r: &ConcreteDstRecognize<Target>, key: &str) -> Target {
+        let req = http::Request::builder()
+            .header(HEADER, key)
+            .body(())
+            .unwrap();
+        rt::Recognize::recognize(r, &req).unwrap()
+    }
+
+    #[test]
+    fn consistent_hash_is_deterministic() {
+        let names = &["a.ns:80", "b.ns:80", "c.ns:80"];
+        let r0 = recognizer(names);
+        let r1 = recognizer(names);
+        for i in 0..100 {
+            let key = format!("session-{}", i);
+            assert_eq!(pick(&r0, &key), pick(&r1, &key));
+        }
+    }
+
+    #[test]
+    fn consistent_hash_remaps_only_removed_backend() {
+        let all = recognizer(&["a.ns:80", "b.ns:80", "c.ns:80"]);
+        let fewer = recognizer(&["a.ns:80", "b.ns:80"]);
+        let removed = Target(NameAddr::from_str("c.ns:80").unwrap());
+        for i in 0..100 {
+            let key = format!("session-{}", i);
+            let before = pick(&all, &key);
+            if before != removed {
+                assert_eq!(before, pick(&fewer, &key));
+            }
+        }
+    }
+
+    #[test]
+    fn consistent_hash_skips_zero_weight_backends() {
+        let mut dsts = backends(&["a.ns:80", "b.ns:80"]);
+        dsts[1].weight = 0;
+        let target = Target(NameAddr::from_str("web.ns.svc.cluster.local:8080").unwrap());
+        let r = ConcreteDstRecognize::consistent_hash(
+            target,
+            http::header::HeaderName::from_static(HEADER),
+            dsts,
+        )
+        .unwrap();
+        let a = Target(NameAddr::from_str("a.ns:80").unwrap());
+        for i in 0..20 {
+            assert_eq!(pick(&r, &format!("session-{}", i)), a);
+        }
+    }
+
+    #[test]
+    fn slow_start_ramps_new_backends() {
+        let dsts = backends(&["a.ns:80", "b.ns:80"])
+            .into_iter()
+            .map(|dst| WeightedAddr { weight: 100, ..dst })
+            .collect::<Vec<_>>();
+        let started = Instant::now();
+        let mut slow_start = SlowStart {
+            config: SlowStartConfig {
+                duration: Duration::from_secs(10),
+                min_weight_fraction: 0.1,
+            },
+            started,
+            ramping: vec![false, true],
+            distribution: None,
+            done: false,
+        };
+
+        assert_eq!(slow_start.weights(&dsts, started), vec![100, 10]);
+        assert!(!slow_start.done);
+
+        let halfway = started + Duration::from_secs(5);
+        assert_eq!(slow_start.weights(&dsts, halfway), vec![100, 55]);
+        assert!(!slow_start.done);
+
+        let after = started + Duration::from_secs(10);
+        assert_eq!(slow_start.weights(&dsts, after), vec![100, 100]);
+        assert!(slow_start.done);
+    }
+
+    #[test]
+    fn slow_start_ignores_existing_backends() {
+        let target = Target(NameAddr::from_str("web.ns.svc.cluster.local:8080").unwrap());
+        let config = SlowStartConfig {
+            duration: Duration::from_secs(10),
+            min_weight_fraction: 0.1,
+        };
+        let r = ConcreteDstRecognize::new(target, backends(&["a.ns:80", "b.ns:80"]))
+            .unwrap()
+            .with_slow_start(config, |_| false);
+        assert!(r.slow_start.is_none());
+    }
+
+    fn weighted(weights: &[u32]) -> Vec<WeightedAddr> {
+        let names = ["a.ns:80", "b.ns:80", "c.ns:80"];
+        backends(&names[..weights.len()])
+            .into_iter()
+            .zip(weights)
+            .map(|(dst, weight)| WeightedAddr {
+                weight: *weight,
+                ..dst
+            })
+            .collect()
+    }
+
+    #[test]
+    fn rejects_all_zero_weights() {
+        let target = Target(NameAddr::from_str("web.ns.svc.cluster.local:8080").unwrap());
+        let err = ConcreteDstRecognize::new(target, weighted(&[0, 0])).err();
+        assert_eq!(err, Some(InvalidDistribution::AllWeightsZero));
+    }
+
+    #[test]
+    fn never_selects_zero_weight_backend() {
+        let target = Target(NameAddr::from_str("web.ns.svc.cluster.local:8080").unwrap());
+        let r = ConcreteDstRecognize::new(target, weighted(&[1, 0, 1])).unwrap();
+        let b = Target(NameAddr::from_str("b.ns:80").unwrap());
+        let req = http::Request::new(());
+        for _ in 0..100 {
+            assert_ne!(rt::Recognize::recognize(&r, &req), Some(b.clone()));
+        }
+    }
+
+    #[test]
+    fn rejects_overflowing_weights() {
+        let target = Target(NameAddr::from_str("web.ns.svc.cluster.local:8080").unwrap());
+        let err = ConcreteDstRecognize::new(target, weighted(&[u32::max_value(), 1])).err();
+        assert_eq!(err, Some(InvalidDistribution::Overflow));
+    }
+
+    #[test]
+    fn distributes_requests_by_weight() {
+        let target = Target(NameAddr::from_str("web.ns.svc.cluster.local:8080").unwrap());
+        let r = ConcreteDstRecognize::new(target, weighted(&[10, 90])).unwrap();
+        let a = Target(NameAddr::from_str("a.ns:80").unwrap());
+        let req = http::Request::new(());
+
+        const REQUESTS: usize = 10_000;
+        let to_a = (0..REQUESTS)
+            .filter(|_| rt::Recognize::recognize(&r, &req) == Some(a.clone()))
+            .count();
+        // The expected count is 1000 with a standard deviation of 30.
+        assert!(
+            to_a > 850 && to_a < 1150,
+            "{} of {} requests were sent to the 10% backend",
+            to_a,
+            REQUESTS
+        );
+    }
+
+    #[test]
+    fn consistent_hash_without_key_uses_weights() {
+        let target = Target(NameAddr::from_str("web.ns.svc.cluster.local:8080").unwrap());
+        let r = ConcreteDstRecognize::consistent_hash(
+            target,
+            http::header::HeaderName::from_static(HEADER),
+            weighted(&[0, 1]),
+        )
+        .unwrap();
+        let b = Target(NameAddr::from_str("b.ns:80").unwrap());
+        let req = http::Request::new(());
+        for _ in 0..20 {
+            assert_eq!(rt::Recognize::recognize(&r, &req), Some(b.clone()));
+        }
+    }
+
+    #[test]
+    fn updates_weights_in_place() {
+        let target = Target(NameAddr::from_str("web.ns.svc.cluster.local:8080").unwrap());
+        let r = ConcreteDstRecognize::new(target, weighted(&[1, 1])).unwrap();
+        let clone = r.clone();
+        let a = Target(NameAddr::from_str("a.ns:80").unwrap());
+        let req = http::Request::new(());
+
+        assert_eq!(r.update_weights(None, &weighted(&[1, 0])), Ok(false));
+        assert_eq!(r.update_weights(None, &weighted(&[1, 1, 1])), Ok(false));
+        assert_eq!(
+            r.update_weights(None, &weighted(&[u32::max_value(), 1])),
+            Err(InvalidDistribution::Overflow)
+        );
+
+        let mut dsts = weighted(&[1_000_000, 1]);
+        assert_eq!(r.update_weights(None, &dsts), Ok(true));
+        let to_a = (0..100)
+            .filter(|_| rt::Recognize::recognize(&clone, &req) == Some(a.clone()))
+            .count();
+        assert!(to_a > 95, "clones must observe updated weights");
+
+        dsts.swap(0, 1);
+        assert_eq!(r.update_weights(None, &dsts), Ok(false));
+    }
+
+    #[test]
+    fn condition_limits_overrides() {
+        let target = Target(NameAddr::from_str("web.ns.svc.cluster.local:8080").unwrap());
+        let r = ConcreteDstRecognize::new(target.clone(), weighted(&[1]))
+            .unwrap()
+            .with_condition(Some(OverrideCondition::header_eq(
+                http::header::HeaderName::from_static("l5d-override-ns"),
+                http::header::HeaderValue::from_static("canary"),
+            )));
+        let a = Target(NameAddr::from_str("a.ns:80").unwrap());
+
+        let req = http::Request::new(());
+        assert_eq!(rt::Recognize::recognize(&r, &req), Some(target.clone()));
+
+        let req = http::Request::builder()
+            .header("l5d-override-ns", "stable")
+            .body(())
+            .unwrap();
+        assert_eq!(rt::Recognize::recognize(&r, &req), Some(target));
+
+        let req = http::Request::builder()
+            .header("l5d-override-ns", "canary")
+            .body(())
+            .unwrap();
+        assert_eq!(rt::Recognize::recognize(&r, &req), Some(a));
+    }
+}
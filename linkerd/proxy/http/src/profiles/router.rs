@@ -1,18 +1,29 @@
-use super::recognize::{ConcreteDstRecognize, RouteRecognize};
-use super::{CanGetDestination, GetRoutes, Route, Routes, WeightedAddr, WithAddr, WithRoute};
-use futures::{Async, Poll, Stream};
+use super::metrics;
+use super::recognize::{ConcreteDstRecognize, OverrideCondition, RouteRecognize};
+use super::{
+    CanGetDestination, GetRoutes, RequestMatch, Route, Routes, SlowStartConfig, WeightedAddr,
+    WithAddr, WithRoute,
+};
+use futures::{Async, Future, Poll, Stream};
 use http;
-use indexmap::IndexMap;
+use indexmap::{IndexMap, IndexSet};
+use linkerd2_addr::NameAddr;
 use linkerd2_error::{Error, Never};
 use linkerd2_router as rt;
 use linkerd2_stack::Shared;
 use std::hash::Hash;
-use tracing::{debug, error};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio_timer::{clock, Delay};
+use tracing::{debug, error, trace, warn};
 
 // A router which routes based on the `dst_overrides` of the profile or, if
 // no `dst_overrdies` exist, on the router's target.
-type ConcreteRouter<Target, Svc, Body> =
-    rt::Router<http::Request<Body>, ConcreteDstRecognize<Target>, rt::FixedMake<Target, Svc>>;
+type ConcreteRouter<Target, Svc, Body> = rt::Router<
+    http::Request<Body>,
+    ConcreteDstRecognize<Target>,
+    rt::FixedMake<Target, metrics::Service<Svc>>,
+>;
 
 // A router which routes based on the "route" of the target.
 type RouteRouter<Target, RouteTarget, Svc, Body> =
@@ -21,6 +32,7 @@ type RouteRouter<Target, RouteTarget, Svc, Body> =
 pub fn layer<G, Inner, RouteLayer, RouteBody, InnerBody>(
     get_routes: G,
     route_layer: RouteLayer,
+    metrics: metrics::Registry,
 ) -> Layer<G, Inner, RouteLayer, RouteBody, InnerBody>
 where
     G: GetRoutes + Clone,
@@ -29,6 +41,10 @@ where
     Layer {
         get_routes,
         route_layer,
+        metrics,
+        slow_start: None,
+        prewarm: None,
+        override_condition: None,
         default_route: Route::default(),
         _p: ::std::marker::PhantomData,
     }
@@ -38,6 +54,10 @@ where
 pub struct Layer<G, Inner, RouteLayer, RouteBody, InnerBody> {
     get_routes: G,
     route_layer: RouteLayer,
+    metrics: metrics::Registry,
+    slow_start: Option<SlowStartConfig>,
+    prewarm: Option<Duration>,
+    override_condition: Option<OverrideCondition>,
     /// This is saved into a field so that the same `Arc`s are used and
     /// cloned, instead of calling `Route::default()` every time.
     default_route: Route,
@@ -49,14 +69,26 @@ pub struct MakeSvc<G, Inner, RouteLayer, RouteBody, InnerBody> {
     inner: Inner,
     get_routes: G,
     route_layer: RouteLayer,
+    metrics: metrics::Registry,
+    slow_start: Option<SlowStartConfig>,
+    prewarm: Option<Duration>,
+    override_condition: Option<OverrideCondition>,
     default_route: Route,
     _p: ::std::marker::PhantomData<fn(RouteBody, InnerBody)>,
 }
 
+/// A profile update that is held until the backends it adds are ready.
+struct Warming<Target, Svc> {
+    routes: Routes,
+    backends: IndexMap<Target, Svc>,
+    timeout: Delay,
+}
+
 /// The Service consists of a RouteRouter which routes over the route
 /// stack built by the `route_layer`.  The per-route stack is terminated by
 /// a shared `concrete_router`.  The `concrete_router` routes over the
-/// underlying stack and passes the concrete dst as the target.
+/// underlying stack and passes the concrete dst as the target. Each concrete
+/// service records per-backend metrics.
 ///
 /// ```plain
 ///     +--------------+
@@ -83,14 +115,62 @@ where
     RouteMake::Value: tower::Service<http::Request<RouteBody>> + Clone,
 {
     target: Target,
+    logical: Option<NameAddr>,
     inner: Inner,
     route_layer: RouteLayer,
+    metrics: metrics::Registry,
+    slow_start: Option<SlowStartConfig>,
+    prewarm: Option<Duration>,
+    override_condition: Option<OverrideCondition>,
     route_stream: Option<RouteStream>,
+    // Records when the profile was last updated, if it has been watched.
+    profile_metrics: Option<Arc<Mutex<metrics::ProfileMetrics>>>,
     concrete_router: Option<ConcreteRouter<Target, Inner::Value, InnerBody>>,
+    // Shares slow start state with the `concrete_router`'s recognizer so
+    // that the ramp may be advanced as the service is polled.
+    concrete_recognize: Option<ConcreteDstRecognize<Target>>,
+    // The backends in the `concrete_router`.
+    active: IndexSet<Target>,
+    warming: Option<Warming<Target, metrics::Service<Inner::Value>>>,
+    // Backends that were removed from the split, or whose weight was set to
+    // zero, are retained until their in-flight requests complete.
+    draining: IndexMap<Target, metrics::Service<Inner::Value>>,
     router: RouteRouter<Target, Target::Output, RouteMake::Value, RouteBody>,
     default_route: Route,
 }
 
+impl<G, Inner, RouteLayer, RouteBody, InnerBody> Layer<G, Inner, RouteLayer, RouteBody, InnerBody> {
+    /// Ramps up the weights of backends that are newly added to a split.
+    pub fn with_slow_start(self, slow_start: SlowStartConfig) -> Self {
+        Self {
+            slow_start: Some(slow_start),
+            ..self
+        }
+    }
+
+    /// Holds profile updates that add backends to a split until those
+    /// backends are ready, or until `timeout` elapses, so that the first
+    /// requests to a new backend do not wait for it to become ready.
+    ///
+    /// Until then, requests continue to be routed by the previous update.
+    pub fn with_prewarm(self, timeout: Duration) -> Self {
+        Self {
+            prewarm: Some(timeout),
+            ..self
+        }
+    }
+
+    /// Only overrides a request's destination with a split backend when
+    /// `condition` holds for the request's headers. Other requests, e.g.
+    /// health checks, are routed to the canonical destination.
+    pub fn with_override_condition(self, condition: OverrideCondition) -> Self {
+        Self {
+            override_condition: Some(condition),
+            ..self
+        }
+    }
+}
+
 impl<G, Inner, RouteLayer, RouteBody, InnerBody> tower::layer::Layer<Inner>
     for Layer<G, Inner, RouteLayer, RouteBody, InnerBody>
 where
@@ -104,6 +184,10 @@ where
             inner,
             get_routes: self.get_routes.clone(),
             route_layer: self.route_layer.clone(),
+            metrics: self.metrics.clone(),
+            slow_start: self.slow_start,
+            prewarm: self.prewarm,
+            override_condition: self.override_condition.clone(),
             default_route: self.default_route.clone(),
             _p: ::std::marker::PhantomData,
         }
@@ -120,6 +204,10 @@ where
         Layer {
             get_routes: self.get_routes.clone(),
             route_layer: self.route_layer.clone(),
+            metrics: self.metrics.clone(),
+            slow_start: self.slow_start,
+            prewarm: self.prewarm,
+            override_condition: self.override_condition.clone(),
             default_route: self.default_route.clone(),
             _p: ::std::marker::PhantomData,
         }
@@ -151,13 +239,20 @@ where
     }
 
     fn call(&mut self, target: Target) -> Self::Future {
+        let logical = target.get_destination().cloned();
+
         let concrete_router = {
             // Initially there are no dst_overrides, so build a concrete router
             // with only the default target.
             let mut make = IndexMap::with_capacity(1);
-            make.insert(target.clone(), self.inner.make(&target));
-
-            let rec = ConcreteDstRecognize::new(target.clone(), Vec::new());
+            let svc = self.inner.make(&target);
+            let svc = self
+                .metrics
+                .instrument(logical.as_ref(), logical.as_ref(), svc);
+            make.insert(target.clone(), svc);
+
+            let rec = ConcreteDstRecognize::new(target.clone(), Vec::new())
+                .expect("an empty split must be valid");
             rt::Router::new_fixed(rec, make)
         };
 
@@ -185,14 +280,29 @@ where
                 None
             }
         };
+        let profile_metrics = match (route_stream.as_ref(), logical.as_ref()) {
+            (Some(_), Some(logical)) => Some(self.metrics.profile(logical)),
+            _ => None,
+        };
 
+        let active = Some(target.clone()).into_iter().collect();
         futures::future::ok(Service {
             target,
+            logical,
             inner: self.inner.clone(),
             route_layer: self.route_layer.clone(),
+            metrics: self.metrics.clone(),
+            slow_start: self.slow_start,
+            prewarm: self.prewarm,
+            override_condition: self.override_condition.clone(),
             route_stream,
+            profile_metrics,
             router,
+            active,
             concrete_router: Some(concrete_router),
+            concrete_recognize: None,
+            warming: None,
+            draining: IndexMap::new(),
             default_route: self.default_route.clone(),
         })
     }
@@ -210,6 +320,10 @@ where
             inner: self.inner.clone(),
             get_routes: self.get_routes.clone(),
             route_layer: self.route_layer.clone(),
+            metrics: self.metrics.clone(),
+            slow_start: self.slow_start,
+            prewarm: self.prewarm,
+            override_condition: self.override_condition.clone(),
             default_route: self.default_route.clone(),
             _p: ::std::marker::PhantomData,
         }
@@ -231,11 +345,140 @@ where
     Inner: rt::Make<Target> + Clone,
     Inner::Value: tower::Service<http::Request<InnerBody>> + Clone,
 {
+    /// Applies a profile update or, if it adds backends that must be warmed,
+    /// holds it until they are ready.
+    fn receive_routes(&mut self, routes: Routes) {
+        let timeout = match self.prewarm {
+            Some(timeout) => timeout,
+            None => return self.update_routes(routes),
+        };
+
+        // An update supersedes any update that is still warming, though
+        // backends that are still wanted continue to warm.
+        let (mut warmed, deadline) = match self.warming.take() {
+            Some(w) => (w.backends, w.timeout.deadline()),
+            None => (IndexMap::new(), clock::now() + timeout),
+        };
+
+        let mut backends = IndexMap::new();
+        for WeightedAddr { addr, weight } in &routes.dst_overrides {
+            let target = self.target.clone().with_addr(addr.clone());
+            if *weight == 0 || self.active.contains(&target) || self.draining.contains_key(&target)
+            {
+                continue;
+            }
+
+            let service = warmed.remove(&target).unwrap_or_else(|| {
+                let svc = self.inner.make(&target);
+                self.metrics
+                    .instrument(self.logical.as_ref(), Some(addr), svc)
+            });
+            backends.insert(target, service);
+        }
+
+        if backends.is_empty() {
+            return self.update_routes(routes);
+        }
+
+        debug!(backends = backends.len(), "warming new backends");
+        self.warming = Some(Warming {
+            routes,
+            backends,
+            timeout: Delay::new(deadline),
+        });
+    }
+
+    /// Applies a warming update once all of its new backends are ready, or
+    /// once its timeout elapses.
+    fn poll_warming(&mut self) {
+        let is_warm = match self.warming.as_mut() {
+            None => return,
+            Some(warming) => {
+                let mut is_warm = true;
+                for (_, service) in warming.backends.iter_mut() {
+                    // Backends that fail are not waited upon.
+                    if let Ok(Async::NotReady) =
+                        tower::Service::<http::Request<InnerBody>>::poll_ready(service)
+                    {
+                        is_warm = false;
+                    }
+                }
+                if !is_warm {
+                    if let Ok(Async::NotReady) = warming.timeout.poll() {
+                        return;
+                    }
+                    debug!("backends did not become ready before the prewarm timeout");
+                }
+                is_warm
+            }
+        };
+
+        let Warming {
+            routes, backends, ..
+        } = self.warming.take().expect("update must be warming");
+        trace!(is_warm, "applying warmed update");
+
+        // Warmed backends are picked up by the update as though they were
+        // being drained.
+        self.draining.extend(backends);
+        self.update_routes(routes);
+    }
+
     fn update_routes(&mut self, routes: Routes) {
+        // Updates that only change the weights of the split's backends are
+        // applied to the existing concrete router, so that it need not be
+        // rebuilt.
+        if let Some(ref recognize) = self.concrete_recognize {
+            match recognize.update_weights(routes.consistent_hash.as_ref(), &routes.dst_overrides) {
+                Ok(false) => {}
+                Ok(true) => {
+                    debug!("updating traffic split weights");
+                    if let Some(logical) = self.logical.as_ref() {
+                        for WeightedAddr { addr, weight } in &routes.dst_overrides {
+                            if *weight > 0 {
+                                if let Ok(mut m) = self.metrics.backend(logical, addr).lock() {
+                                    m.set_weight(*weight);
+                                }
+                            }
+                        }
+                    }
+                    self.update_route_router(routes.routes);
+                    self.record_profile(metrics::ProfileMetrics::updated);
+                    return;
+                }
+                Err(error) => {
+                    warn!(%error, "ignoring profile update");
+                    self.record_profile(metrics::ProfileMetrics::error);
+                    return;
+                }
+            }
+        }
+
+        // If the split's weights cannot be used to distribute requests (e.g.
+        // because they are momentarily all zero), the previous routes are
+        // retained until a valid update is received.
+        let recognize = match routes.consistent_hash {
+            Some(ref header) => ConcreteDstRecognize::consistent_hash(
+                self.target.clone(),
+                header.clone(),
+                routes.dst_overrides.clone(),
+            ),
+            None => ConcreteDstRecognize::new(self.target.clone(), routes.dst_overrides.clone()),
+        };
+        let recognize = match recognize {
+            Ok(recognize) => recognize,
+            Err(error) => {
+                warn!(%error, "ignoring profile update");
+                self.record_profile(metrics::ProfileMetrics::error);
+                return;
+            }
+        };
+
         // We must build a new concrete router with a service for each
         // dst_override.  These services are created eagerly.  If a service
         // was present in the previous concrete router, we reuse that
-        // service in the new concrete router rather than recreating it.
+        // service in the new concrete router rather than recreating it, so
+        // that its backend metrics are preserved.
         let capacity = routes.dst_overrides.len() + 1;
 
         let mut make = IndexMap::with_capacity(capacity);
@@ -245,30 +488,82 @@ where
             .expect("previous concrete dst router is missing")
             .into_make();
 
+        let logical = self.logical.as_ref();
         let target_svc = old_make.remove(&self.target).unwrap_or_else(|| {
             error!("concrete dst router did not contain target dst");
-            self.inner.make(&self.target)
+            let svc = self.inner.make(&self.target);
+            self.metrics.instrument(logical, logical, svc)
         });
         make.insert(self.target.clone(), target_svc);
 
-        for WeightedAddr { addr, .. } in &routes.dst_overrides {
+        let mut added = Vec::new();
+        for WeightedAddr { addr, weight } in &routes.dst_overrides {
             let target = self.target.clone().with_addr(addr.clone());
-            let service = old_make
-                .remove(&target)
-                .unwrap_or_else(|| self.inner.make(&target));
+
+            // Zero-weight backends are never selected, so they are removed
+            // from the router and drained.
+            if *weight == 0 {
+                if let Some(service) = old_make.remove(&target) {
+                    debug!(%addr, "draining zero-weight backend");
+                    service.set_weight(0);
+                    self.draining.insert(target, service);
+                }
+                continue;
+            }
+
+            let service = match old_make.remove(&target) {
+                Some(service) => service,
+                None => {
+                    added.push(addr.clone());
+                    match self.draining.remove(&target) {
+                        Some(service) => service,
+                        None => {
+                            let svc = self.inner.make(&target);
+                            self.metrics.instrument(logical, Some(addr), svc)
+                        }
+                    }
+                }
+            };
+            service.set_weight(*weight);
             make.insert(target, service);
         }
 
-        let concrete_router = rt::Router::new_fixed(
-            ConcreteDstRecognize::new(self.target.clone(), routes.dst_overrides),
-            make,
-        );
+        // Backends that are no longer in the split are drained.
+        for (target, service) in old_make {
+            service.set_weight(0);
+            self.draining.insert(target, service);
+        }
+
+        let recognize = match self.slow_start {
+            Some(config) => recognize.with_slow_start(config, |addr| added.contains(addr)),
+            None => recognize,
+        }
+        .with_condition(self.override_condition.clone());
+        self.concrete_recognize = Some(recognize.clone());
+        self.active = make.keys().cloned().collect();
+        let concrete_router = rt::Router::new_fixed(recognize, make);
 
         // We store the concrete_router directly in the Service struct so
         // that we can extract its services when its time to construct a
         // new concrete router.
-        self.concrete_router = Some(concrete_router.clone());
+        self.concrete_router = Some(concrete_router);
 
+        self.update_route_router(routes.routes);
+        self.record_profile(metrics::ProfileMetrics::updated);
+    }
+
+    fn record_profile(&self, record: impl FnOnce(&mut metrics::ProfileMetrics)) {
+        if let Some(Ok(mut m)) = self.profile_metrics.as_ref().map(|m| m.lock()) {
+            record(&mut *m);
+        }
+    }
+
+    /// Rebuilds the route router over the current concrete router.
+    fn update_route_router(&mut self, routes: Vec<(RequestMatch, Route)>) {
+        let concrete_router = self
+            .concrete_router
+            .clone()
+            .expect("concrete dst router is missing");
         let stack = self.route_layer.layer(Shared::new(concrete_router));
 
         let default_route = self.target.clone().with_route(self.default_route.clone());
@@ -276,22 +571,18 @@ where
         // Create a new fixed router router; we can eagerly make the
         // services and never expire the routes from the profile router
         // cache.
-        let capacity = routes.routes.len() + 1;
+        let capacity = routes.len() + 1;
         let mut make = IndexMap::with_capacity(capacity);
         make.insert(default_route.clone(), stack.make(&default_route));
 
-        for (_, route) in &routes.routes {
+        for (_, route) in &routes {
             let route = self.target.clone().with_route(route.clone());
             let service = stack.make(&route);
             make.insert(route, service);
         }
 
         let router = rt::Router::new_fixed(
-            RouteRecognize::new(
-                self.target.clone(),
-                routes.routes,
-                self.default_route.clone(),
-            ),
+            RouteRecognize::new(self.target.clone(), routes, self.default_route.clone()),
             make,
         );
 
@@ -331,8 +622,30 @@ where
     >;
 
     fn poll_ready(&mut self) -> Poll<(), Self::Error> {
-        while let Some(Async::Ready(Some(routes))) = self.poll_route_stream() {
-            self.update_routes(routes);
+        loop {
+            match self.poll_route_stream() {
+                Some(Async::Ready(Some(routes))) => self.receive_routes(routes),
+                Some(Async::Ready(None)) => {
+                    // The profile is no longer watched, so the current routes
+                    // are retained indefinitely and the profile ages.
+                    warn!("profile watch lost");
+                    self.record_profile(metrics::ProfileMetrics::error);
+                    self.route_stream = None;
+                    break;
+                }
+                Some(Async::NotReady) | None => break,
+            }
+        }
+
+        self.poll_warming();
+
+        if let Some(ref recognize) = self.concrete_recognize {
+            recognize.poll_slow_start();
+        }
+
+        // Drop drained backends once they have no requests in flight.
+        if !self.draining.is_empty() {
+            self.draining.retain(|_, service| service.in_flight() > 0);
         }
 
         Ok(Async::Ready(()))
@@ -342,3 +655,252 @@ where
         self.router.call(req)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::sync::mpsc;
+    use futures::{future, stream};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use tokio::runtime::current_thread::Runtime;
+    use tower::layer::Layer as _;
+    use tower::Service as _;
+
+    #[derive(Clone, Debug, PartialEq, Eq, Hash)]
+    struct Target(NameAddr);
+
+    impl CanGetDestination for Target {
+        fn get_destination(&self) -> Option<&NameAddr> {
+            Some(&self.0)
+        }
+    }
+
+    impl WithAddr for Target {
+        fn with_addr(self, addr: NameAddr) -> Self {
+            Target(addr)
+        }
+    }
+
+    impl WithRoute for Target {
+        type Output = Self;
+
+        fn with_route(self, _: Route) -> Self {
+            self
+        }
+    }
+
+    /// Serves a single profile update.
+    #[derive(Clone)]
+    struct GetProfile(Routes);
+
+    impl GetRoutes for GetProfile {
+        type Stream = stream::IterOk<std::vec::IntoIter<Routes>, Never>;
+
+        fn get_routes(&self, _: &NameAddr) -> Option<Self::Stream> {
+            Some(stream::iter_ok(vec![self.0.clone()]))
+        }
+    }
+
+    /// Serves each profile update that is sent on a channel.
+    #[derive(Clone)]
+    struct WatchProfile(Arc<Mutex<Option<mpsc::UnboundedReceiver<Routes>>>>);
+
+    impl GetRoutes for WatchProfile {
+        type Stream = stream::MapErr<mpsc::UnboundedReceiver<Routes>, fn(()) -> Never>;
+
+        fn get_routes(&self, _: &NameAddr) -> Option<Self::Stream> {
+            let rx = self.0.lock().unwrap().take()?;
+            let never: fn(()) -> Never = |()| unreachable!("receivers do not fail");
+            Some(rx.map_err(never))
+        }
+    }
+
+    /// Uses the concrete router for every route.
+    #[derive(Clone)]
+    struct RouteLayer;
+
+    #[derive(Clone)]
+    struct MakeConcrete<M>(M);
+
+    impl<M> tower::layer::Layer<M> for RouteLayer {
+        type Service = MakeConcrete<M>;
+
+        fn layer(&self, inner: M) -> Self::Service {
+            MakeConcrete(inner)
+        }
+    }
+
+    impl<T: Clone, M> rt::Make<T> for MakeConcrete<M>
+    where
+        M: tower::Service<T, Error = Never> + Clone,
+    {
+        type Value = M::Response;
+
+        fn make(&self, target: &T) -> Self::Value {
+            match self.0.clone().call(target.clone()).wait() {
+                Ok(svc) => svc,
+                Err(never) => match never {},
+            }
+        }
+    }
+
+    /// Records the backend that served each request. Backends are only ready
+    /// once their `ready` flag is set.
+    #[derive(Clone)]
+    struct Backend {
+        addr: NameAddr,
+        ready: Arc<AtomicBool>,
+        served: Arc<Mutex<Vec<NameAddr>>>,
+    }
+
+    impl tower::Service<http::Request<()>> for Backend {
+        type Response = http::Response<()>;
+        type Error = Never;
+        type Future = future::FutureResult<Self::Response, Self::Error>;
+
+        fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+            if self.ready.load(Ordering::SeqCst) {
+                Ok(Async::Ready(()))
+            } else {
+                Ok(Async::NotReady)
+            }
+        }
+
+        fn call(&mut self, _: http::Request<()>) -> Self::Future {
+            self.served.lock().unwrap().push(self.addr.clone());
+            future::ok(http::Response::new(()))
+        }
+    }
+
+    fn addr(s: &str) -> NameAddr {
+        NameAddr::from_str(s).unwrap()
+    }
+
+    fn send<S>(rt: &mut Runtime, svc: &mut S)
+    where
+        S: tower::Service<http::Request<()>, Error = Error>,
+    {
+        rt.block_on(future::lazy(|| {
+            assert!(svc.poll_ready().unwrap().is_ready());
+            svc.call(http::Request::new(()))
+        }))
+        .unwrap();
+    }
+
+    #[test]
+    fn new_backends_are_warmed_before_traffic_shifts() {
+        let web = addr("web.ns.svc.cluster.local:8080");
+        let canary = addr("web-canary.ns.svc.cluster.local:8080");
+        let canary_ready = Arc::new(AtomicBool::new(false));
+        let served = Arc::new(Mutex::new(Vec::new()));
+
+        let inner = {
+            let canary = canary.clone();
+            let canary_ready = canary_ready.clone();
+            let served = served.clone();
+            move |t: &Target| Backend {
+                addr: t.0.clone(),
+                ready: if t.0 == canary {
+                    canary_ready.clone()
+                } else {
+                    Arc::new(AtomicBool::new(true))
+                },
+                served: served.clone(),
+            }
+        };
+        let profile = GetProfile(Routes {
+            dst_overrides: vec![WeightedAddr {
+                addr: canary.clone(),
+                weight: 100,
+            }],
+            ..Routes::default()
+        });
+        let mut make = layer::<_, _, _, (), ()>(profile, RouteLayer, metrics::new().0)
+            .with_prewarm(Duration::from_secs(60))
+            .layer(inner);
+        let mut svc = make.call(Target(web.clone())).wait().unwrap();
+
+        let mut rt = Runtime::new().unwrap();
+
+        // The update adds the canary, which is not yet ready, so requests
+        // continue to be served by the target.
+        send(&mut rt, &mut svc);
+        send(&mut rt, &mut svc);
+        assert_eq!(*served.lock().unwrap(), vec![web.clone(), web.clone()]);
+
+        // Once the canary is ready, the update is applied.
+        canary_ready.store(true, Ordering::SeqCst);
+        send(&mut rt, &mut svc);
+        assert_eq!(served.lock().unwrap().last(), Some(&canary));
+    }
+
+    #[test]
+    fn weight_only_updates_keep_the_concrete_router() {
+        let web = addr("web.ns.svc.cluster.local:8080");
+        let stable = addr("web-stable.ns.svc.cluster.local:8080");
+        let canary = addr("web-canary.ns.svc.cluster.local:8080");
+        let made = Arc::new(Mutex::new(Vec::new()));
+        let served = Arc::new(Mutex::new(Vec::new()));
+
+        let inner = {
+            let made = made.clone();
+            let served = served.clone();
+            move |t: &Target| {
+                made.lock().unwrap().push(t.0.clone());
+                Backend {
+                    addr: t.0.clone(),
+                    ready: Arc::new(AtomicBool::new(true)),
+                    served: served.clone(),
+                }
+            }
+        };
+        let split = |weights: &[(&NameAddr, u32)]| Routes {
+            dst_overrides: weights
+                .iter()
+                .map(|&(addr, weight)| WeightedAddr {
+                    addr: addr.clone(),
+                    weight,
+                })
+                .collect(),
+            ..Routes::default()
+        };
+
+        let (tx, rx) = mpsc::unbounded();
+        let profile = WatchProfile(Arc::new(Mutex::new(Some(rx))));
+        let mut make = layer::<_, _, _, (), ()>(profile, RouteLayer, metrics::new().0).layer(inner);
+        let mut svc = make.call(Target(web.clone())).wait().unwrap();
+        let mut rt = Runtime::new().unwrap();
+
+        tx.unbounded_send(split(&[(&stable, 1), (&canary, 1)]))
+            .unwrap();
+        send(&mut rt, &mut svc);
+        let recognize = svc
+            .concrete_recognize
+            .clone()
+            .expect("split must be applied");
+
+        // Only the weights change, so the concrete router is kept and its
+        // backends are not rebuilt.
+        tx.unbounded_send(split(&[(&stable, 1), (&canary, 1_000_000)]))
+            .unwrap();
+        for _ in 0..10 {
+            send(&mut rt, &mut svc);
+        }
+        let current = svc.concrete_recognize.as_ref().unwrap();
+        assert!(recognize.shares_split(current));
+        assert_eq!(
+            *made.lock().unwrap(),
+            vec![web.clone(), stable.clone(), canary.clone()]
+        );
+        assert!(
+            served.lock().unwrap()[1..].iter().all(|a| *a == canary),
+            "requests must be routed by the updated weights"
+        );
+
+        // Changing the backends rebuilds the concrete router.
+        tx.unbounded_send(split(&[(&canary, 1)])).unwrap();
+        send(&mut rt, &mut svc);
+        let current = svc.concrete_recognize.as_ref().unwrap();
+        assert!(!recognize.shares_split(current));
+    }
+}
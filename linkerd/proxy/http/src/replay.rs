@@ -0,0 +1,282 @@
+//! Buffers request bodies on retryable routes so that requests with bodies may
+//! be retried.
+//!
+//! A request's body is only read into memory when its `content-length` does
+//! not exceed the configured limit. The request is dispatched once its body
+//! has been read. Other requests are dispatched immediately; since their
+//! bodies cannot be cloned, they are never retried.
+
+use crate::glue::{Buffered, HttpBody as Body};
+use crate::retry::CanRetry;
+use bytes::BytesMut;
+use futures::{try_ready, Future, Poll};
+use http::header::CONTENT_LENGTH;
+use hyper::body::Payload;
+use linkerd2_error::Error;
+use linkerd2_router as rt;
+use std::mem;
+use tower_util::Oneshot;
+use tracing::trace;
+
+pub fn layer(max_bytes: usize) -> Layer {
+    Layer { max_bytes }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Layer {
+    max_bytes: usize,
+}
+
+#[derive(Clone, Debug)]
+pub struct Make<M> {
+    inner: M,
+    max_bytes: usize,
+}
+
+/// Buffers request bodies up to `max_bytes`, if the route is retryable.
+#[derive(Clone, Debug)]
+pub struct Service<S> {
+    inner: S,
+    max_bytes: Option<usize>,
+}
+
+pub enum ResponseFuture<S>
+where
+    S: tower::Service<http::Request<Body>>,
+{
+    Buffering {
+        inner: Option<S>,
+        head: Option<http::request::Parts>,
+        body: Body,
+        data: BytesMut,
+    },
+    Dispatch(Oneshot<S, http::Request<Body>>),
+    Inner(S::Future),
+}
+
+// === impl Layer ===
+
+impl<M> tower::layer::Layer<M> for Layer {
+    type Service = Make<M>;
+
+    fn layer(&self, inner: M) -> Self::Service {
+        Make {
+            inner,
+            max_bytes: self.max_bytes,
+        }
+    }
+}
+
+// === impl Make ===
+
+impl<T, M> rt::Make<T> for Make<M>
+where
+    T: CanRetry,
+    M: rt::Make<T>,
+{
+    type Value = Service<M::Value>;
+
+    fn make(&self, target: &T) -> Self::Value {
+        let max_bytes = target.can_retry().map(|_| self.max_bytes);
+        Service {
+            inner: self.inner.make(target),
+            max_bytes,
+        }
+    }
+}
+
+// === impl Service ===
+
+impl<S> Service<S> {
+    fn should_buffer(&self, req: &http::Request<Body>) -> bool {
+        let max_bytes = match self.max_bytes {
+            Some(max_bytes) => max_bytes,
+            None => return false,
+        };
+
+        let body = req.body();
+        if body.upgrade.is_some() || body.buffered.is_some() || body.is_end_stream() {
+            return false;
+        }
+
+        req.headers()
+            .get(CONTENT_LENGTH)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.parse::<usize>().ok())
+            .map(|len| len <= max_bytes)
+            .unwrap_or(false)
+    }
+}
+
+impl<S> tower::Service<http::Request<Body>> for Service<S>
+where
+    S: tower::Service<http::Request<Body>> + Clone,
+    S::Error: Into<Error>,
+{
+    type Response = S::Response;
+    type Error = Error;
+    type Future = ResponseFuture<S>;
+
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        self.inner.poll_ready().map_err(Into::into)
+    }
+
+    fn call(&mut self, req: http::Request<Body>) -> Self::Future {
+        if !self.should_buffer(&req) {
+            return ResponseFuture::Inner(self.inner.call(req));
+        }
+
+        trace!("buffering request body");
+        let (head, body) = req.into_parts();
+        ResponseFuture::Buffering {
+            inner: Some(self.inner.clone()),
+            head: Some(head),
+            body,
+            data: BytesMut::new(),
+        }
+    }
+}
+
+// === impl ResponseFuture ===
+
+impl<S> Future for ResponseFuture<S>
+where
+    S: tower::Service<http::Request<Body>>,
+    S::Error: Into<Error>,
+{
+    type Item = S::Response;
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        loop {
+            *self = match self {
+                ResponseFuture::Inner(f) => return f.poll().map_err(Into::into),
+                ResponseFuture::Dispatch(f) => return f.poll().map_err(Into::into),
+                ResponseFuture::Buffering {
+                    inner,
+                    head,
+                    body,
+                    data,
+                } => {
+                    while let Some(chunk) = try_ready!(body.poll_data()) {
+                        data.extend_from_slice(&chunk);
+                    }
+                    let trailers = try_ready!(body.poll_trailers());
+                    trace!(bytes = data.len(), "buffered request body");
+
+                    let body = Body::buffered(Buffered {
+                        data: mem::replace(data, BytesMut::new()).freeze(),
+                        trailers,
+                    });
+                    let head = head.take().expect("polled after ready");
+                    let inner = inner.take().expect("polled after ready");
+                    ResponseFuture::Dispatch(Oneshot::new(
+                        inner,
+                        http::Request::from_parts(head, body),
+                    ))
+                }
+            };
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::retry::TryClone;
+    use futures::{future, Stream};
+    use tokio::runtime::current_thread::Runtime;
+    use tower::Service as _;
+
+    fn body(data: &'static str) -> Body {
+        Body {
+            body: Some(data.into()),
+            upgrade: None,
+            buffered: None,
+            stream: None,
+        }
+    }
+
+    fn read(mut body: Body) -> String {
+        let data = Runtime::new()
+            .unwrap()
+            .block_on(future::lazy(move || {
+                future::poll_fn(move || body.poll_data())
+                    .into_stream()
+                    .concat2()
+            }))
+            .unwrap();
+        String::from_utf8(data.to_vec()).unwrap()
+    }
+
+    /// Responds with whether the request body could be cloned, and the body.
+    fn echo() -> impl tower::Service<
+        http::Request<Body>,
+        Response = http::Response<(bool, Body)>,
+        Error = Error,
+        Future = future::FutureResult<http::Response<(bool, Body)>, Error>,
+    > + Clone {
+        tower::service_fn(|req: http::Request<Body>| {
+            let cloneable = req.body().try_clone().is_some();
+            future::ok(http::Response::new((cloneable, req.into_body())))
+        })
+    }
+
+    fn request(data: &'static str) -> http::Request<Body> {
+        http::Request::builder()
+            .method(http::Method::POST)
+            .header(CONTENT_LENGTH, data.len())
+            .body(body(data))
+            .unwrap()
+    }
+
+    #[test]
+    fn buffers_small_bodies() {
+        let mut svc = Service {
+            inner: echo(),
+            max_bytes: Some(16),
+        };
+
+        let rsp = Runtime::new()
+            .unwrap()
+            .block_on(svc.call(request("hello")))
+            .unwrap();
+        let (cloneable, body) = rsp.into_body();
+        assert!(cloneable, "buffered bodies must be cloneable");
+
+        let clone = body.try_clone().expect("buffered bodies must be cloneable");
+        assert_eq!(read(body), "hello");
+        assert_eq!(read(clone), "hello");
+    }
+
+    #[test]
+    fn skips_bodies_that_are_too_large() {
+        let mut svc = Service {
+            inner: echo(),
+            max_bytes: Some(4),
+        };
+
+        let rsp = Runtime::new()
+            .unwrap()
+            .block_on(svc.call(request("hello")))
+            .unwrap();
+        let (cloneable, body) = rsp.into_body();
+        assert!(!cloneable, "large bodies must not be buffered");
+        assert_eq!(read(body), "hello");
+    }
+
+    #[test]
+    fn skips_routes_that_are_not_retryable() {
+        let mut svc = Service {
+            inner: echo(),
+            max_bytes: None,
+        };
+
+        let rsp = Runtime::new()
+            .unwrap()
+            .block_on(svc.call(request("hello")))
+            .unwrap();
+        let (cloneable, _) = rsp.into_body();
+        assert!(!cloneable, "bodies must only be buffered for retries");
+    }
+}
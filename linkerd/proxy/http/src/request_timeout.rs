@@ -0,0 +1,306 @@
+//! Applies a timeout to requests that set the `l5d-request-timeout` header.
+//!
+//! The header's value is a duration with a unit, e.g. `300ms` or `2s`. The
+//! header is always removed before the request is forwarded. If no response
+//! is received before the timeout elapses, a `504 Gateway Timeout` response is
+//! returned with a body describing the timeout.
+
+use futures::{Async, Future, Poll};
+use http::header::{HeaderValue, CONTENT_LENGTH};
+use hyper::body::Payload;
+use linkerd2_error::Error;
+use std::time::Duration;
+use std::{error, fmt};
+use tokio_timer as timer;
+use tracing::debug;
+
+pub const L5D_REQUEST_TIMEOUT: &str = "l5d-request-timeout";
+
+pub fn layer() -> Layer {
+    Layer(())
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Layer(());
+
+#[derive(Clone, Debug)]
+pub struct Service<S>(S);
+
+pub enum ResponseFuture<F> {
+    Timeout(timer::Timeout<F>, Duration),
+    Inner(F),
+}
+
+/// Either the inner service's response body or the body of a response
+/// indicating that the request timed out.
+#[derive(Debug)]
+pub enum ResponseBody<B: Payload> {
+    Inner(B),
+    TimedOut(Option<B::Data>),
+}
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct InvalidTimeout(String);
+
+/// Parses a timeout like `300ms` or `2s`.
+///
+/// Timeouts must be positive integers with one of the units `ms`, `s`, `m`,
+/// or `h`.
+pub fn parse_timeout(s: &str) -> Result<Duration, InvalidTimeout> {
+    let invalid = || InvalidTimeout(s.to_owned());
+
+    let unit_at = s.find(|c: char| !c.is_ascii_digit()).ok_or_else(invalid)?;
+    let (n, unit) = s.split_at(unit_at);
+    let n = n.parse::<u64>().map_err(|_| invalid())?;
+    let ms = match unit {
+        "ms" => Some(n),
+        "s" => n.checked_mul(1_000),
+        "m" => n.checked_mul(60 * 1_000),
+        "h" => n.checked_mul(60 * 60 * 1_000),
+        _ => None,
+    };
+    match ms {
+        Some(ms) if ms > 0 => Ok(Duration::from_millis(ms)),
+        _ => Err(invalid()),
+    }
+}
+
+// === impl Layer ===
+
+impl<S> tower::layer::Layer<S> for Layer {
+    type Service = Service<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Service(inner)
+    }
+}
+
+// === impl Service ===
+
+impl<S, A, B> tower::Service<http::Request<A>> for Service<S>
+where
+    S: tower::Service<http::Request<A>, Response = http::Response<B>>,
+    S::Error: Into<Error>,
+    B: Payload,
+    B::Data: From<String>,
+{
+    type Response = http::Response<ResponseBody<B>>;
+    type Error = Error;
+    type Future = ResponseFuture<S::Future>;
+
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        self.0.poll_ready().map_err(Into::into)
+    }
+
+    fn call(&mut self, mut req: http::Request<A>) -> Self::Future {
+        let timeout = req
+            .headers_mut()
+            .remove(L5D_REQUEST_TIMEOUT)
+            .and_then(|value| {
+                let timeout = value
+                    .to_str()
+                    .map_err(|_| InvalidTimeout(format!("{:?}", value)))
+                    .and_then(parse_timeout);
+                match timeout {
+                    Ok(timeout) => Some(timeout),
+                    Err(error) => {
+                        debug!(%error, "ignoring {}", L5D_REQUEST_TIMEOUT);
+                        None
+                    }
+                }
+            });
+
+        let inner = self.0.call(req);
+        match timeout {
+            Some(timeout) => {
+                ResponseFuture::Timeout(timer::Timeout::new(inner, timeout), timeout)
+            }
+            None => ResponseFuture::Inner(inner),
+        }
+    }
+}
+
+// === impl ResponseFuture ===
+
+impl<F, B> Future for ResponseFuture<F>
+where
+    F: Future<Item = http::Response<B>>,
+    F::Error: Into<Error>,
+    B: Payload,
+    B::Data: From<String>,
+{
+    type Item = http::Response<ResponseBody<B>>;
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        let rsp = match self {
+            ResponseFuture::Inner(f) => match f.poll() {
+                Ok(Async::Ready(rsp)) => rsp,
+                Ok(Async::NotReady) => return Ok(Async::NotReady),
+                Err(e) => return Err(e.into()),
+            },
+            ResponseFuture::Timeout(f, timeout) => match f.poll() {
+                Ok(Async::Ready(rsp)) => rsp,
+                Ok(Async::NotReady) => return Ok(Async::NotReady),
+                Err(ref e) if e.is_elapsed() => {
+                    debug!("request timed out after {:?}", timeout);
+                    return Ok(Async::Ready(timed_out(*timeout)));
+                }
+                Err(e) => {
+                    if e.is_timer() {
+                        let e = e.into_timer().expect("timer error");
+                        return Err(e.into());
+                    }
+                    let e = e.into_inner().expect("inner error");
+                    return Err(e.into());
+                }
+            },
+        };
+
+        Ok(Async::Ready(rsp.map(ResponseBody::Inner)))
+    }
+}
+
+fn timed_out<B>(timeout: Duration) -> http::Response<ResponseBody<B>>
+where
+    B: Payload,
+    B::Data: From<String>,
+{
+    let msg = format!(
+        "no response was received within the {} of {:?}\n",
+        L5D_REQUEST_TIMEOUT, timeout
+    );
+    http::Response::builder()
+        .status(http::StatusCode::GATEWAY_TIMEOUT)
+        .header(CONTENT_LENGTH, HeaderValue::from(msg.len()))
+        .body(ResponseBody::TimedOut(Some(msg.into())))
+        .expect("timeout response must be valid")
+}
+
+// === impl ResponseBody ===
+
+impl<B: Payload> Payload for ResponseBody<B> {
+    type Data = B::Data;
+    type Error = B::Error;
+
+    fn is_end_stream(&self) -> bool {
+        match self {
+            ResponseBody::Inner(b) => b.is_end_stream(),
+            ResponseBody::TimedOut(data) => data.is_none(),
+        }
+    }
+
+    fn poll_data(&mut self) -> Poll<Option<Self::Data>, Self::Error> {
+        match self {
+            ResponseBody::Inner(b) => b.poll_data(),
+            ResponseBody::TimedOut(data) => Ok(Async::Ready(data.take())),
+        }
+    }
+
+    fn poll_trailers(&mut self) -> Poll<Option<http::HeaderMap>, Self::Error> {
+        match self {
+            ResponseBody::Inner(b) => b.poll_trailers(),
+            ResponseBody::TimedOut(_) => Ok(Async::Ready(None)),
+        }
+    }
+}
+
+impl<B: Payload + Default> Default for ResponseBody<B> {
+    fn default() -> Self {
+        ResponseBody::Inner(B::default())
+    }
+}
+
+// === impl InvalidTimeout ===
+
+impl fmt::Display for InvalidTimeout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid timeout: {}", self.0)
+    }
+}
+
+impl error::Error for InvalidTimeout {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::{future, Stream};
+    use tokio::runtime::current_thread::Runtime;
+    use tower::layer::Layer as _;
+    use tower::Service as _;
+
+    #[test]
+    fn parses_timeouts() {
+        assert_eq!(parse_timeout("300ms"), Ok(Duration::from_millis(300)));
+        assert_eq!(parse_timeout("2s"), Ok(Duration::from_secs(2)));
+        assert_eq!(parse_timeout("1m"), Ok(Duration::from_secs(60)));
+        assert_eq!(parse_timeout("1h"), Ok(Duration::from_secs(60 * 60)));
+    }
+
+    #[test]
+    fn rejects_invalid_timeouts() {
+        for invalid in &[
+            "",
+            "300",
+            "ms",
+            "0s",
+            "-1s",
+            "1.5s",
+            "1 s",
+            "1d",
+            "1sec",
+            "99999999999999999h",
+        ] {
+            assert!(
+                parse_timeout(invalid).is_err(),
+                "{:?} must be invalid",
+                invalid
+            );
+        }
+    }
+
+    #[test]
+    fn header_is_not_forwarded() {
+        let inner = tower::service_fn(|req: http::Request<()>| {
+            assert!(!req.headers().contains_key(L5D_REQUEST_TIMEOUT));
+            future::ok::<_, Error>(http::Response::new(hyper::Body::from("hello")))
+        });
+        let mut svc = layer().layer(inner);
+
+        for value in &["1s", "invalid"] {
+            let req = http::Request::builder()
+                .header(L5D_REQUEST_TIMEOUT, *value)
+                .body(())
+                .unwrap();
+            let rsp = Runtime::new().unwrap().block_on(svc.call(req)).unwrap();
+            assert_eq!(rsp.status(), http::StatusCode::OK);
+        }
+    }
+
+    #[test]
+    fn times_out_with_gateway_timeout() {
+        let inner = tower::service_fn(|_: http::Request<()>| {
+            future::empty::<http::Response<hyper::Body>, Error>()
+        });
+        let mut svc = layer().layer(inner);
+
+        let req = http::Request::builder()
+            .header(L5D_REQUEST_TIMEOUT, "10ms")
+            .body(())
+            .unwrap();
+        let mut rt = Runtime::new().unwrap();
+        let rsp = rt.block_on(svc.call(req)).unwrap();
+        assert_eq!(rsp.status(), http::StatusCode::GATEWAY_TIMEOUT);
+
+        let body = rt
+            .block_on(future::lazy(|| {
+                let mut body = rsp.into_body();
+                future::poll_fn(move || body.poll_data())
+                    .into_stream()
+                    .concat2()
+            }))
+            .unwrap();
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        assert!(body.contains("10ms"), "unexpected body: {:?}", body);
+    }
+}
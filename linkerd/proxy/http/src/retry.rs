@@ -1,6 +1,7 @@
 use crate::metrics::{handle_time, Scoped, Stats};
 use futures::{future, try_ready, Future, Poll};
 use http::{Request, Response};
+use linkerd2_error::Error;
 use linkerd2_proxy_transport::tls;
 use std::marker::PhantomData;
 use tower::retry as tower_retry;
@@ -14,12 +15,17 @@ pub trait CanRetry {
 
 pub trait Retry: Sized {
     fn retry<B1, B2>(&self, req: &Request<B1>, res: &Response<B2>) -> Result<(), NoRetry>;
+    /// Determines whether a request that failed without a response, e.g.
+    /// because its connection was lost, should be retried.
+    fn retry_error<B>(&self, req: &Request<B>, err: &Error) -> Result<(), NoRetry>;
     fn clone_request<B: TryClone>(&self, req: &Request<B>) -> Option<Request<B>>;
 }
 
 pub enum NoRetry {
     Success,
     Budget,
+    /// The request failed in a way that retrying would not fix.
+    Unretryable,
 }
 
 pub trait TryClone: Sized {
@@ -148,7 +154,7 @@ where
 
 // === impl Policy ===
 
-impl<R, S, A, B, E> tower_retry::Policy<Request<A>, Response<B>, E> for Policy<R, S>
+impl<R, S, A, B> tower_retry::Policy<Request<A>, Response<B>, Error> for Policy<R, S>
 where
     R: Retry + Clone,
     S: Stats + Clone,
@@ -156,21 +162,27 @@ where
 {
     type Future = future::FutureResult<Self, ()>;
 
-    fn retry(&self, req: &Request<A>, result: Result<&Response<B>, &E>) -> Option<Self::Future> {
-        match result {
-            Ok(res) => match self.0.retry(req, res) {
-                Ok(()) => {
-                    trace!("retrying request");
-                    Some(future::ok(self.clone()))
-                }
-                Err(NoRetry::Budget) => {
-                    self.1.incr_retry_skipped_budget();
-                    None
-                }
-                Err(NoRetry::Success) => None,
-            },
-            Err(_err) => {
-                trace!("cannot retry transport error");
+    fn retry(
+        &self,
+        req: &Request<A>,
+        result: Result<&Response<B>, &Error>,
+    ) -> Option<Self::Future> {
+        let retry = match result {
+            Ok(res) => self.0.retry(req, res),
+            Err(err) => self.0.retry_error(req, err),
+        };
+        match retry {
+            Ok(()) => {
+                trace!("retrying request");
+                Some(future::ok(self.clone()))
+            }
+            Err(NoRetry::Budget) => {
+                self.1.incr_retry_skipped_budget();
+                None
+            }
+            Err(NoRetry::Success) => None,
+            Err(NoRetry::Unretryable) => {
+                trace!("cannot retry error");
                 None
             }
         }
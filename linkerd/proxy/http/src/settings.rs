@@ -1,4 +1,5 @@
 use http::{self, header::HOST};
+use std::fmt;
 
 /// HTTP Client Settings portion of the `Recognize` key for a request.
 #[derive(Copy, Clone, Debug, PartialEq, Eq, Hash)]
@@ -15,6 +16,14 @@ pub enum Settings {
         /// absolute URIs be bound to separate service stacks. It is also
         /// used to determine what URI normalization will be necessary.
         was_absolute_form: bool,
+        /// Whether the request was HTTP/1.0.
+        ///
+        /// HTTP/1.0 clients may expect their connection to be closed after
+        /// each response, independently of the client's connections.
+        is_http_1_0: bool,
+        /// Whether the request has a `CONNECT` method, i.e. whether it
+        /// establishes a tunnel rather than expecting a response body.
+        is_connect: bool,
     },
     Http2,
 
@@ -53,6 +62,8 @@ impl Settings {
             keep_alive: !is_missing_authority,
             wants_h1_upgrade,
             was_absolute_form: super::h1::is_absolute_form(req.uri()),
+            is_http_1_0: req.version() == http::Version::HTTP_10,
+            is_connect: req.method() == http::Method::CONNECT,
         }
     }
 
@@ -66,6 +77,17 @@ impl Settings {
         }
     }
 
+    /// Returns true if the request establishes a tunnel with `CONNECT`.
+    ///
+    /// Tunnels cannot be carried by ordinary HTTP/2 streams, so these
+    /// requests must not be upgraded.
+    pub fn is_connect_tunnel(&self) -> bool {
+        match self {
+            Settings::Http1 { is_connect, .. } => *is_connect,
+            Settings::Http2 | Settings::NotHttp => false,
+        }
+    }
+
     pub fn is_http2(&self) -> bool {
         match self {
             Settings::Http2 => true,
@@ -73,3 +95,14 @@ impl Settings {
         }
     }
 }
+
+/// Formats settings as a short tag: `h1`, `h2`, or `tcp`.
+impl fmt::Display for Settings {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Settings::Http1 { .. } => f.write_str("h1"),
+            Settings::Http2 => f.write_str("h2"),
+            Settings::NotHttp => f.write_str("tcp"),
+        }
+    }
+}
@@ -208,7 +208,10 @@ where
 
             Some(halves.server)
         } else {
-            h1::strip_connection_headers(req.headers_mut());
+            if h1::is_http10_keep_alive(&req) {
+                req.extensions_mut().insert(h1::Http10KeepAlive);
+            }
+            h1::strip_connection_headers_from_request(&mut req);
             None
         };
 
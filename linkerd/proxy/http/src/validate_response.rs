@@ -0,0 +1,360 @@
+//! Rejects upstream responses that cannot be safely forwarded.
+//!
+//! A buggy or compromised upstream may emit header values containing control
+//! characters (e.g. CR/LF from an obs-fold or an injection attempt) or a
+//! status code outside of the range defined by RFC 9110. Hyper tolerates
+//! these when reading a response, but re-serializing them onto a downstream
+//! HTTP/1 connection may corrupt that connection. Such responses are failed
+//! with an `InvalidResponse` error instead.
+//!
+//! Destinations that legitimately send borderline values may be exempted by
+//! DNS suffix.
+
+use futures::{try_ready, Future, Poll};
+use indexmap::IndexSet;
+use linkerd2_dns as dns;
+use linkerd2_metrics::{metrics, Counter, FmtLabels, FmtMetric, FmtMetrics};
+use linkerd2_stack::layer;
+use std::fmt;
+use std::sync::{Arc, Mutex};
+use tracing::{debug, warn};
+
+use super::profiles::CanGetDestination;
+
+metrics! {
+    response_validation_failure_total: Counter {
+        "Total count of upstream responses rejected because they could not be forwarded safely"
+    }
+}
+
+pub fn new() -> (Registry, Report) {
+    let failures = Arc::new(Mutex::new(Failures::default()));
+    (Registry(failures.clone()), Report(failures))
+}
+
+/// Why a response was rejected.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Reason {
+    InvalidStatus,
+    InvalidHeaderName,
+    InvalidHeaderValue,
+}
+
+/// Indicates that an upstream response was rejected.
+#[derive(Clone, Debug)]
+pub struct InvalidResponse(Reason);
+
+#[derive(Debug, Default)]
+struct Failures {
+    invalid_status: Counter,
+    invalid_header_name: Counter,
+    invalid_header_value: Counter,
+}
+
+/// Records rejected responses.
+#[derive(Clone, Debug, Default)]
+pub struct Registry(Arc<Mutex<Failures>>);
+
+/// Formats rejected response metrics.
+#[derive(Clone, Debug)]
+pub struct Report(Arc<Mutex<Failures>>);
+
+#[derive(Clone, Debug)]
+pub struct MakeValidate<M> {
+    inner: M,
+    allow: Arc<IndexSet<dns::Suffix>>,
+    registry: Registry,
+}
+
+pub struct MakeFuture<F> {
+    inner: F,
+    validate: Option<Registry>,
+}
+
+#[derive(Clone, Debug)]
+pub struct Validate<S> {
+    inner: S,
+    validate: Option<Registry>,
+}
+
+pub struct ResponseFuture<F> {
+    inner: F,
+    validate: Option<Registry>,
+}
+
+// === impl Layer ===
+
+/// Validates responses from all targets except those whose destination is
+/// contained by one of the `allow` suffixes.
+pub fn layer<M>(
+    allow: Arc<IndexSet<dns::Suffix>>,
+    registry: Registry,
+) -> impl tower::layer::Layer<M, Service = MakeValidate<M>> + Clone {
+    layer::mk(move |inner| MakeValidate {
+        inner,
+        allow: allow.clone(),
+        registry: registry.clone(),
+    })
+}
+
+// === impl MakeValidate ===
+
+impl<T, M> tower::Service<T> for MakeValidate<M>
+where
+    T: CanGetDestination,
+    M: tower::Service<T>,
+{
+    type Response = Validate<M::Response>;
+    type Error = M::Error;
+    type Future = MakeFuture<M::Future>;
+
+    fn poll_ready(&mut self) -> Poll<(), M::Error> {
+        self.inner.poll_ready()
+    }
+
+    fn call(&mut self, target: T) -> Self::Future {
+        let allowed = target
+            .get_destination()
+            .map(|dst| self.allow.iter().any(|sfx| sfx.contains(dst.name())))
+            .unwrap_or(false);
+        if allowed {
+            debug!("response validation disabled by allowlist");
+        }
+
+        MakeFuture {
+            validate: if allowed {
+                None
+            } else {
+                Some(self.registry.clone())
+            },
+            inner: self.inner.call(target),
+        }
+    }
+}
+
+// === impl MakeFuture ===
+
+impl<F: Future> Future for MakeFuture<F> {
+    type Item = Validate<F::Item>;
+    type Error = F::Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        let inner = try_ready!(self.inner.poll());
+        let svc = Validate {
+            inner,
+            validate: self.validate.take(),
+        };
+        Ok(svc.into())
+    }
+}
+
+// === impl Validate ===
+
+impl<S, A, B> tower::Service<http::Request<A>> for Validate<S>
+where
+    S: tower::Service<http::Request<A>, Response = http::Response<B>>,
+    S::Error: Into<linkerd2_error::Error>,
+{
+    type Response = S::Response;
+    type Error = linkerd2_error::Error;
+    type Future = ResponseFuture<S::Future>;
+
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        self.inner.poll_ready().map_err(Into::into)
+    }
+
+    fn call(&mut self, req: http::Request<A>) -> Self::Future {
+        ResponseFuture {
+            inner: self.inner.call(req),
+            validate: self.validate.clone(),
+        }
+    }
+}
+
+impl<F, B> Future for ResponseFuture<F>
+where
+    F: Future<Item = http::Response<B>>,
+    F::Error: Into<linkerd2_error::Error>,
+{
+    type Item = F::Item;
+    type Error = linkerd2_error::Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        let rsp = try_ready!(self.inner.poll().map_err(Into::into));
+
+        if let Some(ref registry) = self.validate {
+            if let Err(reason) = validate(&rsp) {
+                warn!(?reason, status = %rsp.status(), "rejecting invalid response");
+                registry.record(reason);
+                return Err(InvalidResponse(reason).into());
+            }
+        }
+
+        Ok(rsp.into())
+    }
+}
+
+/// Checks that a response's status and headers may be forwarded.
+///
+/// Only bytes that are never valid are scanned for, so this is a single
+/// pass over each header.
+pub fn validate<B>(rsp: &http::Response<B>) -> Result<(), Reason> {
+    // RFC 9110 defines status codes as three digits in the range 100-599.
+    let status = rsp.status().as_u16();
+    if status < 100 || status > 599 {
+        return Err(Reason::InvalidStatus);
+    }
+
+    for (name, value) in rsp.headers() {
+        if !name.as_str().bytes().all(is_tchar) {
+            return Err(Reason::InvalidHeaderName);
+        }
+
+        // CTLs other than HTAB (including the CR/LF of an obs-fold) and DEL
+        // are never valid in a field value.
+        if value
+            .as_bytes()
+            .iter()
+            .any(|&b| (b < 0x20 && b != b'\t') || b == 0x7f)
+        {
+            return Err(Reason::InvalidHeaderValue);
+        }
+    }
+
+    Ok(())
+}
+
+fn is_tchar(b: u8) -> bool {
+    match b {
+        b'!' | b'#' | b'$' | b'%' | b'&' | b'\'' | b'*' | b'+' | b'-' | b'.' | b'^' | b'_'
+        | b'`' | b'|' | b'~' => true,
+        b => b.is_ascii_alphanumeric(),
+    }
+}
+
+// === impl Reason ===
+
+impl fmt::Display for Reason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Reason::InvalidStatus => write!(f, "invalid_status"),
+            Reason::InvalidHeaderName => write!(f, "invalid_header_name"),
+            Reason::InvalidHeaderValue => write!(f, "invalid_header_value"),
+        }
+    }
+}
+
+impl FmtLabels for Reason {
+    fn fmt_labels(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "reason=\"{}\"", self)
+    }
+}
+
+// === impl InvalidResponse ===
+
+impl InvalidResponse {
+    pub fn reason(&self) -> Reason {
+        self.0
+    }
+}
+
+impl fmt::Display for InvalidResponse {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "upstream response rejected: {}", self.0)
+    }
+}
+
+impl std::error::Error for InvalidResponse {}
+
+// === impl Registry ===
+
+impl Registry {
+    fn record(&self, reason: Reason) {
+        if let Ok(mut failures) = self.0.lock() {
+            match reason {
+                Reason::InvalidStatus => failures.invalid_status.incr(),
+                Reason::InvalidHeaderName => failures.invalid_header_name.incr(),
+                Reason::InvalidHeaderValue => failures.invalid_header_value.incr(),
+            }
+        }
+    }
+}
+
+// === impl Report ===
+
+impl FmtMetrics for Report {
+    fn fmt_metrics(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let failures = match self.0.lock() {
+            Err(_) => return Ok(()),
+            Ok(lock) => lock,
+        };
+
+        response_validation_failure_total.fmt_help(f)?;
+        for (reason, counter) in &[
+            (Reason::InvalidStatus, failures.invalid_status),
+            (Reason::InvalidHeaderName, failures.invalid_header_name),
+            (Reason::InvalidHeaderValue, failures.invalid_header_value),
+        ] {
+            counter.fmt_metric_labeled(f, response_validation_failure_total.name, reason)?;
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rsp(status: u16, value: &[u8]) -> http::Response<()> {
+        let mut rsp = http::Response::new(());
+        *rsp.status_mut() = http::StatusCode::from_u16(status).unwrap();
+        rsp.headers_mut().insert(
+            "x-upstream",
+            // Hyper does not re-validate the header values it parses.
+            unsafe { http::HeaderValue::from_shared_unchecked(value.into()) },
+        );
+        rsp
+    }
+
+    #[test]
+    fn accepts_valid_responses() {
+        assert_eq!(validate(&rsp(200, b"ok")), Ok(()));
+        assert_eq!(validate(&rsp(503, b"tab\tand obs-text \xff")), Ok(()));
+    }
+
+    #[test]
+    fn rejects_cr_lf_in_values() {
+        assert_eq!(
+            validate(&rsp(200, b"ok\r\nset-cookie: evil")),
+            Err(Reason::InvalidHeaderValue)
+        );
+        assert_eq!(
+            validate(&rsp(200, b"folded\r\n value")),
+            Err(Reason::InvalidHeaderValue)
+        );
+        assert_eq!(
+            validate(&rsp(200, b"nul\0")),
+            Err(Reason::InvalidHeaderValue)
+        );
+    }
+
+    #[test]
+    fn rejects_out_of_range_status() {
+        assert_eq!(validate(&rsp(600, b"ok")), Err(Reason::InvalidStatus));
+        assert_eq!(validate(&rsp(999, b"ok")), Err(Reason::InvalidStatus));
+    }
+
+    #[test]
+    fn counts_failures_by_reason() {
+        let (registry, report) = new();
+        registry.record(Reason::InvalidHeaderValue);
+        registry.record(Reason::InvalidHeaderValue);
+
+        let out = format!("{}", report.as_display());
+        assert!(
+            out.contains("response_validation_failure_total{reason=\"invalid_header_value\"} 2")
+        );
+        assert!(out.contains("response_validation_failure_total{reason=\"invalid_status\"} 0"));
+    }
+}
@@ -6,7 +6,10 @@ use futures::{future, Async, Future, Poll, Stream};
 use hyper::body::Payload;
 use linkerd2_conditional::Conditional;
 use linkerd2_proxy_api::{http_types, pb_duration, tap as api};
-use linkerd2_proxy_http::HasH2Reason;
+use linkerd2_proxy_http::{
+    client::{ConnectionInfo, ResponseHeadersAt},
+    HasH2Reason,
+};
 use std::convert::TryFrom;
 use std::iter;
 use std::sync::atomic::{AtomicUsize, Ordering};
@@ -383,7 +386,23 @@ impl iface::TapResponse for TapResponse {
     type TapPayload = TapResponsePayload;
 
     fn tap<B: Payload>(mut self, rsp: &http::Response<B>) -> TapResponsePayload {
-        let response_init_at = clock::now();
+        // If the client recorded when the response headers were received, the
+        // response is considered to have been initialized then, so that
+        // `since_request_init` reflects the time to first byte.
+        let response_init_at = rsp
+            .extensions()
+            .get::<ResponseHeadersAt>()
+            .map(|at| at.0)
+            .unwrap_or_else(clock::now);
+
+        // Describe whether the response was received on a reused connection
+        // in this and subsequent events.
+        if let Some(info) = rsp.extensions().get::<ConnectionInfo>() {
+            if let Some(meta) = self.base_event.destination_meta.as_mut() {
+                meta.labels
+                    .insert("connection_reused".to_owned(), info.reused.to_string());
+            }
+        }
 
         let headers = if self.extract_headers {
             let headers = if rsp.version() == http::Version::HTTP_2 {
@@ -582,6 +601,9 @@ fn base_event<B, I: Inspect>(req: &http::Request<B>, inspect: &I) -> api::TapEve
                         .insert("server_id".to_owned(), id.as_ref().to_owned());
                 }
             }
+            if let Some(summary) = inspect.dst_summary(req) {
+                m.labels.insert("endpoint".to_owned(), summary);
+            }
             m
         }),
         route_meta: inspect.route_labels(req).map(|labels| {
@@ -55,6 +55,12 @@ pub trait Inspect {
 
     fn dst_addr<B>(&self, req: &http::Request<B>) -> Option<net::SocketAddr>;
     fn dst_labels<B>(&self, req: &http::Request<B>) -> Option<&IndexMap<String, String>>;
+
+    /// Describes the destination endpoint in tap events, e.g. with its
+    /// identity and protocol hint in addition to its address.
+    fn dst_summary<B>(&self, _: &http::Request<B>) -> Option<String> {
+        None
+    }
     fn dst_tls<B>(
         &self,
         req: &http::Request<B>,
@@ -1,21 +1,26 @@
 use futures::{try_ready, Future, Poll};
-use std::{io, net::SocketAddr, time::Duration};
+use std::{fmt, io, net::SocketAddr, time::Duration};
 use tokio::net::{tcp, TcpStream};
 use tower::{service_fn, Service};
 use tracing::debug;
 
 pub trait HasPeerAddr {
     fn peer_addr(&self) -> SocketAddr;
+
+    /// Describes the peer in connection logs and errors.
+    fn fmt_peer(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Display::fmt(&self.peer_addr(), f)
+    }
 }
 
 pub fn svc<T: HasPeerAddr>(
     keepalive: Option<Duration>,
-) -> impl Service<T, Response = TcpStream, Error = io::Error, Future = ConnectFuture> + Clone {
+) -> impl Service<T, Response = TcpStream, Error = io::Error, Future = ConnectFuture<T>> + Clone {
     service_fn(move |target: T| {
         let addr = target.peer_addr();
-        debug!("connecting to {}", addr);
+        debug!("connecting to {}", Peer(&target));
         ConnectFuture {
-            addr,
+            target,
             keepalive,
             future: TcpStream::connect(&addr),
         }
@@ -23,12 +28,15 @@ pub fn svc<T: HasPeerAddr>(
 }
 
 #[derive(Debug)]
-pub struct ConnectFuture {
-    addr: SocketAddr,
+pub struct ConnectFuture<T> {
+    target: T,
     keepalive: Option<Duration>,
     future: tcp::ConnectFuture,
 }
 
+/// Formats a target with `HasPeerAddr::fmt_peer`.
+struct Peer<'a, T>(&'a T);
+
 impl HasPeerAddr for SocketAddr {
     fn peer_addr(&self) -> SocketAddr {
         *self
@@ -37,18 +45,27 @@ impl HasPeerAddr for SocketAddr {
 
 // === impl ConnectFuture ===
 
-impl Future for ConnectFuture {
+impl<T: HasPeerAddr> Future for ConnectFuture<T> {
     type Item = TcpStream;
     type Error = io::Error;
 
     fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        let target = &self.target;
         let io = try_ready!(self.future.poll().map_err(|e| {
-            let details = format!("{} (address: {})", e, self.addr);
+            let details = format!("{} (endpoint: {})", e, Peer(target));
             io::Error::new(e.kind(), details)
         }));
-        debug!("connection established to {}", self.addr);
+        debug!("connection established to {}", Peer(&self.target));
         super::set_nodelay_or_warn(&io);
         super::set_keepalive_or_warn(&io, self.keepalive);
         Ok(io.into())
     }
 }
+
+// === impl Peer ===
+
+impl<'a, T: HasPeerAddr> fmt::Display for Peer<'a, T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        self.0.fmt_peer(f)
+    }
+}
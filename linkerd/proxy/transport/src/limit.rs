@@ -0,0 +1,291 @@
+//! Limits the number of concurrent connections to each peer address.
+//!
+//! A connection counts against its peer's limit from the time it is
+//! initiated until its transport is dropped.
+
+use crate::connect::HasPeerAddr;
+use bytes::Buf;
+use futures::{future, try_ready, Future, Poll};
+use linkerd2_error::Error;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+use std::{fmt, io};
+use tokio::io::{AsyncRead, AsyncWrite};
+use tracing::debug;
+
+/// Determines how many concurrent connections may be established to a target.
+pub trait HasConnectionLimit {
+    /// Returns `None` to use the layer's default limit.
+    fn connection_limit(&self) -> Option<usize>;
+}
+
+/// Creates a layer that limits concurrent connections to each peer address,
+/// using `default` for targets that do not have their own limit.
+///
+/// When `default` is `None`, such targets are not limited.
+pub fn layer(default: Option<usize>) -> Layer {
+    Layer {
+        default,
+        counts: Counts::default(),
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Layer {
+    default: Option<usize>,
+    counts: Counts,
+}
+
+#[derive(Clone, Debug)]
+pub struct MakeLimit<M> {
+    inner: M,
+    default: Option<usize>,
+    counts: Counts,
+}
+
+#[derive(Debug)]
+pub struct ConnectFuture<F> {
+    inner: F,
+    permit: Option<Permit>,
+}
+
+/// A transport that releases its connection's permit when dropped.
+#[derive(Debug)]
+pub struct Io<T> {
+    io: T,
+    _permit: Option<Permit>,
+}
+
+#[derive(Clone, Debug)]
+pub struct ConnectionLimitExceeded {
+    addr: SocketAddr,
+    max: usize,
+}
+
+#[derive(Clone, Debug, Default)]
+struct Counts(Arc<Mutex<HashMap<SocketAddr, usize>>>);
+
+#[derive(Debug)]
+struct Permit {
+    addr: SocketAddr,
+    counts: Counts,
+}
+
+// === impl Layer ===
+
+impl<M> tower::layer::Layer<M> for Layer {
+    type Service = MakeLimit<M>;
+
+    fn layer(&self, inner: M) -> Self::Service {
+        MakeLimit {
+            inner,
+            default: self.default,
+            counts: self.counts.clone(),
+        }
+    }
+}
+
+// === impl MakeLimit ===
+
+impl<T, M> tower::Service<T> for MakeLimit<M>
+where
+    T: HasPeerAddr + HasConnectionLimit,
+    M: tower::Service<T>,
+    M::Error: Into<Error>,
+{
+    type Response = Io<M::Response>;
+    type Error = Error;
+    type Future =
+        future::Either<ConnectFuture<M::Future>, future::FutureResult<Self::Response, Self::Error>>;
+
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        self.inner.poll_ready().map_err(Into::into)
+    }
+
+    fn call(&mut self, target: T) -> Self::Future {
+        let permit = match target.connection_limit().or(self.default) {
+            None => None,
+            Some(max) => match self.counts.acquire(target.peer_addr(), max) {
+                Ok(permit) => Some(permit),
+                Err(e) => return future::Either::B(future::err(e.into())),
+            },
+        };
+
+        future::Either::A(ConnectFuture {
+            inner: self.inner.call(target),
+            permit,
+        })
+    }
+}
+
+// === impl ConnectFuture ===
+
+impl<F> Future for ConnectFuture<F>
+where
+    F: Future,
+    F::Error: Into<Error>,
+{
+    type Item = Io<F::Item>;
+    type Error = Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        // If the connection fails, the permit is released when the future is
+        // dropped.
+        let io = try_ready!(self.inner.poll().map_err(Into::into));
+        Ok(Io {
+            io,
+            _permit: self.permit.take(),
+        }
+        .into())
+    }
+}
+
+// === impl Io ===
+
+impl<T: AsyncRead + AsyncWrite> io::Read for Io<T> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        self.io.read(buf)
+    }
+}
+
+impl<T: AsyncRead + AsyncWrite> io::Write for Io<T> {
+    fn flush(&mut self) -> io::Result<()> {
+        self.io.flush()
+    }
+
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        self.io.write(buf)
+    }
+}
+
+impl<T: AsyncRead + AsyncWrite> AsyncRead for Io<T> {
+    unsafe fn prepare_uninitialized_buffer(&self, buf: &mut [u8]) -> bool {
+        self.io.prepare_uninitialized_buffer(buf)
+    }
+}
+
+impl<T: AsyncRead + AsyncWrite> AsyncWrite for Io<T> {
+    fn shutdown(&mut self) -> Poll<(), io::Error> {
+        self.io.shutdown()
+    }
+
+    fn write_buf<B: Buf>(&mut self, buf: &mut B) -> Poll<usize, io::Error> {
+        self.io.write_buf(buf)
+    }
+}
+
+// === impl Counts ===
+
+impl Counts {
+    fn acquire(&self, addr: SocketAddr, max: usize) -> Result<Permit, ConnectionLimitExceeded> {
+        let mut counts = self.0.lock().expect("connection counts poisoned");
+        let count = counts.entry(addr).or_insert(0);
+        if *count >= max {
+            debug!(%addr, %max, "connection limit exceeded");
+            return Err(ConnectionLimitExceeded { addr, max });
+        }
+
+        *count += 1;
+        Ok(Permit {
+            addr,
+            counts: self.clone(),
+        })
+    }
+
+    fn release(&self, addr: &SocketAddr) {
+        let mut counts = self.0.lock().expect("connection counts poisoned");
+        if let Some(count) = counts.get_mut(addr) {
+            *count -= 1;
+            if *count == 0 {
+                counts.remove(addr);
+            }
+        }
+    }
+}
+
+// === impl Permit ===
+
+impl Drop for Permit {
+    fn drop(&mut self) {
+        self.counts.release(&self.addr);
+    }
+}
+
+// === impl ConnectionLimitExceeded ===
+
+impl fmt::Display for ConnectionLimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "connection limit exceeded: {} connections to {}",
+            self.max, self.addr
+        )
+    }
+}
+
+impl std::error::Error for ConnectionLimitExceeded {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tower::layer::Layer as _;
+    use tower::Service as _;
+
+    struct Target(SocketAddr, Option<usize>);
+
+    impl HasPeerAddr for Target {
+        fn peer_addr(&self) -> SocketAddr {
+            self.0
+        }
+    }
+
+    impl HasConnectionLimit for Target {
+        fn connection_limit(&self) -> Option<usize> {
+            self.1
+        }
+    }
+
+    fn connect() -> impl tower::Service<
+        Target,
+        Response = (),
+        Error = Error,
+        Future = future::FutureResult<(), Error>,
+    > {
+        tower::service_fn(|_: Target| future::ok(()))
+    }
+
+    #[test]
+    fn limits_connections_per_addr() {
+        let a = "10.0.0.1:80".parse().unwrap();
+        let b = "10.0.0.2:80".parse().unwrap();
+        let mut make = layer(None).layer(connect());
+
+        let conn0 = make.call(Target(a, Some(2))).wait().unwrap();
+        let _conn1 = make.call(Target(a, Some(2))).wait().unwrap();
+        let err = make.call(Target(a, Some(2))).wait().unwrap_err();
+        assert!(err.is::<ConnectionLimitExceeded>(), "{}", err);
+
+        // Other addresses are limited independently.
+        make.call(Target(b, Some(2))).wait().unwrap();
+
+        // Dropping a connection releases its permit.
+        drop(conn0);
+        make.call(Target(a, Some(2))).wait().unwrap();
+    }
+
+    #[test]
+    fn uses_the_default_limit() {
+        let a = "10.0.0.1:80".parse().unwrap();
+
+        let mut make = layer(None).layer(connect());
+        let conns = (0..10)
+            .map(|_| make.call(Target(a, None)).wait().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(conns.len(), 10);
+
+        let mut make = layer(Some(1)).layer(connect());
+        let _conn = make.call(Target(a, None)).wait().unwrap();
+        assert!(make.call(Target(a, None)).wait().is_err());
+    }
+}
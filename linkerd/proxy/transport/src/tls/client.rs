@@ -1,12 +1,12 @@
 use crate::io::BoxedIo;
-use futures::{try_ready, Future, Poll};
+use futures::{try_ready, Async, Future, Poll};
 use linkerd2_conditional::Conditional;
 use linkerd2_identity as identity;
 pub use rustls::ClientConfig as Config;
 use std::io;
 use std::sync::Arc;
 use tokio::net::TcpStream;
-use tracing::trace;
+use tracing::{debug, trace};
 
 pub trait HasConfig {
     fn tls_client_config(&self) -> Arc<Config>;
@@ -24,7 +24,15 @@ pub struct Connect<L, C> {
 pub type Connection = BoxedIo;
 
 /// A socket that is in the process of connecting.
-pub enum ConnectFuture<L, F: Future> {
+pub struct ConnectFuture<L, C, T>
+where
+    C: tower::MakeConnection<T>,
+{
+    state: State<L, <C as tower::MakeConnection<T>>::Future>,
+    retry: Option<Retry<L, C, T>>,
+}
+
+enum State<L, F: Future> {
     Init {
         future: F,
         tls: super::Conditional<(identity::Name, L)>,
@@ -32,6 +40,16 @@ pub enum ConnectFuture<L, F: Future> {
     Handshake(tokio_rustls::Connect<F::Item>),
 }
 
+/// Tracks the alternate identities that may be tried, each on a new
+/// connection, if the peer does not accept a handshake.
+struct Retry<L, C, T> {
+    local: L,
+    connect: C,
+    target: T,
+    alternates: std::vec::IntoIter<identity::Name>,
+    tried: Vec<identity::Name>,
+}
+
 // === impl Layer ===
 
 pub fn layer<L: HasConfig + Clone>(l: super::Conditional<L>) -> Layer<L> {
@@ -57,16 +75,16 @@ where
 /// impl MakeConnection
 impl<L, C, Target> tower::Service<Target> for Connect<L, C>
 where
-    Target: super::HasPeerIdentity,
+    Target: super::HasPeerIdentity + Clone,
     L: HasConfig + Clone,
-    C: tower::MakeConnection<Target, Connection = TcpStream>,
+    C: tower::MakeConnection<Target, Connection = TcpStream> + Clone,
     C::Future: Send + 'static,
     C::Error: ::std::error::Error + Send + Sync + 'static,
     C::Error: From<io::Error>,
 {
     type Response = Connection;
     type Error = C::Error;
-    type Future = ConnectFuture<L, C::Future>;
+    type Future = ConnectFuture<L, C, Target>;
 
     fn poll_ready(&mut self) -> Poll<(), Self::Error> {
         self.inner.poll_ready()
@@ -78,34 +96,54 @@ where
             .local
             .clone()
             .and_then(|l| peer_identity.map(|n| (n, l)));
-        ConnectFuture::Init {
-            future: self.inner.make_connection(target),
-            tls,
+
+        let alternates = target.alternate_peer_identities();
+        let retry = match tls {
+            Conditional::Some((ref name, ref local)) if !alternates.is_empty() => Some(Retry {
+                local: local.clone(),
+                connect: self.inner.clone(),
+                target: target.clone(),
+                alternates: alternates.into_iter(),
+                tried: vec![name.clone()],
+            }),
+            _ => None,
+        };
+
+        ConnectFuture {
+            state: State::Init {
+                future: self.inner.make_connection(target),
+                tls,
+            },
+            retry,
         }
     }
 }
 
 // ===== impl ConnectFuture =====
 
-impl<L, F> Future for ConnectFuture<L, F>
+impl<L, C, T> Future for ConnectFuture<L, C, T>
 where
-    L: HasConfig,
-    F: Future<Item = TcpStream>,
-    F::Error: From<io::Error>,
+    L: HasConfig + Clone,
+    C: tower::MakeConnection<T, Connection = TcpStream>,
+    C::Error: From<io::Error>,
+    T: Clone,
 {
     type Item = Connection;
-    type Error = F::Error;
+    type Error = C::Error;
 
     fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
         loop {
-            *self = match self {
-                ConnectFuture::Init { future, tls } => {
+            self.state = match self.state {
+                State::Init {
+                    ref mut future,
+                    ref tls,
+                } => {
                     let io = try_ready!(future.poll());
 
                     match tls {
                         Conditional::Some((peer_identity, local_tls)) => {
                             trace!(peer.id = %peer_identity, "initiating TLS");
-                            ConnectFuture::Handshake(
+                            State::Handshake(
                                 tokio_rustls::TlsConnector::from(local_tls.tls_client_config())
                                     .connect(peer_identity.as_dns_name_ref(), io),
                             )
@@ -116,11 +154,42 @@ where
                         }
                     }
                 }
-                ConnectFuture::Handshake(ref mut fut) => {
-                    let io = try_ready!(fut.poll());
-                    trace!("established TLS");
-                    return Ok(Connection::new(io).into());
-                }
+                State::Handshake(ref mut fut) => match fut.poll() {
+                    Ok(Async::NotReady) => return Ok(Async::NotReady),
+                    Ok(Async::Ready(io)) => {
+                        trace!("established TLS");
+                        return Ok(Connection::new(io).into());
+                    }
+                    Err(e) => {
+                        let retry = match self.retry.as_mut() {
+                            Some(retry) => retry,
+                            None => return Err(e.into()),
+                        };
+                        match retry.alternates.next() {
+                            Some(peer_identity) => {
+                                debug!(error = %e, peer.id = %peer_identity, "retrying TLS with alternate identity");
+                                retry.tried.push(peer_identity.clone());
+                                State::Init {
+                                    future: retry.connect.make_connection(retry.target.clone()),
+                                    tls: Conditional::Some((peer_identity, retry.local.clone())),
+                                }
+                            }
+                            None => {
+                                let tried = retry
+                                    .tried
+                                    .iter()
+                                    .map(|id| id.as_ref())
+                                    .collect::<Vec<&str>>()
+                                    .join(", ");
+                                let msg = format!(
+                                    "TLS handshake failed for all peer identities [{}]: {}",
+                                    tried, e
+                                );
+                                return Err(io::Error::new(e.kind(), msg).into());
+                            }
+                        }
+                    }
+                },
             };
         }
     }
@@ -16,6 +16,14 @@ pub type PeerIdentity = Conditional<identity::Name>;
 
 pub trait HasPeerIdentity {
     fn peer_identity(&self) -> PeerIdentity;
+
+    /// Identities that are also acceptable for the peer.
+    ///
+    /// When the peer does not accept `peer_identity`, the TLS client tries
+    /// each of these identities, in order, on a new connection.
+    fn alternate_peer_identities(&self) -> Vec<identity::Name> {
+        Vec::new()
+    }
 }
 
 #[derive(Clone, Copy, Debug, Eq, Hash, PartialEq)]
@@ -13,6 +13,14 @@ pub trait RequestFilter<T> {
     fn filter(&self, request: T) -> Result<T, Self::Error>;
 }
 
+/// Wraps services so that each request is filtered by a clone of `filter`.
+pub fn layer<I: Clone>(filter: I) -> Layer<I> {
+    Layer(filter)
+}
+
+#[derive(Clone, Debug)]
+pub struct Layer<I>(I);
+
 #[derive(Clone, Debug)]
 pub struct Service<I, S> {
     filter: I,
@@ -25,6 +33,19 @@ pub enum ResponseFuture<F> {
     Rejected(Option<Error>),
 }
 
+// === impl Layer ===
+
+impl<I: Clone, S> tower::layer::Layer<S> for Layer<I> {
+    type Service = Service<I, S>;
+
+    fn layer(&self, service: S) -> Self::Service {
+        Service {
+            filter: self.0.clone(),
+            service,
+        }
+    }
+}
+
 // === impl Service ===
 
 impl<I, S> Service<I, S> {
@@ -1,6 +1,7 @@
+use crate::metrics::{self, Eviction};
 use futures::{task, Async, Stream};
 use indexmap::IndexMap;
-use std::{hash::Hash, time::Duration};
+use std::{hash::Hash, sync::Arc, time::Duration};
 use tokio_timer::{delay_queue, DelayQueue};
 use tracing::trace;
 
@@ -37,6 +38,8 @@ where
     /// the current state of the cache.
     values: IndexMap<K, Node<V>>,
 
+    metrics: metrics::Handle,
+
     purge_task: Option<task::Task>,
 }
 
@@ -44,8 +47,15 @@ where
 struct Node<T> {
     dq_key: delay_queue::Key,
     value: T,
+    pin: Pin,
 }
 
+/// Prevents a cache value from expiring while it is held.
+///
+/// A value is pinned as long as any clone of its `Pin` exists.
+#[derive(Clone, Debug, Default)]
+pub struct Pin(Arc<()>);
+
 // ===== impl Cache =====
 
 impl<K, V> Cache<K, V>
@@ -60,10 +70,17 @@ where
             expires,
             expirations: DelayQueue::with_capacity(capacity),
             values: IndexMap::default(),
+            metrics: metrics::Handle::default(),
             purge_task: None,
         }
     }
 
+    /// Records the cache's size, insertions, evictions, and lookups.
+    pub fn with_metrics(mut self, metrics: metrics::Handle) -> Self {
+        self.metrics = metrics;
+        self
+    }
+
     pub fn capacity(&self) -> usize {
         self.capacity
     }
@@ -80,13 +97,23 @@ where
         if let Some(node) = self.values.get_mut(key) {
             self.expirations.reset(&node.dq_key, self.expires);
             trace!("reset expiration for cache value associated with key");
+            self.metrics.lookup(true);
 
             return Some(node.value.clone());
         }
 
+        self.metrics.lookup(false);
         None
     }
 
+    /// Pins the item associated with `key`, if it exists.
+    ///
+    /// The item will not expire until the returned `Pin` (and all of its
+    /// clones) are dropped.
+    pub fn pin(&self, key: &K) -> Option<Pin> {
+        self.values.get(key).map(|node| node.pin.clone())
+    }
+
     /// Attempts to insert an item by key.
     ///
     /// If a value is returned, this key has been set to expire after an
@@ -95,20 +122,41 @@ where
         let node = {
             trace!("inserting an item into the cache");
             let dq_key = self.expirations.insert(key.clone(), self.expires);
-            Node { dq_key, value }
+            Node {
+                dq_key,
+                value,
+                pin: Pin::default(),
+            }
         };
 
         if let Some(purge) = self.purge_task.take() {
             purge.notify();
         }
 
-        self.values.insert(key, node).map(|n| n.value)
+        let old = self.values.insert(key, node).map(|n| n.value);
+        if old.is_none() {
+            self.metrics.insert();
+        }
+        old
+    }
+
+    /// Removes an item by key, regardless of whether it has expired.
+    ///
+    /// This is recorded as the eviction of a failed value.
+    pub fn remove(&mut self, key: &K) -> Option<V> {
+        let node = self.values.remove(key)?;
+        self.expirations.remove(&node.dq_key);
+        trace!("removed an item from the cache");
+        self.metrics.evict(Eviction::Failed);
+        Some(node.value)
     }
 
     /// Evict expired values from the cache.
     ///
     /// Polls the underlying `DelayQueue`. When elements are returned from the
-    /// queue, remove the associated key from `values`.
+    /// queue, remove the associated key from `values`. Pinned values are not
+    /// removed; instead, they are set to expire after another `expires` span
+    /// of time.
     pub fn purge(&mut self) {
         loop {
             match self.expirations.poll() {
@@ -119,14 +167,45 @@ where
                     return;
                 }
                 Ok(Async::Ready(Some(key))) => {
+                    let key = key.into_inner();
+                    if let Some(node) = self.values.get_mut(&key) {
+                        if node.pin.is_pinned() {
+                            trace!("deferring the expiration of a pinned item");
+                            node.dq_key = self.expirations.insert(key, self.expires);
+                            continue;
+                        }
+                    }
+
                     trace!("expiring an item from the cache");
-                    self.values.remove(key.get_ref());
+                    if self.values.remove(&key).is_some() {
+                        self.metrics.evict(Eviction::Idle);
+                    }
                 }
             }
         }
     }
 }
 
+impl<K, V> Drop for Cache<K, V>
+where
+    K: Clone + Eq + Hash,
+{
+    fn drop(&mut self) {
+        // Routers with the same name share metrics, so the dropped values must
+        // not be reported by the routers that remain.
+        self.metrics.drop_values(self.values.len());
+    }
+}
+
+// ===== impl Pin =====
+
+impl Pin {
+    fn is_pinned(&self) -> bool {
+        // The cache holds one reference.
+        Arc::strong_count(&self.0) > 1
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -174,6 +253,21 @@ mod tests {
         }))
     }
 
+    #[test]
+    fn remove_value() {
+        current_thread::run(future::lazy(|| {
+            let mut cache = Cache::new(2, Duration::from_millis(10));
+
+            cache.insert(1, 2);
+            assert_eq!(cache.remove(&1), Some(2));
+            assert!(cache.access(&1).is_none());
+            assert_eq!(cache.remove(&1), None);
+            assert!(cache.can_insert());
+
+            Ok::<_, ()>(())
+        }))
+    }
+
     #[test]
     fn insert_and_background_purge() {
         let mut rt = Runtime::new().unwrap();
@@ -210,6 +304,127 @@ mod tests {
         assert_eq!(cache.values.len(), 0);
     }
 
+    #[test]
+    fn records_metrics() {
+        use linkerd2_metrics::FmtMetrics;
+
+        let mut rt = Runtime::new().unwrap();
+
+        let registry = metrics::Registry::default();
+        let mut lock = Lock::new(
+            Cache::new(1, Duration::from_millis(10)).with_metrics(registry.cache("test")),
+        );
+
+        let (purge, _handle) = Purge::new(lock.clone());
+        rt.spawn(purge.map_err(|n| match n {}));
+
+        rt.block_on(future::lazy(|| {
+            let mut cache = match lock.poll_lock() {
+                Async::Ready(cache) => cache,
+                _ => panic!("cache lock should be Ready"),
+            };
+
+            assert!(cache.access(&1).is_none());
+            cache.insert(1, 2);
+            assert!(cache.access(&1).is_some());
+            assert!(!cache.can_insert());
+
+            Ok::<_, ()>(())
+        }))
+        .unwrap();
+
+        let report = registry.as_display().to_string();
+        assert!(report.contains("cache_size{cache=\"test\"} 1\n"));
+        assert!(report.contains("cache_insertions_total{cache=\"test\"} 1\n"));
+        assert!(report.contains("cache_lookups_total{cache=\"test\",result=\"hit\"} 1\n"));
+        assert!(report.contains("cache_lookups_total{cache=\"test\",result=\"miss\"} 1\n"));
+
+        // Sleep for enough time that the value expires.
+        rt.block_on(tokio_timer::sleep(Duration::from_millis(100)))
+            .unwrap();
+
+        let report = registry.as_display().to_string();
+        assert!(report.contains("cache_size{cache=\"test\"} 0\n"));
+        assert!(report.contains("cache_evictions_total{cache=\"test\",reason=\"idle\"} 1\n"));
+        assert!(report.contains("cache_evictions_total{cache=\"test\",reason=\"failed\"} 0\n"));
+    }
+
+    #[test]
+    fn dropped_caches_are_removed_from_the_size() {
+        use linkerd2_metrics::FmtMetrics;
+
+        let registry = metrics::Registry::default();
+        current_thread::run(future::lazy(|| {
+            let mut a = Cache::new(2, Duration::from_secs(10)).with_metrics(registry.cache("test"));
+            let mut b = Cache::new(2, Duration::from_secs(10)).with_metrics(registry.cache("test"));
+
+            a.insert(1, 2);
+            b.insert(1, 2);
+            b.insert(2, 3);
+            let report = registry.as_display().to_string();
+            assert!(report.contains("cache_size{cache=\"test\"} 3\n"));
+
+            drop(b);
+            let report = registry.as_display().to_string();
+            assert!(report.contains("cache_size{cache=\"test\"} 1\n"));
+
+            drop(a);
+            let report = registry.as_display().to_string();
+            assert!(report.contains("cache_size{cache=\"test\"} 0\n"));
+
+            Ok::<_, ()>(())
+        }))
+    }
+
+    #[test]
+    fn pinned_values_do_not_expire() {
+        let mut rt = Runtime::new().unwrap();
+
+        let mut lock = Lock::new(Cache::new(2, Duration::from_millis(10)));
+
+        let (purge, _handle) = Purge::new(lock.clone());
+        rt.spawn(purge.map_err(|n| match n {}));
+
+        // Insert into the cache and hold a pin on the value.
+        let pin = rt
+            .block_on(future::lazy(|| {
+                let mut cache = match lock.poll_lock() {
+                    Async::Ready(cache) => cache,
+                    _ => panic!("cache lock should be Ready"),
+                };
+
+                cache.insert(1, 2);
+                Ok::<_, ()>(cache.pin(&1).expect("value must be cached"))
+            }))
+            .unwrap();
+
+        // Sleep for well beyond the expiration time.
+        rt.block_on(tokio_timer::sleep(Duration::from_millis(100)))
+            .unwrap();
+
+        rt.block_on(future::lazy(|| {
+            let cache = match lock.poll_lock() {
+                Async::Ready(cache) => cache,
+                _ => panic!("cache lock should be Ready"),
+            };
+            assert_eq!(cache.values.len(), 1, "pinned value must not expire");
+
+            Ok::<_, ()>(())
+        }))
+        .unwrap();
+
+        // Once the pin is released, the value expires on a later purge.
+        drop(pin);
+        rt.block_on(tokio_timer::sleep(Duration::from_millis(100)))
+            .unwrap();
+
+        let cache = match lock.poll_lock() {
+            Async::Ready(acquired) => acquired,
+            _ => panic!("cache lock should be Ready"),
+        };
+        assert_eq!(cache.values.len(), 0);
+    }
+
     #[test]
     fn access_resets_expiration() {
         let mut rt = Runtime::new().unwrap();
@@ -1,4 +1,4 @@
-use crate::{Recognize, Router};
+use crate::{metrics, NoInstrument, Recognize, Router};
 use futures::{Future, Poll};
 use linkerd2_error::{Error, Never};
 use std::marker::PhantomData;
@@ -10,6 +10,7 @@ use tracing_futures::Instrument;
 pub struct Config {
     capacity: usize,
     max_idle_age: Duration,
+    metrics: metrics::Handle,
 }
 
 /// A layer that that builds a routing service.
@@ -18,27 +19,29 @@ pub struct Config {
 /// `Req`-typed request. If the router doesn't already have a `Service` for this
 /// target, it uses a `Mk`-typed `Service` stack.
 #[derive(Debug)]
-pub struct Layer<Req, Rec: Recognize<Req>> {
+pub struct Layer<Req, Rec: Recognize<Req>, I = NoInstrument> {
     config: Config,
     recognize: Rec,
+    instrument: I,
     _p: PhantomData<fn() -> Req>,
 }
 
 #[derive(Debug)]
-pub struct Make<Req, Rec: Recognize<Req>, Mk> {
+pub struct Make<Req, Rec: Recognize<Req>, Mk, I = NoInstrument> {
     config: Config,
     recognize: Rec,
     inner: Mk,
+    instrument: I,
     _p: PhantomData<fn() -> Req>,
 }
 
-pub struct Service<Req, Rec, Mk>
+pub struct Service<Req, Rec, Mk, I = NoInstrument>
 where
     Rec: Recognize<Req>,
     Mk: super::Make<Rec::Target>,
     Mk::Value: tower::Service<Req>,
 {
-    inner: Router<Req, Rec, Mk>,
+    inner: Router<Req, Rec, Mk, I>,
 }
 
 // === impl Config ===
@@ -48,6 +51,15 @@ impl Config {
         Self {
             capacity,
             max_idle_age,
+            metrics: metrics::Handle::default(),
+        }
+    }
+
+    /// Reports the router cache's metrics to `registry` as `name`.
+    pub fn with_metrics(self, name: &'static str, registry: &metrics::Registry) -> Self {
+        Self {
+            metrics: registry.cache(name),
+            ..self
         }
     }
 }
@@ -62,73 +74,103 @@ where
         Self {
             config,
             recognize,
+            instrument: NoInstrument,
+            _p: PhantomData,
+        }
+    }
+}
+
+impl<Req, Rec, I> Layer<Req, Rec, I>
+where
+    Rec: Recognize<Req> + Clone + Send + Sync + 'static,
+{
+    /// Instruments each response with the `Pin` of the service that produced
+    /// it. See `Router::with_instrument`.
+    pub fn with_instrument<J>(self, instrument: J) -> Layer<Req, Rec, J> {
+        Layer {
+            config: self.config,
+            recognize: self.recognize,
+            instrument,
             _p: PhantomData,
         }
     }
 }
 
-impl<Req, Rec, Mk> tower::layer::Layer<Mk> for Layer<Req, Rec>
+impl<Req, Rec, Mk, I> tower::layer::Layer<Mk> for Layer<Req, Rec, I>
 where
     Rec: Recognize<Req> + Clone + Send + Sync + 'static,
     Mk: super::Make<Rec::Target> + Clone + Send + Sync + 'static,
     Mk::Value: tower::Service<Req> + Clone,
     <Mk::Value as tower::Service<Req>>::Error: Into<Error>,
+    I: Clone,
 {
-    type Service = Make<Req, Rec, Mk>;
+    type Service = Make<Req, Rec, Mk, I>;
 
     fn layer(&self, inner: Mk) -> Self::Service {
         Make {
             inner,
             config: self.config.clone(),
             recognize: self.recognize.clone(),
+            instrument: self.instrument.clone(),
             _p: PhantomData,
         }
     }
 }
 
-impl<Req, Rec> Clone for Layer<Req, Rec>
+impl<Req, Rec, I> Clone for Layer<Req, Rec, I>
 where
     Rec: Recognize<Req> + Clone + Send + Sync + 'static,
+    I: Clone,
 {
     fn clone(&self) -> Self {
-        Self::new(self.config.clone(), self.recognize.clone())
+        Self {
+            config: self.config.clone(),
+            recognize: self.recognize.clone(),
+            instrument: self.instrument.clone(),
+            _p: PhantomData,
+        }
     }
 }
 // === impl Make ===
 
-impl<Req, Rec, Mk> Make<Req, Rec, Mk>
+impl<Req, Rec, Mk, I> Make<Req, Rec, Mk, I>
 where
     Rec: Recognize<Req> + Clone + Send + Sync + 'static,
     <Rec as Recognize<Req>>::Target: Send + 'static,
     Mk: super::Make<Rec::Target> + Clone + Send + Sync + 'static,
     Mk::Value: tower::Service<Req> + Clone + Send + 'static,
     <Mk::Value as tower::Service<Req>>::Error: Into<Error>,
+    I: Clone,
 {
-    pub fn spawn(&self) -> Service<Req, Rec, Mk> {
-        let (inner, purge) = Router::new(
+    pub fn spawn(&self) -> Service<Req, Rec, Mk, I> {
+        let (inner, purge) = Router::new_with_metrics(
             self.recognize.clone(),
             self.inner.clone(),
             self.config.capacity,
             self.config.max_idle_age,
+            self.config.metrics.clone(),
         );
         tokio::spawn(
             purge
                 .map_err(|e| match e {})
                 .instrument(info_span!("router.purge")),
         );
-        Service { inner }
+        Service {
+            inner: inner.with_instrument(self.instrument.clone()),
+        }
     }
 }
 
-impl<Req, Rec, Mk, T> tower::Service<T> for Make<Req, Rec, Mk>
+impl<Req, Rec, Mk, I, T> tower::Service<T> for Make<Req, Rec, Mk, I>
 where
     Rec: Recognize<Req> + Clone + Send + Sync + 'static,
     <Rec as Recognize<Req>>::Target: Send + 'static,
     Mk: super::Make<Rec::Target> + Clone + Send + Sync + 'static,
     Mk::Value: tower::Service<Req> + Clone + Send + 'static,
     <Mk::Value as tower::Service<Req>>::Error: Into<Error>,
+    I: Clone,
 {
-    type Response = Service<Req, Rec, Mk>;
+    type Response = Service<Req, Rec, Mk, I>;
     type Error = Never;
     type Future = futures::future::FutureResult<Self::Response, Self::Error>;
 
@@ -141,32 +183,35 @@ where
     }
 }
 
-impl<Req, Rec, Mk> Clone for Make<Req, Rec, Mk>
+impl<Req, Rec, Mk, I> Clone for Make<Req, Rec, Mk, I>
 where
     Rec: Recognize<Req> + Clone,
     Mk: Clone,
+    I: Clone,
 {
     fn clone(&self) -> Self {
         Self {
             config: self.config.clone(),
             recognize: self.recognize.clone(),
             inner: self.inner.clone(),
+            instrument: self.instrument.clone(),
             _p: PhantomData,
         }
     }
 }
 // === impl Service ===
 
-impl<Req, Rec, Mk> tower::Service<Req> for Service<Req, Rec, Mk>
+impl<Req, Rec, Mk, I> tower::Service<Req> for Service<Req, Rec, Mk, I>
 where
     Rec: Recognize<Req> + Send + Sync + 'static,
     Mk: super::Make<Rec::Target> + Clone + Send + Sync + 'static,
     Mk::Value: tower::Service<Req> + Clone,
     <Mk::Value as tower::Service<Req>>::Error: Into<Error>,
+    Router<Req, Rec, Mk, I>: tower::Service<Req>,
 {
-    type Response = <Router<Req, Rec, Mk> as tower::Service<Req>>::Response;
-    type Error = <Router<Req, Rec, Mk> as tower::Service<Req>>::Error;
-    type Future = <Router<Req, Rec, Mk> as tower::Service<Req>>::Future;
+    type Response = <Router<Req, Rec, Mk, I> as tower::Service<Req>>::Response;
+    type Error = <Router<Req, Rec, Mk, I> as tower::Service<Req>>::Error;
+    type Future = <Router<Req, Rec, Mk, I> as tower::Service<Req>>::Future;
 
     fn poll_ready(&mut self) -> Poll<(), Self::Error> {
         self.inner.poll_ready()
@@ -178,12 +223,12 @@ where
     }
 }
 
-impl<Req, Rec, Mk> Clone for Service<Req, Rec, Mk>
+impl<Req, Rec, Mk, I> Clone for Service<Req, Rec, Mk, I>
 where
     Rec: Recognize<Req>,
     Mk: super::Make<Rec::Target>,
     Mk::Value: tower::Service<Req>,
-    Router<Req, Rec, Mk>: Clone,
+    Router<Req, Rec, Mk, I>: Clone,
 {
     fn clone(&self) -> Self {
         Self {
@@ -3,27 +3,35 @@
 mod cache;
 pub mod error;
 pub mod layer;
+pub mod metrics;
 mod purge;
 
 use self::cache::Cache;
+pub use self::cache::Pin;
 pub use self::layer::{Config, Layer};
 pub use self::purge::Purge;
-use futures::{Async, Future, Poll};
+use futures::{try_ready, Async, Future, Poll};
 use indexmap::IndexMap;
 use std::hash::Hash;
 use std::time::Duration;
 use tokio::sync::lock::Lock;
+pub use tower_load::{Instrument, NoInstrument};
 pub use tower_load_shed::LoadShed;
 use tracing::{debug, trace};
 
 /// Routes requests based on a configurable `Key`.
-pub struct Router<Req, Rec, Mk>
+///
+/// Each response is instrumented with a `Pin` on the service that produced
+/// it, so that the service is not evicted while the response is in use. By
+/// default, the pin is released as soon as the response is returned.
+pub struct Router<Req, Rec, Mk, I = NoInstrument>
 where
     Rec: Recognize<Req>,
     Mk: Make<Rec::Target>,
     Mk::Value: tower::Service<Req>,
 {
     inner: Inner<Req, Rec, Mk>,
+    instrument: I,
     _hangup: purge::Handle,
 }
 
@@ -58,11 +66,27 @@ where
     }
 }
 
+impl<Target, P, A, B> Make<Target> for linkerd2_stack::switch::MakeSwitch<P, A, B>
+where
+    P: Fn(&Target) -> bool,
+    A: Make<Target>,
+    B: Make<Target>,
+{
+    type Value = tower::util::Either<A::Value, B::Value>;
+
+    fn make(&self, target: &Target) -> Self::Value {
+        match self.select(target) {
+            tower::util::Either::A(inner) => tower::util::Either::A(inner.make(target)),
+            tower::util::Either::B(other) => tower::util::Either::B(other.make(target)),
+        }
+    }
+}
+
 /// A map of known routes and services used when creating a fixed router.
 #[derive(Clone, Debug)]
 pub struct FixedMake<T: Clone + Eq + Hash, Svc>(IndexMap<T, Svc>);
 
-pub struct ResponseFuture<Req, Rec, Mk>
+pub struct ResponseFuture<Req, Rec, Mk, I = NoInstrument>
 where
     Rec: Recognize<Req>,
     Mk: Make<Rec::Target>,
@@ -70,6 +94,7 @@ where
     <Mk::Value as tower::Service<Req>>::Error: Into<error::Error>,
 {
     state: State<Req, Rec, Mk>,
+    instrument: I,
 }
 
 struct Inner<Req, Rec, Mk>
@@ -96,8 +121,14 @@ where
         make: Option<Mk>,
         cache: Lock<Cache<Rec::Target, LoadShed<Mk::Value>>>,
     },
-    Call(Option<Req>, Option<LoadShed<Mk::Value>>),
-    Respond(<LoadShed<Mk::Value> as tower::Service<Req>>::Future),
+    Call(Option<Req>, Option<LoadShed<Mk::Value>>, Option<Pin>),
+    /// The pin prevents the service from being evicted from the cache while
+    /// the response is in flight. It is handed to the response's instrument
+    /// once the response is ready.
+    Respond(
+        <LoadShed<Mk::Value> as tower::Service<Req>>::Future,
+        Option<Pin>,
+    ),
     Error(Option<error::Error>),
 }
 
@@ -147,10 +178,28 @@ where
         capacity: usize,
         max_idle_age: Duration,
     ) -> (Self, Purge<Rec::Target, LoadShed<Mk::Value>>) {
-        let cache = Lock::new(Cache::new(capacity, max_idle_age));
+        Self::new_with_metrics(
+            recognize,
+            make,
+            capacity,
+            max_idle_age,
+            metrics::Handle::default(),
+        )
+    }
+
+    /// A router whose cache records its state to `metrics`.
+    pub fn new_with_metrics(
+        recognize: Rec,
+        make: Mk,
+        capacity: usize,
+        max_idle_age: Duration,
+        metrics: metrics::Handle,
+    ) -> (Self, Purge<Rec::Target, LoadShed<Mk::Value>>) {
+        let cache = Lock::new(Cache::new(capacity, max_idle_age).with_metrics(metrics));
         let (purge, _hangup) = Purge::new(cache.clone());
         let router = Self {
             _hangup,
+            instrument: NoInstrument,
             inner: Inner {
                 recognize,
                 make,
@@ -162,6 +211,24 @@ where
     }
 }
 
+impl<Req, Rec, Mk, I> Router<Req, Rec, Mk, I>
+where
+    Rec: Recognize<Req>,
+    Mk: Make<Rec::Target>,
+    Mk::Value: tower::Service<Req>,
+{
+    /// Instruments each response with the `Pin` of the service that produced
+    /// it, e.g. so that a service is not evicted while its response body
+    /// streams.
+    pub fn with_instrument<J>(self, instrument: J) -> Router<Req, Rec, Mk, J> {
+        Router {
+            inner: self.inner,
+            instrument,
+            _hangup: self._hangup,
+        }
+    }
+}
+
 impl<Req, Rec, Svc> Router<Req, Rec, FixedMake<Rec::Target, Svc>>
 where
     Rec: Recognize<Req>,
@@ -190,16 +257,17 @@ where
     }
 }
 
-impl<Req, Rec, Mk> tower::Service<Req> for Router<Req, Rec, Mk>
+impl<Req, Rec, Mk, I> tower::Service<Req> for Router<Req, Rec, Mk, I>
 where
     Rec: Recognize<Req>,
     Mk: Make<Rec::Target> + Clone,
     Mk::Value: tower::Service<Req> + Clone,
     <Mk::Value as tower::Service<Req>>::Error: Into<error::Error>,
+    I: Instrument<Pin, <Mk::Value as tower::Service<Req>>::Response> + Clone,
 {
-    type Response = <Mk::Value as tower::Service<Req>>::Response;
+    type Response = I::Output;
     type Error = error::Error;
-    type Future = ResponseFuture<Req, Rec, Mk>;
+    type Future = ResponseFuture<Req, Rec, Mk, I>;
 
     /// Always ready to serve.
     ///
@@ -216,7 +284,7 @@ where
     fn call(&mut self, request: Req) -> Self::Future {
         let target = match self.inner.recognize.recognize(&request) {
             Some(target) => target,
-            None => return ResponseFuture::not_recognized(),
+            None => return ResponseFuture::not_recognized(self.instrument.clone()),
         };
 
         ResponseFuture::new(
@@ -224,19 +292,22 @@ where
             target,
             self.inner.make.clone(),
             self.inner.cache.clone(),
+            self.instrument.clone(),
         )
     }
 }
 
-impl<Req, Rec, Mk> Clone for Router<Req, Rec, Mk>
+impl<Req, Rec, Mk, I> Clone for Router<Req, Rec, Mk, I>
 where
     Rec: Recognize<Req> + Clone,
     Mk: Make<Rec::Target> + Clone,
     Mk::Value: tower::Service<Req>,
+    I: Clone,
 {
     fn clone(&self) -> Self {
         Router {
             inner: self.inner.clone(),
+            instrument: self.instrument.clone(),
             _hangup: self._hangup.clone(),
         }
     }
@@ -244,7 +315,7 @@ where
 
 // ===== impl ResponseFuture =====
 
-impl<Req, Rec, Mk> ResponseFuture<Req, Rec, Mk>
+impl<Req, Rec, Mk, I> ResponseFuture<Req, Rec, Mk, I>
 where
     Rec: Recognize<Req>,
     Mk: Make<Rec::Target>,
@@ -256,6 +327,7 @@ where
         target: Rec::Target,
         make: Mk,
         cache: Lock<Cache<Rec::Target, LoadShed<Mk::Value>>>,
+        instrument: I,
     ) -> Self {
         ResponseFuture {
             state: State::Acquire {
@@ -264,28 +336,31 @@ where
                 make: Some(make),
                 cache: cache,
             },
+            instrument,
         }
     }
 
-    fn error(err: error::Error) -> Self {
+    fn error(err: error::Error, instrument: I) -> Self {
         ResponseFuture {
             state: State::Error(Some(err)),
+            instrument,
         }
     }
 
-    fn not_recognized() -> Self {
-        Self::error(error::NotRecognized.into())
+    fn not_recognized(instrument: I) -> Self {
+        Self::error(error::NotRecognized.into(), instrument)
     }
 }
 
-impl<Req, Rec, Mk> Future for ResponseFuture<Req, Rec, Mk>
+impl<Req, Rec, Mk, I> Future for ResponseFuture<Req, Rec, Mk, I>
 where
     Rec: Recognize<Req>,
     Mk: Make<Rec::Target>,
     Mk::Value: tower::Service<Req> + Clone,
     <Mk::Value as tower::Service<Req>>::Error: Into<error::Error>,
+    I: Instrument<Pin, <Mk::Value as tower::Service<Req>>::Response>,
 {
-    type Item = <LoadShed<Mk::Value> as tower::Service<Req>>::Response;
+    type Item = I::Output;
     type Error = error::Error;
 
     fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
@@ -310,9 +385,9 @@ where
 
                     // If the target is already cached, route the request to
                     // the service; otherwise, try to insert it
-                    if let Some(service) = cache.access(&target) {
+                    let mut service = if let Some(service) = cache.access(&target) {
                         trace!("target already cached");
-                        State::Call(Some(request), Some(service))
+                        service
                     } else {
                         debug!("target not cached");
 
@@ -327,22 +402,39 @@ where
                         let service = LoadShed::new(make.make(&target));
 
                         debug!("inserting new target into cache");
-                        cache.insert(target, service.clone());
-                        State::Call(Some(request), Some(service))
+                        cache.insert(target.clone(), service.clone());
+                        service
+                    };
+
+                    // A service that has failed (e.g. because it could not
+                    // be made in time) is evicted, so that it is rebuilt for
+                    // subsequent requests rather than failing them all.
+                    match service.poll_ready() {
+                        Ok(ready) => assert!(
+                            ready.is_ready(),
+                            "load shedding services must always be ready"
+                        ),
+                        Err(e) => {
+                            debug!("evicting failed service from cache");
+                            cache.remove(&target);
+                            return Err(e.into());
+                        }
                     }
+
+                    let pin = cache.pin(&target).expect("target must be cached");
+                    State::Call(Some(request), Some(service), Some(pin))
                 }
-                State::Call(ref mut request, ref mut service) => {
+                State::Call(ref mut request, ref mut service, ref mut pin) => {
                     let mut service = service.take().expect("polled after ready");
-
-                    assert!(
-                        service.poll_ready()?.is_ready(),
-                        "load shedding services must always be ready"
-                    );
-
                     let request = request.take().expect("polled after ready");
-                    State::Respond(service.call(request))
+                    let pin = pin.take().expect("polled after ready");
+                    State::Respond(service.call(request), Some(pin))
+                }
+                State::Respond(ref mut fut, ref mut pin) => {
+                    let rsp = try_ready!(fut.poll().map_err(Into::into));
+                    let pin = pin.take().expect("polled after ready");
+                    return Ok(Async::Ready(self.instrument.instrument(pin, rsp)));
                 }
-                State::Respond(ref mut fut) => return fut.poll().map_err(Into::into),
                 State::Error(ref mut err) => return Err(err.take().expect("polled after ready")),
             }
         }
@@ -577,6 +669,76 @@ mod tests {
         );
     }
 
+    #[test]
+    fn failed_services_are_evicted() {
+        use std::cell::Cell;
+        use std::rc::Rc;
+
+        let makes = Rc::new(Cell::new(0));
+        let make = {
+            let makes = makes.clone();
+            move |_: &usize| {
+                makes.set(makes.get() + 1);
+                MultiplyAndAssign::new(usize::MAX)
+            }
+        };
+        let (mut router, _cache_bg) = Router::new(Recognize, make, 1, Duration::from_secs(60));
+
+        router.call_err(2);
+        router.call_err(2);
+        assert_eq!(makes.get(), 2, "failed service must be rebuilt");
+    }
+
+    #[test]
+    fn held_responses_pin_their_services() {
+        use crate::{Instrument, Pin};
+        use std::cell::Cell;
+        use std::rc::Rc;
+        use tokio::runtime::current_thread::Runtime;
+
+        /// Holds the pin with the response, like a body that is still
+        /// streaming.
+        #[derive(Clone)]
+        struct Hold;
+
+        impl Instrument<Pin, usize> for Hold {
+            type Output = (usize, Pin);
+
+            fn instrument(&self, pin: Pin, rsp: usize) -> Self::Output {
+                (rsp, pin)
+            }
+        }
+
+        let mut rt = Runtime::new().unwrap();
+
+        let makes = Rc::new(Cell::new(0));
+        let make = {
+            let makes = makes.clone();
+            move |_: &usize| {
+                makes.set(makes.get() + 1);
+                MultiplyAndAssign::default()
+            }
+        };
+        let (router, purge) = Router::new(Recognize, make, 1, Duration::from_millis(10));
+        let mut router = router.with_instrument(Hold);
+        rt.spawn(purge.map_err(|n| match n {}));
+
+        let (_, held) = rt.block_on(router.call(Request::Recognized(2))).unwrap();
+
+        // Sleep for well beyond the max idle age while the response is held.
+        rt.block_on(tokio_timer::sleep(Duration::from_millis(100)))
+            .unwrap();
+        rt.block_on(router.call(Request::Recognized(2))).unwrap();
+        assert_eq!(makes.get(), 1, "service must not expire while held");
+
+        // Once the response is released, the service expires.
+        drop(held);
+        rt.block_on(tokio_timer::sleep(Duration::from_millis(100)))
+            .unwrap();
+        rt.block_on(router.call(Request::Recognized(2))).unwrap();
+        assert_eq!(makes.get(), 2, "service must expire once released");
+    }
+
     #[test]
     fn load_shed_from_inner_services() {
         use tower_load_shed::error::Overloaded;
@@ -591,4 +753,24 @@ mod tests {
         let err = router.call_err(2);
         assert!(err.downcast_ref::<Overloaded>().is_some(), "Not overloaded",);
     }
+
+    #[test]
+    fn switch_makes_with_the_selected_stack() {
+        use linkerd2_stack::switch::MakeSwitch;
+        use tower::util::Either;
+
+        let make = MakeSwitch::new(
+            |n: &usize| n % 2 == 0,
+            |n: &usize| n * 10,
+            |n: &usize| format!("odd {}", n),
+        );
+        match make.make(&2) {
+            Either::A(n) => assert_eq!(n, 20),
+            Either::B(_) => panic!("even targets must be made by the inner stack"),
+        }
+        match make.make(&3) {
+            Either::B(s) => assert_eq!(s, "odd 3"),
+            Either::A(_) => panic!("odd targets must be made by the alternate stack"),
+        }
+    }
 }
@@ -0,0 +1,169 @@
+//! Reports the state of each named router cache.
+
+use linkerd2_metrics::{
+    metrics, Counter, FmtLabels, FmtMetric, FmtMetrics, Gauge, NamedMetrics, NamedRegistry,
+};
+use std::fmt;
+
+metrics! {
+    cache_size: Gauge { "The number of services held in a router cache" },
+    cache_insertions_total: Counter {
+        "Total count of services inserted into a router cache"
+    },
+    cache_evictions_total: Counter {
+        "Total count of services evicted from a router cache"
+    },
+    cache_lookups_total: Counter { "Total count of router cache lookups" }
+}
+
+/// Holds the metrics of all named caches.
+#[derive(Clone, Debug, Default)]
+pub struct Registry(NamedRegistry<Metrics>);
+
+/// Records the metrics of a single cache.
+///
+/// Routers spawned with the same name share a handle, so their metrics are
+/// aggregated.
+#[derive(Clone, Debug, Default)]
+pub struct Handle(NamedMetrics<Metrics>);
+
+/// The reason that a service was evicted from a cache.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Eviction {
+    /// The service was not used within the cache's `max_idle_age`.
+    Idle,
+    /// The service failed and must be rebuilt.
+    Failed,
+}
+
+#[derive(Clone, Debug, Default)]
+struct Metrics {
+    size: Gauge,
+    insertions: Counter,
+    idle_evictions: Counter,
+    failed_evictions: Counter,
+    hits: Counter,
+    misses: Counter,
+}
+
+struct CacheLabel(&'static str);
+
+struct Reason(Eviction);
+
+struct Lookup(&'static str);
+
+// === impl Registry ===
+
+impl Registry {
+    /// Returns a handle for the cache named `name`, creating it if necessary.
+    pub fn cache(&self, name: &'static str) -> Handle {
+        Handle(self.0.get(name))
+    }
+}
+
+impl FmtMetrics for Registry {
+    fn fmt_metrics(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let caches = self.0.snapshot();
+        if caches.is_empty() {
+            return Ok(());
+        }
+
+        cache_size.fmt_help(f)?;
+        for &(name, ref m) in &caches {
+            m.size
+                .fmt_metric_labeled(f, cache_size.name, CacheLabel(name))?;
+        }
+
+        cache_insertions_total.fmt_help(f)?;
+        for &(name, ref m) in &caches {
+            m.insertions
+                .fmt_metric_labeled(f, cache_insertions_total.name, CacheLabel(name))?;
+        }
+
+        cache_evictions_total.fmt_help(f)?;
+        for &(name, ref m) in &caches {
+            let label = CacheLabel(name);
+            m.idle_evictions.fmt_metric_labeled(
+                f,
+                cache_evictions_total.name,
+                (&label, Reason(Eviction::Idle)),
+            )?;
+            m.failed_evictions.fmt_metric_labeled(
+                f,
+                cache_evictions_total.name,
+                (&label, Reason(Eviction::Failed)),
+            )?;
+        }
+
+        cache_lookups_total.fmt_help(f)?;
+        for &(name, ref m) in &caches {
+            let label = CacheLabel(name);
+            m.hits
+                .fmt_metric_labeled(f, cache_lookups_total.name, (&label, Lookup("hit")))?;
+            m.misses
+                .fmt_metric_labeled(f, cache_lookups_total.name, (&label, Lookup("miss")))?;
+        }
+
+        Ok(())
+    }
+}
+
+// === impl Handle ===
+
+impl Handle {
+    pub(crate) fn lookup(&self, hit: bool) {
+        self.update(|m| if hit { m.hits.incr() } else { m.misses.incr() });
+    }
+
+    pub(crate) fn insert(&self) {
+        self.update(|m| {
+            m.insertions.incr();
+            m.size.incr();
+        });
+    }
+
+    pub(crate) fn evict(&self, reason: Eviction) {
+        self.update(|m| {
+            m.size.decr();
+            match reason {
+                Eviction::Idle => m.idle_evictions.incr(),
+                Eviction::Failed => m.failed_evictions.incr(),
+            }
+        });
+    }
+
+    /// Removes a dropped cache's values from the cache size.
+    pub(crate) fn drop_values(&self, values: usize) {
+        self.update(|m| {
+            let size: u64 = m.size.into();
+            m.size = size.saturating_sub(values as u64).into();
+        });
+    }
+
+    fn update(&self, f: impl FnOnce(&mut Metrics)) {
+        self.0.update(f)
+    }
+}
+
+// === impl Labels ===
+
+impl FmtLabels for CacheLabel {
+    fn fmt_labels(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "cache=\"{}\"", self.0)
+    }
+}
+
+impl FmtLabels for Reason {
+    fn fmt_labels(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            Eviction::Idle => write!(f, "reason=\"idle\""),
+            Eviction::Failed => write!(f, "reason=\"failed\""),
+        }
+    }
+}
+
+impl FmtLabels for Lookup {
+    fn fmt_labels(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "result=\"{}\"", self.0)
+    }
+}
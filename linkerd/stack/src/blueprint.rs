@@ -0,0 +1,259 @@
+//! Describes how named layers are composed so that their order may be
+//! validated when a stack is built.
+//!
+//! A stack declares anchors--well-known points like a buffer--as it is
+//! assembled. Optional layers are registered with a name and constraints on
+//! whether they must be pushed inside (before) or outside (after) other named
+//! entries. Entries are recorded from the innermost layer outward.
+//!
+//! A blueprint is only metadata: it does not change how layers are applied.
+
+use std::fmt;
+
+/// Declared where requests may be buffered.
+pub const BUFFER: &str = "buffer";
+
+/// Declared where services are cached, e.g. by a router.
+pub const CACHE: &str = "cache";
+
+/// Declared where per-route metrics are recorded.
+pub const ROUTE_METRICS: &str = "route-metrics";
+
+/// The named entries of a stack, from innermost to outermost.
+#[derive(Clone, Debug, Default)]
+pub struct Blueprint {
+    entries: Vec<Named>,
+}
+
+/// A named entry and the constraints on where it may be pushed.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Named {
+    name: &'static str,
+    constraints: Vec<Constraint>,
+}
+
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Constraint {
+    /// The entry must be pushed before the named entry.
+    Inside(&'static str),
+    /// The entry must be pushed after the named entry.
+    Outside(&'static str),
+}
+
+/// Describes a constraint that a blueprint does not satisfy.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum Violation {
+    /// The constraint refers to an entry that is not in the stack.
+    Missing {
+        layer: &'static str,
+        constraint: Constraint,
+    },
+    /// The layer is pushed on the wrong side of the named entry.
+    Misordered {
+        layer: &'static str,
+        constraint: Constraint,
+    },
+}
+
+// === impl Blueprint ===
+
+impl Blueprint {
+    /// Records an anchor as the outermost entry.
+    pub fn push_anchor(self, name: &'static str) -> Self {
+        self.push(Named::new(name))
+    }
+
+    /// Records a named layer as the outermost entry.
+    pub fn push(mut self, named: Named) -> Self {
+        self.entries.push(named);
+        self
+    }
+
+    /// Returns the names of all entries, from innermost to outermost.
+    pub fn names(&self) -> impl Iterator<Item = &'static str> + '_ {
+        self.entries.iter().map(|e| e.name)
+    }
+
+    /// Checks that every constraint is satisfied.
+    ///
+    /// An `Inside` constraint is satisfied if any entry with that name is
+    /// pushed after the layer, and an `Outside` constraint if any is pushed
+    /// before it.
+    pub fn validate(&self) -> Result<(), Violation> {
+        for (idx, entry) in self.entries.iter().enumerate() {
+            for &constraint in &entry.constraints {
+                let positions = self
+                    .entries
+                    .iter()
+                    .enumerate()
+                    .filter(|(_, e)| e.name == constraint.name())
+                    .map(|(i, _)| i)
+                    .collect::<Vec<_>>();
+                if positions.is_empty() {
+                    return Err(Violation::Missing {
+                        layer: entry.name,
+                        constraint,
+                    });
+                }
+
+                let satisfied = match constraint {
+                    Constraint::Inside(_) => positions.iter().any(|&i| i > idx),
+                    Constraint::Outside(_) => positions.iter().any(|&i| i < idx),
+                };
+                if !satisfied {
+                    return Err(Violation::Misordered {
+                        layer: entry.name,
+                        constraint,
+                    });
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+// === impl Named ===
+
+impl Named {
+    pub fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            constraints: Vec::new(),
+        }
+    }
+
+    /// Requires that this layer is pushed before the named entry.
+    pub fn inside(mut self, name: &'static str) -> Self {
+        self.constraints.push(Constraint::Inside(name));
+        self
+    }
+
+    /// Requires that this layer is pushed after the named entry.
+    pub fn outside(mut self, name: &'static str) -> Self {
+        self.constraints.push(Constraint::Outside(name));
+        self
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+}
+
+impl From<&'static str> for Named {
+    fn from(name: &'static str) -> Self {
+        Named::new(name)
+    }
+}
+
+// === impl Constraint ===
+
+impl Constraint {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Constraint::Inside(n) | Constraint::Outside(n) => *n,
+        }
+    }
+}
+
+impl fmt::Display for Constraint {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Constraint::Inside(n) => write!(f, "inside `{}`", n),
+            Constraint::Outside(n) => write!(f, "outside `{}`", n),
+        }
+    }
+}
+
+// === impl Violation ===
+
+impl fmt::Display for Violation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Violation::Missing { layer, constraint } => write!(
+                f,
+                "layer `{}` must be {}, but the stack has no `{}`",
+                layer,
+                constraint,
+                constraint.name()
+            ),
+            Violation::Misordered { layer, constraint } => {
+                let side = match constraint {
+                    Constraint::Inside(_) => "outside",
+                    Constraint::Outside(_) => "inside",
+                };
+                write!(
+                    f,
+                    "layer `{}` must be {}, but it is pushed {} of it",
+                    layer, constraint, side
+                )
+            }
+        }
+    }
+}
+
+impl std::error::Error for Violation {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn retry() -> Named {
+        Named::new("retry").inside(BUFFER).outside(ROUTE_METRICS)
+    }
+
+    #[test]
+    fn validates_ordered_layers() {
+        let bp = Blueprint::default()
+            .push_anchor(ROUTE_METRICS)
+            .push(retry())
+            .push(Named::new("timeout").outside("retry"))
+            .push_anchor(BUFFER);
+
+        assert_eq!(bp.validate(), Ok(()));
+        assert_eq!(
+            bp.names().collect::<Vec<_>>(),
+            vec![ROUTE_METRICS, "retry", "timeout", BUFFER]
+        );
+    }
+
+    #[test]
+    fn rejects_misordered_layers() {
+        // The retry layer is pushed outside of the buffer.
+        let bp = Blueprint::default()
+            .push_anchor(ROUTE_METRICS)
+            .push_anchor(BUFFER)
+            .push(retry());
+
+        let err = bp.validate().unwrap_err();
+        assert_eq!(
+            err,
+            Violation::Misordered {
+                layer: "retry",
+                constraint: Constraint::Inside(BUFFER),
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "layer `retry` must be inside `buffer`, but it is pushed outside of it"
+        );
+    }
+
+    #[test]
+    fn rejects_missing_anchors() {
+        let bp = Blueprint::default().push(retry()).push_anchor(BUFFER);
+
+        let err = bp.validate().unwrap_err();
+        assert_eq!(
+            err,
+            Violation::Missing {
+                layer: "retry",
+                constraint: Constraint::Outside(ROUTE_METRICS),
+            }
+        );
+        assert_eq!(
+            err.to_string(),
+            "layer `retry` must be outside `route-metrics`, but the stack has no `route-metrics`"
+        );
+    }
+}
@@ -0,0 +1,117 @@
+//! Detects layers that dispatch requests to services that are not ready.
+//!
+//! A layer that ignores a not-ready `poll_ready` and calls its inner service
+//! anyway queues the request inside that service, where an outer buffer's
+//! dispatch deadline no longer applies. In debug builds, `CheckReady` asserts
+//! that each request is dispatched only after the service reported that it
+//! is ready.
+
+use futures::{Async, Poll};
+use tower_service as svc;
+
+pub fn layer() -> Layer {
+    Layer(())
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct Layer(());
+
+#[derive(Clone, Debug)]
+pub struct CheckReady<S> {
+    inner: S,
+    ready: bool,
+}
+
+impl<S> super::Layer<S> for Layer {
+    type Service = CheckReady<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        CheckReady {
+            inner,
+            ready: false,
+        }
+    }
+}
+
+impl<S, Req> svc::Service<Req> for CheckReady<S>
+where
+    S: svc::Service<Req>,
+{
+    type Response = S::Response;
+    type Error = S::Error;
+    type Future = S::Future;
+
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        let ready = self.inner.poll_ready();
+        self.ready = match ready {
+            Ok(Async::Ready(())) => true,
+            _ => false,
+        };
+        ready
+    }
+
+    fn call(&mut self, req: Req) -> Self::Future {
+        debug_assert!(
+            self.ready,
+            "a request was dispatched to a service that is not ready"
+        );
+        self.ready = false;
+        self.inner.call(req)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Layer as _;
+    use futures::future;
+    use svc::Service as _;
+
+    struct Limited(bool);
+
+    impl svc::Service<()> for Limited {
+        type Response = ();
+        type Error = ();
+        type Future = future::FutureResult<(), ()>;
+
+        fn poll_ready(&mut self) -> Poll<(), ()> {
+            if self.0 {
+                Ok(Async::Ready(()))
+            } else {
+                Ok(Async::NotReady)
+            }
+        }
+
+        fn call(&mut self, _: ()) -> Self::Future {
+            future::ok(())
+        }
+    }
+
+    #[test]
+    fn allows_calls_after_ready() {
+        let mut svc = layer().layer(Limited(true));
+        assert!(svc.poll_ready().unwrap().is_ready());
+        let _ = svc.call(());
+        assert!(svc.poll_ready().unwrap().is_ready());
+        let _ = svc.call(());
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "not ready")]
+    fn detects_calls_when_not_ready() {
+        let mut svc = layer().layer(Limited(false));
+        assert!(svc.poll_ready().unwrap().is_not_ready());
+        let _ = svc.call(());
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "not ready")]
+    fn detects_calls_without_readiness() {
+        let mut svc = layer().layer(Limited(true));
+        assert!(svc.poll_ready().unwrap().is_ready());
+        let _ = svc.call(());
+        let _ = svc.call(());
+    }
+}
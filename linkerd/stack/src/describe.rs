@@ -0,0 +1,115 @@
+//! Describes the layers of a composition, e.g. for diagnostics.
+//!
+//! Each layer of a composition is wrapped in a `Named` layer, so that the
+//! composition's type may be traversed to list every layer from outermost to
+//! innermost. Layers that are not given a name are listed as `UNNAMED`.
+
+use tower_layer::{Identity, Layer};
+
+/// Listed in place of a layer that was not given a name.
+pub const UNNAMED: &str = "_";
+
+/// Lists the layers of a composition.
+pub trait DescribeStack {
+    /// Appends the names of this composition's layers to `out`, from
+    /// outermost to innermost.
+    fn describe(&self, out: &mut Vec<&'static str>);
+}
+
+/// A layer that is listed by name when its composition is described.
+#[derive(Clone, Debug)]
+pub struct Named<L> {
+    name: &'static str,
+    layer: L,
+}
+
+/// Applies an `inner` layer and then an `outer` layer, like
+/// `tower_layer::util::Stack`, but so that the composition may be described.
+#[derive(Clone, Debug)]
+pub struct Pair<I, O> {
+    inner: I,
+    outer: O,
+}
+
+// === impl Named ===
+
+impl<L> Named<L> {
+    pub fn new(name: &'static str, layer: L) -> Self {
+        Self { name, layer }
+    }
+
+    pub fn unnamed(layer: L) -> Self {
+        Self::new(UNNAMED, layer)
+    }
+
+    pub fn name(&self) -> &'static str {
+        self.name
+    }
+}
+
+impl<S, L: Layer<S>> Layer<S> for Named<L> {
+    type Service = L::Service;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        self.layer.layer(inner)
+    }
+}
+
+impl<L> DescribeStack for Named<L> {
+    fn describe(&self, out: &mut Vec<&'static str>) {
+        out.push(self.name);
+    }
+}
+
+// === impl Pair ===
+
+impl<I, O> Pair<I, O> {
+    pub fn new(inner: I, outer: O) -> Self {
+        Self { inner, outer }
+    }
+}
+
+impl<S, I, O> Layer<S> for Pair<I, O>
+where
+    I: Layer<S>,
+    O: Layer<I::Service>,
+{
+    type Service = O::Service;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        self.outer.layer(self.inner.layer(inner))
+    }
+}
+
+impl<I: DescribeStack, O: DescribeStack> DescribeStack for Pair<I, O> {
+    fn describe(&self, out: &mut Vec<&'static str>) {
+        self.outer.describe(out);
+        self.inner.describe(out);
+    }
+}
+
+// === impl Identity ===
+
+impl DescribeStack for Identity {
+    fn describe(&self, _: &mut Vec<&'static str>) {}
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn describes_every_layer_from_outermost() {
+        let layers = Pair::new(
+            Pair::new(
+                Pair::new(Identity::new(), Named::new("inner", Identity::new())),
+                Named::unnamed(Identity::new()),
+            ),
+            Named::new("outer", Identity::new()),
+        );
+
+        let mut names = Vec::new();
+        layers.describe(&mut names);
+        assert_eq!(names, vec!["outer", UNNAMED, "inner"]);
+    }
+}
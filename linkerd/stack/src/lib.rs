@@ -1,9 +1,16 @@
 #![deny(warnings, rust_2018_idioms)]
 
+pub mod blueprint;
+pub mod check_ready;
+pub mod describe;
 pub mod layer;
+pub mod map_err;
+pub mod map_response;
 pub mod map_target;
 pub mod per_make;
 mod shared;
+pub mod switch;
+pub mod when;
 
 pub use self::layer::{Layer, LayerExt};
 pub use self::shared::Shared;
@@ -0,0 +1,89 @@
+//! Maps the errors of a service, e.g. so that services with incompatible
+//! error types may be composed.
+
+use futures::{future, Future, Poll};
+use tower_service as svc;
+
+pub fn layer<F>(map_err: F) -> Layer<F> {
+    Layer(map_err)
+}
+
+#[derive(Clone, Debug)]
+pub struct Layer<F>(F);
+
+#[derive(Clone, Debug)]
+pub struct MapErr<S, F> {
+    inner: S,
+    map_err: F,
+}
+
+impl<S, F: Clone> tower_layer::Layer<S> for Layer<F> {
+    type Service = MapErr<S, F>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        MapErr {
+            inner,
+            map_err: self.0.clone(),
+        }
+    }
+}
+
+impl<Req, E, S, F> svc::Service<Req> for MapErr<S, F>
+where
+    S: svc::Service<Req>,
+    F: Fn(S::Error) -> E + Clone,
+{
+    type Response = S::Response;
+    type Error = E;
+    type Future = future::MapErr<S::Future, F>;
+
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        let map_err = &self.map_err;
+        self.inner.poll_ready().map_err(|e| (map_err)(e))
+    }
+
+    fn call(&mut self, req: Req) -> Self::Future {
+        self.inner.call(req).map_err(self.map_err.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Layer as _;
+    use linkerd2_error::Error;
+    use svc::Service as _;
+
+    #[derive(Debug)]
+    struct Unavailable;
+
+    /// Fails readiness and requests with an error that is not `Into<Error>`.
+    struct Failing;
+
+    impl svc::Service<()> for Failing {
+        type Response = ();
+        type Error = Unavailable;
+        type Future = future::FutureResult<(), Unavailable>;
+
+        fn poll_ready(&mut self) -> Poll<(), Unavailable> {
+            Err(Unavailable)
+        }
+
+        fn call(&mut self, _: ()) -> Self::Future {
+            future::err(Unavailable)
+        }
+    }
+
+    #[test]
+    fn maps_readiness_and_response_errors() {
+        // Errors are typically mapped into an `Error` so that the service may
+        // be composed with layers, like buffers, that require it.
+        let mut svc = layer(|_: Unavailable| -> Error { "unavailable".into() }).layer(Failing);
+
+        let err = svc.poll_ready().unwrap_err();
+        assert_eq!(err.to_string(), "unavailable");
+
+        let err = svc.call(()).wait().unwrap_err();
+        assert_eq!(err.to_string(), "unavailable");
+    }
+}
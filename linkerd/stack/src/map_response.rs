@@ -0,0 +1,46 @@
+//! Maps the responses of a service, e.g. to wrap them in another type.
+
+use futures::{future, Future, Poll};
+use tower_service as svc;
+
+pub fn layer<F>(map_response: F) -> Layer<F> {
+    Layer(map_response)
+}
+
+#[derive(Clone, Debug)]
+pub struct Layer<F>(F);
+
+#[derive(Clone, Debug)]
+pub struct MapResponse<S, F> {
+    inner: S,
+    map_response: F,
+}
+
+impl<S, F: Clone> tower_layer::Layer<S> for Layer<F> {
+    type Service = MapResponse<S, F>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        MapResponse {
+            inner,
+            map_response: self.0.clone(),
+        }
+    }
+}
+
+impl<Req, Rsp, S, F> svc::Service<Req> for MapResponse<S, F>
+where
+    S: svc::Service<Req>,
+    F: Fn(S::Response) -> Rsp + Clone,
+{
+    type Response = Rsp;
+    type Error = S::Error;
+    type Future = future::Map<S::Future, F>;
+
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        self.inner.poll_ready()
+    }
+
+    fn call(&mut self, req: Req) -> Self::Future {
+        self.inner.call(req).map(self.map_response.clone())
+    }
+}
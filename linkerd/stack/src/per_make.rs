@@ -1,6 +1,13 @@
 use futures::{try_ready, Future, Poll};
 use tower_service as svc;
 
+/// Applies `per_make` to each service made by the inner `MakeService`.
+///
+/// The made service is the layer's service, so its `poll_ready` reflects the
+/// wrapper's own readiness (e.g. of a concurrency limit) as well as the
+/// inner service's. Outer buffers therefore observe the wrapper's
+/// backpressure, and requests wait in the buffer, where dispatch deadlines
+/// apply, rather than within the wrapper.
 pub fn layer<L>(per_make: L) -> Layer<L> {
     Layer(per_make)
 }
@@ -0,0 +1,197 @@
+//! Selects, for each target, whether its service is made by the inner stack
+//! or by an alternate stack.
+//!
+//! Targets that match a predicate are served by the inner stack and all
+//! others by the alternate, so the made service is an `Either` of the two
+//! stacks' services.
+
+use futures::{Async, Future, Poll};
+use tower_service as svc;
+pub use tower_util::Either;
+
+pub fn layer<P, O>(predicate: P, other: O) -> Layer<P, O> {
+    Layer { predicate, other }
+}
+
+#[derive(Clone, Debug)]
+pub struct Layer<P, O> {
+    predicate: P,
+    other: O,
+}
+
+/// Makes services with `inner` for targets that match the predicate, and
+/// with `other` otherwise.
+#[derive(Clone, Debug)]
+pub struct MakeSwitch<P, A, B> {
+    predicate: P,
+    inner: A,
+    other: B,
+}
+
+pub enum MakeFuture<A, B> {
+    Inner(A),
+    Other(B),
+}
+
+impl<P, O, M> tower_layer::Layer<M> for Layer<P, O>
+where
+    P: Clone,
+    O: Clone,
+{
+    type Service = MakeSwitch<P, M, O>;
+
+    fn layer(&self, inner: M) -> Self::Service {
+        MakeSwitch::new(self.predicate.clone(), inner, self.other.clone())
+    }
+}
+
+// === impl MakeSwitch ===
+
+impl<P, A, B> MakeSwitch<P, A, B> {
+    pub fn new(predicate: P, inner: A, other: B) -> Self {
+        Self {
+            predicate,
+            inner,
+            other,
+        }
+    }
+
+    /// Returns the stack that makes the service for `target`.
+    pub fn select<T>(&self, target: &T) -> Either<&A, &B>
+    where
+        P: Fn(&T) -> bool,
+    {
+        if (self.predicate)(target) {
+            Either::A(&self.inner)
+        } else {
+            Either::B(&self.other)
+        }
+    }
+}
+
+impl<T, P, A, B> svc::Service<T> for MakeSwitch<P, A, B>
+where
+    P: Fn(&T) -> bool,
+    A: svc::Service<T>,
+    B: svc::Service<T, Error = A::Error>,
+{
+    type Response = Either<A::Response, B::Response>;
+    type Error = A::Error;
+    type Future = MakeFuture<A::Future, B::Future>;
+
+    fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+        // Either may be used to make the next service, so both must be ready.
+        let inner = self.inner.poll_ready()?;
+        let other = self.other.poll_ready()?;
+        if inner.is_ready() && other.is_ready() {
+            Ok(Async::Ready(()))
+        } else {
+            Ok(Async::NotReady)
+        }
+    }
+
+    fn call(&mut self, target: T) -> Self::Future {
+        if (self.predicate)(&target) {
+            MakeFuture::Inner(self.inner.call(target))
+        } else {
+            MakeFuture::Other(self.other.call(target))
+        }
+    }
+}
+
+// === impl MakeFuture ===
+
+impl<A, B> Future for MakeFuture<A, B>
+where
+    A: Future,
+    B: Future<Error = A::Error>,
+{
+    type Item = Either<A::Item, B::Item>;
+    type Error = A::Error;
+
+    fn poll(&mut self) -> Poll<Self::Item, Self::Error> {
+        match self {
+            MakeFuture::Inner(f) => f.poll().map(|a| a.map(Either::A)),
+            MakeFuture::Other(f) => f.poll().map(|a| a.map(Either::B)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::Layer as _;
+    use futures::future;
+    use svc::Service as _;
+
+    /// Makes `(name, target)`.
+    #[derive(Clone)]
+    struct Named {
+        name: &'static str,
+        ready: bool,
+    }
+
+    impl Named {
+        fn new(name: &'static str) -> Self {
+            Self { name, ready: true }
+        }
+    }
+
+    impl svc::Service<usize> for Named {
+        type Response = (&'static str, usize);
+        type Error = ();
+        type Future = future::FutureResult<Self::Response, ()>;
+
+        fn poll_ready(&mut self) -> Poll<(), ()> {
+            if self.ready {
+                Ok(Async::Ready(()))
+            } else {
+                Ok(Async::NotReady)
+            }
+        }
+
+        fn call(&mut self, target: usize) -> Self::Future {
+            future::ok((self.name, target))
+        }
+    }
+
+    fn is_even(n: &usize) -> bool {
+        n % 2 == 0
+    }
+
+    #[test]
+    fn matching_targets_use_the_inner_stack() {
+        let mut make = layer(is_even, Named::new("other")).layer(Named::new("inner"));
+        match make.call(2).wait() {
+            Ok(Either::A(made)) => assert_eq!(made, ("inner", 2)),
+            _ => panic!("even targets must be made by the inner stack"),
+        }
+        match make.select(&2) {
+            Either::A(inner) => assert_eq!(inner.name, "inner"),
+            Either::B(_) => panic!("even targets must select the inner stack"),
+        }
+    }
+
+    #[test]
+    fn other_targets_use_the_alternate_stack() {
+        let mut make = layer(is_even, Named::new("other")).layer(Named::new("inner"));
+        match make.call(3).wait() {
+            Ok(Either::B(made)) => assert_eq!(made, ("other", 3)),
+            _ => panic!("odd targets must be made by the alternate stack"),
+        }
+        match make.select(&3) {
+            Either::B(other) => assert_eq!(other.name, "other"),
+            Either::A(_) => panic!("odd targets must select the alternate stack"),
+        }
+    }
+
+    #[test]
+    fn ready_only_when_both_stacks_are_ready() {
+        let other = Named {
+            name: "other",
+            ready: false,
+        };
+        let mut make = layer(is_even, other).layer(Named::new("inner"));
+        assert!(make.poll_ready().unwrap().is_not_ready());
+    }
+}
@@ -0,0 +1,100 @@
+//! Applies a layer only to the services made for targets that match a
+//! predicate.
+//!
+//! Services made for targets that do not match are returned unmodified, so
+//! the made service is an `Either` of the layered and original services.
+
+use crate::switch;
+pub use crate::switch::{Either, MakeFuture};
+
+pub fn layer<P, L>(predicate: P, layer: L) -> Layer<P, L> {
+    Layer { predicate, layer }
+}
+
+#[derive(Clone, Debug)]
+pub struct Layer<P, L> {
+    predicate: P,
+    layer: L,
+}
+
+/// Makes services with `layered` for targets that match the predicate, and
+/// with `inner` otherwise.
+pub type MakeWhen<P, N, M> = switch::MakeSwitch<P, N, M>;
+
+impl<P, L, M> tower_layer::Layer<M> for Layer<P, L>
+where
+    P: Clone,
+    L: tower_layer::Layer<M>,
+    M: Clone,
+{
+    type Service = MakeWhen<P, L::Service, M>;
+
+    fn layer(&self, inner: M) -> Self::Service {
+        let layered = self.layer.layer(inner.clone());
+        switch::MakeSwitch::new(self.predicate.clone(), layered, inner)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layer::mk;
+    use crate::Layer as _;
+    use futures::{future, Future, Poll};
+    use svc::Service as _;
+    use tower_service as svc;
+
+    /// Makes the target.
+    #[derive(Clone)]
+    struct Echo;
+
+    impl<T> svc::Service<T> for Echo {
+        type Response = T;
+        type Error = ();
+        type Future = future::FutureResult<T, ()>;
+
+        fn poll_ready(&mut self) -> Poll<(), ()> {
+            Ok(().into())
+        }
+
+        fn call(&mut self, target: T) -> Self::Future {
+            future::ok(target)
+        }
+    }
+
+    #[derive(Debug, PartialEq)]
+    struct Layered(usize);
+
+    /// Wraps made services in `Layered`.
+    #[derive(Clone)]
+    struct Wrap<M>(M);
+
+    impl<M: svc::Service<usize, Response = usize>> svc::Service<usize> for Wrap<M> {
+        type Response = Layered;
+        type Error = M::Error;
+        type Future = future::Map<M::Future, fn(usize) -> Layered>;
+
+        fn poll_ready(&mut self) -> Poll<(), Self::Error> {
+            self.0.poll_ready()
+        }
+
+        fn call(&mut self, target: usize) -> Self::Future {
+            self.0.call(target).map(Layered as fn(_) -> _)
+        }
+    }
+
+    #[test]
+    fn layers_only_matching_targets() {
+        let is_even = |n: &usize| n % 2 == 0;
+        let mut make = layer(is_even, mk(Wrap)).layer(Echo);
+
+        match make.call(2).wait() {
+            Ok(Either::A(layered)) => assert_eq!(layered, Layered(2)),
+            _ => panic!("even targets must be layered"),
+        }
+        match make.call(3).wait() {
+            Ok(Either::B(n)) => assert_eq!(n, 3),
+            _ => panic!("odd targets must not be layered"),
+        }
+    }
+}
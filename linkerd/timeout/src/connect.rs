@@ -0,0 +1,117 @@
+use super::Timeout;
+use linkerd2_stack as stk;
+use std::time::Duration;
+use tokio_timer as timer;
+use tower_service as svc;
+
+/// Determines how long a connection to a target may take to be established.
+pub trait HasConnectTimeout {
+    fn connect_timeout(&self) -> Duration;
+}
+
+/// Creates a layer that applies each target's connect timeout to the future
+/// that makes its service.
+pub fn layer() -> Layer {
+    Layer(())
+}
+
+#[derive(Clone, Debug)]
+pub struct Layer(());
+
+#[derive(Clone, Debug)]
+pub struct MakeConnect<M> {
+    inner: M,
+}
+
+impl<M> stk::Layer<M> for Layer {
+    type Service = MakeConnect<M>;
+
+    fn layer(&self, inner: M) -> Self::Service {
+        MakeConnect { inner }
+    }
+}
+
+impl<T, M> svc::Service<T> for MakeConnect<M>
+where
+    T: HasConnectTimeout,
+    M: svc::Service<T>,
+    M::Error: Into<super::error::Error>,
+{
+    type Response = M::Response;
+    type Error = super::error::Error;
+    type Future = Timeout<timer::Timeout<M::Future>>;
+
+    fn poll_ready(&mut self) -> futures::Poll<(), Self::Error> {
+        self.inner.poll_ready().map_err(Into::into)
+    }
+
+    fn call(&mut self, target: T) -> Self::Future {
+        let duration = target.connect_timeout();
+        let inner = timer::Timeout::new(self.inner.call(target), duration);
+        Timeout::new(inner, duration)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::error::{Error, Timedout};
+    use futures::Future;
+    use linkerd2_stack::Layer as _;
+    use std::time::Instant;
+    use tokio::runtime::current_thread::Runtime;
+    use tower_service::Service as _;
+
+    /// How long it takes `Connect` to make a service.
+    const CONNECT_TIME: Duration = Duration::from_millis(500);
+
+    struct Target(Duration);
+
+    impl HasConnectTimeout for Target {
+        fn connect_timeout(&self) -> Duration {
+            self.0
+        }
+    }
+
+    struct Connect;
+
+    impl svc::Service<Target> for Connect {
+        type Response = ();
+        type Error = Error;
+        type Future = Box<dyn Future<Item = (), Error = Error> + Send>;
+
+        fn poll_ready(&mut self) -> futures::Poll<(), Self::Error> {
+            Ok(().into())
+        }
+
+        fn call(&mut self, _: Target) -> Self::Future {
+            let delay = timer::Delay::new(timer::clock::now() + CONNECT_TIME);
+            Box::new(delay.map_err(Into::into))
+        }
+    }
+
+    #[test]
+    fn uses_each_targets_timeout() {
+        let mut rt = Runtime::new().unwrap();
+        let mut make = layer().layer(Connect);
+
+        let timeout = Duration::from_millis(20);
+        let start = Instant::now();
+        let error = rt
+            .block_on(make.call(Target(timeout)))
+            .expect_err("connect must time out");
+        let elapsed = start.elapsed();
+        let timedout = error.downcast_ref::<Timedout>().expect("must be a timeout");
+        assert_eq!(timedout.duration(), timeout);
+        assert!(
+            elapsed >= timeout && elapsed < CONNECT_TIME,
+            "must time out after the target's timeout, not {:?}",
+            elapsed
+        );
+
+        // A target with a longer timeout is not limited by the other
+        // target's timeout.
+        rt.block_on(make.call(Target(CONNECT_TIME * 2)))
+            .expect("connect must not time out");
+    }
+}
@@ -9,7 +9,10 @@ pub(crate) type Error = Box<dyn std::error::Error + Send + Sync>;
 
 /// An error representing that an operation timed out.
 #[derive(Debug)]
-pub struct Timedout(pub(crate) Duration);
+pub struct Timedout {
+    pub(crate) duration: Duration,
+    pub(crate) name: Option<&'static str>,
+}
 
 /// A duration which pretty-prints as fractional seconds.
 #[derive(Copy, Clone, Debug)]
@@ -20,13 +23,31 @@ struct HumanDuration<'a>(&'a Duration);
 impl Timedout {
     /// Get the amount of time waited until this error was triggered.
     pub fn duration(&self) -> Duration {
-        self.0
+        self.duration
+    }
+
+    /// Get the name of the timeout, describing what was awaited, if it was
+    /// named.
+    pub fn name(&self) -> Option<&'static str> {
+        self.name
     }
 }
 
 impl fmt::Display for Timedout {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
-        write!(f, "operation timed out after {}", HumanDuration(&self.0))
+        match self.name {
+            Some(name) => write!(
+                f,
+                "request timed out after {} waiting for {}",
+                HumanDuration(&self.duration),
+                name.replace('-', " ")
+            ),
+            None => write!(
+                f,
+                "operation timed out after {}",
+                HumanDuration(&self.duration)
+            ),
+        }
     }
 }
 
@@ -39,7 +60,7 @@ impl<'a> fmt::Display for HumanDuration<'a> {
         if secs == 0 {
             write!(fmt, "{}ms", subsec_ms)
         } else {
-            write!(fmt, "{}s", secs as f64 + subsec_ms)
+            write!(fmt, "{}s", secs as f64 + subsec_ms / 1_000f64)
         }
     }
 }
@@ -6,6 +6,7 @@ use tokio_connect::Connect;
 use tokio_timer as timer;
 use tower_service as svc;
 
+pub mod connect;
 pub mod error;
 pub mod stack;
 
@@ -16,6 +17,7 @@ use self::error::{Error, Timedout};
 pub struct Timeout<T> {
     inner: T,
     duration: Duration,
+    name: Option<&'static str>,
 }
 
 //===== impl Timeout =====
@@ -23,7 +25,11 @@ pub struct Timeout<T> {
 impl<T> Timeout<T> {
     /// Construct a new `Timeout` wrapping `inner`.
     pub fn new(inner: T, duration: Duration) -> Self {
-        Timeout { inner, duration }
+        Timeout {
+            inner,
+            duration,
+            name: None,
+        }
     }
 
     fn timeout_error<E>(&self, error: timer::timeout::Error<E>) -> Error
@@ -35,7 +41,11 @@ impl<T> Timeout<T> {
                 .into_timer()
                 .expect("error.into_timer() must succeed if error.is_timer()")
                 .into(),
-            _ if error.is_elapsed() => Timedout(self.duration).into(),
+            _ if error.is_elapsed() => Timedout {
+                duration: self.duration,
+                name: self.name,
+            }
+            .into(),
             _ => error
                 .into_inner()
                 .expect("if error is not elapsed or timer, must be inner")
@@ -62,6 +72,7 @@ where
         Timeout {
             inner,
             duration: self.duration,
+            name: self.name,
         }
     }
 }
@@ -80,6 +91,7 @@ where
         Timeout {
             inner,
             duration: self.duration,
+            name: self.name,
         }
     }
 }
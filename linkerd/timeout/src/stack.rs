@@ -13,11 +13,26 @@ pub fn layer(timeout: Duration) -> Layer {
     Layer { timeout }
 }
 
+/// Creates a layer that applies the timeout to every request of the inner
+/// service, rather than to each service that the inner stack makes.
+///
+/// The timeout's `name` describes what a request was waiting for, e.g.
+/// "endpoint-response", and is included in its `Timedout` error.
+pub fn layer_named(name: &'static str, timeout: Duration) -> NamedLayer {
+    NamedLayer { name, timeout }
+}
+
 #[derive(Clone, Debug)]
 pub struct Layer {
     timeout: Duration,
 }
 
+#[derive(Copy, Clone, Debug)]
+pub struct NamedLayer {
+    name: &'static str,
+    timeout: Duration,
+}
+
 #[derive(Clone, Debug)]
 pub struct Stack<M> {
     inner: M,
@@ -40,6 +55,18 @@ impl<M> stk::Layer<M> for Layer {
     }
 }
 
+impl<S> stk::Layer<S> for NamedLayer {
+    type Service = Timeout<S>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        Timeout {
+            inner,
+            duration: self.timeout,
+            name: Some(self.name),
+        }
+    }
+}
+
 impl<T, M> svc::Service<T> for Stack<M>
 where
     M: svc::Service<T>,